@@ -0,0 +1,126 @@
+package no.divvun.divvunspell;
+
+import android.content.res.AssetFileDescriptor;
+
+import java.io.IOException;
+
+/**
+ * A ZHFST speller backed by libdivvunspell.
+ *
+ * Methods may be called from any thread, but lookups should run on a worker
+ * thread rather than the UI thread. Call {@link #close()} when done.
+ */
+public final class Speller implements AutoCloseable {
+    static {
+        System.loadLibrary("divvunspell");
+    }
+
+    private long handle;
+
+    private Speller(long handle) {
+        this.handle = handle;
+    }
+
+    /** Opens an archive stored uncompressed in the APK's assets. */
+    public static Speller open(AssetFileDescriptor afd) throws IOException {
+        return open(afd, 0);
+    }
+
+    /**
+     * Like {@link #open(AssetFileDescriptor)}, keeping the transducers mapped
+     * and each search's buffers within a quarter of {@code maxResidentBytes}.
+     * Zero means no limit.
+     */
+    public static Speller open(AssetFileDescriptor afd, long maxResidentBytes) throws IOException {
+        return new Speller(nativeOpenFd(afd.getParcelFileDescriptor().getFd(),
+                afd.getStartOffset(), afd.getLength(), maxResidentBytes));
+    }
+
+    public static Speller open(String path) throws IOException {
+        return open(path, 0);
+    }
+
+    public static Speller open(String path, long maxResidentBytes) throws IOException {
+        return new Speller(nativeOpenPath(path, maxResidentBytes));
+    }
+
+    public String locale() {
+        return nativeLocale(handle);
+    }
+
+    /**
+     * Reads in the parts of the archive the first lookup needs. Call it on a
+     * worker thread after opening, so the first keystroke doesn't wait on
+     * storage.
+     */
+    public void warmUp() {
+        nativeWarmUp(handle);
+    }
+
+    public boolean isCorrect(String word) {
+        return nativeIsCorrect(handle, word);
+    }
+
+    /**
+     * Suggestions for {@code word}, best first. Zero means no limit for
+     * {@code nBest} and {@code timeBudgetMs}.
+     */
+    public String[] suggest(String word, int nBest, long timeBudgetMs) {
+        return nativeSuggest(handle, word, nBest, timeBudgetMs);
+    }
+
+    /** Returns false if the word was already in the user dictionary. */
+    public boolean addUserWord(String word) {
+        return nativeAddUserWord(handle, word);
+    }
+
+    public boolean removeUserWord(String word) {
+        return nativeRemoveUserWord(handle, word);
+    }
+
+    public String[] userWords() {
+        return nativeUserWords(handle);
+    }
+
+    /** Whether enough words were added that compileUserWords would pay off. */
+    public boolean needsCompile() {
+        return nativeNeedsCompile(handle);
+    }
+
+    /**
+     * Compiles the user dictionary so that its words are suggested with the
+     * speller's error model. Slow for a large dictionary, so call it on a
+     * background thread; other calls carry on meanwhile.
+     */
+    public void compileUserWords() throws IOException {
+        nativeCompileUserWords(handle);
+    }
+
+    /**
+     * Mapped transducer bytes, transducer bytes copied to the heap, the most
+     * one search has allocated, and how many searches were cut short.
+     */
+    public long[] memoryUsage() {
+        return nativeMemoryUsage(handle);
+    }
+
+    @Override
+    public synchronized void close() {
+        nativeFree(handle);
+        handle = 0;
+    }
+
+    private static native long nativeOpenFd(int fd, long offset, long length, long maxResidentBytes) throws IOException;
+    private static native long nativeOpenPath(String path, long maxResidentBytes) throws IOException;
+    private static native void nativeFree(long handle);
+    private static native String nativeLocale(long handle);
+    private static native void nativeWarmUp(long handle);
+    private static native boolean nativeIsCorrect(long handle, String word);
+    private static native String[] nativeSuggest(long handle, String word, int nBest, long timeBudgetMs);
+    private static native boolean nativeAddUserWord(long handle, String word);
+    private static native boolean nativeRemoveUserWord(long handle, String word);
+    private static native String[] nativeUserWords(long handle);
+    private static native boolean nativeNeedsCompile(long handle);
+    private static native void nativeCompileUserWords(long handle) throws IOException;
+    private static native long[] nativeMemoryUsage(long handle);
+}
@@ -0,0 +1,171 @@
+//! Micro-benchmarks for lookup, suggestion, tokenization and archive loading.
+//!
+//! The speller benches need an archive, given as `DIVVUNSPELL_BENCH_ZHFST`.
+//! The words used can be overridden with `DIVVUNSPELL_BENCH_SHORT` and
+//! `DIVVUNSPELL_BENCH_LONG` (defaults are Northern Sámi), and the thread count
+//! of the parallel bench with `DIVVUNSPELL_BENCH_THREADS`.
+//!
+//! ```
+//! DIVVUNSPELL_BENCH_ZHFST=se.zhfst cargo bench --features bench
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use divvunspell::archive::SpellerArchive;
+use divvunspell::speller::SpellerConfig;
+use divvunspell::tokenizer::Tokenize;
+
+const DEFAULT_SHORT_WORD: &str = "giellaa";
+const DEFAULT_LONG_WORD: &str = "vuovdinfállovuogiŧ";
+
+const TEXT: &str = "Sámegiella lea sámiid giella. Dát lea ovdamearka, mas leat \
+                    máŋga sáni, čuoggát ja \"čujuhusmearkkat\" (ja earát).";
+
+fn archive_path() -> Option<String> {
+    let path = std::env::var("DIVVUNSPELL_BENCH_ZHFST").ok();
+
+    if path.is_none() {
+        eprintln!("DIVVUNSPELL_BENCH_ZHFST not set; skipping speller benchmarks.");
+    }
+
+    path
+}
+
+fn word(var: &str, default: &str) -> String {
+    std::env::var(var).unwrap_or_else(|_| default.to_string())
+}
+
+fn suggest_config() -> SpellerConfig {
+    SpellerConfig {
+        n_best: Some(10),
+        max_weight: Some(50000.0),
+        ..SpellerConfig::default()
+    }
+}
+
+fn archive_load(c: &mut Criterion) {
+    let path = match archive_path() {
+        Some(v) => v,
+        None => return,
+    };
+
+    c.bench_function("archive load", |b| {
+        b.iter(|| SpellerArchive::new(black_box(&path)).unwrap())
+    });
+}
+
+fn is_correct(c: &mut Criterion) {
+    let path = match archive_path() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let speller = SpellerArchive::new(&path).unwrap().speller();
+    let short = word("DIVVUNSPELL_BENCH_SHORT", DEFAULT_SHORT_WORD);
+    let long = word("DIVVUNSPELL_BENCH_LONG", DEFAULT_LONG_WORD);
+
+    c.bench_function("is_correct short", |b| {
+        b.iter(|| speller.clone().is_correct(black_box(&short)))
+    });
+    c.bench_function("is_correct long", |b| {
+        b.iter(|| speller.clone().is_correct(black_box(&long)))
+    });
+}
+
+fn suggest(c: &mut Criterion) {
+    let path = match archive_path() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let speller = SpellerArchive::new(&path).unwrap().speller();
+    let cfg = suggest_config();
+    let short = word("DIVVUNSPELL_BENCH_SHORT", DEFAULT_SHORT_WORD);
+    let long = word("DIVVUNSPELL_BENCH_LONG", DEFAULT_LONG_WORD);
+
+    c.bench_function("suggest short", |b| {
+        b.iter(|| speller.clone().suggest_with_config(black_box(&short), &cfg))
+    });
+    c.bench_function("suggest long", |b| {
+        b.iter(|| speller.clone().suggest_with_config(black_box(&long), &cfg))
+    });
+}
+
+/// Each thread suggests for the short word `SUGGESTIONS_PER_THREAD` times;
+/// with no shared state in the search, time per iteration should stay flat
+/// as threads are added, up to the core count.
+const SUGGESTIONS_PER_THREAD: usize = 16;
+
+fn suggest_parallel(c: &mut Criterion) {
+    let path = match archive_path() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let speller = SpellerArchive::new(&path).unwrap().speller();
+    let short = word("DIVVUNSPELL_BENCH_SHORT", DEFAULT_SHORT_WORD);
+    let threads: usize = word("DIVVUNSPELL_BENCH_THREADS", "4").parse().unwrap();
+
+    c.bench_function(&format!("suggest short x{} threads", threads), |b| {
+        b.iter(|| {
+            let handles: Vec<_> = (0..threads)
+                .map(|_| {
+                    let speller = speller.clone();
+                    let short = short.clone();
+                    std::thread::spawn(move || {
+                        let cfg = suggest_config();
+                        for _ in 0..SUGGESTIONS_PER_THREAD {
+                            black_box(speller.clone().suggest_with_config(&short, &cfg));
+                        }
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        })
+    });
+}
+
+/// The words of `TEXT`, which repeat and vary in case the way running text
+/// does, looked up one at a time and as one `suggest_many` batch.
+fn suggest_many(c: &mut Criterion) {
+    let path = match archive_path() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let speller = SpellerArchive::new(&path).unwrap().speller();
+    let cfg = suggest_config();
+    let words: Vec<&str> = TEXT.words().collect();
+
+    c.bench_function("suggest text words in a loop", |b| {
+        b.iter(|| {
+            for word in &words {
+                black_box(speller.clone().suggest_with_config(word, &cfg));
+            }
+        })
+    });
+    c.bench_function("suggest_many text words", |b| {
+        b.iter(|| black_box(speller.clone().suggest_many(&words, &cfg)))
+    });
+}
+
+fn tokenize(c: &mut Criterion) {
+    c.bench_function("word_bound_indices", |b| {
+        b.iter(|| black_box(TEXT).word_bound_indices().count())
+    });
+    c.bench_function("words", |b| b.iter(|| black_box(TEXT).words().count()));
+}
+
+criterion_group!(
+    benches,
+    archive_load,
+    is_correct,
+    suggest,
+    suggest_parallel,
+    suggest_many,
+    tokenize
+);
+criterion_main!(benches);
@@ -0,0 +1,7 @@
+fn main() {
+    #[cfg(feature = "mobile")]
+    uniffi_build::generate_scaffolding("src/divvunspell.udl").unwrap();
+
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/divvunspell.proto").unwrap();
+}
@@ -0,0 +1,165 @@
+// P/Invoke declarations for the stable C API in include/divvunspell.h.
+// Keep in step with the header: signatures there never change, so this file
+// only grows when DIVVUN_FFI_VERSION is bumped.
+
+using System;
+using System.Runtime.InteropServices;
+using System.Text;
+using Microsoft.Win32.SafeHandles;
+
+namespace Divvunspell
+{
+    internal enum Status
+    {
+        Ok = 0,
+        NullPointer = 1,
+        InvalidUtf8 = 2,
+        OpenFailed = 3,
+        Panic = 4,
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct SuggestConfig
+    {
+        public UIntPtr NBest;
+        public float MaxWeight;
+        public float Beam;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    internal struct NativeWordBoundary
+    {
+        public UIntPtr Offset;
+        public UIntPtr Len;
+        public UIntPtr Utf16Offset;
+        public UIntPtr Utf16Len;
+    }
+
+    internal sealed class SpellerHandle : SafeHandleZeroOrMinusOneIsInvalid
+    {
+        private SpellerHandle() : base(true) { }
+
+        protected override bool ReleaseHandle()
+        {
+            Native.divvun_speller_free(handle);
+            return true;
+        }
+    }
+
+    internal sealed class SuggestionsHandle : SafeHandleZeroOrMinusOneIsInvalid
+    {
+        private SuggestionsHandle() : base(true) { }
+
+        protected override bool ReleaseHandle()
+        {
+            Native.divvun_suggestions_free(handle);
+            return true;
+        }
+    }
+
+    internal sealed class WordBoundariesHandle : SafeHandleZeroOrMinusOneIsInvalid
+    {
+        private WordBoundariesHandle() : base(true) { }
+
+        protected override bool ReleaseHandle()
+        {
+            Native.divvun_word_boundaries_free(handle);
+            return true;
+        }
+    }
+
+    internal static class Native
+    {
+        private const string Library = "divvunspell";
+
+        // The oldest library version with every function declared here.
+        internal const uint RequiredVersion = 2;
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern uint divvun_ffi_version();
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern IntPtr divvun_last_error();
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern Status divvun_speller_open(byte[] path, out SpellerHandle speller);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern void divvun_speller_free(IntPtr speller);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern IntPtr divvun_speller_locale(SpellerHandle speller);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern Status divvun_speller_is_correct(
+            SpellerHandle speller,
+            byte[] word,
+            [MarshalAs(UnmanagedType.U1)] out bool correct);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern Status divvun_speller_suggest(
+            SpellerHandle speller,
+            byte[] word,
+            ref SuggestConfig config,
+            out SuggestionsHandle suggestions);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern UIntPtr divvun_suggestions_len(SuggestionsHandle suggestions);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern IntPtr divvun_suggestions_value(SuggestionsHandle suggestions, UIntPtr index);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern float divvun_suggestions_weight(SuggestionsHandle suggestions, UIntPtr index);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern void divvun_suggestions_free(IntPtr suggestions);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern Status divvun_word_boundaries(byte[] text, out WordBoundariesHandle boundaries);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern UIntPtr divvun_word_boundaries_len(WordBoundariesHandle boundaries);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern IntPtr divvun_word_boundaries_get(WordBoundariesHandle boundaries, UIntPtr index);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl)]
+        internal static extern void divvun_word_boundaries_free(IntPtr boundaries);
+
+        // NUL-terminated UTF-8, as every string parameter expects.
+        internal static byte[] ToUtf8(string value)
+        {
+            var bytes = new byte[Encoding.UTF8.GetByteCount(value) + 1];
+            Encoding.UTF8.GetBytes(value, 0, value.Length, bytes, 0);
+            return bytes;
+        }
+
+        // Marshal.PtrToStringUTF8 is missing from netstandard2.0.
+        internal static string FromUtf8(IntPtr ptr)
+        {
+            if (ptr == IntPtr.Zero)
+            {
+                return null;
+            }
+
+            var len = 0;
+            while (Marshal.ReadByte(ptr, len) != 0)
+            {
+                len++;
+            }
+
+            var bytes = new byte[len];
+            Marshal.Copy(ptr, bytes, 0, len);
+            return Encoding.UTF8.GetString(bytes);
+        }
+
+        internal static void Check(Status status)
+        {
+            if (status != Status.Ok)
+            {
+                throw new SpellerException((int)status, FromUtf8(divvun_last_error()));
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+using System;
+using System.Collections.Generic;
+
+namespace Divvunspell
+{
+    public sealed class SpellerException : Exception
+    {
+        /// <summary>The library's DivvunStatus code.</summary>
+        public int Status { get; }
+
+        internal SpellerException(int status, string message) : base(message ?? "divvunspell error " + status)
+        {
+            Status = status;
+        }
+    }
+
+    public sealed class Suggestion
+    {
+        public string Value { get; }
+
+        /// <summary>Lower is better.</summary>
+        public float Weight { get; }
+
+        internal Suggestion(string value, float weight)
+        {
+            Value = value;
+            Weight = weight;
+        }
+
+        public override string ToString() => Value;
+    }
+
+    /// <summary>
+    /// A speller for one ZHFST archive or CHFST bundle. Safe to use from
+    /// several threads at once.
+    /// </summary>
+    public sealed class Speller : IDisposable
+    {
+        private readonly SpellerHandle handle;
+
+        private Speller(SpellerHandle handle)
+        {
+            this.handle = handle;
+            Locale = Native.FromUtf8(Native.divvun_speller_locale(handle));
+        }
+
+        /// <summary>Opens a .zhfst file, or a CHFST bundle if the path is a directory.</summary>
+        public static Speller Open(string path)
+        {
+            if (path == null) throw new ArgumentNullException(nameof(path));
+
+            var version = Native.divvun_ffi_version();
+            if (version < Native.RequiredVersion)
+            {
+                throw new SpellerException(0, "divvunspell library version " + version +
+                    " is older than the required " + Native.RequiredVersion);
+            }
+
+            Native.Check(Native.divvun_speller_open(Native.ToUtf8(path), out var handle));
+            return new Speller(handle);
+        }
+
+        /// <summary>The archive's locale, or null for CHFST bundles.</summary>
+        public string Locale { get; }
+
+        public bool IsCorrect(string word)
+        {
+            if (word == null) throw new ArgumentNullException(nameof(word));
+
+            Native.Check(Native.divvun_speller_is_correct(handle, Native.ToUtf8(word), out var correct));
+            return correct;
+        }
+
+        /// <summary>Suggestions for the word, best first. Zero leaves a limit unset.</summary>
+        public IReadOnlyList<Suggestion> Suggest(string word, int nBest = 0, float maxWeight = 0, float beam = 0)
+        {
+            if (word == null) throw new ArgumentNullException(nameof(word));
+
+            var config = new SuggestConfig
+            {
+                NBest = (UIntPtr)Math.Max(nBest, 0),
+                MaxWeight = maxWeight,
+                Beam = beam,
+            };
+
+            Native.Check(Native.divvun_speller_suggest(handle, Native.ToUtf8(word), ref config, out var suggestions));
+
+            using (suggestions)
+            {
+                var len = (int)Native.divvun_suggestions_len(suggestions);
+                var result = new List<Suggestion>(len);
+
+                for (var i = 0; i < len; i++)
+                {
+                    result.Add(new Suggestion(
+                        Native.FromUtf8(Native.divvun_suggestions_value(suggestions, (UIntPtr)i)),
+                        Native.divvun_suggestions_weight(suggestions, (UIntPtr)i)));
+                }
+
+                return result;
+            }
+        }
+
+        public void Dispose() => handle.Dispose();
+    }
+}
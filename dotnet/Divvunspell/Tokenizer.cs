@@ -0,0 +1,60 @@
+using System;
+using System.Collections.Generic;
+using System.Linq;
+using System.Runtime.InteropServices;
+
+namespace Divvunspell
+{
+    /// <summary>A segment of a string, by UTF-16 index as used by System.String.</summary>
+    public struct WordBoundary
+    {
+        public int Offset { get; }
+        public int Length { get; }
+        public string Text { get; }
+
+        internal WordBoundary(int offset, int length, string text)
+        {
+            Offset = offset;
+            Length = length;
+            Text = text;
+        }
+
+        /// <summary>True unless the segment is only whitespace or punctuation.</summary>
+        public bool IsWord => Text.Any(char.IsLetterOrDigit);
+
+        public override string ToString() => Text;
+    }
+
+    /// <summary>Word segmentation with the same rules the speller uses for text.</summary>
+    public static class Tokenizer
+    {
+        /// <summary>Every segment of the text, including whitespace and punctuation.</summary>
+        public static IReadOnlyList<WordBoundary> WordBoundaries(string text)
+        {
+            if (text == null) throw new ArgumentNullException(nameof(text));
+
+            Native.Check(Native.divvun_word_boundaries(Native.ToUtf8(text), out var boundaries));
+
+            using (boundaries)
+            {
+                var len = (int)Native.divvun_word_boundaries_len(boundaries);
+                var result = new List<WordBoundary>(len);
+
+                for (var i = 0; i < len; i++)
+                {
+                    var ptr = Native.divvun_word_boundaries_get(boundaries, (UIntPtr)i);
+                    var b = Marshal.PtrToStructure<NativeWordBoundary>(ptr);
+                    var offset = (int)b.Utf16Offset;
+                    var length = (int)b.Utf16Len;
+                    result.Add(new WordBoundary(offset, length, text.Substring(offset, length)));
+                }
+
+                return result;
+            }
+        }
+
+        /// <summary>The segments that are words.</summary>
+        public static IEnumerable<WordBoundary> Words(string text) =>
+            WordBoundaries(text).Where(b => b.IsWord);
+    }
+}
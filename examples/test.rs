@@ -5,7 +5,7 @@ use std::time::Instant;
 
 use divvunspell::archive::SpellerArchive;
 
-use divvunspell::speller::{Speller, SpellerConfig};
+use divvunspell::speller::{SeenNodes, Speller, SpellerConfig, WeightSemiring};
 
 use divvunspell::transducer::HfstTransducer;
 
@@ -155,12 +155,7 @@ static tuples: &'static [TestLine] = &[
             "borramušráhkadanfihtiid",
         ],
     ),
-    (
-        "humašivččii",
-        "humašii",
-        6.762947,
-        &["gumažii", "jumažii"],
-    ),
+    ("humašivččii", "humašii", 6.762947, &["gumažii", "jumažii"]),
     (
         "buorranivččii",
         "buorránivččii",
@@ -301,11 +296,25 @@ fn main() {
     let mut cfg = SpellerConfig {
         max_weight: Some(100.0),
         n_best: Some(5),
+        max_edit_distance: None,
+        max_weight_gap: None,
         beam: None,
         pool_max: 128,
         pool_start: 128,
+        seen_nodes: SeenNodes::Off,
         seen_node_sample_rate: 20,
+        seen_false_positive_rate: 0.001,
         with_caps: true,
+        adaptive_beam: false,
+        beam_min: 5.0,
+        beam_max: 40.0,
+        time_budget_ms: None,
+        prefix_cache: None,
+        semiring: WeightSemiring::Tropical,
+        input_profile: None,
+        proper_nouns: false,
+        constrain_huge_errmodels: true,
+        fallback_distance: None,
     };
 
     // let res: Vec<Vec<Suggestion>> = human_rights.iter().map(|w| speller.suggest(w, &cfg)).collect();
@@ -0,0 +1,31 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use divvunspell::transducer::{HfstTransducer, Transducer};
+use memmap::MmapMut;
+use std::sync::Arc;
+
+fuzz_target!(|data: &[u8]| {
+    // Anonymous maps cannot be empty.
+    if data.is_empty() {
+        return;
+    }
+
+    let mut map = MmapMut::map_anon(data.len()).unwrap();
+    map.copy_from_slice(data);
+    let map = map.make_read_only().unwrap();
+
+    // Inputs whose header or alphabet don't parse are rejected, not crashes.
+    let transducer = match HfstTransducer::from_mapped_memory(Arc::new(map.into())) {
+        Ok(transducer) => transducer,
+        Err(_) => return,
+    };
+
+    // Touch the first few states so the table reads are exercised too.
+    for i in 0..16 {
+        let _ = transducer.is_final(i);
+        let _ = transducer.final_weight(i);
+        let _ = transducer.has_epsilons_or_flags(i);
+        let _ = transducer.take_epsilons_and_flags(i);
+    }
+});
@@ -0,0 +1,33 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use divvunspell::archive::SpellerArchive;
+use divvunspell::speller::{Speller, SpellerConfig};
+use divvunspell::transducer::HfstTransducer;
+use std::sync::Arc;
+
+thread_local! {
+    static SPELLER: Arc<Speller<HfstTransducer>> = {
+        let path = std::env::var("DIVVUNSPELL_FUZZ_ZHFST")
+            .expect("DIVVUNSPELL_FUZZ_ZHFST must point to a ZHFST file");
+        SpellerArchive::new(&path).expect("speller archive").speller()
+    };
+}
+
+fuzz_target!(|data: &[u8]| {
+    let word = match std::str::from_utf8(data) {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+
+    let cfg = SpellerConfig {
+        n_best: Some(5),
+        max_weight: Some(50.0),
+        ..SpellerConfig::default()
+    };
+
+    SPELLER.with(|speller| {
+        let _ = speller.clone().is_correct(word);
+        let _ = speller.clone().suggest_with_config(word, &cfg);
+    });
+});
@@ -0,0 +1,20 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use divvunspell::archive::SpellerArchive;
+use std::io::Write;
+
+fuzz_target!(|data: &[u8]| {
+    // SpellerArchive only opens paths, so round-trip through a temp file.
+    let dir = tempdir::TempDir::new("divvunspell-fuzz").unwrap();
+    let path = dir.path().join("input.zhfst");
+
+    {
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(data).unwrap();
+    }
+
+    if let Ok(archive) = SpellerArchive::new(path.to_str().unwrap()) {
+        let _ = archive.speller().is_correct("fuzz");
+    }
+});
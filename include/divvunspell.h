@@ -0,0 +1,140 @@
+/*
+ * Stable C API for divvunspell.
+ *
+ * Link against the cdylib (libdivvunspell.so, .dylib or divvunspell.dll) or
+ * the staticlib built by `cargo build --release`.
+ *
+ * Conventions:
+ *  - Strings in and out are NUL-terminated UTF-8.
+ *  - Fallible functions return a DivvunStatus and write their result through
+ *    the last (out) parameter. On failure, divvun_last_error() describes why.
+ *  - Returned strings are owned by the handle they came from and stay valid
+ *    until that handle is freed. Free every handle with its _free function;
+ *    passing NULL to a _free function is a no-op.
+ *  - A DivvunSpeller may be used from several threads at once. Calls block
+ *    the calling thread, except divvun_speller_suggest_async.
+ */
+
+#ifndef DIVVUNSPELL_H
+#define DIVVUNSPELL_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DIVVUN_FFI_VERSION 4
+
+typedef enum {
+    DIVVUN_OK = 0,
+    DIVVUN_NULL_POINTER = 1,
+    DIVVUN_INVALID_UTF8 = 2,
+    DIVVUN_OPEN_FAILED = 3,
+    DIVVUN_PANIC = 4,
+} DivvunStatus;
+
+/* Suggestion limits. Zero means no limit for each field. */
+typedef struct {
+    size_t n_best;
+    float max_weight;
+    float beam;
+} DivvunSuggestConfig;
+
+/* A segment of text between word boundaries, in UTF-8 bytes and in UTF-16
+ * code units. */
+typedef struct {
+    size_t offset;
+    size_t len;
+    size_t utf16_offset;
+    size_t utf16_len;
+} DivvunWordBoundary;
+
+typedef struct DivvunSpeller DivvunSpeller;
+typedef struct DivvunSuggestions DivvunSuggestions;
+typedef struct DivvunWordBoundaries DivvunWordBoundaries;
+
+/* The DIVVUN_FFI_VERSION the library was built with. */
+uint32_t divvun_ffi_version(void);
+
+/* Message for the last failed call on this thread, or NULL. Valid until the
+ * next failing call on the same thread. */
+const char *divvun_last_error(void);
+
+/* Opens a .zhfst file, or a CHFST bundle if path is a directory. */
+DivvunStatus divvun_speller_open(const char *path, DivvunSpeller **out);
+void divvun_speller_free(DivvunSpeller *speller);
+
+/* Opens a speller from a file:// URL, such as a bundle resource URL's
+ * absoluteString. Since version 3. */
+DivvunStatus divvun_speller_open_url(const char *url, DivvunSpeller **out);
+
+/* Opens a .zhfst archive from len bytes in memory. The transducers are
+ * copied, so data only needs to stay valid for the call. Since version 3. */
+DivvunStatus divvun_speller_open_bytes(const uint8_t *data, size_t len, DivvunSpeller **out);
+
+/* The archive's locale, or NULL for CHFST bundles. */
+const char *divvun_speller_locale(const DivvunSpeller *speller);
+
+DivvunStatus divvun_speller_is_correct(const DivvunSpeller *speller, const char *word,
+                                       bool *out);
+
+/* The lexicon's weight for word, lower being likelier, for ranking candidates
+ * such as a speech recognizer's n-best list. accepted is false, and weight
+ * left alone, if the lexicon doesn't accept it. Since version 4. */
+DivvunStatus divvun_speller_score(const DivvunSpeller *speller, const char *word,
+                                  bool *accepted, float *weight);
+
+/* config may be NULL for the defaults. */
+DivvunStatus divvun_speller_suggest(const DivvunSpeller *speller, const char *word,
+                                    const DivvunSuggestConfig *config,
+                                    DivvunSuggestions **out);
+
+/* Called once with the result of divvun_speller_suggest_async: DIVVUN_OK and
+ * suggestions to free, or another status and NULL. */
+typedef void (*DivvunSuggestCallback)(void *context, DivvunStatus status,
+                                      DivvunSuggestions *suggestions);
+
+/* Queues a search and returns at once. Searches run one at a time on a
+ * background thread owned by the library, and callback is called on that
+ * thread, never the caller's: dispatch UI work back to the main thread.
+ * speller may be freed before the callback. On any status but DIVVUN_OK,
+ * nothing was queued and callback is not called. Since version 3. */
+DivvunStatus divvun_speller_suggest_async(const DivvunSpeller *speller, const char *word,
+                                          const DivvunSuggestConfig *config,
+                                          DivvunSuggestCallback callback, void *context);
+
+size_t divvun_suggestions_len(const DivvunSuggestions *suggestions);
+
+/* All suggestions, best first, as an array of divvun_suggestions_len()
+ * strings. */
+const char *const *divvun_suggestions_values(const DivvunSuggestions *suggestions);
+
+/* The suggestion at index, or NULL if out of range. */
+const char *divvun_suggestions_value(const DivvunSuggestions *suggestions, size_t index);
+
+/* The weight of the suggestion at index, or NaN if out of range. Lower is
+ * better. */
+float divvun_suggestions_weight(const DivvunSuggestions *suggestions, size_t index);
+
+void divvun_suggestions_free(DivvunSuggestions *suggestions);
+
+/* Splits text at word boundaries, including whitespace and punctuation
+ * segments. Since version 2. */
+DivvunStatus divvun_word_boundaries(const char *text, DivvunWordBoundaries **out);
+
+size_t divvun_word_boundaries_len(const DivvunWordBoundaries *boundaries);
+
+/* The segment at index, or NULL if out of range. */
+const DivvunWordBoundary *divvun_word_boundaries_get(const DivvunWordBoundaries *boundaries,
+                                                     size_t index);
+
+void divvun_word_boundaries_free(DivvunWordBoundaries *boundaries);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DIVVUNSPELL_H */
@@ -0,0 +1,366 @@
+//! JNI bindings for Android input methods, built with the `android` feature.
+//!
+//! The native methods belong to `no.divvun.divvunspell.Speller`, found in
+//! `android/`. Archives are usually opened straight from an uncompressed APK
+//! asset through its `AssetFileDescriptor`:
+//!
+//! ```java
+//! AssetFileDescriptor afd = getAssets().openFd("se.zhfst");
+//! Speller speller = Speller.open(afd);
+//! speller.suggest("gielaa", 5, 50); // at most 5 suggestions, within 50 ms
+//! ```
+//!
+//! Every method may be called from any thread and never waits on another
+//! lookup, but lookups take long enough that they belong on the IME's worker
+//! thread rather than the UI thread. `timeBudgetMs` bounds how long a single
+//! `suggest` call runs. Failures are thrown as Java exceptions.
+
+use jni::objects::{JClass, JObject, JString};
+use jni::sys::{jboolean, jint, jlong, jlongArray, jobjectArray, jsize, JNI_FALSE, JNI_TRUE};
+use jni::JNIEnv;
+use parking_lot::RwLock;
+use std::fs::File;
+use std::os::unix::io::FromRawFd;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr::null_mut;
+
+use crate::archive::SpellerArchive;
+use crate::memory::MemoryPolicy;
+use crate::speller::user_dictionary::UserDictionary;
+use crate::speller::SpellerConfig;
+
+const IO_EXCEPTION: &str = "java/io/IOException";
+const ILLEGAL_STATE_EXCEPTION: &str = "java/lang/IllegalStateException";
+const NULL_POINTER_EXCEPTION: &str = "java/lang/NullPointerException";
+
+/// The Java exception class to throw, and its message.
+type JavaResult<T> = Result<T, (&'static str, String)>;
+
+pub struct AndroidSpeller {
+    archive: SpellerArchive,
+    user: RwLock<UserDictionary>,
+}
+
+impl AndroidSpeller {
+    fn new(archive: SpellerArchive) -> jlong {
+        Box::into_raw(Box::new(AndroidSpeller {
+            archive,
+            user: RwLock::new(UserDictionary::new()),
+        })) as jlong
+    }
+}
+
+fn guard<T>(env: &JNIEnv, fallback: T, f: impl FnOnce() -> JavaResult<T>) -> T {
+    let (class, message) = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(e)) => e,
+        Err(_) => (ILLEGAL_STATE_EXCEPTION, "divvunspell panicked".to_string()),
+    };
+
+    // A failed JNI call has usually left its own exception pending already.
+    if !env.exception_check().unwrap_or(false) {
+        let _ = env.throw_new(class, message);
+    }
+
+    fallback
+}
+
+fn jni_error(e: jni::errors::Error) -> (&'static str, String) {
+    (ILLEGAL_STATE_EXCEPTION, e.to_string())
+}
+
+fn speller<'a>(handle: jlong) -> JavaResult<&'a AndroidSpeller> {
+    if handle == 0 {
+        return Err((NULL_POINTER_EXCEPTION, "speller is closed".to_string()));
+    }
+
+    Ok(unsafe { &*(handle as *const AndroidSpeller) })
+}
+
+fn string(env: &JNIEnv, value: JString) -> JavaResult<String> {
+    env.get_string(value).map(String::from).map_err(jni_error)
+}
+
+fn string_array<'a, I>(env: &JNIEnv, len: usize, values: I) -> JavaResult<jobjectArray>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let array = env
+        .new_object_array(len as jsize, "java/lang/String", JObject::null())
+        .map_err(jni_error)?;
+
+    for (i, value) in values.into_iter().enumerate() {
+        let value = env.new_string(value).map_err(jni_error)?;
+        env.set_object_array_element(array, i as jsize, value.into())
+            .map_err(jni_error)?;
+        env.delete_local_ref(value.into()).map_err(jni_error)?;
+    }
+
+    Ok(array)
+}
+
+fn to_jboolean(value: bool) -> jboolean {
+    if value {
+        JNI_TRUE
+    } else {
+        JNI_FALSE
+    }
+}
+
+/// Opens the `length` bytes at `offset` in `fd`. The descriptor is
+/// duplicated, so the caller may close its `AssetFileDescriptor` afterwards.
+/// A positive `max_resident_bytes` applies `MemoryPolicy::limited`.
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeOpenFd(
+    env: JNIEnv,
+    _class: JClass,
+    fd: jint,
+    offset: jlong,
+    length: jlong,
+    max_resident_bytes: jlong,
+) -> jlong {
+    guard(&env, 0, || {
+        if offset < 0 || length <= 0 {
+            return Err((IO_EXCEPTION, "invalid asset range".to_string()));
+        }
+
+        let fd = unsafe { libc::dup(fd) };
+        if fd < 0 {
+            return Err((IO_EXCEPTION, std::io::Error::last_os_error().to_string()));
+        }
+
+        let file = unsafe { File::from_raw_fd(fd) };
+        let archive = SpellerArchive::from_file_range(
+            &file,
+            offset as u64,
+            length as usize,
+            &memory_policy(max_resident_bytes),
+        )
+        .map_err(|e| (IO_EXCEPTION, e.to_string()))?;
+        Ok(AndroidSpeller::new(archive))
+    })
+}
+
+fn memory_policy(max_resident_bytes: jlong) -> MemoryPolicy {
+    if max_resident_bytes > 0 {
+        MemoryPolicy::limited(max_resident_bytes as usize)
+    } else {
+        MemoryPolicy::unlimited()
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeOpenPath(
+    env: JNIEnv,
+    _class: JClass,
+    path: JString,
+    max_resident_bytes: jlong,
+) -> jlong {
+    guard(&env, 0, || {
+        let path = string(&env, path)?;
+        let archive = SpellerArchive::open_with_policy(&path, &memory_policy(max_resident_bytes))
+            .map_err(|e| (IO_EXCEPTION, format!("{}: {}", path, e)))?;
+        Ok(AndroidSpeller::new(archive))
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeFree(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) {
+    guard(&env, (), || {
+        if handle != 0 {
+            unsafe { drop(Box::from_raw(handle as *mut AndroidSpeller)) };
+        }
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeLocale(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jni::sys::jstring {
+    guard(&env, null_mut(), || {
+        let speller = speller(handle)?;
+        let locale = &speller.archive.metadata().info.locale;
+        Ok(env.new_string(&**locale).map_err(jni_error)?.into_inner())
+    })
+}
+
+/// Words in the user dictionary count as correct.
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeWarmUp(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) {
+    guard(&env, (), || {
+        speller(handle)?.archive.speller().warm_up();
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeIsCorrect(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    word: JString,
+) -> jboolean {
+    guard(&env, JNI_FALSE, || {
+        let speller = speller(handle)?;
+        let word = string(&env, word)?;
+
+        if speller.user.read().contains(&word) {
+            return Ok(JNI_TRUE);
+        }
+
+        Ok(to_jboolean(speller.archive.speller().is_correct(&word)))
+    })
+}
+
+/// Zero or negative `n_best` and `time_budget_ms` mean no limit. User words
+/// close to `word` come first.
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeSuggest(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    word: JString,
+    n_best: jint,
+    time_budget_ms: jlong,
+) -> jobjectArray {
+    guard(&env, null_mut(), || {
+        let speller = speller(handle)?;
+        let word = string(&env, word)?;
+        let cfg = SpellerConfig {
+            n_best: if n_best > 0 {
+                Some(n_best as usize)
+            } else {
+                None
+            },
+            time_budget_ms: if time_budget_ms > 0 {
+                Some(time_budget_ms as u64)
+            } else {
+                None
+            },
+            ..SpellerConfig::default()
+        };
+
+        if speller.user.read().contains(&word) {
+            return string_array(&env, 0, vec![]);
+        }
+
+        // The user dictionary is not locked during the search, so adding a
+        // word never waits for a slow lookup.
+        let found = speller.archive.speller().suggest_with_config(&word, &cfg);
+        let suggestions = speller.user.read().merge(&word, found, cfg.n_best);
+
+        string_array(
+            &env,
+            suggestions.len(),
+            suggestions.iter().map(|x| x.value()),
+        )
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeAddUserWord(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    word: JString,
+) -> jboolean {
+    guard(&env, JNI_FALSE, || {
+        let speller = speller(handle)?;
+        let word = string(&env, word)?;
+        Ok(to_jboolean(speller.user.write().insert(&word)))
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeRemoveUserWord(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    word: JString,
+) -> jboolean {
+    guard(&env, JNI_FALSE, || {
+        let speller = speller(handle)?;
+        let word = string(&env, word)?;
+        Ok(to_jboolean(speller.user.write().remove(&word)))
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeUserWords(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jobjectArray {
+    guard(&env, null_mut(), || {
+        let speller = speller(handle)?;
+        let user = speller.user.read().clone();
+        string_array(&env, user.len(), user.words())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeNeedsCompile(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jboolean {
+    guard(&env, JNI_FALSE, || {
+        Ok(to_jboolean(speller(handle)?.user.read().needs_compile()))
+    })
+}
+
+/// Compiles a copy of the user dictionary, so that words can be added and
+/// looked up while it runs.
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeCompileUserWords(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) {
+    guard(&env, (), || {
+        let speller = speller(handle)?;
+        let snapshot = speller.user.read().clone();
+        let overlay = snapshot
+            .compile(speller.archive.speller().mutator())
+            .map_err(|e| (IO_EXCEPTION, e.to_string()))?;
+        speller.user.write().install(overlay);
+        Ok(())
+    })
+}
+
+/// Mapped bytes, heap bytes, peak search bytes and searches cut short, as
+/// in `MemoryUsage`.
+#[no_mangle]
+pub extern "system" fn Java_no_divvun_divvunspell_Speller_nativeMemoryUsage(
+    env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) -> jlongArray {
+    guard(&env, null_mut(), || {
+        let usage = speller(handle)?.archive.memory_usage();
+        let values = [
+            usage.mapped_bytes as jlong,
+            usage.heap_bytes as jlong,
+            usage.search_peak_bytes as jlong,
+            usage.searches_cut_short as jlong,
+        ];
+
+        let array = env
+            .new_long_array(values.len() as jsize)
+            .map_err(jni_error)?;
+        env.set_long_array_region(array, 0, &values)
+            .map_err(jni_error)?;
+        Ok(array)
+    })
+}
@@ -0,0 +1,115 @@
+//! Finding installed ZHFST archives by language tag, for integrations such
+//! as Enchant that are asked for a language rather than a file.
+//!
+//! Archives are named after their tag (`se.zhfst`, `sma-NO.zhfst`) and looked
+//! for in each directory of `$DIVVUNSPELL_PATH`, then
+//! `$XDG_DATA_HOME/divvunspell` (`~/.local/share/divvunspell` by default),
+//! then the system locations in `SYSTEM_PATHS`. The first archive found for a
+//! tag wins.
+//!
+//! When there's no tag to go by, `detect` picks the archive for the
+//! language a text looks to be in.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::langid::LanguageIdentifier;
+use super::{SpellerArchive, SpellerArchiveError};
+
+pub const SYSTEM_PATHS: &[&str] = &[
+    "/usr/local/share/divvunspell",
+    "/usr/share/divvunspell",
+    "/usr/local/share/voikko/4",
+    "/usr/share/voikko/4",
+];
+
+pub fn search_paths() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match std::env::var_os("DIVVUNSPELL_PATH") {
+        Some(v) => std::env::split_paths(&v).collect(),
+        None => vec![],
+    };
+
+    match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => paths.push(PathBuf::from(dir).join("divvunspell")),
+        _ => {
+            if let Some(home) = std::env::var_os("HOME") {
+                paths.push(PathBuf::from(home).join(".local/share/divvunspell"));
+            }
+        }
+    }
+
+    paths.extend(SYSTEM_PATHS.iter().map(PathBuf::from));
+    paths
+}
+
+/// Normalises a tag for comparison, so `se_NO`, `se-no` and `SE-NO` match.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.replace('_', "-").to_lowercase()
+}
+
+/// Every archive in the search paths, keyed by the tag in its file name.
+pub fn discover() -> BTreeMap<String, PathBuf> {
+    let mut found = BTreeMap::new();
+
+    for dir in search_paths() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.extension().and_then(|x| x.to_str()) != Some("zhfst") {
+                continue;
+            }
+
+            if let Some(tag) = path.file_stem().and_then(|x| x.to_str()) {
+                found.entry(tag.to_string()).or_insert(path);
+            }
+        }
+    }
+
+    found
+}
+
+/// The archive for `tag` among `archives`, falling back to less specific
+/// tags, so a request for `se_NO` is served by `se.zhfst`.
+pub fn find<'a>(archives: &'a BTreeMap<String, PathBuf>, tag: &str) -> Option<&'a PathBuf> {
+    let mut tag = normalize_tag(tag);
+
+    loop {
+        if let Some((_, path)) = archives.iter().find(|(k, _)| normalize_tag(k) == tag) {
+            return Some(path);
+        }
+
+        match tag.rfind('-') {
+            Some(i) => tag.truncate(i),
+            None => return None,
+        }
+    }
+}
+
+/// The archive among `archives` for the language `text` looks most like
+/// it's in, with its tag. Only languages with an archive are candidates, so
+/// some archive is picked for any text with letters in it.
+pub fn detect<'a>(
+    archives: &'a BTreeMap<String, PathBuf>,
+    text: &str,
+) -> Option<(&'a str, &'a PathBuf)> {
+    let (tag, _) = LanguageIdentifier::builtin().best(text, archives.keys().map(|x| &**x))?;
+    archives.get(tag).map(|path| (tag, path))
+}
+
+/// Opens the installed archive `detect` picks for `text`, with its tag.
+pub fn detect_and_load(
+    text: &str,
+) -> Result<Option<(String, SpellerArchive)>, SpellerArchiveError> {
+    let archives = discover();
+    let (tag, path) = match detect(&archives, text) {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+
+    let archive = SpellerArchive::new(&path.to_string_lossy())?;
+    Ok(Some((tag.to_string(), archive)))
+}
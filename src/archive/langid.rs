@@ -0,0 +1,264 @@
+//! Guessing the language of a text from its character n-grams, to pick an
+//! archive when none was asked for.
+//!
+//! Each language has a profile: its most frequent n-grams of one to three
+//! letters, in order, with `_` marking the ends of words. A text is ranked
+//! against each profile by how far its own n-grams are from their places in
+//! the profile (Cavnar and Trenkle's out-of-place measure). This does well on
+//! a sentence or more and is unreliable on a word or two.
+//!
+//! Profiles are trained offline with `train-langid` and read from lines of
+//! `lang<TAB>n-gram`, most frequent first. `LanguageIdentifier::builtin`
+//! has the ones shipped in `data/langid.txt`.
+
+use hashbrown::HashMap;
+use once_cell::sync::OnceCell;
+use smol_str::SmolStr;
+use std::io::{self, Write};
+
+use super::discovery::normalize_tag;
+
+/// The longest n-grams counted, in letters.
+pub const MAX_NGRAM: usize = 3;
+
+/// How many n-grams a profile keeps.
+pub const PROFILE_SIZE: usize = 300;
+
+const BUILTIN: &str = include_str!("../../data/langid.txt");
+
+/// The n-grams of `text` by how often they occur, most frequent first, ties
+/// in order.
+fn ngrams(text: &str, limit: usize) -> Vec<SmolStr> {
+    let mut counts: HashMap<SmolStr, usize> = HashMap::new();
+
+    for word in text.split(|c: char| !c.is_alphabetic()) {
+        if word.is_empty() {
+            continue;
+        }
+
+        let chars: Vec<char> = std::iter::once('_')
+            .chain(word.chars().flat_map(char::to_lowercase))
+            .chain(std::iter::once('_'))
+            .collect();
+
+        for n in 1..=MAX_NGRAM {
+            for gram in chars.windows(n) {
+                *counts
+                    .entry(SmolStr::from(gram.iter().collect::<String>()))
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut ranked: Vec<(SmolStr, usize)> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked.truncate(limit);
+    ranked.into_iter().map(|(gram, _)| gram).collect()
+}
+
+#[derive(Debug)]
+struct Profile {
+    lang: String,
+    ngrams: Vec<SmolStr>,
+    ranks: HashMap<SmolStr, usize>,
+}
+
+impl Profile {
+    fn new(lang: String, ngrams: Vec<SmolStr>) -> Profile {
+        let ranks = ngrams
+            .iter()
+            .enumerate()
+            .map(|(i, gram)| (gram.clone(), i))
+            .collect();
+
+        Profile {
+            lang,
+            ngrams,
+            ranks,
+        }
+    }
+
+    /// How close `text`, as ranked n-grams, is to this profile, from 0 for
+    /// nothing in common to 1 for the same order.
+    fn score(&self, text: &[SmolStr]) -> f32 {
+        let max = PROFILE_SIZE;
+        let distance: usize = text
+            .iter()
+            .enumerate()
+            .map(|(i, gram)| match self.ranks.get(gram) {
+                Some(&rank) => (rank as isize - i as isize).unsigned_abs(),
+                None => max,
+            })
+            .map(|d| d.min(max))
+            .sum();
+
+        1.0 - distance as f32 / (text.len() * max) as f32
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LanguageIdentifier {
+    profiles: Vec<Profile>,
+}
+
+impl LanguageIdentifier {
+    /// The profiles shipped with divvunspell.
+    pub fn builtin() -> &'static LanguageIdentifier {
+        static BUILTIN_PROFILES: OnceCell<LanguageIdentifier> = OnceCell::new();
+        BUILTIN_PROFILES
+            .get_or_init(|| LanguageIdentifier::parse(BUILTIN).expect("data/langid.txt is valid"))
+    }
+
+    /// Reads profiles from lines of `lang<TAB>n-gram`, each language's
+    /// n-grams most frequent first. Lines starting with `#` are skipped.
+    pub fn parse(data: &str) -> io::Result<LanguageIdentifier> {
+        let mut profiles: Vec<(String, Vec<SmolStr>)> = vec![];
+
+        for (i, line) in data.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.splitn(2, '\t');
+            let (lang, gram) = match (fields.next(), fields.next()) {
+                (Some(lang), Some(gram)) if !lang.is_empty() && !gram.is_empty() => (lang, gram),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("line {}: expected a language and an n-gram", i + 1),
+                    ))
+                }
+            };
+
+            match profiles.iter_mut().find(|p| p.0 == lang) {
+                Some(profile) => profile.1.push(SmolStr::from(gram)),
+                None => profiles.push((lang.to_string(), vec![SmolStr::from(gram)])),
+            }
+        }
+
+        Ok(LanguageIdentifier {
+            profiles: profiles
+                .into_iter()
+                .map(|(lang, ngrams)| Profile::new(lang, ngrams))
+                .collect(),
+        })
+    }
+
+    /// Profiles from sample text in each language, several samples of a
+    /// language counting as one.
+    pub fn train<'a, I>(samples: I) -> LanguageIdentifier
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut texts: Vec<(String, String)> = vec![];
+
+        for (lang, text) in samples {
+            match texts.iter_mut().find(|t| t.0 == lang) {
+                Some(t) => {
+                    t.1.push('\n');
+                    t.1.push_str(text);
+                }
+                None => texts.push((lang.to_string(), text.to_string())),
+            }
+        }
+
+        LanguageIdentifier {
+            profiles: texts
+                .into_iter()
+                .map(|(lang, text)| {
+                    let ngrams = ngrams(&text, PROFILE_SIZE);
+                    Profile::new(lang, ngrams)
+                })
+                .collect(),
+        }
+    }
+
+    /// Writes the profiles in the form `parse` reads.
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        for profile in &self.profiles {
+            for gram in &profile.ngrams {
+                writeln!(w, "{}\t{}", profile.lang, gram)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.profiles.iter().map(|p| &*p.lang)
+    }
+
+    /// Every language with how well `text` matches it, from 0 to 1, best
+    /// first. Empty when `text` has no letters.
+    pub fn rank(&self, text: &str) -> Vec<(&str, f32)> {
+        let grams = ngrams(text, PROFILE_SIZE);
+        if grams.is_empty() {
+            return vec![];
+        }
+
+        let mut scores: Vec<(&str, f32)> = self
+            .profiles
+            .iter()
+            .map(|p| (&*p.lang, p.score(&grams)))
+            .collect();
+        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scores
+    }
+
+    pub fn identify(&self, text: &str) -> Option<(&str, f32)> {
+        self.rank(text).into_iter().next()
+    }
+
+    /// The best match for `text` among `tags`, such as the installed
+    /// archives', with its score. A profile for `se` matches `se-NO` too.
+    pub fn best<'a, I>(&self, text: &str, tags: I) -> Option<(&'a str, f32)>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let tags: Vec<(&'a str, String)> = tags
+            .into_iter()
+            .map(|tag| (tag, normalize_tag(tag)))
+            .collect();
+
+        self.rank(text).into_iter().find_map(|(lang, score)| {
+            let lang = normalize_tag(lang);
+            tags.iter()
+                .find(|(_, tag)| {
+                    *tag == lang || (tag.starts_with(&*lang) && tag[lang.len()..].starts_with('-'))
+                })
+                .map(|&(tag, _)| (tag, score))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifies() {
+        let trained = LanguageIdentifier::train(vec![
+            (
+                "se",
+                "Mun lean sámi. Dat lea buorre beaivi. Mii leat dáppe.",
+            ),
+            ("nb", "Jeg er norsk. Det er en fin dag. Vi er her."),
+        ]);
+
+        let mut written = vec![];
+        trained.write(&mut written).unwrap();
+        let id = LanguageIdentifier::parse(std::str::from_utf8(&written).unwrap()).unwrap();
+
+        assert_eq!(id.identify("Lean dáppe").unwrap().0, "se");
+        assert_eq!(id.identify("Det er her").unwrap().0, "nb");
+        assert_eq!(
+            id.best("Det er her", vec!["se-NO", "nb-NO"]).unwrap().0,
+            "nb-NO"
+        );
+        assert!(id.best("Det er her", vec!["smj"]).is_none());
+        assert!(id.identify("123 !").is_none());
+
+        let builtin = LanguageIdentifier::builtin();
+        let text = "Mun lean oahppan sámegiela skuvllas, ja dál mun hálan dan juohke beaivve.";
+        assert_eq!(builtin.identify(text).unwrap().0, "se");
+    }
+}
@@ -0,0 +1,598 @@
+//! Sanity checks for ZHFST archives, aimed at language maintainers.
+//!
+//! None of these stop an archive from loading; they point out metadata gaps and
+//! transducer combinations that make the speller behave worse than intended.
+
+use hashbrown::{HashMap, HashSet};
+use smol_str::SmolStr;
+
+use super::meta::{SpellerMetadata, SpellerTitle};
+use super::SpellerArchive;
+use crate::speller::degrade;
+use crate::transducer::alphabet::TransducerAlphabet;
+use crate::transducer::{HfstTransducer, Transducer};
+use crate::types::HeaderFlag;
+
+/// Tags used by the Giella infrastructure to mark words that are recognised
+/// but should never be offered as suggestions.
+const FORBIDDEN_MARKERS: &[&str] = &["Use/-Spell", "Use/SpellNoSugg", "Err/"];
+
+/// How many offending symbols to list in a single message.
+const SYMBOLS_SHOWN: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LintMessage {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl std::fmt::Display for LintMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        };
+        write!(f, "{}[{}]: {}", severity, self.code, self.message)
+    }
+}
+
+struct Linter {
+    messages: Vec<LintMessage>,
+}
+
+impl Linter {
+    fn push(&mut self, severity: Severity, code: &'static str, message: String) {
+        self.messages.push(LintMessage {
+            severity,
+            code,
+            message,
+        });
+    }
+
+    fn require_text(&mut self, what: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.push(
+                Severity::Warning,
+                "metadata-missing",
+                format!("{} is empty", what),
+            );
+        }
+    }
+
+    fn require_titles(&mut self, what: &str, titles: &[SpellerTitle]) {
+        if titles.is_empty() {
+            self.push(
+                Severity::Warning,
+                "metadata-missing",
+                format!("{} has no <title>", what),
+            );
+            return;
+        }
+
+        for title in titles {
+            self.require_text(&format!("{} title", what), &title.value);
+
+            match &title.lang {
+                Some(lang) if !is_language_tag(lang) => self.push(
+                    Severity::Warning,
+                    "metadata-title-lang",
+                    format!(
+                        "{} title xml:lang {:?} is not a BCP 47 language tag",
+                        what, lang
+                    ),
+                ),
+                _ => {}
+            }
+        }
+
+        if titles.len() > 1 && titles.iter().any(|t| t.lang.is_none()) {
+            self.push(
+                Severity::Note,
+                "metadata-title-lang",
+                format!(
+                    "{} has several titles but not all of them set xml:lang",
+                    what
+                ),
+            );
+        }
+    }
+
+    fn metadata(&mut self, meta: &SpellerMetadata) {
+        let locale = meta.info.locale.trim();
+        self.require_text("info/locale", locale);
+
+        if locale.contains('_') && is_language_tag(&locale.replace('_', "-")) {
+            self.push(
+                Severity::Warning,
+                "metadata-locale",
+                format!(
+                    "info/locale {:?} separates its subtags with _, where BCP 47 uses -",
+                    locale
+                ),
+            );
+        } else if !locale.is_empty() && !is_language_tag(locale) {
+            self.push(
+                Severity::Warning,
+                "metadata-locale",
+                format!("info/locale {:?} is not a BCP 47 language tag", locale),
+            );
+        }
+
+        self.require_titles("info", &meta.info.title);
+        self.locale_title(locale, &meta.info.title);
+        self.require_text("info/description", &meta.info.description);
+        self.require_text("info/producer", &meta.info.producer);
+
+        self.require_titles("acceptor", &meta.acceptor.title);
+        self.require_text("acceptor/description", &meta.acceptor.description);
+        self.require_text("acceptor/@type", &meta.acceptor.type_);
+
+        self.require_titles("errmodel", &meta.errmodel.title);
+        self.require_text("errmodel/description", &meta.errmodel.description);
+
+        self.ids(&meta.acceptor.id, &meta.errmodel.id);
+    }
+
+    /// With every title in a language, one should be in the locale's.
+    fn locale_title(&mut self, locale: &str, titles: &[SpellerTitle]) {
+        let language = primary_language(locale);
+        if language.is_empty() || titles.is_empty() || titles.iter().any(|t| t.lang.is_none()) {
+            return;
+        }
+
+        if !titles
+            .iter()
+            .filter_map(|t| t.lang.as_ref())
+            .any(|lang| primary_language(lang).eq_ignore_ascii_case(language))
+        {
+            self.push(
+                Severity::Warning,
+                "metadata-locale-title",
+                format!("info has no title in its own language, {}", language),
+            );
+        }
+    }
+
+    fn ids(&mut self, acceptor: &str, errmodel: &str) {
+        self.require_text("acceptor/@id", acceptor);
+        self.require_text("errmodel/@id", errmodel);
+
+        if !acceptor.is_empty() && acceptor == errmodel {
+            self.push(
+                Severity::Error,
+                "metadata-ids",
+                format!("acceptor and errmodel have the same id, {:?}", acceptor),
+            );
+            return;
+        }
+
+        for (kind, id) in &[("acceptor", acceptor), ("errmodel", errmodel)] {
+            if !id.is_empty() && !id.starts_with(&format!("{}.", kind)) {
+                self.push(
+                    Severity::Note,
+                    "metadata-ids",
+                    format!(
+                        "{} id {:?} doesn't start with \"{}.\", so index.xml must be parsed to open the archive",
+                        kind, id, kind
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Whether the transducers the archive loaded are the ones `index.xml`
+    /// names; they are found by name when it isn't parsed.
+    fn entries(&mut self, meta: &SpellerMetadata, acceptor: &str, errmodel: &str) {
+        for (kind, id, entry) in &[
+            ("acceptor", &meta.acceptor.id, acceptor),
+            ("errmodel", &meta.errmodel.id, errmodel),
+        ] {
+            if id.as_str() != *entry {
+                self.push(
+                    Severity::Error,
+                    "metadata-ids",
+                    format!(
+                        "index.xml names the {} {:?}, but the archive's {} is {:?}",
+                        kind, id, kind, entry
+                    ),
+                );
+            }
+        }
+    }
+
+    fn header(&mut self, name: &str, transducer: &HfstTransducer) {
+        if !transducer.is_weighted() {
+            self.push(
+                Severity::Warning,
+                "unweighted",
+                format!(
+                    "{} is unweighted, so suggestions cannot be ranked by it",
+                    name
+                ),
+            );
+        }
+
+        if transducer
+            .header()
+            .has_flag(HeaderFlag::HasInputEpsilonCycles)
+        {
+            self.push(
+                Severity::Warning,
+                "epsilon-cycles",
+                format!(
+                    "{} has input epsilon cycles; lookups may only terminate through the weight limit",
+                    name
+                ),
+            );
+        }
+    }
+
+    fn errmodel_size(&mut self, mutator: &HfstTransducer) {
+        let transitions = mutator.header().transitions();
+        if degrade::is_huge(transitions) {
+            self.push(
+                Severity::Warning,
+                "errmodel-huge",
+                format!(
+                    "error model has {} transitions; searches get beam {} and edit distance {} unless configured otherwise",
+                    transitions,
+                    degrade::CONSTRAINED_BEAM,
+                    degrade::CONSTRAINED_MAX_EDIT_DISTANCE
+                ),
+            );
+        }
+    }
+
+    fn alphabets(&mut self, lexicon: &[SmolStr], mutator: &[SmolStr], has_wildcards: bool) {
+        let lexicon_symbols: HashSet<&SmolStr> = lexicon.iter().filter(|s| is_plain(s)).collect();
+        let mutator_symbols: HashSet<&SmolStr> = mutator.iter().filter(|s| is_plain(s)).collect();
+
+        let mut unknown: Vec<&str> = mutator_symbols
+            .difference(&lexicon_symbols)
+            .map(|s| s.as_str())
+            .collect();
+        unknown.sort();
+
+        if !unknown.is_empty() {
+            // Identity and unknown arcs in the acceptor can still consume these.
+            let severity = if has_wildcards {
+                Severity::Note
+            } else {
+                Severity::Warning
+            };
+
+            self.push(
+                severity,
+                "alphabet-mutator-only",
+                format!(
+                    "error model produces {} symbol(s) missing from the acceptor alphabet: {}",
+                    unknown.len(),
+                    symbol_list(&unknown)
+                ),
+            );
+        }
+
+        let mut unreachable: Vec<&str> = lexicon_symbols
+            .difference(&mutator_symbols)
+            .filter(|s| s.chars().count() == 1 && s.chars().all(char::is_alphabetic))
+            .map(|s| s.as_str())
+            .collect();
+        unreachable.sort();
+
+        if !unreachable.is_empty() {
+            self.push(
+                Severity::Warning,
+                "alphabet-acceptor-only",
+                format!(
+                    "error model never outputs {} letter(s) of the acceptor, so they cannot be inserted or substituted: {}",
+                    unreachable.len(),
+                    symbol_list(&unreachable)
+                ),
+            );
+        }
+    }
+
+    fn forbidden_words(&mut self, lexicon: &[SmolStr]) {
+        let has_markers = lexicon
+            .iter()
+            .any(|s| FORBIDDEN_MARKERS.iter().any(|m| s.contains(m)));
+
+        if !has_markers {
+            self.push(
+                Severity::Note,
+                "no-forbidden-words",
+                format!(
+                    "acceptor has no forbidden-word markers ({}); every accepted word may be suggested",
+                    FORBIDDEN_MARKERS.join(", ")
+                ),
+            );
+        }
+    }
+
+    fn flags(&mut self, name: &str, alphabet: &[SmolStr]) {
+        // feature -> operators used on it
+        let mut features: HashMap<&str, HashSet<char>> = HashMap::new();
+
+        for symbol in alphabet {
+            if let Some((op, feature, _)) = parse_flag(symbol) {
+                features
+                    .entry(feature)
+                    .or_insert_with(HashSet::new)
+                    .insert(op);
+            }
+        }
+
+        let mut features: Vec<_> = features.into_iter().collect();
+        features.sort_by_key(|(feature, _)| *feature);
+
+        for (feature, ops) in features {
+            let sets = ops.contains(&'P') || ops.contains(&'N') || ops.contains(&'U');
+
+            if !sets && ops.contains(&'R') {
+                self.push(
+                    Severity::Error,
+                    "flag-never-set",
+                    format!(
+                        "{}: @R.{}@ is checked but {} is never set, so those paths are dead",
+                        name, feature, feature
+                    ),
+                );
+            } else if !sets && ops.contains(&'D') {
+                self.push(
+                    Severity::Note,
+                    "flag-never-set",
+                    format!(
+                        "{}: @D.{}@ is checked but {} is never set, so the flag has no effect",
+                        name, feature, feature
+                    ),
+                );
+            } else if sets && !ops.iter().any(|op| *op == 'R' || *op == 'D' || *op == 'U') {
+                self.push(
+                    Severity::Note,
+                    "flag-never-checked",
+                    format!("{}: flag {} is set but never checked", name, feature),
+                );
+            }
+        }
+    }
+}
+
+/// Whether `tag` is a well formed BCP 47 language tag. Only the shape of
+/// each subtag is checked, not whether it is registered.
+pub fn is_language_tag(tag: &str) -> bool {
+    let parts: Vec<&str> = tag.split('-').collect();
+    let len = parts.len();
+
+    if parts[0].eq_ignore_ascii_case("x") {
+        return is_private_use(&parts[1..]);
+    }
+
+    let language = parts[0];
+    if !(is_alpha(language, 2, 3) || is_alpha(language, 5, 8)) {
+        return false;
+    }
+
+    let mut i = 1;
+    if language.len() <= 3 {
+        let extlangs = i + 3;
+        while i < len.min(extlangs) && is_alpha(parts[i], 3, 3) {
+            i += 1;
+        }
+    }
+
+    if i < len && is_alpha(parts[i], 4, 4) {
+        i += 1;
+    }
+
+    if i < len && (is_alpha(parts[i], 2, 2) || is_digit(parts[i], 3)) {
+        i += 1;
+    }
+
+    while i < len
+        && (is_alnum(parts[i], 5, 8)
+            || (is_alnum(parts[i], 4, 4) && parts[i].starts_with(|c: char| c.is_ascii_digit())))
+    {
+        i += 1;
+    }
+
+    while i < len && is_alnum(parts[i], 1, 1) && !parts[i].eq_ignore_ascii_case("x") {
+        i += 1;
+        let start = i;
+        while i < len && is_alnum(parts[i], 2, 8) {
+            i += 1;
+        }
+
+        if i == start {
+            return false;
+        }
+    }
+
+    if i < len && parts[i].eq_ignore_ascii_case("x") {
+        return is_private_use(&parts[i + 1..]);
+    }
+
+    i == len
+}
+
+fn is_private_use(parts: &[&str]) -> bool {
+    !parts.is_empty() && parts.iter().all(|part| is_alnum(part, 1, 8))
+}
+
+fn is_alpha(part: &str, min: usize, max: usize) -> bool {
+    part.len() >= min && part.len() <= max && part.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn is_alnum(part: &str, min: usize, max: usize) -> bool {
+    part.len() >= min && part.len() <= max && part.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn is_digit(part: &str, len: usize) -> bool {
+    part.len() == len && part.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The language subtag of a tag, such as `se` for `se-NO` or `se_NO`.
+fn primary_language(tag: &str) -> &str {
+    tag.split(['-', '_']).next().unwrap_or("").trim()
+}
+
+/// Checks `index.xml` on its own, most severe findings first. These are
+/// the metadata findings `lint` reports.
+pub fn lint_metadata(meta: &SpellerMetadata) -> Vec<LintMessage> {
+    let mut linter = Linter { messages: vec![] };
+    linter.metadata(meta);
+
+    let mut messages = linter.messages;
+    messages.sort_by_key(|m| m.severity);
+    messages
+}
+
+/// Findings about how the archive's searches will be run, which `meta`
+/// reports alongside the metadata checks.
+pub fn lint_search(archive: &SpellerArchive) -> Vec<LintMessage> {
+    let mut linter = Linter { messages: vec![] };
+    linter.errmodel_size(archive.speller().mutator());
+    linter.messages
+}
+
+/// True for symbols that stand for input or output text, as opposed to
+/// epsilon, flag diacritics and other `@...@` specials.
+fn is_plain(symbol: &str) -> bool {
+    !symbol.is_empty() && (symbol.len() == 1 || !symbol.starts_with('@') || !symbol.ends_with('@'))
+}
+
+/// Splits `@P.FEATURE.VALUE@` into its operator, feature and value.
+fn parse_flag(symbol: &str) -> Option<(char, &str, &str)> {
+    if symbol.len() < 5 || !symbol.starts_with('@') || !symbol.ends_with('@') {
+        return None;
+    }
+
+    let mut chunks = symbol[1..symbol.len() - 1].split('.');
+    let op = chunks.next()?;
+    let feature = chunks.next()?;
+    let value = chunks.next().unwrap_or("");
+
+    match op {
+        "P" | "N" | "R" | "D" | "C" | "U" => Some((op.chars().next().unwrap(), feature, value)),
+        _ => None,
+    }
+}
+
+fn symbol_list(symbols: &[&str]) -> String {
+    let mut out = symbols
+        .iter()
+        .take(SYMBOLS_SHOWN)
+        .map(|s| format!("{:?}", s))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if symbols.len() > SYMBOLS_SHOWN {
+        out.push_str(&format!(" and {} more", symbols.len() - SYMBOLS_SHOWN));
+    }
+
+    out
+}
+
+/// The acceptor's own symbols, without those the speller appended from the
+/// error model when building its alphabet translator.
+fn original_symbols(alphabet: &TransducerAlphabet) -> &[SmolStr] {
+    let count = alphabet.initial_symbol_count() as usize;
+    let keys = alphabet.key_table();
+    &keys[..count.min(keys.len())]
+}
+
+/// Runs every check against the archive, most severe findings first.
+pub fn lint(archive: &SpellerArchive) -> Vec<LintMessage> {
+    let speller = archive.speller();
+    let lexicon = speller.lexicon();
+    let mutator = speller.mutator();
+
+    let lexicon_symbols = original_symbols(lexicon.alphabet());
+    let mutator_symbols = original_symbols(mutator.alphabet());
+
+    let mut linter = Linter { messages: vec![] };
+
+    let meta = archive.metadata();
+    let (acceptor, errmodel) = archive.transducer_entries();
+    linter.metadata(meta);
+    linter.entries(meta, acceptor, errmodel);
+    linter.header("acceptor", lexicon);
+    linter.header("errmodel", mutator);
+    linter.alphabets(
+        lexicon_symbols,
+        mutator_symbols,
+        lexicon.alphabet().identity().is_some() || lexicon.alphabet().unknown().is_some(),
+    );
+    linter.forbidden_words(lexicon_symbols);
+    linter.flags("acceptor", lexicon_symbols);
+
+    linter.errmodel_size(mutator);
+    if mutator_symbols.iter().any(|s| parse_flag(s).is_some()) {
+        linter.push(
+            Severity::Warning,
+            "errmodel-flags",
+            "error model contains flag diacritics, which divvunspell does not apply".into(),
+        );
+    }
+
+    let mut messages = linter.messages;
+    messages.sort_by_key(|m| m.severity);
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags() {
+        assert_eq!(parse_flag("@P.CASE.UP@"), Some(('P', "CASE", "UP")));
+        assert_eq!(parse_flag("@D.CASE@"), Some(('D', "CASE", "")));
+        assert_eq!(parse_flag("@_EPSILON_SYMBOL_@"), None);
+        assert_eq!(parse_flag("@"), None);
+
+        let symbols: Vec<SmolStr> = vec!["a".into(), "@R.X.Y@".into(), "@P.Z.A@".into()];
+        let mut linter = Linter { messages: vec![] };
+        linter.flags("acceptor", &symbols);
+
+        let codes: Vec<_> = linter
+            .messages
+            .iter()
+            .map(|m| (m.severity, m.code))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                (Severity::Error, "flag-never-set"),
+                (Severity::Note, "flag-never-checked"),
+            ]
+        );
+    }
+
+    #[test]
+    fn language_tags() {
+        for tag in &[
+            "se",
+            "sma-Latn-NO",
+            "zh-yue-HK",
+            "sl-rozaj-1994",
+            "en-a-bbb-x-ccc",
+            "x-giella",
+        ] {
+            assert!(is_language_tag(tag), "{}", tag);
+        }
+
+        for tag in &["", "s", "se_NO", "se-", "se-NOR-x", "en-a", "toolongtag"] {
+            assert!(!is_language_tag(tag), "{}", tag);
+        }
+    }
+}
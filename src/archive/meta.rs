@@ -1,16 +1,39 @@
-use serde_xml_rs::{from_reader, Error, ParserConfig};
+use serde_xml_rs::{from_reader, Error, ErrorKind, ParserConfig};
+use xml::reader::XmlEvent;
 
+use crate::speller::defaults::SpellerDefaults;
+
+/// An archive's `index.xml`. In JSON it keeps the same structure:
+/// `{"info": {"locale", "title", "description", "version", "date",
+/// "producer"}, "acceptor": {"type", "id", "title", "description"},
+/// "errmodel": {"id", "title", "description"}, "defaults": {"n_best",
+/// "max_weight", "beam", "max_edit_distance"}}`, with every `title` a list
+/// of `SpellerTitle`s. `version`, `date` and `defaults` are `null` if not
+/// given.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SpellerMetadata {
     pub info: SpellerMetadataInfo,
     pub acceptor: SpellerMetadataAcceptor,
     pub errmodel: SpellerMetadataErrmodel,
+    /// Recommended search settings; see `speller::defaults`.
+    #[serde(default)]
+    pub defaults: Option<SpellerDefaults>,
 }
 
+/// A title in one language. In JSON, `{"lang": "se", "value": "Davvisámegiella"}`.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SpellerTitle {
     pub lang: Option<String>,
-    #[serde(rename = "$value")]
+    #[serde(rename(serialize = "value", deserialize = "$value"), alias = "value")]
+    pub value: String,
+}
+
+/// The lexicon's version and the revision it was built from. In JSON,
+/// `{"vcsrev": "r12345", "value": "1.2.0"}`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SpellerVersion {
+    pub vcsrev: Option<String>,
+    #[serde(rename(serialize = "value", deserialize = "$value"), alias = "value")]
     pub value: String,
 }
 
@@ -19,6 +42,10 @@ pub struct SpellerMetadataInfo {
     pub locale: String,
     pub title: Vec<SpellerTitle>,
     pub description: String,
+    #[serde(default)]
+    pub version: Option<SpellerVersion>,
+    #[serde(default)]
+    pub date: Option<String>,
     pub producer: String,
 }
 
@@ -43,6 +70,12 @@ impl SpellerMetadata {
         SpellerMetadata::from_bytes(string.as_bytes())
     }
 
+    /// Problems with this metadata, such as missing titles, a locale that
+    /// isn't a BCP 47 tag or ids that clash; see `lint::lint_metadata`.
+    pub fn validate(&self) -> Vec<super::lint::LintMessage> {
+        super::lint::lint_metadata(self)
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<SpellerMetadata, Error> {
         let mut reader = ParserConfig::new()
             .trim_whitespace(true)
@@ -53,8 +86,70 @@ impl SpellerMetadata {
 
         from_reader(&mut reader)
     }
+
+    /// Checks that `bytes` is well-formed and has every element and
+    /// attribute `from_bytes` can't do without, without building anything
+    /// from it. An archive checked this way can leave parsing its
+    /// `index.xml` until it is asked for.
+    pub fn check_bytes(bytes: &[u8]) -> Result<(), Error> {
+        let reader = ParserConfig::new()
+            .trim_whitespace(true)
+            .ignore_comments(true)
+            .create_reader(bytes);
+        let mut path: Vec<String> = vec![];
+        let mut found: Vec<String> = vec![];
+
+        for event in reader {
+            match event? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    match path.len() {
+                        1 => {
+                            found.push(name.local_name.clone());
+                            for attribute in attributes {
+                                found.push(format!(
+                                    "{}/{}",
+                                    name.local_name, attribute.name.local_name
+                                ));
+                            }
+                        }
+                        2 => found.push(format!("{}/{}", path[1], name.local_name)),
+                        _ => {}
+                    }
+                    path.push(name.local_name);
+                }
+                XmlEvent::EndElement { .. } => {
+                    path.pop();
+                }
+                _ => {}
+            }
+        }
+
+        for (parent, children) in REQUIRED {
+            if !found.iter().any(|f| f == parent) {
+                return Err(ErrorKind::Custom(format!("missing field `{}`", parent)).into());
+            }
+            for child in *children {
+                let field = format!("{}/{}", parent, child);
+                if !found.contains(&field) {
+                    return Err(ErrorKind::Custom(format!("missing field `{}`", field)).into());
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
+/// The elements `SpellerMetadata::from_bytes` needs under each of the root's
+/// children; `id` may be given as an attribute instead.
+const REQUIRED: &[(&str, &[&str])] = &[
+    ("info", &["locale", "title", "description", "producer"]),
+    ("acceptor", &["id", "title", "description"]),
+    ("errmodel", &["id", "title", "description"]),
+];
+
 #[test]
 fn test_xml_parse() {
     let xml_data = r##"
@@ -85,8 +180,59 @@ fn test_xml_parse() {
             <type type="default"/>
             <model>errormodel.default.hfst</model>
         </errmodel>
+        <defaults>
+            <n_best>5</n_best>
+            <beam>20</beam>
+        </defaults>
+        </hfstspeller>
+    "##;
+
+    let meta = SpellerMetadata::from_str(&xml_data).unwrap();
+    let version = meta.info.version.unwrap();
+    assert_eq!(version.value, "GT_VERSION");
+    assert_eq!(version.vcsrev.as_deref(), Some("GT_REVISION"));
+    assert_eq!(meta.info.date.as_deref(), Some("DATE"));
+    let defaults = meta.defaults.unwrap();
+    assert_eq!(defaults.n_best, Some(5));
+    assert_eq!(defaults.beam, Some(20.0));
+    assert_eq!(defaults.max_weight, None);
+}
+
+#[test]
+fn test_xml_check() {
+    let xml_data = r##"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <hfstspeller dtdversion="1.0" hfstversion="3">
+        <info>
+            <locale>se</locale>
+            <title>Northern Sami</title>
+            <description>Speller</description>
+            <producer>Divvun</producer>
+        </info>
+        <acceptor type="general" id="acceptor.default.hfst">
+            <title>Dictionary</title>
+            <description>Dictionary</description>
+        </acceptor>
+        <errmodel id="errmodel.default.hfst">
+            <title>Edit distance</title>
+            <description>Misstrokes</description>
+        </errmodel>
         </hfstspeller>
     "##;
 
-    let _ = SpellerMetadata::from_str(&xml_data).unwrap();
+    assert!(SpellerMetadata::check_bytes(xml_data.as_bytes()).is_ok());
+    assert!(SpellerMetadata::from_str(xml_data).is_ok());
+
+    let missing = xml_data.replace("<producer>Divvun</producer>", "");
+    assert!(SpellerMetadata::check_bytes(missing.as_bytes()).is_err());
+    assert!(SpellerMetadata::from_str(&missing).is_err());
+
+    let nested = xml_data.replace(
+        "<producer>Divvun</producer>",
+        "<x><producer>Divvun</producer></x>",
+    );
+    assert!(SpellerMetadata::check_bytes(nested.as_bytes()).is_err());
+
+    let truncated = &xml_data[..xml_data.len() / 2];
+    assert!(SpellerMetadata::check_bytes(truncated.as_bytes()).is_err());
 }
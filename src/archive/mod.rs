@@ -1,36 +1,156 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub mod discovery;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod langid;
+pub mod lint;
 pub mod meta;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod path;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod repository;
 
+#[cfg(not(target_arch = "wasm32"))]
 use memmap::{Mmap, MmapOptions};
+use once_cell::sync::OnceCell;
+#[cfg(not(target_arch = "wasm32"))]
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::Seek;
 use std::sync::Arc;
+use std::time::Duration;
+use zip::result::ZipError;
 use zip::ZipArchive;
 
 use self::meta::SpellerMetadata;
-use crate::speller::Speller;
-use crate::transducer::HfstTransducer;
+use crate::hyphenator::Hyphenator;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::memory::Storage;
+use crate::memory::{MemoryPolicy, MemoryUsage};
+use crate::speller::defaults::SpellerDefaults;
+use crate::speller::feedback::Feedback;
+use crate::speller::{degrade, ArcSpeller, Speller};
+use crate::tokenizer::abbreviations::Abbreviations;
+use crate::transducer::alphabet::TransducerAlphabet;
+use crate::transducer::header::TransducerHeader;
+use crate::transducer::{HfstTransducer, TransducerBuf, TransducerError};
 
 pub struct SpellerArchive {
-    metadata: SpellerMetadata,
+    /// `index.xml` as stored, parsed on the first call to `metadata`.
+    metadata_xml: Vec<u8>,
+    metadata: OnceCell<SpellerMetadata>,
+    /// The acceptor and errmodel entries the transducers were read from.
+    entries: (String, String),
     speller: Arc<Speller<HfstTransducer>>,
+    hyphenator: Option<Hyphenator>,
+    hyphenator_entry: Option<String>,
+    feedback: Option<Feedback>,
+    /// What the transducers cost; search figures are kept by the speller.
+    memory: MemoryUsage,
+    profile: LoadProfile,
+    /// Worked out on the first call to `fingerprint`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fingerprint: OnceCell<u64>,
 }
 
+/// How long opening an archive took, step by step, from
+/// `SpellerArchive::load_profile`. Always zero on WebAssembly, which has no
+/// clock.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LoadProfile {
+    /// Reading the zip directory.
+    pub zip_parse: Duration,
+    /// Mapping the entries, or reading them and copying them into memory.
+    pub mmap: Duration,
+    /// Reading both transducers' headers.
+    pub header_parse: Duration,
+    /// Reading both alphabets, and building the speller's symbol translation
+    /// and case table from them.
+    pub alphabet_build: Duration,
+}
+
+impl LoadProfile {
+    pub fn total(&self) -> Duration {
+        self.zip_parse + self.mmap + self.header_parse + self.alphabet_build
+    }
+}
+
+/// An entry of the archive, from `SpellerArchive::contents`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub bytes: usize,
+    /// The FNV-1a hash of the entry, as 16 hex digits.
+    pub hash: String,
+    /// What its header says, if the entry is a transducer.
+    pub automaton: Option<AutomatonInfo>,
+}
+
+/// The sizes and properties in a transducer's header.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AutomatonInfo {
+    pub states: u32,
+    pub transitions: u32,
+    pub symbols: u16,
+    pub input_symbols: u16,
+    pub weighted: bool,
+}
+
+impl AutomatonInfo {
+    pub fn of(transducer: &HfstTransducer) -> AutomatonInfo {
+        let header = transducer.header();
+        AutomatonInfo {
+            states: header.states(),
+            transitions: header.transitions(),
+            symbols: header.symbol_count(),
+            input_symbols: header.input_symbol_count(),
+            weighted: transducer.is_weighted(),
+        }
+    }
+}
+
+fn archive_entry(name: &str, bytes: &[u8], automaton: Option<AutomatonInfo>) -> ArchiveEntry {
+    use crate::speller::suggestion_cache::{fnv1a, FNV_OFFSET};
+
+    ArchiveEntry {
+        name: name.to_string(),
+        bytes: bytes.len(),
+        hash: format!("{:016x}", fnv1a(FNV_OFFSET, bytes)),
+        automaton,
+    }
+}
+
+/// Runs `f`, adding the time it took to `total`.
+#[cfg(not(target_arch = "wasm32"))]
+fn timed<T>(total: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let start = std::time::Instant::now();
+    let value = f();
+    *total += start.elapsed();
+    value
+}
+
+#[cfg(target_arch = "wasm32")]
+fn timed<T>(_total: &mut Duration, f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 pub struct TempMmap {
-    mmap: Arc<Mmap>,
+    mmap: Arc<TransducerBuf>,
 
     // Not really dead, needed to drop when TempMmap drops
     #[allow(dead_code)]
     tempdir: tempdir::TempDir,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub enum MmapRef {
-    Direct(Arc<Mmap>),
+    Direct(Arc<TransducerBuf>),
     Temp(TempMmap),
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl MmapRef {
-    pub fn map(&self) -> Arc<Mmap> {
+    pub fn map(&self) -> Arc<TransducerBuf> {
         match self {
             MmapRef::Direct(mmap) => Arc::clone(mmap),
             MmapRef::Temp(tmmap) => Arc::clone(&tmmap.mmap),
@@ -38,12 +158,113 @@ impl MmapRef {
     }
 }
 
+/// Asks for the pages of `map` to be read in now, as the policy asks,
+/// instead of on first use.
+#[cfg(target_os = "linux")]
+fn populate(map: &Mmap, policy: &MemoryPolicy) {
+    if !policy.populate || map.is_empty() {
+        return;
+    }
+
+    // Mappings at an offset into the archive start inside a page, and
+    // madvise wants the address of the page.
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let start = map.as_ptr() as usize;
+    let aligned = start - start % page;
+    unsafe {
+        libc::madvise(
+            aligned as *mut libc::c_void,
+            map.len() + (start - aligned),
+            libc::MADV_WILLNEED,
+        );
+    }
+}
+
+#[cfg(all(not(target_arch = "wasm32"), not(target_os = "linux")))]
+fn populate(_map: &Mmap, _policy: &MemoryPolicy) {}
+
+/// Asks for `map` to be backed by transparent huge pages. Only the 2 MiB
+/// aligned stretches inside it can be, which for a large transducer is
+/// nearly all of it. Kernels without them refuse, which changes nothing.
+#[cfg(target_os = "linux")]
+fn advise_huge_pages(map: &mut [u8]) {
+    unsafe {
+        libc::madvise(
+            map.as_mut_ptr() as *mut libc::c_void,
+            map.len(),
+            libc::MADV_HUGEPAGE,
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_huge_pages(_map: &mut [u8]) {}
+
+/// Copies a transducer into anonymous memory, as the policy asks.
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_transducer(bytes: &[u8], policy: &MemoryPolicy) -> std::io::Result<Arc<TransducerBuf>> {
+    let mut map = memmap::MmapMut::map_anon(bytes.len())?;
+    if policy.huge_pages {
+        advise_huge_pages(&mut map);
+    }
+
+    map.copy_from_slice(bytes);
+    Ok(Arc::new(map.make_read_only()?.into()))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn copy_transducer(bytes: &[u8], _policy: &MemoryPolicy) -> std::io::Result<Arc<TransducerBuf>> {
+    Ok(Arc::new(bytes.to_vec()))
+}
+
+/// Reads a transducer's header and alphabet, timing each. Its tables are
+/// left unread until the first lookup.
+fn load_transducer(
+    buf: Arc<TransducerBuf>,
+    policy: &MemoryPolicy,
+    profile: &mut LoadProfile,
+) -> Result<HfstTransducer, SpellerArchiveError> {
+    let header = timed(&mut profile.header_parse, || TransducerHeader::new(&buf))
+        .map_err(SpellerArchiveError::TransducerParseFailed)?;
+    let alphabet = timed(&mut profile.alphabet_build, || {
+        TransducerAlphabet::new(&buf[header.len()..], header.symbol_count())
+    })
+    .map_err(SpellerArchiveError::TransducerParseFailed)?;
+
+    let mut transducer = HfstTransducer::from_parts(buf, header, alphabet);
+    transducer.set_checked(policy.checked_tables);
+    Ok(transducer)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 fn mmap_by_name<'a, R: Read + Seek>(
     zipfile: &mut File,
     archive: &mut ZipArchive<R>,
     name: &str,
+    policy: &MemoryPolicy,
 ) -> Result<MmapRef, std::io::Error> {
-    let mut index = archive.by_name(name).unwrap();
+    mmap_by_name_at(zipfile, 0, archive, name, policy)
+}
+
+/// Like `mmap_by_name`, for an archive that starts `base` bytes into
+/// `zipfile`, such as an asset inside an APK.
+#[cfg(not(target_arch = "wasm32"))]
+fn mmap_by_name_at<R: Read + Seek>(
+    zipfile: &File,
+    base: u64,
+    archive: &mut ZipArchive<R>,
+    name: &str,
+    policy: &MemoryPolicy,
+) -> Result<MmapRef, std::io::Error> {
+    let mut index = archive.by_name(name)?;
+
+    // Windows can't delete a file while it is mapped, so the temporary
+    // directory would be left behind; decompress into memory instead.
+    if cfg!(windows) && index.compression() != zip::CompressionMethod::Stored {
+        let mut bytes = Vec::with_capacity(index.size() as usize);
+        index.read_to_end(&mut bytes)?;
+        return Ok(MmapRef::Direct(copy_transducer(&bytes, policy)?));
+    }
 
     if index.compression() != zip::CompressionMethod::Stored {
         let tempdir = tempdir::TempDir::new("divvunspell")?;
@@ -54,28 +275,24 @@ fn mmap_by_name<'a, R: Read + Seek>(
 
         let outfile = File::open(&outpath)?;
 
-        let mmap = unsafe { MmapOptions::new().map(&outfile) };
+        let mmap = unsafe { Mmap::map(&outfile)? };
+        populate(&mmap, policy);
 
-        return match mmap {
-            Ok(v) => Ok(MmapRef::Temp(TempMmap {
-                mmap: Arc::new(v),
-                tempdir,
-            })),
-            Err(err) => panic!(err),
-        };
+        return Ok(MmapRef::Temp(TempMmap {
+            mmap: Arc::new(mmap.into()),
+            tempdir,
+        }));
     }
 
     let mmap = unsafe {
         MmapOptions::new()
-            .offset(index.data_start())
+            .offset(base + index.data_start())
             .len(index.size() as usize)
-            .map(&zipfile)
+            .map(zipfile)?
     };
+    populate(&mmap, policy);
 
-    match mmap {
-        Ok(v) => Ok(MmapRef::Direct(Arc::new(v))),
-        Err(err) => panic!(err),
-    }
+    Ok(MmapRef::Direct(Arc::new(mmap.into())))
 }
 
 #[derive(Debug)]
@@ -86,6 +303,18 @@ pub enum SpellerArchiveError {
     AcceptorMmapFailed(std::io::Error),
     ErrmodelMmapFailed(std::io::Error),
     UnsupportedCompressed,
+    /// An archive given as bytes was not a valid ZHFST file.
+    ReadFailed(std::io::Error),
+    /// `index.xml` could not be parsed, or lacks something it needs.
+    MetadataParseFailed(serde_xml_rs::Error),
+    /// A transducer's header or alphabet is cut short or malformed.
+    TransducerParseFailed(TransducerError),
+    /// Copying the transducers into memory would take `needed` bytes of
+    /// the `limit` a `MemoryPolicy` leaves for them.
+    OverMemoryBudget {
+        needed: usize,
+        limit: usize,
+    },
     Unknown(u8),
 }
 
@@ -97,38 +326,614 @@ impl std::fmt::Display for SpellerArchiveError {
     }
 }
 
+/// Reads the entry `name` out of an archive held in memory.
+fn read_by_name<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Vec<u8>, std::io::Error> {
+    let mut entry = archive.by_name(name)?;
+    let mut buf = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// The hyphenator entry, named after its kind as in
+/// `hyphenator.default.hfst`, if the archive has one.
+fn hyphenator_name<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<Option<String>, SpellerArchiveError> {
+    for i in 0..archive.len() {
+        let entry = archive
+            .by_index(i)
+            .map_err(|e| SpellerArchiveError::ReadFailed(e.into()))?;
+
+        if entry.name().starts_with("hyphenator.") {
+            return Ok(Some(entry.name().to_string()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The entry an archive's abbreviations are read from, if it has one; see
+/// `tokenizer::abbreviations`.
+pub const ABBREVIATIONS_ENTRY: &str = "abbreviations.txt";
+
+/// The archive's abbreviations, or `None` if it has no such entry.
+fn read_abbreviations<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<Option<Abbreviations>, SpellerArchiveError> {
+    let mut entry = match archive.by_name(ABBREVIATIONS_ENTRY) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(SpellerArchiveError::ReadFailed(e.into())),
+    };
+
+    let mut data = String::new();
+    entry
+        .read_to_string(&mut data)
+        .map_err(SpellerArchiveError::ReadFailed)?;
+    Ok(Some(Abbreviations::parse(&data)))
+}
+
+/// The entry an archive's explanations for learners are read from, if it
+/// has one; see `speller::feedback`.
+pub const FEEDBACK_ENTRY: &str = "feedback.tsv";
+
+/// The archive's explanations, or `None` if it has no such entry.
+fn read_feedback<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<Option<Feedback>, SpellerArchiveError> {
+    let mut entry = match archive.by_name(FEEDBACK_ENTRY) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(SpellerArchiveError::ReadFailed(e.into())),
+    };
+
+    let mut data = String::new();
+    entry
+        .read_to_string(&mut data)
+        .map_err(SpellerArchiveError::ReadFailed)?;
+    Ok(Some(Feedback::parse(&data)))
+}
+
+/// The acceptor and error model entries. They are named after their kind,
+/// as in `acceptor.default.hfst`, so where there is one of each they are
+/// found from the zip directory alone and `index.xml` is only checked, to
+/// be parsed when `metadata` is first called; otherwise it is parsed into
+/// `metadata` to find them.
+fn transducer_names<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    xml: &[u8],
+    metadata: &OnceCell<SpellerMetadata>,
+) -> Result<(String, String), SpellerArchiveError> {
+    let mut acceptors = vec![];
+    let mut errmodels = vec![];
+
+    for i in 0..archive.len() {
+        let entry = archive
+            .by_index(i)
+            .map_err(|e| SpellerArchiveError::ReadFailed(e.into()))?;
+
+        if entry.name().starts_with("acceptor.") {
+            acceptors.push(entry.name().to_string());
+        } else if entry.name().starts_with("errmodel.") {
+            errmodels.push(entry.name().to_string());
+        }
+    }
+
+    if acceptors.len() == 1 && errmodels.len() == 1 {
+        SpellerMetadata::check_bytes(xml).map_err(SpellerArchiveError::MetadataParseFailed)?;
+        return Ok((acceptors.remove(0), errmodels.remove(0)));
+    }
+
+    let metadata = metadata.get_or_try_init(|| {
+        SpellerMetadata::from_bytes(xml).map_err(SpellerArchiveError::MetadataParseFailed)
+    })?;
+    Ok((metadata.acceptor.id.clone(), metadata.errmodel.id.clone()))
+}
+
+/// The settings `index.xml` recommends, if it has a `<defaults>` element,
+/// which makes it parsed while opening. Other archives' `index.xml` may be
+/// left unparsed until `metadata` needs it.
+fn metadata_defaults(
+    xml: &[u8],
+    metadata: &OnceCell<SpellerMetadata>,
+) -> Result<Option<SpellerDefaults>, SpellerArchiveError> {
+    const TAG: &[u8] = b"<defaults";
+
+    if !xml.windows(TAG.len()).any(|w| w == TAG) {
+        return Ok(None);
+    }
+
+    let metadata = metadata.get_or_try_init(|| {
+        SpellerMetadata::from_bytes(xml).map_err(SpellerArchiveError::MetadataParseFailed)
+    })?;
+    Ok(metadata.defaults.filter(|defaults| !defaults.is_empty()))
+}
+
+/// What every way of opening an archive reads from it besides the
+/// transducers themselves.
+struct ArchiveEntries {
+    metadata_xml: Vec<u8>,
+    metadata: OnceCell<SpellerMetadata>,
+    acceptor: String,
+    errmodel: String,
+    hyphenator: Option<String>,
+    abbreviations: Option<Abbreviations>,
+    feedback: Option<Feedback>,
+}
+
+fn read_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    metadata_xml: Vec<u8>,
+) -> Result<ArchiveEntries, SpellerArchiveError> {
+    let metadata = OnceCell::new();
+    let (acceptor, errmodel) = transducer_names(archive, &metadata_xml, &metadata)?;
+
+    Ok(ArchiveEntries {
+        abbreviations: read_abbreviations(archive)?,
+        feedback: read_feedback(archive)?,
+        hyphenator: hyphenator_name(archive)?,
+        metadata_xml,
+        metadata,
+        acceptor,
+        errmodel,
+    })
+}
+
+/// An archive's entries and loaded transducers, however they were read.
+struct ArchiveParts {
+    entries: ArchiveEntries,
+    acceptor: HfstTransducer,
+    errmodel: HfstTransducer,
+    hyphenator: Option<HfstTransducer>,
+    memory: MemoryUsage,
+    profile: LoadProfile,
+}
+
+/// Builds the speller from the parts of an archive and sets it up from the
+/// archive's other entries.
+fn finish(
+    parts: ArchiveParts,
+    policy: &MemoryPolicy,
+) -> Result<SpellerArchive, SpellerArchiveError> {
+    let ArchiveParts {
+        entries,
+        acceptor,
+        errmodel,
+        hyphenator,
+        memory,
+        mut profile,
+    } = parts;
+
+    let speller = timed(&mut profile.alphabet_build, || {
+        Speller::with_memory_policy(errmodel, acceptor, policy)
+    });
+    speller.set_huge_errmodel(degrade::is_huge(speller.mutator().header().transitions()));
+
+    if let Some(abbreviations) = entries.abbreviations {
+        speller.set_abbreviations(abbreviations);
+    }
+    if let Some(defaults) = metadata_defaults(&entries.metadata_xml, &entries.metadata)? {
+        speller.set_defaults(defaults);
+    }
+
+    Ok(SpellerArchive {
+        metadata_xml: entries.metadata_xml,
+        metadata: entries.metadata,
+        entries: (entries.acceptor, entries.errmodel),
+        speller,
+        hyphenator: hyphenator.map(Hyphenator::new),
+        hyphenator_entry: entries.hyphenator,
+        feedback: entries.feedback,
+        memory,
+        profile,
+        #[cfg(not(target_arch = "wasm32"))]
+        fingerprint: OnceCell::new(),
+    })
+}
+
+fn check_heap_budget(needed: usize, policy: &MemoryPolicy) -> Result<(), SpellerArchiveError> {
+    match policy.heap_budget() {
+        Some(limit) if needed > limit => {
+            Err(SpellerArchiveError::OverMemoryBudget { needed, limit })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Builds the transducers from their mapped entries, copying them into
+/// memory if the policy prefers that and they fit.
+#[cfg(not(target_arch = "wasm32"))]
+fn transducers_from_maps(
+    acceptor: Arc<TransducerBuf>,
+    errmodel: Arc<TransducerBuf>,
+    policy: &MemoryPolicy,
+    profile: &mut LoadProfile,
+) -> Result<(HfstTransducer, HfstTransducer, MemoryUsage), SpellerArchiveError> {
+    let total = acceptor.len() + errmodel.len();
+    let copy = policy.storage == Storage::PreferHeap && check_heap_budget(total, policy).is_ok();
+    trace_event!(bytes = total, copy = copy, "loading transducers");
+
+    if !copy {
+        let usage = MemoryUsage {
+            mapped_bytes: total,
+            ..MemoryUsage::default()
+        };
+
+        return Ok((
+            load_transducer(acceptor, policy, profile)?,
+            load_transducer(errmodel, policy, profile)?,
+            usage,
+        ));
+    }
+
+    let usage = MemoryUsage {
+        heap_bytes: total,
+        ..MemoryUsage::default()
+    };
+
+    let (acceptor, errmodel) = timed(&mut profile.mmap, || {
+        Ok((
+            copy_transducer(&acceptor, policy)?,
+            copy_transducer(&errmodel, policy)?,
+        ))
+    })
+    .map_err(SpellerArchiveError::ReadFailed)?;
+
+    Ok((
+        load_transducer(acceptor, policy, profile)?,
+        load_transducer(errmodel, policy, profile)?,
+        usage,
+    ))
+}
+
 impl SpellerArchive {
+    /// Loads an archive from bytes in memory, copying out each transducer.
+    /// This is the only constructor on platforms without memory mapping.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SpellerArchive, SpellerArchiveError> {
+        SpellerArchive::from_bytes_with_policy(bytes, &MemoryPolicy::default())
+    }
+
+    /// Like `from_bytes`. The copies always count against the budget, so
+    /// this fails with `OverMemoryBudget` if they don't fit.
+    pub fn from_bytes_with_policy(
+        bytes: &[u8],
+        policy: &MemoryPolicy,
+    ) -> Result<SpellerArchive, SpellerArchiveError> {
+        trace_span!("archive_from_bytes", bytes = bytes.len());
+
+        let mut profile = LoadProfile::default();
+        let mut archive = timed(&mut profile.zip_parse, || {
+            ZipArchive::new(std::io::Cursor::new(bytes))
+        })
+        .map_err(|e| SpellerArchiveError::ReadFailed(e.into()))?;
+
+        let metadata_xml = timed(&mut profile.mmap, || {
+            read_by_name(&mut archive, "index.xml")
+        })
+        .map_err(SpellerArchiveError::ReadFailed)?;
+        let entries = read_entries(&mut archive, metadata_xml)?;
+
+        let (acceptor, errmodel, hyphenator) = timed(&mut profile.mmap, || {
+            let acceptor = read_by_name(&mut archive, &entries.acceptor)?;
+            let errmodel = read_by_name(&mut archive, &entries.errmodel)?;
+            let hyphenator = match &entries.hyphenator {
+                Some(name) => Some(read_by_name(&mut archive, name)?),
+                None => None,
+            };
+            Ok((acceptor, errmodel, hyphenator))
+        })
+        .map_err(SpellerArchiveError::ReadFailed)?;
+
+        let heap_bytes =
+            acceptor.len() + errmodel.len() + hyphenator.as_ref().map_or(0, |h| h.len());
+        check_heap_budget(heap_bytes, policy)?;
+
+        let (acceptor, errmodel) = timed(&mut profile.mmap, || {
+            Ok((
+                copy_transducer(&acceptor, policy)?,
+                copy_transducer(&errmodel, policy)?,
+            ))
+        })
+        .map_err(SpellerArchiveError::ReadFailed)?;
+
+        let hyphenator = match hyphenator {
+            Some(bytes) => {
+                Some(copy_transducer(&bytes, policy).map_err(SpellerArchiveError::ReadFailed)?)
+            }
+            None => None,
+        };
+
+        let acceptor = load_transducer(acceptor, policy, &mut profile)?;
+        let errmodel = load_transducer(errmodel, policy, &mut profile)?;
+        let hyphenator = match hyphenator {
+            Some(buf) => Some(load_transducer(buf, policy, &mut profile)?),
+            None => None,
+        };
+        let memory = MemoryUsage {
+            heap_bytes,
+            ..MemoryUsage::default()
+        };
+
+        finish(
+            ArchiveParts {
+                entries,
+                acceptor,
+                errmodel,
+                hyphenator,
+                memory,
+                profile,
+            },
+            policy,
+        )
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn new(file_path: &str) -> Result<SpellerArchive, SpellerArchiveError> {
-        let file = File::open(file_path).map_err(SpellerArchiveError::OpenFileFailed)?;
+        SpellerArchive::open_with_policy(file_path, &MemoryPolicy::default())
+    }
+
+    /// Opens the archive at `file_path`. On Windows, long and UNC paths are
+    /// opened in their extended-length form, and archives on network shares
+    /// are read into memory rather than mapped, as by `from_bytes`, so that
+    /// the share going away can't crash the process and the file isn't held
+    /// open. Local archives that are replaced while in use should be opened
+    /// with `Storage::PreferHeap`, since Windows won't replace a mapped file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_with_policy(
+        file_path: &str,
+        policy: &MemoryPolicy,
+    ) -> Result<SpellerArchive, SpellerArchiveError> {
+        trace_span!("archive_open", path = file_path);
+
+        let native_path = path::native_path(file_path);
+        if cfg!(windows) && path::is_network_path(&native_path.to_string_lossy()) {
+            let bytes = std::fs::read(&native_path).map_err(SpellerArchiveError::OpenFileFailed)?;
+            return SpellerArchive::from_bytes_with_policy(&bytes, policy);
+        }
+
+        let mut profile = LoadProfile::default();
+        let file = File::open(&native_path).map_err(SpellerArchiveError::OpenFileFailed)?;
         let reader = std::io::BufReader::new(&file);
-        let mut archive = ZipArchive::new(reader).expect("zip");
+        let mut archive = timed(&mut profile.zip_parse, || ZipArchive::new(reader))
+            .map_err(|e| SpellerArchiveError::ReadFailed(e.into()))?;
 
         // Open file a second time to get around borrow checker
-        let mut file = File::open(file_path).map_err(SpellerArchiveError::OpenFileFailed)?;
+        let mut file = File::open(&native_path).map_err(SpellerArchiveError::OpenFileFailed)?;
 
-        let metadata_mmap = mmap_by_name(&mut file, &mut archive, "index.xml")
-            .map_err(SpellerArchiveError::MetadataMmapFailed)?;
-        let metadata = SpellerMetadata::from_bytes(&*metadata_mmap.map()).expect("meta");
+        let metadata_xml = timed(&mut profile.mmap, || {
+            read_by_name(&mut archive, "index.xml")
+        })
+        .map_err(SpellerArchiveError::MetadataMmapFailed)?;
+        let entries = read_entries(&mut archive, metadata_xml)?;
 
-        let acceptor_mmap = mmap_by_name(&mut file, &mut archive, &metadata.acceptor.id)
-            .map_err(SpellerArchiveError::AcceptorMmapFailed)?;
-        let errmodel_mmap = mmap_by_name(&mut file, &mut archive, &metadata.errmodel.id)
-            .map_err(SpellerArchiveError::ErrmodelMmapFailed)?;
+        let acceptor_mmap = timed(&mut profile.mmap, || {
+            mmap_by_name(&mut file, &mut archive, &entries.acceptor, policy)
+        })
+        .map_err(SpellerArchiveError::AcceptorMmapFailed)?;
+        let errmodel_mmap = timed(&mut profile.mmap, || {
+            mmap_by_name(&mut file, &mut archive, &entries.errmodel, policy)
+        })
+        .map_err(SpellerArchiveError::ErrmodelMmapFailed)?;
+        let hyphenator_mmap = match &entries.hyphenator {
+            Some(name) => Some(
+                timed(&mut profile.mmap, || {
+                    mmap_by_name(&mut file, &mut archive, name, policy)
+                })
+                .map_err(SpellerArchiveError::ReadFailed)?,
+            ),
+            None => None,
+        };
         drop(archive);
 
-        let acceptor = HfstTransducer::from_mapped_memory(acceptor_mmap.map());
-        let errmodel = HfstTransducer::from_mapped_memory(errmodel_mmap.map());
+        let (acceptor, errmodel, mut memory) = transducers_from_maps(
+            acceptor_mmap.map(),
+            errmodel_mmap.map(),
+            policy,
+            &mut profile,
+        )?;
+        let hyphenator = match hyphenator_mmap {
+            Some(map) => {
+                let map = map.map();
+                memory.mapped_bytes += map.len();
+                Some(load_transducer(map, policy, &mut profile)?)
+            }
+            None => None,
+        };
+
+        finish(
+            ArchiveParts {
+                entries,
+                acceptor,
+                errmodel,
+                hyphenator,
+                memory,
+                profile,
+            },
+            policy,
+        )
+    }
+
+    /// Opens the archive stored in `len` bytes at `offset` in `file`, such
+    /// as an uncompressed asset in an APK, mapping its transducers straight
+    /// from the file rather than copying the whole archive.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file_range(
+        file: &File,
+        offset: u64,
+        len: usize,
+        policy: &MemoryPolicy,
+    ) -> Result<SpellerArchive, SpellerArchiveError> {
+        trace_span!("archive_from_file_range", offset = offset, len = len);
+
+        let mut profile = LoadProfile::default();
+        let map = timed(&mut profile.mmap, || unsafe {
+            MmapOptions::new().offset(offset).len(len).map(file)
+        })
+        .map_err(SpellerArchiveError::MmapFailed)?;
+        let mut archive = timed(&mut profile.zip_parse, || {
+            ZipArchive::new(std::io::Cursor::new(&map[..]))
+        })
+        .map_err(|e| SpellerArchiveError::ReadFailed(e.into()))?;
 
-        let speller = Speller::new(errmodel, acceptor);
+        let metadata_xml = timed(&mut profile.mmap, || {
+            read_by_name(&mut archive, "index.xml")
+        })
+        .map_err(SpellerArchiveError::ReadFailed)?;
+        let entries = read_entries(&mut archive, metadata_xml)?;
 
-        Ok(SpellerArchive { metadata, speller })
+        let acceptor_mmap = timed(&mut profile.mmap, || {
+            mmap_by_name_at(file, offset, &mut archive, &entries.acceptor, policy)
+        })
+        .map_err(SpellerArchiveError::AcceptorMmapFailed)?;
+        let errmodel_mmap = timed(&mut profile.mmap, || {
+            mmap_by_name_at(file, offset, &mut archive, &entries.errmodel, policy)
+        })
+        .map_err(SpellerArchiveError::ErrmodelMmapFailed)?;
+        let hyphenator_mmap = match &entries.hyphenator {
+            Some(name) => Some(
+                timed(&mut profile.mmap, || {
+                    mmap_by_name_at(file, offset, &mut archive, name, policy)
+                })
+                .map_err(SpellerArchiveError::ReadFailed)?,
+            ),
+            None => None,
+        };
+        drop(archive);
+
+        let (acceptor, errmodel, mut memory) = transducers_from_maps(
+            acceptor_mmap.map(),
+            errmodel_mmap.map(),
+            policy,
+            &mut profile,
+        )?;
+        let hyphenator = match hyphenator_mmap {
+            Some(map) => {
+                let map = map.map();
+                memory.mapped_bytes += map.len();
+                Some(load_transducer(map, policy, &mut profile)?)
+            }
+            None => None,
+        };
+
+        finish(
+            ArchiveParts {
+                entries,
+                acceptor,
+                errmodel,
+                hyphenator,
+                memory,
+                profile,
+            },
+            policy,
+        )
     }
 
-    pub fn speller(&self) -> Arc<Speller<HfstTransducer>> {
+    pub fn speller(&self) -> ArcSpeller {
         self.speller.clone()
     }
 
+    /// The lexicon transducer, for looking words up directly with
+    /// `transducer::lookup`. Its alphabet also has the error model's
+    /// symbols, added past `initial_symbol_count`.
+    pub fn acceptor(&self) -> &HfstTransducer {
+        self.speller.lexicon()
+    }
+
+    /// The error model transducer, mapping input to the strings it may have
+    /// been meant as.
+    pub fn errmodel(&self) -> &HfstTransducer {
+        self.speller.mutator()
+    }
+
+    /// The archive's hyphenator, if it has a `hyphenator.*` entry.
+    pub fn hyphenator(&self) -> Option<&Hyphenator> {
+        self.hyphenator.as_ref()
+    }
+
+    /// The archive's abbreviations, from its `abbreviations.txt`; empty if
+    /// it has none.
+    pub fn abbreviations(&self) -> Arc<Abbreviations> {
+        self.speller.abbreviations()
+    }
+
+    /// The archive's explanations of misspellings for learners, from its
+    /// `feedback.tsv`, if it has one.
+    pub fn feedback(&self) -> Option<&Feedback> {
+        self.feedback.as_ref()
+    }
+
+    /// The archive's `index.xml`. It was checked when the archive was
+    /// opened, but an archive whose transducers are named after their kind
+    /// only parses it on the first call.
     pub fn metadata(&self) -> &SpellerMetadata {
-        &self.metadata
+        self.metadata.get_or_init(|| {
+            SpellerMetadata::from_bytes(&self.metadata_xml)
+                .expect("index.xml was checked when the archive was opened")
+        })
+    }
+
+    /// The names of the acceptor and errmodel entries in the archive.
+    pub fn transducer_entries(&self) -> (&str, &str) {
+        (&self.entries.0, &self.entries.1)
+    }
+
+    /// A hash of `index.xml` and both transducers, the same from one run to
+    /// the next, for telling a rebuilt archive from the one a
+    /// `SuggestionCache` was saved for. The first call reads every page of
+    /// the transducers; later ones return what it worked out.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn fingerprint(&self) -> u64 {
+        use crate::speller::suggestion_cache::{fnv1a, FNV_OFFSET};
+
+        *self.fingerprint.get_or_init(|| {
+            let hash = fnv1a(FNV_OFFSET, &self.metadata_xml);
+            let hash = fnv1a(hash, self.speller.lexicon().buffer());
+            fnv1a(hash, self.speller.mutator().buffer())
+        })
+    }
+
+    /// `index.xml` and the transducers the archive was opened with, in that
+    /// order, for manifests. Reads every page of the transducers.
+    pub fn contents(&self) -> Vec<ArchiveEntry> {
+        let mut contents = vec![archive_entry("index.xml", &self.metadata_xml, None)];
+        let (acceptor, errmodel) = self.transducer_entries();
+
+        for &(name, transducer) in &[
+            (acceptor, self.speller.lexicon()),
+            (errmodel, self.speller.mutator()),
+        ] {
+            contents.push(archive_entry(
+                name,
+                transducer.buffer(),
+                Some(AutomatonInfo::of(transducer)),
+            ));
+        }
+
+        if let (Some(name), Some(hyphenator)) = (&self.hyphenator_entry, &self.hyphenator) {
+            let transducer = hyphenator.transducer();
+            contents.push(archive_entry(
+                name,
+                transducer.buffer(),
+                Some(AutomatonInfo::of(transducer)),
+            ));
+        }
+
+        contents
+    }
+
+    /// How long opening the archive took.
+    pub fn load_profile(&self) -> LoadProfile {
+        self.profile
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            search_peak_bytes: self.speller.search_memory().peak_bytes(),
+            searches_cut_short: self.speller.search_memory().cut_short(),
+            ..self.memory.clone()
+        }
     }
 }
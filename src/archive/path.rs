@@ -0,0 +1,135 @@
+//! Archive paths as the platform needs them.
+//!
+//! On Windows, paths longer than `MAX_PATH` (260 characters) only open in
+//! their extended-length form, `\\?\C:\...` or `\\?\UNC\server\share\...`,
+//! which Windows takes verbatim: no `/` separators and no `.` or `..`. Both
+//! are resolved here instead. Archives on network shares are also told apart,
+//! since mapping them over SMB faults if the connection drops, and holds the
+//! file open for as long as the speller lives.
+
+use std::path::PathBuf;
+
+/// `path` in the form the platform opens best. Anywhere but Windows, or for
+/// paths already in extended-length form, that is `path` unchanged.
+pub fn native_path(path: &str) -> PathBuf {
+    if !cfg!(windows) {
+        return PathBuf::from(path);
+    }
+
+    if let Some(extended) = extended_length(path) {
+        return PathBuf::from(extended);
+    }
+
+    match std::env::current_dir() {
+        Ok(dir) if !is_drive_relative(path) => {
+            let absolute = dir.join(path);
+            extended_length(&absolute.to_string_lossy())
+                .map(PathBuf::from)
+                .unwrap_or(absolute)
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Whether `path` is on a network share, as a UNC path.
+pub fn is_network_path(path: &str) -> bool {
+    let path = path.replace('/', "\\");
+    if let Some(verbatim) = path.strip_prefix(r"\\?\") {
+        return verbatim.to_ascii_uppercase().starts_with(r"UNC\");
+    }
+
+    path.starts_with(r"\\") && !path.starts_with(r"\\.\")
+}
+
+fn has_drive(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// `C:\foo`.
+fn is_drive_absolute(path: &str) -> bool {
+    has_drive(path) && path.as_bytes().get(2) == Some(&b'\\')
+}
+
+/// `C:foo`, relative to the current directory of drive C.
+fn is_drive_relative(path: &str) -> bool {
+    has_drive(path) && !is_drive_absolute(&path.replace('/', "\\"))
+}
+
+/// The extended-length form of an absolute Windows path, or `None` for a
+/// relative one. Device paths (`\\.\`) and verbatim ones are kept as is.
+fn extended_length(path: &str) -> Option<String> {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return Some(path.to_string());
+    }
+
+    let path = path.replace('/', "\\");
+    let (prefix, rest) = if let Some(unc) = path.strip_prefix(r"\\") {
+        (r"\\?\UNC\".to_string(), unc)
+    } else if is_drive_absolute(&path) {
+        (format!(r"\\?\{}\", &path[..2]), &path[3..])
+    } else {
+        return None;
+    };
+
+    let mut components: Vec<&str> = vec![];
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            // A UNC path's server and share can't be climbed out of.
+            ".." if prefix.ends_with(r"UNC\") && components.len() <= 2 => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+
+    Some(prefix + &components.join("\\"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_paths() {
+        assert_eq!(
+            extended_length(r"C:\Users\sámi\..\Public\./se.zhfst"),
+            Some(r"\\?\C:\Users\Public\se.zhfst".to_string())
+        );
+        assert_eq!(
+            extended_length("//fileserver/spellers/se.zhfst"),
+            Some(r"\\?\UNC\fileserver\spellers\se.zhfst".to_string())
+        );
+        assert_eq!(
+            extended_length(r"\\fileserver\spellers\..\..\se.zhfst"),
+            Some(r"\\?\UNC\fileserver\spellers\se.zhfst".to_string())
+        );
+        assert_eq!(extended_length("se.zhfst"), None);
+        assert_eq!(extended_length("C:se.zhfst"), None);
+
+        assert!(is_network_path(r"\\fileserver\spellers\se.zhfst"));
+        assert!(is_network_path(r"\\?\UNC\fileserver\spellers\se.zhfst"));
+        assert!(!is_network_path(r"\\?\C:\se.zhfst"));
+        assert!(!is_network_path(r"C:\se.zhfst"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_paths() {
+        let dir = tempdir::TempDir::new("divvunspell-path").unwrap();
+        let mut path = dir.path().to_path_buf();
+        while path.to_string_lossy().len() < 300 {
+            path.push("a-directory-name-long-enough-to-need-extending");
+        }
+        std::fs::create_dir_all(native_path(&path.to_string_lossy())).unwrap();
+
+        let file = path.join("se.zhfst");
+        std::fs::write(native_path(&file.to_string_lossy()), b"zhfst").unwrap();
+        assert_eq!(
+            std::fs::read(native_path(&file.to_string_lossy())).unwrap(),
+            b"zhfst"
+        );
+    }
+}
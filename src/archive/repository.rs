@@ -0,0 +1,343 @@
+//! Archives for many languages, opened when first asked for, for servers.
+//!
+//! A `SpellerRepository` knows the path of each language's archive and opens
+//! it on the first `get` for that language, handing out `ArchiveHandle`s
+//! that are cheap to clone and keep the archive open for as long as they are
+//! held. With a memory budget, opening an archive that takes the transducers
+//! past it closes the least recently used ones until they fit again; those
+//! are opened again when next asked for. An archive still held by a handle
+//! stays open until the handle is dropped, so the budget is a target rather
+//! than a limit.
+//!
+//! Tags are matched ignoring case, falling back to less specific ones, so
+//! `se-Latn-NO` is served by `se`. Archives are opened without the
+//! repository's lock held, so a slow open doesn't hold up other languages.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{SpellerArchive, SpellerArchiveError};
+use crate::memory::MemoryPolicy;
+use crate::sync::Mutex;
+
+pub type ArchiveHandle = Arc<SpellerArchive>;
+
+/// Run on each archive as it is opened, with its tag, to set up what the
+/// application needs on it, such as metrics.
+pub type OnLoad = Box<dyn Fn(&str, &SpellerArchive) + Send + Sync>;
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    /// No archive is known for the tag or any less specific one.
+    UnknownLanguage(String),
+    OpenFailed {
+        tag: String,
+        path: PathBuf,
+        error: SpellerArchiveError,
+    },
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RepositoryError::UnknownLanguage(tag) => write!(f, "Unknown language: {}", tag),
+            RepositoryError::OpenFailed { path, error, .. } => {
+                write!(f, "{}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+/// Why `SpellerRepository::from_paths` refused a list of archives.
+#[derive(Debug)]
+pub enum RepositoryConfigError {
+    NoArchives,
+    DuplicateLanguage(String),
+}
+
+impl std::error::Error for RepositoryConfigError {}
+
+impl fmt::Display for RepositoryConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RepositoryConfigError::NoArchives => write!(f, "No archives given or configured"),
+            RepositoryConfigError::DuplicateLanguage(tag) => {
+                write!(f, "More than one archive given for {}", tag)
+            }
+        }
+    }
+}
+
+struct Loaded {
+    tag: String,
+    archive: ArchiveHandle,
+    /// What its transducers take, mapped or copied.
+    bytes: usize,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Cache {
+    loaded: Vec<Loaded>,
+    clock: u64,
+}
+
+pub struct SpellerRepository {
+    /// Sorted by tag.
+    paths: Vec<(String, PathBuf)>,
+    policy: MemoryPolicy,
+    memory_budget: Option<usize>,
+    on_load: Option<OnLoad>,
+    cache: Mutex<Cache>,
+}
+
+impl SpellerRepository {
+    /// A repository serving each tag from the archive at its path, opened
+    /// with `policy`. Nothing is opened until asked for.
+    pub fn new<I: IntoIterator<Item = (String, PathBuf)>>(
+        archives: I,
+        policy: MemoryPolicy,
+    ) -> SpellerRepository {
+        let mut paths: Vec<(String, PathBuf)> = archives.into_iter().collect();
+        paths.sort_by(|a, b| a.0.cmp(&b.0));
+        paths.dedup_by(|a, b| a.0 == b.0);
+
+        SpellerRepository {
+            paths,
+            policy,
+            memory_budget: None,
+            on_load: None,
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    /// A repository of the given archives, closing the least recently used
+    /// past `memory_budget` bytes as `set_memory_budget` does. Refuses an
+    /// empty list, and tags given twice, which would shadow one another.
+    pub fn from_paths<I: IntoIterator<Item = (String, PathBuf)>>(
+        archives: I,
+        policy: MemoryPolicy,
+        memory_budget: Option<usize>,
+    ) -> Result<SpellerRepository, RepositoryConfigError> {
+        let archives: Vec<(String, PathBuf)> = archives.into_iter().collect();
+        if archives.is_empty() {
+            return Err(RepositoryConfigError::NoArchives);
+        }
+        for (i, (tag, _)) in archives.iter().enumerate() {
+            if archives[..i]
+                .iter()
+                .any(|(other, _)| other.eq_ignore_ascii_case(tag))
+            {
+                return Err(RepositoryConfigError::DuplicateLanguage(tag.clone()));
+            }
+        }
+
+        let mut repository = SpellerRepository::new(archives, policy);
+        repository.set_memory_budget(memory_budget);
+        Ok(repository)
+    }
+
+    /// Closes the least recently used archives when those open take more
+    /// than `bytes`. `None`, the default, keeps every archive open once
+    /// opened.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.memory_budget = bytes;
+    }
+
+    pub fn set_on_load(&mut self, on_load: OnLoad) {
+        self.on_load = Some(on_load);
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.paths.iter().map(|(tag, _)| &**tag)
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// The tag `tag` is served as: one known ignoring case, or else the
+    /// closest less specific one.
+    pub fn resolve(&self, tag: &str) -> Option<&str> {
+        let mut prefix = tag;
+
+        loop {
+            if let Some((known, _)) = self
+                .paths
+                .iter()
+                .find(|(known, _)| known.eq_ignore_ascii_case(prefix))
+            {
+                return Some(known);
+            }
+
+            match prefix.rfind('-') {
+                Some(i) => prefix = &prefix[..i],
+                None => return None,
+            }
+        }
+    }
+
+    pub fn path(&self, tag: &str) -> Option<&Path> {
+        let tag = self.resolve(tag)?;
+        self.paths
+            .iter()
+            .find(|(known, _)| known == tag)
+            .map(|(_, path)| &**path)
+    }
+
+    /// The archive for `tag`, opening it if it isn't open.
+    pub fn get(&self, tag: &str) -> Result<ArchiveHandle, RepositoryError> {
+        let resolved = self
+            .resolve(tag)
+            .ok_or_else(|| RepositoryError::UnknownLanguage(tag.to_string()))?;
+
+        if let Some(archive) = self.touch(resolved) {
+            return Ok(archive);
+        }
+
+        let path = self.path(resolved).expect("resolved tag");
+        let archive = SpellerArchive::open_with_policy(&path.to_string_lossy(), &self.policy)
+            .map_err(|error| RepositoryError::OpenFailed {
+                tag: resolved.to_string(),
+                path: path.to_path_buf(),
+                error,
+            })?;
+        if let Some(on_load) = &self.on_load {
+            on_load(resolved, &archive);
+        }
+
+        let usage = archive.memory_usage();
+        let mut cache = self.cache.lock();
+
+        // Another thread may have opened it meanwhile; keep theirs
+        if let Some(loaded) = cache.loaded.iter().find(|l| l.tag == resolved) {
+            return Ok(loaded.archive.clone());
+        }
+
+        cache.clock += 1;
+        let archive = Arc::new(archive);
+        let now = cache.clock;
+        cache.loaded.push(Loaded {
+            tag: resolved.to_string(),
+            archive: archive.clone(),
+            bytes: usage.mapped_bytes + usage.heap_bytes,
+            last_used: now,
+        });
+
+        if let Some(budget) = self.memory_budget {
+            while cache.loaded.len() > 1
+                && cache.loaded.iter().map(|l| l.bytes).sum::<usize>() > budget
+            {
+                let oldest = (0..cache.loaded.len())
+                    .min_by_key(|&i| cache.loaded[i].last_used)
+                    .expect("loaded archives");
+                cache.loaded.remove(oldest);
+            }
+        }
+
+        Ok(archive)
+    }
+
+    /// The archive for `tag` if it is open, without opening it.
+    pub fn get_loaded(&self, tag: &str) -> Option<ArchiveHandle> {
+        self.touch(self.resolve(tag)?)
+    }
+
+    /// Opens every archive, stopping at the first that fails, for servers
+    /// that would rather find out at startup.
+    pub fn load_all(&self) -> Result<(), RepositoryError> {
+        for (tag, _) in &self.paths {
+            self.get(tag)?;
+        }
+        Ok(())
+    }
+
+    /// The tags of the archives open, most recently used first.
+    pub fn loaded(&self) -> Vec<String> {
+        let cache = self.cache.lock();
+        let mut loaded: Vec<&Loaded> = cache.loaded.iter().collect();
+        loaded.sort_by_key(|l| std::cmp::Reverse(l.last_used));
+        loaded.iter().map(|l| l.tag.clone()).collect()
+    }
+
+    /// What the transducers of the archives open take.
+    pub fn loaded_bytes(&self) -> usize {
+        self.cache.lock().loaded.iter().map(|l| l.bytes).sum()
+    }
+
+    fn touch(&self, tag: &str) -> Option<ArchiveHandle> {
+        let mut cache = self.cache.lock();
+        cache.clock += 1;
+        let now = cache.clock;
+
+        let loaded = cache.loaded.iter_mut().find(|l| l.tag == tag)?;
+        loaded.last_used = now;
+        Some(loaded.archive.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_and_errors() {
+        let repository = SpellerRepository::new(
+            vec![
+                ("se".to_string(), PathBuf::from("/nonexistent/se.zhfst")),
+                ("sma".to_string(), PathBuf::from("/nonexistent/sma.zhfst")),
+            ],
+            MemoryPolicy::default(),
+        );
+
+        assert_eq!(repository.tags().collect::<Vec<_>>(), vec!["se", "sma"]);
+        assert_eq!(repository.resolve("SE-Latn-NO"), Some("se"));
+        assert_eq!(repository.resolve("smj"), None);
+
+        match repository.get("smj") {
+            Err(RepositoryError::UnknownLanguage(tag)) => assert_eq!(tag, "smj"),
+            _ => panic!("expected an unknown language"),
+        }
+        match repository.get("se-NO") {
+            Err(RepositoryError::OpenFailed { tag, .. }) => assert_eq!(tag, "se"),
+            _ => panic!("expected the archive to fail to open"),
+        }
+
+        assert!(repository.get_loaded("se").is_none());
+        assert!(repository.loaded().is_empty());
+        assert_eq!(repository.loaded_bytes(), 0);
+    }
+
+    #[test]
+    fn from_paths() {
+        let path = |tag: &str| (tag.to_string(), PathBuf::from("/nonexistent/se.zhfst"));
+
+        let repository = SpellerRepository::from_paths(
+            vec![path("se"), path("sma")],
+            MemoryPolicy::default(),
+            Some(1024),
+        )
+        .unwrap();
+        assert_eq!(repository.tags().collect::<Vec<_>>(), vec!["se", "sma"]);
+
+        match SpellerRepository::from_paths(vec![], MemoryPolicy::default(), None) {
+            Err(RepositoryConfigError::NoArchives) => {}
+            _ => panic!("expected no archives to be refused"),
+        }
+        match SpellerRepository::from_paths(
+            vec![path("se"), path("SE")],
+            MemoryPolicy::default(),
+            None,
+        ) {
+            Err(RepositoryConfigError::DuplicateLanguage(tag)) => assert_eq!(tag, "SE"),
+            _ => panic!("expected a tag given twice to be refused"),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Async variants of the blocking calls, for tokio, built with the `async`
+//! feature.
+//!
+//! Opening an archive and searching for suggestions are CPU-bound and would
+//! hold up a runtime's reactor threads, so these run them on tokio's
+//! blocking pool and can be awaited from a server handler or an async GUI.
+//! Suggestions take an optional deadline, which becomes the search's time
+//! budget: a search that runs out of time returns what it has found, rather
+//! than keeping a blocking thread busy after the caller has stopped waiting.
+
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::task::JoinError;
+
+use crate::archive::{SpellerArchive, SpellerArchiveError};
+use crate::memory::MemoryPolicy;
+use crate::speller::issue::{within_deadline, SpellingIssue};
+use crate::speller::suggestion::Suggestion;
+use crate::speller::{Speller, SpellerConfig, WordCheck};
+use crate::transducer::Transducer;
+
+#[derive(Debug)]
+pub enum AsyncError {
+    Archive(SpellerArchiveError),
+    Io(io::Error),
+    /// The blocking task panicked, or the runtime shut down before it ran.
+    Task(JoinError),
+}
+
+impl std::error::Error for AsyncError {}
+
+impl fmt::Display for AsyncError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsyncError::Archive(e) => write!(f, "{}", e),
+            AsyncError::Io(e) => write!(f, "{}", e),
+            AsyncError::Task(e) => write!(f, "speller task failed: {}", e),
+        }
+    }
+}
+
+impl From<SpellerArchiveError> for AsyncError {
+    fn from(e: SpellerArchiveError) -> AsyncError {
+        AsyncError::Archive(e)
+    }
+}
+
+impl From<io::Error> for AsyncError {
+    fn from(e: io::Error) -> AsyncError {
+        AsyncError::Io(e)
+    }
+}
+
+/// Runs `f` on the blocking pool.
+pub async fn blocking<T, F>(f: F) -> Result<T, AsyncError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(AsyncError::Task)
+}
+
+/// Opens the archive at `path`, as `SpellerArchive::open_with_policy`.
+pub async fn open_archive(path: &str, policy: &MemoryPolicy) -> Result<SpellerArchive, AsyncError> {
+    let path = path.to_string();
+    let policy = policy.clone();
+
+    Ok(blocking(move || SpellerArchive::open_with_policy(&path, &policy)).await??)
+}
+
+/// Reads a whole archive from `reader`, such as a download or an asset
+/// stream, and opens it from memory as `SpellerArchive::from_bytes_with_policy`.
+pub async fn read_archive<R: AsyncRead + Unpin>(
+    mut reader: R,
+    policy: &MemoryPolicy,
+) -> Result<SpellerArchive, AsyncError> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes).await?;
+    let policy = policy.clone();
+
+    Ok(blocking(move || SpellerArchive::from_bytes_with_policy(&bytes, &policy)).await??)
+}
+
+pub async fn is_correct<T>(speller: Arc<Speller<T>>, word: &str) -> Result<bool, AsyncError>
+where
+    T: Transducer + Send + Sync + 'static,
+{
+    let word = word.to_string();
+    blocking(move || speller.is_correct(&word)).await
+}
+
+/// Suggestions for `word`, searching until `deadline` at the latest. Past
+/// the deadline there are none.
+pub async fn suggest<T>(
+    speller: Arc<Speller<T>>,
+    word: &str,
+    config: &SpellerConfig,
+    deadline: Option<Instant>,
+) -> Result<Vec<Suggestion>, AsyncError>
+where
+    T: Transducer + Send + Sync + 'static,
+{
+    let config = match within_deadline(config, deadline) {
+        Some(config) => config,
+        None => return Ok(vec![]),
+    };
+    let word = word.to_string();
+
+    blocking(move || speller.suggest_with_config(&word, &config)).await
+}
+
+/// Whether `word` is correct and suggestions for it, as `Speller::check`,
+/// searching until `deadline` at the latest. Past the deadline, the word is
+/// still checked but there are no suggestions.
+pub async fn check<T>(
+    speller: Arc<Speller<T>>,
+    word: &str,
+    config: &SpellerConfig,
+    deadline: Option<Instant>,
+) -> Result<WordCheck, AsyncError>
+where
+    T: Transducer + Send + Sync + 'static,
+{
+    let word = word.to_string();
+
+    match within_deadline(config, deadline) {
+        Some(config) => blocking(move || speller.check(&word, &config)).await,
+        None => Ok(WordCheck {
+            correct: is_correct(speller, &word).await?,
+            suggestions: vec![],
+        }),
+    }
+}
+
+/// The misspellings in `text`, as `Speller::check_text_until` finds them,
+/// with suggestions for as many as there is time for before `deadline`.
+/// Those found after it are reported without.
+pub async fn check_text<T>(
+    speller: Arc<Speller<T>>,
+    text: &str,
+    config: &SpellerConfig,
+    deadline: Option<Instant>,
+) -> Result<Vec<SpellingIssue>, AsyncError>
+where
+    T: Transducer + Send + Sync + 'static,
+{
+    let text = text.to_string();
+    let config = config.clone();
+
+    blocking(move || {
+        speller
+            .clone()
+            .check_text_until(&text, &config, deadline, |word, config| {
+                speller.clone().suggest_with_config(word, config)
+            })
+    })
+    .await
+}
@@ -0,0 +1,112 @@
+//! Opening the archive under test in any format the library reads, so that
+//! the same typo list can show whether a converted archive suggests what the
+//! original did.
+//!
+//! A directory is read as a CHFST bundle, with `lexicon` and `mutator`
+//! subdirectories as `divvunspell convert` writes them, and anything else as
+//! a ZHFST file. CHFST bundles have no `index.xml`, so their reports carry
+//! placeholder metadata: the `und` locale and the subdirectory names as ids.
+
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+use divvunspell::archive::meta::{
+    SpellerMetadata, SpellerMetadataAcceptor, SpellerMetadataErrmodel, SpellerMetadataInfo,
+};
+use divvunspell::archive::SpellerArchive;
+use divvunspell::memory::MemoryUsage;
+use divvunspell::speller::suggestion::Suggestion;
+use divvunspell::speller::{Speller, SpellerConfig};
+use divvunspell::transducer::chunk::{ChfstBundle, ChfstTransducer};
+use divvunspell::types::Weight;
+
+pub enum Archive {
+    Zhfst(SpellerArchive),
+    Chfst {
+        speller: Arc<Speller<ChfstTransducer>>,
+        metadata: SpellerMetadata,
+    },
+}
+
+impl Archive {
+    pub fn open(path: &str) -> Result<Archive, Box<dyn Error>> {
+        let file = Path::new(path);
+
+        if file.is_dir() {
+            let bundle = ChfstBundle::from_path(file).map_err(|e| format!("{}: {}", path, e))?;
+            return Ok(Archive::Chfst {
+                speller: bundle.speller(),
+                metadata: chfst_metadata(),
+            });
+        }
+
+        if file.extension().map_or(false, |ext| ext == "bhfst") {
+            return Err(format!("{}: BHFST archives aren't supported by this build", path).into());
+        }
+
+        Ok(Archive::Zhfst(SpellerArchive::new(path)?))
+    }
+
+    pub fn metadata(&self) -> &SpellerMetadata {
+        match self {
+            Archive::Zhfst(archive) => archive.metadata(),
+            Archive::Chfst { metadata, .. } => metadata,
+        }
+    }
+
+    /// What the transducers take and the most a search has allocated, for
+    /// ZHFST archives.
+    pub fn memory_usage(&self) -> Option<MemoryUsage> {
+        match self {
+            Archive::Zhfst(archive) => Some(archive.memory_usage()),
+            Archive::Chfst { .. } => None,
+        }
+    }
+
+    pub fn is_correct(&self, word: &str) -> bool {
+        match self {
+            Archive::Zhfst(archive) => archive.speller().is_correct(word),
+            Archive::Chfst { speller, .. } => speller.clone().is_correct(word),
+        }
+    }
+
+    pub fn correction_weight(&self, word: &str, expected: &str) -> Option<Weight> {
+        match self {
+            Archive::Zhfst(archive) => archive.speller().correction_weight(word, expected),
+            Archive::Chfst { speller, .. } => speller.clone().correction_weight(word, expected),
+        }
+    }
+
+    pub fn suggest(&self, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
+        match self {
+            Archive::Zhfst(archive) => archive.speller().suggest_with_config(word, config),
+            Archive::Chfst { speller, .. } => speller.clone().suggest_with_config(word, config),
+        }
+    }
+}
+
+fn chfst_metadata() -> SpellerMetadata {
+    SpellerMetadata {
+        info: SpellerMetadataInfo {
+            locale: "und".to_string(),
+            title: vec![],
+            description: String::new(),
+            version: None,
+            date: None,
+            producer: String::new(),
+        },
+        acceptor: SpellerMetadataAcceptor {
+            type_: "general".to_string(),
+            id: "lexicon".to_string(),
+            title: vec![],
+            description: String::new(),
+        },
+        errmodel: SpellerMetadataErrmodel {
+            id: "mutator".to_string(),
+            title: vec![],
+            description: String::new(),
+        },
+        defaults: None,
+    }
+}
@@ -0,0 +1,67 @@
+//! Suggestion weights by rank, for fitting how likely a suggestion at a given
+//! weight is to be right, as an autocorrect threshold needs.
+//!
+//! The dump is a TSV file with a header row and a row per suggestion:
+//! `input`, `expected`, `rank` from 1, `suggestion`, `weight` and `correct`.
+//! When the expected word wasn't suggested it gets a row of its own with an
+//! empty rank and the weight it would have had, from
+//! `Speller::correction_weight`, or an empty weight if the error model can't
+//! reach it.
+
+use std::error::Error;
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use super::archive::Archive;
+use super::AccuracyResult;
+
+pub fn write_weights(
+    path: &str,
+    archive: &Archive,
+    results: &[AccuracyResult<'_>],
+) -> Result<(), Box<dyn Error>> {
+    let expected_weights = results
+        .par_iter()
+        .map(|r| match r.position {
+            Some(_) => None,
+            None => archive.correction_weight(r.input, r.expected),
+        })
+        .collect::<Vec<_>>();
+
+    let mut wtr = csv::WriterBuilder::new().delimiter(b'\t').from_path(path)?;
+    wtr.write_record(&[
+        "input",
+        "expected",
+        "rank",
+        "suggestion",
+        "weight",
+        "correct",
+    ])?;
+
+    for (result, expected_weight) in results.iter().zip(expected_weights) {
+        for (i, s) in result.suggestions.iter().enumerate() {
+            wtr.write_record(&[
+                result.input,
+                result.expected,
+                &(i + 1).to_string(),
+                &s.value,
+                &s.weight.to_string(),
+                &(result.position == Some(i)).to_string(),
+            ])?;
+        }
+
+        if result.position.is_none() {
+            wtr.write_record(&[
+                result.input,
+                result.expected,
+                "",
+                result.expected,
+                &expected_weight.map(|w| w.to_string()).unwrap_or_default(),
+                "true",
+            ])?;
+        }
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Periodic on-disk checkpointing of per-word results, so long runs can be
+//! resumed with `--resume` instead of starting over.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use divvunspell::speller::suggestion::Suggestion;
+use hashbrown::HashMap;
+use parking_lot::Mutex;
+use serde_derive::{Deserialize, Serialize};
+
+use super::{AccuracyResult, Time};
+
+/// How many results are buffered before the checkpoint file is flushed.
+const FLUSH_INTERVAL: usize = 100;
+
+#[derive(Serialize)]
+struct EntryRef<'a> {
+    index: usize,
+    input: &'a str,
+    suggestions: &'a [Suggestion],
+    position: Option<usize>,
+    time: Time,
+}
+
+#[derive(Deserialize)]
+pub struct Entry {
+    index: usize,
+    input: String,
+    pub suggestions: Vec<Suggestion>,
+    pub position: Option<usize>,
+    pub time: Time,
+}
+
+pub struct Checkpoint {
+    writer: Mutex<BufWriter<File>>,
+    written: AtomicUsize,
+    done: HashMap<usize, Entry>,
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint at `path`. When resuming, previously completed
+    /// entries are loaded (a truncated trailing line from an interrupted run is
+    /// dropped) and kept; otherwise the file is started afresh.
+    pub fn open(path: &str, resume: bool, words: &[(String, String)]) -> io::Result<Checkpoint> {
+        let mut done = HashMap::new();
+
+        if resume {
+            match File::open(path) {
+                Ok(file) => {
+                    for line in BufReader::new(file).lines() {
+                        let entry: Entry = match serde_json::from_str(&line?) {
+                            Ok(v) => v,
+                            Err(_) => continue,
+                        };
+
+                        // Guard against resuming with a different word list
+                        match words.get(entry.index) {
+                            Some((input, _)) if input == &entry.input => {
+                                done.insert(entry.index, entry);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+
+            eprintln!("Resuming with {} words already checked.", done.len());
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        for entry in done.values() {
+            write_entry(
+                &mut writer,
+                &EntryRef {
+                    index: entry.index,
+                    input: &entry.input,
+                    suggestions: &entry.suggestions,
+                    position: entry.position,
+                    time: entry.time,
+                },
+            )?;
+        }
+        writer.flush()?;
+
+        Ok(Checkpoint {
+            writer: Mutex::new(writer),
+            written: AtomicUsize::new(0),
+            done,
+        })
+    }
+
+    pub fn resumed(&self, index: usize) -> Option<&Entry> {
+        self.done.get(&index)
+    }
+
+    pub fn record(&self, index: usize, result: &AccuracyResult<'_>) -> io::Result<()> {
+        let entry = EntryRef {
+            index,
+            input: result.input,
+            suggestions: &result.suggestions,
+            position: result.position,
+            time: result.time,
+        };
+
+        let mut writer = self.writer.lock();
+        write_entry(&mut *writer, &entry)?;
+
+        if self.written.fetch_add(1, Ordering::Relaxed) % FLUSH_INTERVAL == FLUSH_INTERVAL - 1 {
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_entry<W: Write>(writer: &mut W, entry: &EntryRef<'_>) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, entry)?;
+    writeln!(writer)
+}
@@ -0,0 +1,159 @@
+//! Detection metrics over a gold corpus containing both correct and misspelled
+//! tokens, measuring what users see: how often real errors are flagged and how
+//! often correct words are wrongly flagged.
+
+use std::error::Error;
+use std::time::Instant;
+
+use indicatif::ParallelProgressIterator;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde_derive::Serialize;
+
+use super::archive::Archive;
+use super::{progress_bar, Time};
+
+/// Loads a gold corpus where each row is `token[TAB correction]`. A token with
+/// no correction, or a correction equal to itself, is considered correct.
+pub fn load_gold(
+    path: &str,
+    max_words: Option<usize>,
+) -> Result<Vec<(String, bool)>, Box<dyn Error>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .delimiter(b'\t')
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)?;
+
+    Ok(rdr
+        .records()
+        .filter_map(Result::ok)
+        .filter_map(|r| {
+            r.get(0).map(|token| {
+                let is_misspelled = match r.get(1) {
+                    Some(c) => !c.is_empty() && c != token,
+                    None => false,
+                };
+                (token.to_string(), is_misspelled)
+            })
+        })
+        .take(max_words.unwrap_or(std::usize::MAX))
+        .collect())
+}
+
+#[derive(Debug, Default, Serialize)]
+struct DetectionSummary {
+    total_words: u32,
+    true_positives: u32,
+    false_positives: u32,
+    true_negatives: u32,
+    false_negatives: u32,
+    precision: f32,
+    recall: f32,
+    f1: f32,
+    false_positive_rate: f32,
+}
+
+fn ratio(a: u32, b: u32) -> f32 {
+    if b == 0 {
+        0.0
+    } else {
+        a as f32 / b as f32
+    }
+}
+
+impl DetectionSummary {
+    fn finish(&mut self) {
+        let tp = self.true_positives;
+        self.precision = ratio(tp, tp + self.false_positives);
+        self.recall = ratio(tp, tp + self.false_negatives);
+        self.f1 = if self.precision + self.recall > 0.0 {
+            2.0 * self.precision * self.recall / (self.precision + self.recall)
+        } else {
+            0.0
+        };
+        self.false_positive_rate = ratio(
+            self.false_positives,
+            self.false_positives + self.true_negatives,
+        );
+    }
+}
+
+impl std::fmt::Display for DetectionSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "[precision] {:.2}% [recall] {:.2}% [F1] {:.4} [FP rate] {:.2}% [tp] {} [fp] {} [tn] {} [fn] {}",
+            self.precision * 100f32,
+            self.recall * 100f32,
+            self.f1,
+            self.false_positive_rate * 100f32,
+            self.true_positives,
+            self.false_positives,
+            self.true_negatives,
+            self.false_negatives
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DetectionReport<'a> {
+    metadata: &'a divvunspell::archive::meta::SpellerMetadata,
+    summary: DetectionSummary,
+    false_positives: Vec<&'a str>,
+    false_negatives: Vec<&'a str>,
+    total_time: Time,
+}
+
+pub fn run(
+    archive: &Archive,
+    words: &[(String, bool)],
+    json_output: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let start_time = Instant::now();
+    let flagged = words
+        .par_iter()
+        .progress_with(progress_bar(words.len()))
+        .map(|(word, _)| !archive.is_correct(word))
+        .collect::<Vec<_>>();
+    let total_time = Time::from(start_time.elapsed());
+
+    let mut summary = DetectionSummary::default();
+    let mut false_positives = vec![];
+    let mut false_negatives = vec![];
+
+    for ((word, is_misspelled), is_flagged) in words.iter().zip(flagged.into_iter()) {
+        summary.total_words += 1;
+
+        match (*is_misspelled, is_flagged) {
+            (true, true) => summary.true_positives += 1,
+            (true, false) => {
+                summary.false_negatives += 1;
+                false_negatives.push(word.as_str());
+            }
+            (false, true) => {
+                summary.false_positives += 1;
+                false_positives.push(word.as_str());
+            }
+            (false, false) => summary.true_negatives += 1,
+        }
+    }
+
+    summary.finish();
+    println!("{}", summary);
+
+    if let Some(path) = json_output {
+        let output = std::fs::File::create(path)?;
+        let report = DetectionReport {
+            metadata: archive.metadata(),
+            summary,
+            false_positives,
+            false_negatives,
+            total_time,
+        };
+        println!("Writing JSON report…");
+        serde_json::to_writer_pretty(output, &report)?;
+    }
+
+    Ok(())
+}
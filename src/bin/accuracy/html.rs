@@ -0,0 +1,265 @@
+//! Self-contained HTML rendering of the accuracy report. Everything (styles and
+//! charts) is inlined so the file can be mailed or uploaded as-is.
+
+use std::io::{self, Write};
+
+use super::{Breakdown, Report};
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em auto; max-width: 60em; color: #222; }
+h1, h2 { font-weight: normal; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { padding: 0.25em 0.75em; border-bottom: 1px solid #ddd; text-align: left; }
+td.num { text-align: right; font-variant-numeric: tabular-nums; }
+svg text { font-size: 11px; }
+.first { fill: #2b7a3d; }
+.top5 { fill: #6fb36f; }
+.any { fill: #b9dcb0; }
+.latency { fill: #4a77b4; }
+"#;
+
+const WORST_SHOWN: usize = 50;
+const CHART_WIDTH: usize = 600;
+const BAR_HEIGHT: usize = 18;
+const LABEL_WIDTH: usize = 90;
+
+/// Upper bounds (exclusive, in milliseconds) of the latency histogram buckets.
+const LATENCY_BUCKETS: &[u64] = &[1, 5, 10, 50, 100, 500, 1000];
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+
+    for ch in s.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+
+    out
+}
+
+fn percent(v: u32, total: u32) -> f32 {
+    if total == 0 {
+        0.0
+    } else {
+        v as f32 / total as f32 * 100f32
+    }
+}
+
+fn write_breakdown_table<W: Write>(w: &mut W, title: &str, rows: &[Breakdown]) -> io::Result<()> {
+    writeln!(w, "<h2>{}</h2>", escape(title))?;
+    writeln!(
+        w,
+        "<table><tr><th>Bucket</th><th>Words</th><th>#1</th><th>Top 5</th><th>Any</th><th>None</th><th>Wrong</th></tr>"
+    )?;
+
+    for row in rows {
+        let a = &row.accuracy;
+        writeln!(
+            w,
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{:.2}%</td><td class=\"num\">{:.2}%</td><td class=\"num\">{:.2}%</td><td class=\"num\">{:.2}%</td><td class=\"num\">{:.2}%</td></tr>",
+            escape(&row.bucket),
+            a.total_words,
+            percent(a.first_position, a.total_words),
+            percent(a.top_five, a.total_words),
+            percent(a.any_position, a.total_words),
+            percent(a.no_suggestions, a.total_words),
+            percent(a.only_wrong, a.total_words),
+        )?;
+    }
+
+    writeln!(w, "</table>")?;
+    write_accuracy_chart(w, rows)
+}
+
+/// Overlapping horizontal bars per bucket: any position, top five and first
+/// position, drawn widest first.
+fn write_accuracy_chart<W: Write>(w: &mut W, rows: &[Breakdown]) -> io::Result<()> {
+    let bar_space = (CHART_WIDTH - LABEL_WIDTH) as f32;
+    let height = rows.len() * (BAR_HEIGHT + 4) + 4;
+
+    writeln!(
+        w,
+        "<svg width=\"{}\" height=\"{}\" role=\"img\">",
+        CHART_WIDTH, height
+    )?;
+
+    for (i, row) in rows.iter().enumerate() {
+        let a = &row.accuracy;
+        let y = i * (BAR_HEIGHT + 4) + 2;
+
+        writeln!(
+            w,
+            "<text x=\"0\" y=\"{}\">{}</text>",
+            y + BAR_HEIGHT - 5,
+            escape(&row.bucket)
+        )?;
+
+        for (class, value) in &[
+            ("any", a.any_position),
+            ("top5", a.top_five),
+            ("first", a.first_position),
+        ] {
+            let width = percent(*value, a.total_words) / 100f32 * bar_space;
+            writeln!(
+                w,
+                "<rect class=\"{}\" x=\"{}\" y=\"{}\" width=\"{:.1}\" height=\"{}\"><title>{}: {:.2}%</title></rect>",
+                class,
+                LABEL_WIDTH,
+                y,
+                width,
+                BAR_HEIGHT,
+                class,
+                percent(*value, a.total_words)
+            )?;
+        }
+    }
+
+    writeln!(w, "</svg>")
+}
+
+fn write_latency_chart<W: Write>(w: &mut W, report: &Report<'_>) -> io::Result<()> {
+    let mut counts = vec![0usize; LATENCY_BUCKETS.len() + 1];
+
+    for result in report.results.iter() {
+        let ms = result.time.millis();
+        let i = LATENCY_BUCKETS
+            .iter()
+            .position(|&max| ms < max)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        counts[i] += 1;
+    }
+
+    let max = counts.iter().cloned().max().unwrap_or(0).max(1);
+    let bar_space = (CHART_WIDTH - LABEL_WIDTH) as f32;
+    let height = counts.len() * (BAR_HEIGHT + 4) + 4;
+
+    writeln!(w, "<h2>Lookup latency</h2>")?;
+    writeln!(
+        w,
+        "<svg width=\"{}\" height=\"{}\" role=\"img\">",
+        CHART_WIDTH, height
+    )?;
+
+    for (i, count) in counts.iter().enumerate() {
+        let label = match i {
+            0 => format!("< {}ms", LATENCY_BUCKETS[0]),
+            i if i == LATENCY_BUCKETS.len() => format!("≥ {}ms", LATENCY_BUCKETS[i - 1]),
+            i => format!("{}–{}ms", LATENCY_BUCKETS[i - 1], LATENCY_BUCKETS[i]),
+        };
+        let y = i * (BAR_HEIGHT + 4) + 2;
+        let width = *count as f32 / max as f32 * bar_space;
+
+        writeln!(
+            w,
+            "<text x=\"0\" y=\"{}\">{}</text><rect class=\"latency\" x=\"{}\" y=\"{}\" width=\"{:.1}\" height=\"{}\"><title>{} words</title></rect>",
+            y + BAR_HEIGHT - 5,
+            escape(&label),
+            LABEL_WIDTH,
+            y,
+            width,
+            BAR_HEIGHT,
+            count
+        )?;
+    }
+
+    writeln!(w, "</svg>")
+}
+
+fn write_worst<W: Write>(w: &mut W, report: &Report<'_>) -> io::Result<()> {
+    let mut missed = report
+        .results
+        .iter()
+        .filter(|r| r.position.is_none())
+        .collect::<Vec<_>>();
+    missed.sort_by(|a, b| b.time.cmp(&a.time));
+
+    writeln!(w, "<h2>Missed corrections (slowest first)</h2>")?;
+    writeln!(
+        w,
+        "<table><tr><th>Input</th><th>Expected</th><th>Time</th><th>Suggestions</th></tr>"
+    )?;
+
+    for r in missed.iter().take(WORST_SHOWN) {
+        let suggestions = r
+            .suggestions
+            .iter()
+            .take(5)
+            .map(|s| escape(s.value()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        writeln!(
+            w,
+            "<tr><td>{}</td><td>{}</td><td class=\"num\">{}</td><td>{}</td></tr>",
+            escape(r.input),
+            escape(r.expected),
+            r.time,
+            suggestions
+        )?;
+    }
+
+    writeln!(w, "</table>")
+}
+
+pub fn write_report<W: Write>(w: &mut W, report: &Report<'_>) -> io::Result<()> {
+    let summary = &report.summary;
+    let a = &summary.accuracy;
+    let locale = &report.metadata.info.locale;
+
+    writeln!(w, "<!DOCTYPE html>")?;
+    writeln!(w, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(w, "<title>DivvunSpell accuracy: {}</title>", escape(locale))?;
+    writeln!(w, "<style>{}</style></head><body>", STYLE)?;
+    writeln!(
+        w,
+        "<h1>DivvunSpell accuracy report: {}</h1>",
+        escape(locale)
+    )?;
+
+    writeln!(w, "<h2>Summary</h2><table>")?;
+    for (label, value) in &[
+        ("Words", a.total_words.to_string()),
+        ("First position", a.percent(a.first_position)),
+        ("Top five", a.percent(a.top_five)),
+        ("Any position", a.percent(a.any_position)),
+        ("No suggestions", a.percent(a.no_suggestions)),
+        ("Only wrong", a.percent(a.only_wrong)),
+        ("Fastest lookup", summary.fastest_lookup.to_string()),
+        ("Slowest lookup", summary.slowest_lookup.to_string()),
+        ("Total time", report.total_time.to_string()),
+    ] {
+        writeln!(
+            w,
+            "<tr><th>{}</th><td class=\"num\">{}</td></tr>",
+            label,
+            escape(value)
+        )?;
+    }
+    writeln!(w, "</table>")?;
+
+    write_breakdown_table(w, "By edit distance", &summary.by_edit_distance)?;
+    write_breakdown_table(w, "By word length", &summary.by_word_length)?;
+    write_latency_chart(w, report)?;
+
+    writeln!(w, "<h2>Most common unhandled edits</h2>")?;
+    writeln!(w, "<table><tr><th>Edit</th><th>Count</th></tr>")?;
+    for c in report.confusions.iter().take(WORST_SHOWN) {
+        writeln!(
+            w,
+            "<tr><td>{}</td><td class=\"num\">{}</td></tr>",
+            escape(&c.edit.to_string()),
+            c.count
+        )?;
+    }
+    writeln!(w, "</table>")?;
+
+    write_worst(w, report)?;
+
+    writeln!(w, "</body></html>")
+}
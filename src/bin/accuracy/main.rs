@@ -0,0 +1,718 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::time::{Duration, Instant, SystemTime};
+
+use clap::{App, AppSettings, Arg};
+use divvunspell::config::CliConfig;
+use divvunspell::errmodel::align::{align, levenshtein, Edit};
+use divvunspell::speller::suggestion::Suggestion;
+use divvunspell::speller::{SeenNodes, SpellerConfig, WeightSemiring};
+use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use serde_derive::{Deserialize, Serialize};
+
+use self::archive::Archive;
+
+mod archive;
+mod calibration;
+mod checkpoint;
+mod detection;
+mod html;
+mod memory;
+mod sample;
+mod sqlite;
+mod sweep;
+mod throughput;
+
+static CFG: SpellerConfig = SpellerConfig {
+    max_weight: Some(50000.0),
+    n_best: Some(10),
+    max_edit_distance: None,
+    max_weight_gap: None,
+    beam: None,
+    pool_max: 128,
+    pool_start: 128,
+    seen_nodes: SeenNodes::Off,
+    seen_node_sample_rate: 15,
+    seen_false_positive_rate: 0.001,
+    with_caps: true,
+    adaptive_beam: false,
+    beam_min: 5.0,
+    beam_max: 40.0,
+    time_budget_ms: None,
+    prefix_cache: None,
+    semiring: WeightSemiring::Tropical,
+    input_profile: None,
+    proper_nouns: false,
+    constrain_huge_errmodels: true,
+    fallback_distance: None,
+};
+
+fn load_words(
+    path: &str,
+    max_words: Option<usize>,
+) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .delimiter(b'\t')
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)?;
+
+    Ok(rdr
+        .records()
+        .filter_map(Result::ok)
+        .filter_map(|r| {
+            r.get(0)
+                .and_then(|x| r.get(1).map(|y| (x.to_string(), y.to_string())))
+        })
+        .take(max_words.unwrap_or(std::usize::MAX))
+        .collect())
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialOrd, Ord, PartialEq, Eq, Clone, Copy)]
+struct Time {
+    secs: u64,
+    subsec_nanos: u32,
+}
+
+impl Time {
+    fn millis(&self) -> u64 {
+        self.secs * 1000 + (self.subsec_nanos as u64 / 1000000)
+    }
+}
+
+impl From<Duration> for Time {
+    fn from(d: Duration) -> Time {
+        Time {
+            secs: d.as_secs(),
+            subsec_nanos: d.subsec_nanos(),
+        }
+    }
+}
+
+impl std::fmt::Display for Time {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{}ms", self.millis())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AccuracyResult<'a> {
+    input: &'a str,
+    expected: &'a str,
+    suggestions: Vec<Suggestion>,
+    position: Option<usize>,
+    time: Time,
+    edit_distance: usize,
+}
+
+const MAX_EDIT_DISTANCE_BUCKET: usize = 5;
+const WORD_LENGTH_BUCKET_SIZE: usize = 4;
+const MAX_WORD_LENGTH_BUCKET: usize = 20;
+
+fn edit_distance_bucket(distance: usize) -> (usize, String) {
+    if distance >= MAX_EDIT_DISTANCE_BUCKET {
+        (
+            MAX_EDIT_DISTANCE_BUCKET,
+            format!("{}+", MAX_EDIT_DISTANCE_BUCKET),
+        )
+    } else {
+        (distance, distance.to_string())
+    }
+}
+
+fn word_length_bucket(len: usize) -> (usize, String) {
+    if len > MAX_WORD_LENGTH_BUCKET {
+        return (
+            MAX_WORD_LENGTH_BUCKET + 1,
+            format!("{}+", MAX_WORD_LENGTH_BUCKET + 1),
+        );
+    }
+
+    let start = len.saturating_sub(1) / WORD_LENGTH_BUCKET_SIZE * WORD_LENGTH_BUCKET_SIZE + 1;
+    (
+        start,
+        format!("{}-{}", start, start + WORD_LENGTH_BUCKET_SIZE - 1),
+    )
+}
+
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    metadata: &'a divvunspell::archive::meta::SpellerMetadata,
+    config: &'a SpellerConfig,
+    summary: Summary,
+    confusions: Vec<Confusion>,
+    results: Vec<AccuracyResult<'a>>,
+    start_timestamp: Time,
+    total_time: Time,
+    memory: memory::MemoryReport,
+}
+
+#[derive(Serialize, Default, Debug, Clone)]
+struct Accuracy {
+    total_words: u32,
+    first_position: u32,
+    top_five: u32,
+    any_position: u32,
+    no_suggestions: u32,
+    only_wrong: u32,
+}
+
+impl Accuracy {
+    fn add(&mut self, result: &AccuracyResult<'_>) {
+        self.total_words += 1;
+
+        if let Some(position) = result.position {
+            self.any_position += 1;
+
+            if position == 0 {
+                self.first_position += 1;
+            }
+
+            if position < 5 {
+                self.top_five += 1;
+            }
+        } else if result.suggestions.len() == 0 {
+            self.no_suggestions += 1;
+        } else {
+            self.only_wrong += 1;
+        }
+    }
+
+    fn percent(&self, v: u32) -> String {
+        format!("{:.2}%", v as f32 / self.total_words as f32 * 100f32)
+    }
+}
+
+impl std::fmt::Display for Accuracy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "[#1] {} [^5] {} [any] {} [none] {} [wrong] {}",
+            self.percent(self.first_position),
+            self.percent(self.top_five),
+            self.percent(self.any_position),
+            self.percent(self.no_suggestions),
+            self.percent(self.only_wrong),
+        )
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct Breakdown {
+    bucket: String,
+    #[serde(flatten)]
+    accuracy: Accuracy,
+}
+
+impl Breakdown {
+    fn group<'a, F>(results: &[AccuracyResult<'a>], bucket: F) -> Vec<Breakdown>
+    where
+        F: Fn(&AccuracyResult<'a>) -> (usize, String),
+    {
+        let mut groups: BTreeMap<usize, Breakdown> = BTreeMap::new();
+
+        for result in results {
+            let (key, label) = bucket(result);
+            groups
+                .entry(key)
+                .or_insert_with(|| Breakdown {
+                    bucket: label,
+                    accuracy: Accuracy::default(),
+                })
+                .accuracy
+                .add(result);
+        }
+
+        groups.into_iter().map(|(_, v)| v).collect()
+    }
+}
+
+/// An edit the speller failed to undo, with how many failing words needed it.
+#[derive(Serialize, Debug, Clone)]
+struct Confusion {
+    #[serde(flatten)]
+    edit: Edit,
+    count: u32,
+}
+
+impl Confusion {
+    /// Aggregates the alignments of every result where the expected word was
+    /// not suggested, most common edits first.
+    fn collect<'a>(results: &[AccuracyResult<'a>]) -> Vec<Confusion> {
+        let mut counts: BTreeMap<Edit, u32> = BTreeMap::new();
+
+        for result in results.iter().filter(|r| r.position.is_none()) {
+            for edit in align(result.input, result.expected) {
+                *counts.entry(edit).or_insert(0) += 1;
+            }
+        }
+
+        let mut confusions = counts
+            .into_iter()
+            .map(|(edit, count)| Confusion { edit, count })
+            .collect::<Vec<_>>();
+        confusions.sort_by(|a, b| b.count.cmp(&a.count));
+        confusions
+    }
+}
+
+const CONFUSIONS_SHOWN: usize = 10;
+
+#[derive(Serialize, Default, Debug, Clone)]
+struct Summary {
+    #[serde(flatten)]
+    accuracy: Accuracy,
+    slowest_lookup: Time,
+    fastest_lookup: Time,
+    average_time: Time,
+    average_time_95pc: Time,
+    by_edit_distance: Vec<Breakdown>,
+    by_word_length: Vec<Breakdown>,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        writeln!(
+            f,
+            "{} [fast] {} [slow] {}",
+            self.accuracy, self.fastest_lookup, self.slowest_lookup
+        )?;
+
+        writeln!(f, "By edit distance:")?;
+        for b in self.by_edit_distance.iter() {
+            writeln!(
+                f,
+                "  {:>5} ({} words) {}",
+                b.bucket, b.accuracy.total_words, b.accuracy
+            )?;
+        }
+
+        writeln!(f, "By word length:")?;
+        for b in self.by_word_length.iter() {
+            writeln!(
+                f,
+                "  {:>5} ({} words) {}",
+                b.bucket, b.accuracy.total_words, b.accuracy
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Summary {
+    fn new<'a>(results: &[AccuracyResult<'a>]) -> Summary {
+        let mut summary = Summary::default();
+
+        results
+            .iter()
+            .for_each(|result| summary.accuracy.add(result));
+
+        summary.by_edit_distance =
+            Breakdown::group(results, |r| edit_distance_bucket(r.edit_distance));
+        summary.by_word_length =
+            Breakdown::group(results, |r| word_length_bucket(r.input.chars().count()));
+
+        summary.slowest_lookup = results
+            .iter()
+            .max_by(|x, y| x.time.cmp(&y.time))
+            .unwrap()
+            .time
+            .clone();
+        summary.fastest_lookup = results
+            .iter()
+            .min_by(|x, y| x.time.cmp(&y.time))
+            .unwrap()
+            .time
+            .clone();
+
+        summary
+    }
+}
+
+fn progress_bar(len: usize) -> ProgressBar {
+    let pb = ProgressBar::new(len as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{pos}/{len} [{percent}%] {wide_bar} {elapsed_precise}"),
+    );
+    pb
+}
+
+fn check_words<'a>(
+    archive: &Archive,
+    words: &'a [(String, String)],
+    cfg: &SpellerConfig,
+    checkpoint: Option<&checkpoint::Checkpoint>,
+) -> Vec<AccuracyResult<'a>> {
+    words
+        .par_iter()
+        .enumerate()
+        .progress_with(progress_bar(words.len()))
+        .map(|(index, (input, expected))| {
+            let edit_distance = levenshtein(input, expected);
+
+            if let Some(entry) = checkpoint.and_then(|c| c.resumed(index)) {
+                return AccuracyResult {
+                    input,
+                    expected,
+                    time: entry.time,
+                    suggestions: entry.suggestions.clone(),
+                    position: entry.position,
+                    edit_distance,
+                };
+            }
+
+            let now = Instant::now();
+            let suggestions = archive.suggest(&input, cfg);
+            let time = Time::from(now.elapsed());
+
+            let position = suggestions.iter().position(|x| x.value == expected);
+
+            let result = AccuracyResult {
+                input,
+                expected,
+                time,
+                suggestions,
+                position,
+                edit_distance,
+            };
+
+            if let Some(checkpoint) = checkpoint {
+                if let Err(e) = checkpoint.record(index, &result) {
+                    eprintln!("Failed to write checkpoint: {}", e);
+                }
+            }
+
+            result
+        })
+        .collect::<Vec<_>>()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let matches = App::new("divvunspell-accuracy")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .version(env!("CARGO_PKG_VERSION"))
+        .author("Brendan Molloy <brendan@bbqsrc.net>")
+        .about("Accuracy testing for DivvunSpell.")
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .takes_value(true)
+                .help("Provide JSON config file to override test defaults"),
+        )
+        .arg(
+            Arg::with_name("words")
+                .value_name("WORDS")
+                .help("The 'input -> expected' list in tab-delimited value file (TSV)"),
+        )
+        .arg(
+            Arg::with_name("zhfst")
+                .value_name("ARCHIVE")
+                .help("Use the given ZHFST file or CHFST directory (default: the configured default language)"),
+        )
+        .arg(
+            Arg::with_name("json-output")
+                .short("o")
+                .value_name("JSON-OUTPUT")
+                .help("The file path for the JSON report output"),
+        )
+        .arg(
+            Arg::with_name("html-output")
+                .long("html-output")
+                .value_name("HTML-OUTPUT")
+                .help("The file path for a self-contained HTML report"),
+        )
+        .arg(
+            Arg::with_name("weights-output")
+                .long("weights-output")
+                .value_name("TSV")
+                .help("Write every suggestion's weight by rank, and the expected word's, for calibration"),
+        )
+        .arg(
+            Arg::with_name("sqlite-output")
+                .long("sqlite-output")
+                .value_name("DB")
+                .help("Append the results of this run to the given SQLite database"),
+        )
+        .arg(
+            Arg::with_name("sweep")
+                .long("sweep")
+                .value_name("GRID")
+                .help(
+                    "Run the word list over a JSON grid of config values and report the tradeoffs",
+                ),
+        )
+        .arg(
+            Arg::with_name("detect")
+                .long("detect")
+                .conflicts_with("sweep")
+                .help("Measure detection on a gold corpus of 'token[TAB correction]' rows instead"),
+        )
+        .arg(
+            Arg::with_name("bench")
+                .long("bench")
+                .conflicts_with_all(&["sweep", "detect", "checkpoint"])
+                .help("Measure words per second at several thread counts instead of accuracy"),
+        )
+        .arg(
+            Arg::with_name("bench-threads")
+                .long("bench-threads")
+                .value_name("COUNTS")
+                .requires("bench")
+                .help("Comma-separated thread counts to measure (default: powers of two up to the number of cores)"),
+        )
+        .arg(
+            Arg::with_name("warmup")
+                .long("warmup")
+                .value_name("N")
+                .requires("bench")
+                .help("Passes over the list before each measured one (default: 1)"),
+        )
+        .arg(
+            Arg::with_name("checkpoint")
+                .long("checkpoint")
+                .value_name("CHECKPOINT")
+                .help("Periodically save per-word results to the given file"),
+        )
+        .arg(
+            Arg::with_name("resume")
+                .long("resume")
+                .requires("checkpoint")
+                .help("Skip words already present in the checkpoint file"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .short("j")
+                .long("threads")
+                .takes_value(true)
+                .help("Check words on at most this many threads (default: one per core)"),
+        )
+        .arg(
+            Arg::with_name("sample")
+                .long("sample")
+                .value_name("N")
+                .help("Check a reproducible sample of N words from the list"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("S")
+                .requires("sample")
+                .help("Seed for the sample (default: 1)"),
+        )
+        .arg(
+            Arg::with_name("stratify")
+                .long("stratify")
+                .requires("sample")
+                .conflicts_with("detect")
+                .possible_values(&["length", "distance"])
+                .help("Sample from each word length or edit distance bucket in proportion"),
+        )
+        .arg(
+            Arg::with_name("max-words")
+                .short("w")
+                .takes_value(true)
+                .help("Truncate typos list to max number of words specified"),
+        )
+        .get_matches();
+
+    if let Some(threads) = matches.value_of("threads") {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.parse()?)
+            .build_global()?;
+    }
+
+    let user_config = CliConfig::load()?;
+
+    let cfg: SpellerConfig = match matches.value_of("config") {
+        Some(path) => {
+            let file = std::fs::File::open(path)?;
+            serde_json::from_reader(file)?
+        }
+        None => user_config.speller.clone().unwrap_or_else(|| CFG.clone()),
+    };
+
+    let zhfst = matches.value_of("zhfst").map(str::to_string).or_else(|| {
+        user_config
+            .archive(None)
+            .map(|path| path.to_string_lossy().into_owned())
+    });
+
+    let before_load = memory::Rss::now();
+    let archive = match zhfst {
+        Some(ref path) => Archive::open(path)?,
+        None => {
+            eprintln!("No archive found for given path; aborting.");
+            std::process::exit(1);
+        }
+    };
+
+    let after_load = memory::Rss::now();
+
+    let max_words = matches
+        .value_of("max-words")
+        .and_then(|x| x.parse::<usize>().ok());
+    let sample_size = match matches.value_of("sample") {
+        Some(n) => Some(n.parse::<usize>()?),
+        None => None,
+    };
+    let seed = match matches.value_of("seed") {
+        Some(seed) => seed.parse::<u64>()?,
+        None => 1,
+    };
+
+    if matches.is_present("detect") {
+        let words = match matches.value_of("words") {
+            Some(path) => {
+                let words = detection::load_gold(path, max_words)?;
+                match sample_size {
+                    Some(n) => sample::sample(words, n, seed, |_| 0),
+                    None => words,
+                }
+            }
+            None => {
+                eprintln!("No word list for given path; aborting.");
+                std::process::exit(1);
+            }
+        };
+
+        return detection::run(&archive, &words, matches.value_of("json-output"));
+    }
+
+    let words = match matches.value_of("words") {
+        Some(path) => {
+            let words = load_words(path, max_words)?;
+            match sample_size {
+                Some(n) => {
+                    let strata = sample::Strata::from_arg(matches.value_of("stratify"));
+                    sample::sample_words(words, n, seed, strata)
+                }
+                None => words,
+            }
+        }
+        None => {
+            eprintln!("No word list for given path; aborting.");
+            std::process::exit(1);
+        }
+    };
+
+    if matches.is_present("bench") {
+        let thread_counts = match matches.value_of("bench-threads") {
+            Some(counts) => counts
+                .split(',')
+                .map(|n| n.trim().parse::<usize>())
+                .collect::<Result<Vec<_>, _>>()?,
+            None => throughput::default_thread_counts(),
+        };
+        let warmup = match matches.value_of("warmup") {
+            Some(n) => n.parse::<usize>()?,
+            None => 1,
+        };
+        return throughput::run(
+            &archive,
+            &words,
+            &cfg,
+            &thread_counts,
+            warmup,
+            matches.value_of("json-output"),
+        );
+    }
+
+    if let Some(path) = matches.value_of("sweep") {
+        let file = std::fs::File::open(path)?;
+        let grid: sweep::Grid = serde_json::from_reader(file)?;
+        return sweep::run(
+            &archive,
+            &words,
+            &cfg,
+            &grid,
+            matches.value_of("json-output"),
+        );
+    }
+
+    let checkpoint = match matches.value_of("checkpoint") {
+        Some(path) => Some(checkpoint::Checkpoint::open(
+            path,
+            matches.is_present("resume"),
+            &words,
+        )?),
+        None => None,
+    };
+
+    let start_time = Instant::now();
+    let results = check_words(&archive, &words, &cfg, checkpoint.as_ref());
+    let total_time = Time::from(start_time.elapsed());
+    let memory = memory::MemoryReport {
+        before_load,
+        after_load,
+        after_search: memory::Rss::now(),
+        archive: archive.memory_usage(),
+    };
+    let now_date = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let start_timestamp = Time::from(now_date);
+
+    let summary = Summary::new(&results);
+    print!("{}", summary);
+    print!("{}", memory);
+
+    let confusions = Confusion::collect(&results);
+    if !confusions.is_empty() {
+        println!("Most common unhandled edits:");
+        for c in confusions.iter().take(CONFUSIONS_SHOWN) {
+            println!("  {:>8} {}", c.edit, c.count);
+        }
+    }
+
+    if let Some(path) = matches.value_of("weights-output") {
+        println!("Writing suggestion weights…");
+        calibration::write_weights(path, &archive, &results)?;
+    }
+
+    let json_output = matches.value_of("json-output");
+    let html_output = matches.value_of("html-output");
+    let sqlite_output = matches.value_of("sqlite-output");
+
+    if json_output.is_some() || html_output.is_some() || sqlite_output.is_some() {
+        let report = Report {
+            metadata: archive.metadata(),
+            config: &cfg,
+            summary,
+            confusions,
+            results,
+            start_timestamp,
+            total_time,
+            memory,
+        };
+
+        if let Some(path) = json_output {
+            let output = std::fs::File::create(path)?;
+            println!("Writing JSON report…");
+            serde_json::to_writer_pretty(output, &report)?;
+        }
+
+        if let Some(path) = html_output {
+            let mut output = std::io::BufWriter::new(std::fs::File::create(path)?);
+            println!("Writing HTML report…");
+            html::write_report(&mut output, &report)?;
+        }
+
+        if let Some(path) = sqlite_output {
+            println!("Writing SQLite results…");
+            let run_id = sqlite::write_report(
+                path,
+                &report,
+                matches.value_of("words"),
+                zhfst.as_ref().map(|x| &**x),
+            )?;
+            println!("Recorded run {}", run_id);
+        }
+    };
+
+    println!("Done!");
+    Ok(())
+}
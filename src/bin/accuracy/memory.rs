@@ -0,0 +1,122 @@
+//! The process's memory use at each phase of a run, for judging whether a
+//! speller fits on a phone as much as whether it is right.
+//!
+//! Resident set sizes come from `/proc/self/status` on Linux. macOS only
+//! reports the peak, and other systems nothing, so those figures are `None`
+//! there. The archive's own figures, what its transducers take and the most
+//! a search allocated, come from `SpellerArchive::memory_usage` and are only
+//! known for ZHFST archives.
+
+use divvunspell::memory::MemoryUsage;
+use serde_derive::Serialize;
+
+/// Resident and peak resident bytes, as far as the system tells.
+#[derive(Debug, Default, Serialize, Clone, Copy)]
+pub struct Rss {
+    pub resident_bytes: Option<u64>,
+    pub peak_bytes: Option<u64>,
+}
+
+impl Rss {
+    #[cfg(target_os = "linux")]
+    pub fn now() -> Rss {
+        let status = match std::fs::read_to_string("/proc/self/status") {
+            Ok(v) => v,
+            Err(_) => return Rss::default(),
+        };
+
+        // Lines such as "VmRSS:	  123456 kB"
+        let field = |name: &str| {
+            status
+                .lines()
+                .find(|line| line.starts_with(name))
+                .and_then(|line| line[name.len()..].split_whitespace().next())
+                .and_then(|kb| kb.parse::<u64>().ok())
+                .map(|kb| kb * 1024)
+        };
+
+        Rss {
+            resident_bytes: field("VmRSS:"),
+            peak_bytes: field("VmHWM:"),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn now() -> Rss {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+            return Rss::default();
+        }
+
+        Rss {
+            resident_bytes: None,
+            peak_bytes: Some(usage.ru_maxrss as u64),
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    pub fn now() -> Rss {
+        Rss::default()
+    }
+}
+
+/// Memory before and after opening the archive and after checking the words.
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct MemoryReport {
+    pub before_load: Rss,
+    pub after_load: Rss,
+    pub after_search: Rss,
+    pub archive: Option<MemoryUsage>,
+}
+
+impl MemoryReport {
+    /// How much opening the archive added to the resident set.
+    pub fn load_bytes(&self) -> Option<u64> {
+        delta(
+            self.before_load.resident_bytes,
+            self.after_load.resident_bytes,
+        )
+    }
+
+    /// How much checking the words added to the resident set.
+    pub fn search_bytes(&self) -> Option<u64> {
+        delta(
+            self.after_load.resident_bytes,
+            self.after_search.resident_bytes,
+        )
+    }
+}
+
+fn delta(before: Option<u64>, after: Option<u64>) -> Option<u64> {
+    Some(after?.saturating_sub(before?))
+}
+
+impl std::fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let mb = |bytes: Option<u64>| match bytes {
+            Some(b) => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+            None => "unknown".to_string(),
+        };
+
+        writeln!(f, "Memory:")?;
+        writeln!(f, "  archive load:   {}", mb(self.load_bytes()))?;
+        writeln!(f, "  search:         {}", mb(self.search_bytes()))?;
+        writeln!(f, "  peak RSS:       {}", mb(self.after_search.peak_bytes))?;
+
+        if let Some(archive) = &self.archive {
+            writeln!(
+                f,
+                "  transducers:    {} mapped, {} on the heap",
+                mb(Some(archive.mapped_bytes as u64)),
+                mb(Some(archive.heap_bytes as u64))
+            )?;
+            writeln!(
+                f,
+                "  largest search: {}",
+                mb(Some(archive.search_peak_bytes as u64))
+            )?;
+        }
+
+        Ok(())
+    }
+}
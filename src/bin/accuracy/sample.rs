@@ -0,0 +1,99 @@
+//! Reproducible samples of a word list, for quick runs whose results can be
+//! compared from one invocation to the next.
+//!
+//! The same list, size and seed always give the same sample. A stratified
+//! sample takes from each word length or edit distance bucket, as the report
+//! groups them, in proportion to its share of the list, so that a small
+//! sample doesn't happen to be all short words. Sampled words keep their
+//! order in the list.
+
+use divvunspell::errmodel::align::levenshtein;
+use divvunspell::errmodel::typos::Rng;
+
+use super::{edit_distance_bucket, word_length_bucket};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strata {
+    None,
+    WordLength,
+    EditDistance,
+}
+
+impl Strata {
+    pub fn from_arg(arg: Option<&str>) -> Strata {
+        match arg {
+            Some("length") => Strata::WordLength,
+            Some("distance") => Strata::EditDistance,
+            _ => Strata::None,
+        }
+    }
+
+    fn bucket(&self, input: &str, expected: &str) -> usize {
+        match self {
+            Strata::None => 0,
+            Strata::WordLength => word_length_bucket(expected.chars().count()).0,
+            Strata::EditDistance => edit_distance_bucket(levenshtein(input, expected)).0,
+        }
+    }
+}
+
+/// At most `n` of `words`, chosen with `seed`, taken from each of `strata`
+/// in proportion.
+pub fn sample_words(
+    words: Vec<(String, String)>,
+    n: usize,
+    seed: u64,
+    strata: Strata,
+) -> Vec<(String, String)> {
+    let buckets: Vec<usize> = words
+        .iter()
+        .map(|(input, expected)| strata.bucket(input, expected))
+        .collect();
+    sample(words, n, seed, |i| buckets[i])
+}
+
+/// At most `n` of `items`, chosen with `seed`, taken from each group of
+/// items with the same `bucket` of their index in proportion to its size.
+pub fn sample<T>(items: Vec<T>, n: usize, seed: u64, bucket: impl Fn(usize) -> usize) -> Vec<T> {
+    if n >= items.len() {
+        return items;
+    }
+
+    let mut groups: Vec<(usize, Vec<usize>)> = vec![];
+    for i in 0..items.len() {
+        let key = bucket(i);
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, group)) => group.push(i),
+            None => groups.push((key, vec![i])),
+        }
+    }
+    groups.sort_by_key(|(key, _)| *key);
+
+    // Largest remainder, so that the quotas add up to `n` exactly
+    let total = items.len();
+    let mut quotas: Vec<usize> = groups.iter().map(|(_, g)| g.len() * n / total).collect();
+    let mut by_remainder: Vec<usize> = (0..groups.len()).collect();
+    by_remainder.sort_by_key(|&g| std::cmp::Reverse(groups[g].1.len() * n % total));
+    let short = n - quotas.iter().sum::<usize>();
+    for &g in by_remainder.iter().take(short) {
+        quotas[g] += 1;
+    }
+
+    let mut rng = Rng::new(seed);
+    let mut chosen = vec![false; total];
+    for ((_, group), quota) in groups.iter_mut().zip(quotas) {
+        // The first `quota` places of a Fisher-Yates shuffle
+        for i in 0..quota {
+            let j = i + rng.below(group.len() - i);
+            group.swap(i, j);
+            chosen[group[i]] = true;
+        }
+    }
+
+    items
+        .into_iter()
+        .zip(chosen)
+        .filter(|(_, chosen)| *chosen)
+        .map(|(item, _)| item)
+        .collect()
+}
@@ -0,0 +1,184 @@
+//! Appends accuracy runs to an SQLite database, so speller quality can be
+//! tracked over time by querying across runs.
+//!
+//! Every run gets a row in `runs`; its summaries, confusions and per-word
+//! results reference it by `run_id`. Tables are created when missing, so the
+//! same file can be passed to every run.
+
+use std::error::Error;
+
+use rusqlite::{params, Connection};
+
+use super::{Breakdown, Report, Time};
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    start_timestamp INTEGER NOT NULL,
+    total_time_ms REAL NOT NULL,
+    words_path TEXT,
+    zhfst_path TEXT,
+    locale TEXT NOT NULL,
+    title TEXT,
+    acceptor_id TEXT NOT NULL,
+    errmodel_id TEXT NOT NULL,
+    metadata TEXT NOT NULL,
+    config TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS summaries (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    grouping TEXT NOT NULL,
+    bucket TEXT NOT NULL,
+    total_words INTEGER NOT NULL,
+    first_position INTEGER NOT NULL,
+    top_five INTEGER NOT NULL,
+    any_position INTEGER NOT NULL,
+    no_suggestions INTEGER NOT NULL,
+    only_wrong INTEGER NOT NULL,
+    slowest_lookup_ms REAL,
+    fastest_lookup_ms REAL
+);
+
+CREATE TABLE IF NOT EXISTS confusions (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    edit TEXT NOT NULL,
+    count INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS results (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    input TEXT NOT NULL,
+    expected TEXT NOT NULL,
+    position INTEGER,
+    time_ms REAL NOT NULL,
+    edit_distance INTEGER NOT NULL,
+    suggestions TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS summaries_run_id ON summaries(run_id);
+CREATE INDEX IF NOT EXISTS confusions_run_id ON confusions(run_id);
+CREATE INDEX IF NOT EXISTS results_run_id ON results(run_id);
+"#;
+
+fn millis(time: &Time) -> f64 {
+    time.secs as f64 * 1000.0 + f64::from(time.subsec_nanos) / 1_000_000.0
+}
+
+/// Writes `report` as a new run, returning its id.
+pub fn write_report(
+    path: &str,
+    report: &Report<'_>,
+    words_path: Option<&str>,
+    zhfst_path: Option<&str>,
+) -> Result<i64, Box<dyn Error>> {
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+
+    // One transaction, so an interrupted write doesn't leave a partial run
+    let tx = conn.transaction()?;
+
+    let metadata = report.metadata;
+    let title = metadata.info.title.first().map(|t| t.value.clone());
+
+    tx.execute(
+        "INSERT INTO runs (start_timestamp, total_time_ms, words_path, zhfst_path, locale, title,
+            acceptor_id, errmodel_id, metadata, config)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            report.start_timestamp.millis() as i64,
+            millis(&report.total_time),
+            words_path,
+            zhfst_path,
+            metadata.info.locale,
+            title,
+            metadata.acceptor.id,
+            metadata.errmodel.id,
+            serde_json::to_string(metadata)?,
+            serde_json::to_string(report.config)?,
+        ],
+    )?;
+    let run_id = tx.last_insert_rowid();
+
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO summaries (run_id, grouping, bucket, total_words, first_position,
+                top_five, any_position, no_suggestions, only_wrong, slowest_lookup_ms,
+                fastest_lookup_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )?;
+
+        let summary = &report.summary;
+        let total = Breakdown {
+            bucket: "all".into(),
+            accuracy: summary.accuracy.clone(),
+        };
+        let rows = std::iter::once(("total", &total))
+            .chain(
+                summary
+                    .by_edit_distance
+                    .iter()
+                    .map(|b| ("edit_distance", b)),
+            )
+            .chain(summary.by_word_length.iter().map(|b| ("word_length", b)));
+
+        for (grouping, b) in rows {
+            // Lookup times are only known for the run as a whole
+            let (slowest, fastest) = if grouping == "total" {
+                (
+                    Some(millis(&summary.slowest_lookup)),
+                    Some(millis(&summary.fastest_lookup)),
+                )
+            } else {
+                (None, None)
+            };
+
+            let a = &b.accuracy;
+            stmt.execute(params![
+                run_id,
+                grouping,
+                b.bucket,
+                a.total_words,
+                a.first_position,
+                a.top_five,
+                a.any_position,
+                a.no_suggestions,
+                a.only_wrong,
+                slowest,
+                fastest,
+            ])?;
+        }
+    }
+
+    {
+        let mut stmt =
+            tx.prepare("INSERT INTO confusions (run_id, edit, count) VALUES (?1, ?2, ?3)")?;
+
+        for c in &report.confusions {
+            stmt.execute(params![run_id, c.edit.to_string(), c.count])?;
+        }
+    }
+
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO results (run_id, input, expected, position, time_ms, edit_distance,
+                suggestions)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+
+        for r in &report.results {
+            stmt.execute(params![
+                run_id,
+                r.input,
+                r.expected,
+                r.position.map(|p| p as i64),
+                millis(&r.time),
+                r.edit_distance as i64,
+                serde_json::to_string(&r.suggestions)?,
+            ])?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(run_id)
+}
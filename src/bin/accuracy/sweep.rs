@@ -0,0 +1,172 @@
+//! Grid search over `SpellerConfig` values, reporting the accuracy/latency
+//! tradeoff of each combination and which ones are Pareto-optimal.
+
+use std::error::Error;
+use std::time::Instant;
+
+use divvunspell::speller::SpellerConfig;
+use divvunspell::types::Weight;
+use serde_derive::{Deserialize, Serialize};
+
+use super::archive::Archive;
+use super::{check_words, Accuracy, Time};
+
+/// Values to try for each config field. An empty (or missing) list keeps the
+/// value from the base config, e.g.:
+///
+/// ```json
+/// { "max_weight": [5000, 10000, null], "beam": [null, 15.0], "n_best": [5, 10] }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct Grid {
+    #[serde(default)]
+    max_weight: Vec<Option<Weight>>,
+    #[serde(default)]
+    beam: Vec<Option<Weight>>,
+    #[serde(default)]
+    n_best: Vec<Option<usize>>,
+    #[serde(default)]
+    pool_start: Vec<usize>,
+    #[serde(default)]
+    pool_max: Vec<usize>,
+}
+
+fn or_base<T: Clone>(values: &[T], base: T) -> Vec<T> {
+    if values.is_empty() {
+        vec![base]
+    } else {
+        values.to_vec()
+    }
+}
+
+impl Grid {
+    fn configs(&self, base: &SpellerConfig) -> Vec<SpellerConfig> {
+        let mut out = vec![];
+
+        for max_weight in or_base(&self.max_weight, base.max_weight) {
+            for beam in or_base(&self.beam, base.beam) {
+                for n_best in or_base(&self.n_best, base.n_best) {
+                    for pool_start in or_base(&self.pool_start, base.pool_start) {
+                        for pool_max in or_base(&self.pool_max, base.pool_max) {
+                            out.push(SpellerConfig {
+                                max_weight,
+                                beam,
+                                n_best,
+                                pool_start,
+                                pool_max,
+                                ..base.clone()
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SweepResult {
+    config: SpellerConfig,
+    accuracy: Accuracy,
+    total_time: Time,
+    average_lookup_ms: f64,
+    pareto_optimal: bool,
+}
+
+impl SweepResult {
+    fn first_rate(&self) -> f64 {
+        self.accuracy.first_position as f64 / self.accuracy.total_words.max(1) as f64
+    }
+
+    fn dominates(&self, other: &SweepResult) -> bool {
+        let (a, b) = (self.first_rate(), other.first_rate());
+        let (la, lb) = (self.average_lookup_ms, other.average_lookup_ms);
+
+        a >= b && la <= lb && (a > b || la < lb)
+    }
+}
+
+fn mark_pareto_front(results: &mut [SweepResult]) {
+    for i in 0..results.len() {
+        let dominated = results
+            .iter()
+            .enumerate()
+            .any(|(j, other)| i != j && other.dominates(&results[i]));
+        results[i].pareto_optimal = !dominated;
+    }
+}
+
+pub fn run(
+    archive: &Archive,
+    words: &[(String, String)],
+    base: &SpellerConfig,
+    grid: &Grid,
+    json_output: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let configs = grid.configs(base);
+    let mut results = Vec::with_capacity(configs.len());
+
+    for (i, config) in configs.into_iter().enumerate() {
+        eprintln!(
+            "[{}] max_weight: {:?} beam: {:?} n_best: {:?} pool: {}/{}",
+            i + 1,
+            config.max_weight,
+            config.beam,
+            config.n_best,
+            config.pool_start,
+            config.pool_max
+        );
+
+        let start_time = Instant::now();
+        let word_results = check_words(archive, words, &config, None);
+        let total_time = Time::from(start_time.elapsed());
+
+        let mut accuracy = Accuracy::default();
+        word_results.iter().for_each(|r| accuracy.add(r));
+
+        let lookup_ms: f64 = word_results
+            .iter()
+            .map(|r| r.time.secs as f64 * 1000f64 + r.time.subsec_nanos as f64 / 1e6)
+            .sum();
+
+        results.push(SweepResult {
+            config,
+            accuracy,
+            total_time,
+            average_lookup_ms: lookup_ms / word_results.len().max(1) as f64,
+            pareto_optimal: false,
+        });
+    }
+
+    mark_pareto_front(&mut results);
+    results.sort_by(|a, b| {
+        a.average_lookup_ms
+            .partial_cmp(&b.average_lookup_ms)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    for r in results.iter() {
+        println!(
+            "{} {:>10.3}ms max_weight: {:?} beam: {:?} n_best: {:?} pool: {}/{} {}",
+            if r.pareto_optimal { "*" } else { " " },
+            r.average_lookup_ms,
+            r.config.max_weight,
+            r.config.beam,
+            r.config.n_best,
+            r.config.pool_start,
+            r.config.pool_max,
+            r.accuracy
+        );
+    }
+    println!("* = Pareto-optimal (top-1 accuracy vs. average lookup time)");
+
+    if let Some(path) = json_output {
+        let output = std::fs::File::create(path)?;
+        println!("Writing JSON sweep report…");
+        serde_json::to_writer_pretty(output, &results)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! Words per second at each of several thread counts, for sizing servers.
+//!
+//! Only the suggestion searches are timed: nothing is compared with the
+//! expected words or kept. Before each measured pass the list is searched
+//! `warmup` times on the same pool, so that the archive's pages are resident
+//! and the threads started.
+
+use std::error::Error;
+use std::time::Instant;
+
+use divvunspell::speller::parallel::thread_pool;
+use divvunspell::speller::SpellerConfig;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde_derive::Serialize;
+
+use super::archive::Archive;
+
+#[derive(Debug, Serialize)]
+pub struct ThroughputResult {
+    pub threads: usize,
+    pub words: usize,
+    pub seconds: f64,
+    pub words_per_second: f64,
+}
+
+/// The thread counts to try by default: powers of two up to the number of
+/// cores, and the number of cores itself.
+pub fn default_thread_counts() -> Vec<usize> {
+    let cores = rayon::current_num_threads().max(1);
+    let mut counts: Vec<usize> = (0..).map(|i| 1 << i).take_while(|&n| n < cores).collect();
+    counts.push(cores);
+    counts
+}
+
+pub fn run(
+    archive: &Archive,
+    words: &[(String, String)],
+    config: &SpellerConfig,
+    thread_counts: &[usize],
+    warmup: usize,
+    json_output: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let inputs: Vec<&str> = words.iter().map(|(input, _)| &**input).collect();
+    let mut results = Vec::with_capacity(thread_counts.len());
+
+    println!("{:>8} {:>10} {:>12}", "threads", "seconds", "words/sec");
+    for &threads in thread_counts {
+        let pool = thread_pool(threads)?;
+        let search = || {
+            inputs.par_iter().for_each(|word| {
+                archive.suggest(word, config);
+            })
+        };
+
+        for _ in 0..warmup {
+            pool.install(search);
+        }
+
+        let start = Instant::now();
+        pool.install(search);
+        let seconds = start.elapsed().as_secs_f64();
+
+        let result = ThroughputResult {
+            threads,
+            words: inputs.len(),
+            seconds,
+            words_per_second: inputs.len() as f64 / seconds,
+        };
+        println!(
+            "{:>8} {:>10.3} {:>12.1}",
+            result.threads, result.seconds, result.words_per_second
+        );
+        results.push(result);
+    }
+
+    if let Some(path) = json_output {
+        let output = std::fs::File::create(path)?;
+        println!("Writing JSON report…");
+        serde_json::to_writer_pretty(output, &results)?;
+    }
+
+    Ok(())
+}
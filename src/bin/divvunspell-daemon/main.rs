@@ -0,0 +1,364 @@
+#![cfg(feature = "binaries")]
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{App, AppSettings, Arg, ArgMatches};
+
+use divvunspell::archive::repository::SpellerRepository;
+use divvunspell::archive::SpellerArchive;
+use divvunspell::config::CliConfig;
+use divvunspell::daemon::{
+    default_socket_path, read_message, write_message, DaemonError, Request, Response,
+};
+use divvunspell::speller::document_cache::DocumentCache;
+use divvunspell::speller::issue::SpellingIssue;
+use divvunspell::speller::snapshot::SpellerSnapshot;
+use divvunspell::speller::suggestion_cache::{CachedLanguage, SuggestionCaches};
+use divvunspell::speller::SpellerConfig;
+
+#[path = "../shared/cli.rs"]
+mod cli;
+
+use cli::{load_repository, parse_arg};
+
+#[cfg(windows)]
+mod pipe;
+
+const DEFAULT_SUGGESTIONS: usize = 5;
+const DEFAULT_CACHE_SIZE: usize = 10_000;
+const CACHE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+struct State {
+    repository: SpellerRepository,
+    caches: Option<Arc<SuggestionCaches>>,
+    /// What was found in the last version of each document clients named.
+    documents: DocumentCache,
+    cfg: SpellerConfig,
+}
+
+impl State {
+    /// An exact match ignoring case, or else the closest less specific tag.
+    /// Its archive is opened if it isn't.
+    fn language(&self, tag: &str) -> Result<CachedLanguage, String> {
+        CachedLanguage::open(&self.repository, self.caches.as_deref(), tag)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Requests may shorten the daemon's time budget, not extend it.
+    fn config(&self, n_best: Option<usize>, time_budget_ms: Option<u64>) -> SpellerConfig {
+        let time_budget_ms = match (time_budget_ms, self.cfg.time_budget_ms) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        SpellerConfig {
+            n_best: n_best.or(self.cfg.n_best),
+            time_budget_ms,
+            ..self.cfg.clone()
+        }
+    }
+}
+
+/// The time budget covers the whole text; misspellings found after it runs
+/// out are reported without suggestions.
+fn check(lang: &CachedLanguage, text: &str, cfg: &SpellerConfig) -> Vec<SpellingIssue> {
+    let deadline = cfg
+        .time_budget_ms
+        .map(|ms| Instant::now() + Duration::from_millis(ms));
+
+    lang.check_text(text, cfg, deadline)
+}
+
+fn respond(state: &State, request: Request) -> Result<Response, String> {
+    Ok(match request {
+        Request::Languages => Response::Languages {
+            languages: state.repository.tags().map(str::to_string).collect(),
+        },
+        Request::IsCorrect { lang, word } => Response::IsCorrect {
+            correct: state.language(&lang)?.archive.speller().is_correct(&word),
+        },
+        Request::Suggest {
+            lang,
+            word,
+            n_best,
+            time_budget_ms,
+        } => {
+            let cfg = state.config(n_best, time_budget_ms);
+            Response::Suggest {
+                suggestions: state.language(&lang)?.suggest(&word, &cfg),
+            }
+        }
+        Request::Check {
+            lang,
+            text,
+            n_best,
+            time_budget_ms,
+            document,
+        } => {
+            let cfg = state.config(n_best, time_budget_ms);
+            let lang = state.language(&lang)?;
+            let errors = match document {
+                Some(document) => {
+                    // Names are per archive, so a reloaded archive that has
+                    // changed starts its documents over.
+                    let document = format!(
+                        "{}\t{:x}\t{}",
+                        lang.tag,
+                        lang.archive.fingerprint(),
+                        document
+                    );
+                    state
+                        .documents
+                        .check(lang.archive.speller(), &document, &text, &cfg)
+                }
+                None => check(&lang, &text, &cfg),
+            };
+            Response::Check { errors }
+        }
+    })
+}
+
+/// Answers requests until the client disconnects. A request that can't be
+/// parsed ends the connection, as the stream can't be resynchronised.
+fn serve<S: Read + Write>(state: &State, mut stream: S) {
+    loop {
+        let response = match read_message::<_, Request>(&mut stream) {
+            Ok(Some(request)) => {
+                respond(state, request).unwrap_or_else(|message| Response::Error { message })
+            }
+            Ok(None) => return,
+            Err(DaemonError::Io(_)) => return,
+            Err(e) => {
+                let _ = write_message(
+                    &mut stream,
+                    &Response::Error {
+                        message: e.to_string(),
+                    },
+                );
+                return;
+            }
+        };
+
+        if write_message(&mut stream, &response).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn listen(state: Arc<State>, path: PathBuf) {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            eprintln!("{}: a daemon is already listening", path.display());
+            std::process::exit(1);
+        }
+        // Left behind by a daemon that did not shut down cleanly.
+        let _ = std::fs::remove_file(&path);
+    }
+
+    let listener = match UnixListener::bind(&path) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    eprintln!("Listening on {}", path.display());
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let state = state.clone();
+                thread::spawn(move || serve(&state, stream));
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn listen(state: Arc<State>, path: PathBuf) {
+    eprintln!("Listening on {}", path.display());
+
+    loop {
+        match pipe::accept(&path) {
+            Ok(stream) => {
+                let state = state.clone();
+                thread::spawn(move || serve(&state, stream));
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Restores the archive's snapshot from `dir`, where it is kept as
+/// `TAG.snapshot`, or takes and writes one if there is none for it yet.
+fn restore_snapshot(dir: &Path, tag: &str, archive: &SpellerArchive) {
+    let path = dir.join(format!("{}.snapshot", tag));
+    let speller = archive.speller();
+    let fingerprint = archive.fingerprint();
+
+    match SpellerSnapshot::load(&path, fingerprint) {
+        Ok(Some(snapshot)) => snapshot.restore(&speller),
+        Ok(None) => {
+            let snapshot = SpellerSnapshot::take(fingerprint, &speller);
+            if let Err(e) = snapshot.save(&path) {
+                eprintln!("{}: {}", path.display(), e);
+            }
+        }
+        Err(e) => eprintln!("{}: {}", path.display(), e),
+    }
+}
+
+/// Restores each archive's snapshot from `--snapshot-dir` as it is opened.
+fn set_up_snapshots(repository: &mut SpellerRepository, matches: &ArgMatches) {
+    let snapshots = matches.value_of("snapshot-dir").map(PathBuf::from);
+    if let Some(dir) = &snapshots {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("{}: {}", dir.display(), e);
+            std::process::exit(1);
+        }
+    }
+    repository.set_on_load(Box::new(move |tag, archive| {
+        if let Some(dir) = &snapshots {
+            restore_snapshot(dir, tag, archive);
+        }
+        eprintln!("Loaded {}", tag);
+    }));
+}
+
+fn main() {
+    let matches = App::new("divvunspell-daemon")
+        .setting(AppSettings::ColoredHelp)
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Keeps spellers loaded and serves requests over a local socket")
+        .arg(
+            Arg::with_name("archive")
+                .short("a")
+                .long("archive")
+                .value_name("TAG=PATH")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Serve a ZHFST archive for a language tag (default: all configured archives)"),
+        )
+        .arg(
+            Arg::with_name("socket")
+                .short("s")
+                .long("socket")
+                .value_name("PATH")
+                .help("Socket or pipe to listen on (default: $DIVVUNSPELL_SOCKET, or one in $XDG_RUNTIME_DIR)"),
+        )
+        .arg(
+            Arg::with_name("nbest")
+                .short("n")
+                .long("nbest")
+                .value_name("N")
+                .help("Default number of suggestions per word (default 5)"),
+        )
+        .arg(
+            Arg::with_name("time-budget")
+                .long("time-budget")
+                .value_name("MS")
+                .help("Longest time a request may spend on suggestions"),
+        )
+        .arg(
+            Arg::with_name("suggestion-cache")
+                .long("suggestion-cache")
+                .value_name("DIR")
+                .help("Keep suggestions for frequent misspellings in DIR between runs"),
+        )
+        .arg(
+            Arg::with_name("cache-size")
+                .long("cache-size")
+                .value_name("N")
+                .requires("suggestion-cache")
+                .help("Words kept in each language's suggestion cache (default 10000)"),
+        )
+        .arg(
+            Arg::with_name("snapshot-dir")
+                .long("snapshot-dir")
+                .value_name("DIR")
+                .help("Restore each archive's case table from a snapshot in DIR on loading, writing one if there is none"),
+        )
+        .arg(
+            Arg::with_name("memory-budget")
+                .long("memory-budget")
+                .value_name("MB")
+                .help("Open archives when first asked for, closing the least recently used when those open take more than MB"),
+        )
+        .get_matches();
+
+    let config = match CliConfig::load() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}: {}", CliConfig::path().unwrap_or_default().display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut repository = load_repository(&matches, &config);
+    set_up_snapshots(&mut repository, &matches);
+
+    let defaults = config.speller_config();
+    let cfg = SpellerConfig {
+        n_best: parse_arg(&matches, "nbest")
+            .or(defaults.n_best)
+            .or(Some(DEFAULT_SUGGESTIONS)),
+        time_budget_ms: parse_arg(&matches, "time-budget").or(defaults.time_budget_ms),
+        ..defaults
+    };
+
+    let caches = matches.value_of("suggestion-cache").map(|dir| {
+        let capacity = parse_arg(&matches, "cache-size").unwrap_or(DEFAULT_CACHE_SIZE);
+        let mut caches = match SuggestionCaches::new(dir, &cfg, capacity) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("{}: {}", dir, e);
+                std::process::exit(1);
+            }
+        };
+        caches.set_on_open(Box::new(|tag, path, cache| match cache {
+            Ok(cache) => eprintln!("Cached {}: {} words", tag, cache.len()),
+            Err(e) => eprintln!("{}: {}, starting without a cache", path.display(), e),
+        }));
+        Arc::new(caches)
+    });
+
+    // Without a budget nothing is ever closed, so open everything now and
+    // fail at startup rather than on the first request
+    if matches.value_of("memory-budget").is_none() {
+        if let Err(e) = repository.load_all() {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let path = matches
+        .value_of("socket")
+        .map(PathBuf::from)
+        .unwrap_or_else(default_socket_path);
+
+    let state = Arc::new(State {
+        repository,
+        caches,
+        documents: DocumentCache::default(),
+        cfg,
+    });
+    if let Some(caches) = &state.caches {
+        caches
+            .clone()
+            .save_every(CACHE_SAVE_INTERVAL, |tag, e| eprintln!("{}: {}", tag, e));
+    }
+
+    listen(state, path);
+}
@@ -0,0 +1,50 @@
+//! Named pipe server for Windows, which has no Unix domain sockets in std.
+
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io;
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::io::FromRawHandle;
+use std::path::Path;
+use std::ptr::null_mut;
+
+use winapi::shared::winerror::ERROR_PIPE_CONNECTED;
+use winapi::um::errhandlingapi::GetLastError;
+use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+use winapi::um::namedpipeapi::{ConnectNamedPipe, CreateNamedPipeW};
+use winapi::um::winbase::{
+    PIPE_ACCESS_DUPLEX, PIPE_READMODE_BYTE, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+};
+
+const BUFFER_SIZE: u32 = 64 * 1024;
+
+/// Creates a new instance of the pipe and waits for a client to open it.
+pub fn accept(path: &Path) -> io::Result<File> {
+    let name: Vec<u16> = OsStr::new(path).encode_wide().chain(Some(0)).collect();
+
+    unsafe {
+        let handle = CreateNamedPipeW(
+            name.as_ptr(),
+            PIPE_ACCESS_DUPLEX,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT,
+            PIPE_UNLIMITED_INSTANCES,
+            BUFFER_SIZE,
+            BUFFER_SIZE,
+            0,
+            null_mut(),
+        );
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+
+        // A client that connected between creation and this call is
+        // reported as an error, but is ready to use.
+        if ConnectNamedPipe(handle, null_mut()) == 0 && GetLastError() != ERROR_PIPE_CONNECTED {
+            let err = io::Error::last_os_error();
+            CloseHandle(handle);
+            return Err(err);
+        }
+
+        Ok(File::from_raw_handle(handle as _))
+    }
+}
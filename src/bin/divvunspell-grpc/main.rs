@@ -0,0 +1,261 @@
+#![cfg(all(feature = "binaries", feature = "grpc"))]
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::{App, AppSettings, Arg};
+use tokio::sync::mpsc;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+use divvunspell::archive::repository::{ArchiveHandle, RepositoryError, SpellerRepository};
+use divvunspell::asynchronous::{self, AsyncError};
+use divvunspell::config::CliConfig;
+use divvunspell::speller::SpellerConfig;
+
+#[path = "../shared/cli.rs"]
+mod cli;
+
+use cli::{load_repository, parse_arg};
+
+mod proto {
+    tonic::include_proto!("divvunspell");
+}
+
+use proto::speller_server::{Speller, SpellerServer};
+use proto::{
+    CheckRequest, CheckResponse, Misspelling, SuggestRequest, SuggestResponse, Suggestion,
+};
+
+const DEFAULT_BIND: &str = "127.0.0.1:50051";
+const DEFAULT_SUGGESTIONS: usize = 5;
+/// Parts of a `CheckDocument` stream checked ahead of the client reading
+/// their results.
+const STREAM_BUFFER: usize = 4;
+
+struct State {
+    repository: SpellerRepository,
+    cfg: SpellerConfig,
+}
+
+impl State {
+    /// An exact match ignoring case, or else the closest less specific tag.
+    fn archive(&self, tag: &str) -> Result<ArchiveHandle, Status> {
+        self.repository.get(tag).map_err(|e| match e {
+            RepositoryError::UnknownLanguage(_) => Status::not_found(e.to_string()),
+            RepositoryError::OpenFailed { .. } => Status::unavailable(e.to_string()),
+        })
+    }
+
+    /// Zero leaves the server's default. Requests may shorten the server's
+    /// time budget, not extend it.
+    fn config(&self, n_best: u32, time_budget_ms: u64) -> SpellerConfig {
+        let n_best = Some(n_best as usize).filter(|n| *n > 0);
+        let time_budget_ms = match (
+            Some(time_budget_ms).filter(|ms| *ms > 0),
+            self.cfg.time_budget_ms,
+        ) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        SpellerConfig {
+            n_best: n_best.or(self.cfg.n_best),
+            time_budget_ms,
+            ..self.cfg.clone()
+        }
+    }
+
+    /// The time budget covers the whole text; misspellings found after it
+    /// runs out are reported without suggestions.
+    async fn check(&self, req: CheckRequest) -> Result<CheckResponse, Status> {
+        let speller = self.archive(&req.lang)?.speller();
+        let cfg = self.config(req.n_best, req.time_budget_ms);
+        let deadline = cfg
+            .time_budget_ms
+            .map(|ms| Instant::now() + Duration::from_millis(ms));
+
+        let errors = asynchronous::check_text(speller, &req.text, &cfg, deadline)
+            .await
+            .map_err(internal)?
+            .into_iter()
+            .map(|issue| Misspelling {
+                start: issue.offset as u32,
+                end: (issue.offset + issue.word.len()) as u32,
+                word: issue.word.to_string(),
+                suggestions: to_proto(issue.suggestions),
+            })
+            .collect();
+
+        Ok(CheckResponse { errors })
+    }
+}
+
+fn to_proto(suggestions: Vec<divvunspell::speller::suggestion::Suggestion>) -> Vec<Suggestion> {
+    suggestions
+        .into_iter()
+        .map(|s| Suggestion {
+            value: s.value.to_string(),
+            weight: s.weight,
+        })
+        .collect()
+}
+
+fn internal(e: AsyncError) -> Status {
+    Status::internal(e.to_string())
+}
+
+struct Service {
+    state: Arc<State>,
+}
+
+#[tonic::async_trait]
+impl Speller for Service {
+    async fn check(
+        &self,
+        request: Request<CheckRequest>,
+    ) -> Result<Response<CheckResponse>, Status> {
+        self.state
+            .check(request.into_inner())
+            .await
+            .map(Response::new)
+    }
+
+    async fn suggest(
+        &self,
+        request: Request<SuggestRequest>,
+    ) -> Result<Response<SuggestResponse>, Status> {
+        let req = request.into_inner();
+        let speller = self.state.archive(&req.lang)?.speller();
+        let cfg = self.state.config(req.n_best, req.time_budget_ms);
+
+        let check = asynchronous::check(speller, &req.word, &cfg, None)
+            .await
+            .map_err(internal)?;
+
+        Ok(Response::new(SuggestResponse {
+            correct: check.correct,
+            suggestions: to_proto(check.suggestions),
+        }))
+    }
+
+    type CheckDocumentStream = mpsc::Receiver<Result<CheckResponse, Status>>;
+
+    /// The stream ends at the first part that fails, after reporting it.
+    async fn check_document(
+        &self,
+        request: Request<Streaming<CheckRequest>>,
+    ) -> Result<Response<Self::CheckDocumentStream>, Status> {
+        let state = self.state.clone();
+        let mut parts = request.into_inner();
+        let (mut tx, rx) = mpsc::channel(STREAM_BUFFER);
+
+        tokio::spawn(async move {
+            loop {
+                let result = match parts.message().await {
+                    Ok(Some(req)) => state.check(req).await,
+                    Ok(None) => return,
+                    Err(e) => Err(e),
+                };
+
+                let failed = result.is_err();
+                if tx.send(result).await.is_err() || failed {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(rx))
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let matches = App::new("divvunspell-grpc")
+        .setting(AppSettings::ColoredHelp)
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("gRPC spellchecking server")
+        .arg(
+            Arg::with_name("archive")
+                .short("a")
+                .long("archive")
+                .value_name("TAG=PATH")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Serve a ZHFST archive for a BCP-47 language tag (default: all configured archives)"),
+        )
+        .arg(
+            Arg::with_name("bind")
+                .short("b")
+                .long("bind")
+                .value_name("ADDR")
+                .help("Address to listen on (default 127.0.0.1:50051)"),
+        )
+        .arg(
+            Arg::with_name("nbest")
+                .short("n")
+                .long("nbest")
+                .value_name("N")
+                .help("Default number of suggestions per word (default 5)"),
+        )
+        .arg(
+            Arg::with_name("time-budget")
+                .long("time-budget")
+                .value_name("MS")
+                .help("Longest time a request may spend on suggestions"),
+        )
+        .arg(
+            Arg::with_name("preload")
+                .long("preload")
+                .help("Copy archives into memory at startup, on huge pages where Linux allows"),
+        )
+        .arg(
+            Arg::with_name("paranoid")
+                .long("paranoid")
+                .help("Bounds check every transducer table read, for archives that aren't trusted"),
+        )
+        .get_matches();
+
+    let config = match CliConfig::load() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}: {}", CliConfig::path().unwrap_or_default().display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut repository = load_repository(&matches, &config);
+
+    repository.set_on_load(Box::new(|tag, _| eprintln!("Loaded {}", tag)));
+    if let Err(e) = repository.load_all() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    let defaults = config.speller_config();
+    let cfg = SpellerConfig {
+        n_best: parse_arg(&matches, "nbest")
+            .or(defaults.n_best)
+            .or(Some(DEFAULT_SUGGESTIONS)),
+        time_budget_ms: parse_arg(&matches, "time-budget").or(defaults.time_budget_ms),
+        ..defaults
+    };
+
+    let bind: SocketAddr =
+        parse_arg(&matches, "bind").unwrap_or_else(|| DEFAULT_BIND.parse().unwrap());
+    let service = Service {
+        state: Arc::new(State { repository, cfg }),
+    };
+
+    eprintln!("Listening on {}", bind);
+
+    if let Err(e) = Server::builder()
+        .add_service(SpellerServer::new(service))
+        .serve(bind)
+        .await
+    {
+        eprintln!("{}: {}", bind, e);
+        std::process::exit(1);
+    }
+}
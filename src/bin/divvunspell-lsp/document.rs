@@ -0,0 +1,218 @@
+use lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+use std::collections::HashMap;
+
+/// A misspelled word, in UTF-16 code units from the start of its line as
+/// LSP positions are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Misspelling {
+    pub start: u64,
+    pub end: u64,
+    pub word: String,
+}
+
+/// An open document, kept as lines so that an edit only rechecks the lines it
+/// touches.
+pub struct Document {
+    pub version: i64,
+    lines: Vec<String>,
+    misspellings: Vec<Vec<Misspelling>>,
+}
+
+/// The byte offset of UTF-16 column `character` in `line`, clamped to its end.
+fn byte_offset(line: &str, character: u64) -> usize {
+    let mut units = 0;
+
+    for (i, c) in line.char_indices() {
+        if units >= character {
+            return i;
+        }
+        units += c.len_utf16() as u64;
+    }
+
+    line.len()
+}
+
+impl Document {
+    pub fn new<F>(text: &str, version: i64, check_line: F) -> Document
+    where
+        F: Fn(&str) -> Vec<Misspelling>,
+    {
+        let lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+        let misspellings = lines.iter().map(|line| check_line(line)).collect();
+
+        Document {
+            version,
+            lines,
+            misspellings,
+        }
+    }
+
+    /// Applies an edit, rechecking only the lines it replaced.
+    pub fn apply_change<F>(&mut self, change: &TextDocumentContentChangeEvent, check_line: F)
+    where
+        F: Fn(&str) -> Vec<Misspelling>,
+    {
+        let range = match change.range {
+            Some(range) => range,
+            None => {
+                self.replace(&change.text, check_line);
+                return;
+            }
+        };
+
+        let last = self.lines.len() - 1;
+        let first_line = (range.start.line as usize).min(last);
+        let last_line = (range.end.line as usize).min(last).max(first_line);
+
+        let prefix = {
+            let line = &self.lines[first_line];
+            &line[..byte_offset(line, range.start.character)]
+        };
+        let suffix = {
+            let line = &self.lines[last_line];
+            let end = if range.end.line as usize > last {
+                line.len()
+            } else {
+                byte_offset(line, range.end.character)
+            };
+            &line[end..]
+        };
+
+        let text = format!("{}{}{}", prefix, change.text, suffix);
+        let lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+        let misspellings: Vec<Vec<Misspelling>> =
+            lines.iter().map(|line| check_line(line)).collect();
+
+        self.lines.splice(first_line..=last_line, lines);
+        self.misspellings
+            .splice(first_line..=last_line, misspellings);
+    }
+
+    /// Replaces the whole text, as clients without incremental sync send
+    /// it, rechecking only lines that weren't in it before.
+    fn replace<F>(&mut self, text: &str, check_line: F)
+    where
+        F: Fn(&str) -> Vec<Misspelling>,
+    {
+        let lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+        let misspellings = {
+            let known: HashMap<&str, &Vec<Misspelling>> = self
+                .lines
+                .iter()
+                .map(String::as_str)
+                .zip(&self.misspellings)
+                .collect();
+            lines
+                .iter()
+                .map(|line| match known.get(line.as_str()) {
+                    Some(found) => (*found).clone(),
+                    None => check_line(line),
+                })
+                .collect()
+        };
+
+        self.lines = lines;
+        self.misspellings = misspellings;
+    }
+
+    /// Every misspelling with the line it is on.
+    pub fn misspellings(&self) -> impl Iterator<Item = (u64, &Misspelling)> {
+        self.misspellings
+            .iter()
+            .enumerate()
+            .flat_map(|(line, found)| found.iter().map(move |m| (line as u64, m)))
+    }
+
+    /// The misspelling covering `range`, if there is one.
+    pub fn misspelling_at(&self, range: &Range) -> Option<&Misspelling> {
+        self.misspellings
+            .get(range.start.line as usize)?
+            .iter()
+            .find(|m| m.start <= range.start.character && range.end.character <= m.end)
+    }
+}
+
+pub fn misspelling_range(line: u64, misspelling: &Misspelling) -> Range {
+    Range::new(
+        Position::new(line, misspelling.start),
+        Position::new(line, misspelling.end),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_line(line: &str) -> Vec<Misspelling> {
+        let mut offset = 0;
+        let mut found = vec![];
+
+        for word in line.split(' ') {
+            let len = word.encode_utf16().count() as u64;
+            if word.contains('x') {
+                found.push(Misspelling {
+                    start: offset,
+                    end: offset + len,
+                    word: word.to_string(),
+                });
+            }
+            offset += len + 1;
+        }
+
+        found
+    }
+
+    fn words(doc: &Document) -> Vec<(u64, &str)> {
+        doc.misspellings()
+            .map(|(line, m)| (line, &*m.word))
+            .collect()
+    }
+
+    #[test]
+    fn incremental_edits() {
+        let mut doc = Document::new("ok xa ok\nšox ok\nok", 1, check_line);
+        assert_eq!(words(&doc), vec![(0, "xa"), (1, "šox")]);
+
+        // Fix "xa" and split the first line in two.
+        doc.apply_change(
+            &TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(0, 3), Position::new(0, 6))),
+                range_length: None,
+                text: "ab\nbx ".to_string(),
+            },
+            check_line,
+        );
+        assert_eq!(doc.lines, vec!["ok ab", "bx ok", "šox ok", "ok"]);
+        assert_eq!(words(&doc), vec![(1, "bx"), (2, "šox")]);
+
+        // Join lines, with a range measured in UTF-16 past the "š".
+        doc.apply_change(
+            &TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(1, 5), Position::new(2, 3))),
+                range_length: None,
+                text: String::new(),
+            },
+            check_line,
+        );
+        assert_eq!(doc.lines, vec!["ok ab", "bx ok ok", "ok"]);
+        assert_eq!(
+            doc.misspelling_at(&Range::new(Position::new(1, 0), Position::new(1, 2))),
+            Some(&Misspelling {
+                start: 0,
+                end: 2,
+                word: "bx".to_string()
+            })
+        );
+
+        // Replace the whole text, keeping one line and changing another.
+        doc.apply_change(
+            &TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "bx ok ok\nxo".to_string(),
+            },
+            check_line,
+        );
+        assert_eq!(words(&doc), vec![(0, "bx"), (1, "xo")]);
+    }
+}
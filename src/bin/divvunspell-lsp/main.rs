@@ -0,0 +1,315 @@
+#![cfg(feature = "binaries")]
+
+mod document;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::{App, AppSettings, Arg};
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationType, PublishDiagnostics,
+};
+use lsp_types::request::{CodeActionRequest, Request as RequestType};
+use lsp_types::{
+    CodeAction, CodeActionOrCommand, CodeActionParams, CodeActionProviderCapability, Diagnostic,
+    DiagnosticSeverity, PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Url, WorkspaceEdit,
+};
+
+use divvunspell::archive::repository::SpellerRepository;
+use divvunspell::config::CliConfig;
+use divvunspell::memory::MemoryPolicy;
+use divvunspell::speller::{Speller, SpellerConfig};
+use divvunspell::tokenizer::Tokenize;
+use divvunspell::transducer::HfstTransducer;
+
+use document::{misspelling_range, Document, Misspelling};
+
+const DIAGNOSTIC_SOURCE: &str = "divvunspell";
+const DEFAULT_SUGGESTIONS: usize = 5;
+
+struct Server {
+    speller: Arc<Speller<HfstTransducer>>,
+    cfg: SpellerConfig,
+    documents: HashMap<Url, Document>,
+}
+
+impl Server {
+    fn check_line(&self, line: &str) -> Vec<Misspelling> {
+        let mut offset = 0;
+        let mut found = vec![];
+
+        for (_, token) in line.word_bound_indices() {
+            let start = offset;
+            offset += token.encode_utf16().count() as u64;
+
+            if token.chars().any(char::is_alphabetic) && !self.speller.clone().is_correct(token) {
+                found.push(Misspelling {
+                    start,
+                    end: offset,
+                    word: token.to_string(),
+                });
+            }
+        }
+
+        found
+    }
+
+    fn diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        let doc = match self.documents.get(uri) {
+            Some(v) => v,
+            None => return vec![],
+        };
+
+        doc.misspellings()
+            .map(|(line, m)| Diagnostic {
+                range: misspelling_range(line, m),
+                severity: Some(DiagnosticSeverity::Information),
+                source: Some(DIAGNOSTIC_SOURCE.to_string()),
+                message: format!("Unknown word: {}", m.word),
+                ..Diagnostic::default()
+            })
+            .collect()
+    }
+
+    fn publish(&self, connection: &Connection, uri: Url) -> Result<(), Box<dyn Error>> {
+        let params = PublishDiagnosticsParams::new(uri.clone(), self.diagnostics(&uri));
+        let not = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        connection.sender.send(not.into())?;
+        Ok(())
+    }
+
+    fn handle_notification(
+        &mut self,
+        connection: &Connection,
+        not: Notification,
+    ) -> Result<(), Box<dyn Error>> {
+        let not = match not.extract::<<DidOpenTextDocument as NotificationType>::Params>(
+            DidOpenTextDocument::METHOD,
+        ) {
+            Ok(params) => {
+                let item = params.text_document;
+                let doc = Document::new(&item.text, item.version, |l| self.check_line(l));
+                self.documents.insert(item.uri.clone(), doc);
+                return self.publish(connection, item.uri);
+            }
+            Err(not) => not,
+        };
+
+        let not = match not.extract::<<DidChangeTextDocument as NotificationType>::Params>(
+            DidChangeTextDocument::METHOD,
+        ) {
+            Ok(params) => {
+                let uri = params.text_document.uri;
+                let mut doc = match self.documents.remove(&uri) {
+                    Some(v) => v,
+                    None => return Ok(()),
+                };
+
+                for change in params.content_changes.iter() {
+                    doc.apply_change(change, |l| self.check_line(l));
+                }
+                if let Some(version) = params.text_document.version {
+                    doc.version = version;
+                }
+
+                self.documents.insert(uri.clone(), doc);
+                return self.publish(connection, uri);
+            }
+            Err(not) => not,
+        };
+
+        if let Ok(params) = not.extract::<<DidCloseTextDocument as NotificationType>::Params>(
+            DidCloseTextDocument::METHOD,
+        ) {
+            let uri = params.text_document.uri;
+            self.documents.remove(&uri);
+            // Clear the closed document's diagnostics in the client.
+            return self.publish(connection, uri);
+        }
+
+        Ok(())
+    }
+
+    /// Offers each suggestion for the misspellings in range as a quick fix.
+    fn code_actions(&self, params: CodeActionParams) -> Vec<CodeActionOrCommand> {
+        let uri = params.text_document.uri;
+        let doc = match self.documents.get(&uri) {
+            Some(v) => v,
+            None => return vec![],
+        };
+
+        let mut actions = vec![];
+
+        for diagnostic in params.context.diagnostics.iter() {
+            if diagnostic.source.as_deref() != Some(DIAGNOSTIC_SOURCE) {
+                continue;
+            }
+
+            let misspelling = match doc.misspelling_at(&diagnostic.range) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            let suggestions = self
+                .speller
+                .clone()
+                .suggest_with_config(&misspelling.word, &self.cfg);
+
+            for suggestion in suggestions.iter() {
+                let mut changes = HashMap::new();
+                changes.insert(
+                    uri.clone(),
+                    vec![TextEdit::new(
+                        diagnostic.range,
+                        suggestion.value().to_string(),
+                    )],
+                );
+
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("Change to \u{201c}{}\u{201d}", suggestion.value()),
+                    kind: Some("quickfix".to_string()),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit::new(changes)),
+                    command: None,
+                }));
+            }
+        }
+
+        actions
+    }
+
+    fn handle_request(&self, connection: &Connection, req: Request) -> Result<(), Box<dyn Error>> {
+        let (id, params) = match req
+            .extract::<<CodeActionRequest as RequestType>::Params>(CodeActionRequest::METHOD)
+        {
+            Ok(v) => v,
+            Err(req) => {
+                let resp = Response::new_err(
+                    req.id,
+                    lsp_server::ErrorCode::MethodNotFound as i32,
+                    format!("Unhandled method: {}", req.method),
+                );
+                connection.sender.send(resp.into())?;
+                return Ok(());
+            }
+        };
+
+        let resp = Response::new_ok(id, self.code_actions(params));
+        connection.sender.send(resp.into())?;
+        Ok(())
+    }
+}
+
+fn run(mut server: Server) -> Result<(), Box<dyn Error>> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::Incremental,
+        )),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(&capabilities)?)?;
+
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    break;
+                }
+                server.handle_request(&connection, req)?;
+            }
+            Message::Notification(not) => server.handle_notification(&connection, not)?,
+            Message::Response(_) => {}
+        }
+    }
+
+    io_threads.join()?;
+    Ok(())
+}
+
+fn main() {
+    let matches = App::new("divvunspell-lsp")
+        .setting(AppSettings::ColoredHelp)
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Language server publishing spelling diagnostics over stdio")
+        .arg(
+            Arg::with_name("zhfst")
+                .short("z")
+                .long("zhfst")
+                .value_name("ZHFST")
+                .help("Use the given ZHFST file"),
+        )
+        .arg(
+            Arg::with_name("lang")
+                .short("l")
+                .long("lang")
+                .value_name("LANG")
+                .conflicts_with("zhfst")
+                .help("Use the archive configured for this language"),
+        )
+        .arg(
+            Arg::with_name("nbest")
+                .short("n")
+                .long("nbest")
+                .value_name("N")
+                .help("Maximum number of quick fixes per word (default 5)"),
+        )
+        .get_matches();
+
+    let config = match CliConfig::load() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}: {}", CliConfig::path().unwrap_or_default().display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let path = match matches.value_of("zhfst") {
+        Some(path) => PathBuf::from(path),
+        None => match config.archive(matches.value_of("lang")) {
+            Some(path) => path.to_path_buf(),
+            None => {
+                eprintln!("No ZHFST file given or configured");
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let tag = matches.value_of("lang").unwrap_or("default").to_string();
+    let repository = SpellerRepository::new(vec![(tag.clone(), path)], MemoryPolicy::default());
+    let archive = match repository.get(&tag) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let defaults = config.speller_config();
+    let n_best = match matches.value_of("nbest") {
+        Some(v) => Some(v.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!("Invalid value for --nbest: {}", v);
+            std::process::exit(1);
+        })),
+        None => defaults.n_best.or(Some(DEFAULT_SUGGESTIONS)),
+    };
+
+    let server = Server {
+        speller: archive.speller(),
+        cfg: SpellerConfig { n_best, ..defaults },
+        documents: HashMap::new(),
+    };
+
+    // stdout carries the protocol, so errors can only go to stderr.
+    if let Err(e) = run(server) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,317 @@
+//! LanguageTool's `/v2/check` and `/v2/languages`, so that browser extensions
+//! and other clients written for a LanguageTool server can use divvunspell
+//! unchanged. Only spelling is checked, reported under a single rule. With
+//! `language=auto`, or no language, the text's language is detected among
+//! those loaded, falling back to the default language.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use divvunspell::archive::langid::LanguageIdentifier;
+use divvunspell::archive::repository::ArchiveHandle;
+use divvunspell::archive::SpellerArchive;
+use serde_derive::{Deserialize, Serialize};
+use tiny_http::{Method, Request};
+
+use super::{deadline, find_errors, find_language, read_body, to_json, ApiError, State};
+
+pub const RULE_ID: &str = "DIVVUNSPELL_SPELLING";
+
+/// Characters of surrounding text included in each match's context.
+const CONTEXT_UNITS: usize = 40;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Software {
+    name: &'static str,
+    version: &'static str,
+    api_version: u32,
+    status: &'static str,
+    premium: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Warnings {
+    incomplete_results: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DetectedLanguage<'a> {
+    name: &'a str,
+    code: &'a str,
+    confidence: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckedLanguage<'a> {
+    name: &'a str,
+    code: &'a str,
+    detected_language: DetectedLanguage<'a>,
+}
+
+#[derive(Serialize)]
+struct Replacement {
+    value: String,
+}
+
+#[derive(Serialize)]
+struct Context {
+    text: String,
+    offset: usize,
+    length: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MatchType {
+    type_name: &'static str,
+}
+
+#[derive(Serialize)]
+struct Category {
+    id: &'static str,
+    name: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: &'static str,
+    description: &'static str,
+    issue_type: &'static str,
+    category: Category,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Match {
+    message: &'static str,
+    short_message: &'static str,
+    replacements: Vec<Replacement>,
+    offset: usize,
+    length: usize,
+    context: Context,
+    sentence: String,
+    #[serde(rename = "type")]
+    type_: MatchType,
+    rule: Rule,
+    ignore_for_incomplete_sentence: bool,
+    context_for_sure_match: i32,
+}
+
+#[derive(Serialize)]
+struct CheckResponse<'a> {
+    software: Software,
+    warnings: Warnings,
+    language: CheckedLanguage<'a>,
+    matches: Vec<Match>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LanguageEntry<'a> {
+    name: &'a str,
+    code: &'a str,
+    long_code: &'a str,
+}
+
+/// The `data` parameter: text interleaved with markup that is not checked
+/// but still counts towards offsets.
+#[derive(Deserialize)]
+struct AnnotatedText {
+    annotation: Vec<Annotation>,
+}
+
+#[derive(Deserialize)]
+struct Annotation {
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    markup: Option<String>,
+}
+
+fn language_name<'a>(tag: &'a str, archive: Option<&'a SpellerArchive>) -> &'a str {
+    archive
+        .and_then(|archive| archive.metadata().info.title.first())
+        .map(|t| &*t.value)
+        .unwrap_or(tag)
+}
+
+/// Query string parameters, plus the form-encoded body of a POST.
+fn params(request: &mut Request) -> Result<HashMap<String, String>, ApiError> {
+    let mut params = HashMap::new();
+
+    if let Some(i) = request.url().find('?') {
+        let query = request.url().as_bytes()[i + 1..].to_vec();
+        params.extend(url::form_urlencoded::parse(&query).into_owned());
+    }
+
+    if *request.method() == Method::Post {
+        let body = read_body(request)?;
+        params.extend(url::form_urlencoded::parse(&body).into_owned());
+    }
+
+    Ok(params)
+}
+
+/// Splits the request into checked text and its UTF-16 offset in the whole
+/// document.
+fn fragments(params: &HashMap<String, String>) -> Result<Vec<(usize, String)>, ApiError> {
+    if let Some(text) = params.get("text") {
+        return Ok(vec![(0, text.clone())]);
+    }
+
+    let data = params
+        .get("data")
+        .ok_or_else(|| ApiError::BadRequest("Missing 'text' or 'data' parameter".into()))?;
+    let data: AnnotatedText =
+        serde_json::from_str(data).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let mut offset = 0;
+    let mut out = vec![];
+
+    for annotation in data.annotation {
+        match (annotation.text, annotation.markup) {
+            (Some(text), _) => {
+                let len = text.encode_utf16().count();
+                out.push((offset, text));
+                offset += len;
+            }
+            (None, Some(markup)) => offset += markup.encode_utf16().count(),
+            (None, None) => {}
+        }
+    }
+
+    Ok(out)
+}
+
+fn context(units: &[u16], start: usize, end: usize) -> Context {
+    let from = start.saturating_sub(CONTEXT_UNITS);
+    let to = (end + CONTEXT_UNITS).min(units.len());
+
+    Context {
+        text: String::from_utf16_lossy(&units[from..to]),
+        offset: start - from,
+        length: end - start,
+    }
+}
+
+pub fn languages(state: &State) -> Result<String, ApiError> {
+    let archives: Vec<(&str, Option<ArchiveHandle>)> = state
+        .repository
+        .tags()
+        .map(|tag| (tag, state.repository.get_loaded(tag)))
+        .collect();
+    let entries: Vec<LanguageEntry> = archives
+        .iter()
+        .map(|(tag, archive)| LanguageEntry {
+            name: language_name(tag, archive.as_ref().map(|a| &**a)),
+            code: tag.split('-').next().unwrap_or(tag),
+            long_code: tag,
+        })
+        .collect();
+
+    to_json(&entries)
+}
+
+pub fn check(state: &State, request: &mut Request) -> Result<String, ApiError> {
+    let params = params(request)?;
+    let fragments = fragments(&params)?;
+
+    let (tag, confidence) = match params.get("language").map(|x| &**x) {
+        Some("auto") | None => {
+            let text: Vec<&str> = fragments.iter().map(|(_, text)| &**text).collect();
+            let tags = state.repository.tags();
+
+            match LanguageIdentifier::builtin().best(&text.join(" "), tags) {
+                Some(found) => found,
+                None => state
+                    .default_language
+                    .as_ref()
+                    .map(|x| (&**x, 0.0))
+                    .ok_or_else(|| {
+                        ApiError::BadRequest("Could not detect the language; set 'language'".into())
+                    })?,
+            }
+        }
+        Some(tag) => (tag, 1.0),
+    };
+    let lang = find_language(state, tag)?;
+
+    let disabled = params
+        .get("disabledRules")
+        .map(|rules| rules.split(',').any(|r| r.trim() == RULE_ID))
+        .unwrap_or(false);
+
+    let cfg = state.config(None, None);
+    let deadline = deadline(&cfg);
+    let mut matches = vec![];
+
+    if !disabled {
+        for (offset, text) in fragments.iter() {
+            let units: Vec<u16> = text.encode_utf16().collect();
+
+            for error in find_errors(&lang, text, &cfg, deadline) {
+                let context = context(&units, error.start, error.end);
+
+                matches.push(Match {
+                    message: "Possible spelling mistake found.",
+                    short_message: "Spelling mistake",
+                    replacements: error
+                        .suggestions
+                        .into_iter()
+                        .map(|s| Replacement {
+                            value: s.value.to_string(),
+                        })
+                        .collect(),
+                    offset: offset + error.start,
+                    length: error.end - error.start,
+                    sentence: context.text.clone(),
+                    context,
+                    type_: MatchType {
+                        type_name: "UnknownWord",
+                    },
+                    rule: Rule {
+                        id: RULE_ID,
+                        description: "Possible spelling mistake",
+                        issue_type: "misspelling",
+                        category: Category {
+                            id: "TYPOS",
+                            name: "Possible Typo",
+                        },
+                    },
+                    ignore_for_incomplete_sentence: false,
+                    context_for_sure_match: 0,
+                });
+            }
+        }
+    }
+
+    let name = language_name(&lang.tag, Some(&lang.archive));
+
+    to_json(&CheckResponse {
+        software: Software {
+            name: "divvunspell",
+            version: env!("CARGO_PKG_VERSION"),
+            api_version: 1,
+            status: "",
+            premium: false,
+        },
+        warnings: Warnings {
+            incomplete_results: deadline.map(|d| Instant::now() >= d).unwrap_or(false),
+        },
+        language: CheckedLanguage {
+            name,
+            code: &lang.tag,
+            detected_language: DetectedLanguage {
+                name,
+                code: &lang.tag,
+                confidence,
+            },
+        },
+        matches,
+    })
+}
@@ -0,0 +1,568 @@
+#![cfg(feature = "binaries")]
+
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{App, AppSettings, Arg};
+use serde_derive::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use divvunspell::archive::repository::{ArchiveHandle, RepositoryError, SpellerRepository};
+use divvunspell::config::CliConfig;
+use divvunspell::metrics::Counters;
+use divvunspell::speller::issue::SpellingIssue;
+use divvunspell::speller::stream::CheckStream;
+use divvunspell::speller::suggestion::Suggestion;
+use divvunspell::speller::suggestion_cache::{CachedLanguage, SuggestionCaches};
+use divvunspell::speller::SpellerConfig;
+use divvunspell::transducer::HfstTransducer;
+
+#[path = "../shared/cli.rs"]
+mod cli;
+mod languagetool;
+
+use cli::{load_repository, parse_arg};
+
+const DEFAULT_BIND: &str = "127.0.0.1:8080";
+const DEFAULT_THREADS: usize = 4;
+const DEFAULT_SUGGESTIONS: usize = 5;
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+const MAX_STREAM_BODY_SIZE: u64 = 64 * 1024 * 1024;
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+const DEFAULT_CACHE_SIZE: usize = 10_000;
+const CACHE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+struct State {
+    repository: SpellerRepository,
+    caches: Option<Arc<SuggestionCaches>>,
+    /// Used when a client asks the server to pick the language.
+    default_language: Option<String>,
+    cfg: SpellerConfig,
+    /// Shared by every language's speller, served at `/metrics`.
+    metrics: Arc<Counters>,
+}
+
+impl State {
+    /// The language for a BCP-47 tag, or the closest less specific one, so
+    /// `se-Latn-NO` falls back to `se`. Its archive is opened if it isn't.
+    fn language(&self, tag: &str) -> Result<CachedLanguage, RepositoryError> {
+        CachedLanguage::open(&self.repository, self.caches.as_deref(), tag)
+    }
+
+    /// Requests may ask for a shorter time budget than the server's, not a
+    /// longer one.
+    fn config(&self, n_best: Option<usize>, time_budget_ms: Option<u64>) -> SpellerConfig {
+        let time_budget_ms = match (time_budget_ms, self.cfg.time_budget_ms) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        SpellerConfig {
+            n_best: n_best.or(self.cfg.n_best),
+            time_budget_ms,
+            ..self.cfg.clone()
+        }
+    }
+}
+
+enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    MethodNotAllowed,
+    /// The request body is longer than the limit, in bytes, for its endpoint.
+    PayloadTooLarge(u64),
+    /// The language's archive couldn't be opened.
+    Unavailable(String),
+}
+
+impl ApiError {
+    fn status(&self) -> u16 {
+        match self {
+            ApiError::BadRequest(_) => 400,
+            ApiError::NotFound(_) => 404,
+            ApiError::MethodNotAllowed => 405,
+            ApiError::PayloadTooLarge(_) => 413,
+            ApiError::Unavailable(_) => 503,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            ApiError::BadRequest(e) => write!(f, "{}", e),
+            ApiError::NotFound(e) => write!(f, "{}", e),
+            ApiError::MethodNotAllowed => write!(f, "Method not allowed"),
+            ApiError::PayloadTooLarge(limit) => {
+                write!(f, "Request body is larger than {} bytes", limit)
+            }
+            ApiError::Unavailable(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SuggestRequest {
+    lang: String,
+    word: String,
+    #[serde(default)]
+    n_best: Option<usize>,
+    #[serde(default)]
+    time_budget_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct CheckRequest {
+    lang: String,
+    text: String,
+    #[serde(default)]
+    n_best: Option<usize>,
+    #[serde(default)]
+    time_budget_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct LanguageInfo<'a> {
+    tag: &'a str,
+    locale: &'a str,
+    title: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct LanguagesResponse<'a> {
+    languages: Vec<LanguageInfo<'a>>,
+}
+
+#[derive(Serialize)]
+struct SuggestResponse<'a> {
+    lang: &'a str,
+    word: &'a str,
+    correct: bool,
+    suggestions: Vec<Suggestion>,
+}
+
+#[derive(Serialize)]
+struct CheckResponse<'a> {
+    lang: &'a str,
+    errors: Vec<SpellingIssue>,
+}
+
+#[derive(Serialize)]
+struct StreamLine<'a> {
+    start: usize,
+    end: usize,
+    errors: &'a [SpellingIssue],
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+fn read_body(request: &mut Request) -> Result<Vec<u8>, ApiError> {
+    read_body_up_to(request, MAX_BODY_SIZE)
+}
+
+fn read_body_up_to(request: &mut Request, limit: u64) -> Result<Vec<u8>, ApiError> {
+    if *request.method() != Method::Post {
+        return Err(ApiError::MethodNotAllowed);
+    }
+
+    if request.body_length().is_some_and(|len| len as u64 > limit) {
+        return Err(ApiError::PayloadTooLarge(limit));
+    }
+
+    // Read one byte past the limit to tell a body that fills it from one
+    // that goes over, as with chunked requests that give no length
+    let mut body = vec![];
+    request
+        .as_reader()
+        .take(limit + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    if body.len() as u64 > limit {
+        return Err(ApiError::PayloadTooLarge(limit));
+    }
+    Ok(body)
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(request: &mut Request) -> Result<T, ApiError> {
+    let body = read_body(request)?;
+    serde_json::from_slice(&body).map_err(|e| ApiError::BadRequest(e.to_string()))
+}
+
+fn find_language(state: &State, tag: &str) -> Result<CachedLanguage, ApiError> {
+    state.language(tag).map_err(|e| match e {
+        RepositoryError::UnknownLanguage(_) => ApiError::NotFound(e.to_string()),
+        RepositoryError::OpenFailed { .. } => {
+            eprintln!("{}", e);
+            ApiError::Unavailable(e.to_string())
+        }
+    })
+}
+
+/// Archives not open yet are listed by their tag alone, rather than opened
+/// to read their metadata.
+fn languages(state: &State) -> Result<String, ApiError> {
+    let archives: Vec<(&str, Option<ArchiveHandle>)> = state
+        .repository
+        .tags()
+        .map(|tag| (tag, state.repository.get_loaded(tag)))
+        .collect();
+    let languages = archives
+        .iter()
+        .map(|(tag, archive)| match archive {
+            Some(archive) => {
+                let info = &archive.metadata().info;
+                LanguageInfo {
+                    tag,
+                    locale: &info.locale,
+                    title: info.title.first().map(|t| &*t.value),
+                }
+            }
+            None => LanguageInfo {
+                tag,
+                locale: tag,
+                title: None,
+            },
+        })
+        .collect();
+
+    to_json(&LanguagesResponse { languages })
+}
+
+fn suggest(state: &State, request: &mut Request) -> Result<String, ApiError> {
+    let req: SuggestRequest = read_json(request)?;
+    let lang = find_language(state, &req.lang)?;
+    let cfg = state.config(req.n_best, req.time_budget_ms);
+    let check = lang.check(&req.word, &cfg);
+
+    to_json(&SuggestResponse {
+        lang: &lang.tag,
+        word: &req.word,
+        correct: check.correct,
+        suggestions: check.suggestions,
+    })
+}
+
+/// Finds the misspellings in `text`, with suggestions until `deadline`.
+/// Misspellings found after it passes are still reported, without
+/// suggestions.
+fn find_errors(
+    lang: &CachedLanguage,
+    text: &str,
+    cfg: &SpellerConfig,
+    deadline: Option<Instant>,
+) -> Vec<SpellingIssue> {
+    lang.check_text(text, cfg, deadline)
+}
+
+fn deadline(cfg: &SpellerConfig) -> Option<Instant> {
+    cfg.time_budget_ms
+        .map(|ms| Instant::now() + Duration::from_millis(ms))
+}
+
+/// The time budget covers the whole text.
+fn check(state: &State, request: &mut Request) -> Result<String, ApiError> {
+    let req: CheckRequest = read_json(request)?;
+    let lang = find_language(state, &req.lang)?;
+    let cfg = state.config(req.n_best, req.time_budget_ms);
+    let errors = find_errors(&lang, &req.text, &cfg, deadline(&cfg));
+
+    to_json(&CheckResponse {
+        lang: &lang.tag,
+        errors,
+    })
+}
+
+/// A check stream read as newline-delimited JSON, a line per chunk. Each
+/// chunk is checked only when the response is ready for more, so a client
+/// that reads slowly holds the check up rather than results piling up.
+struct ChunkLines {
+    stream: CheckStream<HfstTransducer, String>,
+    line: Vec<u8>,
+    sent: usize,
+}
+
+impl Read for ChunkLines {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.sent == self.line.len() {
+            let chunk = match self.stream.next() {
+                Some(chunk) => chunk,
+                None => return Ok(0),
+            };
+            self.line = serde_json::to_vec(&StreamLine {
+                start: chunk.start,
+                end: chunk.end,
+                errors: &chunk.issues,
+            })?;
+            self.line.push(b'\n');
+            self.sent = 0;
+        }
+
+        let n = buf.len().min(self.line.len() - self.sent);
+        buf[..n].copy_from_slice(&self.line[self.sent..self.sent + n]);
+        self.sent += n;
+        Ok(n)
+    }
+}
+
+/// As `check`, for documents of up to 64 MB, answering with a line per
+/// chunk as it is checked. Suggestions don't go through the cache.
+fn check_stream(state: &State, request: &mut Request) -> Result<ChunkLines, ApiError> {
+    let body = read_body_up_to(request, MAX_STREAM_BODY_SIZE)?;
+    let req: CheckRequest =
+        serde_json::from_slice(&body).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let lang = find_language(state, &req.lang)?;
+    let cfg = state.config(req.n_best, req.time_budget_ms);
+
+    let mut stream = lang.archive.speller().check_stream(req.text, &cfg);
+    if let Some(deadline) = deadline(&cfg) {
+        stream = stream.with_deadline(deadline);
+    }
+
+    Ok(ChunkLines {
+        stream,
+        line: vec![],
+        sent: 0,
+    })
+}
+
+fn respond_stream(request: Request, lines: ChunkLines) {
+    let content_type =
+        Header::from_bytes(&b"Content-Type"[..], &b"application/x-ndjson"[..]).unwrap();
+    let response = Response::new(StatusCode(200), vec![content_type], lines, None, None);
+
+    if let Err(e) = request.respond(response) {
+        eprintln!("/check/stream: {}", e);
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, ApiError> {
+    serde_json::to_string(value).map_err(|e| ApiError::BadRequest(e.to_string()))
+}
+
+fn handle(state: &State, mut request: Request) {
+    let path = request.url().split('?').next().unwrap_or("").to_string();
+
+    let result = match &*path {
+        "/languages" => match request.method() {
+            Method::Get => languages(state),
+            _ => Err(ApiError::MethodNotAllowed),
+        },
+        "/suggest" => suggest(state, &mut request),
+        "/check" => check(state, &mut request),
+        "/check/stream" => match check_stream(state, &mut request) {
+            Ok(lines) => return respond_stream(request, lines),
+            Err(e) => Err(e),
+        },
+        "/v2/languages" => languagetool::languages(state),
+        "/v2/check" => languagetool::check(state, &mut request),
+        "/metrics" => match request.method() {
+            Method::Get => Ok(state.metrics.prometheus()),
+            _ => Err(ApiError::MethodNotAllowed),
+        },
+        _ => Err(ApiError::NotFound(format!("No such endpoint: {}", path))),
+    };
+
+    let (status, body) = match result {
+        Ok(body) => (200, body),
+        Err(e) => (
+            e.status(),
+            serde_json::to_string(&ErrorResponse {
+                error: e.to_string(),
+            })
+            .unwrap_or_default(),
+        ),
+    };
+
+    let content_type = match (&*path, status) {
+        ("/metrics", 200) => PROMETHEUS_CONTENT_TYPE,
+        _ => "application/json",
+    };
+    let content_type = Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap();
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type);
+
+    if let Err(e) = request.respond(response) {
+        eprintln!("{}: {}", path, e);
+    }
+}
+
+fn main() {
+    let matches = App::new("divvunspell-server")
+        .setting(AppSettings::ColoredHelp)
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("HTTP JSON spellchecking server")
+        .arg(
+            Arg::with_name("archive")
+                .short("a")
+                .long("archive")
+                .value_name("TAG=PATH")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Serve a ZHFST archive for a BCP-47 language tag (default: all configured archives)"),
+        )
+        .arg(
+            Arg::with_name("bind")
+                .short("b")
+                .long("bind")
+                .value_name("ADDR")
+                .help("Address to listen on (default 127.0.0.1:8080)"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .short("t")
+                .long("threads")
+                .value_name("N")
+                .help("Number of requests handled at once (default 4)"),
+        )
+        .arg(
+            Arg::with_name("nbest")
+                .short("n")
+                .long("nbest")
+                .value_name("N")
+                .help("Default number of suggestions per word (default 5)"),
+        )
+        .arg(
+            Arg::with_name("time-budget")
+                .long("time-budget")
+                .value_name("MS")
+                .help("Longest time a request may spend on suggestions"),
+        )
+        .arg(
+            Arg::with_name("preload")
+                .long("preload")
+                .help("Copy archives into memory at startup, on huge pages where Linux allows"),
+        )
+        .arg(
+            Arg::with_name("paranoid")
+                .long("paranoid")
+                .help("Bounds check every transducer table read, for archives that aren't trusted"),
+        )
+        .arg(
+            Arg::with_name("suggestion-cache")
+                .long("suggestion-cache")
+                .value_name("DIR")
+                .help("Keep suggestions for frequent misspellings in DIR between runs"),
+        )
+        .arg(
+            Arg::with_name("cache-size")
+                .long("cache-size")
+                .value_name("N")
+                .requires("suggestion-cache")
+                .help("Words kept in each language's suggestion cache (default 10000)"),
+        )
+        .arg(
+            Arg::with_name("memory-budget")
+                .long("memory-budget")
+                .value_name("MB")
+                .help("Open archives when first asked for, closing the least recently used when those open take more than MB"),
+        )
+        .get_matches();
+
+    let config = match CliConfig::load() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}: {}", CliConfig::path().unwrap_or_default().display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut repository = load_repository(&matches, &config);
+
+    let defaults = config.speller_config();
+    let cfg = SpellerConfig {
+        n_best: parse_arg(&matches, "nbest")
+            .or(defaults.n_best)
+            .or(Some(DEFAULT_SUGGESTIONS)),
+        time_budget_ms: parse_arg(&matches, "time-budget").or(defaults.time_budget_ms),
+        ..defaults
+    };
+
+    let caches = matches.value_of("suggestion-cache").map(|dir| {
+        let capacity = parse_arg(&matches, "cache-size").unwrap_or(DEFAULT_CACHE_SIZE);
+        let mut caches = match SuggestionCaches::new(dir, &cfg, capacity) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("{}: {}", dir, e);
+                std::process::exit(1);
+            }
+        };
+        caches.set_on_open(Box::new(|tag, path, cache| match cache {
+            Ok(cache) => eprintln!("Cached {}: {} words", tag, cache.len()),
+            Err(e) => eprintln!("{}: {}, starting without a cache", path.display(), e),
+        }));
+        Arc::new(caches)
+    });
+
+    let default_language = match config.default_language {
+        Some(tag) => Some(tag),
+        None if repository.len() == 1 => repository.tags().next().map(str::to_string),
+        None => None,
+    };
+
+    let metrics = Arc::new(Counters::new());
+    {
+        let metrics = metrics.clone();
+        repository.set_on_load(Box::new(move |tag, archive| {
+            eprintln!("Loaded {}", tag);
+            archive.speller().set_metrics(metrics.clone());
+        }));
+    }
+
+    // Without a budget nothing is ever closed, so open everything now and
+    // fail at startup rather than on the first request
+    if matches.is_present("preload") || matches.value_of("memory-budget").is_none() {
+        if let Err(e) = repository.load_all() {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let state = Arc::new(State {
+        repository,
+        caches,
+        default_language,
+        cfg,
+        metrics,
+    });
+    if let Some(caches) = &state.caches {
+        caches
+            .clone()
+            .save_every(CACHE_SAVE_INTERVAL, |tag, e| eprintln!("{}: {}", tag, e));
+    }
+
+    let bind = matches.value_of("bind").unwrap_or(DEFAULT_BIND);
+    let threads = parse_arg(&matches, "threads").unwrap_or(DEFAULT_THREADS);
+
+    let server = match Server::http(bind) {
+        Ok(v) => Arc::new(v),
+        Err(e) => {
+            eprintln!("{}: {}", bind, e);
+            std::process::exit(1);
+        }
+    };
+
+    eprintln!("Listening on http://{}", bind);
+
+    let workers: Vec<_> = (0..threads.max(1))
+        .map(|_| {
+            let server = server.clone();
+            let state = state.clone();
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    handle(&state, request);
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+}
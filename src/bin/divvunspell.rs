@@ -1,25 +1,68 @@
 #![cfg(feature = "binaries")]
 
-use std::io::{self, Read};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use clap::{App, AppSettings, Arg, SubCommand};
+use clap::{App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
 use hashbrown::HashMap;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
 
-use divvunspell::archive::SpellerArchive;
+use divvunspell::archive::discovery;
+use divvunspell::archive::lint::{self, LintMessage, Severity};
+use divvunspell::archive::meta::{SpellerMetadata, SpellerTitle};
+use divvunspell::archive::{ArchiveEntry, LoadProfile, SpellerArchive};
+use divvunspell::config::CliConfig;
+use divvunspell::daemon::{default_socket_path, Client};
+use divvunspell::errmodel::typos::{Keyboard, TypoConfig, TypoGenerator};
+use divvunspell::speller::confusion::{Bigrams, ConfusionSets, RealWordChecker};
+use divvunspell::speller::corpus::{CorpusSurvey, SurveyConfig};
+use divvunspell::speller::frequency::{FrequencyList, FrequencyListBuilder, Scoring};
+use divvunspell::speller::issue::SpellingIssue;
+use divvunspell::speller::replay::SearchTrace;
 use divvunspell::speller::suggestion::Suggestion;
-use divvunspell::speller::{Speller, SpellerConfig};
+use divvunspell::speller::user_dictionary::UserDictionary;
+use divvunspell::speller::{InputProfile, Speller, SpellerConfig};
 use divvunspell::tokenizer::Tokenize;
-use divvunspell::transducer::chunk::ChfstBundle;
+use divvunspell::transducer::chunk::{verify, ChfstBundle};
+use divvunspell::transducer::{check_chunk_size, SerializeProgress, SerializeStage, Transducer};
+use divvunspell::types::Weight;
 
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use smol_str::SmolStr;
 
 trait OutputWriter {
     fn write_correction(&mut self, word: &str, is_correct: bool);
-    fn write_suggestions(&mut self, word: &str, suggestions: &[Suggestion]);
+    fn write_suggestions(&mut self, word: &str, suggestions: &[SuggestionDetail]);
     fn finish(&mut self);
 }
 
-struct StdoutWriter;
+/// A suggestion, and with `--details` how sure the speller is of it and
+/// what its weight is made of.
+#[derive(Serialize, Clone)]
+struct SuggestionDetail {
+    #[serde(flatten)]
+    suggestion: Suggestion,
+    /// Its share of the probability of all the word's suggestions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confidence: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lexicon_weight: Option<Weight>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errmodel_weight: Option<Weight>,
+    /// What frequency reranking added to its weight.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_weight: Option<Weight>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'static str>,
+}
+
+struct StdoutWriter {
+    details: bool,
+}
 
 impl OutputWriter for StdoutWriter {
     fn write_correction(&mut self, word: &str, is_correct: bool) {
@@ -30,9 +73,29 @@ impl OutputWriter for StdoutWriter {
         );
     }
 
-    fn write_suggestions(&mut self, word: &str, suggestions: &[Suggestion]) {
+    fn write_suggestions(&mut self, word: &str, suggestions: &[SuggestionDetail]) {
+        if self.details && !suggestions.is_empty() {
+            println!("suggestion\t\tweight\tconfidence\tlexicon\terrmodel\tfrequency\tsource");
+        }
+
         for sugg in suggestions {
-            println!("{}\t\t{}", sugg.value, sugg.weight);
+            let Suggestion { value, weight } = &sugg.suggestion;
+            if !self.details {
+                println!("{}\t\t{}", value, weight);
+                continue;
+            }
+
+            let column = |v: Option<Weight>| v.map_or("-".to_string(), |v| v.to_string());
+            println!(
+                "{}\t\t{}\t{:.3}\t{}\t{}\t{}\t{}",
+                value,
+                weight,
+                sugg.confidence.unwrap_or(0.0),
+                column(sugg.lexicon_weight),
+                column(sugg.errmodel_weight),
+                column(sugg.frequency_weight),
+                sugg.source.unwrap_or("-"),
+            );
         }
         println!("");
     }
@@ -40,11 +103,18 @@ impl OutputWriter for StdoutWriter {
     fn finish(&mut self) {}
 }
 
+#[derive(Serialize)]
+struct MetaReport<'a> {
+    metadata: &'a SpellerMetadata,
+    contents: &'a [ArchiveEntry],
+    messages: &'a [LintMessage],
+}
+
 #[derive(Serialize)]
 struct SuggestionRequest {
     word: String,
     is_correct: bool,
-    suggestions: Vec<Suggestion>,
+    suggestions: Vec<SuggestionDetail>,
 }
 
 #[derive(Serialize)]
@@ -68,7 +138,7 @@ impl OutputWriter for JsonWriter {
         });
     }
 
-    fn write_suggestions(&mut self, word: &str, suggestions: &[Suggestion]) {
+    fn write_suggestions(&mut self, word: &str, suggestions: &[SuggestionDetail]) {
         let i = self.results.len() - 1;
         self.results[i].suggestions = suggestions.to_vec();
     }
@@ -78,8 +148,1035 @@ impl OutputWriter for JsonWriter {
     }
 }
 
-fn main() {
-    let matches = App::new("divvunspell")
+/// Counts up each table's chunks on one line of stderr.
+fn print_progress(progress: SerializeProgress) {
+    let table = match progress.stage {
+        SerializeStage::IndexTable => "index table",
+        SerializeStage::TransitionTable => "transition table",
+        SerializeStage::Meta => return,
+    };
+
+    eprint!(
+        "\r  {}: {}/{} chunks",
+        table, progress.chunk, progress.chunks
+    );
+    if progress.chunk == progress.chunks {
+        eprintln!();
+    }
+}
+
+fn convert_to_chfst(
+    input: &Path,
+    output: &Path,
+    chunk_size: usize,
+    samples: u32,
+    check_words: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let start = Instant::now();
+    let step = |n: usize, msg: &str| {
+        eprintln!("[{}/4 {:>6.1}s] {}", n, start.elapsed().as_secs_f32(), msg);
+    };
+
+    step(1, "Loading archive...");
+    let archive = SpellerArchive::new(&input.to_string_lossy())?;
+    let speller = archive.speller();
+
+    step(2, "Serializing lexicon...");
+    speller
+        .lexicon()
+        .serialize_with_progress(chunk_size, &output.join("lexicon"), print_progress)?;
+
+    step(3, "Serializing mutator...");
+    speller
+        .mutator()
+        .serialize_with_progress(chunk_size, &output.join("mutator"), print_progress)?;
+
+    step(4, "Verifying...");
+    let bundle = ChfstBundle::from_path(output)?;
+
+    for (name, expected, actual) in &[
+        ("lexicon", speller.lexicon(), &bundle.lexicon),
+        ("mutator", speller.mutator(), &bundle.mutator),
+    ] {
+        let header = expected.header();
+        let checked = verify::spot_check(
+            *expected,
+            *actual,
+            header.index_table_size() as u32,
+            header.target_table_size() as u32,
+            samples,
+        )
+        .map_err(|e| format!("{}: {}", name, e))?;
+        eprintln!("  {}: {} table positions match", name, checked);
+    }
+
+    if let Some(path) = check_words {
+        let words = std::fs::read_to_string(path)?;
+        let chfst_speller = bundle.speller();
+        let cfg = SpellerConfig::default();
+        let mut count = 0;
+
+        for word in words.lines().map(str::trim).filter(|w| !w.is_empty()) {
+            let expected = speller.clone().suggest_with_config(word, &cfg);
+            let actual = chfst_speller.clone().suggest_with_config(word, &cfg);
+
+            if speller.clone().is_correct(word) != chfst_speller.clone().is_correct(word)
+                || expected != actual
+            {
+                return Err(format!("lookup results differ for {:?}", word).into());
+            }
+
+            count += 1;
+        }
+
+        eprintln!("  {} word lookups match", count);
+    }
+
+    eprintln!(
+        "Wrote {} in {:.1}s",
+        output.display(),
+        start.elapsed().as_secs_f32()
+    );
+
+    Ok(())
+}
+
+fn parse_arg<T: std::str::FromStr>(
+    matches: &ArgMatches,
+    name: &str,
+    default: T,
+) -> Result<T, Box<dyn Error>> {
+    match matches.value_of(name) {
+        Some(v) => v
+            .parse::<T>()
+            .map_err(|_| format!("Invalid value for --{}: {}", name, v).into()),
+        None => Ok(default),
+    }
+}
+
+fn generate_typos(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let defaults = TypoConfig::default();
+    let cfg = TypoConfig {
+        error_rate: parse_arg(matches, "error-rate", defaults.error_rate)?,
+        max_edits: parse_arg(matches, "max-edits", defaults.max_edits)?,
+        keyboard: parse_arg(matches, "keyboard", defaults.keyboard)?,
+        transposition: parse_arg(matches, "transposition", defaults.transposition)?,
+        diacritic: parse_arg(matches, "diacritic", defaults.diacritic)?,
+        deletion: parse_arg(matches, "deletion", defaults.deletion)?,
+        insertion: parse_arg(matches, "insertion", defaults.insertion)?,
+    };
+    let per_word: usize = parse_arg(matches, "per-word", 1)?;
+    let seed: u64 = parse_arg(matches, "seed", 1)?;
+
+    let keyboard = match matches.value_of("layout") {
+        Some(path) => {
+            let layout = std::fs::read_to_string(path)?;
+            let rows: Vec<&str> = layout.lines().filter(|l| !l.trim().is_empty()).collect();
+            Keyboard::from_rows(&rows)
+        }
+        None => Keyboard::qwerty(),
+    };
+
+    let words = std::fs::read_to_string(matches.value_of("WORDS").unwrap())?;
+    let mut generator = TypoGenerator::new(cfg, keyboard, seed);
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+
+    for word in words.lines().map(str::trim).filter(|w| !w.is_empty()) {
+        for _ in 0..per_word {
+            if let Some(typo) = generator.typo(word) {
+                writeln!(out, "{}\t{}", typo, word)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads `word<TAB>count` rows (or `count<TAB>word`, as written by
+/// `sort | uniq -c`) and writes them as a compiled frequency list.
+fn compile_frequencies(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let input = std::fs::read_to_string(matches.value_of("INPUT").unwrap())?;
+    let mut builder = FrequencyListBuilder::new();
+
+    for (n, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line
+            .split(['\t', ' '])
+            .filter(|f| !f.is_empty());
+        let (word, count) = match (fields.next(), fields.next()) {
+            (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                (_, Ok(count)) => (a, count),
+                (Ok(count), Err(_)) => (b, count),
+                _ => return Err(format!("line {}: no count in {:?}", n + 1, line).into()),
+            },
+            (Some(a), None) => (a, 1),
+            _ => continue,
+        };
+
+        builder.add(word, count);
+    }
+
+    let output = matches.value_of("OUTPUT").unwrap();
+    builder.write(io::BufWriter::new(std::fs::File::create(output)?))?;
+    eprintln!("Wrote {} words to {}", builder.len(), output);
+
+    Ok(())
+}
+
+/// Prints a trace of the search for `WORD`, or with `--replay`, runs a
+/// saved trace's query again and reports the first step that differs.
+/// Returns whether the replay matched.
+fn trace_search(matches: &ArgMatches, config: &CliConfig) -> Result<bool, Box<dyn Error>> {
+    let path = archive_path(matches, config).ok_or("No ZHFST file given or configured")?;
+    let archive = SpellerArchive::new(&path).map_err(|e| format!("{}: {:?}", path, e))?;
+    let speller = archive.speller();
+
+    let saved = match matches.value_of("replay") {
+        Some(file) => Some(SearchTrace::parse(&std::fs::read_to_string(file)?)?),
+        None => None,
+    };
+
+    let trace = match &saved {
+        Some(saved) => speller.replay(saved),
+        None => {
+            let defaults = config.speller_config();
+            let suggest_cfg = SpellerConfig {
+                n_best: matches
+                    .value_of("nbest")
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .or(defaults.n_best),
+                max_weight: matches
+                    .value_of("weight")
+                    .and_then(|v| v.parse::<f32>().ok())
+                    .or(defaults.max_weight),
+                ..defaults
+            };
+            speller.suggest_traced(matches.value_of("WORD").unwrap(), &suggest_cfg)
+        }
+    };
+
+    if let Some(output) = matches.value_of("output") {
+        std::fs::write(output, trace.to_string())?;
+    }
+
+    let saved = match saved {
+        Some(saved) => saved,
+        None => {
+            if matches.value_of("output").is_none() {
+                print!("{}", trace);
+            }
+            return Ok(true);
+        }
+    };
+
+    match saved.diff(&trace) {
+        None => {
+            println!("Same {} steps", trace.steps.len());
+            Ok(true)
+        }
+        Some(divergence) => {
+            let show = |step: Option<_>| match step {
+                Some(step) => format!("{}", step),
+                None => "(end of trace)".to_string(),
+            };
+            println!("Differs at step {}:", divergence.step + 1);
+            println!("  saved:  {}", show(divergence.expected));
+            println!("  replay: {}", show(divergence.found));
+            println!("Saved suggestions:  {:?}", saved.suggestions());
+            println!("Replay suggestions: {:?}", trace.suggestions());
+            Ok(false)
+        }
+    }
+}
+
+/// The ZHFST file given with `--zhfst`, or configured for `--lang` or the
+/// default language.
+fn archive_path(matches: &ArgMatches, config: &CliConfig) -> Option<String> {
+    match (matches.value_of("zhfst"), matches.value_of("lang")) {
+        (Some(path), _) => Some(path.to_string()),
+        (None, Some(lang)) => match config.archive(Some(lang)) {
+            Some(path) => Some(path.to_string_lossy().into_owned()),
+            None => {
+                eprintln!("No archive configured for language {:?}", lang);
+                std::process::exit(1);
+            }
+        },
+        (None, None) if !matches.is_present("chfst") => config
+            .archive(None)
+            .map(|path| path.to_string_lossy().into_owned()),
+        _ => None,
+    }
+}
+
+/// The archive for the language the `FILES` are in, among those configured
+/// and installed, for when none was given.
+fn detect_archive(matches: &ArgMatches, config: &CliConfig) -> Option<String> {
+    let mut text = String::new();
+    for path in matches.values_of("FILES")? {
+        if let Ok(v) = std::fs::read_to_string(path) {
+            text.push_str(&v);
+            text.push('\n');
+        }
+    }
+
+    let mut archives = discovery::discover();
+    archives.extend(config.archives.clone());
+
+    let (tag, path) = discovery::detect(&archives, &text)?;
+    eprintln!("Detected language {}, using {}", tag, path.display());
+    Some(path.to_string_lossy().into_owned())
+}
+
+const DIFF_CONTEXT: usize = 3;
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// ANSI colours for terminal output, or nothing when colour is off.
+struct Colours {
+    enabled: bool,
+}
+
+impl Colours {
+    fn from_matches(matches: &ArgMatches) -> Colours {
+        let enabled = match matches.value_of("color") {
+            Some("always") => true,
+            Some("never") => false,
+            _ => is_terminal(),
+        };
+
+        Colours { enabled }
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.enabled {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn red(&self, text: &str) -> String {
+        self.paint("31", text)
+    }
+
+    fn green(&self, text: &str) -> String {
+        self.paint("32", text)
+    }
+
+    fn cyan(&self, text: &str) -> String {
+        self.paint("36", text)
+    }
+
+    fn bold(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+}
+
+#[cfg(unix)]
+fn is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) != 0 }
+}
+
+#[cfg(not(unix))]
+fn is_terminal() -> bool {
+    false
+}
+
+/// How sure the speller is of its top suggestion, as its share of the total
+/// probability of all suggestions given.
+fn confidence(suggestions: &[Suggestion]) -> f32 {
+    match suggestions.first() {
+        Some(best) => share(best, suggestions),
+        None => 0.0,
+    }
+}
+
+/// The share of `suggestion` in the total probability of `suggestions`.
+fn share(suggestion: &Suggestion, suggestions: &[Suggestion]) -> f32 {
+    1.0 / suggestions
+        .iter()
+        .map(|s| (suggestion.weight - s.weight).exp())
+        .sum::<f32>()
+}
+
+/// `ranked`, the suggestions the speller `source` gave as `found` and then
+/// reranked, if `rescored`. With `details` each weight is broken down: the
+/// lexicon's part, from `lexicon_weight`, and the error model's, the rest of
+/// its weight as found, and what reranking added.
+fn describe(
+    found: &[Suggestion],
+    ranked: &[Suggestion],
+    source: &'static str,
+    rescored: bool,
+    details: bool,
+    lexicon_weight: impl Fn(&str) -> Option<Weight>,
+) -> Vec<SuggestionDetail> {
+    ranked
+        .iter()
+        .map(|s| {
+            let mut detail = SuggestionDetail {
+                suggestion: s.clone(),
+                confidence: None,
+                lexicon_weight: None,
+                errmodel_weight: None,
+                frequency_weight: None,
+                source: None,
+            };
+            if !details {
+                return detail;
+            }
+
+            let original = found.iter().find(|f| f.value == s.value).map(|f| f.weight);
+            let lexicon =
+                lexicon_weight(&s.value).or_else(|| lexicon_weight(&s.value.to_lowercase()));
+
+            detail.confidence = Some(share(s, ranked));
+            detail.lexicon_weight = lexicon;
+            detail.errmodel_weight = match (original, lexicon) {
+                (Some(original), Some(lexicon)) => Some(original - lexicon),
+                _ => None,
+            };
+            if rescored {
+                detail.frequency_weight = original.map(|original| s.weight - original);
+            }
+            detail.source = Some(source);
+            detail
+        })
+        .collect()
+}
+
+fn find_misspellings<T: Transducer>(
+    speller: &Arc<Speller<T>>,
+    text: &str,
+    cfg: &SpellerConfig,
+) -> Vec<SpellingIssue> {
+    speller.clone().check_text_with_config(text, cfg)
+}
+
+/// Prints `old` and `new` as a unified diff. Corrections never add or remove
+/// newlines, so lines correspond one to one.
+fn write_diff<W: Write>(
+    w: &mut W,
+    path: &str,
+    old: &str,
+    new: &str,
+    colours: &Colours,
+) -> io::Result<()> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let changed: Vec<usize> = (0..old.len()).filter(|&i| old[i] != new[i]).collect();
+
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(w, "{}", colours.bold(&format!("--- a/{}", path)))?;
+    writeln!(w, "{}", colours.bold(&format!("+++ b/{}", path)))?;
+
+    let mut i = 0;
+    while i < changed.len() {
+        // Lines changed close enough together share a hunk
+        let mut j = i;
+        while j + 1 < changed.len() && changed[j + 1] - changed[j] <= DIFF_CONTEXT * 2 {
+            j += 1;
+        }
+
+        let start = changed[i].saturating_sub(DIFF_CONTEXT);
+        let end = (changed[j] + DIFF_CONTEXT + 1).min(old.len());
+        let header = format!(
+            "@@ -{},{} +{},{} @@",
+            start + 1,
+            end - start,
+            start + 1,
+            end - start
+        );
+        writeln!(w, "{}", colours.cyan(&header))?;
+
+        let mut line = start;
+        while line < end {
+            if old[line] == new[line] {
+                writeln!(w, " {}", old[line])?;
+                line += 1;
+                continue;
+            }
+
+            let run_end = (line..end).find(|&l| old[l] == new[l]).unwrap_or(end);
+            for l in &old[line..run_end] {
+                writeln!(w, "{}", colours.red(&format!("-{}", l)))?;
+            }
+            for l in &new[line..run_end] {
+                writeln!(w, "{}", colours.green(&format!("+{}", l)))?;
+            }
+            line = run_end;
+        }
+
+        i = j + 1;
+    }
+
+    Ok(())
+}
+
+struct CheckOptions {
+    colours: Colours,
+    threshold: f32,
+    is_fixing: bool,
+    is_writing: bool,
+    real_words: Option<RealWordChecker>,
+}
+
+impl CheckOptions {
+    fn from_matches(matches: &ArgMatches) -> Result<CheckOptions, Box<dyn Error>> {
+        let real_words = match (matches.value_of("confusions"), matches.value_of("bigrams")) {
+            (Some(sets), Some(bigrams)) => Some(RealWordChecker::new(
+                ConfusionSets::open(sets).map_err(|e| format!("{}: {}", sets, e))?,
+                Bigrams::open(bigrams).map_err(|e| format!("{}: {}", bigrams, e))?,
+            )),
+            _ => None,
+        };
+
+        Ok(CheckOptions {
+            colours: Colours::from_matches(matches),
+            threshold: parse_arg(matches, "threshold", 0.8)?,
+            is_fixing: matches.is_present("fix"),
+            is_writing: matches.is_present("write"),
+            real_words,
+        })
+    }
+}
+
+/// Checks one file, listing misspellings or, with `--fix`, proposing the top
+/// suggestion for those above the confidence threshold. Returns the number of
+/// misspellings found.
+fn check_file<T: Transducer, W: Write>(
+    speller: &Arc<Speller<T>>,
+    path: &str,
+    cfg: &SpellerConfig,
+    opts: &CheckOptions,
+    out: &mut W,
+) -> Result<usize, Box<dyn Error>> {
+    let colours = &opts.colours;
+    let text = std::fs::read_to_string(path)?;
+    let misspellings = find_misspellings(speller, &text, cfg);
+
+    if !opts.is_fixing {
+        let confusions = match &opts.real_words {
+            Some(checker) => speller.clone().check_confusions(&text, checker),
+            None => vec![],
+        };
+
+        // Possible confusions are listed with the misspellings, in order,
+        // but aren't counted as misspellings.
+        let mut found: Vec<(usize, String, &[Suggestion])> = misspellings
+            .iter()
+            .map(|m| (m.offset, colours.red(&m.word), &m.suggestions[..]))
+            .collect();
+        found.extend(confusions.iter().map(|c| {
+            let word = format!("{} (possible confusion)", colours.cyan(c.word));
+            (c.offset, word, &c.alternatives[..])
+        }));
+        found.sort_by_key(|f| f.0);
+
+        for (offset, word, suggestions) in &found {
+            let before = &text[..*offset];
+            let line = before.matches('\n').count() + 1;
+            let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+            let suggestions: Vec<&str> = suggestions.iter().map(|s| s.value()).collect();
+
+            writeln!(
+                out,
+                "{}:{}:{}: {} {}",
+                path,
+                line,
+                column,
+                word,
+                colours.green(&suggestions.join(", "))
+            )?;
+        }
+
+        return Ok(misspellings.len());
+    }
+
+    let mut fixed = String::with_capacity(text.len());
+    let mut last = 0;
+    let mut applied = 0;
+
+    for m in &misspellings {
+        let best = match m.suggestions.first() {
+            Some(best) if confidence(&m.suggestions) >= opts.threshold => best,
+            _ => continue,
+        };
+
+        if best.value().contains('\n') {
+            continue;
+        }
+
+        fixed.push_str(&text[last..m.offset]);
+        fixed.push_str(best.value());
+        last = m.offset + m.word.len();
+        applied += 1;
+    }
+    fixed.push_str(&text[last..]);
+
+    if opts.is_writing {
+        if applied > 0 {
+            std::fs::write(path, &fixed)?;
+        }
+        eprintln!(
+            "{}: applied {} of {} corrections",
+            path,
+            applied,
+            misspellings.len()
+        );
+    } else {
+        write_diff(out, path, &text, &fixed, colours)?;
+    }
+
+    Ok(misspellings.len())
+}
+
+/// Checks the given files, then with `--watch` keeps the speller loaded and
+/// re-checks files under the watched directory as they change. Returns the
+/// number of misspellings found in the given files.
+fn check_files<T: Transducer>(
+    speller: Arc<Speller<T>>,
+    matches: &ArgMatches,
+    cfg: &SpellerConfig,
+) -> Result<usize, Box<dyn Error>> {
+    let opts = CheckOptions::from_matches(matches)?;
+    let stdout = io::stdout();
+    let mut total = 0;
+
+    if let Some(paths) = matches.values_of("FILES") {
+        let mut out = io::BufWriter::new(stdout.lock());
+        for path in paths {
+            total += check_file(&speller, path, cfg, &opts, &mut out)?;
+        }
+    }
+
+    if let Some(dir) = matches.value_of("watch") {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = notify::watcher(tx, WATCH_DEBOUNCE)?;
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+        eprintln!("Watching {} for changes...", dir);
+
+        for event in rx {
+            let path = match event {
+                DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => path,
+                DebouncedEvent::Error(e, _) => {
+                    eprintln!("{}", e);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let is_hidden = path
+                .file_name()
+                .map(|n| n.to_string_lossy().starts_with('.'))
+                .unwrap_or(true);
+            if !path.is_file() || is_hidden {
+                continue;
+            }
+
+            let path = path.to_string_lossy();
+            let start = Instant::now();
+            let mut out = stdout.lock();
+
+            // Binary or half-written files aren't worth stopping for
+            match check_file(&speller, &path, cfg, &opts, &mut out) {
+                Ok(count) => writeln!(
+                    out,
+                    "{}",
+                    opts.colours.bold(&format!(
+                        "{}: {} misspelling(s) in {:.0}ms",
+                        path,
+                        count,
+                        start.elapsed().as_secs_f32() * 1000.0
+                    ))
+                )?,
+                Err(e) => eprintln!("{}: {}", path, e),
+            }
+        }
+    }
+
+    Ok(total)
+}
+
+/// Runs a `CorpusSurvey` over the given files, or standard input, and
+/// prints its report.
+fn survey_corpus<T: Transducer>(
+    speller: Arc<Speller<T>>,
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn Error>> {
+    let defaults = SurveyConfig::default();
+    let survey_config = SurveyConfig {
+        top_words: parse_arg(matches, "top", defaults.top_words)?,
+        sample_size: parse_arg(matches, "sample", defaults.sample_size)?,
+        seed: parse_arg(matches, "seed", defaults.seed)?,
+    };
+    let mut survey = CorpusSurvey::new(speller, survey_config);
+
+    match matches.values_of("FILES") {
+        Some(paths) => {
+            for path in paths {
+                let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+                survey.add_reader(path, io::BufReader::new(file))?;
+            }
+        }
+        None => {
+            let stdin = io::stdin();
+            survey.add_reader("-", stdin.lock())?;
+        }
+    }
+
+    let report = survey.report();
+
+    if matches.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!(
+        "{} tokens, {} flagged ({:.2}%), {} of {} distinct words flagged",
+        report.tokens,
+        report.flagged,
+        report.error_rate() * 100.0,
+        report.flagged_words,
+        report.words
+    );
+
+    if !report.top.is_empty() {
+        println!("\nMost frequent flagged words:");
+        for word in &report.top {
+            match &word.suggestion {
+                Some(s) => println!("{:>8}  {} -> {}", word.count, word.word, s),
+                None => println!("{:>8}  {}", word.count, word.word),
+            }
+        }
+    }
+
+    if !report.sample.is_empty() {
+        println!("\nSample for review:");
+        for token in &report.sample {
+            println!(
+                "{}:{}: {}: {}",
+                token.source, token.line, token.word, token.context
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// JSON-RPC 2.0 error codes.
+const RPC_PARSE_ERROR: i64 = -32700;
+const RPC_METHOD_NOT_FOUND: i64 = -32601;
+const RPC_INVALID_PARAMS: i64 = -32602;
+/// An archive could not be opened, or no archive is open.
+const RPC_ARCHIVE_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    /// Absent for notifications, which get no response.
+    #[serde(default)]
+    id: Option<serde_json::Value>,
+    method: String,
+    #[serde(default)]
+    params: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct RpcResponse {
+    jsonrpc: &'static str,
+    id: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct OpenArchiveParams {
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    lang: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckTextParams {
+    #[serde(default)]
+    archive: Option<usize>,
+    text: String,
+    #[serde(default)]
+    suggestions: bool,
+    #[serde(default)]
+    n_best: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SuggestParams {
+    #[serde(default)]
+    archive: Option<usize>,
+    word: String,
+    #[serde(default)]
+    n_best: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct UserDictParams {
+    #[serde(default)]
+    archive: Option<usize>,
+    word: String,
+}
+
+#[derive(Serialize)]
+struct OpenArchiveResult<'a> {
+    archive: usize,
+    locale: &'a str,
+}
+
+/// Offsets are in UTF-16 code units, as in most editors' string types.
+#[derive(Serialize)]
+struct RpcMisspelling {
+    word: SmolStr,
+    start: usize,
+    end: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestions: Option<Vec<Suggestion>>,
+}
+
+#[derive(Serialize)]
+struct CheckTextResult {
+    errors: Vec<RpcMisspelling>,
+}
+
+#[derive(Serialize)]
+struct SuggestResult {
+    correct: bool,
+    suggestions: Vec<Suggestion>,
+}
+
+#[derive(Serialize)]
+struct AddToUserDictResult {
+    added: bool,
+}
+
+struct RpcArchive {
+    archive: SpellerArchive,
+    user: UserDictionary,
+}
+
+/// Archives opened over JSON-RPC, by the index `openArchive` returns.
+struct RpcSession<'a> {
+    config: &'a CliConfig,
+    cfg: SpellerConfig,
+    archives: Vec<RpcArchive>,
+}
+
+fn rpc_error(code: i64, message: String) -> RpcError {
+    RpcError { code, message }
+}
+
+fn rpc_params<T: serde::de::DeserializeOwned>(
+    params: Option<serde_json::Value>,
+) -> Result<T, RpcError> {
+    serde_json::from_value(params.unwrap_or(serde_json::Value::Null))
+        .map_err(|e| rpc_error(RPC_INVALID_PARAMS, e.to_string()))
+}
+
+fn rpc_result<T: serde::Serialize>(value: &T) -> Result<serde_json::Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| rpc_error(RPC_PARSE_ERROR, e.to_string()))
+}
+
+impl<'a> RpcSession<'a> {
+    /// The given archive, or the most recently opened one.
+    fn archive(&mut self, index: Option<usize>) -> Result<&mut RpcArchive, RpcError> {
+        let index = match index {
+            Some(v) => v,
+            None if !self.archives.is_empty() => self.archives.len() - 1,
+            None => return Err(rpc_error(RPC_ARCHIVE_ERROR, "No archive is open".into())),
+        };
+
+        self.archives
+            .get_mut(index)
+            .ok_or_else(|| rpc_error(RPC_ARCHIVE_ERROR, format!("No archive {}", index)))
+    }
+
+    fn suggest_config(&self, n_best: Option<usize>) -> SpellerConfig {
+        SpellerConfig {
+            n_best: n_best.or(self.cfg.n_best),
+            ..self.cfg.clone()
+        }
+    }
+
+    fn open_archive(&mut self, params: OpenArchiveParams) -> Result<serde_json::Value, RpcError> {
+        let path = match (params.path, params.lang) {
+            (Some(path), _) => path,
+            (None, lang) => match self.config.archive(lang.as_deref()) {
+                Some(path) => path.to_string_lossy().into_owned(),
+                None => {
+                    return Err(rpc_error(
+                        RPC_ARCHIVE_ERROR,
+                        format!("No archive configured for {}", lang.unwrap_or_default()),
+                    ))
+                }
+            },
+        };
+
+        let archive = SpellerArchive::new(&path)
+            .map_err(|e| rpc_error(RPC_ARCHIVE_ERROR, format!("{}: {}", path, e)))?;
+
+        self.archives.push(RpcArchive {
+            archive,
+            user: UserDictionary::new(),
+        });
+        let index = self.archives.len() - 1;
+
+        rpc_result(&OpenArchiveResult {
+            archive: index,
+            locale: &self.archives[index].archive.metadata().info.locale,
+        })
+    }
+
+    fn check_text(&mut self, params: CheckTextParams) -> Result<serde_json::Value, RpcError> {
+        let cfg = self.suggest_config(params.n_best);
+        let entry = self.archive(params.archive)?;
+        let speller = entry.archive.speller();
+        let text = &params.text;
+
+        let mut errors = vec![];
+        for issue in SpellingIssue::locate(text, speller.clone().check_text(text)) {
+            if entry.user.contains(&issue.word) {
+                continue;
+            }
+
+            let suggestions = if params.suggestions {
+                let found = speller.clone().suggest_with_config(&issue.word, &cfg);
+                Some(entry.user.merge(&issue.word, found, cfg.n_best))
+            } else {
+                None
+            };
+
+            errors.push(RpcMisspelling {
+                word: issue.word,
+                start: issue.start,
+                end: issue.end,
+                suggestions,
+            });
+        }
+
+        rpc_result(&CheckTextResult { errors })
+    }
+
+    fn suggest(&mut self, params: SuggestParams) -> Result<serde_json::Value, RpcError> {
+        let cfg = self.suggest_config(params.n_best);
+        let entry = self.archive(params.archive)?;
+        let speller = entry.archive.speller();
+        let word = &params.word;
+
+        let correct = entry.user.contains(word) || speller.clone().is_correct(word);
+        let found = speller.suggest_with_config(word, &cfg);
+
+        rpc_result(&SuggestResult {
+            correct,
+            suggestions: entry.user.merge(word, found, cfg.n_best),
+        })
+    }
+
+    fn add_to_user_dict(&mut self, params: UserDictParams) -> Result<serde_json::Value, RpcError> {
+        let entry = self.archive(params.archive)?;
+        rpc_result(&AddToUserDictResult {
+            added: entry.user.insert(&params.word),
+        })
+    }
+
+    fn handle(
+        &mut self,
+        method: &str,
+        params: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value, RpcError> {
+        match method {
+            "openArchive" => self.open_archive(rpc_params(params)?),
+            "checkText" => self.check_text(rpc_params(params)?),
+            "suggest" => self.suggest(rpc_params(params)?),
+            "addToUserDict" => self.add_to_user_dict(rpc_params(params)?),
+            _ => Err(rpc_error(
+                RPC_METHOD_NOT_FOUND,
+                format!("Unknown method: {}", method),
+            )),
+        }
+    }
+}
+
+/// Answers JSON-RPC 2.0 requests, one per line on stdin, with one response
+/// per line on stdout, until stdin is closed.
+fn jsonrpc(config: &CliConfig) -> io::Result<()> {
+    let mut session = RpcSession {
+        config,
+        cfg: config.speller_config(),
+        archives: vec![],
+    };
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (id, result) = match serde_json::from_str::<RpcRequest>(&line) {
+            Ok(request) => {
+                let result = session.handle(&request.method, request.params);
+                match request.id {
+                    Some(id) => (id, result),
+                    None => continue,
+                }
+            }
+            Err(e) => (
+                serde_json::Value::Null,
+                Err(rpc_error(RPC_PARSE_ERROR, e.to_string())),
+            ),
+        };
+
+        let (result, error) = match result {
+            Ok(v) => (Some(v), None),
+            Err(e) => (None, Some(e)),
+        };
+
+        serde_json::to_writer(
+            &mut out,
+            &RpcResponse {
+                jsonrpc: "2.0",
+                id,
+                result,
+                error,
+            },
+        )?;
+        out.write_all(b"\n")?;
+        out.flush()?;
+    }
+
+    Ok(())
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("divvunspell")
         .setting(AppSettings::ArgRequiredElseHelp)
         .version(env!("CARGO_PKG_VERSION"))
         .author("Brendan Molloy <brendan@bbqsrc.net>")
@@ -93,6 +1190,15 @@ fn main() {
                 .help("Use the given ZHFST file")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("lang")
+                .short("l")
+                .long("lang")
+                .value_name("LANG")
+                .conflicts_with_all(&["zhfst", "chfst"])
+                .help("Use the ZHFST file configured for this language")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("chfst")
                 .short("c")
@@ -129,33 +1235,565 @@ fn main() {
                 .takes_value(true)
                 .help("Maximum number of results for suggestions"),
         )
+        .arg(
+            Arg::with_name("max-edit-distance")
+                .long("max-edit-distance")
+                .requires("suggest")
+                .takes_value(true)
+                .help("Maximum number of error model edits in a suggestion"),
+        )
+        .arg(
+            Arg::with_name("fallback-distance")
+                .long("fallback-distance")
+                .value_name("N")
+                .requires("suggest")
+                .takes_value(true)
+                .help("When the error model suggests nothing, suggest words within N edits"),
+        )
+        .arg(
+            Arg::with_name("frequencies")
+                .long("frequencies")
+                .value_name("FILE")
+                .requires("suggest")
+                .takes_value(true)
+                .help("Rerank suggestions with a list compiled by compile-frequencies"),
+        )
+        .arg(
+            Arg::with_name("frequency-weight")
+                .long("frequency-weight")
+                .requires("frequencies")
+                .takes_value(true)
+                .help("How much word frequency counts towards a suggestion's weight (default: 1.0)"),
+        )
+        .arg(
+            Arg::with_name("scoring")
+                .long("scoring")
+                .requires("frequencies")
+                .takes_value(true)
+                .possible_values(&["noisy-channel", "additive"])
+                .default_value("noisy-channel")
+                .help("How frequencies combine with suggestion weights: as the prior in place of the lexicon's weights, or added to the whole weight"),
+        )
+        .arg(
+            Arg::with_name("input-profile")
+                .long("input-profile")
+                .takes_value(true)
+                .possible_values(&["touch-keyboard", "physical-keyboard", "ocr", "dictation"])
+                .help("Weigh the error model's edits for text from this kind of input"),
+        )
+        .arg(
+            Arg::with_name("details")
+                .long("details")
+                .requires("suggest")
+                .help("Show each suggestion's confidence, and its weight split into lexicon, error model and frequency parts"),
+        )
+        .arg(
+            Arg::with_name("profile-load")
+                .long("profile-load")
+                .help("Print how long each step of opening the archive took"),
+        )
         .arg(
             Arg::with_name("json")
                 .long("json")
                 .help("Output results in JSON"),
         )
+        .arg(
+            Arg::with_name("daemon")
+                .short("d")
+                .long("daemon")
+                .conflicts_with_all(&["zhfst", "chfst", "weight"])
+                .help("Ask a running divvunspell-daemon for --lang or the default language"),
+        )
         .arg(
             Arg::with_name("WORDS")
                 .multiple(true)
                 .help("The words to be processed"),
         )
         .subcommand(
-            SubCommand::with_name("chunk").arg(
-                Arg::with_name("zhfst")
-                    .short("z")
-                    .long("zhfst")
-                    .value_name("ZHFST")
-                    .required(true)
-                    .help("Use the given ZHFST file")
-                    .takes_value(true),
-            ),
+            SubCommand::with_name("check")
+                .about("Check files for misspellings, optionally proposing corrections")
+                .arg(
+                    Arg::with_name("zhfst")
+                        .short("z")
+                        .long("zhfst")
+                        .value_name("ZHFST")
+                        .takes_value(true)
+                        .help("Use the given ZHFST file"),
+                )
+                .arg(
+                    Arg::with_name("lang")
+                        .short("l")
+                        .long("lang")
+                        .value_name("LANG")
+                        .takes_value(true)
+                        .conflicts_with("zhfst")
+                        .help("Use the ZHFST file configured for this language"),
+                )
+                .arg(
+                    Arg::with_name("fix")
+                        .long("fix")
+                        .help("Replace misspellings with the top suggestion, if confident enough"),
+                )
+                .arg(
+                    Arg::with_name("diff")
+                        .long("diff")
+                        .requires("fix")
+                        .conflicts_with("write")
+                        .help("Show the corrections as a unified diff (the default with --fix)"),
+                )
+                .arg(
+                    Arg::with_name("write")
+                        .long("write")
+                        .requires("fix")
+                        .help("Apply the corrections to the files in place"),
+                )
+                .arg(
+                    Arg::with_name("threshold")
+                        .long("threshold")
+                        .takes_value(true)
+                        .requires("fix")
+                        .help("Minimum confidence, 0 to 1, for applying a suggestion (default: 0.8)"),
+                )
+                .arg(
+                    Arg::with_name("confusions")
+                        .long("confusions")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .requires("bigrams")
+                        .conflicts_with("fix")
+                        .help("Also flag correct words that another word of their confusion set fits better"),
+                )
+                .arg(
+                    Arg::with_name("bigrams")
+                        .long("bigrams")
+                        .value_name("TSV")
+                        .takes_value(true)
+                        .requires("confusions")
+                        .help("Word pair counts for --confusions, as previous, word and count columns"),
+                )
+                .arg(
+                    Arg::with_name("color")
+                        .long("color")
+                        .takes_value(true)
+                        .possible_values(&["auto", "always", "never"])
+                        .default_value("auto")
+                        .help("When to colour the output"),
+                )
+                .arg(
+                    Arg::with_name("watch")
+                        .long("watch")
+                        .value_name("DIR")
+                        .takes_value(true)
+                        .conflicts_with("write")
+                        .help("Keep running, re-checking files under this directory as they change"),
+                )
+                .arg(
+                    Arg::with_name("FILES")
+                        .required_unless("watch")
+                        .multiple(true)
+                        .help("The files to check"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("survey")
+                .about("Estimate how much of a corpus is flagged, listing the most frequent flagged words and a sample for review")
+                .arg(
+                    Arg::with_name("zhfst")
+                        .short("z")
+                        .long("zhfst")
+                        .value_name("ZHFST")
+                        .takes_value(true)
+                        .help("Use the given ZHFST file"),
+                )
+                .arg(
+                    Arg::with_name("lang")
+                        .short("l")
+                        .long("lang")
+                        .value_name("LANG")
+                        .takes_value(true)
+                        .conflicts_with("zhfst")
+                        .help("Use the ZHFST file configured for this language"),
+                )
+                .arg(
+                    Arg::with_name("top")
+                        .long("top")
+                        .takes_value(true)
+                        .help("How many of the most frequent flagged words to list (default: 50)"),
+                )
+                .arg(
+                    Arg::with_name("sample")
+                        .long("sample")
+                        .takes_value(true)
+                        .help("How many flagged tokens to sample for review (default: 100)"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .default_value("1")
+                        .help("Seed for the sample"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Output the report in JSON"),
+                )
+                .arg(
+                    Arg::with_name("FILES")
+                        .multiple(true)
+                        .help("The corpus files; standard input if none are given"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compile-frequencies")
+                .about("Compile a word frequency list for reranking suggestions")
+                .arg(
+                    Arg::with_name("INPUT")
+                        .required(true)
+                        .help("TSV file of words and their counts"),
+                )
+                .arg(
+                    Arg::with_name("OUTPUT")
+                        .required(true)
+                        .help("The compiled file to write"),
+                ),
         )
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name("convert")
+                .about("Convert a ZHFST file to another archive format")
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("FORMAT")
+                        .possible_values(&["chfst"])
+                        .required(true)
+                        .help("The format to convert to"),
+                )
+                .arg(
+                    Arg::with_name("chunk-size")
+                        .long("chunk-size")
+                        .value_name("BYTES")
+                        .default_value("25165824")
+                        .help("Maximum size of each table chunk, a multiple of 24"),
+                )
+                .arg(
+                    Arg::with_name("samples")
+                        .long("samples")
+                        .value_name("N")
+                        .default_value("10000")
+                        .help("Table positions to compare when verifying the result"),
+                )
+                .arg(
+                    Arg::with_name("check-words")
+                        .long("check-words")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .help("Also compare lookups and suggestions for these words, one per line"),
+                )
+                .arg(
+                    Arg::with_name("INPUT")
+                        .required(true)
+                        .help("The ZHFST file to convert"),
+                )
+                .arg(
+                    Arg::with_name("OUTPUT")
+                        .required(true)
+                        .help("The directory to write the converted archive to"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("generate-typos")
+                .about("Generate synthetic typos for a list of correct words, as input for the accuracy tool")
+                .arg(
+                    Arg::with_name("WORDS")
+                        .required(true)
+                        .help("File of correct words, one per line"),
+                )
+                .arg(
+                    Arg::with_name("per-word")
+                        .short("n")
+                        .long("per-word")
+                        .default_value("1")
+                        .help("Typos to generate for each word"),
+                )
+                .arg(
+                    Arg::with_name("error-rate")
+                        .long("error-rate")
+                        .takes_value(true)
+                        .help("Chance of an error at each character (default: 0.05)"),
+                )
+                .arg(
+                    Arg::with_name("max-edits")
+                        .long("max-edits")
+                        .takes_value(true)
+                        .help("Most errors in one typo (default: 2)"),
+                )
+                .arg(
+                    Arg::with_name("keyboard")
+                        .long("keyboard")
+                        .takes_value(true)
+                        .help("Relative weight of adjacent key slips (default: 0.4)"),
+                )
+                .arg(
+                    Arg::with_name("transposition")
+                        .long("transposition")
+                        .takes_value(true)
+                        .help("Relative weight of swapped letters (default: 0.2)"),
+                )
+                .arg(
+                    Arg::with_name("diacritic")
+                        .long("diacritic")
+                        .takes_value(true)
+                        .help("Relative weight of dropped diacritics (default: 0.2)"),
+                )
+                .arg(
+                    Arg::with_name("deletion")
+                        .long("deletion")
+                        .takes_value(true)
+                        .help("Relative weight of left out letters (default: 0.1)"),
+                )
+                .arg(
+                    Arg::with_name("insertion")
+                        .long("insertion")
+                        .takes_value(true)
+                        .help("Relative weight of doubled letters (default: 0.1)"),
+                )
+                .arg(
+                    Arg::with_name("layout")
+                        .long("layout")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .help("Keyboard rows, one per line, top to bottom (default: QWERTY)"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .default_value("1")
+                        .help("Seed for the random number generator"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("lint")
+                .about("Check a ZHFST file for problems affecting suggestion quality")
+                .arg(
+                    Arg::with_name("ZHFST")
+                        .required(true)
+                        .help("The ZHFST file to check"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Output results in JSON"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("meta")
+                .about("Print a ZHFST file's metadata, entries and any problems with its metadata")
+                .arg(
+                    Arg::with_name("ZHFST")
+                        .required(true)
+                        .help("The ZHFST file to read"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Output results in JSON"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("trace")
+                .about("Record every decision the suggestion search makes for a word, or replay a recorded search and show where it differs")
+                .arg(
+                    Arg::with_name("zhfst")
+                        .short("z")
+                        .long("zhfst")
+                        .value_name("ZHFST")
+                        .takes_value(true)
+                        .help("Use the given ZHFST file"),
+                )
+                .arg(
+                    Arg::with_name("lang")
+                        .short("l")
+                        .long("lang")
+                        .value_name("LANG")
+                        .takes_value(true)
+                        .conflicts_with("zhfst")
+                        .help("Use the ZHFST file configured for this language"),
+                )
+                .arg(
+                    Arg::with_name("weight")
+                        .short("w")
+                        .long("weight")
+                        .takes_value(true)
+                        .help("Maximum weight limit for suggestions"),
+                )
+                .arg(
+                    Arg::with_name("nbest")
+                        .short("n")
+                        .long("nbest")
+                        .takes_value(true)
+                        .help("Maximum number of results for suggestions"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .help("Write the trace to this file instead of printing it"),
+                )
+                .arg(
+                    Arg::with_name("replay")
+                        .long("replay")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .conflicts_with_all(&["WORD", "weight", "nbest"])
+                        .help("Run the search recorded in this trace again and compare"),
+                )
+                .arg(
+                    Arg::with_name("WORD")
+                        .required_unless("replay")
+                        .help("The word to trace suggestions for"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("jsonrpc")
+                .about("Serve JSON-RPC requests on stdin and stdout, for editor plugins"),
+        )
+        .subcommand(
+            SubCommand::with_name("completions")
+                .about("Print a shell completion script")
+                .arg(
+                    Arg::with_name("SHELL")
+                        .required(true)
+                        .possible_values(&["bash", "zsh", "fish"])
+                        .help("The shell to generate completions for"),
+                ),
+        )
+}
 
-    if let Some(ref matches) = matches.subcommand_matches("chunk") {
-        let zhfst_file = matches.value_of("zhfst").unwrap();
+fn print_load_profile(profile: &LoadProfile) {
+    let steps = [
+        ("zip parse", profile.zip_parse),
+        ("mmap", profile.mmap),
+        ("header parse", profile.header_parse),
+        ("alphabet build", profile.alphabet_build),
+        ("total", profile.total()),
+    ];
 
-        let archive = match divvunspell::archive::SpellerArchive::new(zhfst_file) {
+    for (name, took) in steps.iter() {
+        eprintln!("{:>14}: {:>9.3} ms", name, took.as_secs_f64() * 1000.0);
+    }
+}
+
+fn main() {
+    let matches = app().get_matches();
+
+    if let Some(matches) = matches.subcommand_matches("completions") {
+        let shell = matches.value_of("SHELL").unwrap().parse::<Shell>().unwrap();
+        app().gen_completions_to("divvunspell", shell, &mut io::stdout());
+        return;
+    }
+
+    let config = match CliConfig::load() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}: {}", CliConfig::path().unwrap_or_default().display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(matches) = matches.subcommand_matches("check") {
+        let path = archive_path(matches, &config).or_else(|| detect_archive(matches, &config));
+        let archive = match path.map(|p| SpellerArchive::new(&p)) {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => {
+                eprintln!("{:?}", e);
+                std::process::exit(1);
+            }
+            None => {
+                eprintln!("No ZHFST file given, configured or detected");
+                std::process::exit(1);
+            }
+        };
+
+        match check_files(archive.speller(), matches, &config.speller_config()) {
+            Ok(0) => {}
+            Ok(_) if matches.is_present("write") || matches.is_present("watch") => {}
+            Ok(_) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("survey") {
+        let archive = match archive_path(matches, &config).map(|p| SpellerArchive::new(&p)) {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => {
+                eprintln!("{:?}", e);
+                std::process::exit(1);
+            }
+            None => {
+                eprintln!("No ZHFST file given or configured");
+                std::process::exit(1);
+            }
+        };
+
+        if let Err(e) = survey_corpus(archive.speller(), matches) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("trace") {
+        match trace_search(matches, &config) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+
+        return;
+    }
+
+    if matches.subcommand_matches("jsonrpc").is_some() {
+        if let Err(e) = jsonrpc(&config) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("compile-frequencies") {
+        if let Err(e) = compile_frequencies(matches) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("generate-typos") {
+        if let Err(e) = generate_typos(matches) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("lint") {
+        let zhfst_file = matches.value_of("ZHFST").unwrap();
+
+        let archive = match SpellerArchive::new(zhfst_file) {
             Ok(v) => v,
             Err(e) => {
                 eprintln!("{:?}", e);
@@ -163,24 +1801,171 @@ fn main() {
             }
         };
 
-        let speller = archive.speller();
-        let mutator = speller.mutator();
-        let lexicon = speller.lexicon();
+        let messages = lint::lint(&archive);
+        let count = |severity| messages.iter().filter(|m| m.severity == severity).count();
+
+        if matches.is_present("json") {
+            println!("{}", serde_json::to_string_pretty(&messages).unwrap());
+        } else {
+            for message in &messages {
+                println!("{}", message);
+            }
+
+            println!(
+                "{} error(s), {} warning(s), {} note(s)",
+                count(Severity::Error),
+                count(Severity::Warning),
+                count(Severity::Note),
+            );
+        }
+
+        if count(Severity::Error) > 0 {
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("meta") {
+        let zhfst_file = matches.value_of("ZHFST").unwrap();
+
+        let archive = match SpellerArchive::new(zhfst_file) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                std::process::exit(1);
+            }
+        };
 
-        use std::path::Path;
+        let meta = archive.metadata();
+        let mut messages = meta.validate();
+        messages.extend(lint::lint_search(&archive));
+        messages.sort_by_key(|m| m.severity);
+        let contents = archive.contents();
 
-        let target_dir = Path::new("./out.chfst");
-        let chunk_size: usize = 24 * 1024 * 1024;
+        if matches.is_present("json") {
+            let output = MetaReport {
+                metadata: meta,
+                contents: &contents,
+                messages: &messages,
+            };
+            println!("{}", serde_json::to_string_pretty(&output).unwrap());
+        } else {
+            let titles = |titles: &[SpellerTitle]| {
+                titles
+                    .iter()
+                    .map(|t| match &t.lang {
+                        Some(lang) => format!("{} ({})", t.value, lang),
+                        None => t.value.clone(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
 
-        eprintln!("Serializing lexicon...");
-        lexicon
-            .serialize(chunk_size, &target_dir.join("lexicon"))
-            .unwrap();
+            println!("Locale:   {}", meta.info.locale);
+            println!("Title:    {}", titles(&meta.info.title));
+            if let Some(version) = &meta.info.version {
+                match &version.vcsrev {
+                    Some(rev) => println!("Version:  {} ({})", version.value, rev),
+                    None => println!("Version:  {}", version.value),
+                }
+            }
+            if let Some(date) = &meta.info.date {
+                println!("Date:     {}", date);
+            }
+            println!("Producer: {}", meta.info.producer);
+            println!(
+                "Acceptor: {} ({}): {}",
+                meta.acceptor.id,
+                meta.acceptor.type_,
+                titles(&meta.acceptor.title)
+            );
+            println!(
+                "Errmodel: {}: {}",
+                meta.errmodel.id,
+                titles(&meta.errmodel.title)
+            );
+            if let Some(defaults) = &meta.defaults {
+                let mut settings = vec![];
+                if let Some(n) = defaults.n_best {
+                    settings.push(format!("n_best {}", n));
+                }
+                if let Some(w) = defaults.max_weight {
+                    settings.push(format!("max_weight {}", w));
+                }
+                if let Some(w) = defaults.beam {
+                    settings.push(format!("beam {}", w));
+                }
+                if let Some(d) = defaults.max_edit_distance {
+                    settings.push(format!("max_edit_distance {}", d));
+                }
+                println!("Defaults: {}", settings.join(", "));
+            }
 
-        eprintln!("Serializing mutator...");
-        mutator
-            .serialize(chunk_size, &target_dir.join("mutator"))
-            .unwrap();
+            println!();
+            for entry in &contents {
+                print!(
+                    "{:<28} {:>10} bytes  {}",
+                    entry.name, entry.bytes, entry.hash
+                );
+                if let Some(info) = &entry.automaton {
+                    print!(
+                        "  {} states, {} transitions, {} symbols ({} input){}",
+                        info.states,
+                        info.transitions,
+                        info.symbols,
+                        info.input_symbols,
+                        if info.weighted { ", weighted" } else { "" }
+                    );
+                }
+                println!();
+            }
+
+            if !messages.is_empty() {
+                println!();
+            }
+            for message in &messages {
+                println!("{}", message);
+            }
+        }
+
+        if messages.iter().any(|m| m.severity == Severity::Error) {
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("convert") {
+        let chunk_size = matches
+            .value_of("chunk-size")
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or_else(|| {
+                eprintln!("--chunk-size must be a number");
+                std::process::exit(1);
+            });
+        if let Err(e) = check_chunk_size(chunk_size) {
+            eprintln!("--chunk-size: {}", e);
+            std::process::exit(1);
+        }
+        let samples = matches
+            .value_of("samples")
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or_else(|| {
+                eprintln!("--samples must be a number");
+                std::process::exit(1);
+            });
+
+        if let Err(e) = convert_to_chfst(
+            Path::new(matches.value_of("INPUT").unwrap()),
+            Path::new(matches.value_of("OUTPUT").unwrap()),
+            chunk_size,
+            samples,
+            matches.value_of("check-words").map(Path::new),
+        ) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
 
         return;
     }
@@ -188,6 +1973,7 @@ fn main() {
     let is_always_suggesting = matches.is_present("always-suggest");
     let is_suggesting = matches.is_present("suggest") || is_always_suggesting;
     let is_json = matches.is_present("json");
+    let is_detailed = matches.is_present("details");
 
     let n_best = matches
         .value_of("nbest")
@@ -195,6 +1981,12 @@ fn main() {
     let max_weight = matches
         .value_of("weight")
         .and_then(|v| v.parse::<f32>().ok());
+    let max_edit_distance = matches
+        .value_of("max-edit-distance")
+        .and_then(|v| v.parse::<u8>().ok());
+    let fallback_distance = matches
+        .value_of("fallback-distance")
+        .and_then(|v| v.parse::<u8>().ok());
 
     let words: Vec<String> = match matches.values_of("WORDS") {
         Some(v) => v.map(|x| x.to_string()).collect(),
@@ -211,21 +2003,96 @@ fn main() {
     let mut writer: Box<OutputWriter> = if is_json {
         Box::new(JsonWriter::new())
     } else {
-        Box::new(StdoutWriter)
+        Box::new(StdoutWriter {
+            details: is_detailed,
+        })
     };
 
-    let suggest_cfg = SpellerConfig {
-        max_weight,
-        n_best,
-        beam: None,
-        pool_max: 128,
-        pool_start: 128,
-        seen_node_sample_rate: 20,
-        with_caps: true,
+    let defaults = config.speller_config();
+    let mut suggest_cfg = SpellerConfig {
+        max_weight: max_weight.or(defaults.max_weight),
+        n_best: n_best.or(defaults.n_best),
+        max_edit_distance: max_edit_distance.or(defaults.max_edit_distance),
+        fallback_distance: fallback_distance.or(defaults.fallback_distance),
+        ..defaults
+    };
+    let input_profile = match matches.value_of("input-profile") {
+        Some("touch-keyboard") => Some(InputProfile::TouchKeyboard),
+        Some("physical-keyboard") => Some(InputProfile::PhysicalKeyboard),
+        Some("ocr") => Some(InputProfile::Ocr),
+        Some("dictation") => Some(InputProfile::Dictation),
+        _ => None,
     };
+    if let Some(profile) = input_profile {
+        suggest_cfg = suggest_cfg.with_profile(profile);
+    }
 
-    if let Some(zhfst_file) = matches.value_of("zhfst") {
-        let archive = match divvunspell::archive::SpellerArchive::new(zhfst_file) {
+    let frequencies = matches.value_of("frequencies").map(|path| {
+        FrequencyList::open(path).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        })
+    });
+    let frequency_weight = matches
+        .value_of("frequency-weight")
+        .and_then(|v| v.parse::<f32>().ok())
+        .unwrap_or(1.0);
+    let scoring = match matches.value_of("scoring") {
+        Some("additive") => Scoring::Additive,
+        _ => Scoring::NoisyChannel,
+    };
+
+    if matches.is_present("daemon") {
+        let lang = match matches
+            .value_of("lang")
+            .or(config.default_language.as_deref())
+        {
+            Some(v) => v,
+            None => {
+                eprintln!("--daemon needs --lang or a configured default language");
+                std::process::exit(1);
+            }
+        };
+
+        let result = Client::connect_default().and_then(|mut client| {
+            for word in words {
+                let is_correct = client.is_correct(lang, &word)?;
+                writer.write_correction(&word, is_correct);
+
+                if is_suggesting && (is_always_suggesting || !is_correct) {
+                    let found = client.suggest(lang, &word, suggest_cfg.n_best)?;
+                    let mut suggestions = found.clone();
+                    if let Some(ref frequencies) = frequencies {
+                        // The daemon's lexicon weights aren't known here
+                        frequencies.rescore(&mut suggestions, scoring, frequency_weight, |_| None);
+                    }
+                    let described = describe(
+                        &found,
+                        &suggestions,
+                        "daemon",
+                        frequencies.is_some(),
+                        is_detailed,
+                        |_| None,
+                    );
+                    writer.write_suggestions(&word, &described);
+                }
+            }
+            Ok(())
+        });
+
+        if let Err(e) = result {
+            eprintln!("{}: {}", default_socket_path().display(), e);
+            std::process::exit(1);
+        }
+
+        writer.finish();
+        return;
+    }
+
+    let zhfst_file = archive_path(&matches, &config);
+
+    if let Some(zhfst_file) = zhfst_file {
+        let archive = match divvunspell::archive::SpellerArchive::new(&zhfst_file) {
             Ok(v) => v,
             Err(e) => {
                 eprintln!("{:?}", e);
@@ -233,6 +2100,10 @@ fn main() {
             }
         };
 
+        if matches.is_present("profile-load") {
+            print_load_profile(&archive.load_profile());
+        }
+
         let speller = archive.speller();
 
         for word in words {
@@ -240,8 +2111,22 @@ fn main() {
             writer.write_correction(&word, is_correct);
 
             if is_suggesting && (is_always_suggesting || !is_correct) {
-                let suggestions = speller.clone().suggest_with_config(&word, &suggest_cfg);
-                writer.write_suggestions(&word, &suggestions);
+                let found = speller.clone().suggest_with_config(&word, &suggest_cfg);
+                let mut suggestions = found.clone();
+                if let Some(ref frequencies) = frequencies {
+                    frequencies.rescore(&mut suggestions, scoring, frequency_weight, |w| {
+                        speller.clone().lexicon_weight(w)
+                    });
+                }
+                let described = describe(
+                    &found,
+                    &suggestions,
+                    "speller",
+                    frequencies.is_some(),
+                    is_detailed,
+                    |w| speller.clone().lexicon_weight(w),
+                );
+                writer.write_suggestions(&word, &described);
             }
         }
     } else if let Some(chfst_file) = matches.value_of("chfst") {
@@ -260,8 +2145,22 @@ fn main() {
             writer.write_correction(&word, is_correct);
 
             if is_suggesting && (is_always_suggesting || !is_correct) {
-                let suggestions = speller.clone().suggest_with_config(&word, &suggest_cfg);
-                writer.write_suggestions(&word, &suggestions);
+                let found = speller.clone().suggest_with_config(&word, &suggest_cfg);
+                let mut suggestions = found.clone();
+                if let Some(ref frequencies) = frequencies {
+                    frequencies.rescore(&mut suggestions, scoring, frequency_weight, |w| {
+                        speller.clone().lexicon_weight(w)
+                    });
+                }
+                let described = describe(
+                    &found,
+                    &suggestions,
+                    "speller",
+                    frequencies.is_some(),
+                    is_detailed,
+                    |w| speller.clone().lexicon_weight(w),
+                );
+                writer.write_suggestions(&word, &described);
             }
         }
     }
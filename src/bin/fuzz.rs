@@ -0,0 +1,279 @@
+#![cfg(feature = "binaries")]
+
+//! A dependency-free fuzzer for environments where cargo-fuzz is unavailable.
+//!
+//! Each iteration mutates a seed input, feeds it to the chosen target and
+//! saves any input that panics. Runs are deterministic for a given `--seed`,
+//! so a crash that takes the whole process down (e.g. a bad mmap read) can be
+//! reproduced by rerunning with the seed printed at startup.
+
+use std::io::Write;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::{App, AppSettings, Arg, SubCommand};
+use memmap::MmapMut;
+
+use divvunspell::archive::SpellerArchive;
+use divvunspell::memory::MemoryPolicy;
+use divvunspell::speller::{Speller, SpellerConfig};
+use divvunspell::transducer::{HfstTransducer, Transducer};
+
+const MAX_MUTATIONS: u64 = 8;
+const MAX_WORD_LENGTH: u64 = 32;
+
+/// xorshift64*, good enough for picking mutations.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(if seed == 0 {
+            0x9E37_79B9_7F4A_7C15
+        } else {
+            seed
+        })
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            0
+        } else {
+            self.next() % n
+        }
+    }
+}
+
+fn mutate(rng: &mut Rng, seed: &[u8]) -> Vec<u8> {
+    let mut data = seed.to_vec();
+
+    for _ in 0..=rng.below(MAX_MUTATIONS) {
+        let len = data.len() as u64;
+
+        match rng.below(5) {
+            // Flip a bit
+            0 if len > 0 => {
+                let i = rng.below(len) as usize;
+                data[i] ^= 1 << rng.below(8);
+            }
+            // Overwrite a byte, favouring boundary values
+            1 if len > 0 => {
+                let i = rng.below(len) as usize;
+                data[i] = match rng.below(4) {
+                    0 => 0x00,
+                    1 => 0xFF,
+                    2 => 0x7F,
+                    _ => rng.next() as u8,
+                };
+            }
+            // Insert a byte
+            2 => {
+                let i = rng.below(len + 1) as usize;
+                data.insert(i, rng.next() as u8);
+            }
+            // Delete a byte
+            3 if len > 0 => {
+                let i = rng.below(len) as usize;
+                data.remove(i);
+            }
+            // Truncate
+            4 if len > 0 => {
+                let i = rng.below(len) as usize;
+                data.truncate(i);
+            }
+            _ => {}
+        }
+    }
+
+    data
+}
+
+fn random_word(rng: &mut Rng) -> String {
+    (0..rng.below(MAX_WORD_LENGTH))
+        .filter_map(|_| match rng.below(4) {
+            // Mostly letters, with some of the full Unicode range mixed in
+            0 => std::char::from_u32(0x61 + rng.below(26) as u32),
+            1 => std::char::from_u32(0xC0 + rng.below(0x1C0) as u32),
+            2 => std::char::from_u32(rng.below(0x80) as u32),
+            _ => std::char::from_u32(rng.below(0x11_0000) as u32),
+        })
+        .collect()
+}
+
+fn check_zhfst(data: &[u8]) {
+    let dir = tempdir::TempDir::new("divvunspell-fuzz").unwrap();
+    let path = dir.path().join("input.zhfst");
+
+    {
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(data).unwrap();
+    }
+
+    let policy = MemoryPolicy::untrusted();
+    if let Ok(archive) = SpellerArchive::open_with_policy(path.to_str().unwrap(), &policy) {
+        let _ = archive.speller().is_correct("fuzz");
+    }
+}
+
+fn check_hfst(data: &[u8]) {
+    // Anonymous maps cannot be empty.
+    if data.is_empty() {
+        return;
+    }
+
+    let mut map = MmapMut::map_anon(data.len()).unwrap();
+    map.copy_from_slice(data);
+    let map = map.make_read_only().unwrap();
+
+    // Inputs whose header or alphabet don't parse are rejected, not crashes.
+    let mut transducer = match HfstTransducer::from_mapped_memory(Arc::new(map.into())) {
+        Ok(transducer) => transducer,
+        Err(_) => return,
+    };
+    transducer.set_checked(true);
+
+    for i in 0..16 {
+        let _ = transducer.is_final(i);
+        let _ = transducer.final_weight(i);
+        let _ = transducer.has_epsilons_or_flags(i);
+        let _ = transducer.take_epsilons_and_flags(i);
+    }
+}
+
+fn check_suggest(speller: &Arc<Speller<HfstTransducer>>, cfg: &SpellerConfig, word: &str) {
+    let _ = speller.clone().is_correct(word);
+    let _ = speller.clone().suggest_with_config(word, cfg);
+}
+
+fn save_crash(out_dir: &Path, iteration: u64, data: &[u8]) -> PathBuf {
+    std::fs::create_dir_all(out_dir).expect("create crash dir");
+    let path = out_dir.join(format!("crash-{}", iteration));
+    std::fs::write(&path, data).expect("write crash input");
+    path
+}
+
+fn main() {
+    let seed_arg = Arg::with_name("seed")
+        .long("seed")
+        .takes_value(true)
+        .help("Seed for the random number generator (default: current time)");
+    let iterations_arg = Arg::with_name("iterations")
+        .short("n")
+        .long("iterations")
+        .takes_value(true)
+        .default_value("10000")
+        .help("Number of inputs to try");
+    let out_arg = Arg::with_name("out")
+        .short("o")
+        .long("out")
+        .takes_value(true)
+        .default_value("fuzz-crashes")
+        .help("Directory to save panicking inputs in");
+    let input_arg = Arg::with_name("INPUT").required(true);
+
+    let matches = App::new("fuzz")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .about("Feed mutated inputs to the archive and transducer parsers")
+        .subcommand(
+            SubCommand::with_name("zhfst")
+                .about("Mutate a ZHFST file and open it as an archive")
+                .arg(input_arg.clone().help("Seed ZHFST file"))
+                .args(&[seed_arg.clone(), iterations_arg.clone(), out_arg.clone()]),
+        )
+        .subcommand(
+            SubCommand::with_name("hfst")
+                .about("Mutate a raw HFST transducer and parse its header and tables")
+                .arg(input_arg.clone().help("Seed HFST file"))
+                .args(&[seed_arg.clone(), iterations_arg.clone(), out_arg.clone()]),
+        )
+        .subcommand(
+            SubCommand::with_name("suggest")
+                .about("Look up and suggest random Unicode strings")
+                .arg(input_arg.help("ZHFST file to use"))
+                .args(&[seed_arg, iterations_arg, out_arg]),
+        )
+        .get_matches();
+
+    let (target, matches) = matches.subcommand();
+    let matches = matches.unwrap();
+
+    let input = matches.value_of("INPUT").unwrap();
+    let out_dir = Path::new(matches.value_of("out").unwrap());
+    let iterations = matches
+        .value_of("iterations")
+        .and_then(|v| v.parse::<u64>().ok())
+        .expect("iterations must be a number");
+    let seed = matches
+        .value_of("seed")
+        .map(|v| v.parse::<u64>().expect("seed must be a number"))
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        });
+
+    eprintln!("Fuzzing {} with seed {}", target, seed);
+
+    let speller = if target == "suggest" {
+        Some(
+            SpellerArchive::new(input)
+                .expect("speller archive")
+                .speller(),
+        )
+    } else {
+        None
+    };
+    let seed_input = if speller.is_none() {
+        std::fs::read(input).expect("read seed input")
+    } else {
+        vec![]
+    };
+    let cfg = SpellerConfig {
+        n_best: Some(5),
+        max_weight: Some(50.0),
+        ..SpellerConfig::default()
+    };
+
+    // Panics are expected and reported below, so keep the default hook quiet.
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut rng = Rng::new(seed);
+    let mut crashes = 0;
+
+    for iteration in 0..iterations {
+        let data = match speller {
+            Some(_) => random_word(&mut rng).into_bytes(),
+            None => mutate(&mut rng, &seed_input),
+        };
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| match speller {
+            Some(ref speller) => check_suggest(speller, &cfg, std::str::from_utf8(&data).unwrap()),
+            None if target == "zhfst" => check_zhfst(&data),
+            None => check_hfst(&data),
+        }));
+
+        if result.is_err() {
+            crashes += 1;
+            let path = save_crash(out_dir, iteration, &data);
+            eprintln!(
+                "Iteration {}: panicked, input saved to {:?}",
+                iteration, path
+            );
+        }
+    }
+
+    eprintln!("{} iterations, {} panics", iterations, crashes);
+
+    if crashes > 0 {
+        std::process::exit(1);
+    }
+}
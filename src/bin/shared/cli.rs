@@ -0,0 +1,56 @@
+//! Command line handling shared by the servers, included by each with
+//! `#[path]`. Bad values end the process with a message, as they would in
+//! each server's `main`.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::ArgMatches;
+
+use divvunspell::archive::repository::SpellerRepository;
+use divvunspell::config::CliConfig;
+use divvunspell::memory::MemoryPolicy;
+
+pub fn parse_arg<T: FromStr>(matches: &ArgMatches, name: &str) -> Option<T> {
+    matches.value_of(name).map(|v| {
+        v.parse::<T>().unwrap_or_else(|_| {
+            eprintln!("Invalid value for --{}: {}", name, v);
+            std::process::exit(1);
+        })
+    })
+}
+
+/// Archives from `--archive TAG=PATH`, or every archive in the user config.
+/// `--preload` and `--paranoid` set the memory policy and `--memory-budget`
+/// the budget in megabytes, for the servers that take them.
+pub fn load_repository(matches: &ArgMatches, config: &CliConfig) -> SpellerRepository {
+    let paths: Vec<(String, PathBuf)> = match matches.values_of("archive") {
+        Some(values) => values
+            .map(|v| match v.find('=') {
+                Some(i) => (v[..i].to_string(), PathBuf::from(&v[i + 1..])),
+                None => {
+                    eprintln!("Expected TAG=PATH for --archive: {}", v);
+                    std::process::exit(1);
+                }
+            })
+            .collect(),
+        None => config
+            .archives
+            .iter()
+            .map(|(tag, path)| (tag.clone(), path.clone()))
+            .collect(),
+    };
+
+    let mut policy = if matches.is_present("preload") {
+        MemoryPolicy::server()
+    } else {
+        MemoryPolicy::default()
+    };
+    policy.checked_tables = matches.is_present("paranoid");
+    let memory_budget = parse_arg::<usize>(matches, "memory-budget").map(|mb| mb * 1024 * 1024);
+
+    SpellerRepository::from_paths(paths, policy, memory_budget).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
@@ -0,0 +1,254 @@
+#![cfg(feature = "binaries")]
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use clap::{App, AppSettings, Arg};
+use divvunspell::archive::SpellerArchive;
+use divvunspell::errmodel::{EditCounts, ErrorModel, TrainConfig, WeightedEdit};
+use divvunspell::transducer::Transducer;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const EDITS_SHOWN: usize = 10;
+
+fn parse_arg<T: std::str::FromStr>(matches: &clap::ArgMatches, name: &str, default: T) -> T {
+    match matches.value_of(name) {
+        Some(v) => v.parse::<T>().unwrap_or_else(|_| {
+            eprintln!("Invalid value for --{}: {}", name, v);
+            std::process::exit(1);
+        }),
+        None => default,
+    }
+}
+
+/// Reads `typo<TAB>correction[<TAB>count]` rows into `counts`.
+fn load_pairs(
+    path: &str,
+    counts: &mut EditCounts,
+    cfg: &TrainConfig,
+) -> Result<(), Box<dyn Error>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .delimiter(b'\t')
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)?;
+
+    for result in rdr.records() {
+        let record = result?;
+
+        let (typo, correction) = match (record.get(0), record.get(1)) {
+            (Some(a), Some(b)) if !a.is_empty() && !b.is_empty() => (a, b),
+            _ => continue,
+        };
+
+        let count = match record.get(2) {
+            Some(v) => v.trim().parse::<u64>()?,
+            None => 1,
+        };
+
+        counts.add(typo, correction, count, cfg);
+    }
+
+    Ok(())
+}
+
+fn edit_label(edit: &WeightedEdit) -> String {
+    let show = |c: Option<char>| c.map(|c| c.to_string()).unwrap_or_else(|| "ε".into());
+    format!("{}:{}", show(edit.input), show(edit.output))
+}
+
+fn write_weights(path: &str, model: &ErrorModel) -> Result<(), Box<dyn Error>> {
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "# input\toutput\tcount\tweight")?;
+
+    for edit in model.edits() {
+        let show = |c: Option<char>| c.map(|c| c.to_string()).unwrap_or_default();
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}",
+            show(edit.input),
+            show(edit.output),
+            edit.count,
+            edit.weight
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Copies `base` to `output`, replacing its error model with `errmodel`.
+fn write_archive(
+    base: &str,
+    output: &str,
+    errmodel_id: &str,
+    errmodel: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let mut archive = ZipArchive::new(File::open(base)?)?;
+    let mut zip = ZipWriter::new(File::create(output)?);
+
+    // Stored, so the archive can be memory mapped directly
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_string();
+
+        zip.start_file(name.clone(), options)?;
+
+        if name == errmodel_id {
+            zip.write_all(errmodel)?;
+        } else {
+            io::copy(&mut file, &mut zip)?;
+        }
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let matches = App::new("train-errmodel")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Estimate an error model from (typo, correction) pairs")
+        .arg(
+            Arg::with_name("PAIRS")
+                .required(true)
+                .help("TSV file of typo, correction and an optional count per row"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("HFST")
+                .takes_value(true)
+                .required(true)
+                .help("Write the error model transducer to this file"),
+        )
+        .arg(
+            Arg::with_name("zhfst")
+                .short("z")
+                .long("zhfst")
+                .value_name("ZHFST")
+                .takes_value(true)
+                .help("Pass through every letter of this speller's acceptor unchanged"),
+        )
+        .arg(
+            Arg::with_name("zhfst-output")
+                .long("zhfst-output")
+                .value_name("ZHFST")
+                .takes_value(true)
+                .requires("zhfst")
+                .help("Also write a copy of --zhfst using the new error model"),
+        )
+        .arg(
+            Arg::with_name("weights-output")
+                .long("weights-output")
+                .value_name("TSV")
+                .takes_value(true)
+                .help("Write the estimated edit weights as TSV"),
+        )
+        .arg(
+            Arg::with_name("max-edits")
+                .long("max-edits")
+                .takes_value(true)
+                .help("Edits allowed per word (default: 2)"),
+        )
+        .arg(
+            Arg::with_name("max-distance")
+                .long("max-distance")
+                .takes_value(true)
+                .help("Skip pairs with a larger edit distance (default: 4)"),
+        )
+        .arg(
+            Arg::with_name("min-count")
+                .long("min-count")
+                .takes_value(true)
+                .help("Leave out edits seen fewer times (default: 1)"),
+        )
+        .arg(
+            Arg::with_name("smoothing")
+                .long("smoothing")
+                .takes_value(true)
+                .help("Additive smoothing for edit counts (default: 1.0)"),
+        )
+        .get_matches();
+
+    let defaults = TrainConfig::default();
+    let cfg = TrainConfig {
+        max_edits: parse_arg(&matches, "max-edits", defaults.max_edits),
+        max_distance: parse_arg(&matches, "max-distance", defaults.max_distance),
+        min_count: parse_arg(&matches, "min-count", defaults.min_count),
+        smoothing: parse_arg(&matches, "smoothing", defaults.smoothing),
+    };
+
+    let mut counts = EditCounts::new();
+    load_pairs(matches.value_of("PAIRS").unwrap(), &mut counts, &cfg)?;
+    eprintln!(
+        "Counted {} pairs, skipped {} identical or too distant",
+        counts.pairs(),
+        counts.skipped()
+    );
+
+    let archive = match matches.value_of("zhfst") {
+        Some(path) => Some(SpellerArchive::new(path)?),
+        None => None,
+    };
+
+    if let Some(ref archive) = archive {
+        let speller = archive.speller();
+        let alphabet = speller.lexicon().alphabet();
+
+        for symbol in alphabet
+            .key_table()
+            .iter()
+            .take(alphabet.initial_symbol_count() as usize)
+        {
+            let mut chars = symbol.chars();
+            if let (Some(ch), None) = (chars.next(), chars.next()) {
+                if ch.is_alphabetic() {
+                    counts.add_alphabet_char(ch);
+                }
+            }
+        }
+    }
+
+    let model = ErrorModel::train(&counts, &cfg);
+    eprintln!(
+        "{} edits over an alphabet of {} characters",
+        model.edits().len(),
+        model.alphabet().len()
+    );
+
+    for edit in model.edits().iter().take(EDITS_SHOWN) {
+        eprintln!(
+            "  {:<8} {:>8} {:>8.3}",
+            edit_label(edit),
+            edit.count,
+            edit.weight
+        );
+    }
+
+    let mut buf = vec![];
+    model.write_hfst(&mut buf)?;
+    std::fs::write(matches.value_of("output").unwrap(), &buf)?;
+
+    if let Some(path) = matches.value_of("weights-output") {
+        write_weights(path, &model)?;
+    }
+
+    if let (Some(archive), Some(output)) = (archive, matches.value_of("zhfst-output")) {
+        write_archive(
+            matches.value_of("zhfst").unwrap(),
+            output,
+            &archive.metadata().errmodel.id,
+            &buf,
+        )?;
+        eprintln!("Wrote {}", output);
+    }
+
+    Ok(())
+}
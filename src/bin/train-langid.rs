@@ -0,0 +1,67 @@
+#![cfg(feature = "binaries")]
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use clap::{App, AppSettings, Arg};
+use divvunspell::archive::langid::{LanguageIdentifier, MAX_NGRAM, PROFILE_SIZE};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let matches = App::new("train-langid")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Build language identification profiles from sample text")
+        .arg(
+            Arg::with_name("SAMPLES")
+                .required(true)
+                .multiple(true)
+                .help("LANG=FILE pairs of a language tag and plain text in that language"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("TXT")
+                .takes_value(true)
+                .required(true)
+                .help("Write the profiles to this file, such as data/langid.txt"),
+        )
+        .get_matches();
+
+    let mut samples = vec![];
+    for arg in matches.values_of("SAMPLES").unwrap() {
+        let mut parts = arg.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(lang), Some(path)) if !lang.is_empty() => {
+                samples.push((lang.to_string(), std::fs::read_to_string(path)?));
+            }
+            _ => {
+                eprintln!("Expected LANG=FILE, got {}", arg);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let id = LanguageIdentifier::train(samples.iter().map(|(l, t)| (&**l, &**t)));
+
+    let mut w = BufWriter::new(File::create(matches.value_of("output").unwrap())?);
+    writeln!(
+        w,
+        "# Language identification profiles: lang<TAB>n-gram, most frequent first."
+    )?;
+    writeln!(
+        w,
+        "# Up to {} n-grams of 1 to {} letters per language; _ marks word ends.",
+        PROFILE_SIZE, MAX_NGRAM
+    )?;
+    writeln!(w, "# Generated by train-langid.")?;
+    id.write(&mut w)?;
+    w.flush()?;
+
+    eprintln!(
+        "Wrote profiles for {}",
+        id.languages().collect::<Vec<_>>().join(", ")
+    );
+    Ok(())
+}
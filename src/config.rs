@@ -0,0 +1,98 @@
+//! User configuration shared by the command line tools.
+//!
+//! Read from `$DIVVUNSPELL_CONFIG`, or `divvunspell/config.toml` under
+//! `$XDG_CONFIG_HOME` (`~/.config` by default):
+//!
+//! ```toml
+//! default_language = "se"
+//!
+//! [archives]
+//! se = "/usr/share/voikko/4/se.zhfst"
+//! sma = "/usr/share/voikko/4/sma.zhfst"
+//!
+//! [speller]
+//! n_best = 10
+//! max_weight = 10000.0
+//! ```
+//!
+//! Every key is optional; `[speller]` takes any `SpellerConfig` field.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::speller::SpellerConfig;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+}
+
+impl std::error::Error for ConfigError {}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CliConfig {
+    /// Language used when no archive is given on the command line.
+    pub default_language: Option<String>,
+    /// ZHFST archive for each language tag.
+    pub archives: BTreeMap<String, PathBuf>,
+    /// Defaults for suggestions, overridden by command line flags.
+    pub speller: Option<SpellerConfig>,
+}
+
+impl CliConfig {
+    /// Where the configuration is read from, if a location can be determined.
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("DIVVUNSPELL_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(config_dir.join("divvunspell").join("config.toml"))
+    }
+
+    /// Loads the configuration, or the empty default if there is no file.
+    pub fn load() -> Result<CliConfig, ConfigError> {
+        match CliConfig::path() {
+            Some(path) if path.exists() => CliConfig::from_path(&path),
+            _ => Ok(CliConfig::default()),
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Result<CliConfig, ConfigError> {
+        let data = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        data.parse()
+    }
+
+    /// The archive for `language`, or for the default language if `None`.
+    pub fn archive(&self, language: Option<&str>) -> Option<&Path> {
+        let language = language.or(self.default_language.as_deref())?;
+        self.archives.get(language).map(|x| &**x)
+    }
+
+    pub fn speller_config(&self) -> SpellerConfig {
+        self.speller.clone().unwrap_or_else(SpellerConfig::default)
+    }
+}
+
+impl std::str::FromStr for CliConfig {
+    type Err = ConfigError;
+
+    fn from_str(data: &str) -> Result<CliConfig, ConfigError> {
+        toml::from_str(data).map_err(ConfigError::Parse)
+    }
+}
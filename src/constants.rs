@@ -9,7 +9,7 @@ mod tests {
     #[test]
     fn test_INDEX_TABLE_SIZE() {
         use crate::types::*;
-        use std::mem;
+        use core::mem;
 
         let c = mem::size_of::<SymbolNumber>() + mem::size_of::<TransitionTableIndex>();
 
@@ -19,7 +19,7 @@ mod tests {
     #[test]
     fn test_TRANS_TABLE_SIZE() {
         use crate::types::*;
-        use std::mem;
+        use core::mem;
 
         let c = 2 * mem::size_of::<SymbolNumber>()
             + mem::size_of::<TransitionTableIndex>()
@@ -0,0 +1,289 @@
+//! Client for `divvunspell-daemon`, which keeps archives loaded between
+//! requests so short-lived tools don't pay for opening them every time.
+//!
+//! The daemon listens on a Unix domain socket, or a named pipe on Windows.
+//! Each message is JSON preceded by its length as a 4-byte little-endian
+//! integer, and every request gets exactly one response.
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::speller::issue::SpellingIssue;
+use crate::speller::suggestion::Suggestion;
+
+#[cfg(unix)]
+type Stream = std::os::unix::net::UnixStream;
+#[cfg(windows)]
+type Stream = std::fs::File;
+
+/// Messages larger than this are refused rather than allocated.
+pub const MAX_MESSAGE_SIZE: u32 = 16 * 1024 * 1024;
+
+#[cfg(windows)]
+pub const DEFAULT_PIPE: &str = r"\\.\pipe\divvunspell";
+
+/// `$DIVVUNSPELL_SOCKET`, or `divvunspell.sock` in `$XDG_RUNTIME_DIR`,
+/// falling back to a per-user socket in the temporary directory.
+#[cfg(unix)]
+pub fn default_socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("DIVVUNSPELL_SOCKET") {
+        return PathBuf::from(path);
+    }
+
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("divvunspell.sock"),
+        _ => {
+            let user = std::env::var("USER").unwrap_or_else(|_| "default".into());
+            std::env::temp_dir().join(format!("divvunspell-{}.sock", user))
+        }
+    }
+}
+
+/// `$DIVVUNSPELL_SOCKET`, or `DEFAULT_PIPE`.
+#[cfg(windows)]
+pub fn default_socket_path() -> PathBuf {
+    std::env::var_os("DIVVUNSPELL_SOCKET")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_PIPE))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Request {
+    Languages,
+    IsCorrect {
+        lang: String,
+        word: String,
+    },
+    Suggest {
+        lang: String,
+        word: String,
+        #[serde(default)]
+        n_best: Option<usize>,
+        #[serde(default)]
+        time_budget_ms: Option<u64>,
+    },
+    Check {
+        lang: String,
+        text: String,
+        #[serde(default)]
+        n_best: Option<usize>,
+        #[serde(default)]
+        time_budget_ms: Option<u64>,
+        /// Names the text across requests, such as an editor's file path,
+        /// so that checking it again only looks up the words that changed.
+        #[serde(default)]
+        document: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Response {
+    Languages { languages: Vec<String> },
+    IsCorrect { correct: bool },
+    Suggest { suggestions: Vec<Suggestion> },
+    Check { errors: Vec<SpellingIssue> },
+    Error { message: String },
+}
+
+#[derive(Debug)]
+pub enum DaemonError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    TooLarge(u32),
+    /// The daemon could not handle the request.
+    Remote(String),
+    UnexpectedResponse,
+}
+
+impl std::error::Error for DaemonError {}
+
+impl std::fmt::Display for DaemonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            DaemonError::Io(e) => write!(f, "{}", e),
+            DaemonError::Json(e) => write!(f, "{}", e),
+            DaemonError::TooLarge(n) => write!(f, "Message of {} bytes is too large", n),
+            DaemonError::Remote(e) => write!(f, "{}", e),
+            DaemonError::UnexpectedResponse => write!(f, "Unexpected response from daemon"),
+        }
+    }
+}
+
+pub fn write_message<W: Write, T: serde::Serialize>(
+    writer: &mut W,
+    message: &T,
+) -> Result<(), DaemonError> {
+    let data = serde_json::to_vec(message).map_err(DaemonError::Json)?;
+    writer
+        .write_u32::<LittleEndian>(data.len() as u32)
+        .map_err(DaemonError::Io)?;
+    writer.write_all(&data).map_err(DaemonError::Io)?;
+    writer.flush().map_err(DaemonError::Io)
+}
+
+/// Reads the next message, or `None` if the stream ended cleanly before it.
+pub fn read_message<R: Read, T: serde::de::DeserializeOwned>(
+    reader: &mut R,
+) -> Result<Option<T>, DaemonError> {
+    let len = match reader.read_u32::<LittleEndian>() {
+        Ok(v) => v,
+        Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(DaemonError::Io(e)),
+    };
+
+    if len > MAX_MESSAGE_SIZE {
+        return Err(DaemonError::TooLarge(len));
+    }
+
+    let mut data = vec![0u8; len as usize];
+    reader.read_exact(&mut data).map_err(DaemonError::Io)?;
+    serde_json::from_slice(&data)
+        .map(Some)
+        .map_err(DaemonError::Json)
+}
+
+/// A connection to the daemon. Requests are answered in order, so one
+/// connection can be reused for as many as needed.
+pub struct Client {
+    stream: Stream,
+}
+
+impl Client {
+    #[cfg(unix)]
+    pub fn connect<P: AsRef<std::path::Path>>(path: P) -> Result<Client, DaemonError> {
+        let stream = Stream::connect(path).map_err(DaemonError::Io)?;
+        Ok(Client { stream })
+    }
+
+    #[cfg(windows)]
+    pub fn connect<P: AsRef<std::path::Path>>(path: P) -> Result<Client, DaemonError> {
+        let stream = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(DaemonError::Io)?;
+        Ok(Client { stream })
+    }
+
+    pub fn connect_default() -> Result<Client, DaemonError> {
+        Client::connect(default_socket_path())
+    }
+
+    pub fn request(&mut self, request: &Request) -> Result<Response, DaemonError> {
+        write_message(&mut self.stream, request)?;
+
+        match read_message(&mut self.stream)? {
+            Some(Response::Error { message }) => Err(DaemonError::Remote(message)),
+            Some(response) => Ok(response),
+            None => Err(DaemonError::UnexpectedResponse),
+        }
+    }
+
+    /// Tags of the languages the daemon has loaded.
+    pub fn languages(&mut self) -> Result<Vec<String>, DaemonError> {
+        match self.request(&Request::Languages)? {
+            Response::Languages { languages } => Ok(languages),
+            _ => Err(DaemonError::UnexpectedResponse),
+        }
+    }
+
+    pub fn is_correct(&mut self, lang: &str, word: &str) -> Result<bool, DaemonError> {
+        match self.request(&Request::IsCorrect {
+            lang: lang.to_string(),
+            word: word.to_string(),
+        })? {
+            Response::IsCorrect { correct } => Ok(correct),
+            _ => Err(DaemonError::UnexpectedResponse),
+        }
+    }
+
+    /// `None` uses the daemon's defaults.
+    pub fn suggest(
+        &mut self,
+        lang: &str,
+        word: &str,
+        n_best: Option<usize>,
+    ) -> Result<Vec<Suggestion>, DaemonError> {
+        match self.request(&Request::Suggest {
+            lang: lang.to_string(),
+            word: word.to_string(),
+            n_best,
+            time_budget_ms: None,
+        })? {
+            Response::Suggest { suggestions } => Ok(suggestions),
+            _ => Err(DaemonError::UnexpectedResponse),
+        }
+    }
+
+    pub fn check(
+        &mut self,
+        lang: &str,
+        text: &str,
+        n_best: Option<usize>,
+    ) -> Result<Vec<SpellingIssue>, DaemonError> {
+        match self.request(&Request::Check {
+            lang: lang.to_string(),
+            text: text.to_string(),
+            n_best,
+            time_budget_ms: None,
+            document: None,
+        })? {
+            Response::Check { errors } => Ok(errors),
+            _ => Err(DaemonError::UnexpectedResponse),
+        }
+    }
+
+    /// As `check`, for a version of `document`: the daemon remembers what it
+    /// found in the last version and only looks up the words that changed.
+    pub fn check_document(
+        &mut self,
+        lang: &str,
+        document: &str,
+        text: &str,
+        n_best: Option<usize>,
+    ) -> Result<Vec<SpellingIssue>, DaemonError> {
+        match self.request(&Request::Check {
+            lang: lang.to_string(),
+            text: text.to_string(),
+            n_best,
+            time_budget_ms: None,
+            document: Some(document.to_string()),
+        })? {
+            Response::Check { errors } => Ok(errors),
+            _ => Err(DaemonError::UnexpectedResponse),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framing() {
+        let mut buf = vec![];
+        let request = Request::IsCorrect {
+            lang: "se".into(),
+            word: "sámegiella".into(),
+        };
+        write_message(&mut buf, &request).unwrap();
+        write_message(&mut buf, &Request::Languages).unwrap();
+
+        let mut reader = &buf[..];
+        match read_message(&mut reader).unwrap() {
+            Some(Request::IsCorrect { lang, word }) => {
+                assert_eq!(lang, "se");
+                assert_eq!(word, "sámegiella");
+            }
+            other => panic!("{:?}", other),
+        }
+        match read_message(&mut reader).unwrap() {
+            Some(Request::Languages) => {}
+            other => panic!("{:?}", other),
+        }
+        assert!(read_message::<_, Request>(&mut reader).unwrap().is_none());
+    }
+}
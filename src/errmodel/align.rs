@@ -0,0 +1,118 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// A single edit needed to turn the input (typo) into the expected word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Edit {
+    Substitution { from: char, to: char },
+    Insertion { to: char },
+    Deletion { from: char },
+}
+
+impl std::fmt::Display for Edit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Edit::Substitution { from, to } => write!(f, "{}→{}", from, to),
+            Edit::Insertion { to } => write!(f, "+{}", to),
+            Edit::Deletion { from } => write!(f, "-{}", from),
+        }
+    }
+}
+
+fn distance_matrix(a: &[char], b: &[char]) -> Vec<Vec<usize>> {
+    let mut m = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in m.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for (j, cell) in m[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            m[i][j] = (m[i - 1][j] + 1)
+                .min(m[i][j - 1] + 1)
+                .min(m[i - 1][j - 1] + cost);
+        }
+    }
+
+    m
+}
+
+/// Character-level Levenshtein distance between a typo and its correction.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            let next = (row[j] + 1).min(row[j + 1] + 1).min(prev + cost);
+            prev = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Returns the edits of a minimal character alignment of `input` against
+/// `expected`, in word order. Matching characters are omitted.
+pub fn align(input: &str, expected: &str) -> Vec<Edit> {
+    let a: Vec<char> = input.chars().collect();
+    let b: Vec<char> = expected.chars().collect();
+    let m = distance_matrix(&a, &b);
+
+    let mut edits = vec![];
+    let (mut i, mut j) = (a.len(), b.len());
+
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && a[i - 1] == b[j - 1] && m[i][j] == m[i - 1][j - 1] {
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && j > 0 && m[i][j] == m[i - 1][j - 1] + 1 {
+            edits.push(Edit::Substitution {
+                from: a[i - 1],
+                to: b[j - 1],
+            });
+            i -= 1;
+            j -= 1;
+        } else if j > 0 && m[i][j] == m[i][j - 1] + 1 {
+            edits.push(Edit::Insertion { to: b[j - 1] });
+            j -= 1;
+        } else {
+            edits.push(Edit::Deletion { from: a[i - 1] });
+            i -= 1;
+        }
+    }
+
+    edits.reverse();
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn align_edits() {
+        assert_eq!(levenshtein("giella", "giella"), 0);
+        assert_eq!(levenshtein("gilla", "giella"), 1);
+        assert_eq!(align("gilla", "giella"), vec![Edit::Insertion { to: 'e' }]);
+        assert_eq!(
+            align("gáella", "giela"),
+            vec![
+                Edit::Substitution {
+                    from: 'á', to: 'i'
+                },
+                Edit::Deletion { from: 'l' }
+            ]
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! Writes an error model as an HFST optimized-lookup transducer.
+//!
+//! The transducer has one state per number of edits made so far. Every state
+//! is final and passes each alphabet symbol through at no cost; an edit moves
+//! to the next state, and the last state allows no further edits. All states
+//! are laid out in the index table, one slot per symbol, which wastes some
+//! space but keeps lookups simple for an alphabet this size.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::fmt;
+use std::io::{self, Write};
+
+use super::WeightedEdit;
+use crate::constants::TARGET_TABLE;
+use crate::types::{SymbolNumber, TransitionTableIndex, Weight};
+
+const HEADER: &[u8] = b"version\x003.3\x00type\x00HFST_OLW\x00";
+
+pub(crate) struct Arc {
+    pub input: SymbolNumber,
+    pub output: SymbolNumber,
+    pub target: TransitionTableIndex,
+    pub weight: Weight,
+}
+
+pub(super) fn write<W: Write>(
+    w: W,
+    alphabet: &[char],
+    edits: &[WeightedEdit],
+    max_edits: u8,
+) -> io::Result<()> {
+    // Symbol 0 is epsilon, alphabet characters follow in order.
+    let symbol_count = alphabet.len() + 1;
+    if symbol_count >= u16::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "alphabet too large",
+        ));
+    }
+
+    let symbol = |ch: Option<char>| -> SymbolNumber {
+        match ch {
+            Some(ch) => alphabet.binary_search(&ch).map(|i| i as u16 + 1).unwrap(),
+            None => 0,
+        }
+    };
+
+    let states = u32::from(max_edits) + 1;
+    let block = symbol_count as u32 + 1;
+    let state_index = |state: u32| state * block;
+
+    // (index table slot, first transition) for every symbol with arcs
+    let mut index: Vec<(SymbolNumber, TransitionTableIndex)> =
+        vec![(u16::MAX, u32::MAX); (states * block) as usize];
+    let mut transitions: Vec<Option<Arc>> = vec![];
+    let mut arc_count = 0;
+
+    for state in 0..states {
+        // Final, with weight 0
+        index[state_index(state) as usize] = (u16::MAX, 0.0f32.to_bits());
+
+        for sym in 0..symbol_count as SymbolNumber {
+            let mut arcs = vec![];
+
+            if sym != 0 {
+                arcs.push(Arc {
+                    input: sym,
+                    output: sym,
+                    target: state_index(state),
+                    weight: 0.0,
+                });
+            }
+
+            if state + 1 < states {
+                for edit in edits.iter().filter(|e| symbol(e.input) == sym) {
+                    arcs.push(Arc {
+                        input: sym,
+                        output: symbol(edit.output),
+                        target: state_index(state + 1),
+                        weight: edit.weight,
+                    });
+                }
+            }
+
+            if arcs.is_empty() {
+                continue;
+            }
+
+            index[(state_index(state) + 1 + u32::from(sym)) as usize] =
+                (sym, TARGET_TABLE + transitions.len() as u32);
+            arc_count += arcs.len();
+            transitions.extend(arcs.into_iter().map(Some));
+            // Ends the run of arcs for this symbol
+            transitions.push(None);
+        }
+    }
+
+    let has_insertions = edits.iter().any(|e| e.input.is_none());
+
+    // Weighted, deterministic, input deterministic, minimized, cyclic,
+    // epsilon-epsilon, input epsilon, input epsilon cycles, unweighted
+    // input epsilon cycles
+    let properties = [
+        true,
+        false,
+        false,
+        false,
+        true,
+        false,
+        has_insertions,
+        false,
+        false,
+    ];
+
+    write_tables(
+        w,
+        alphabet,
+        &index,
+        &transitions,
+        states,
+        arc_count,
+        properties,
+    )
+}
+
+/// Writes a transducer from its tables: `index` slots as (input symbol,
+/// target), and transitions where `None` ends a run of arcs. Symbol 0 is
+/// epsilon and the symbols of `alphabet`, letters or multicharacter
+/// symbols, follow it in order.
+pub(crate) fn write_tables<W: Write, S: fmt::Display>(
+    mut w: W,
+    alphabet: &[S],
+    index: &[(SymbolNumber, TransitionTableIndex)],
+    transitions: &[Option<Arc>],
+    states: u32,
+    arc_count: usize,
+    properties: [bool; 9],
+) -> io::Result<()> {
+    let symbol_count = alphabet.len() + 1;
+
+    w.write_all(b"HFST\0")?;
+    w.write_u16::<LittleEndian>(HEADER.len() as u16)?;
+    w.write_u8(0)?;
+    w.write_all(HEADER)?;
+
+    w.write_u16::<LittleEndian>(symbol_count as u16)?;
+    w.write_u16::<LittleEndian>(symbol_count as u16)?;
+    w.write_u32::<LittleEndian>(index.len() as u32)?;
+    w.write_u32::<LittleEndian>(transitions.len() as u32)?;
+    w.write_u32::<LittleEndian>(states)?;
+    w.write_u32::<LittleEndian>(arc_count as u32)?;
+
+    for &p in &properties {
+        w.write_u32::<LittleEndian>(p as u32)?;
+    }
+
+    w.write_all(b"@_EPSILON_SYMBOL_@\0")?;
+    for symbol in alphabet {
+        write!(w, "{}", symbol)?;
+        w.write_u8(0)?;
+    }
+
+    for &(sym, target) in index {
+        w.write_u16::<LittleEndian>(sym)?;
+        w.write_u32::<LittleEndian>(target)?;
+    }
+
+    for arc in transitions {
+        match arc {
+            Some(arc) => {
+                w.write_u16::<LittleEndian>(arc.input)?;
+                w.write_u16::<LittleEndian>(arc.output)?;
+                w.write_u32::<LittleEndian>(arc.target)?;
+                w.write_f32::<LittleEndian>(arc.weight)?;
+            }
+            None => {
+                w.write_u16::<LittleEndian>(u16::MAX)?;
+                w.write_u16::<LittleEndian>(u16::MAX)?;
+                w.write_u32::<LittleEndian>(u32::MAX)?;
+                w.write_f32::<LittleEndian>(0.0)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::{test_buf, HfstTransducer, Transducer};
+
+    #[test]
+    fn loads() {
+        let alphabet = ['a', 'b'];
+        let edits = [WeightedEdit {
+            input: Some('a'),
+            output: Some('b'),
+            count: 1,
+            weight: 2.5,
+        }];
+
+        let mut buf = vec![];
+        write(&mut buf, &alphabet, &edits, 1).unwrap();
+
+        let t = HfstTransducer::from_mapped_memory(test_buf(&buf)).unwrap();
+
+        assert!(t.is_weighted());
+        assert_eq!(t.alphabet().key_table().len(), 3);
+        assert!(t.is_final(0));
+
+        // "a" from the start state: identity to state 0, then a:b to state 1
+        assert!(t.has_transitions(1, Some(1)));
+        let next = t.next(0, 1).unwrap();
+        let identity = t.take_non_epsilons(next, 1).unwrap();
+        assert_eq!((identity.symbol(), identity.target()), (Some(1), Some(0)));
+        let edit = t.take_non_epsilons(next + 1, 1).unwrap();
+        assert_eq!((edit.symbol(), edit.target()), (Some(2), Some(4)));
+        assert_eq!(edit.weight(), Some(2.5));
+        assert!(t.take_non_epsilons(next + 2, 1).is_none());
+
+        // No edits left in state 1
+        let next = t.next(4, 1).unwrap();
+        assert!(t.take_non_epsilons(next + 1, 1).is_none());
+    }
+}
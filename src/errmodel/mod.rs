@@ -0,0 +1,207 @@
+//! Estimating error models from (typo, correction) pairs.
+//!
+//! Pairs are aligned character by character, the edits are counted, and each
+//! observed edit is given the weight `-ln P(edit)`, smoothed with add-α. The
+//! result can be written as an HFST optimized-lookup transducer and used as
+//! the `errmodel` of a speller.
+
+pub mod align;
+pub(crate) mod hfst;
+pub mod typos;
+
+use hashbrown::HashMap;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use self::align::{align, levenshtein, Edit};
+use crate::types::Weight;
+
+#[derive(Debug, Clone)]
+pub struct TrainConfig {
+    /// Edits allowed per word by the generated transducer.
+    pub max_edits: u8,
+    /// Pairs further apart than this are assumed to be noise and skipped.
+    pub max_distance: usize,
+    /// Edits seen fewer times than this are left out of the model.
+    pub min_count: u32,
+    /// Additive smoothing applied to every count.
+    pub smoothing: f64,
+}
+
+impl Default for TrainConfig {
+    fn default() -> TrainConfig {
+        TrainConfig {
+            max_edits: 2,
+            max_distance: 4,
+            min_count: 1,
+            smoothing: 1.0,
+        }
+    }
+}
+
+/// Edit and character counts accumulated over a set of pairs.
+#[derive(Debug, Default)]
+pub struct EditCounts {
+    edits: HashMap<Edit, u64>,
+    /// Occurrences of each character in the typos, i.e. the chances it had to
+    /// be substituted or deleted.
+    chars: HashMap<char, u64>,
+    /// Places a character could have been inserted into the typos.
+    positions: u64,
+    alphabet: BTreeSet<char>,
+    pairs: u64,
+    skipped: u64,
+}
+
+impl EditCounts {
+    pub fn new() -> EditCounts {
+        EditCounts::default()
+    }
+
+    /// Counts one (typo, correction) pair `count` times. Returns false if the
+    /// pair was skipped as identical or too distant.
+    pub fn add(&mut self, typo: &str, correction: &str, count: u64, cfg: &TrainConfig) -> bool {
+        if typo == correction || levenshtein(typo, correction) > cfg.max_distance {
+            self.skipped += count;
+            return false;
+        }
+
+        for ch in typo.chars().chain(correction.chars()) {
+            self.alphabet.insert(ch);
+        }
+
+        for ch in typo.chars() {
+            *self.chars.entry(ch).or_insert(0) += count;
+        }
+        self.positions += (typo.chars().count() as u64 + 1) * count;
+
+        for edit in align(typo, correction) {
+            *self.edits.entry(edit).or_insert(0) += count;
+        }
+
+        self.pairs += count;
+        true
+    }
+
+    /// Makes sure the model passes `ch` through unchanged even if no pair
+    /// contained it.
+    pub fn add_alphabet_char(&mut self, ch: char) {
+        self.alphabet.insert(ch);
+    }
+
+    pub fn pairs(&self) -> u64 {
+        self.pairs
+    }
+
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+/// A weighted edit of the error model. `None` is epsilon: an input of `None`
+/// inserts `output`, an output of `None` deletes `input`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WeightedEdit {
+    pub input: Option<char>,
+    pub output: Option<char>,
+    pub count: u64,
+    pub weight: Weight,
+}
+
+#[derive(Debug)]
+pub struct ErrorModel {
+    alphabet: Vec<char>,
+    edits: Vec<WeightedEdit>,
+    max_edits: u8,
+}
+
+impl ErrorModel {
+    pub fn train(counts: &EditCounts, cfg: &TrainConfig) -> ErrorModel {
+        let alphabet: Vec<char> = counts
+            .alphabet
+            .iter()
+            .cloned()
+            .filter(|c| *c != '\0')
+            .collect();
+        let alpha = cfg.smoothing;
+        let outcomes = alphabet.len() as f64 + 1.0;
+
+        let weight = |count: u64, total: u64| -> Weight {
+            let p = (count as f64 + alpha) / (total as f64 + alpha * outcomes);
+            (-p.ln()) as Weight
+        };
+
+        let mut edits: Vec<WeightedEdit> = counts
+            .edits
+            .iter()
+            .filter(|(_, &count)| count >= u64::from(cfg.min_count))
+            .map(|(edit, &count)| {
+                let (input, output, total) = match *edit {
+                    Edit::Substitution { from, to } => (Some(from), Some(to), counts.chars[&from]),
+                    Edit::Deletion { from } => (Some(from), None, counts.chars[&from]),
+                    Edit::Insertion { to } => (None, Some(to), counts.positions),
+                };
+
+                WeightedEdit {
+                    input,
+                    output,
+                    count,
+                    weight: weight(count, total),
+                }
+            })
+            .filter(|e| e.input != Some('\0') && e.output != Some('\0'))
+            .collect();
+
+        edits.sort_by(|a, b| {
+            a.weight
+                .partial_cmp(&b.weight)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| (a.input, a.output).cmp(&(b.input, b.output)))
+        });
+
+        ErrorModel {
+            alphabet,
+            edits,
+            max_edits: cfg.max_edits,
+        }
+    }
+
+    /// Edits in the model, cheapest first.
+    pub fn edits(&self) -> &[WeightedEdit] {
+        &self.edits
+    }
+
+    pub fn alphabet(&self) -> &[char] {
+        &self.alphabet
+    }
+
+    /// Writes the model as an HFST optimized-lookup transducer, loadable with
+    /// `HfstTransducer::from_mapped_memory`.
+    pub fn write_hfst<W: Write>(&self, writer: W) -> io::Result<()> {
+        hfst::write(writer, &self.alphabet, &self.edits, self.max_edits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn train() {
+        let cfg = TrainConfig::default();
+        let mut counts = EditCounts::new();
+
+        assert!(counts.add("gilla", "giella", 3, &cfg));
+        assert!(counts.add("giela", "giella", 1, &cfg));
+        assert!(!counts.add("giella", "giella", 1, &cfg));
+        assert_eq!(counts.pairs(), 4);
+
+        let model = ErrorModel::train(&counts, &cfg);
+        let edits = model.edits();
+
+        assert_eq!(edits.len(), 2);
+        assert_eq!((edits[0].input, edits[0].output), (None, Some('e')));
+        assert_eq!((edits[1].input, edits[1].output), (None, Some('l')));
+        assert!(edits[0].weight < edits[1].weight);
+    }
+}
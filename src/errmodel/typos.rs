@@ -0,0 +1,352 @@
+//! Synthetic typos for languages without a curated typo corpus.
+//!
+//! Each character of a word is an opportunity for an error. Errors are drawn
+//! from keyboard slips (an adjacent key), transpositions, dropped
+//! diacritics, deletions and doubled letters, in configurable proportions.
+
+use hashbrown::HashMap;
+
+/// Rows of the default keyboard, top to bottom, each offset half a key to the
+/// right of the one above it.
+pub const QWERTY: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+/// Letters whose diacritic is commonly left out, and what they become.
+const DIACRITICS: &[(char, char)] = &[
+    ('á', 'a'),
+    ('à', 'a'),
+    ('â', 'a'),
+    ('ä', 'a'),
+    ('å', 'a'),
+    ('č', 'c'),
+    ('ç', 'c'),
+    ('đ', 'd'),
+    ('é', 'e'),
+    ('è', 'e'),
+    ('ê', 'e'),
+    ('ë', 'e'),
+    ('í', 'i'),
+    ('ï', 'i'),
+    ('ŋ', 'n'),
+    ('ñ', 'n'),
+    ('ó', 'o'),
+    ('ô', 'o'),
+    ('ö', 'o'),
+    ('ø', 'o'),
+    ('š', 's'),
+    ('ŧ', 't'),
+    ('ú', 'u'),
+    ('ü', 'u'),
+    ('ž', 'z'),
+    ('ʒ', 'z'),
+    ('ǯ', 'ʒ'),
+];
+
+#[derive(Debug, Clone)]
+pub struct TypoConfig {
+    /// Chance of an error at each character.
+    pub error_rate: f64,
+    /// Most errors made in one word; every typo has at least one.
+    pub max_edits: usize,
+    /// Relative weights of each kind of error.
+    pub keyboard: f64,
+    pub transposition: f64,
+    pub diacritic: f64,
+    pub deletion: f64,
+    pub insertion: f64,
+}
+
+impl Default for TypoConfig {
+    fn default() -> TypoConfig {
+        TypoConfig {
+            error_rate: 0.05,
+            max_edits: 2,
+            keyboard: 0.4,
+            transposition: 0.2,
+            diacritic: 0.2,
+            deletion: 0.1,
+            insertion: 0.1,
+        }
+    }
+}
+
+/// Which keys are next to which, for keyboard slips.
+#[derive(Debug, Clone)]
+pub struct Keyboard {
+    neighbours: HashMap<char, Vec<char>>,
+}
+
+impl Keyboard {
+    /// Builds adjacency from rows of keys laid out like `QWERTY`.
+    pub fn from_rows<S: AsRef<str>>(rows: &[S]) -> Keyboard {
+        let grid: Vec<Vec<char>> = rows
+            .iter()
+            .map(|r| r.as_ref().chars().filter(|c| !c.is_whitespace()).collect())
+            .collect();
+        let mut neighbours: HashMap<char, Vec<char>> = HashMap::new();
+
+        let key = |row: isize, col: isize| -> Option<char> {
+            if row < 0 || col < 0 {
+                return None;
+            }
+            grid.get(row as usize)
+                .and_then(|r| r.get(col as usize))
+                .cloned()
+        };
+
+        for (r, row) in grid.iter().enumerate() {
+            for (c, &ch) in row.iter().enumerate() {
+                let (r, c) = (r as isize, c as isize);
+
+                // Same row, the row above (shifted left) and the row below
+                // (shifted right).
+                let around = [
+                    (r, c - 1),
+                    (r, c + 1),
+                    (r - 1, c),
+                    (r - 1, c + 1),
+                    (r + 1, c - 1),
+                    (r + 1, c),
+                ];
+
+                let entry = neighbours.entry(ch).or_insert_with(Vec::new);
+                for &(nr, nc) in &around {
+                    if let Some(n) = key(nr, nc) {
+                        if !entry.contains(&n) {
+                            entry.push(n);
+                        }
+                    }
+                }
+            }
+        }
+
+        Keyboard { neighbours }
+    }
+
+    pub fn qwerty() -> Keyboard {
+        Keyboard::from_rows(QWERTY)
+    }
+
+    pub fn neighbours(&self, ch: char) -> &[char] {
+        self.neighbours.get(&ch).map(|v| &v[..]).unwrap_or(&[])
+    }
+}
+
+/// xorshift64*, so runs are reproducible from a seed without pulling in a
+/// random number crate.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(if seed == 0 {
+            0x9E37_79B9_7F4A_7C15
+        } else {
+            seed
+        })
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number from 0 up to but not including `n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn lowercase(ch: char) -> char {
+    ch.to_lowercase().next().unwrap_or(ch)
+}
+
+fn with_case_of(ch: char, original: char) -> char {
+    if original.is_uppercase() {
+        ch.to_uppercase().next().unwrap_or(ch)
+    } else {
+        ch
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Keyboard,
+    Transposition,
+    Diacritic,
+    Deletion,
+    Insertion,
+}
+
+pub struct TypoGenerator {
+    cfg: TypoConfig,
+    keyboard: Keyboard,
+    rng: Rng,
+}
+
+impl TypoGenerator {
+    pub fn new(cfg: TypoConfig, keyboard: Keyboard, seed: u64) -> TypoGenerator {
+        TypoGenerator {
+            cfg,
+            keyboard,
+            rng: Rng::new(seed),
+        }
+    }
+
+    fn strip_diacritic(ch: char) -> Option<char> {
+        let lower = lowercase(ch);
+        DIACRITICS
+            .iter()
+            .find(|(from, _)| *from == lower)
+            .map(|(_, to)| with_case_of(*to, ch))
+    }
+
+    fn slip(&mut self, ch: char) -> Option<char> {
+        let neighbours = self.keyboard.neighbours(lowercase(ch));
+
+        if neighbours.is_empty() {
+            return None;
+        }
+
+        let n = neighbours[self.rng.below(neighbours.len())];
+        Some(with_case_of(n, ch))
+    }
+
+    /// Applies one error at `i`, returning false if none of the enabled kinds
+    /// apply there.
+    fn apply(&mut self, chars: &mut Vec<char>, i: usize) -> bool {
+        let ch = chars[i];
+        let kinds = [
+            (Kind::Keyboard, self.cfg.keyboard),
+            (Kind::Transposition, self.cfg.transposition),
+            (Kind::Diacritic, self.cfg.diacritic),
+            (Kind::Deletion, self.cfg.deletion),
+            (Kind::Insertion, self.cfg.insertion),
+        ];
+
+        let applicable: Vec<(Kind, f64)> = kinds
+            .iter()
+            .cloned()
+            .filter(|&(kind, weight)| {
+                weight > 0.0
+                    && match kind {
+                        Kind::Keyboard => !self.keyboard.neighbours(lowercase(ch)).is_empty(),
+                        Kind::Transposition => i + 1 < chars.len() && chars[i + 1] != ch,
+                        Kind::Diacritic => TypoGenerator::strip_diacritic(ch).is_some(),
+                        Kind::Deletion => chars.len() > 1,
+                        Kind::Insertion => true,
+                    }
+            })
+            .collect();
+
+        let total: f64 = applicable.iter().map(|(_, w)| w).sum();
+        if total <= 0.0 {
+            return false;
+        }
+
+        let mut pick = self.rng.chance() * total;
+        let kind = applicable
+            .iter()
+            .find(|(_, w)| {
+                pick -= w;
+                pick < 0.0
+            })
+            .unwrap_or(&applicable[applicable.len() - 1])
+            .0;
+
+        match kind {
+            Kind::Keyboard => match self.slip(ch) {
+                Some(n) => chars[i] = n,
+                None => return false,
+            },
+            Kind::Transposition => chars.swap(i, i + 1),
+            Kind::Diacritic => chars[i] = TypoGenerator::strip_diacritic(ch).unwrap(),
+            Kind::Deletion => {
+                chars.remove(i);
+            }
+            Kind::Insertion => chars.insert(i, ch),
+        }
+
+        true
+    }
+
+    /// Returns a misspelling of `word`, or `None` if no error could be made.
+    pub fn typo(&mut self, word: &str) -> Option<String> {
+        let original: Vec<char> = word.chars().collect();
+        if original.is_empty() || self.cfg.max_edits == 0 {
+            return None;
+        }
+
+        let mut chars = original.clone();
+        let mut edits = 0;
+        let mut i = 0;
+
+        while i < chars.len() && edits < self.cfg.max_edits {
+            if self.rng.chance() < self.cfg.error_rate && self.apply(&mut chars, i) {
+                edits += 1;
+                // Keep errors from undoing or compounding each other
+                i += 1;
+            }
+            i += 1;
+        }
+
+        // Every typo needs at least one error; try a few random positions.
+        let mut attempts = 0;
+        while edits == 0 && attempts < original.len() * 2 {
+            let i = self.rng.below(chars.len());
+            if self.apply(&mut chars, i) {
+                edits += 1;
+            }
+            attempts += 1;
+        }
+
+        if chars == original {
+            None
+        } else {
+            Some(chars.into_iter().collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyboard() {
+        let kb = Keyboard::qwerty();
+        let mut n = kb.neighbours('s').to_vec();
+        n.sort();
+        assert_eq!(n, vec!['a', 'd', 'e', 'w', 'x', 'z']);
+    }
+
+    #[test]
+    fn typos() {
+        let cfg = TypoConfig::default();
+        let mut gen = TypoGenerator::new(cfg.clone(), Keyboard::qwerty(), 42);
+
+        for _ in 0..100 {
+            let typo = gen.typo("čoahkkin").unwrap();
+            assert_ne!(typo, "čoahkkin");
+
+            let distance = crate::errmodel::align::levenshtein(&typo, "čoahkkin");
+            // A transposition counts as two Levenshtein edits
+            assert!(distance >= 1 && distance <= cfg.max_edits * 2);
+        }
+
+        let only_diacritics = TypoConfig {
+            keyboard: 0.0,
+            transposition: 0.0,
+            deletion: 0.0,
+            insertion: 0.0,
+            ..TypoConfig::default()
+        };
+        let mut gen = TypoGenerator::new(only_diacritics, Keyboard::qwerty(), 1);
+        assert_eq!(gen.typo("Šaddu").as_ref().map(|s| &s[..]), Some("Saddu"));
+        assert_eq!(gen.typo("giella"), None);
+    }
+}
@@ -0,0 +1,291 @@
+//! An Enchant 2 provider, built with the `enchant` feature.
+//!
+//! Enchant loads providers from its module directory by file name, so the
+//! cdylib is installed as `enchant_divvunspell.so` there (usually
+//! `/usr/lib/enchant-2`). Dictionary tags are mapped to archives found by
+//! `archive::discovery`. Enchant frees the string lists it is given with
+//! `g_strfreev`, so they are allocated through GLib.
+
+use libc::{c_char, c_int, c_void, size_t};
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr::null_mut;
+
+use crate::archive::discovery;
+use crate::archive::SpellerArchive;
+use crate::speller::user_dictionary::UserDictionary;
+
+#[link(name = "glib-2.0")]
+extern "C" {
+    fn g_malloc0(n_bytes: size_t) -> *mut c_void;
+    fn g_strdup(s: *const c_char) -> *mut c_char;
+}
+
+/// `EnchantProvider` from `enchant-provider.h`.
+#[repr(C)]
+pub struct EnchantProvider {
+    user_data: *mut c_void,
+    enchant_private_data: *mut c_void,
+    dispose: extern "C" fn(me: *mut EnchantProvider),
+    request_dict: extern "C" fn(me: *mut EnchantProvider, tag: *const c_char) -> *mut EnchantDict,
+    dispose_dict: extern "C" fn(me: *mut EnchantProvider, dict: *mut EnchantDict),
+    dictionary_exists: extern "C" fn(me: *mut EnchantProvider, tag: *const c_char) -> c_int,
+    identify: extern "C" fn(me: *mut EnchantProvider) -> *const c_char,
+    describe: extern "C" fn(me: *mut EnchantProvider) -> *const c_char,
+    list_dicts:
+        extern "C" fn(me: *mut EnchantProvider, out_n_dicts: *mut size_t) -> *mut *mut c_char,
+}
+
+/// `EnchantDict` from `enchant-provider.h`.
+#[repr(C)]
+pub struct EnchantDict {
+    user_data: *mut c_void,
+    enchant_private_data: *mut c_void,
+    check: extern "C" fn(me: *mut EnchantDict, word: *const c_char, len: size_t) -> c_int,
+    suggest: extern "C" fn(
+        me: *mut EnchantDict,
+        word: *const c_char,
+        len: size_t,
+        out_n_suggs: *mut size_t,
+    ) -> *mut *mut c_char,
+    add_to_session: extern "C" fn(me: *mut EnchantDict, word: *const c_char, len: size_t),
+    remove_from_session: extern "C" fn(me: *mut EnchantDict, word: *const c_char, len: size_t),
+    get_extra_word_characters: extern "C" fn(me: *mut EnchantDict) -> *const c_char,
+    is_word_character: extern "C" fn(me: *mut EnchantDict, uc: u32, n: size_t) -> c_int,
+}
+
+struct Provider {
+    archives: BTreeMap<String, PathBuf>,
+}
+
+struct Dict {
+    archive: SpellerArchive,
+    session: RwLock<UserDictionary>,
+}
+
+const IDENTIFY: &[u8] = b"divvunspell\0";
+const DESCRIBE: &[u8] = b"Divvun ZHFST spellers\0";
+const EXTRA_WORD_CHARACTERS: &[u8] = b"'\xe2\x80\x99-\0";
+
+fn guard<T, F: FnOnce() -> T>(fallback: T, f: F) -> T {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(fallback)
+}
+
+fn provider<'a>(me: *mut EnchantProvider) -> &'a Provider {
+    unsafe { &*((*me).user_data as *const Provider) }
+}
+
+fn dict<'a>(me: *mut EnchantDict) -> &'a Dict {
+    unsafe { &*((*me).user_data as *const Dict) }
+}
+
+fn tag<'a>(tag: *const c_char) -> Option<&'a str> {
+    if tag.is_null() {
+        return None;
+    }
+    unsafe { std::ffi::CStr::from_ptr(tag) }.to_str().ok()
+}
+
+/// Enchant passes words with an explicit length, not NUL-terminated.
+fn word<'a>(word: *const c_char, len: size_t) -> Option<&'a str> {
+    if word.is_null() {
+        return None;
+    }
+    std::str::from_utf8(unsafe { std::slice::from_raw_parts(word as *const u8, len) }).ok()
+}
+
+/// A NULL-terminated string list for Enchant to free with `g_strfreev`.
+fn glib_strv<'a, I: IntoIterator<Item = &'a str>>(
+    items: I,
+    out_len: *mut size_t,
+) -> *mut *mut c_char {
+    let items: Vec<CString> = items
+        .into_iter()
+        .filter_map(|s| CString::new(s).ok())
+        .collect();
+
+    unsafe {
+        let strv =
+            g_malloc0((items.len() + 1) * std::mem::size_of::<*mut c_char>()) as *mut *mut c_char;
+        for (i, item) in items.iter().enumerate() {
+            *strv.add(i) = g_strdup(item.as_ptr());
+        }
+        if !out_len.is_null() {
+            *out_len = items.len();
+        }
+        strv
+    }
+}
+
+extern "C" fn provider_dispose(me: *mut EnchantProvider) {
+    guard((), || unsafe {
+        drop(Box::from_raw((*me).user_data as *mut Provider));
+        drop(Box::from_raw(me));
+    })
+}
+
+extern "C" fn provider_request_dict(
+    me: *mut EnchantProvider,
+    tag_ptr: *const c_char,
+) -> *mut EnchantDict {
+    guard(null_mut(), || {
+        let path = match tag(tag_ptr).and_then(|t| discovery::find(&provider(me).archives, t)) {
+            Some(v) => v,
+            None => return null_mut(),
+        };
+
+        let archive = match SpellerArchive::new(&path.to_string_lossy()) {
+            Ok(v) => v,
+            Err(_) => return null_mut(),
+        };
+
+        let data = Box::new(Dict {
+            archive,
+            session: RwLock::new(UserDictionary::new()),
+        });
+
+        Box::into_raw(Box::new(EnchantDict {
+            user_data: Box::into_raw(data) as *mut c_void,
+            enchant_private_data: null_mut(),
+            check: dict_check,
+            suggest: dict_suggest,
+            add_to_session: dict_add_to_session,
+            remove_from_session: dict_remove_from_session,
+            get_extra_word_characters: dict_get_extra_word_characters,
+            is_word_character: dict_is_word_character,
+        }))
+    })
+}
+
+extern "C" fn provider_dispose_dict(_me: *mut EnchantProvider, dict: *mut EnchantDict) {
+    guard((), || unsafe {
+        drop(Box::from_raw((*dict).user_data as *mut Dict));
+        drop(Box::from_raw(dict));
+    })
+}
+
+extern "C" fn provider_dictionary_exists(
+    me: *mut EnchantProvider,
+    tag_ptr: *const c_char,
+) -> c_int {
+    guard(0, || {
+        tag(tag_ptr)
+            .and_then(|t| discovery::find(&provider(me).archives, t))
+            .is_some() as c_int
+    })
+}
+
+extern "C" fn provider_identify(_me: *mut EnchantProvider) -> *const c_char {
+    IDENTIFY.as_ptr() as *const c_char
+}
+
+extern "C" fn provider_describe(_me: *mut EnchantProvider) -> *const c_char {
+    DESCRIBE.as_ptr() as *const c_char
+}
+
+/// Tags are listed the way Enchant writes them, with underscores.
+extern "C" fn provider_list_dicts(
+    me: *mut EnchantProvider,
+    out_n_dicts: *mut size_t,
+) -> *mut *mut c_char {
+    guard(null_mut(), || {
+        let tags: Vec<String> = provider(me)
+            .archives
+            .keys()
+            .map(|tag| tag.replace('-', "_"))
+            .collect();
+        glib_strv(tags.iter().map(|x| &**x), out_n_dicts)
+    })
+}
+
+/// 0 if the word is correct, 1 if not, -1 on error.
+extern "C" fn dict_check(me: *mut EnchantDict, word_ptr: *const c_char, len: size_t) -> c_int {
+    guard(-1, || {
+        let word = match word(word_ptr, len) {
+            Some(v) => v,
+            None => return -1,
+        };
+        let dict = dict(me);
+
+        if dict.session.read().contains(word) || dict.archive.speller().is_correct(word) {
+            0
+        } else {
+            1
+        }
+    })
+}
+
+extern "C" fn dict_suggest(
+    me: *mut EnchantDict,
+    word_ptr: *const c_char,
+    len: size_t,
+    out_n_suggs: *mut size_t,
+) -> *mut *mut c_char {
+    guard(null_mut(), || {
+        let word = match word(word_ptr, len) {
+            Some(v) => v,
+            None => return null_mut(),
+        };
+        let dict = dict(me);
+
+        let found = dict.archive.speller().suggest(word);
+        let suggestions = dict.session.read().merge(word, found, None);
+        glib_strv(suggestions.iter().map(|x| x.value()), out_n_suggs)
+    })
+}
+
+extern "C" fn dict_add_to_session(me: *mut EnchantDict, word_ptr: *const c_char, len: size_t) {
+    guard((), || {
+        if let Some(word) = word(word_ptr, len) {
+            dict(me).session.write().insert(word);
+        }
+    })
+}
+
+extern "C" fn dict_remove_from_session(me: *mut EnchantDict, word_ptr: *const c_char, len: size_t) {
+    guard((), || {
+        if let Some(word) = word(word_ptr, len) {
+            dict(me).session.write().remove(word);
+        }
+    })
+}
+
+extern "C" fn dict_get_extra_word_characters(_me: *mut EnchantDict) -> *const c_char {
+    EXTRA_WORD_CHARACTERS.as_ptr() as *const c_char
+}
+
+/// `n` is 0 at the start of a word, 1 inside it and 2 at its end.
+/// Apostrophes and hyphens only count inside a word.
+extern "C" fn dict_is_word_character(_me: *mut EnchantDict, uc: u32, n: size_t) -> c_int {
+    let c = match std::char::from_u32(uc) {
+        Some(v) => v,
+        None => return 0,
+    };
+
+    (c.is_alphanumeric() || (n == 1 && (c == '\'' || c == '’' || c == '-'))) as c_int
+}
+
+/// Entry point called by Enchant after loading the module.
+#[no_mangle]
+pub extern "C" fn init_enchant_provider() -> *mut EnchantProvider {
+    guard(null_mut(), || {
+        let data = Box::new(Provider {
+            archives: discovery::discover(),
+        });
+
+        Box::into_raw(Box::new(EnchantProvider {
+            user_data: Box::into_raw(data) as *mut c_void,
+            enchant_private_data: null_mut(),
+            dispose: provider_dispose,
+            request_dict: provider_request_dict,
+            dispose_dict: provider_dispose_dict,
+            dictionary_exists: provider_dictionary_exists,
+            identify: provider_identify,
+            describe: provider_describe,
+            list_dicts: provider_list_dicts,
+        }))
+    })
+}
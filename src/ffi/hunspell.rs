@@ -0,0 +1,224 @@
+//! The most used functions of Hunspell's C API, built with the `hunspell`
+//! feature, so a program linked against libhunspell can use a ZHFST archive
+//! by loading this library in its place.
+//!
+//! Hunspell is opened with `.aff` and `.dic` paths. Either may name a
+//! `.zhfst` file directly; otherwise the archive is the `.zhfst` next to the
+//! `.aff` file with the same name (`se_NO.aff` → `se_NO.zhfst`), or the one
+//! `archive::discovery` finds for that name as a language tag. Words added
+//! with `Hunspell_add` last until the handle is destroyed, as in Hunspell.
+//! Strings are always UTF-8.
+
+use libc::{c_char, c_int, c_void};
+use parking_lot::RwLock;
+use std::ffi::{CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::ptr::null_mut;
+
+use crate::archive::discovery;
+use crate::archive::SpellerArchive;
+use crate::speller::user_dictionary::UserDictionary;
+
+pub struct Hunhandle {
+    archive: SpellerArchive,
+    session: RwLock<UserDictionary>,
+}
+
+const ENCODING: &[u8] = b"UTF-8\0";
+
+fn guard<T, F: FnOnce() -> T>(fallback: T, f: F) -> T {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(fallback)
+}
+
+fn to_str<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str().ok()
+}
+
+fn hunhandle<'a>(ptr: *mut Hunhandle) -> Option<&'a Hunhandle> {
+    unsafe { ptr.as_ref() }
+}
+
+fn archive_path(affpath: &str, dpath: &str) -> Option<PathBuf> {
+    for path in [dpath, affpath].iter().map(Path::new) {
+        if path.extension().and_then(|x| x.to_str()) == Some("zhfst") {
+            return Some(path.to_path_buf());
+        }
+    }
+
+    let aff = Path::new(affpath);
+    let sibling = aff.with_extension("zhfst");
+    if sibling.exists() {
+        return Some(sibling);
+    }
+
+    let tag = aff.file_stem()?.to_str()?;
+    discovery::find(&discovery::discover(), tag).cloned()
+}
+
+#[no_mangle]
+pub extern "C" fn Hunspell_create(affpath: *const c_char, dpath: *const c_char) -> *mut Hunhandle {
+    guard(null_mut(), || {
+        let path = match archive_path(to_str(affpath).unwrap_or(""), to_str(dpath).unwrap_or("")) {
+            Some(v) => v,
+            None => return null_mut(),
+        };
+
+        match SpellerArchive::new(&path.to_string_lossy()) {
+            Ok(archive) => Box::into_raw(Box::new(Hunhandle {
+                archive,
+                session: RwLock::new(UserDictionary::new()),
+            })),
+            Err(_) => null_mut(),
+        }
+    })
+}
+
+/// Archives are not encrypted, so the key is ignored.
+#[no_mangle]
+pub extern "C" fn Hunspell_create_key(
+    affpath: *const c_char,
+    dpath: *const c_char,
+    _key: *const c_char,
+) -> *mut Hunhandle {
+    Hunspell_create(affpath, dpath)
+}
+
+#[no_mangle]
+pub extern "C" fn Hunspell_destroy(handle: *mut Hunhandle) {
+    if !handle.is_null() {
+        guard((), || unsafe { drop(Box::from_raw(handle)) })
+    }
+}
+
+/// Not supported; an archive is a complete dictionary. Returns 1 as Hunspell
+/// does when no dictionary could be added.
+#[no_mangle]
+pub extern "C" fn Hunspell_add_dic(_handle: *mut Hunhandle, _dpath: *const c_char) -> c_int {
+    1
+}
+
+/// Nonzero if the word is correct.
+#[no_mangle]
+pub extern "C" fn Hunspell_spell(handle: *mut Hunhandle, word: *const c_char) -> c_int {
+    guard(0, || {
+        let (handle, word) = match (hunhandle(handle), to_str(word)) {
+            (Some(h), Some(w)) => (h, w),
+            _ => return 0,
+        };
+
+        (handle.session.read().contains(word) || handle.archive.speller().is_correct(word)) as c_int
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn Hunspell_get_dic_encoding(_handle: *mut Hunhandle) -> *mut c_char {
+    ENCODING.as_ptr() as *mut c_char
+}
+
+/// Writes a `malloc`ed list of suggestions to `slst` and returns its length.
+/// Free it with `Hunspell_free_list`.
+#[no_mangle]
+pub extern "C" fn Hunspell_suggest(
+    handle: *mut Hunhandle,
+    slst: *mut *mut *mut c_char,
+    word: *const c_char,
+) -> c_int {
+    guard(0, || {
+        if slst.is_null() {
+            return 0;
+        }
+        unsafe { *slst = null_mut() };
+
+        let (handle, word) = match (hunhandle(handle), to_str(word)) {
+            (Some(h), Some(w)) => (h, w),
+            _ => return 0,
+        };
+
+        let found = handle.archive.speller().suggest(word);
+        let suggestions: Vec<CString> = handle
+            .session
+            .read()
+            .merge(word, found, None)
+            .into_iter()
+            .filter_map(|s| CString::new(s.value()).ok())
+            .collect();
+
+        if suggestions.is_empty() {
+            return 0;
+        }
+
+        unsafe {
+            let list = libc::malloc(suggestions.len() * std::mem::size_of::<*mut c_char>())
+                as *mut *mut c_char;
+            if list.is_null() {
+                return 0;
+            }
+            for (i, s) in suggestions.iter().enumerate() {
+                *list.add(i) = libc::strdup(s.as_ptr());
+            }
+            *slst = list;
+        }
+
+        suggestions.len() as c_int
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn Hunspell_free_list(
+    _handle: *mut Hunhandle,
+    slst: *mut *mut *mut c_char,
+    n: c_int,
+) {
+    if slst.is_null() {
+        return;
+    }
+
+    unsafe {
+        let list = *slst;
+        if list.is_null() {
+            return;
+        }
+        for i in 0..n.max(0) as usize {
+            libc::free(*list.add(i) as *mut c_void);
+        }
+        libc::free(list as *mut c_void);
+        *slst = null_mut();
+    }
+}
+
+/// Adds a word for the lifetime of the handle. Returns 0 on success.
+#[no_mangle]
+pub extern "C" fn Hunspell_add(handle: *mut Hunhandle, word: *const c_char) -> c_int {
+    guard(1, || match (hunhandle(handle), to_str(word)) {
+        (Some(h), Some(w)) => {
+            h.session.write().insert(w);
+            0
+        }
+        _ => 1,
+    })
+}
+
+/// Affixes are a Hunspell concept, so the word is added on its own.
+#[no_mangle]
+pub extern "C" fn Hunspell_add_with_affix(
+    handle: *mut Hunhandle,
+    word: *const c_char,
+    _example: *const c_char,
+) -> c_int {
+    Hunspell_add(handle, word)
+}
+
+#[no_mangle]
+pub extern "C" fn Hunspell_remove(handle: *mut Hunhandle, word: *const c_char) -> c_int {
+    guard(1, || match (hunhandle(handle), to_str(word)) {
+        (Some(h), Some(w)) => {
+            h.session.write().remove(w);
+            0
+        }
+        _ => 1,
+    })
+}
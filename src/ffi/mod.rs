@@ -1,5 +1,11 @@
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
 
+#[cfg(feature = "enchant")]
+pub mod enchant;
+#[cfg(feature = "hunspell")]
+pub mod hunspell;
+pub mod stable;
+
 use libc::{c_char, size_t};
 use std::ffi::{CStr, CString};
 use std::path::Path;
@@ -8,7 +14,7 @@ use std::sync::Arc;
 
 use crate::archive::SpellerArchive;
 use crate::speller::suggestion::Suggestion;
-use crate::speller::{Speller, SpellerConfig};
+use crate::speller::{SeenNodes, Speller, SpellerConfig, WeightSemiring};
 // use crate::tokenizer::{Tokenize, Tokenizer, Token};
 use crate::transducer::chunk::{ChfstBundle, ChfstTransducer};
 
@@ -123,11 +129,25 @@ pub extern "C" fn chfst_suggest(
                 None
             },
             n_best: if n_best > 0 { Some(n_best) } else { None },
+            max_edit_distance: None,
+            max_weight_gap: None,
             beam: if beam > 0.0 { Some(beam) } else { None },
             pool_max: 128,
             pool_start: 128,
+            seen_nodes: SeenNodes::Off,
             seen_node_sample_rate: 20,
+            seen_false_positive_rate: 0.001,
             with_caps: true,
+            adaptive_beam: false,
+            beam_min: 5.0,
+            beam_max: 40.0,
+            time_budget_ms: None,
+            prefix_cache: None,
+            semiring: WeightSemiring::Tropical,
+            input_profile: None,
+            proper_nouns: false,
+            constrain_huge_errmodels: true,
+            fallback_distance: None,
         },
     );
 
@@ -189,11 +209,25 @@ pub extern "C" fn speller_suggest(
                 None
             },
             n_best: if n_best > 0 { Some(n_best) } else { None },
+            max_edit_distance: None,
+            max_weight_gap: None,
             beam: if beam > 0.0 { Some(beam) } else { None },
             pool_max: 128,
             pool_start: 128,
+            seen_nodes: SeenNodes::Off,
             seen_node_sample_rate: 20,
+            seen_false_positive_rate: 0.001,
             with_caps: true,
+            adaptive_beam: false,
+            beam_min: 5.0,
+            beam_max: 40.0,
+            time_budget_ms: None,
+            prefix_cache: None,
+            semiring: WeightSemiring::Tropical,
+            input_profile: None,
+            proper_nouns: false,
+            constrain_huge_errmodels: true,
+            fallback_distance: None,
         },
     );
 
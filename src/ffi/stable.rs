@@ -0,0 +1,769 @@
+//! The stable C API, declared in `include/divvunspell.h`.
+//!
+//! Unlike the older functions in this module's parent, nothing here panics
+//! across the FFI boundary or takes ownership of caller memory. Every fallible
+//! function returns a `DivvunStatus`, writes its result through an out
+//! pointer, and leaves a message for `divvun_last_error` on failure. Handles
+//! are opaque and must be released with the matching `_free` function.
+//!
+//! Symbols are prefixed `divvun_`. Existing signatures do not change; new
+//! functionality is added as new functions, and `DIVVUN_FFI_VERSION` is
+//! bumped when that happens.
+//!
+//! Calls are synchronous and may come from any thread, except
+//! `divvun_speller_suggest_async`, which queues the search on a single
+//! background thread owned by the library and returns at once. That keeps a
+//! keyboard's UI thread free without it having to manage threads of its own.
+
+use libc::{c_char, c_void, size_t};
+use once_cell::sync::OnceCell;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::ptr::{null, null_mut};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::archive::SpellerArchive;
+use crate::speller::suggestion::Suggestion;
+use crate::speller::{Speller, SpellerConfig};
+use crate::tokenizer::Tokenize;
+use crate::transducer::chunk::{ChfstBundle, ChfstTransducer};
+use crate::types::Weight;
+
+pub const DIVVUN_FFI_VERSION: u32 = 4;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivvunStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidUtf8 = 2,
+    OpenFailed = 3,
+    Panic = 4,
+}
+
+/// Suggestion limits. Zero means no limit for each field.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DivvunSuggestConfig {
+    pub n_best: size_t,
+    pub max_weight: f32,
+    pub beam: f32,
+}
+
+impl DivvunSuggestConfig {
+    fn to_speller_config(self) -> SpellerConfig {
+        SpellerConfig {
+            n_best: if self.n_best > 0 {
+                Some(self.n_best)
+            } else {
+                None
+            },
+            max_weight: if self.max_weight > 0.0 {
+                Some(self.max_weight)
+            } else {
+                None
+            },
+            beam: if self.beam > 0.0 {
+                Some(self.beam)
+            } else {
+                None
+            },
+            ..SpellerConfig::default()
+        }
+    }
+}
+
+/// A search that can run on another thread, with its own speller.
+type Suggester = Box<dyn Fn(&str, &SpellerConfig) -> Vec<Suggestion> + Send>;
+
+enum Inner {
+    Zhfst(Box<SpellerArchive>),
+    Chfst(Arc<Speller<ChfstTransducer>>),
+}
+
+/// An opened archive and its speller.
+pub struct DivvunSpeller {
+    inner: Inner,
+    locale: Option<CString>,
+}
+
+impl DivvunSpeller {
+    fn is_correct(&self, word: &str) -> bool {
+        match &self.inner {
+            Inner::Zhfst(archive) => archive.speller().is_correct(word),
+            Inner::Chfst(speller) => speller.clone().is_correct(word),
+        }
+    }
+
+    fn score(&self, word: &str) -> Option<Weight> {
+        match &self.inner {
+            Inner::Zhfst(archive) => archive.speller().score(word),
+            Inner::Chfst(speller) => speller.clone().score(word),
+        }
+    }
+
+    fn suggest(&self, word: &str, cfg: &SpellerConfig) -> Vec<Suggestion> {
+        match &self.inner {
+            Inner::Zhfst(archive) => archive.speller().suggest_with_config(word, cfg),
+            Inner::Chfst(speller) => speller.clone().suggest_with_config(word, cfg),
+        }
+    }
+
+    /// A search that holds its own reference to the speller, so that it can
+    /// outlive this handle.
+    fn suggester(&self) -> Suggester {
+        match &self.inner {
+            Inner::Zhfst(archive) => {
+                let speller = archive.speller();
+                Box::new(move |word, cfg| speller.clone().suggest_with_config(word, cfg))
+            }
+            Inner::Chfst(speller) => {
+                let speller = speller.clone();
+                Box::new(move |word, cfg| speller.clone().suggest_with_config(word, cfg))
+            }
+        }
+    }
+
+    fn from_archive(archive: SpellerArchive) -> DivvunSpeller {
+        DivvunSpeller {
+            locale: CString::new(archive.metadata().info.locale.clone()).ok(),
+            inner: Inner::Zhfst(Box::new(archive)),
+        }
+    }
+}
+
+/// Suggestions for one word, as UTF-8 strings and their weights.
+pub struct DivvunSuggestions {
+    values: Vec<CString>,
+    pointers: Vec<*const c_char>,
+    weights: Vec<f32>,
+}
+
+impl DivvunSuggestions {
+    fn new(suggestions: Vec<Suggestion>) -> DivvunSuggestions {
+        // Suggestions come from the lexicon, which can't contain NUL, but
+        // drop any that do rather than fail the whole call.
+        let (values, weights): (Vec<CString>, Vec<f32>) = suggestions
+            .into_iter()
+            .filter_map(|s| CString::new(s.value()).ok().map(|v| (v, s.weight())))
+            .unzip();
+        let pointers = values.iter().map(|v| v.as_ptr()).collect();
+
+        DivvunSuggestions {
+            values,
+            pointers,
+            weights,
+        }
+    }
+}
+
+/// A segment of text between word boundaries, in UTF-8 bytes and in UTF-16
+/// code units, for callers whose strings are UTF-16.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DivvunWordBoundary {
+    pub offset: size_t,
+    pub len: size_t,
+    pub utf16_offset: size_t,
+    pub utf16_len: size_t,
+}
+
+/// The segments of a text, including whitespace and punctuation.
+pub struct DivvunWordBoundaries {
+    items: Vec<DivvunWordBoundary>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+fn fail(status: DivvunStatus, message: String) -> DivvunStatus {
+    set_last_error(message);
+    status
+}
+
+/// Runs `f`, turning a panic into `DivvunStatus::Panic`.
+fn guard<F: FnOnce() -> DivvunStatus>(f: F) -> DivvunStatus {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(status) => status,
+        Err(e) => {
+            let message = e
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| e.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".into());
+            fail(DivvunStatus::Panic, message)
+        }
+    }
+}
+
+fn to_str<'a>(ptr: *const c_char) -> Result<&'a str, DivvunStatus> {
+    if ptr.is_null() {
+        return Err(fail(DivvunStatus::NullPointer, "string is null".into()));
+    }
+
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|e| fail(DivvunStatus::InvalidUtf8, e.to_string()))
+}
+
+macro_rules! try_status {
+    ($e:expr) => {
+        match $e {
+            Ok(v) => v,
+            Err(status) => return status,
+        }
+    };
+}
+
+macro_rules! not_null {
+    ($ptr:expr) => {
+        if $ptr.is_null() {
+            return fail(
+                DivvunStatus::NullPointer,
+                concat!(stringify!($ptr), " is null").into(),
+            );
+        }
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn divvun_ffi_version() -> u32 {
+    DIVVUN_FFI_VERSION
+}
+
+/// The message for the last failed call on this thread, or null. Valid until
+/// the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn divvun_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| match &*e.borrow() {
+        Some(message) => message.as_ptr(),
+        None => null(),
+    })
+}
+
+/// Opens a `.zhfst` file, or a CHFST bundle if `path` is a directory.
+#[no_mangle]
+pub extern "C" fn divvun_speller_open(
+    path: *const c_char,
+    out: *mut *mut DivvunSpeller,
+) -> DivvunStatus {
+    guard(|| {
+        not_null!(out);
+        unsafe { *out = null_mut() };
+        let path = try_status!(to_str(path));
+
+        let speller = if Path::new(path).is_dir() {
+            match ChfstBundle::from_path(Path::new(path)) {
+                Ok(bundle) => DivvunSpeller {
+                    inner: Inner::Chfst(bundle.speller()),
+                    locale: None,
+                },
+                Err(e) => return fail(DivvunStatus::OpenFailed, format!("{}: {}", path, e)),
+            }
+        } else {
+            match SpellerArchive::new(path) {
+                Ok(archive) => DivvunSpeller::from_archive(archive),
+                Err(e) => return fail(DivvunStatus::OpenFailed, format!("{}: {}", path, e)),
+            }
+        };
+
+        unsafe { *out = Box::into_raw(Box::new(speller)) };
+        DivvunStatus::Ok
+    })
+}
+
+/// The path of a `file://` URL, percent-decoded, or `None` for any other URL.
+/// Only local URLs, with an empty or `localhost` host, are accepted.
+fn file_url_to_path(url: &str) -> Option<PathBuf> {
+    if !url.starts_with("file://") {
+        return None;
+    }
+    let rest = &url["file://".len()..];
+    let path = rest.strip_prefix("localhost").unwrap_or(rest);
+    if !path.starts_with('/') {
+        return None;
+    }
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok().map(PathBuf::from)
+}
+
+/// Opens a speller from a `file://` URL, such as the absolute string of a
+/// bundle resource URL. Otherwise like `divvun_speller_open`. Since version 3.
+#[no_mangle]
+pub extern "C" fn divvun_speller_open_url(
+    url: *const c_char,
+    out: *mut *mut DivvunSpeller,
+) -> DivvunStatus {
+    guard(|| {
+        not_null!(out);
+        unsafe { *out = null_mut() };
+        let url = try_status!(to_str(url));
+
+        let path = match file_url_to_path(url) {
+            Some(path) => path,
+            None => return fail(DivvunStatus::OpenFailed, format!("{}: not a file URL", url)),
+        };
+        let path = match CString::new(path.to_string_lossy().into_owned()) {
+            Ok(path) => path,
+            Err(_) => return fail(DivvunStatus::InvalidUtf8, format!("{}: contains NUL", url)),
+        };
+
+        divvun_speller_open(path.as_ptr(), out)
+    })
+}
+
+/// Opens a `.zhfst` archive from `len` bytes in memory. The transducers are
+/// copied out, so the bytes only need to live for the call and no temporary
+/// file is written. Since version 3.
+#[no_mangle]
+pub extern "C" fn divvun_speller_open_bytes(
+    data: *const u8,
+    len: size_t,
+    out: *mut *mut DivvunSpeller,
+) -> DivvunStatus {
+    guard(|| {
+        not_null!(data);
+        not_null!(out);
+        unsafe { *out = null_mut() };
+
+        let bytes = unsafe { std::slice::from_raw_parts(data, len) };
+        match SpellerArchive::from_bytes(bytes) {
+            Ok(archive) => {
+                let speller = DivvunSpeller::from_archive(archive);
+                unsafe { *out = Box::into_raw(Box::new(speller)) };
+                DivvunStatus::Ok
+            }
+            Err(e) => fail(DivvunStatus::OpenFailed, e.to_string()),
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn divvun_speller_free(speller: *mut DivvunSpeller) {
+    if !speller.is_null() {
+        drop(unsafe { Box::from_raw(speller) });
+    }
+}
+
+/// The archive's locale, or null for CHFST bundles. Owned by the speller.
+#[no_mangle]
+pub extern "C" fn divvun_speller_locale(speller: *const DivvunSpeller) -> *const c_char {
+    if speller.is_null() {
+        return null();
+    }
+
+    match unsafe { &(*speller).locale } {
+        Some(locale) => locale.as_ptr(),
+        None => null(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn divvun_speller_is_correct(
+    speller: *const DivvunSpeller,
+    word: *const c_char,
+    out: *mut bool,
+) -> DivvunStatus {
+    guard(|| {
+        not_null!(speller);
+        not_null!(out);
+        let word = try_status!(to_str(word));
+
+        let speller = unsafe { &*speller };
+        unsafe { *out = speller.is_correct(word) };
+        DivvunStatus::Ok
+    })
+}
+
+/// The lexicon's weight for `word`, for ranking candidates from elsewhere.
+/// `accepted` is false, and `weight` left alone, if the lexicon doesn't
+/// accept it. Since version 4.
+#[no_mangle]
+pub extern "C" fn divvun_speller_score(
+    speller: *const DivvunSpeller,
+    word: *const c_char,
+    accepted: *mut bool,
+    weight: *mut f32,
+) -> DivvunStatus {
+    guard(|| {
+        not_null!(speller);
+        not_null!(accepted);
+        not_null!(weight);
+        let word = try_status!(to_str(word));
+
+        let speller = unsafe { &*speller };
+        match speller.score(word) {
+            Some(score) => unsafe {
+                *accepted = true;
+                *weight = score;
+            },
+            None => unsafe { *accepted = false },
+        }
+        DivvunStatus::Ok
+    })
+}
+
+/// Suggests corrections for `word`. `config` may be null for the defaults.
+#[no_mangle]
+pub extern "C" fn divvun_speller_suggest(
+    speller: *const DivvunSpeller,
+    word: *const c_char,
+    config: *const DivvunSuggestConfig,
+    out: *mut *mut DivvunSuggestions,
+) -> DivvunStatus {
+    guard(|| {
+        not_null!(speller);
+        not_null!(out);
+        unsafe { *out = null_mut() };
+        let word = try_status!(to_str(word));
+
+        let cfg = if config.is_null() {
+            SpellerConfig::default()
+        } else {
+            unsafe { &*config }.to_speller_config()
+        };
+
+        let speller = unsafe { &*speller };
+        let suggestions = DivvunSuggestions::new(speller.suggest(word, &cfg));
+        unsafe { *out = Box::into_raw(Box::new(suggestions)) };
+        DivvunStatus::Ok
+    })
+}
+
+/// Called once with the result of `divvun_speller_suggest_async`: `Ok` and
+/// suggestions the callee must free, or another status and null.
+pub type DivvunSuggestCallback =
+    extern "C" fn(context: *mut c_void, status: DivvunStatus, out: *mut DivvunSuggestions);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The caller's context pointer, which is only handed back to the caller.
+struct Context(*mut c_void);
+
+unsafe impl Send for Context {}
+
+static WORKER: OnceCell<Mutex<Sender<Job>>> = OnceCell::new();
+
+/// Queues `job` on the background thread, starting it on first use.
+fn spawn_job(job: Job) -> Result<(), DivvunStatus> {
+    let worker = WORKER.get_or_try_init(|| {
+        let (sender, receiver) = channel::<Job>();
+        thread::Builder::new()
+            .name("divvunspell-suggest".into())
+            .spawn(move || {
+                for job in receiver {
+                    job();
+                }
+            })
+            .map(|_| Mutex::new(sender))
+            .map_err(|e| fail(DivvunStatus::Panic, e.to_string()))
+    })?;
+
+    worker
+        .lock()
+        .map_err(|_| fail(DivvunStatus::Panic, "worker lock poisoned".into()))?
+        .send(job)
+        .map_err(|_| fail(DivvunStatus::Panic, "worker has stopped".into()))
+}
+
+/// Like `divvun_speller_suggest`, but returns at once and passes the result
+/// to `callback` with `context`. Searches run one at a time, in the order
+/// queued, on a thread owned by the library, and `callback` is called on
+/// that thread, so UI work must be dispatched back to the main thread. The
+/// word and config are copied, and the search keeps the speller alive, so
+/// `speller` may be freed before the callback. A status other than `Ok`
+/// means nothing was queued and `callback` won't be called. Since version 3.
+#[no_mangle]
+pub extern "C" fn divvun_speller_suggest_async(
+    speller: *const DivvunSpeller,
+    word: *const c_char,
+    config: *const DivvunSuggestConfig,
+    callback: Option<DivvunSuggestCallback>,
+    context: *mut c_void,
+) -> DivvunStatus {
+    guard(|| {
+        not_null!(speller);
+        let callback = match callback {
+            Some(callback) => callback,
+            None => return fail(DivvunStatus::NullPointer, "callback is null".into()),
+        };
+        let word = try_status!(to_str(word)).to_string();
+
+        let cfg = if config.is_null() {
+            SpellerConfig::default()
+        } else {
+            unsafe { &*config }.to_speller_config()
+        };
+
+        let suggest = unsafe { &*speller }.suggester();
+        let context = Context(context);
+        try_status!(spawn_job(Box::new(move || {
+            let context = context;
+            let mut out = null_mut();
+            let status = guard(|| {
+                let suggestions = DivvunSuggestions::new(suggest(&word, &cfg));
+                out = Box::into_raw(Box::new(suggestions));
+                DivvunStatus::Ok
+            });
+            callback(context.0, status, out);
+        })));
+
+        DivvunStatus::Ok
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn divvun_suggestions_len(suggestions: *const DivvunSuggestions) -> size_t {
+    if suggestions.is_null() {
+        return 0;
+    }
+
+    unsafe { &*suggestions }.values.len()
+}
+
+/// All suggestion strings, best first, as an array of
+/// `divvun_suggestions_len` pointers owned by `suggestions`.
+#[no_mangle]
+pub extern "C" fn divvun_suggestions_values(
+    suggestions: *const DivvunSuggestions,
+) -> *const *const c_char {
+    if suggestions.is_null() {
+        return null();
+    }
+
+    unsafe { &*suggestions }.pointers.as_ptr()
+}
+
+/// The suggestion at `index`, or null if out of range. Owned by `suggestions`.
+#[no_mangle]
+pub extern "C" fn divvun_suggestions_value(
+    suggestions: *const DivvunSuggestions,
+    index: size_t,
+) -> *const c_char {
+    if suggestions.is_null() {
+        return null();
+    }
+
+    match unsafe { &*suggestions }.values.get(index) {
+        Some(value) => value.as_ptr(),
+        None => null(),
+    }
+}
+
+/// The weight of the suggestion at `index`, or NaN if out of range.
+#[no_mangle]
+pub extern "C" fn divvun_suggestions_weight(
+    suggestions: *const DivvunSuggestions,
+    index: size_t,
+) -> f32 {
+    if suggestions.is_null() {
+        return f32::NAN;
+    }
+
+    unsafe { &*suggestions }
+        .weights
+        .get(index)
+        .cloned()
+        .unwrap_or(f32::NAN)
+}
+
+#[no_mangle]
+pub extern "C" fn divvun_suggestions_free(suggestions: *mut DivvunSuggestions) {
+    if !suggestions.is_null() {
+        drop(unsafe { Box::from_raw(suggestions) });
+    }
+}
+
+/// Splits `text` at word boundaries. Since version 2.
+#[no_mangle]
+pub extern "C" fn divvun_word_boundaries(
+    text: *const c_char,
+    out: *mut *mut DivvunWordBoundaries,
+) -> DivvunStatus {
+    guard(|| {
+        not_null!(out);
+        unsafe { *out = null_mut() };
+        let text = try_status!(to_str(text));
+
+        let mut utf16_offset = 0;
+        let items = text
+            .word_bound_indices()
+            .map(|(offset, segment)| {
+                let utf16_len = segment.encode_utf16().count();
+                let boundary = DivvunWordBoundary {
+                    offset,
+                    len: segment.len(),
+                    utf16_offset,
+                    utf16_len,
+                };
+                utf16_offset += utf16_len;
+                boundary
+            })
+            .collect();
+
+        unsafe { *out = Box::into_raw(Box::new(DivvunWordBoundaries { items })) };
+        DivvunStatus::Ok
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn divvun_word_boundaries_len(boundaries: *const DivvunWordBoundaries) -> size_t {
+    if boundaries.is_null() {
+        return 0;
+    }
+
+    unsafe { &*boundaries }.items.len()
+}
+
+/// The segment at `index`, or null if out of range. Owned by `boundaries`.
+#[no_mangle]
+pub extern "C" fn divvun_word_boundaries_get(
+    boundaries: *const DivvunWordBoundaries,
+    index: size_t,
+) -> *const DivvunWordBoundary {
+    if boundaries.is_null() {
+        return null();
+    }
+
+    match unsafe { &*boundaries }.items.get(index) {
+        Some(boundary) => boundary,
+        None => null(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn divvun_word_boundaries_free(boundaries: *mut DivvunWordBoundaries) {
+    if !boundaries.is_null() {
+        drop(unsafe { Box::from_raw(boundaries) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors() {
+        let mut speller = null_mut();
+        let path = CString::new("/nonexistent.zhfst").unwrap();
+
+        assert_eq!(
+            divvun_speller_open(path.as_ptr(), &mut speller),
+            DivvunStatus::OpenFailed
+        );
+        assert!(speller.is_null());
+        assert!(!divvun_last_error().is_null());
+
+        assert_eq!(
+            divvun_speller_open(null(), &mut speller),
+            DivvunStatus::NullPointer
+        );
+
+        let invalid = [0xffu8 as c_char, 0];
+        assert_eq!(
+            divvun_speller_open(invalid.as_ptr(), &mut speller),
+            DivvunStatus::InvalidUtf8
+        );
+
+        let mut out = false;
+        assert_eq!(
+            divvun_speller_is_correct(null(), path.as_ptr(), &mut out),
+            DivvunStatus::NullPointer
+        );
+
+        assert_eq!(divvun_suggestions_len(null()), 0);
+        divvun_speller_free(null_mut());
+        divvun_suggestions_free(null_mut());
+    }
+
+    #[test]
+    fn file_urls() {
+        assert_eq!(
+            file_url_to_path("file:///var/containers/Bundle/se%20NO.zhfst"),
+            Some(PathBuf::from("/var/containers/Bundle/se NO.zhfst"))
+        );
+        assert_eq!(
+            file_url_to_path("file://localhost/tmp/%C4%8C.zhfst"),
+            Some(PathBuf::from("/tmp/Č.zhfst"))
+        );
+        assert_eq!(file_url_to_path("https://example.com/se.zhfst"), None);
+        assert_eq!(file_url_to_path("file://host/se.zhfst"), None);
+        assert_eq!(file_url_to_path("file:///se%2.zhfst"), None);
+
+        let mut speller = null_mut();
+        let bytes = b"not a zip";
+        assert_eq!(
+            divvun_speller_open_bytes(bytes.as_ptr(), bytes.len(), &mut speller),
+            DivvunStatus::OpenFailed
+        );
+        assert!(speller.is_null());
+    }
+
+    #[test]
+    fn suggestions() {
+        let suggestions = DivvunSuggestions::new(vec![
+            Suggestion::new("giella".into(), 1.0),
+            Suggestion::new("gielas".into(), 2.5),
+        ]);
+        let ptr = Box::into_raw(Box::new(suggestions));
+
+        assert_eq!(divvun_suggestions_len(ptr), 2);
+        let second = unsafe { CStr::from_ptr(*divvun_suggestions_values(ptr).add(1)) };
+        assert_eq!(second.to_str(), Ok("gielas"));
+        assert_eq!(divvun_suggestions_weight(ptr, 1), 2.5);
+        assert!(divvun_suggestions_value(ptr, 2).is_null());
+        assert!(divvun_suggestions_weight(ptr, 2).is_nan());
+
+        divvun_suggestions_free(ptr);
+    }
+
+    #[test]
+    fn word_boundaries() {
+        let text = CString::new("Čálli 😀 sáni").unwrap();
+        let mut boundaries = null_mut();
+
+        assert_eq!(
+            divvun_word_boundaries(text.as_ptr(), &mut boundaries),
+            DivvunStatus::Ok
+        );
+        assert_eq!(divvun_word_boundaries_len(boundaries), 5);
+
+        let last = unsafe { *divvun_word_boundaries_get(boundaries, 4) };
+        assert_eq!(
+            last,
+            DivvunWordBoundary {
+                offset: 13,
+                len: 5,
+                utf16_offset: 9,
+                utf16_len: 4,
+            }
+        );
+        assert!(divvun_word_boundaries_get(boundaries, 5).is_null());
+
+        divvun_word_boundaries_free(boundaries);
+    }
+}
@@ -0,0 +1,153 @@
+//! Where words may be broken across lines, from an archive's hyphenator
+//! transducer, for typesetting.
+//!
+//! A hyphenator maps a word to the same word with `^` at each point where
+//! it may be hyphenated, as Giella's hyphenation transducers do, and an
+//! archive carries one as an entry named `hyphenator.*`, such as
+//! `hyphenator.default.hfst`. The lightest result is used. Words it has
+//! nothing for as written are looked up in lower case, so that "GIELLA" and
+//! "Giella" break where "giella" does.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::transducer::lookup::lookup;
+use crate::transducer::HfstTransducer;
+
+/// Marks a hyphenation point in the hyphenator's output.
+pub const HYPHEN_MARK: char = '^';
+
+pub struct Hyphenator {
+    transducer: HfstTransducer,
+}
+
+impl Hyphenator {
+    pub fn new(transducer: HfstTransducer) -> Hyphenator {
+        Hyphenator { transducer }
+    }
+
+    pub fn transducer(&self) -> &HfstTransducer {
+        &self.transducer
+    }
+
+    /// The byte offsets in `word` where it may be broken, in order, never
+    /// at its start or end. Empty if the hyphenator doesn't know the word.
+    pub fn hyphenate(&self, word: &str) -> Vec<usize> {
+        if let Some(found) = self.breaks(word, word) {
+            return found;
+        }
+
+        let lower: String = word
+            .chars()
+            .map(|ch| ch.to_lowercase().next().unwrap_or(ch))
+            .collect();
+        self.breaks(word, &lower).unwrap_or_default()
+    }
+
+    /// Where `word` breaks by the hyphenator's output for `input`, a form
+    /// of it with as many characters.
+    fn breaks(&self, word: &str, input: &str) -> Option<Vec<usize>> {
+        let output = lookup(&self.transducer, input, 1)
+            .into_iter()
+            .next()?
+            .output;
+        let mut chars = word.char_indices().zip(input.chars()).peekable();
+        let mut found = vec![];
+
+        for ch in output.chars() {
+            match chars.peek() {
+                Some(&(_, expected)) if expected == ch => {
+                    chars.next();
+                }
+                Some(&((offset, _), _)) if ch == HYPHEN_MARK => {
+                    if offset > 0 && found.last() != Some(&offset) {
+                        found.push(offset);
+                    }
+                }
+                None if ch == HYPHEN_MARK => {}
+                _ => return None,
+            }
+        }
+
+        if chars.next().is_some() {
+            return None;
+        }
+        Some(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::TARGET_TABLE;
+    use crate::errmodel::hfst::{write_tables, Arc as Transition};
+    use crate::transducer::test_buf;
+
+    /// A transducer taking the first of each pair to the second, in order,
+    /// `None` reading nothing.
+    fn chain(pairs: &[(Option<char>, char)]) -> HfstTransducer {
+        let mut alphabet: Vec<char> = pairs
+            .iter()
+            .flat_map(|&(i, o)| i.into_iter().chain(Some(o)))
+            .collect();
+        alphabet.sort();
+        alphabet.dedup();
+
+        let symbol = |ch: Option<char>| match ch {
+            Some(ch) => alphabet.binary_search(&ch).unwrap() as u16 + 1,
+            None => 0,
+        };
+        let block = alphabet.len() as u32 + 2;
+
+        let mut index = vec![(u16::MAX, u32::MAX); (pairs.len() + 1) * block as usize];
+        let mut transitions = vec![];
+
+        for (state, &(input, output)) in pairs.iter().enumerate() {
+            let base = state as u32 * block;
+            index[(base + 1 + symbol(input) as u32) as usize] =
+                (symbol(input), TARGET_TABLE + transitions.len() as u32);
+            transitions.push(Some(Transition {
+                input: symbol(input),
+                output: symbol(Some(output)),
+                target: base + block,
+                weight: 0.0,
+            }));
+            transitions.push(None);
+        }
+        index[pairs.len() * block as usize] = (u16::MAX, 0.0f32.to_bits());
+
+        let properties = [true, true, false, false, false, false, true, false, false];
+        let mut bytes = vec![];
+        write_tables(
+            &mut bytes,
+            &alphabet,
+            &index,
+            &transitions,
+            pairs.len() as u32 + 1,
+            pairs.len(),
+            properties,
+        )
+        .unwrap();
+        HfstTransducer::from_mapped_memory(test_buf(&bytes)).unwrap()
+    }
+
+    #[test]
+    fn breaks_in_any_case() {
+        let pairs: Vec<(Option<char>, char)> = "gi^el^la"
+            .chars()
+            .map(|ch| {
+                if ch == HYPHEN_MARK {
+                    (None, ch)
+                } else {
+                    (Some(ch), ch)
+                }
+            })
+            .collect();
+        let hyphenator = Hyphenator::new(chain(&pairs));
+
+        assert_eq!(hyphenator.hyphenate("giella"), vec![2, 4]);
+        assert_eq!(hyphenator.hyphenate("Giella"), vec![2, 4]);
+        assert_eq!(hyphenator.hyphenate("GIELLA"), vec![2, 4]);
+        assert!(hyphenator.hyphenate("gielat").is_empty());
+    }
+}
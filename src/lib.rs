@@ -1,18 +1,61 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 #[global_allocator]
 static GLOBAL: mimallocator::Mimalloc = mimallocator::Mimalloc;
 
-#[macro_use]
-extern crate serde_derive;
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
 extern crate byteorder;
+#[cfg(feature = "std")]
 extern crate libc;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 extern crate memmap;
+#[cfg_attr(feature = "std", macro_use)]
+extern crate serde_derive;
+#[cfg(feature = "std")]
 extern crate serde_xml_rs;
+#[cfg(feature = "std")]
+extern crate xml;
+#[cfg(feature = "std")]
 extern crate zip;
 
+#[macro_use]
+mod trace;
+
+#[cfg(feature = "android")]
+pub mod android;
+#[cfg(feature = "std")]
 pub mod archive;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+#[cfg(feature = "binaries")]
+pub mod config;
+#[cfg(feature = "internals")]
 pub mod constants;
+#[cfg(not(feature = "internals"))]
+pub(crate) mod constants;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod daemon;
+#[cfg(feature = "std")]
+pub mod errmodel;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 pub mod ffi;
+pub mod hyphenator;
+pub mod memory;
+pub mod metrics;
+pub mod prelude;
+#[cfg(feature = "mobile")]
+pub mod mobile;
+#[cfg(feature = "node")]
+pub mod node;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod speller;
+mod sync;
+pub mod testing;
 pub mod tokenizer;
 pub mod transducer;
 pub mod types;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,172 @@
+//! Memory limits for constrained devices, such as a keyboard process that
+//! the system kills once it grows past a fixed size.
+//!
+//! A `MemoryPolicy` is given when an archive is opened. It decides whether
+//! transducers are mapped from the archive file or copied into memory, and
+//! caps how much a single search may allocate. Mapped transducers are backed
+//! by the file, so the system can drop their pages under pressure; only
+//! copies and search buffers count against `max_resident_bytes`.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use serde_derive::{Deserialize, Serialize};
+
+/// Of `MemoryPolicy::limited`'s budget, the share one search may use.
+const SEARCH_SHARE: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Storage {
+    /// Map transducers from the archive where they are stored uncompressed.
+    PreferMmap,
+    /// Copy transducers into memory if they fit in the budget, to avoid
+    /// page faults on first use; map them otherwise.
+    PreferHeap,
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryPolicy {
+    /// Transducer copies plus one search's buffers must fit in this.
+    pub max_resident_bytes: Option<usize>,
+    pub storage: Storage,
+    /// A search that grows its node stack, arena and seen-set past this
+    /// stops early with the suggestions found so far, the way a time budget
+    /// does. Search buffers kept between queries are freed past it too.
+    pub max_search_bytes: Option<usize>,
+    /// On Linux, advise the kernel to read mapped transducers in when the
+    /// archive is opened (`MADV_WILLNEED`), instead of on first use.
+    pub populate: bool,
+    /// On Linux, ask for transducer copies (see `Storage::PreferHeap`) to be
+    /// backed by transparent huge pages, for fewer TLB misses on large
+    /// transducers. Mapped transducers are not affected.
+    pub huge_pages: bool,
+    /// Read the transducer tables through bounds checked accessors, for
+    /// archives that aren't trusted. See `HfstTransducer::set_checked`.
+    pub checked_tables: bool,
+}
+
+impl Default for MemoryPolicy {
+    fn default() -> MemoryPolicy {
+        MemoryPolicy::unlimited()
+    }
+}
+
+impl MemoryPolicy {
+    pub fn unlimited() -> MemoryPolicy {
+        MemoryPolicy {
+            max_resident_bytes: None,
+            storage: Storage::PreferMmap,
+            max_search_bytes: None,
+            populate: false,
+            huge_pages: false,
+            checked_tables: false,
+        }
+    }
+
+    /// For long running servers with memory to spare: transducers are
+    /// copied into huge pages, read from a prefaulted mapping.
+    pub fn server() -> MemoryPolicy {
+        MemoryPolicy {
+            storage: Storage::PreferHeap,
+            populate: true,
+            huge_pages: true,
+            ..MemoryPolicy::unlimited()
+        }
+    }
+
+    /// For archives from users or the network: tables are bounds checked.
+    pub fn untrusted() -> MemoryPolicy {
+        MemoryPolicy {
+            checked_tables: true,
+            ..MemoryPolicy::unlimited()
+        }
+    }
+
+    /// Maps transducers and lets a search use a quarter of `bytes`.
+    pub fn limited(bytes: usize) -> MemoryPolicy {
+        MemoryPolicy {
+            max_resident_bytes: Some(bytes),
+            storage: Storage::PreferMmap,
+            max_search_bytes: Some(bytes / SEARCH_SHARE),
+            populate: false,
+            huge_pages: false,
+            checked_tables: false,
+        }
+    }
+
+    /// What is left of `max_resident_bytes` for transducer copies.
+    #[cfg(feature = "std")]
+    pub(crate) fn heap_budget(&self) -> Option<usize> {
+        self.max_resident_bytes
+            .map(|max| max.saturating_sub(self.max_search_bytes.unwrap_or(0)))
+    }
+}
+
+/// How much memory a speller is using, from `SpellerArchive::memory_usage`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    /// Transducer bytes mapped from a file.
+    pub mapped_bytes: usize,
+    /// Transducer bytes copied into memory.
+    pub heap_bytes: usize,
+    /// The most any one search has allocated.
+    pub search_peak_bytes: usize,
+    /// Searches stopped early by `max_search_bytes`.
+    pub searches_cut_short: usize,
+}
+
+/// Search statistics shared by every query on a speller.
+#[derive(Debug, Default)]
+pub(crate) struct SearchMemory {
+    peak_bytes: AtomicUsize,
+    cut_short: AtomicUsize,
+}
+
+impl SearchMemory {
+    pub fn record(&self, bytes: usize, cut_short: bool) {
+        let mut peak = self.peak_bytes.load(Ordering::Relaxed);
+        while bytes > peak {
+            match self.peak_bytes.compare_exchange_weak(
+                peak,
+                bytes,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(current) => peak = current,
+            }
+        }
+
+        if cut_short {
+            self.cut_short.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn peak_bytes(&self) -> usize {
+        self.peak_bytes.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn cut_short(&self) -> usize {
+        self.cut_short.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budgets() {
+        let policy = MemoryPolicy::limited(64 << 20);
+        assert_eq!(policy.max_search_bytes, Some(16 << 20));
+        assert_eq!(policy.heap_budget(), Some(48 << 20));
+        assert_eq!(MemoryPolicy::unlimited().heap_budget(), None);
+
+        let memory = SearchMemory::default();
+        memory.record(10, false);
+        memory.record(30, true);
+        memory.record(20, false);
+        assert_eq!(memory.peak_bytes(), 30);
+        assert_eq!(memory.cut_short(), 1);
+    }
+}
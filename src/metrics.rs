@@ -0,0 +1,277 @@
+//! Hooks for counting what a speller does, so that a server can export
+//! lookup rates, suggestion latency and cache behaviour to its monitoring.
+//!
+//! A `Metrics` implementation is attached with `Speller::set_metrics`; every
+//! method has an empty default, so an implementation only provides the ones
+//! it records. `Counters` is a ready-made one that renders the Prometheus
+//! text format.
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::fmt::Write;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::sync::RwLock;
+
+/// Why a search returned before it had looked at every candidate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchStop {
+    /// The `time_budget_ms` ran out.
+    TimedOut,
+    /// The speller's `max_search_bytes` was reached.
+    OverMemory,
+}
+
+/// Called from the lookup threads, so implementations should be cheap and
+/// must not block.
+pub trait Metrics: Send + Sync {
+    /// An `is_correct` lookup.
+    fn lookup(&self, _correct: bool) {}
+
+    /// A finished `suggest` call and how long it took, case variants
+    /// included. Not called on WebAssembly or without `std`, which have no
+    /// clock.
+    fn suggest(&self, _elapsed: Duration) {}
+
+    /// A search with `SpellerConfig::prefix_cache` set, and whether it could
+    /// resume from a cached prefix.
+    fn prefix_cache(&self, _hit: bool) {}
+
+    /// A suggestion query through a `SuggestionCache`, and whether the cache
+    /// had the word. Hits make no `suggest` call.
+    fn suggestion_cache(&self, _hit: bool) {}
+
+    fn search_stopped(&self, _reason: SearchStop) {}
+
+    /// A search outgrew the buffers its thread's pool keeps between queries
+    /// (`pool_max`, or `max_search_bytes`), so they were freed afterwards.
+    /// Frequent events mean the pool is sized too small for the traffic.
+    fn pool_exhausted(&self) {}
+}
+
+/// Records nothing; the default for every speller.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoMetrics;
+
+impl Metrics for NoMetrics {}
+
+/// The `Metrics` a speller reports to, replaceable while it is in use.
+pub(crate) struct MetricsSlot(RwLock<Arc<dyn Metrics>>);
+
+impl MetricsSlot {
+    pub fn new() -> MetricsSlot {
+        MetricsSlot(RwLock::new(Arc::new(NoMetrics)))
+    }
+
+    pub fn get(&self) -> Arc<dyn Metrics> {
+        self.0.read().clone()
+    }
+
+    pub fn set(&self, metrics: Arc<dyn Metrics>) {
+        *self.0.write() = metrics;
+    }
+}
+
+impl fmt::Debug for MetricsSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("MetricsSlot")
+    }
+}
+
+/// Upper bounds, in seconds, of the suggestion latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 10] =
+    [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// Counters and a suggestion latency histogram, shareable between spellers.
+/// Needs `std`, as not every `no_std` target has 64 bit atomics.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct Counters {
+    lookups: AtomicU64,
+    lookups_incorrect: AtomicU64,
+    suggestions: AtomicU64,
+    latency_buckets: [AtomicU64; 10],
+    latency_micros: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    suggestion_cache_hits: AtomicU64,
+    suggestion_cache_misses: AtomicU64,
+    timed_out: AtomicU64,
+    over_memory: AtomicU64,
+    pool_exhausted: AtomicU64,
+}
+
+#[cfg(feature = "std")]
+fn add(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(feature = "std")]
+fn get(counter: &AtomicU64) -> u64 {
+    counter.load(Ordering::Relaxed)
+}
+
+#[cfg(feature = "std")]
+impl Counters {
+    pub fn new() -> Counters {
+        Counters::default()
+    }
+
+    /// Every counter in the Prometheus text exposition format, each name
+    /// prefixed with `divvunspell_`.
+    pub fn prometheus(&self) -> String {
+        let mut out = String::new();
+
+        let counters = [
+            (
+                "lookups_total",
+                "Words checked with is_correct.",
+                get(&self.lookups),
+            ),
+            (
+                "lookups_incorrect_total",
+                "Words is_correct rejected.",
+                get(&self.lookups_incorrect),
+            ),
+            (
+                "prefix_cache_hits_total",
+                "Searches resumed from a cached prefix.",
+                get(&self.cache_hits),
+            ),
+            (
+                "prefix_cache_misses_total",
+                "Searches with the prefix cache on that started from the beginning.",
+                get(&self.cache_misses),
+            ),
+            (
+                "suggestion_cache_hits_total",
+                "Suggestion queries answered from the suggestion cache.",
+                get(&self.suggestion_cache_hits),
+            ),
+            (
+                "suggestion_cache_misses_total",
+                "Suggestion queries the suggestion cache didn't have.",
+                get(&self.suggestion_cache_misses),
+            ),
+            (
+                "searches_timed_out_total",
+                "Searches stopped by the time budget.",
+                get(&self.timed_out),
+            ),
+            (
+                "searches_over_memory_total",
+                "Searches stopped by the memory budget.",
+                get(&self.over_memory),
+            ),
+            (
+                "pool_exhausted_total",
+                "Searches whose buffers outgrew the thread pool's limits.",
+                get(&self.pool_exhausted),
+            ),
+        ];
+
+        for (name, help, value) in counters.iter() {
+            let _ = writeln!(out, "# HELP divvunspell_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE divvunspell_{} counter", name);
+            let _ = writeln!(out, "divvunspell_{} {}", name, value);
+        }
+
+        let name = "divvunspell_suggest_seconds";
+        let _ = writeln!(out, "# HELP {} Time taken by suggest calls.", name);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.latency_buckets.iter()) {
+            cumulative += get(count);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+
+        let total = get(&self.suggestions);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, total);
+        let _ = writeln!(
+            out,
+            "{}_sum {}",
+            name,
+            get(&self.latency_micros) as f64 / 1_000_000.0
+        );
+        let _ = writeln!(out, "{}_count {}", name, total);
+
+        out
+    }
+}
+
+#[cfg(feature = "std")]
+impl Metrics for Counters {
+    fn lookup(&self, correct: bool) {
+        add(&self.lookups);
+        if !correct {
+            add(&self.lookups_incorrect);
+        }
+    }
+
+    fn suggest(&self, elapsed: Duration) {
+        add(&self.suggestions);
+
+        let micros = elapsed.as_secs() * 1_000_000 + u64::from(elapsed.subsec_micros());
+        self.latency_micros.fetch_add(micros, Ordering::Relaxed);
+
+        let seconds = micros as f64 / 1_000_000.0;
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            add(&self.latency_buckets[i]);
+        }
+    }
+
+    fn prefix_cache(&self, hit: bool) {
+        add(if hit {
+            &self.cache_hits
+        } else {
+            &self.cache_misses
+        });
+    }
+
+    fn suggestion_cache(&self, hit: bool) {
+        add(if hit {
+            &self.suggestion_cache_hits
+        } else {
+            &self.suggestion_cache_misses
+        });
+    }
+
+    fn search_stopped(&self, reason: SearchStop) {
+        add(match reason {
+            SearchStop::TimedOut => &self.timed_out,
+            SearchStop::OverMemory => &self.over_memory,
+        });
+    }
+
+    fn pool_exhausted(&self) {
+        add(&self.pool_exhausted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prometheus_histogram() {
+        let counters = Counters::new();
+        counters.lookup(true);
+        counters.lookup(false);
+        counters.suggest(Duration::from_micros(800));
+        counters.suggest(Duration::from_millis(30));
+        counters.suggest(Duration::from_secs(2));
+
+        let text = counters.prometheus();
+        assert!(text.contains("divvunspell_lookups_total 2\n"));
+        assert!(text.contains("divvunspell_lookups_incorrect_total 1\n"));
+        assert!(text.contains("divvunspell_suggest_seconds_bucket{le=\"0.001\"} 1\n"));
+        assert!(text.contains("divvunspell_suggest_seconds_bucket{le=\"0.05\"} 2\n"));
+        assert!(text.contains("divvunspell_suggest_seconds_bucket{le=\"1\"} 2\n"));
+        assert!(text.contains("divvunspell_suggest_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("divvunspell_suggest_seconds_count 3\n"));
+    }
+}
@@ -0,0 +1,175 @@
+//! Swift and Kotlin bindings generated by UniFFI from `src/divvunspell.udl`,
+//! built with the `mobile` feature.
+//!
+//! Objects are shared between threads on the generated side, so the user
+//! dictionary sits behind a lock that is never held during a lookup.
+
+use parking_lot::RwLock;
+
+use crate::archive::SpellerArchive;
+use crate::speller::suggestion::Suggestion as CoreSuggestion;
+use crate::speller::user_dictionary::UserDictionary;
+use crate::speller::SpellerConfig;
+use crate::tokenizer::Tokenize;
+
+uniffi::include_scaffolding!("divvunspell");
+
+#[derive(Debug)]
+pub enum SpellerError {
+    Open { message: String },
+}
+
+impl std::error::Error for SpellerError {}
+
+impl std::fmt::Display for SpellerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            SpellerError::Open { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+pub struct Suggestion {
+    pub value: String,
+    pub weight: f32,
+}
+
+impl From<CoreSuggestion> for Suggestion {
+    fn from(s: CoreSuggestion) -> Suggestion {
+        Suggestion {
+            value: s.value.to_string(),
+            weight: s.weight,
+        }
+    }
+}
+
+pub struct WordBoundary {
+    pub offset: u64,
+    pub utf16_offset: u64,
+    pub segment: String,
+}
+
+pub struct SuggestOptions {
+    pub n_best: Option<u32>,
+    pub max_weight: Option<f32>,
+    pub beam: Option<f32>,
+    pub time_budget_ms: Option<u64>,
+}
+
+pub struct Speller {
+    archive: SpellerArchive,
+    user: RwLock<UserDictionary>,
+}
+
+impl Speller {
+    pub fn new(path: String) -> Result<Speller, SpellerError> {
+        match SpellerArchive::new(&path) {
+            Ok(archive) => Ok(Speller::with_archive(archive)),
+            Err(e) => Err(SpellerError::Open {
+                message: format!("{}: {}", path, e),
+            }),
+        }
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Speller, SpellerError> {
+        match SpellerArchive::from_bytes(&data) {
+            Ok(archive) => Ok(Speller::with_archive(archive)),
+            Err(e) => Err(SpellerError::Open {
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    fn with_archive(archive: SpellerArchive) -> Speller {
+        Speller {
+            archive,
+            user: RwLock::new(UserDictionary::new()),
+        }
+    }
+
+    pub fn locale(&self) -> String {
+        self.archive.metadata().info.locale.clone()
+    }
+
+    /// Words in the user dictionary count as correct.
+    pub fn is_correct(&self, word: String) -> bool {
+        self.user.read().contains(&word) || self.archive.speller().is_correct(&word)
+    }
+
+    /// Suggestions for `word`, best first, with close user words ahead of
+    /// the speller's own.
+    pub fn suggest(&self, word: String, options: SuggestOptions) -> Vec<Suggestion> {
+        let defaults = SpellerConfig::default();
+        let cfg = SpellerConfig {
+            n_best: options.n_best.map(|n| n as usize).or(defaults.n_best),
+            max_weight: options.max_weight.or(defaults.max_weight),
+            beam: options.beam.or(defaults.beam),
+            time_budget_ms: options.time_budget_ms.or(defaults.time_budget_ms),
+            ..defaults
+        };
+
+        if self.user.read().contains(&word) {
+            return vec![];
+        }
+
+        let found = self.archive.speller().suggest_with_config(&word, &cfg);
+        self.user
+            .read()
+            .merge(&word, found, cfg.n_best)
+            .into_iter()
+            .map(Suggestion::from)
+            .collect()
+    }
+
+    pub fn add_user_word(&self, word: String) -> bool {
+        self.user.write().insert(&word)
+    }
+
+    pub fn remove_user_word(&self, word: String) -> bool {
+        self.user.write().remove(&word)
+    }
+
+    pub fn user_words(&self) -> Vec<String> {
+        self.user.read().words().map(str::to_string).collect()
+    }
+
+    /// Whether enough user words have been added that `compile_user_words`
+    /// would speed up suggestions.
+    pub fn needs_compile(&self) -> bool {
+        self.user.read().needs_compile()
+    }
+
+    /// Compiles the user words into an overlay searched with the speller's
+    /// error model. Slow for a large dictionary, so call it off the UI
+    /// thread; lookups and edits carry on meanwhile.
+    pub fn compile_user_words(&self) -> bool {
+        let snapshot = self.user.read().clone();
+        match snapshot.compile(self.archive.speller().mutator()) {
+            Ok(overlay) => {
+                self.user.write().install(overlay);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+pub fn words(text: String) -> Vec<String> {
+    text.words().map(str::to_string).collect()
+}
+
+pub fn word_bound_indices(text: String) -> Vec<WordBoundary> {
+    let mut utf16_offset = 0;
+
+    text.word_bound_indices()
+        .map(|(offset, segment)| {
+            let boundary = WordBoundary {
+                offset: offset as u64,
+                utf16_offset,
+                segment: segment.to_string(),
+            };
+            utf16_offset += segment.encode_utf16().count() as u64;
+            boundary
+        })
+        .collect()
+}
@@ -0,0 +1,204 @@
+//! Node.js bindings through N-API, built with the `node` feature.
+//!
+//! ```js
+//! const { Speller, TextChecker } = require("./divvunspell.node");
+//!
+//! const speller = Speller.open("se.zhfst");
+//! speller.isCorrect("giella");             // true
+//! await speller.suggest("gielaa", 5);      // [{ value: "giella", weight: 10.2 }, ...]
+//!
+//! const checker = new TextChecker(speller, 3);
+//! await checker.check("Mun lean gielaa."); // [{ word: "gielaa", start: 9, end: 15, suggestions: [...] }]
+//! ```
+//!
+//! `suggest` and `check` run on the libuv threadpool and return promises, so
+//! they never block the event loop. Offsets count UTF-16 code units, like
+//! JavaScript string indexes.
+
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use std::sync::Arc;
+
+use crate::archive::SpellerArchive;
+use crate::speller::suggestion::Suggestion;
+use crate::speller::{Speller, SpellerConfig};
+use crate::tokenizer::Tokenize;
+use crate::transducer::HfstTransducer;
+
+#[napi(object, js_name = "Suggestion")]
+pub struct JsSuggestion {
+    pub value: String,
+    pub weight: f64,
+}
+
+impl From<Suggestion> for JsSuggestion {
+    fn from(s: Suggestion) -> JsSuggestion {
+        JsSuggestion {
+            value: s.value.to_string(),
+            weight: f64::from(s.weight),
+        }
+    }
+}
+
+#[napi(object)]
+pub struct Misspelling {
+    pub word: String,
+    pub start: u32,
+    pub end: u32,
+    pub suggestions: Vec<JsSuggestion>,
+}
+
+fn suggest_config(n_best: Option<u32>) -> SpellerConfig {
+    let defaults = SpellerConfig::default();
+    SpellerConfig {
+        n_best: n_best.map(|n| n as usize).or(defaults.n_best),
+        ..defaults
+    }
+}
+
+#[napi(js_name = "Speller")]
+pub struct NodeSpeller {
+    speller: Arc<Speller<HfstTransducer>>,
+    locale: String,
+}
+
+#[napi]
+impl NodeSpeller {
+    /// Opens a ZHFST archive.
+    #[napi(factory)]
+    pub fn open(path: String) -> Result<NodeSpeller> {
+        let archive = SpellerArchive::new(&path)
+            .map_err(|e| Error::new(Status::InvalidArg, format!("{}: {}", path, e)))?;
+
+        Ok(NodeSpeller {
+            speller: archive.speller(),
+            locale: archive.metadata().info.locale.clone(),
+        })
+    }
+
+    #[napi(getter)]
+    pub fn locale(&self) -> String {
+        self.locale.clone()
+    }
+
+    #[napi]
+    pub fn is_correct(&self, word: String) -> bool {
+        self.speller.clone().is_correct(&word)
+    }
+
+    /// Resolves to `{ value, weight }` objects, best first.
+    #[napi(ts_return_type = "Promise<Suggestion[]>")]
+    pub fn suggest(&self, word: String, n_best: Option<u32>) -> AsyncTask<SuggestTask> {
+        AsyncTask::new(SuggestTask {
+            speller: self.speller.clone(),
+            word,
+            cfg: suggest_config(n_best),
+        })
+    }
+}
+
+pub struct SuggestTask {
+    speller: Arc<Speller<HfstTransducer>>,
+    word: String,
+    cfg: SpellerConfig,
+}
+
+impl Task for SuggestTask {
+    type Output = Vec<Suggestion>;
+    type JsValue = Vec<JsSuggestion>;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        Ok(self
+            .speller
+            .clone()
+            .suggest_with_config(&self.word, &self.cfg))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output.into_iter().map(JsSuggestion::from).collect())
+    }
+}
+
+/// Finds misspelled words in running text, with suggestions for each.
+#[napi]
+pub struct TextChecker {
+    speller: Arc<Speller<HfstTransducer>>,
+    cfg: SpellerConfig,
+}
+
+#[napi]
+impl TextChecker {
+    /// `n_best` limits the suggestions given for each misspelling.
+    #[napi(constructor)]
+    pub fn new(speller: &NodeSpeller, n_best: Option<u32>) -> TextChecker {
+        TextChecker {
+            speller: speller.speller.clone(),
+            cfg: suggest_config(n_best),
+        }
+    }
+
+    #[napi(ts_return_type = "Promise<Misspelling[]>")]
+    pub fn check(&self, text: String) -> AsyncTask<CheckTask> {
+        AsyncTask::new(CheckTask {
+            speller: self.speller.clone(),
+            text,
+            cfg: self.cfg.clone(),
+        })
+    }
+
+    #[napi]
+    pub fn check_sync(&self, text: String) -> Vec<Misspelling> {
+        check_text(&self.speller, &text, &self.cfg)
+    }
+}
+
+pub struct CheckTask {
+    speller: Arc<Speller<HfstTransducer>>,
+    text: String,
+    cfg: SpellerConfig,
+}
+
+impl Task for CheckTask {
+    type Output = Vec<Misspelling>;
+    type JsValue = Vec<Misspelling>;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        Ok(check_text(&self.speller, &self.text, &self.cfg))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+fn check_text(
+    speller: &Arc<Speller<HfstTransducer>>,
+    text: &str,
+    cfg: &SpellerConfig,
+) -> Vec<Misspelling> {
+    let mut offset = 0;
+    let mut misspellings = vec![];
+
+    for (_, token) in text.word_bound_indices() {
+        let start = offset;
+        offset += token.encode_utf16().count() as u32;
+
+        if !token.chars().any(char::is_alphabetic) || speller.clone().is_correct(token) {
+            continue;
+        }
+
+        misspellings.push(Misspelling {
+            word: token.to_string(),
+            start,
+            end: offset,
+            suggestions: speller
+                .clone()
+                .suggest_with_config(token, cfg)
+                .into_iter()
+                .map(JsSuggestion::from)
+                .collect(),
+        });
+    }
+
+    misspellings
+}
@@ -0,0 +1,24 @@
+//! What most programs need to check and correct text, in one import:
+//!
+//! ```rust,ignore
+//! use divvunspell::prelude::*;
+//!
+//! let archive = SpellerArchive::new("se.zhfst")?;
+//! let speller = archive.speller();
+//! for (offset, word) in speller.clone().check_text("Mun hálan sámegiela") {
+//!     let suggestions = speller.clone().suggest(word);
+//! }
+//! ```
+//!
+//! These names follow semver: they are only removed or changed in a major
+//! release. The transducer tables and search internals are not part of it,
+//! and are only public with the `internals` feature.
+
+#[cfg(feature = "std")]
+pub use crate::archive::SpellerArchive;
+pub use crate::speller::issue::SpellingIssue;
+pub use crate::speller::suggestion::Suggestion;
+pub use crate::speller::{ArcSpeller, Speller, SpellerConfig, WordCheck};
+#[cfg(feature = "std")]
+pub use crate::tokenizer::Tokenize;
+pub use crate::transducer::{HfstTransducer, Transducer};
@@ -0,0 +1,124 @@
+//! Python bindings, built with the `python` feature.
+//!
+//! ```python
+//! import divvunspell
+//!
+//! archive = divvunspell.SpellerArchive("se.zhfst")
+//! speller = archive.speller()
+//! speller.is_correct("giella")
+//! speller.suggest("gielaa", n_best=5)  # [("giella", 10.2), ...]
+//! divvunspell.words("Mun lean giella.")
+//! ```
+//!
+//! Lookups release the GIL, so a speller can be shared by Python threads.
+
+use pyo3::exceptions::IOError;
+use pyo3::prelude::*;
+use pyo3::wrap_pyfunction;
+use std::sync::Arc;
+
+use crate::archive::SpellerArchive;
+use crate::speller::{Speller, SpellerConfig};
+use crate::tokenizer::Tokenize;
+use crate::transducer::HfstTransducer;
+
+#[pyclass(name = SpellerArchive)]
+pub struct PySpellerArchive {
+    archive: SpellerArchive,
+}
+
+#[pymethods]
+impl PySpellerArchive {
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        match SpellerArchive::new(path) {
+            Ok(archive) => Ok(PySpellerArchive { archive }),
+            Err(e) => Err(IOError::py_err(format!("{}: {}", path, e))),
+        }
+    }
+
+    fn speller(&self) -> PySpeller {
+        PySpeller {
+            speller: self.archive.speller(),
+        }
+    }
+
+    #[getter]
+    fn locale(&self) -> String {
+        self.archive.metadata().info.locale.clone()
+    }
+}
+
+#[pyclass(name = Speller)]
+pub struct PySpeller {
+    speller: Arc<Speller<HfstTransducer>>,
+}
+
+#[pymethods]
+impl PySpeller {
+    fn is_correct(&self, py: Python, word: &str) -> bool {
+        let speller = self.speller.clone();
+        py.allow_threads(move || speller.is_correct(word))
+    }
+
+    /// The lexicon's weight for `word`, lower being likelier, or `None` if
+    /// it isn't accepted.
+    fn score(&self, py: Python, word: &str) -> Option<f32> {
+        let speller = self.speller.clone();
+        py.allow_threads(move || speller.score(word))
+    }
+
+    /// Returns `(suggestion, weight)` pairs, best first. Unset limits use the
+    /// library defaults.
+    #[args(n_best = "None", max_weight = "None", beam = "None")]
+    fn suggest(
+        &self,
+        py: Python,
+        word: &str,
+        n_best: Option<usize>,
+        max_weight: Option<f32>,
+        beam: Option<f32>,
+    ) -> Vec<(String, f32)> {
+        let defaults = SpellerConfig::default();
+        let cfg = SpellerConfig {
+            n_best: n_best.or(defaults.n_best),
+            max_weight: max_weight.or(defaults.max_weight),
+            beam: beam.or(defaults.beam),
+            ..defaults
+        };
+        let speller = self.speller.clone();
+
+        py.allow_threads(move || {
+            speller
+                .suggest_with_config(word, &cfg)
+                .into_iter()
+                .map(|s| (s.value().to_string(), s.weight()))
+                .collect()
+        })
+    }
+}
+
+/// Splits `text` at word boundaries, returning `(byte offset, segment)`
+/// pairs that cover the whole text.
+#[pyfunction]
+fn word_bound_indices(text: &str) -> Vec<(usize, String)> {
+    text.word_bound_indices()
+        .map(|(i, s)| (i, s.to_string()))
+        .collect()
+}
+
+/// The words in `text`, skipping whitespace and punctuation.
+#[pyfunction]
+fn words(text: &str) -> Vec<String> {
+    text.words().map(str::to_string).collect()
+}
+
+#[pymodule]
+fn divvunspell(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PySpellerArchive>()?;
+    m.add_class::<PySpeller>()?;
+    m.add_wrapped(wrap_pyfunction!(word_bound_indices))?;
+    m.add_wrapped(wrap_pyfunction!(words))?;
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    Ok(())
+}
@@ -0,0 +1,130 @@
+//! A beam whose width follows the search, for `SpellerConfig::adaptive_beam`.
+//!
+//! A fixed beam suits some words and not others: short words have few
+//! candidates within a narrow beam, long ones thousands within a wide one.
+//! Here the width starts at `beam` and is looked at again every window of
+//! expanded nodes. It widens, up to `beam_max`, while most nodes in a window
+//! are pruned by it and fewer than `n_best` suggestions have been found; once
+//! `n_best` suggestions are found it narrows toward `beam_min`, never below
+//! what the worst of them needs.
+
+use super::SpellerConfig;
+use crate::speller::suggestion::Suggestion;
+use crate::types::Weight;
+
+/// Each widening multiplies the width by this, each narrowing divides it.
+const BEAM_STEP: Weight = 1.5;
+
+/// Width to start from when `beam` isn't set.
+const DEFAULT_START: Weight = 10.0;
+
+#[derive(Debug)]
+pub struct AdaptiveBeam {
+    width: Weight,
+    min: Weight,
+    max: Weight,
+    n_best: usize,
+    kept: usize,
+    pruned: usize,
+}
+
+impl AdaptiveBeam {
+    pub fn new(config: &SpellerConfig) -> Option<AdaptiveBeam> {
+        if !config.adaptive_beam {
+            return None;
+        }
+
+        let min = config.beam_min.max(0.0);
+        let max = config.beam_max.max(min);
+
+        Some(AdaptiveBeam {
+            width: config.beam.unwrap_or(DEFAULT_START).max(min).min(max),
+            min,
+            max,
+            n_best: config.n_best.unwrap_or(1).max(1),
+            kept: 0,
+            pruned: 0,
+        })
+    }
+
+    pub fn width(&self) -> Weight {
+        self.width
+    }
+
+    /// The weight limit for the next node. `suggestions` are sorted, best
+    /// first.
+    pub fn limit(&self, max_weight: Weight, suggestions: &[Suggestion]) -> Weight {
+        let best = match suggestions.first() {
+            Some(best) => best.weight(),
+            None => return max_weight,
+        };
+
+        let mut limit = (best + self.width).min(max_weight);
+        if suggestions.len() >= self.n_best {
+            limit = limit.min(suggestions[self.n_best - 1].weight());
+        }
+
+        limit
+    }
+
+    /// Counts a node against the current window. Only nodes under the
+    /// fixed `max_weight` that the beam prunes count as pruned.
+    pub fn observe(&mut self, pruned_by_beam: bool) {
+        if pruned_by_beam {
+            self.pruned += 1;
+        } else {
+            self.kept += 1;
+        }
+    }
+
+    /// Ends a window, adjusting the width for the next one.
+    pub fn adjust(&mut self, suggestions: &[Suggestion]) {
+        if suggestions.len() >= self.n_best {
+            let needed = suggestions[self.n_best - 1].weight() - suggestions[0].weight();
+            self.width = (self.width / BEAM_STEP).max(self.min).max(needed);
+        } else if self.pruned > self.kept {
+            self.width = (self.width * BEAM_STEP).min(self.max);
+        }
+
+        self.kept = 0;
+        self.pruned = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widens_then_narrows() {
+        let config = SpellerConfig {
+            n_best: Some(2),
+            beam: Some(4.0),
+            adaptive_beam: true,
+            beam_min: 2.0,
+            beam_max: 9.0,
+            ..SpellerConfig::default()
+        };
+        let mut beam = AdaptiveBeam::new(&config).unwrap();
+        let one = vec![Suggestion::new("a".into(), 1.0)];
+
+        assert_eq!(beam.limit(100.0, &[]), 100.0);
+        assert_eq!(beam.limit(100.0, &one), 5.0);
+
+        for _ in 0..3 {
+            (0..10).for_each(|_| beam.observe(true));
+            beam.adjust(&one);
+        }
+        assert_eq!(beam.width(), 9.0);
+
+        let two = vec![
+            Suggestion::new("a".into(), 1.0),
+            Suggestion::new("b".into(), 3.5),
+        ];
+        assert_eq!(beam.limit(100.0, &two), 3.5);
+        for _ in 0..5 {
+            beam.adjust(&two);
+        }
+        assert_eq!(beam.width(), 2.5);
+    }
+}
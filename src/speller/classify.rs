@@ -0,0 +1,194 @@
+//! What kind of mistake a misspelling probably is, for analytics and for
+//! language learning tools that explain errors rather than just fix them.
+//!
+//! A misspelling is classified by the edits that turn it into its best
+//! suggestion, in this order:
+//!
+//! - `compound_split`: the suggestion is the word with a space or hyphen
+//!   put in, as for two words run together
+//! - `transposition`: two neighbouring letters swapped
+//! - `missing_diacritic`: letters that differ only in their diacritics,
+//!   usually "a" typed for "á"
+//! - `keyboard_slip`: letters replaced by ones on neighbouring QWERTY keys,
+//!   or a letter hit twice or next to its neighbour
+//! - `other`: anything else
+//!
+//! A misspelling with no suggestions is an `unknown_word`. Case is ignored
+//! throughout.
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::profile::InputProfile;
+use crate::errmodel::align::{align, Edit};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    MissingDiacritic,
+    Transposition,
+    KeyboardSlip,
+    CompoundSplit,
+    UnknownWord,
+    Other,
+}
+
+impl std::fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let name = match self {
+            ErrorClass::MissingDiacritic => "missing diacritic",
+            ErrorClass::Transposition => "transposition",
+            ErrorClass::KeyboardSlip => "keyboard slip",
+            ErrorClass::CompoundSplit => "compound split needed",
+            ErrorClass::UnknownWord => "unknown word",
+            ErrorClass::Other => "other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Letters with diacritics and the letter they are written on.
+const DIACRITICS: &[(&str, char)] = &[
+    ("áàâäãåāąă", 'a'),
+    ("čćçĉ", 'c'),
+    ("đď", 'd'),
+    ("éèêëēęěė", 'e'),
+    ("ǧğ", 'g'),
+    ("íìîïīį", 'i'),
+    ("ǩ", 'k'),
+    ("łľĺ", 'l'),
+    ("ŋńñňņ", 'n'),
+    ("óòôöõøōő", 'o'),
+    ("řŕ", 'r'),
+    ("šśş", 's'),
+    ("ŧťţ", 't'),
+    ("úùûüūůű", 'u'),
+    ("ýÿ", 'y'),
+    ("žźż", 'z'),
+    ("ʒǯ", 'ʒ'),
+];
+
+fn lower(ch: char) -> char {
+    ch.to_lowercase().next().unwrap_or(ch)
+}
+
+fn base_letter(ch: char) -> char {
+    DIACRITICS
+        .iter()
+        .find(|(marked, _)| marked.contains(ch))
+        .map(|&(_, base)| base)
+        .unwrap_or(ch)
+}
+
+fn same_but_diacritics(a: char, b: char) -> bool {
+    a != b && base_letter(a) == base_letter(b)
+}
+
+fn is_transposition(word: &[char], suggestion: &[char]) -> bool {
+    if word.len() != suggestion.len() {
+        return false;
+    }
+
+    let differ: Vec<usize> = (0..word.len())
+        .filter(|&i| word[i] != suggestion[i])
+        .collect();
+    match differ[..] {
+        [i, j] => j == i + 1 && word[i] == suggestion[j] && word[j] == suggestion[i],
+        _ => false,
+    }
+}
+
+/// Whether the extra `letter` at `at` in `word` sits next to a copy of
+/// itself or a neighbouring key, as when a key is hit twice or with its
+/// neighbour.
+fn is_extra_key(word: &[char], at: usize, letter: char) -> bool {
+    let keyboard = InputProfile::PhysicalKeyboard;
+    let near = |ch: char| ch == letter || keyboard.is_close(ch, letter);
+
+    (at > 0 && near(word[at - 1])) || word.get(at + 1).is_some_and(|&ch| near(ch))
+}
+
+/// What kind of mistake `word` is, taking `suggestion` as what was meant,
+/// or `None` if nothing was suggested.
+pub fn classify(word: &str, suggestion: Option<&str>) -> ErrorClass {
+    let suggestion = match suggestion {
+        Some(s) => s,
+        None => return ErrorClass::UnknownWord,
+    };
+
+    let word: String = word.chars().map(lower).collect();
+    let suggestion: String = suggestion.chars().map(lower).collect();
+
+    let joined: String = suggestion
+        .chars()
+        .filter(|&ch| ch != ' ' && ch != '-')
+        .collect();
+    if joined == word && joined != suggestion {
+        return ErrorClass::CompoundSplit;
+    }
+
+    let word_chars: Vec<char> = word.chars().collect();
+    let suggestion_chars: Vec<char> = suggestion.chars().collect();
+    if is_transposition(&word_chars, &suggestion_chars) {
+        return ErrorClass::Transposition;
+    }
+
+    let edits = align(&word, &suggestion);
+    if edits.is_empty() {
+        return ErrorClass::Other;
+    }
+
+    if edits.iter().all(|edit| match *edit {
+        Edit::Substitution { from, to } => same_but_diacritics(from, to),
+        _ => false,
+    }) {
+        return ErrorClass::MissingDiacritic;
+    }
+
+    let keyboard = InputProfile::PhysicalKeyboard;
+    if edits.len() == 1 {
+        let slip = match edits[0] {
+            Edit::Substitution { from, to } => keyboard.is_close(from, to),
+            Edit::Deletion { from } => word_chars
+                .iter()
+                .enumerate()
+                .filter(|&(_, &ch)| ch == from)
+                .any(|(at, _)| is_extra_key(&word_chars, at, from)),
+            Edit::Insertion { .. } => false,
+        };
+        if slip {
+            return ErrorClass::KeyboardSlip;
+        }
+    } else if edits.iter().all(|edit| match *edit {
+        Edit::Substitution { from, to } => keyboard.is_close(from, to),
+        _ => false,
+    }) {
+        return ErrorClass::KeyboardSlip;
+    }
+
+    ErrorClass::Other
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classes() {
+        let class = |word, suggestion| classify(word, Some(suggestion));
+
+        assert_eq!(
+            class("samegiella", "sámegiella"),
+            ErrorClass::MissingDiacritic
+        );
+        assert_eq!(class("Cahci", "Čáhci"), ErrorClass::MissingDiacritic);
+        assert_eq!(class("gielal", "giella"), ErrorClass::Transposition);
+        assert_eq!(class("giwlla", "giella"), ErrorClass::KeyboardSlip);
+        assert_eq!(class("gieella", "giella"), ErrorClass::KeyboardSlip);
+        assert_eq!(
+            class("sámegiella", "sáme giella"),
+            ErrorClass::CompoundSplit
+        );
+        assert_eq!(class("gilla", "giella"), ErrorClass::Other);
+        assert_eq!(classify("xyz", None), ErrorClass::UnknownWord);
+    }
+}
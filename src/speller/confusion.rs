@@ -0,0 +1,334 @@
+//! Real-word errors: tokens the lexicon accepts that are probably not the
+//! word the writer meant, such as "their" for "there".
+//!
+//! A lexicon lookup can't catch these, so `Speller::check_confusions` looks
+//! at context instead. Each word in a `ConfusionSets` entry is scored
+//! against its neighbours with a `Bigrams` model, and a token is flagged as
+//! a `PossibleConfusion` when another word of its set scores better by more
+//! than `RealWordChecker::margin`. Only words in a confusion set are ever
+//! flagged, so the layer costs nothing for the rest of the text.
+//!
+//! Both files are plain text. Confusion sets are one set per line, its
+//! words separated by whitespace; bigrams are `previous\tword\tcount` lines.
+//! Lines starting with `#` are skipped in both.
+
+use hashbrown::{HashMap, HashSet};
+use smol_str::SmolStr;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::suggestion::Suggestion;
+use super::Speller;
+use crate::tokenizer::caps::{is_first_caps, lower_case, upper_first};
+use crate::transducer::Transducer;
+use crate::types::Weight;
+
+/// How much more likely, as a natural log ratio, an alternative must be
+/// before a token is flagged. About a factor of 20.
+const DEFAULT_MARGIN: f64 = 3.0;
+
+#[derive(Debug)]
+pub enum ConfusionError {
+    Io(io::Error),
+    BadLine { line: usize, text: String },
+}
+
+impl std::error::Error for ConfusionError {}
+
+impl fmt::Display for ConfusionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfusionError::Io(e) => write!(f, "{}", e),
+            ConfusionError::BadLine { line, text } => write!(f, "line {}: {}", line, text),
+        }
+    }
+}
+
+impl From<io::Error> for ConfusionError {
+    fn from(e: io::Error) -> ConfusionError {
+        ConfusionError::Io(e)
+    }
+}
+
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Groups of words that are easily written for one another.
+#[derive(Debug, Default)]
+pub struct ConfusionSets {
+    sets: Vec<Vec<SmolStr>>,
+    index: HashMap<SmolStr, Vec<usize>>,
+}
+
+impl ConfusionSets {
+    pub fn new() -> ConfusionSets {
+        ConfusionSets::default()
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ConfusionSets, ConfusionError> {
+        ConfusionSets::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<ConfusionSets, ConfusionError> {
+        let mut sets = ConfusionSets::new();
+
+        for (line, words) in lines(text) {
+            let words: Vec<&str> = words.split_whitespace().collect();
+            if words.len() < 2 {
+                return Err(ConfusionError::BadLine {
+                    line,
+                    text: words.join(" "),
+                });
+            }
+            sets.add(&words);
+        }
+
+        Ok(sets)
+    }
+
+    /// Adds a set; words are matched case insensitively.
+    pub fn add(&mut self, words: &[&str]) {
+        let set: Vec<SmolStr> = words.iter().map(|w| lower_case(w)).collect();
+        let id = self.sets.len();
+
+        for word in &set {
+            self.index
+                .entry(word.clone())
+                .or_insert_with(Vec::new)
+                .push(id);
+        }
+        self.sets.push(set);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// The other words of every set `word` is in.
+    pub fn alternatives(&self, word: &str) -> Vec<SmolStr> {
+        let word = lower_case(word);
+        let mut alternatives: Vec<SmolStr> = self
+            .index
+            .get(&word)
+            .into_iter()
+            .flatten()
+            .flat_map(|id| self.sets[*id].iter())
+            .filter(|w| **w != word)
+            .cloned()
+            .collect();
+
+        alternatives.sort();
+        alternatives.dedup();
+        alternatives
+    }
+}
+
+/// Counts of adjacent word pairs from a corpus, lower cased.
+#[derive(Debug, Default)]
+pub struct Bigrams {
+    pairs: HashMap<(SmolStr, SmolStr), u64>,
+    /// How often each word starts a pair.
+    firsts: HashMap<SmolStr, u64>,
+    vocabulary: HashSet<SmolStr>,
+}
+
+impl Bigrams {
+    pub fn new() -> Bigrams {
+        Bigrams::default()
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Bigrams, ConfusionError> {
+        Bigrams::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Bigrams, ConfusionError> {
+        let mut bigrams = Bigrams::new();
+
+        for (line, fields) in lines(text) {
+            let parts: Vec<&str> = fields.split('\t').collect();
+            match (
+                parts.len(),
+                parts.get(2).and_then(|c| c.trim().parse().ok()),
+            ) {
+                (3, Some(count)) => bigrams.add(parts[0], parts[1], count),
+                _ => {
+                    return Err(ConfusionError::BadLine {
+                        line,
+                        text: fields.to_string(),
+                    })
+                }
+            }
+        }
+
+        Ok(bigrams)
+    }
+
+    /// Adds `count` occurrences of `word` following `previous`.
+    pub fn add(&mut self, previous: &str, word: &str, count: u64) {
+        let previous = lower_case(previous);
+        let word = lower_case(word);
+
+        *self.firsts.entry(previous.clone()).or_insert(0) += count;
+        self.vocabulary.insert(previous.clone());
+        self.vocabulary.insert(word.clone());
+        *self.pairs.entry((previous, word)).or_insert(0) += count;
+    }
+
+    /// The log probability of `word` following `previous`, add-one smoothed.
+    fn log_probability(&self, previous: &str, word: &str) -> f64 {
+        let key = (SmolStr::new(previous), SmolStr::new(word));
+        let count = self.pairs.get(&key).copied().unwrap_or(0);
+        let total = self.firsts.get(previous).copied().unwrap_or(0);
+
+        ((count + 1) as f64 / (total + self.vocabulary.len() as u64 + 1) as f64).ln()
+    }
+
+    /// How well `word` fits between its neighbours, as a log probability.
+    pub fn score(&self, previous: Option<&str>, word: &str, next: Option<&str>) -> f64 {
+        let word = lower_case(word);
+        let before = previous.map_or(0.0, |p| self.log_probability(&lower_case(p), &word));
+        let after = next.map_or(0.0, |n| self.log_probability(&word, &lower_case(n)));
+        before + after
+    }
+}
+
+/// Confusion sets and the model that decides between them.
+#[derive(Debug)]
+pub struct RealWordChecker {
+    pub sets: ConfusionSets,
+    pub bigrams: Bigrams,
+    /// How much better, as a natural log ratio, an alternative has to score
+    /// than the word written for the word to be flagged.
+    pub margin: f64,
+}
+
+impl RealWordChecker {
+    pub fn new(sets: ConfusionSets, bigrams: Bigrams) -> RealWordChecker {
+        RealWordChecker {
+            sets,
+            bigrams,
+            margin: DEFAULT_MARGIN,
+        }
+    }
+
+    /// The alternatives to `word` that fit its context better by more than
+    /// `margin`, best first. Weights are the log ratio of the word written
+    /// to the alternative, so that lighter is likelier, as with suggestions.
+    pub fn alternatives(
+        &self,
+        previous: Option<&str>,
+        word: &str,
+        next: Option<&str>,
+    ) -> Vec<Suggestion> {
+        let written = self.bigrams.score(previous, word, next);
+
+        let mut alternatives: Vec<Suggestion> = self
+            .sets
+            .alternatives(word)
+            .into_iter()
+            .filter_map(|alt| {
+                let gain = self.bigrams.score(previous, &alt, next) - written;
+                if gain <= self.margin {
+                    return None;
+                }
+
+                let value = if is_first_caps(word) {
+                    upper_first(&alt)
+                } else {
+                    alt
+                };
+                Some(Suggestion::new(value, -gain as Weight))
+            })
+            .collect();
+
+        alternatives.sort();
+        alternatives
+    }
+}
+
+/// A correctly spelled token that another word fits better in context.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PossibleConfusion<'a> {
+    /// Byte offset of the token in the text.
+    pub offset: usize,
+    pub word: &'a str,
+    pub alternatives: Vec<Suggestion>,
+}
+
+impl<T: Transducer> Speller<T> {
+    /// The words in `text` that the lexicon accepts but that `checker`
+    /// thinks were meant as another word of their confusion set. Only
+    /// alternatives the lexicon accepts are given. Neighbours are the
+    /// adjacent word tokens, punctuation skipped; misspelled words are left
+    /// to `check_text`.
+    pub fn check_confusions<'a>(
+        self: Arc<Self>,
+        text: &'a str,
+        checker: &RealWordChecker,
+    ) -> Vec<PossibleConfusion<'a>> {
+        use crate::tokenizer::Tokenize;
+
+        let words: Vec<(usize, &str)> = text
+            .word_bound_indices()
+            .filter(|(_, token)| token.chars().any(char::is_alphabetic))
+            .collect();
+
+        let mut confusions = vec![];
+        for (i, (offset, word)) in words.iter().enumerate() {
+            if checker.sets.alternatives(word).is_empty() || !self.clone().is_correct(word) {
+                continue;
+            }
+
+            let previous = i.checked_sub(1).map(|i| words[i].1);
+            let next = words.get(i + 1).map(|w| w.1);
+            let alternatives: Vec<Suggestion> = checker
+                .alternatives(previous, word, next)
+                .into_iter()
+                .filter(|alt| self.clone().is_correct(alt.value()))
+                .collect();
+
+            if !alternatives.is_empty() {
+                confusions.push(PossibleConfusion {
+                    offset: *offset,
+                    word,
+                    alternatives,
+                });
+            }
+        }
+
+        confusions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_decides() {
+        let sets = ConfusionSets::parse("# their/there\ntheir there\n").unwrap();
+        let bigrams =
+            Bigrams::parse("over\tthere\t100\nover\ttheir\t1\ntheir\thouse\t30\n").unwrap();
+        let checker = RealWordChecker::new(sets, bigrams);
+
+        let flagged = checker.alternatives(Some("over"), "Their", None);
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].value(), "There");
+        assert!(checker.alternatives(Some("over"), "there", None).is_empty());
+        assert!(checker
+            .alternatives(None, "their", Some("house"))
+            .is_empty());
+
+        assert!(Bigrams::parse("over\tthere\n").is_err());
+    }
+}
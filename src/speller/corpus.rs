@@ -0,0 +1,249 @@
+//! Estimating how much of a raw corpus a speller flags, to tell lexicon
+//! gaps from genuine errors.
+//!
+//! A `CorpusSurvey` checks every word token it is given and counts the
+//! ones the speller rejects. Its `CorpusReport` has the flagged share of
+//! tokens, the most frequent flagged words, which in a large corpus are
+//! mostly words missing from the lexicon, and a uniform random sample of
+//! flagged tokens in context, to review by hand for how many are real
+//! errors. Each distinct word is only looked up once.
+
+use hashbrown::{HashMap, HashSet};
+use serde_derive::{Deserialize, Serialize};
+use std::io::{self, BufRead};
+use std::sync::Arc;
+
+use super::Speller;
+use crate::errmodel::typos::Rng;
+use crate::tokenizer::Tokenize;
+use crate::transducer::Transducer;
+
+/// Characters of context kept on each side of a sampled token.
+const CONTEXT_CHARS: usize = 30;
+
+#[derive(Clone, Debug)]
+pub struct SurveyConfig {
+    /// How many of the most frequent flagged words to report.
+    pub top_words: usize,
+    /// How many flagged tokens to sample for review.
+    pub sample_size: usize,
+    /// Seed for the sample, so that a run can be repeated.
+    pub seed: u64,
+}
+
+impl Default for SurveyConfig {
+    fn default() -> SurveyConfig {
+        SurveyConfig {
+            top_words: 50,
+            sample_size: 100,
+            seed: 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FlaggedWord {
+    pub word: String,
+    pub count: u64,
+    /// The speller's top suggestion, if it has one.
+    pub suggestion: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FlaggedToken {
+    pub word: String,
+    /// The file, or other source, as given to `CorpusSurvey::add_reader`.
+    pub source: String,
+    pub line: usize,
+    /// The token with up to `CONTEXT_CHARS` characters either side.
+    pub context: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CorpusReport {
+    /// Word tokens checked.
+    pub tokens: u64,
+    /// Of those, the ones the speller rejected.
+    pub flagged: u64,
+    /// Distinct words checked and distinct words flagged.
+    pub words: usize,
+    pub flagged_words: usize,
+    /// The most frequent flagged words, most frequent first.
+    pub top: Vec<FlaggedWord>,
+    pub sample: Vec<FlaggedToken>,
+}
+
+impl CorpusReport {
+    /// The share of tokens flagged, from 0 to 1.
+    pub fn error_rate(&self) -> f64 {
+        if self.tokens == 0 {
+            0.0
+        } else {
+            self.flagged as f64 / self.tokens as f64
+        }
+    }
+}
+
+pub struct CorpusSurvey<T: Transducer> {
+    speller: Arc<Speller<T>>,
+    config: SurveyConfig,
+    correct: HashSet<String>,
+    flagged: HashMap<String, u64>,
+    tokens: u64,
+    flagged_tokens: u64,
+    sample: Vec<FlaggedToken>,
+    rng: Rng,
+}
+
+impl<T: Transducer> CorpusSurvey<T> {
+    pub fn new(speller: Arc<Speller<T>>, config: SurveyConfig) -> CorpusSurvey<T> {
+        let rng = Rng::new(config.seed);
+
+        CorpusSurvey {
+            speller,
+            config,
+            correct: HashSet::new(),
+            flagged: HashMap::new(),
+            tokens: 0,
+            flagged_tokens: 0,
+            sample: vec![],
+            rng,
+        }
+    }
+
+    /// Checks every line of `reader`, naming it `source` in the sample.
+    pub fn add_reader<R: BufRead>(&mut self, source: &str, reader: R) -> io::Result<()> {
+        for (i, line) in reader.lines().enumerate() {
+            self.add_line(source, i + 1, &line?);
+        }
+
+        Ok(())
+    }
+
+    pub fn add_line(&mut self, source: &str, line_number: usize, line: &str) {
+        for (offset, word) in line.word_bound_indices() {
+            if !word.chars().any(char::is_alphabetic) {
+                continue;
+            }
+
+            self.tokens += 1;
+            if self.correct.contains(word) {
+                continue;
+            }
+
+            if let Some(count) = self.flagged.get_mut(word) {
+                *count += 1;
+            } else if self.speller.clone().is_correct(word) {
+                self.correct.insert(word.to_string());
+                continue;
+            } else {
+                self.flagged.insert(word.to_string(), 1);
+            }
+
+            self.flagged_tokens += 1;
+            self.sample_token(source, line_number, line, offset, word);
+        }
+    }
+
+    /// Keeps a uniform sample of the flagged tokens seen so far.
+    fn sample_token(
+        &mut self,
+        source: &str,
+        line_number: usize,
+        line: &str,
+        offset: usize,
+        word: &str,
+    ) {
+        let size = self.config.sample_size;
+        let slot = if self.sample.len() < size {
+            self.sample.len()
+        } else {
+            match self.rng.below(self.flagged_tokens as usize) {
+                slot if slot < size => slot,
+                _ => return,
+            }
+        };
+
+        let before: String = line[..offset].chars().rev().take(CONTEXT_CHARS).collect();
+        let after: String = line[offset + word.len()..]
+            .chars()
+            .take(CONTEXT_CHARS)
+            .collect();
+        let token = FlaggedToken {
+            word: word.to_string(),
+            source: source.to_string(),
+            line: line_number,
+            context: format!(
+                "{}{}{}",
+                before.chars().rev().collect::<String>(),
+                word,
+                after
+            ),
+        };
+
+        if slot == self.sample.len() {
+            self.sample.push(token);
+        } else {
+            self.sample[slot] = token;
+        }
+    }
+
+    /// The report so far. Suggestions are looked up for the top words only.
+    pub fn report(&self) -> CorpusReport {
+        let mut top: Vec<(&String, &u64)> = self.flagged.iter().collect();
+        top.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        top.truncate(self.config.top_words);
+
+        let top = top
+            .into_iter()
+            .map(|(word, count)| FlaggedWord {
+                word: word.clone(),
+                count: *count,
+                suggestion: self
+                    .speller
+                    .clone()
+                    .suggest(word)
+                    .first()
+                    .map(|s| s.value().to_string()),
+            })
+            .collect();
+
+        let mut sample = self.sample.clone();
+        sample.sort_by(|a, b| (&a.source, a.line).cmp(&(&b.source, b.line)));
+
+        CorpusReport {
+            tokens: self.tokens,
+            flagged: self.flagged_tokens,
+            words: self.correct.len() + self.flagged.len(),
+            flagged_words: self.flagged.len(),
+            top,
+            sample,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_rate() {
+        let report = CorpusReport {
+            tokens: 200,
+            flagged: 5,
+            words: 120,
+            flagged_words: 4,
+            top: vec![],
+            sample: vec![],
+        };
+        assert_eq!(report.error_rate(), 0.025);
+        assert_eq!(
+            CorpusReport {
+                tokens: 0,
+                ..report
+            }
+            .error_rate(),
+            0.0
+        );
+    }
+}
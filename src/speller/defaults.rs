@@ -0,0 +1,61 @@
+//! Settings a language's maintainers recommend for its speller, shipped in
+//! the archive rather than hard-coded in every client.
+//!
+//! An archive's `index.xml` may have a `<defaults>` element with any of
+//! `<n_best>`, `<max_weight>`, `<beam>` and `<max_edit_distance>`. Each is
+//! used wherever a query's `SpellerConfig` leaves that setting `None`; a
+//! value the caller gives always wins.
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::SpellerConfig;
+use crate::types::Weight;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpellerDefaults {
+    pub n_best: Option<usize>,
+    pub max_weight: Option<Weight>,
+    pub beam: Option<Weight>,
+    pub max_edit_distance: Option<u8>,
+}
+
+impl SpellerDefaults {
+    pub fn is_empty(&self) -> bool {
+        *self == SpellerDefaults::default()
+    }
+
+    /// `config` with these settings wherever it has none.
+    pub fn apply(&self, config: &SpellerConfig) -> SpellerConfig {
+        SpellerConfig {
+            n_best: config.n_best.or(self.n_best),
+            max_weight: config.max_weight.or(self.max_weight),
+            beam: config.beam.or(self.beam),
+            max_edit_distance: config.max_edit_distance.or(self.max_edit_distance),
+            ..config.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caller_wins() {
+        let defaults = SpellerDefaults {
+            n_best: Some(5),
+            beam: Some(20.0),
+            ..SpellerDefaults::default()
+        };
+        let config = SpellerConfig {
+            n_best: Some(10),
+            ..SpellerConfig::default()
+        };
+
+        let applied = defaults.apply(&config);
+        assert_eq!(applied.n_best, Some(10));
+        assert_eq!(applied.beam, Some(20.0));
+        assert_eq!(applied.max_weight, None);
+    }
+}
@@ -0,0 +1,69 @@
+//! Tighter searches for error models too large to search exhaustively.
+//!
+//! Some languages ship error models with millions of transitions, typically
+//! from long lists of whole-word corrections, and with the default settings
+//! a single suggestion query against one can take seconds. Archives whose
+//! error model has more than `HUGE_ERRMODEL_TRANSITIONS` transitions are
+//! marked as such when opened, and their searches get a beam and an edit
+//! distance cap unless the config sets its own. Set
+//! `SpellerConfig::constrain_huge_errmodels` to `false` to search them like
+//! any other. The archive's lint reports it as `errmodel-huge`.
+
+use super::SpellerConfig;
+use crate::types::Weight;
+
+/// Error models with more transitions than this have their searches
+/// constrained.
+pub const HUGE_ERRMODEL_TRANSITIONS: u32 = 4_000_000;
+
+/// The beam searches of a huge error model get, unless the config has one.
+pub const CONSTRAINED_BEAM: Weight = 15.0;
+
+/// The edit distance cap searches of a huge error model get, unless the
+/// config has one.
+pub const CONSTRAINED_MAX_EDIT_DISTANCE: u8 = 2;
+
+pub fn is_huge(transitions: u32) -> bool {
+    transitions > HUGE_ERRMODEL_TRANSITIONS
+}
+
+/// `config` with a beam and an edit distance cap where it has none, and an
+/// adaptive beam kept from widening past `CONSTRAINED_BEAM`.
+pub fn constrain(config: &SpellerConfig) -> SpellerConfig {
+    let beam = config.beam.unwrap_or(CONSTRAINED_BEAM);
+
+    SpellerConfig {
+        beam: Some(beam),
+        max_edit_distance: config
+            .max_edit_distance
+            .or(Some(CONSTRAINED_MAX_EDIT_DISTANCE)),
+        beam_max: config.beam_max.min(beam.max(config.beam_min)),
+        ..config.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_settings_win() {
+        let constrained = constrain(&SpellerConfig::default());
+        assert_eq!(constrained.beam, Some(CONSTRAINED_BEAM));
+        assert_eq!(
+            constrained.max_edit_distance,
+            Some(CONSTRAINED_MAX_EDIT_DISTANCE)
+        );
+        assert_eq!(constrained.beam_max, CONSTRAINED_BEAM);
+
+        let config = SpellerConfig {
+            beam: Some(30.0),
+            max_edit_distance: Some(4),
+            ..SpellerConfig::default()
+        };
+        let constrained = constrain(&config);
+        assert_eq!(constrained.beam, Some(30.0));
+        assert_eq!(constrained.max_edit_distance, Some(4));
+        assert_eq!(constrained.beam_max, 30.0);
+    }
+}
@@ -0,0 +1,201 @@
+//! Checking documents that mix languages.
+//!
+//! A `DocumentChecker` holds a speller per language tag and checks every
+//! word with the speller for its language. The language of a word comes
+//! from, in order: the `LanguageRange` annotations given with the text,
+//! such as an editor's `lang` attributes; the identifier hook, called once
+//! per sentence that isn't fully annotated; and the checker's default. Tags
+//! fall back to less specific ones, so `se-NO` is checked with `se`. Words
+//! in a language without a speller are skipped rather than flagged.
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::issue::SpellingIssue;
+use super::{Speller, SpellerConfig};
+use crate::tokenizer::Tokenize;
+use crate::transducer::Transducer;
+
+/// Text from byte `start` to `end` is in `lang`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LanguageRange {
+    pub start: usize,
+    pub end: usize,
+    pub lang: String,
+}
+
+/// A misspelling and the language it was checked in, the tag of the
+/// speller used.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DocumentIssue {
+    pub lang: String,
+    #[serde(flatten)]
+    pub issue: SpellingIssue,
+}
+
+/// Guesses the language tag of a sentence, or `None` if it can't tell.
+pub type LanguageIdentifier = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+pub struct DocumentChecker<T: Transducer> {
+    spellers: BTreeMap<String, Arc<Speller<T>>>,
+    default: Option<String>,
+    identifier: Option<LanguageIdentifier>,
+    config: SpellerConfig,
+}
+
+impl<T: Transducer> DocumentChecker<T> {
+    /// A checker making suggestions with `config`. Without any languages
+    /// added, nothing is flagged.
+    pub fn new(config: SpellerConfig) -> DocumentChecker<T> {
+        DocumentChecker {
+            spellers: BTreeMap::new(),
+            default: None,
+            identifier: None,
+            config,
+        }
+    }
+
+    /// Checks words in `tag` with `speller`. The first language added is the
+    /// default until `set_default` is called.
+    pub fn add_language(&mut self, tag: &str, speller: Arc<Speller<T>>) {
+        if self.default.is_none() {
+            self.default = Some(tag.to_string());
+        }
+        self.spellers.insert(tag.to_string(), speller);
+    }
+
+    /// The language of words neither annotated nor identified, or `None` to
+    /// skip them.
+    pub fn set_default(&mut self, tag: Option<&str>) {
+        self.default = tag.map(str::to_string);
+    }
+
+    pub fn set_identifier(&mut self, identifier: LanguageIdentifier) {
+        self.identifier = Some(identifier);
+    }
+
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.spellers.keys().map(|x| &**x)
+    }
+
+    /// The speller for `tag` or the closest less specific tag, ignoring case,
+    /// with the tag it was added as.
+    pub fn speller(&self, tag: &str) -> Option<(&str, &Arc<Speller<T>>)> {
+        let mut prefix = tag;
+
+        loop {
+            if let Some((tag, speller)) = self
+                .spellers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(prefix))
+            {
+                return Some((tag, speller));
+            }
+
+            match prefix.rfind('-') {
+                Some(i) => prefix = &prefix[..i],
+                None => return None,
+            }
+        }
+    }
+
+    /// The misspellings in `text`, each checked and corrected in its own
+    /// language. Where `ranges` overlap, the first one given wins.
+    pub fn check(&self, text: &str, ranges: &[LanguageRange]) -> Vec<DocumentIssue> {
+        let mut found = vec![];
+        let mut langs = vec![];
+
+        for (start, sentence) in text.sentence_bound_indices() {
+            let mut identified = None;
+
+            for (offset, word) in sentence.word_bound_indices() {
+                if !word.chars().any(char::is_alphabetic) {
+                    continue;
+                }
+
+                let at = start + offset;
+                let lang = match ranges.iter().find(|r| r.start <= at && at < r.end) {
+                    Some(range) => Some(&*range.lang),
+                    None => identified
+                        .get_or_insert_with(|| self.identify(sentence))
+                        .as_ref()
+                        .map(|x| &**x),
+                };
+
+                let (tag, speller) = match lang.and_then(|lang| self.speller(lang)) {
+                    Some(found) => found,
+                    None => continue,
+                };
+
+                if !speller.clone().is_correct(word) {
+                    found.push((at, word));
+                    langs.push(tag);
+                }
+            }
+        }
+
+        SpellingIssue::locate(text, found)
+            .into_iter()
+            .zip(langs)
+            .map(|(mut issue, tag)| {
+                let speller = &self.spellers[tag];
+                issue.suggestions = speller
+                    .clone()
+                    .suggest_with_config(&issue.word, &self.config);
+
+                DocumentIssue {
+                    lang: tag.to_string(),
+                    issue,
+                }
+            })
+            .collect()
+    }
+
+    /// The identifier's guess for `sentence`, or the default.
+    fn identify(&self, sentence: &str) -> Option<String> {
+        self.identifier
+            .as_ref()
+            .and_then(|identify| identify(sentence))
+            .or_else(|| self.default.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::test_speller;
+
+    #[test]
+    fn languages_by_range_and_sentence() {
+        let pairs = [("sami", "sámi", 1)];
+        let se = test_speller(&pairs, &["mun", "lean", "sámi"]);
+        let nb = test_speller(&pairs, &["jeg", "er", "norsk"]);
+
+        let mut checker = DocumentChecker::new(SpellerConfig::default());
+        checker.add_language("se", se);
+        checker.add_language("nb", nb);
+        assert_eq!(checker.speller("NB-no").unwrap().0, "nb");
+
+        let text = "Mun lean sami. Jeg er norsk. Mun lean norsk.";
+        let ranges = vec![LanguageRange {
+            start: 15,
+            end: 28,
+            lang: "nb-NO".to_string(),
+        }];
+
+        let issues = checker.check(text, &ranges);
+        let words: Vec<(&str, &str)> = issues.iter().map(|i| (&*i.lang, &*i.issue.word)).collect();
+        assert_eq!(words, vec![("se", "sami"), ("se", "norsk")]);
+        assert_eq!(issues[0].issue.suggestions[0].value(), "sámi");
+
+        checker.set_identifier(Box::new(|sentence| {
+            if sentence.contains("Jeg") {
+                Some("nb".to_string())
+            } else {
+                None
+            }
+        }));
+        assert_eq!(checker.check(text, &[]), issues);
+    }
+}
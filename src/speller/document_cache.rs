@@ -0,0 +1,265 @@
+//! Check results kept per document, so that an editor or daemon checking a
+//! file again after each edit only checks what the edit changed.
+//!
+//! A `DocumentCache` remembers, for each document it is given, a hash of
+//! the text last checked and whether each of its words was correct, with
+//! the suggestions for those that weren't. Checking the same text again
+//! gives the same issues without looking anything up; checking a changed
+//! text looks up only the words that weren't in the last version, so a
+//! re-check costs about as much as the edit. Words are kept with whether
+//! they may be capitalized as at the start of a sentence, as that changes
+//! whether they are correct.
+//!
+//! A document's words are forgotten when it is checked with another config,
+//! and words dropped from a version are forgotten with it. Suggestions that
+//! were cut short by a time budget are not kept, and are searched for again
+//! on the next check. Past `capacity` documents, the least recently checked
+//! is dropped.
+
+use hashbrown::HashMap;
+use smol_str::SmolStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::issue::SpellingIssue;
+use super::suggestion::Suggestion;
+use super::suggestion_cache::{config_fingerprint, fnv1a, FNV_OFFSET};
+use super::{Speller, SpellerConfig};
+use crate::sync::Mutex;
+use crate::transducer::Transducer;
+
+/// How many documents a cache keeps, unless it is given another capacity.
+pub const DEFAULT_DOCUMENTS: usize = 64;
+
+#[derive(Clone, Debug)]
+struct Checked {
+    correct: bool,
+    /// `None` for a misspelling whose suggestions were cut short.
+    suggestions: Option<Vec<Suggestion>>,
+}
+
+#[derive(Debug)]
+struct CachedDocument {
+    text: u64,
+    config: (u64, Option<usize>),
+    words: HashMap<(SmolStr, bool), Checked>,
+    /// The issues of the last version, if they all had their suggestions.
+    issues: Option<Vec<SpellingIssue>>,
+    used: u64,
+}
+
+#[derive(Debug)]
+struct Documents {
+    documents: HashMap<String, CachedDocument>,
+    clock: u64,
+}
+
+#[derive(Debug)]
+pub struct DocumentCache {
+    capacity: usize,
+    documents: Mutex<Documents>,
+}
+
+impl Default for DocumentCache {
+    fn default() -> DocumentCache {
+        DocumentCache::new(DEFAULT_DOCUMENTS)
+    }
+}
+
+impl DocumentCache {
+    pub fn new(capacity: usize) -> DocumentCache {
+        DocumentCache {
+            capacity: capacity.max(1),
+            documents: Mutex::new(Documents {
+                documents: HashMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// How many documents are kept.
+    pub fn len(&self) -> usize {
+        self.documents.lock().documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops what is kept for `document`, as when an editor closes it.
+    pub fn forget(&self, document: &str) {
+        self.documents.lock().documents.remove(document);
+    }
+
+    /// The misspellings in `text`, the current version of `document`, as
+    /// `Speller::check_text_with_config` finds them, looking up only words
+    /// that weren't in the version checked before. The time budget covers
+    /// the whole text; misspellings found after it runs out are reported
+    /// without suggestions.
+    pub fn check<T: Transducer>(
+        &self,
+        speller: Arc<Speller<T>>,
+        document: &str,
+        text: &str,
+        config: &SpellerConfig,
+    ) -> Vec<SpellingIssue> {
+        let config = speller.effective_config(config);
+        let hash = fnv1a(FNV_OFFSET, text.as_bytes());
+        let fingerprint = (config_fingerprint(&config), config.n_best);
+
+        // The words of the last version are taken out while looking up the
+        // new ones, so other documents can be checked meanwhile.
+        let words = {
+            let mut documents = self.documents.lock();
+            documents.clock += 1;
+            let clock = documents.clock;
+
+            match documents.documents.get_mut(document) {
+                Some(cached) if cached.config == fingerprint => {
+                    cached.used = clock;
+                    if cached.text == hash {
+                        if let Some(issues) = &cached.issues {
+                            return issues.clone();
+                        }
+                    }
+                    std::mem::replace(&mut cached.words, HashMap::new())
+                }
+                _ => HashMap::new(),
+            }
+        };
+
+        let deadline = config
+            .time_budget_ms
+            .map(|ms| Instant::now() + std::time::Duration::from_millis(ms));
+        let mut kept = HashMap::new();
+        let mut found = vec![];
+
+        for (offset, word, initial) in speller.text_words(text) {
+            let key = (SmolStr::from(word), initial);
+            let checked = match kept.get(&key).or_else(|| words.get(&key)) {
+                Some(checked) if checked.suggestions.is_some() => checked.clone(),
+                _ => check_word(&speller, word, initial, &config, deadline),
+            };
+
+            if !checked.correct {
+                found.push((offset, word, checked.suggestions.clone()));
+            }
+            kept.insert(key, checked);
+        }
+
+        let complete = found.iter().all(|(_, _, s)| s.is_some());
+        let mut issues = SpellingIssue::locate(
+            text,
+            found
+                .iter()
+                .map(|&(offset, word, _)| (offset, word))
+                .collect(),
+        );
+        for (issue, (_, _, suggestions)) in issues.iter_mut().zip(found) {
+            issue.suggestions = suggestions.unwrap_or_default();
+        }
+
+        let mut documents = self.documents.lock();
+        let used = documents.clock;
+        if !documents.documents.contains_key(document) && documents.documents.len() >= self.capacity
+        {
+            let oldest = documents
+                .documents
+                .iter()
+                .min_by_key(|(_, cached)| cached.used)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                documents.documents.remove(&oldest);
+            }
+        }
+        documents.documents.insert(
+            document.to_string(),
+            CachedDocument {
+                text: hash,
+                config: fingerprint,
+                words: kept,
+                issues: if complete { Some(issues.clone()) } else { None },
+                used,
+            },
+        );
+
+        issues
+    }
+}
+
+/// Whether `word` is correct where it is, and if not its suggestions,
+/// unless `deadline` has passed or the search is cut short.
+fn check_word<T: Transducer>(
+    speller: &Arc<Speller<T>>,
+    word: &str,
+    initial: bool,
+    config: &SpellerConfig,
+    deadline: Option<Instant>,
+) -> Checked {
+    if speller.clone().is_correct_in_sentence(word, initial) {
+        return Checked {
+            correct: true,
+            suggestions: Some(vec![]),
+        };
+    }
+
+    let now = Instant::now();
+    let config = match deadline {
+        Some(deadline) if now >= deadline => {
+            return Checked {
+                correct: false,
+                suggestions: None,
+            }
+        }
+        Some(deadline) => SpellerConfig {
+            time_budget_ms: Some((deadline - now).as_millis() as u64),
+            ..config.clone()
+        },
+        None => config.clone(),
+    };
+
+    let cut_short = speller.search_memory().cut_short();
+    let suggestions = speller.clone().suggest_in_sentence(word, initial, &config);
+    let out_of_time = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+    Checked {
+        correct: false,
+        suggestions: if out_of_time || speller.search_memory().cut_short() != cut_short {
+            None
+        } else {
+            Some(suggestions)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::test_speller;
+
+    #[test]
+    fn rechecks_match_whole_text() {
+        let speller = test_speller(
+            &[("giela", "giella", 2)],
+            &["mun", "lean", "giella", "sámi"],
+        );
+
+        let config = SpellerConfig::default();
+        let cache = DocumentCache::new(1);
+        let versions = [
+            "Mun lean giela. Sámi giella.",
+            "Mun lean giela. Sámi giella. Mun lean sámi giellaa!",
+            "Mun lean giella. Sámi giella lean.",
+        ];
+        for text in versions.iter() {
+            let whole = speller.clone().check_text_with_config(text, &config);
+            assert_eq!(cache.check(speller.clone(), "a.txt", text, &config), whole);
+            assert_eq!(cache.check(speller.clone(), "a.txt", text, &config), whole);
+        }
+
+        cache.check(speller.clone(), "b.txt", versions[0], &config);
+        assert_eq!(cache.len(), 1);
+        cache.forget("b.txt");
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+//! Suggestions for words the error model has no edits for.
+//!
+//! An error model is written by hand or trained on a list of typos, and a
+//! word whose mistakes it doesn't cover gets no suggestions under the weight
+//! limit at all. With `SpellerConfig::fallback_distance` set, such a word is
+//! looked up in the lexicon by plain edit distance instead: the lexicon is
+//! walked as if intersected with a Levenshtein automaton for the word,
+//! keeping the edit distances from the word to the letters written so far
+//! and leaving a path once none of them is within the distance. Every
+//! insertion, deletion and substitution counts as one, whatever the letters.
+//!
+//! A suggestion weighs its lexicon weight plus `FALLBACK_EDIT_WEIGHT` per
+//! edit, so that it ranks below what an error model would have suggested.
+//! Tags are passed over without counting as edits, and as in the error model
+//! search are left out of suggestions in `SpellerConfig::proper_nouns` mode
+//! only. Flag diacritics are obeyed. A walk gives up after `MAX_STEPS` states, or at the query's
+//! deadline, with what it has found so far.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+use smol_str::SmolStr;
+
+use super::proper_noun::is_tag;
+use super::suggestion::Suggestion;
+use super::Instant;
+use crate::transducer::lookup::{apply_flag, MAX_EPSILONS};
+use crate::transducer::Transducer;
+use crate::types::{TransitionTableIndex, Weight};
+
+/// What each edit adds to a fallback suggestion's weight, about what an
+/// error model gives one uncommon edit.
+pub const FALLBACK_EDIT_WEIGHT: Weight = 10.0;
+
+pub const MAX_STEPS: usize = 200_000;
+
+/// How often the deadline is looked at, in states.
+const DEADLINE_CHECK_INTERVAL: usize = 1024;
+
+struct Walk<'a, T: Transducer> {
+    lexicon: &'a T,
+    word: Vec<char>,
+    max_distance: usize,
+    /// Leave tags out of what is written, as in proper noun mode.
+    strip_tags: bool,
+    deadline: Option<Instant>,
+    found: HashMap<SmolStr, Weight>,
+    steps: usize,
+}
+
+impl<'a, T: Transducer> Walk<'a, T> {
+    /// The edit distances from each prefix of the word to what was written
+    /// before `row`, followed by `ch`.
+    fn next_row(&self, row: &[usize], ch: char) -> Vec<usize> {
+        let mut next = Vec::with_capacity(row.len());
+        next.push(row[0] + 1);
+        for (j, &letter) in self.word.iter().enumerate() {
+            let substitute = row[j] + if letter == ch { 0 } else { 1 };
+            next.push(substitute.min(row[j + 1] + 1).min(next[j] + 1));
+        }
+        next
+    }
+
+    fn out_of_time(&mut self) -> bool {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return true;
+        }
+        match self.deadline {
+            Some(deadline) if self.steps.is_multiple_of(DEADLINE_CHECK_INTERVAL) => {
+                if Instant::now() >= deadline {
+                    self.steps = MAX_STEPS + 1;
+                    return true;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn step(
+        &mut self,
+        state: TransitionTableIndex,
+        row: &[usize],
+        epsilons: usize,
+        weight: Weight,
+        output: &mut String,
+        flags: &mut [i16],
+    ) {
+        if self.out_of_time() {
+            return;
+        }
+
+        let t = self.lexicon;
+        let distance = row[self.word.len()];
+        if distance <= self.max_distance && t.is_final(state) {
+            let weight = weight
+                + t.final_weight(state).unwrap_or(0.0)
+                + distance as Weight * FALLBACK_EDIT_WEIGHT;
+            let entry = self.found.entry(SmolStr::new(&*output)).or_insert(weight);
+            if weight < *entry {
+                *entry = weight;
+            }
+        }
+
+        if epsilons < MAX_EPSILONS && t.has_epsilons_or_flags(state + 1) {
+            if let Some(mut next) = t.next(state, 0) {
+                while let Some(transition) = t.take_epsilons_and_flags(next) {
+                    let target = transition.target();
+                    let weight = weight + transition.weight().unwrap_or(0.0);
+                    match (t.transition_input_symbol(next), target) {
+                        (Some(0), Some(target)) => {
+                            self.step(target, row, epsilons + 1, weight, output, flags)
+                        }
+                        (Some(symbol), Some(target)) => {
+                            if let Some(op) = t.alphabet().operations().get(&symbol) {
+                                let saved = flags[op.feature as usize];
+                                if apply_flag(op, flags) {
+                                    self.step(target, row, epsilons + 1, weight, output, flags);
+                                }
+                                flags[op.feature as usize] = saved;
+                            }
+                        }
+                        _ => {}
+                    }
+
+                    next += 1;
+                }
+            }
+        }
+
+        let alphabet = t.alphabet();
+        for symbol in 1..alphabet.initial_symbol_count() {
+            if alphabet.is_flag(symbol)
+                || Some(symbol) == alphabet.identity()
+                || Some(symbol) == alphabet.unknown()
+                || !t.has_transitions(state + 1, Some(symbol))
+            {
+                continue;
+            }
+            let first = match t.next(state, symbol) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            let key = match alphabet.key_table().get(symbol as usize) {
+                Some(key) => key,
+                None => continue,
+            };
+            let (next_row, next_epsilons) = if is_tag(key) {
+                if epsilons >= MAX_EPSILONS {
+                    continue;
+                }
+                (row.to_vec(), epsilons + 1)
+            } else {
+                let next_row = key
+                    .chars()
+                    .fold(row.to_vec(), |row, ch| self.next_row(&row, ch));
+                (next_row, 0)
+            };
+            if next_row.iter().all(|&d| d > self.max_distance) {
+                continue;
+            }
+
+            let len = output.len();
+            if !self.strip_tags || !is_tag(key) {
+                output.push_str(key);
+            }
+            for i in first..first + t.non_epsilon_run(first, symbol) {
+                let transition = t.transition(i);
+                if let Some(target) = transition.target() {
+                    let weight = weight + transition.weight().unwrap_or(0.0);
+                    self.step(target, &next_row, next_epsilons, weight, output, flags);
+                }
+            }
+            output.truncate(len);
+        }
+    }
+}
+
+/// The words of `lexicon` within `max_distance` edits of `word`, lightest
+/// first, with their tags left out if `proper_nouns` is set.
+pub fn suggest<T: Transducer>(
+    lexicon: &T,
+    word: &str,
+    max_distance: u8,
+    proper_nouns: bool,
+    deadline: Option<Instant>,
+) -> Vec<Suggestion> {
+    let mut walk = Walk {
+        lexicon,
+        word: word.chars().collect(),
+        max_distance: max_distance as usize,
+        strip_tags: proper_nouns,
+        deadline,
+        found: HashMap::new(),
+        steps: 0,
+    };
+
+    let row: Vec<usize> = (0..=walk.word.len()).collect();
+    let mut flags = alloc::vec![0i16; lexicon.alphabet().state_size() as usize];
+    walk.step(0, &row, 0, 0.0, &mut String::new(), &mut flags);
+
+    let mut suggestions: Vec<Suggestion> = walk
+        .found
+        .into_iter()
+        .map(|(value, weight)| Suggestion::new(value, weight))
+        .collect();
+    suggestions.sort_by(|a, b| {
+        a.weight
+            .partial_cmp(&b.weight)
+            .unwrap_or(core::cmp::Ordering::Equal)
+            .then_with(|| a.value.cmp(&b.value))
+    });
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speller::SpellerConfig;
+    use crate::transducer::test_speller;
+
+    #[test]
+    fn suggests_what_the_error_model_cannot() {
+        let speller = test_speller(&[("divun", "divvun", 2)], &["divvun", "giella", "gielda"]);
+
+        let found = suggest(speller.lexicon(), "giellq", 2, false, None);
+        let values: Vec<&str> = found.iter().map(|s| s.value()).collect();
+        assert_eq!(values, vec!["giella", "gielda"]);
+        assert!(suggest(speller.lexicon(), "xyzxyz", 2, false, None).is_empty());
+
+        let config = SpellerConfig {
+            fallback_distance: Some(2),
+            ..SpellerConfig::default()
+        };
+        assert!(speller.clone().suggest("giellq").is_empty());
+        let suggestions = speller.suggest_with_config("giellq", &config);
+        assert_eq!(suggestions[0].value(), "giella");
+    }
+}
@@ -0,0 +1,199 @@
+//! Explanations of misspellings for learners, such as "you wrote a where á
+//! is expected", written by the language's maintainers and shipped in the
+//! archive.
+//!
+//! An archive can bring them in a `feedback.tsv` entry, with a line per
+//! rule: the letter typed, the letter expected and the message, separated by
+//! tabs. An empty letter stands for none, so a rule with nothing typed
+//! explains a missing letter and one with nothing expected an extra one,
+//! and `*` stands for any letter. In messages, `{typed}` and `{expected}`
+//! are replaced by the letters. For each edit between a word and a
+//! suggestion, the first rule that matches is used, so specific rules go
+//! before general ones; edits no rule matches go unexplained. Blank lines
+//! and lines starting with `#` are skipped. With tabs shown as `→`:
+//!
+//! ```text
+//! a→á→Long vowels are written with an acute accent: "á", not "a".
+//! →*→"{expected}" is missing.
+//! *→*→You wrote "{typed}" where "{expected}" is expected.
+//! ```
+//!
+//! Other sources of explanations, such as a learner's native language
+//! deciding which mistakes are worth pointing out, can implement `Explain`.
+
+use serde_derive::Serialize;
+use smol_str::SmolStr;
+
+use super::suggestion::Suggestion;
+use crate::errmodel::align::{align, Edit};
+
+/// One edit between a word and a suggestion, and what to tell a learner
+/// about it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Explanation {
+    pub edit: Edit,
+    pub message: String,
+}
+
+/// A suggestion with the explanations of how the word differs from it. In
+/// JSON, the suggestion's `value` and `weight` and a `feedback` list.
+#[derive(Clone, Debug, Serialize)]
+pub struct SuggestionFeedback {
+    #[serde(flatten)]
+    pub suggestion: Suggestion,
+    pub feedback: Vec<Explanation>,
+}
+
+pub trait Explain: Send + Sync {
+    /// What to tell a learner about each edit that turns `word` into
+    /// `suggestion`, in word order.
+    fn explain(&self, word: &str, suggestion: &str) -> Vec<Explanation>;
+
+    /// Each of `suggestions` for `word` with its explanations.
+    fn annotate(&self, word: &str, suggestions: &[Suggestion]) -> Vec<SuggestionFeedback> {
+        suggestions
+            .iter()
+            .map(|s| SuggestionFeedback {
+                suggestion: s.clone(),
+                feedback: self.explain(word, s.value()),
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Letter {
+    None,
+    Any,
+    Is(SmolStr),
+}
+
+impl Letter {
+    fn parse(field: &str) -> Letter {
+        match field {
+            "" => Letter::None,
+            "*" => Letter::Any,
+            letter => Letter::Is(SmolStr::from(letter)),
+        }
+    }
+
+    fn matches(&self, ch: Option<char>) -> bool {
+        match (self, ch) {
+            (Letter::None, None) => true,
+            (Letter::Any, Some(_)) => true,
+            (Letter::Is(letter), Some(ch)) => {
+                let mut chars = letter.chars();
+                chars.next() == Some(ch) && chars.next().is_none()
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    typed: Letter,
+    expected: Letter,
+    message: String,
+}
+
+/// The rules of a `feedback.tsv`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Feedback {
+    rules: Vec<Rule>,
+}
+
+impl Feedback {
+    /// Reads a rule per line, skipping blank lines, lines starting with `#`
+    /// and lines without a message.
+    pub fn parse(data: &str) -> Feedback {
+        let rules = data
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let typed = fields.next()?;
+                let expected = fields.next()?;
+                let message = fields.next()?.trim();
+                if message.is_empty() {
+                    return None;
+                }
+
+                Some(Rule {
+                    typed: Letter::parse(typed.trim()),
+                    expected: Letter::parse(expected.trim()),
+                    message: message.to_string(),
+                })
+            })
+            .collect();
+
+        Feedback { rules }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+impl Explain for Feedback {
+    fn explain(&self, word: &str, suggestion: &str) -> Vec<Explanation> {
+        align(word, suggestion)
+            .into_iter()
+            .filter_map(|edit| {
+                let (typed, expected) = match edit {
+                    Edit::Substitution { from, to } => (Some(from), Some(to)),
+                    Edit::Insertion { to } => (None, Some(to)),
+                    Edit::Deletion { from } => (Some(from), None),
+                };
+                let rule = self
+                    .rules
+                    .iter()
+                    .find(|r| r.typed.matches(typed) && r.expected.matches(expected))?;
+
+                let text = |ch: Option<char>| ch.map(String::from).unwrap_or_default();
+                let message = rule
+                    .message
+                    .replace("{typed}", &text(typed))
+                    .replace("{expected}", &text(expected));
+                Some(Explanation { edit, message })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_matching_rule() {
+        let feedback = Feedback::parse(
+            "# typed\texpected\tmessage\n\
+             a\tá\tWrite the long vowel as á.\n\
+             \t*\t\"{expected}\" is missing.\n\
+             *\t*\tYou wrote {typed} where {expected} is expected.\n",
+        );
+        assert_eq!(feedback.len(), 3);
+
+        let messages = |word, suggestion| {
+            feedback
+                .explain(word, suggestion)
+                .into_iter()
+                .map(|e| e.message)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            messages("samegilla", "sámegiella"),
+            vec!["Write the long vowel as á.", "\"e\" is missing."]
+        );
+        assert_eq!(
+            messages("giwlla", "giella"),
+            vec!["You wrote w where e is expected."]
+        );
+        assert!(messages("giellaa", "giella").is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+//! Rules for which candidates may be suggested at all.
+//!
+//! A client can register filters on a speller with `Speller::add_filter`,
+//! and a candidate any of them rejects is dropped as soon as the search
+//! finds it, before the suggestions are cut to `n_best`, so that useless
+//! candidates don't take the places of useful ones and don't narrow the
+//! search as if they had been found. Filters see the word as it was
+//! searched for, which may be a case variant of the one asked about, and
+//! the candidate before it is recased. Suggestions already kept, as by a
+//! `SuggestionCache`, aren't filtered again, so filters go on the speller
+//! before it is queried.
+//!
+//! `MinLength`, `SameScript` and `NoTags` cover the usual cases, and any
+//! `Fn(&str, &str) -> bool` is a filter, so a regex or a list of forms the
+//! client doesn't want suggested can be used as well.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::sync::RwLock;
+
+pub trait SuggestionFilter: Send + Sync {
+    /// Whether `candidate` may be suggested for `word`.
+    fn allows(&self, word: &str, candidate: &str) -> bool;
+}
+
+impl<F> SuggestionFilter for F
+where
+    F: Fn(&str, &str) -> bool + Send + Sync,
+{
+    fn allows(&self, word: &str, candidate: &str) -> bool {
+        self(word, candidate)
+    }
+}
+
+/// Rejects candidates of fewer than this many characters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinLength(pub usize);
+
+impl SuggestionFilter for MinLength {
+    fn allows(&self, _word: &str, candidate: &str) -> bool {
+        candidate.chars().count() >= self.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Georgian,
+    Han,
+    Kana,
+    Hangul,
+    Other,
+}
+
+/// The script of a letter, roughly by its Unicode block; `None` for
+/// characters that aren't letters, which all scripts share.
+fn script(ch: char) -> Option<Script> {
+    if !ch.is_alphabetic() {
+        return None;
+    }
+
+    Some(match ch as u32 {
+        0x0000..=0x02af | 0x1e00..=0x1eff | 0x2c60..=0x2c7f | 0xa720..=0xa7ff => Script::Latin,
+        0x0370..=0x03ff | 0x1f00..=0x1fff => Script::Greek,
+        0x0400..=0x052f | 0x2de0..=0x2dff | 0xa640..=0xa69f => Script::Cyrillic,
+        0x0530..=0x058f => Script::Armenian,
+        0x0590..=0x05ff => Script::Hebrew,
+        0x0600..=0x06ff | 0x0750..=0x077f | 0x08a0..=0x08ff => Script::Arabic,
+        0x0900..=0x097f => Script::Devanagari,
+        0x10a0..=0x10ff => Script::Georgian,
+        0x3040..=0x30ff => Script::Kana,
+        0x4e00..=0x9fff | 0x3400..=0x4dbf => Script::Han,
+        0xac00..=0xd7af | 0x1100..=0x11ff => Script::Hangul,
+        _ => Script::Other,
+    })
+}
+
+/// Rejects candidates with letters in a script the word has none of, such
+/// as Cyrillic suggestions for a word typed in Latin letters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SameScript;
+
+impl SuggestionFilter for SameScript {
+    fn allows(&self, word: &str, candidate: &str) -> bool {
+        let scripts: Vec<Script> = word.chars().filter_map(script).collect();
+        candidate
+            .chars()
+            .filter_map(script)
+            .all(|s| scripts.contains(&s))
+    }
+}
+
+/// Rejects candidates with something like a tag left in them, a `+` or `<`
+/// followed by a letter. Multicharacter tags are only left out of
+/// suggestions in `SpellerConfig::proper_nouns` mode, so this catches them
+/// otherwise, as well as tags in lexicons that spell them out letter by
+/// letter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoTags;
+
+impl SuggestionFilter for NoTags {
+    fn allows(&self, _word: &str, candidate: &str) -> bool {
+        let mut chars = candidate.chars().peekable();
+        while let Some(ch) = chars.next() {
+            if (ch == '+' || ch == '<') && chars.peek().is_some_and(|c| c.is_alphabetic()) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A speller's filters, swapped whole so a search keeps the ones it started
+/// with.
+pub(crate) struct FilterSlot(RwLock<Arc<Vec<Arc<dyn SuggestionFilter>>>>);
+
+impl FilterSlot {
+    pub fn new() -> FilterSlot {
+        FilterSlot(RwLock::new(Arc::new(Vec::new())))
+    }
+
+    pub fn get(&self) -> Arc<Vec<Arc<dyn SuggestionFilter>>> {
+        self.0.read().clone()
+    }
+
+    pub fn add(&self, filter: Arc<dyn SuggestionFilter>) {
+        let mut filters = self.0.write();
+        let mut added = (**filters).clone();
+        added.push(filter);
+        *filters = Arc::new(added);
+    }
+
+    pub fn clear(&self) {
+        *self.0.write() = Arc::new(Vec::new());
+    }
+}
+
+impl fmt::Debug for FilterSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("FilterSlot")
+    }
+}
+
+/// Whether every filter allows `candidate` for `word`.
+pub(crate) fn allows(filters: &[Arc<dyn SuggestionFilter>], word: &str, candidate: &str) -> bool {
+    filters.iter().all(|filter| filter.allows(word, candidate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speller::SpellerConfig;
+    use crate::transducer::test_speller;
+
+    #[test]
+    fn filtered_before_n_best() {
+        assert!(!MinLength(2).allows("a", "á"));
+        assert!(MinLength(2).allows("a", "ád"));
+        assert!(SameScript.allows("giela", "giella"));
+        assert!(!SameScript.allows("giela", "гиела"));
+        assert!(!NoTags.allows("giela", "giella+N"));
+        assert!(NoTags.allows("giela", "c++"));
+
+        let speller = test_speller(
+            &[("giela", "giella", 2), ("giela", "gielda", 3)],
+            &["giella", "gielda"],
+        );
+
+        let config = SpellerConfig {
+            n_best: Some(1),
+            ..SpellerConfig::default()
+        };
+        let best = speller.clone().suggest_with_config("giela", &config);
+        assert_eq!(best.len(), 1);
+
+        let rejected = best[0].value.clone();
+        speller.add_filter(move |_: &str, candidate: &str| candidate != rejected);
+        let filtered = speller.clone().suggest_with_config("giela", &config);
+        assert_eq!(filtered.len(), 1);
+        assert_ne!(filtered[0].value, best[0].value);
+
+        speller.clear_filters();
+        assert_eq!(speller.suggest_with_config("giela", &config), best);
+    }
+}
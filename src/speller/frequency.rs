@@ -0,0 +1,346 @@
+//! Compiled word frequency lists for frequency-aware reranking.
+//!
+//! A list is compiled once from a corpus count TSV and memory mapped at
+//! startup, so lookups need no parsing. The layout is little endian:
+//!
+//! ```text
+//! magic "DVFQ" | version: u32 | count: u32 | total: u64
+//! offsets: [u32; count + 1]    start of each word in the string data
+//! frequencies: [u32; count]
+//! string data                  UTF-8, sorted bytewise
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use memmap::{Mmap, MmapOptions};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::speller::suggestion::Suggestion;
+use crate::types::Weight;
+
+const MAGIC: &[u8; 4] = b"DVFQ";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 20;
+
+#[derive(Debug)]
+pub enum FrequencyListError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    TooLarge,
+}
+
+impl std::error::Error for FrequencyListError {}
+
+impl std::fmt::Display for FrequencyListError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl From<io::Error> for FrequencyListError {
+    fn from(e: io::Error) -> FrequencyListError {
+        FrequencyListError::Io(e)
+    }
+}
+
+/// Collects word counts and writes them in the compiled format.
+#[derive(Debug, Default)]
+pub struct FrequencyListBuilder {
+    counts: BTreeMap<String, u64>,
+}
+
+impl FrequencyListBuilder {
+    pub fn new() -> FrequencyListBuilder {
+        FrequencyListBuilder::default()
+    }
+
+    /// Adds `count` occurrences of `word`; repeated words are summed.
+    pub fn add(&mut self, word: &str, count: u64) {
+        *self.counts.entry(word.to_string()).or_insert(0) += count;
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> Result<(), FrequencyListError> {
+        let strings_len: usize = self.counts.keys().map(|k| k.len()).sum();
+        if self.counts.len() >= u32::MAX as usize || strings_len >= u32::MAX as usize {
+            return Err(FrequencyListError::TooLarge);
+        }
+
+        let total: u64 = self.counts.values().sum();
+
+        w.write_all(MAGIC)?;
+        w.write_u32::<LittleEndian>(VERSION)?;
+        w.write_u32::<LittleEndian>(self.counts.len() as u32)?;
+        w.write_u64::<LittleEndian>(total)?;
+
+        // BTreeMap iterates in bytewise order, which is what lookups expect.
+        let mut offset = 0u32;
+        for word in self.counts.keys() {
+            w.write_u32::<LittleEndian>(offset)?;
+            offset += word.len() as u32;
+        }
+        w.write_u32::<LittleEndian>(offset)?;
+
+        for &count in self.counts.values() {
+            w.write_u32::<LittleEndian>(count.min(u64::from(u32::MAX)) as u32)?;
+        }
+
+        for word in self.counts.keys() {
+            w.write_all(word.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How `FrequencyList::rescore` combines a suggestion's weight with the
+/// frequency of the word suggested.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scoring {
+    /// Adds the frequency cost to the search weight, lexicon weight and all.
+    Additive,
+    /// `-log P(word) - log P(typo | word)`: the error model's share of the
+    /// weight is the channel, and the frequency list replaces the lexicon's
+    /// weight as the prior. A suggestion whose lexicon weight isn't known
+    /// keeps its whole weight as the channel.
+    NoisyChannel,
+}
+
+/// A memory mapped, compiled frequency list.
+pub struct FrequencyList {
+    buf: Mmap,
+    count: usize,
+    total: u64,
+}
+
+impl std::fmt::Debug for FrequencyList {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "FrequencyList {{ words: {}, total: {} }}",
+            self.count, self.total
+        )
+    }
+}
+
+impl FrequencyList {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FrequencyList, FrequencyListError> {
+        let file = File::open(path)?;
+        let buf = unsafe { MmapOptions::new().map(&file)? };
+        FrequencyList::from_mmap(buf)
+    }
+
+    pub fn from_mmap(buf: Mmap) -> Result<FrequencyList, FrequencyListError> {
+        if buf.len() < HEADER_SIZE {
+            return Err(FrequencyListError::Truncated);
+        }
+
+        if &buf[0..4] != MAGIC {
+            return Err(FrequencyListError::BadMagic);
+        }
+
+        let version = LittleEndian::read_u32(&buf[4..8]);
+        if version != VERSION {
+            return Err(FrequencyListError::UnsupportedVersion(version));
+        }
+
+        let count = LittleEndian::read_u32(&buf[8..12]) as usize;
+        let total = LittleEndian::read_u64(&buf[12..20]);
+
+        let list = FrequencyList { buf, count, total };
+
+        // Check the tables and the last offset fit, so lookups can't read
+        // past the end of the map.
+        let strings_start = list.strings_start();
+        if list.buf.len() < strings_start
+            || list.buf.len() < strings_start + list.offset(count) as usize
+        {
+            return Err(FrequencyListError::Truncated);
+        }
+
+        Ok(list)
+    }
+
+    #[inline(always)]
+    fn offset(&self, i: usize) -> u32 {
+        let at = HEADER_SIZE + i * 4;
+        LittleEndian::read_u32(&self.buf[at..at + 4])
+    }
+
+    #[inline(always)]
+    fn strings_start(&self) -> usize {
+        HEADER_SIZE + (self.count + 1) * 4 + self.count * 4
+    }
+
+    fn word(&self, i: usize) -> &[u8] {
+        let start = self.strings_start();
+        &self.buf[start + self.offset(i) as usize..start + self.offset(i + 1) as usize]
+    }
+
+    fn frequency_at(&self, i: usize) -> u32 {
+        let at = HEADER_SIZE + (self.count + 1) * 4 + i * 4;
+        LittleEndian::read_u32(&self.buf[at..at + 4])
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Sum of all counts in the source list.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn frequency(&self, word: &str) -> Option<u32> {
+        let needle = word.as_bytes();
+        let (mut lo, mut hi) = (0, self.count);
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.word(mid).cmp(needle) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(self.frequency_at(mid)),
+            }
+        }
+
+        None
+    }
+
+    /// Up to `n` words, most frequent first, for `Speller::warm_up_words`.
+    pub fn most_frequent(&self, n: usize) -> Vec<&str> {
+        let mut indices: Vec<usize> = (0..self.count).collect();
+        indices.sort_by_key(|i| std::cmp::Reverse(self.frequency_at(*i)));
+
+        indices
+            .into_iter()
+            .filter_map(|i| std::str::from_utf8(self.word(i)).ok())
+            .take(n)
+            .collect()
+    }
+
+    /// Negative log probability of `word`, add-one smoothed so unseen words
+    /// get a finite cost.
+    pub fn cost(&self, word: &str) -> Weight {
+        let count = f64::from(self.frequency(word).unwrap_or(0)) + 1.0;
+        let total = self.total as f64 + self.count as f64 + 1.0;
+        (-(count / total).ln()) as Weight
+    }
+
+    /// Adds `factor` times each suggestion's frequency cost to its weight and
+    /// re-sorts, so common words move up among similarly weighted ones. The
+    /// same as `rescore` with `Scoring::Additive`.
+    pub fn rerank(&self, suggestions: &mut [Suggestion], factor: Weight) {
+        self.rescore(suggestions, Scoring::Additive, factor, |_| None);
+    }
+
+    /// Rescores `suggestions` with `factor` times their frequency cost as
+    /// `scoring` says, and re-sorts them. `lexicon_weight` gives the weight
+    /// the lexicon has for a suggestion, as `Speller::lexicon_weight` does;
+    /// it is only used by `Scoring::NoisyChannel`.
+    pub fn rescore<F>(
+        &self,
+        suggestions: &mut [Suggestion],
+        scoring: Scoring,
+        factor: Weight,
+        lexicon_weight: F,
+    ) where
+        F: Fn(&str) -> Option<Weight>,
+    {
+        trace_span!("rerank", suggestions = suggestions.len());
+
+        for s in suggestions.iter_mut() {
+            let prior = factor * self.cost(&s.value);
+            s.weight = match scoring {
+                Scoring::Additive => s.weight + prior,
+                Scoring::NoisyChannel => {
+                    let channel = s.weight - lexicon_weight(&s.value).unwrap_or(0.0);
+                    channel.max(0.0) + prior
+                }
+            };
+        }
+        suggestions.sort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(words: &[(&str, u64)]) -> Result<FrequencyList, FrequencyListError> {
+        let mut builder = FrequencyListBuilder::new();
+        for &(w, c) in words {
+            builder.add(w, c);
+        }
+
+        let mut buf = vec![];
+        builder.write(&mut buf).unwrap();
+
+        let mut map = memmap::MmapMut::map_anon(buf.len()).unwrap();
+        map.copy_from_slice(&buf);
+        FrequencyList::from_mmap(map.make_read_only().unwrap())
+    }
+
+    #[test]
+    fn lookup() {
+        let list = compile(&[("giella", 10), ("sátni", 3), ("giella", 2), ("ája", 1)]).unwrap();
+
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.total(), 16);
+        assert_eq!(list.frequency("giella"), Some(12));
+        assert_eq!(list.frequency("sátni"), Some(3));
+        assert_eq!(list.frequency("ája"), Some(1));
+        assert_eq!(list.frequency("gie"), None);
+        assert!(list.cost("giella") < list.cost("sátni"));
+
+        let mut suggestions = vec![
+            Suggestion::new("sátni".into(), 1.0),
+            Suggestion::new("giella".into(), 1.1),
+        ];
+        list.rerank(&mut suggestions, 1.0);
+        assert_eq!(suggestions[0].value(), "giella");
+
+        // Heavy in the lexicon, but only because of its own prior.
+        let lexicon_weight = |w: &str| Some(if w == "giella" { 11.0 } else { 0.5 });
+        for &(scoring, best) in &[
+            (Scoring::Additive, "sátni"),
+            (Scoring::NoisyChannel, "giella"),
+        ] {
+            let mut suggestions = vec![
+                Suggestion::new("sátni".into(), 3.0),
+                Suggestion::new("giella".into(), 12.0),
+            ];
+            list.rescore(&mut suggestions, scoring, 1.0, lexicon_weight);
+            assert_eq!(suggestions[0].value(), best);
+        }
+
+        assert_eq!(list.most_frequent(2), vec!["giella", "sátni"]);
+    }
+
+    #[test]
+    fn truncated() {
+        match compile(&[]) {
+            Ok(list) => assert!(list.is_empty()),
+            Err(e) => panic!("{}", e),
+        }
+
+        let mut map = memmap::MmapMut::map_anon(8).unwrap();
+        map.copy_from_slice(b"DVFQ\x01\0\0\0");
+        assert!(FrequencyList::from_mmap(map.make_read_only().unwrap()).is_err());
+    }
+}
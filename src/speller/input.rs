@@ -0,0 +1,95 @@
+//! Input that is answered without a search.
+//!
+//! Empty strings, whitespace, control characters and text far longer than
+//! any word reach a speller from servers fed whatever a client sends. None
+//! of them can be in the lexicon, but searching for suggestions to them can
+//! take as long as any real word, or, for long input, far longer. The
+//! speller turns them away first: `is_correct` is false for them and they
+//! get no suggestions. `Speller::check_input` says why.
+
+use core::fmt;
+
+/// The most characters a word may have to be looked up.
+pub const MAX_INPUT_CHARS: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputError {
+    Empty,
+    /// Nothing but whitespace.
+    Whitespace,
+    /// A control character, such as a newline or NUL, in a word.
+    ControlCharacter,
+    /// Longer than `MAX_INPUT_CHARS`.
+    TooLong {
+        chars: usize,
+    },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputError::Empty => write!(f, "empty input"),
+            InputError::Whitespace => write!(f, "input is only whitespace"),
+            InputError::ControlCharacter => write!(f, "input has control characters"),
+            InputError::TooLong { chars } => write!(
+                f,
+                "input is {} characters long, more than {}",
+                chars, MAX_INPUT_CHARS
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InputError {}
+
+/// Whether `word` can be looked up at all.
+pub fn check_input(word: &str) -> Result<(), InputError> {
+    if word.is_empty() {
+        return Err(InputError::Empty);
+    }
+
+    // Every character is at least a byte, so this only counts long input.
+    if word.len() > MAX_INPUT_CHARS {
+        let chars = word.chars().count();
+        if chars > MAX_INPUT_CHARS {
+            return Err(InputError::TooLong { chars });
+        }
+    }
+
+    if word.chars().all(char::is_whitespace) {
+        return Err(InputError::Whitespace);
+    }
+
+    if word.chars().any(char::is_control) {
+        return Err(InputError::ControlCharacter);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(check_input("giella"), Ok(()));
+        assert_eq!(check_input("sámegiella"), Ok(()));
+        assert_eq!(check_input(""), Err(InputError::Empty));
+        assert_eq!(check_input(" \t\n"), Err(InputError::Whitespace));
+        assert_eq!(
+            check_input("giel\u{0}la"),
+            Err(InputError::ControlCharacter)
+        );
+        assert_eq!(check_input("giella\n"), Err(InputError::ControlCharacter));
+
+        assert_eq!(check_input(&"á".repeat(MAX_INPUT_CHARS)), Ok(()));
+        assert_eq!(
+            check_input(&"a".repeat(MAX_INPUT_CHARS + 1)),
+            Err(InputError::TooLong {
+                chars: MAX_INPUT_CHARS + 1
+            })
+        );
+    }
+}
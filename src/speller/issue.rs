@@ -0,0 +1,175 @@
+//! Misspellings found in a text, as every frontend reports them.
+//!
+//! Serialized as
+//!
+//! ```json
+//! {"word": "gielaa", "offset": 9, "start": 9, "end": 15,
+//!  "suggestions": [{"value": "giella", "weight": 14.5}]}
+//! ```
+//!
+//! where `offset` is in UTF-8 bytes, for slicing the text in Rust, and
+//! `start` and `end` are in UTF-16 code units, as JavaScript, Java and most
+//! editors index strings. `suggestions` may be empty. The kind of mistake
+//! is worked out on request by `error_class`, so it isn't serialized.
+//!
+//! A time budget for a whole text is a deadline for its suggestions:
+//! `Speller::check_text_until` and `SpellingIssue::suggest_until` give each
+//! search what is left of it, and leave the misspellings found after it
+//! passes without suggestions.
+
+use alloc::vec::Vec;
+use serde_derive::{Deserialize, Serialize};
+use smol_str::SmolStr;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use super::suggestion::Suggestion;
+#[cfg(feature = "std")]
+use super::SpellerConfig;
+
+/// `config` with its time budget cut to what is left until `deadline`, or
+/// `None` once it has passed.
+#[cfg(feature = "std")]
+pub fn within_deadline(config: &SpellerConfig, deadline: Option<Instant>) -> Option<SpellerConfig> {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return Some(config.clone()),
+    };
+
+    let now = Instant::now();
+    if now >= deadline {
+        return None;
+    }
+
+    let left = (deadline - now).as_millis() as u64;
+    Some(SpellerConfig {
+        time_budget_ms: Some(config.time_budget_ms.map_or(left, |ms| ms.min(left))),
+        ..config.clone()
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpellingIssue {
+    pub word: SmolStr,
+    pub offset: usize,
+    pub start: usize,
+    pub end: usize,
+    #[serde(default)]
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl SpellingIssue {
+    /// Issues without suggestions for words `Speller::check_text` found in
+    /// `text`, counting UTF-16 offsets once over the whole text.
+    pub fn locate(text: &str, found: Vec<(usize, &str)>) -> Vec<SpellingIssue> {
+        let mut start = 0;
+        let mut counted = 0;
+
+        found
+            .into_iter()
+            .map(|(offset, word)| {
+                start += text[counted..offset].encode_utf16().count();
+                counted = offset;
+
+                SpellingIssue {
+                    word: SmolStr::from(word),
+                    offset,
+                    start,
+                    end: start + word.encode_utf16().count(),
+                    suggestions: Vec::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// Fills in suggestions from `suggest` for as many of `issues` as there
+    /// is time for before `deadline`, in order, each search's time budget
+    /// cut to what is left. Those after it passes are left without.
+    #[cfg(feature = "std")]
+    pub fn suggest_until<F>(
+        issues: &mut [SpellingIssue],
+        config: &SpellerConfig,
+        deadline: Option<Instant>,
+        mut suggest: F,
+    ) where
+        F: FnMut(&str, &SpellerConfig) -> Vec<Suggestion>,
+    {
+        for issue in issues {
+            let config = match within_deadline(config, deadline) {
+                Some(config) => config,
+                None => break,
+            };
+            issue.suggestions = suggest(&issue.word, &config);
+        }
+    }
+
+    /// What kind of mistake this probably is, judging by the best
+    /// suggestion; see `speller::classify`.
+    #[cfg(feature = "std")]
+    pub fn error_class(&self) -> super::classify::ErrorClass {
+        super::classify::classify(&self.word, self.suggestions.first().map(|s| s.value()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf16_offsets() {
+        let text = "Sámegiella 😀 gielaa";
+        let issues = SpellingIssue::locate(text, vec![(0, "Sámegiella"), (17, "gielaa")]);
+
+        assert_eq!(
+            (issues[0].offset, issues[0].start, issues[0].end),
+            (0, 0, 10)
+        );
+        assert_eq!(&text[issues[1].offset..], "gielaa");
+        assert_eq!((issues[1].start, issues[1].end), (14, 20));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn deadlines() {
+        use std::time::Duration;
+
+        let config = SpellerConfig {
+            time_budget_ms: Some(50),
+            ..SpellerConfig::default()
+        };
+
+        let far = Instant::now() + Duration::from_secs(60);
+        assert_eq!(
+            within_deadline(&config, Some(far)).unwrap().time_budget_ms,
+            Some(50)
+        );
+        assert_eq!(
+            within_deadline(&config, None).unwrap().time_budget_ms,
+            Some(50)
+        );
+
+        let near = Instant::now() + Duration::from_secs(10);
+        let unlimited = SpellerConfig::default();
+        let budget = within_deadline(&unlimited, Some(near))
+            .unwrap()
+            .time_budget_ms;
+        assert!(budget.unwrap() <= 10_000);
+
+        assert!(within_deadline(&config, Some(Instant::now())).is_none());
+
+        let mut issues = SpellingIssue::locate("a b", vec![(0, "a"), (2, "b")]);
+        let mut asked = vec![];
+        SpellingIssue::suggest_until(&mut issues, &config, Some(far), |word, _| {
+            asked.push(word.to_string());
+            vec![Suggestion::new(SmolStr::from("c"), 1.0)]
+        });
+        assert_eq!(asked, vec!["a", "b"]);
+        assert!(issues.iter().all(|issue| issue.suggestions.len() == 1));
+
+        let mut issues = SpellingIssue::locate("a b", vec![(0, "a"), (2, "b")]);
+        SpellingIssue::suggest_until(&mut issues, &config, Some(Instant::now()), |_, _| {
+            panic!("no time left to suggest")
+        });
+        assert!(issues.iter().all(|issue| issue.suggestions.is_empty()));
+    }
+}
@@ -1,26 +1,139 @@
+pub mod beam;
+#[cfg(feature = "std")]
+pub mod classify;
+#[cfg(feature = "std")]
+pub mod confusion;
+#[cfg(feature = "std")]
+pub mod corpus;
+pub mod defaults;
+pub mod degrade;
+#[cfg(feature = "std")]
+pub mod document;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod document_cache;
+pub mod fallback;
+#[cfg(feature = "std")]
+pub mod feedback;
+pub mod filter;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod frequency;
+pub mod input;
+pub mod issue;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod prefix_cache;
+pub mod profile;
+pub mod proper_noun;
+pub mod replay;
+pub mod seen;
+pub mod semiring;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod snapshot;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod stream;
 pub mod suggestion;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod suggestion_cache;
+#[cfg(feature = "std")]
+pub mod user_dictionary;
+#[cfg(feature = "std")]
+pub mod user_overlay;
+pub mod warm_up;
 pub mod worker;
 
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::f32;
+use core::sync::atomic::{AtomicBool, Ordering};
 use hashbrown::HashMap;
 use serde_derive::{Deserialize, Serialize};
 use smol_str::SmolStr;
-use std::f32;
-use std::sync::Arc;
+#[cfg(feature = "std")]
+pub use std::time::Instant;
 
+use self::filter::{FilterSlot, SuggestionFilter};
+use self::input::InputError;
+#[cfg(feature = "std")]
+use self::issue::SpellingIssue;
+use self::prefix_cache::PrefixCache;
+pub use self::profile::InputProfile;
+use self::replay::{SearchTrace, Step};
+pub use self::seen::SeenNodes;
+pub use self::semiring::WeightSemiring;
 use self::worker::SpellerWorker;
+use crate::memory::{MemoryPolicy, SearchMemory};
+use crate::metrics::{Metrics, MetricsSlot};
+use crate::speller::defaults::SpellerDefaults;
 use crate::speller::suggestion::Suggestion;
-use crate::transducer::Transducer;
+use crate::sync::RwLock;
+use crate::tokenizer::abbreviations::Abbreviations;
+use crate::tokenizer::caps::{Caps, CaseTable};
+use crate::transducer::{lookup, scan};
+use crate::transducer::{HfstTransducer, Transducer};
 use crate::types::{SymbolNumber, Weight};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default = "SpellerConfig::default")]
 pub struct SpellerConfig {
     pub n_best: Option<usize>,
     pub max_weight: Option<Weight>,
+    /// Drop search paths that make more than this many error model edits
+    /// (insertions, deletions and substitutions), however light they are.
+    pub max_edit_distance: Option<u8>,
+    /// Drop suggestions weighing more than this past the best one, so that
+    /// an `n_best` list doesn't trail off into unlikely words.
+    pub max_weight_gap: Option<Weight>,
     pub beam: Option<Weight>,
+    /// Search a word's case variants too, and recase the suggestions to
+    /// match it. Only affects suggestions; `Speller::set_caps_enabled` turns
+    /// case handling off for correctness checks as well.
     pub with_caps: bool,
+    /// Adjust the beam as the search goes, starting from `beam`, instead of
+    /// keeping it fixed; see `speller::beam`.
+    pub adaptive_beam: bool,
+    /// With `adaptive_beam`, the narrowest the beam gets once `n_best`
+    /// suggestions are found.
+    pub beam_min: Weight,
+    /// With `adaptive_beam`, the widest the beam gets while suggestions are
+    /// scarce.
+    pub beam_max: Weight,
+    /// Nodes each thread's search stack has room for before it has to grow.
     pub pool_start: usize,
+    /// Capacity a thread's search stack may keep between queries; a stack
+    /// that grew larger is shrunk back.
     pub pool_max: usize,
+    /// How expanded nodes are remembered so that duplicates can be skipped.
+    pub seen_nodes: SeenNodes,
+    /// With `SeenNodes::Sampled`, one in this many expanded nodes is kept.
     pub seen_node_sample_rate: u64,
+    /// With `SeenNodes::Bloom`, the chance that a node is wrongly taken for
+    /// one already expanded.
+    pub seen_false_positive_rate: f64,
+    /// Stop searching after this many milliseconds and return the best
+    /// suggestions found so far. Ignored on WebAssembly and without `std`,
+    /// which have no clock.
+    pub time_budget_ms: Option<u64>,
+    /// Keep the search states of this many recent inputs, so that a
+    /// suggestion query for a word that extends one of them (as when
+    /// checking while typing) resumes its search. Searches that fill the
+    /// cache prune less, so a single query can be slower.
+    pub prefix_cache: Option<usize>,
+    /// How the weights of several paths to one suggestion combine; see
+    /// `speller::semiring`.
+    pub semiring: WeightSemiring,
+    /// Weigh error model edits for this kind of input; see
+    /// `speller::profile`.
+    pub input_profile: Option<InputProfile>,
+    /// Treat a capitalized word as a name, as clients do for words that
+    /// aren't at the start of a sentence; see `speller::proper_noun`.
+    pub proper_nouns: bool,
+    /// Give searches of an error model too large to search exhaustively a
+    /// beam and an edit distance cap where this config has none; see
+    /// `speller::degrade`.
+    pub constrain_huge_errmodels: bool,
+    /// When the error model suggests nothing, suggest the lexicon's words
+    /// within this many edits of the word instead; see `speller::fallback`.
+    pub fallback_distance: Option<u8>,
 }
 
 impl SpellerConfig {
@@ -28,33 +141,292 @@ impl SpellerConfig {
         SpellerConfig {
             n_best: None,
             max_weight: None,
+            max_edit_distance: None,
+            max_weight_gap: None,
             beam: None,
             with_caps: true,
+            adaptive_beam: false,
+            beam_min: 5.0,
+            beam_max: 40.0,
             pool_start: 128,
             pool_max: 128,
+            seen_nodes: SeenNodes::Off,
             seen_node_sample_rate: 20,
+            seen_false_positive_rate: 0.001,
+            time_budget_ms: None,
+            prefix_cache: None,
+            semiring: WeightSemiring::Tropical,
+            input_profile: None,
+            proper_nouns: false,
+            constrain_huge_errmodels: true,
+            fallback_distance: None,
         }
     }
+
+    /// This config for text typed in the way `profile` describes, with the
+    /// search adjusted to suit it.
+    pub fn with_profile(&self, profile: InputProfile) -> SpellerConfig {
+        profile.configure(self)
+    }
+}
+
+/// Stands in for `std::time::Instant` without `std`, where there is no
+/// clock and so no search has a deadline.
+#[cfg(not(feature = "std"))]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Instant;
+
+#[cfg(not(feature = "std"))]
+impl Instant {
+    pub fn now() -> Instant {
+        Instant
+    }
+}
+
+/// When a search started now has to stop, shared by every case variant of a
+/// word so the budget covers the whole query.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+fn deadline(config: &SpellerConfig) -> Option<Instant> {
+    config
+        .time_budget_ms
+        .map(|ms| Instant::now() + core::time::Duration::from_millis(ms))
+}
+
+#[cfg(any(not(feature = "std"), target_arch = "wasm32"))]
+fn deadline(_config: &SpellerConfig) -> Option<Instant> {
+    None
+}
+
+/// Whether a word is correct and what it may have been meant as, from
+/// `Speller::check`. Serialized as `{"correct": false, "suggestions": [...]}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WordCheck {
+    pub correct: bool,
+    pub suggestions: Vec<Suggestion>,
 }
 
+/// A speller for one lexicon and error model.
+///
+/// Spellers are made in an `Arc`, as an `ArcSpeller`, and are meant to be
+/// shared: any number of threads can check and correct words with the same
+/// speller at once. The transducers and lookup tables are never changed
+/// once the speller is made, and what can change, such as abbreviations,
+/// defaults and caches, is behind its own lock or atomic, held only for the
+/// moment it is read or swapped, never for a search. There is nothing to
+/// gain from a lock around the speller itself; it only makes queries wait
+/// for each other.
 #[derive(Debug)]
 pub struct Speller<T: Transducer> {
     mutator: T,
     lexicon: T,
     alphabet_translator: Vec<SymbolNumber>,
+    /// The mutator's key table as characters, `NO_CHAR` for keys that are
+    /// not exactly one character, so input can be looked up with a SIMD
+    /// scan.
+    input_chars: Vec<u32>,
+    /// The mutator's keys of several characters, longest first, which
+    /// input is matched against before single characters.
+    multichar_inputs: Vec<(SmolStr, SymbolNumber)>,
+    /// Built with the speller; restoring a snapshot replaces it.
+    case_table: RwLock<Arc<CaseTable>>,
+    /// The lexicon's `+Prop` symbol, if it tags proper nouns.
+    proper_noun_tag: Option<SymbolNumber>,
+    abbreviations: RwLock<Arc<Abbreviations>>,
+    prefix_cache: PrefixCache,
+    max_search_bytes: Option<usize>,
+    search_memory: SearchMemory,
+    metrics: MetricsSlot,
+    huge_errmodel: AtomicBool,
+    defaults: RwLock<SpellerDefaults>,
+    caps: AtomicBool,
+    filters: FilterSlot,
+}
+
+/// A speller shared between threads, as `Speller::new` and
+/// `SpellerArchive::speller` give it. Cloning one only counts a reference.
+pub type ArcSpeller<T = HfstTransducer> = Arc<Speller<T>>;
+
+/// Not a valid `char`, so matches no input.
+const NO_CHAR: u32 = u32::MAX;
+
+/// The keys of `key_table` input can spell that are longer than one
+/// character, such as "ij" or "+Der", longest first. Flag diacritics and
+/// other `@...@` specials aren't matched against input.
+fn multichar_inputs(key_table: &[SmolStr]) -> Vec<(SmolStr, SymbolNumber)> {
+    let mut keys: Vec<(SmolStr, SymbolNumber)> = key_table
+        .iter()
+        .enumerate()
+        .filter(|(_, key)| key.chars().nth(1).is_some())
+        .filter(|(_, key)| !(key.starts_with('@') && key.ends_with('@')))
+        .map(|(i, key)| (key.clone(), i as SymbolNumber))
+        .collect();
+    keys.sort_by_key(|(key, _)| core::cmp::Reverse(key.len()));
+    keys
+}
+
+/// `word` as input symbols, longest match first: at each point the longest
+/// of `multichar` it starts with is taken, else its next character. So with
+/// a key "ij", "bijl" is read as "b", "ij", "l", never "b", "i", "j", "l",
+/// even if the mutator has "i" and "j" too. Characters with no key are left
+/// out, as they can't be read.
+fn input_symbols(
+    word: &str,
+    chars: &[u32],
+    multichar: &[(SmolStr, SymbolNumber)],
+) -> Vec<SymbolNumber> {
+    if multichar.is_empty() {
+        return word
+            .chars()
+            .filter_map(|ch| scan::position(chars, ch as u32))
+            .map(|x| x as SymbolNumber)
+            .collect();
+    }
+
+    let mut symbols = Vec::with_capacity(word.len());
+    let mut rest = word;
+    while let Some(ch) = rest.chars().next() {
+        if let Some((key, symbol)) = multichar.iter().find(|(key, _)| rest.starts_with(&**key)) {
+            symbols.push(*symbol);
+            rest = &rest[key.len()..];
+            continue;
+        }
+
+        if let Some(x) = scan::position(chars, ch as u32) {
+            symbols.push(x as SymbolNumber);
+        }
+        rest = &rest[ch.len_utf8()..];
+    }
+    symbols
 }
 
 impl<T: Transducer> Speller<T> {
-    pub fn new(mutator: T, mut lexicon: T) -> Arc<Speller<T>> {
+    pub fn new(mutator: T, lexicon: T) -> ArcSpeller<T> {
+        Speller::with_memory_policy(mutator, lexicon, &MemoryPolicy::default())
+    }
+
+    /// A speller whose searches keep to the policy's `max_search_bytes`.
+    pub fn with_memory_policy(mutator: T, mut lexicon: T, policy: &MemoryPolicy) -> ArcSpeller<T> {
         let alphabet_translator = lexicon.mut_alphabet().create_translator_from(&mutator);
+        let input_chars = mutator
+            .alphabet()
+            .key_table()
+            .iter()
+            .map(|key| {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => ch as u32,
+                    _ => NO_CHAR,
+                }
+            })
+            .collect();
+        let multichar_inputs = multichar_inputs(mutator.alphabet().key_table());
+        // Without the `caps` feature no word is ever recased, so the table
+        // is left empty
+        let case_table = if cfg!(feature = "caps") {
+            CaseTable::new(lexicon.alphabet().key_table())
+        } else {
+            CaseTable::new(&[])
+        };
+        let proper_noun_tag = lexicon
+            .alphabet()
+            .key_table()
+            .iter()
+            .position(|key| key == proper_noun::PROPER_NOUN_TAG)
+            .map(|i| i as SymbolNumber);
 
         Arc::new(Speller {
             mutator,
             lexicon,
             alphabet_translator,
+            input_chars,
+            multichar_inputs,
+            case_table: RwLock::new(Arc::new(case_table)),
+            proper_noun_tag,
+            abbreviations: RwLock::new(Arc::new(Abbreviations::new())),
+            prefix_cache: PrefixCache::new(),
+            max_search_bytes: policy.max_search_bytes,
+            search_memory: SearchMemory::default(),
+            metrics: MetricsSlot::new(),
+            huge_errmodel: AtomicBool::new(false),
+            defaults: RwLock::new(SpellerDefaults::default()),
+            caps: AtomicBool::new(true),
+            filters: FilterSlot::new(),
         })
     }
 
+    pub fn max_search_bytes(&self) -> Option<usize> {
+        self.max_search_bytes
+    }
+
+    /// Whether the error model is too large to search exhaustively, as
+    /// `SpellerArchive` marks it when opening the archive.
+    pub fn huge_errmodel(&self) -> bool {
+        self.huge_errmodel.load(Ordering::Relaxed)
+    }
+
+    pub fn set_huge_errmodel(&self, huge: bool) {
+        self.huge_errmodel.store(huge, Ordering::Relaxed);
+    }
+
+    /// Whether words are tried in their other case variants too, as "Giella"
+    /// as "giella", and suggestions recased to match. Always `false` without
+    /// the `caps` feature.
+    pub fn caps_enabled(&self) -> bool {
+        cfg!(feature = "caps") && self.caps.load(Ordering::Relaxed)
+    }
+
+    /// Turns case handling off for clients that recase words themselves: a
+    /// word is only looked up exactly as written, and its suggestions are
+    /// searched for as with `SpellerConfig::with_caps` off, whatever the
+    /// config says.
+    pub fn set_caps_enabled(&self, enabled: bool) {
+        self.caps.store(enabled, Ordering::Relaxed);
+    }
+
+    /// `word`'s case variants, or just `word` with case handling off.
+    fn word_variants(&self, word: &str) -> Vec<SmolStr> {
+        if self.caps_enabled() {
+            self.case_table().word_variants(word)
+        } else {
+            vec![SmolStr::from(word)]
+        }
+    }
+
+    /// Settings used where a query's config has none, as the archive
+    /// recommends them; see `speller::defaults`.
+    pub fn defaults(&self) -> SpellerDefaults {
+        *self.defaults.read()
+    }
+
+    pub fn set_defaults(&self, defaults: SpellerDefaults) {
+        *self.defaults.write() = defaults;
+    }
+
+    /// The config searches with `config` actually run with: with the
+    /// speller's `defaults` where it has no settings of its own, then
+    /// constrained as `degrade::constrain` does it for a huge error model.
+    pub fn effective_config(&self, config: &SpellerConfig) -> SpellerConfig {
+        let config = self.defaults().apply(config);
+        if config.constrain_huge_errmodels && self.huge_errmodel() {
+            degrade::constrain(&config)
+        } else {
+            config
+        }
+    }
+
+    pub(crate) fn search_memory(&self) -> &SearchMemory {
+        &self.search_memory
+    }
+
+    pub fn metrics(&self) -> Arc<dyn Metrics> {
+        self.metrics.get()
+    }
+
+    /// Reports this speller's lookups to `metrics` from now on.
+    pub fn set_metrics(&self, metrics: Arc<dyn Metrics>) {
+        self.metrics.set(metrics);
+    }
+
     pub fn mutator(&self) -> &T {
         &self.mutator
     }
@@ -63,151 +435,430 @@ impl<T: Transducer> Speller<T> {
         &self.lexicon
     }
 
+    /// Case mappings for the lexicon's alphabet, as used for case variants.
+    pub fn case_table(&self) -> Arc<CaseTable> {
+        self.case_table.read().clone()
+    }
+
+    /// Uses `table` for case variants instead of the one built with the
+    /// speller, as when restoring a `SpellerSnapshot`. It has to have been
+    /// built for this lexicon's alphabet.
+    pub fn set_case_table(&self, table: CaseTable) {
+        *self.case_table.write() = Arc::new(table);
+    }
+
+    /// Words accepted however the lexicon has them, and after which a
+    /// sentence doesn't end; an archive's come from its
+    /// `abbreviations.txt`.
+    pub fn abbreviations(&self) -> Arc<Abbreviations> {
+        self.abbreviations.read().clone()
+    }
+
+    pub fn set_abbreviations(&self, abbreviations: Abbreviations) {
+        *self.abbreviations.write() = Arc::new(abbreviations);
+    }
+
+    /// Drops candidates `filter` rejects from every search from now on, as
+    /// well as those the filters already added reject; see `filter`.
+    pub fn add_filter<F: SuggestionFilter + 'static>(&self, filter: F) {
+        self.filters.add(Arc::new(filter));
+    }
+
+    pub fn clear_filters(&self) {
+        self.filters.clear();
+    }
+
+    pub(crate) fn filters(&self) -> Arc<Vec<Arc<dyn SuggestionFilter>>> {
+        self.filters.get()
+    }
+
+    pub(crate) fn proper_noun_tag(&self) -> Option<SymbolNumber> {
+        self.proper_noun_tag
+    }
+
+    /// Shared by every query with `SpellerConfig::prefix_cache` set.
+    pub fn prefix_cache(&self) -> &PrefixCache {
+        &self.prefix_cache
+    }
+
     fn alphabet_translator(&self) -> &Vec<SymbolNumber> {
         &self.alphabet_translator
     }
 
     fn to_input_vec(&self, word: &str) -> Vec<SymbolNumber> {
-        let key_table = self.mutator().alphabet().key_table();
+        input_symbols(word, &self.input_chars, &self.multichar_inputs)
+    }
 
-        word.chars()
-            .filter_map(|ch| {
-                let s = ch.to_string();
-                key_table.iter().position(|x| x == &s)
-            })
-            .map(|x| x as u16)
-            .collect()
+    /// Whether `word` is one the speller looks up at all; see `input`.
+    pub fn check_input(&self, word: &str) -> Result<(), InputError> {
+        input::check_input(word)
     }
 
     pub fn is_correct(self: Arc<Self>, word: &str) -> bool {
-        use crate::tokenizer::caps::*;
+        if self.abbreviations.read().contains(word) {
+            self.metrics().lookup(true);
+            return true;
+        }
+
+        if input::check_input(word).is_err() {
+            return false;
+        }
 
-        let words = word_variants(self.lexicon().alphabet().key_table(), word);
+        let words = self.word_variants(word);
+        self.any_correct(words)
+    }
 
-        for word in words.into_iter() {
+    /// Whether any of `words` is accepted as written.
+    fn any_correct(self: Arc<Self>, words: Vec<SmolStr>) -> bool {
+        let correct = words.into_iter().any(|word| {
             let worker = SpellerWorker::new(
                 self.clone(),
                 self.to_input_vec(&word),
                 SpellerConfig::default(),
+                None,
             );
 
-            if worker.is_correct() {
-                return true;
+            worker.is_correct()
+        });
+
+        self.metrics().lookup(correct);
+        correct
+    }
+
+    /// As `is_correct`, except that a capitalized word in the middle of a
+    /// sentence is taken for a name and only accepted with its capital.
+    #[cfg(feature = "std")]
+    pub(crate) fn is_correct_in_sentence(
+        self: Arc<Self>,
+        word: &str,
+        sentence_initial: bool,
+    ) -> bool {
+        if !self.caps_enabled() || sentence_initial || Caps::of(word) != Caps::FirstCaps {
+            return self.is_correct(word);
+        }
+
+        if input::check_input(word).is_err() {
+            return false;
+        }
+
+        let words = self
+            .case_table()
+            .word_variants(word)
+            .into_iter()
+            .filter(|variant| Caps::of(variant) != Caps::Lower)
+            .collect();
+        self.any_correct(words)
+    }
+
+    /// The weight the lexicon gives `word` as written, without trying its
+    /// case variants, or `None` if it isn't accepted.
+    pub fn lexicon_weight(self: Arc<Self>, word: &str) -> Option<Weight> {
+        if input::check_input(word).is_err() {
+            return None;
+        }
+
+        let worker = SpellerWorker::new(
+            self.clone(),
+            self.to_input_vec(word),
+            SpellerConfig::default(),
+            None,
+        );
+        worker.lexicon_weight()
+    }
+
+    /// How likely the lexicon finds `word`, for ranking candidates from
+    /// elsewhere, such as a speech recognizer's n-best list or the paths of
+    /// an OCR lattice: the weight of its lightest path through the lexicon,
+    /// trying the case variants `is_correct` tries, or `None` if it isn't
+    /// accepted. Lighter is likelier, as with suggestion weights.
+    pub fn score(self: Arc<Self>, word: &str) -> Option<Weight> {
+        if input::check_input(word).is_err() {
+            return None;
+        }
+
+        self.word_variants(word)
+            .into_iter()
+            .filter_map(|variant| self.clone().lexicon_weight(&variant))
+            .fold(None, |best: Option<Weight>, weight| {
+                Some(best.map_or(weight, |best| best.min(weight)))
+            })
+    }
+
+    /// The weight `expected` would have as a suggestion for `word`, whether
+    /// or not a search finds it: the lightest error model path from one to
+    /// the other plus the lexicon's weight for `expected`. Pairs the error
+    /// model doesn't connect as written are tried in lower case.
+    pub fn correction_weight(self: Arc<Self>, word: &str, expected: &str) -> Option<Weight> {
+        let weigh = |word: &str, expected: &str| {
+            let edits = lookup::lookup_weight(self.mutator(), word, expected)?;
+            let lexicon = self.clone().lexicon_weight(expected)?;
+            Some(edits + lexicon)
+        };
+
+        weigh(word, expected).or_else(|| {
+            let (word, expected) = (word.to_lowercase(), expected.to_lowercase());
+            weigh(&word, &expected)
+        })
+    }
+
+    /// The misspelled words in `text`, with their byte offsets. Tokens
+    /// without letters, such as numbers and punctuation, are skipped.
+    ///
+    /// A capitalized word at the start of a sentence is correct if its lower
+    /// case form is, as "Dat" for "dat", but in the middle of one it is taken
+    /// for a name and has to be in the lexicon capitalized. Sentences whose
+    /// words are all capitalized, as headings often are, are checked as if
+    /// every word started one.
+    #[cfg(feature = "std")]
+    pub fn check_text(self: Arc<Self>, text: &str) -> Vec<(usize, &str)> {
+        self.text_words(text)
+            .into_iter()
+            .filter(|&(_, word, initial)| !self.clone().is_correct_in_sentence(word, initial))
+            .map(|(offset, word, _)| (offset, word))
+            .collect()
+    }
+
+    /// The words of `text` with letters in them that aren't abbreviations,
+    /// with their byte offsets and whether they may be capitalized as at the
+    /// start of a sentence: they start one, or every word of their sentence
+    /// is capitalized.
+    #[cfg(feature = "std")]
+    pub(crate) fn text_words<'a>(&self, text: &'a str) -> Vec<(usize, &'a str, bool)> {
+        use crate::tokenizer::Tokenize;
+
+        let abbreviations = self.abbreviations();
+        let mut words = vec![];
+
+        for (start, sentence) in text.sentence_bound_indices_with(&abbreviations) {
+            let first = words.len();
+            for (offset, word) in sentence.word_bound_indices() {
+                if word.chars().any(char::is_alphabetic) {
+                    words.push((start + offset, word, words.len() == first));
+                }
+            }
+
+            let heading = words[first..]
+                .iter()
+                .all(|&(_, word, _)| Caps::of(word) != Caps::Lower);
+            if heading {
+                for word in &mut words[first..] {
+                    word.2 = true;
+                }
             }
         }
 
-        false
+        let spans = abbreviations.find(text);
+        words.retain(|&(offset, _, _)| {
+            !spans
+                .iter()
+                .any(|&(start, abbr)| start <= offset && offset < start + abbr.len())
+        });
+        words
     }
 
-    pub fn suggest(self: Arc<Self>, word: &str) -> Vec<Suggestion> {
-        self.suggest_with_config(word, &SpellerConfig::default())
+    /// The misspellings `check_text` finds in `text`, with suggestions cased
+    /// for where each word is: those for a capitalized word in the middle of
+    /// a sentence are searched for in `SpellerConfig::proper_nouns` mode,
+    /// after its lower case form if that is correct.
+    #[cfg(feature = "std")]
+    pub fn check_text_with_config(
+        self: Arc<Self>,
+        text: &str,
+        config: &SpellerConfig,
+    ) -> Vec<SpellingIssue> {
+        let speller = self.clone();
+        self.check_text_until(text, config, None, |word, config| {
+            speller.clone().suggest_with_config(word, config)
+        })
     }
 
-    fn suggest_single(self: Arc<Self>, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
-        let worker = SpellerWorker::new(self.clone(), self.to_input_vec(word), config.clone());
+    /// Like `check_text_with_config`, with suggestions for as many of the
+    /// misspellings as there is time for before `deadline`, each search's
+    /// time budget cut to what is left, and searched for by `suggest`, so
+    /// that they can come from a cache. Those after it are left without.
+    #[cfg(feature = "std")]
+    pub fn check_text_until<F>(
+        self: Arc<Self>,
+        text: &str,
+        config: &SpellerConfig,
+        deadline: Option<Instant>,
+        mut suggest: F,
+    ) -> Vec<SpellingIssue>
+    where
+        F: FnMut(&str, &SpellerConfig) -> Vec<Suggestion>,
+    {
+        let found: Vec<(usize, &str, bool)> = self
+            .text_words(text)
+            .into_iter()
+            .filter(|&(_, word, initial)| !self.clone().is_correct_in_sentence(word, initial))
+            .collect();
+
+        let located = found
+            .iter()
+            .map(|&(offset, word, _)| (offset, word))
+            .collect();
+        let mut issues = SpellingIssue::locate(text, located);
+
+        for (issue, &(_, _, initial)) in issues.iter_mut().zip(&found) {
+            let config = match issue::within_deadline(config, deadline) {
+                Some(config) => config,
+                None => break,
+            };
+            issue.suggestions =
+                self.clone()
+                    .suggest_in_sentence_with(&issue.word, initial, &config, &mut suggest);
+        }
 
-        worker.suggest()
+        issues
     }
 
-    fn suggest_caps_merging(
+    /// Suggestions for a misspelled `word` of a text: a capitalized word in
+    /// the middle of a sentence is searched for as a name, after its lower
+    /// case form if that is correct.
+    #[cfg(feature = "std")]
+    pub(crate) fn suggest_in_sentence(
         self: Arc<Self>,
-        ref_word: &str,
-        words: Vec<SmolStr>,
+        word: &str,
+        sentence_initial: bool,
         config: &SpellerConfig,
     ) -> Vec<Suggestion> {
-        use crate::tokenizer::caps::*;
+        let speller = self.clone();
+        self.suggest_in_sentence_with(word, sentence_initial, config, &mut |word, config| {
+            speller.clone().suggest_with_config(word, config)
+        })
+    }
 
-        let mut best: HashMap<SmolStr, f32> = HashMap::new();
+    /// `suggest_in_sentence`, with each search done by `suggest`.
+    #[cfg(feature = "std")]
+    fn suggest_in_sentence_with(
+        self: Arc<Self>,
+        word: &str,
+        sentence_initial: bool,
+        config: &SpellerConfig,
+        suggest: &mut dyn FnMut(&str, &SpellerConfig) -> Vec<Suggestion>,
+    ) -> Vec<Suggestion> {
+        if !self.caps_enabled() || sentence_initial || Caps::of(word) != Caps::FirstCaps {
+            return suggest(word, config);
+        }
 
-        for word in words.into_iter() {
-            let worker = SpellerWorker::new(self.clone(), self.to_input_vec(&word), config.clone());
+        let in_sentence = SpellerConfig {
+            proper_nouns: true,
+            ..config.clone()
+        };
+        let mut suggestions = suggest(word, &in_sentence);
 
-            let suggestions = worker.suggest();
+        let lower = self.case_table().lower_case(word);
+        if let Some(weight) = self.clone().lexicon_weight(&lower) {
+            suggestions.retain(|s| s.value != lower);
+            suggestions.insert(0, Suggestion::new(lower, weight));
+            if let Some(n) = self.effective_config(config).n_best {
+                suggestions.truncate(n);
+            }
+        }
 
-            if !suggestions.is_empty() {
-                let r = if is_all_caps(ref_word) {
-                    suggestions
-                        .into_iter()
-                        .map(|mut x| {
-                            x.value = upper_case(x.value());
-                            x
-                        })
-                        .collect()
-                } else if is_first_caps(ref_word) {
-                    suggestions
-                        .into_iter()
-                        .map(|mut x| {
-                            x.value = upper_first(x.value());
-                            x
-                        })
-                        .collect()
-                } else {
-                    suggestions
-                };
-
-                for sugg in r.into_iter() {
-                    best.entry(sugg.value.clone())
-                        .and_modify(|entry| {
-                            if entry as &_ > &sugg.weight {
-                                *entry = sugg.weight
-                            }
-                        })
-                        .or_insert(sugg.weight);
+        suggestions
+    }
+
+    pub fn suggest(self: Arc<Self>, word: &str) -> Vec<Suggestion> {
+        self.suggest_with_config(word, &SpellerConfig::default())
+    }
+
+    /// Suggestions for each of `words`, in order, looked up one after
+    /// another on the calling thread with `suggest_many`. See `parallel` for
+    /// a threaded version.
+    pub fn suggest_batch<S: AsRef<str>>(
+        self: Arc<Self>,
+        words: &[S],
+        config: &SpellerConfig,
+    ) -> Vec<Vec<Suggestion>> {
+        self.suggest_many(words, config)
+    }
+
+    pub fn is_correct_batch<S: AsRef<str>>(self: Arc<Self>, words: &[S]) -> Vec<bool> {
+        words
+            .iter()
+            .map(|word| self.clone().is_correct(word.as_ref()))
+            .collect()
+    }
+
+    /// Suggestions for one case variant, `word`, whose input symbols are
+    /// `input`, from the error model, or by edit distance if it has none and
+    /// the config asks for that; see `fallback`.
+    fn search(
+        self: Arc<Self>,
+        word: &str,
+        input: Vec<SymbolNumber>,
+        config: &SpellerConfig,
+        deadline: Option<Instant>,
+    ) -> Vec<Suggestion> {
+        let suggestions =
+            SpellerWorker::new(self.clone(), input, config.clone(), deadline).suggest();
+
+        match config.fallback_distance {
+            Some(distance) if suggestions.is_empty() => {
+                let mut suggestions = fallback::suggest(
+                    self.lexicon(),
+                    word,
+                    distance,
+                    config.proper_nouns,
+                    deadline,
+                );
+                let filters = self.filters();
+                suggestions.retain(|s| filter::allows(&filters, word, s.value()));
+                suggestion::within_gap(&mut suggestions, config.max_weight_gap);
+                if let Some(n) = config.n_best {
+                    suggestions.truncate(n);
                 }
+                suggestions
             }
+            _ => suggestions,
         }
+    }
 
-        let mut out = best
-            .into_iter()
-            .map(|(k, v)| Suggestion {
-                value: k,
-                weight: v,
-            })
-            .collect::<Vec<_>>();
-        out.sort();
-        if let Some(n_best) = config.n_best {
-            out.truncate(n_best);
+    /// Suggestions for `ref_word`, searching each of its case variants with
+    /// `search` as needed and recasing the results to match it.
+    fn suggest_cased(
+        &self,
+        ref_word: &str,
+        config: &SpellerConfig,
+        search: &mut dyn FnMut(&str) -> Vec<Suggestion>,
+    ) -> Vec<Suggestion> {
+        if !config.with_caps || !self.caps_enabled() {
+            return search(ref_word);
         }
-        out
+
+        let words = {
+            trace_span!("caps_variants");
+            self.case_table().word_variants(ref_word)
+        };
+        self.suggest_variants(ref_word, words, config, search)
     }
 
-    fn suggest_caps(
-        self: Arc<Self>,
+    /// Suggestions for `ref_word` from those for its case variants `words`.
+    fn suggest_variants(
+        &self,
         ref_word: &str,
         words: Vec<SmolStr>,
         config: &SpellerConfig,
+        search: &mut dyn FnMut(&str) -> Vec<Suggestion>,
     ) -> Vec<Suggestion> {
-        use crate::tokenizer::caps::*;
-
-        for word in words.into_iter() {
-            let worker = SpellerWorker::new(self.clone(), self.to_input_vec(&word), config.clone());
-
-            let suggestions = worker.suggest();
+        let cases = self.case_table();
+        let cases = &*cases;
+        trace_event!(variants = words.len(), "case variants");
 
+        if config.proper_nouns && Caps::of(ref_word) == Caps::FirstCaps {
+            let suggestions = search(ref_word);
             if !suggestions.is_empty() {
-                if is_all_caps(ref_word) {
-                    return suggestions
-                        .into_iter()
-                        .map(|mut x| {
-                            x.value = upper_case(x.value());
-                            x
-                        })
-                        .collect();
-                } else if is_first_caps(ref_word) {
-                    return suggestions
-                        .into_iter()
-                        .map(|mut x| {
-                            x.value = upper_first(x.value());
-                            x
-                        })
-                        .collect();
-                }
-
                 return suggestions;
             }
         }
 
-        vec![]
+        // TODO: check for the actual caps patterns, this is rather naive
+        if words.len() == 2 || words.len() == 3 {
+            suggest_caps_merging(cases, ref_word, words, config, search)
+        } else {
+            suggest_caps(cases, ref_word, words, search)
+        }
     }
 
     pub fn suggest_with_config(
@@ -215,19 +866,433 @@ impl<T: Transducer> Speller<T> {
         word: &str,
         config: &SpellerConfig,
     ) -> Vec<Suggestion> {
-        use crate::tokenizer::caps::*;
+        if input::check_input(word).is_err() {
+            return vec![];
+        }
 
-        if config.with_caps {
-            let words = word_variants(self.lexicon().alphabet().key_table(), word);
+        trace_span!("suggest", word = word);
 
-            // TODO: check for the actual caps patterns, this is rather naive
-            if words.len() == 2 || words.len() == 3 {
-                self.suggest_caps_merging(word, words, config)
-            } else {
-                self.suggest_caps(word, words, config)
-            }
+        #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+        let started = Instant::now();
+
+        let config = &self.effective_config(config);
+        let deadline = deadline(config);
+        let suggestions = self.suggest_cased(word, config, &mut |variant| {
+            self.clone()
+                .search(variant, self.to_input_vec(variant), config, deadline)
+        });
+
+        #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+        self.metrics().suggest(started.elapsed());
+
+        suggestions
+    }
+
+    /// Whether `word` is correct, as `is_correct` has it, together with
+    /// suggestions for it, as `suggest_with_config` gives them, for the cost
+    /// of one call: its case variants and their input symbols are worked out
+    /// once for both.
+    pub fn check(self: Arc<Self>, word: &str, config: &SpellerConfig) -> WordCheck {
+        if self.abbreviations.read().contains(word) {
+            self.metrics().lookup(true);
+            return WordCheck {
+                correct: true,
+                suggestions: self.suggest_with_config(word, config),
+            };
+        }
+
+        if input::check_input(word).is_err() {
+            return WordCheck {
+                correct: false,
+                suggestions: vec![],
+            };
+        }
+
+        trace_span!("check", word = word);
+
+        #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+        let started = Instant::now();
+
+        let variants = self.word_variants(word);
+        let inputs: Vec<(SmolStr, Vec<SymbolNumber>)> = variants
+            .iter()
+            .map(|variant| (variant.clone(), self.to_input_vec(variant)))
+            .collect();
+
+        let correct = inputs.iter().any(|(_, input)| {
+            SpellerWorker::new(self.clone(), input.clone(), SpellerConfig::default(), None)
+                .is_correct()
+        });
+        self.metrics().lookup(correct);
+
+        let config = &self.effective_config(config);
+        let deadline = deadline(config);
+        let mut search = |variant: &str| {
+            let input = match inputs.iter().find(|(v, _)| &**v == variant) {
+                Some((_, input)) => input.clone(),
+                None => self.to_input_vec(variant),
+            };
+            self.clone().search(variant, input, config, deadline)
+        };
+
+        let suggestions = if config.with_caps && self.caps_enabled() {
+            self.suggest_variants(word, variants, config, &mut search)
         } else {
-            self.suggest_single(word, config)
+            search(word)
+        };
+
+        #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+        self.metrics().suggest(started.elapsed());
+
+        WordCheck {
+            correct,
+            suggestions,
+        }
+    }
+
+    /// Suggestions for each of `words`, in order, as `suggest_with_config`
+    /// would give them, but with the work shared across the batch: a word
+    /// that repeats is looked up once, and so is a case variant that several
+    /// words have in common, such as "giella" for "Giella" and "GIELLA".
+    /// Each word still gets its own time budget; a variant whose search ran
+    /// out of it is reused as it is.
+    pub fn suggest_many<S: AsRef<str>>(
+        self: Arc<Self>,
+        words: &[S],
+        config: &SpellerConfig,
+    ) -> Vec<Vec<Suggestion>> {
+        let config = &self.effective_config(config);
+        let mut by_word: HashMap<&str, Vec<Suggestion>> = HashMap::new();
+        let mut by_input: HashMap<Vec<SymbolNumber>, Vec<Suggestion>> = HashMap::new();
+        #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+        let metrics = self.metrics();
+        let mut out = Vec::with_capacity(words.len());
+
+        for word in words {
+            let word = word.as_ref();
+            if let Some(found) = by_word.get(word) {
+                out.push(found.clone());
+                continue;
+            }
+
+            if input::check_input(word).is_err() {
+                out.push(vec![]);
+                continue;
+            }
+
+            trace_span!("suggest", word = word);
+
+            #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+            let started = Instant::now();
+
+            let deadline = deadline(config);
+            let suggestions = self.suggest_cased(word, config, &mut |variant| {
+                let input = self.to_input_vec(variant);
+                if let Some(found) = by_input.get(&input) {
+                    return found.clone();
+                }
+
+                let found = self
+                    .clone()
+                    .search(variant, input.clone(), config, deadline);
+                by_input.insert(input, found.clone());
+                found
+            });
+
+            #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+            metrics.suggest(started.elapsed());
+
+            by_word.insert(word, suggestions.clone());
+            out.push(suggestions);
+        }
+
+        out
+    }
+
+    /// Suggestions for `word` as `suggest_with_config` would give them,
+    /// with a trace of every decision the search made on the way; see
+    /// `replay`. The time budget and prefix cache are left out.
+    pub fn suggest_traced(self: Arc<Self>, word: &str, config: &SpellerConfig) -> SearchTrace {
+        let config = self.effective_config(&replay::traced_config(config));
+        let mut steps = vec![];
+
+        if input::check_input(word).is_err() {
+            return SearchTrace {
+                word: SmolStr::from(word),
+                config,
+                steps,
+            };
+        }
+
+        let suggestions = self.suggest_cased(word, &config, &mut |variant| {
+            steps.push(Step::Variant(SmolStr::from(variant)));
+            SpellerWorker::new(
+                self.clone(),
+                self.to_input_vec(variant),
+                config.clone(),
+                None,
+            )
+            .suggest_traced(&mut steps)
+        });
+
+        steps.extend(
+            suggestions
+                .into_iter()
+                .map(|x| Step::Suggestion(x.value, x.weight)),
+        );
+
+        SearchTrace {
+            word: SmolStr::from(word),
+            config,
+            steps,
+        }
+    }
+
+    /// Runs the query `trace` recorded again, with this speller, for
+    /// comparing with `SearchTrace::diff`.
+    pub fn replay(self: Arc<Self>, trace: &SearchTrace) -> SearchTrace {
+        self.suggest_traced(&trace.word, &trace.config)
+    }
+}
+
+fn recase(cases: &CaseTable, ref_word: &str, suggestions: Vec<Suggestion>) -> Vec<Suggestion> {
+    if cases.is_all_caps(ref_word) {
+        suggestions
+            .into_iter()
+            .map(|mut x| {
+                x.value = cases.upper_case(x.value());
+                x
+            })
+            .collect()
+    } else if cases.is_first_caps(ref_word) {
+        suggestions
+            .into_iter()
+            .map(|mut x| {
+                x.value = cases.upper_first(x.value());
+                x
+            })
+            .collect()
+    } else {
+        suggestions
+    }
+}
+
+fn suggest_caps_merging(
+    cases: &CaseTable,
+    ref_word: &str,
+    words: Vec<SmolStr>,
+    config: &SpellerConfig,
+    search: &mut dyn FnMut(&str) -> Vec<Suggestion>,
+) -> Vec<Suggestion> {
+    let mut best: HashMap<SmolStr, f32> = HashMap::new();
+
+    for word in words.into_iter() {
+        let suggestions = search(&word);
+
+        if !suggestions.is_empty() {
+            for sugg in recase(cases, ref_word, suggestions).into_iter() {
+                best.entry(sugg.value.clone())
+                    .and_modify(|entry| {
+                        if entry as &_ > &sugg.weight {
+                            *entry = sugg.weight
+                        }
+                    })
+                    .or_insert(sugg.weight);
+            }
+        }
+    }
+
+    let mut out = best
+        .into_iter()
+        .map(|(k, v)| Suggestion {
+            value: k,
+            weight: v,
+        })
+        .collect::<Vec<_>>();
+    out.sort();
+    suggestion::within_gap(&mut out, config.max_weight_gap);
+    if let Some(n_best) = config.n_best {
+        out.truncate(n_best);
+    }
+    out
+}
+
+fn suggest_caps(
+    cases: &CaseTable,
+    ref_word: &str,
+    words: Vec<SmolStr>,
+    search: &mut dyn FnMut(&str) -> Vec<Suggestion>,
+) -> Vec<Suggestion> {
+    for word in words.into_iter() {
+        let suggestions = search(&word);
+
+        if !suggestions.is_empty() {
+            return recase(cases, ref_word, suggestions);
         }
     }
+
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::test_speller;
+
+    #[test]
+    fn shared_between_threads() {
+        fn shareable<T: Send + Sync + Clone>() {}
+        shareable::<ArcSpeller>();
+
+        let speller = test_speller(&[("divun", "divvun", 2)], &["divvun"]);
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let speller = speller.clone();
+                std::thread::spawn(move || speller.suggest("divun"))
+            })
+            .collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap()[0].value(), "divvun");
+        }
+    }
+
+    #[test]
+    fn multichar_input_longest_first() {
+        let keys: Vec<SmolStr> = ["", "b", "i", "j", "l", "ij", "@P.X.Y@", "+Der"]
+            .iter()
+            .map(|&k| SmolStr::from(k))
+            .collect();
+        let chars: Vec<u32> = keys
+            .iter()
+            .map(|k| {
+                let mut chars = k.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => ch as u32,
+                    _ => NO_CHAR,
+                }
+            })
+            .collect();
+        let multichar = multichar_inputs(&keys);
+        assert_eq!(
+            multichar.iter().map(|(k, _)| &**k).collect::<Vec<_>>(),
+            vec!["+Der", "ij"]
+        );
+
+        assert_eq!(input_symbols("bijl", &chars, &multichar), vec![1, 5, 4]);
+        assert_eq!(input_symbols("jib", &chars, &multichar), vec![3, 2, 1]);
+        assert_eq!(input_symbols("bi+Der", &chars, &multichar), vec![1, 2, 7]);
+        assert_eq!(input_symbols("bxl", &chars, &multichar), vec![1, 4]);
+        assert_eq!(input_symbols("bijl", &chars, &[]), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn check_matches_is_correct_and_suggest() {
+        let speller = test_speller(&[("divun", "divvun", 2)], &["divvun"]);
+        let config = SpellerConfig::default();
+
+        for word in &["divvun", "Divun", "DIVUN", "xyz"] {
+            let check = speller.clone().check(word, &config);
+            assert_eq!(check.correct, speller.clone().is_correct(word));
+            assert_eq!(check.correct, speller.clone().score(word).is_some());
+            assert_eq!(
+                check.suggestions,
+                speller.clone().suggest_with_config(word, &config)
+            );
+        }
+
+        let check = speller.check("Divun", &config);
+        assert!(!check.correct);
+        assert_eq!(check.suggestions[0].value(), "Divvun");
+    }
+
+    #[test]
+    fn max_edit_distance_prunes_paths() {
+        let speller = test_speller(
+            &[("divun", "divvun", 2), ("dvvun", "divvun", 2)],
+            &["divvun"],
+        );
+
+        let within = |edits: Option<u8>, word: &str| {
+            let config = SpellerConfig {
+                max_edit_distance: edits,
+                ..SpellerConfig::default()
+            };
+            speller
+                .clone()
+                .suggest_with_config(word, &config)
+                .iter()
+                .any(|s| s.value() == "divvun")
+        };
+
+        assert!(within(None, "divun"));
+        assert!(within(Some(1), "divun"));
+        assert!(!within(Some(0), "divun"));
+        assert!(within(None, "dvun"));
+        assert!(!within(Some(1), "dvun"));
+    }
+
+    #[test]
+    fn capitals_by_place_in_sentence() {
+        let speller = test_speller(&[("divun", "divvun", 2)], &["dat", "lea", "Bergen"]);
+
+        let text = "Dat lea Bergen. Lea Dat bergen.\nDat Lea";
+        let found: Vec<&str> = speller
+            .clone()
+            .check_text(text)
+            .into_iter()
+            .map(|(_, word)| word)
+            .collect();
+        assert_eq!(found, vec!["Dat", "bergen"]);
+
+        let issues = speller
+            .clone()
+            .check_text_with_config(text, &SpellerConfig::default());
+        assert_eq!(issues[0].word, "Dat");
+        assert_eq!(issues[0].suggestions[0].value(), "dat");
+
+        // A searcher standing in for a cache sees the name search too, and
+        // the lower case form still comes first.
+        let mut asked = vec![];
+        let issues = speller.clone().check_text_until(
+            text,
+            &SpellerConfig::default(),
+            None,
+            |word, config| {
+                asked.push((word.to_string(), config.proper_nouns));
+                vec![]
+            },
+        );
+        assert_eq!(asked[0], ("Dat".to_string(), true));
+        assert_eq!(issues[0].suggestions[0].value(), "dat");
+
+        let past = Instant::now();
+        let issues =
+            speller.check_text_until(text, &SpellerConfig::default(), Some(past), |_, _| {
+                panic!("searched after the deadline")
+            });
+        assert_eq!(issues.len(), 2);
+        assert!(issues.iter().all(|issue| issue.suggestions.is_empty()));
+    }
+
+    #[test]
+    fn abbreviations_accepted() {
+        let speller = test_speller(&[("divun", "divvun", 2)], &["dat", "lea", "buorre"]);
+
+        let text = "Dat lea s.b. Buorre UiT.";
+        let found = |speller: &Arc<Speller<HfstTransducer>>| {
+            speller
+                .clone()
+                .check_text(text)
+                .into_iter()
+                .map(|(_, word)| word)
+                .collect::<Vec<_>>()
+        };
+        assert!(!found(&speller).contains(&"Buorre"));
+        assert!(found(&speller).contains(&"UiT"));
+
+        speller.set_abbreviations(Abbreviations::parse("s.b.\nUiT"));
+        assert!(speller.clone().is_correct("UiT"));
+        assert_eq!(found(&speller), vec!["Buorre"]);
+    }
 }
@@ -0,0 +1,71 @@
+//! Batch lookups spread over a rayon thread pool, behind the `parallel`
+//! feature. Without it the library links no thread pool at all, and the
+//! sequential `Speller::suggest_batch` and `Speller::is_correct_batch` are
+//! what there is.
+//!
+//! Applications that manage their own threads pass a pool of theirs; with
+//! `None` the work runs on rayon's global pool.
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rayon::slice::ParallelSlice;
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use std::sync::Arc;
+
+use super::{Speller, SpellerConfig};
+use crate::speller::suggestion::Suggestion;
+use crate::transducer::Transducer;
+
+/// Words each thread takes at a time, sharing lookups within them as
+/// `suggest_many` does.
+const CHUNK_SIZE: usize = 64;
+
+/// A pool of at most `max_threads` threads, for callers that want a limit
+/// without depending on rayon themselves. Zero lets rayon choose.
+pub fn thread_pool(max_threads: usize) -> Result<ThreadPool, ThreadPoolBuildError> {
+    ThreadPoolBuilder::new().num_threads(max_threads).build()
+}
+
+fn install<R, F>(pool: Option<&ThreadPool>, f: F) -> R
+where
+    R: Send,
+    F: FnOnce() -> R + Send,
+{
+    match pool {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }
+}
+
+impl<T: Transducer + Send + Sync> Speller<T> {
+    /// Like `suggest_batch`, with the words looked up in parallel in chunks
+    /// of `CHUNK_SIZE`.
+    pub fn par_suggest_batch<S: AsRef<str> + Sync>(
+        self: Arc<Self>,
+        words: &[S],
+        config: &SpellerConfig,
+        pool: Option<&ThreadPool>,
+    ) -> Vec<Vec<Suggestion>> {
+        let chunks: Vec<Vec<Vec<Suggestion>>> = install(pool, || {
+            words
+                .par_chunks(CHUNK_SIZE)
+                .map(|chunk| self.clone().suggest_many(chunk, config))
+                .collect()
+        });
+
+        chunks.into_iter().flatten().collect()
+    }
+
+    /// Like `is_correct_batch`, with the words looked up in parallel.
+    pub fn par_is_correct_batch<S: AsRef<str> + Sync>(
+        self: Arc<Self>,
+        words: &[S],
+        pool: Option<&ThreadPool>,
+    ) -> Vec<bool> {
+        install(pool, || {
+            words
+                .par_iter()
+                .map(|word| self.clone().is_correct(word.as_ref()))
+                .collect()
+        })
+    }
+}
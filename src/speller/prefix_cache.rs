@@ -0,0 +1,167 @@
+//! Search states shared between suggestion queries for words with a common
+//! prefix, so that checking a word as it is typed ("beca", "becau",
+//! "becaus") picks up where the last keystroke's search left off.
+//!
+//! For a given input, the cache holds every node the search created by
+//! consuming the last input symbol. Any longer input with that prefix
+//! reaches its own end only through one of them, so its search can start
+//! there instead of at the beginning. The nodes are only complete when the
+//! weight limit was the fixed `max_weight` throughout; a search that fills
+//! the cache therefore prunes by `max_weight` alone until it reaches the end
+//! of its input, and gives up on caching if it finds too many nodes or runs
+//! out of time.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+
+use super::profile::InputProfile;
+use crate::sync::Mutex;
+use crate::transducer::tree_node::DetachedNode;
+use crate::types::{SymbolNumber, Weight};
+
+/// Inputs whose search is left with more nodes than this are not cached.
+pub const MAX_FRONTIER_NODES: usize = 4096;
+
+#[derive(Debug)]
+struct Entry {
+    max_weight: Option<Weight>,
+    max_edits: Option<u8>,
+    profile: Option<InputProfile>,
+    nodes: Arc<Vec<DetachedNode>>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    map: HashMap<Vec<SymbolNumber>, Entry>,
+    order: VecDeque<Vec<SymbolNumber>>,
+}
+
+/// A bounded map from input prefixes to the search nodes that consumed them.
+/// The oldest prefix is dropped first once it is full.
+#[derive(Debug, Default)]
+pub struct PrefixCache {
+    entries: Mutex<Entries>,
+}
+
+impl PrefixCache {
+    pub fn new() -> PrefixCache {
+        PrefixCache::default()
+    }
+
+    /// The nodes for the longest cached prefix of `input` that was searched
+    /// with the same `max_weight`, edit limit and input profile, and the
+    /// length of that prefix.
+    pub fn longest_prefix(
+        &self,
+        input: &[SymbolNumber],
+        max_weight: Option<Weight>,
+        max_edits: Option<u8>,
+        profile: Option<InputProfile>,
+    ) -> Option<(usize, Arc<Vec<DetachedNode>>)> {
+        let entries = self.entries.lock();
+
+        (1..=input.len()).rev().find_map(|len| {
+            entries
+                .map
+                .get(&input[..len])
+                .filter(|entry| {
+                    entry.max_weight == max_weight
+                        && entry.max_edits == max_edits
+                        && entry.profile == profile
+                })
+                .map(|entry| (len, entry.nodes.clone()))
+        })
+    }
+
+    pub fn insert(
+        &self,
+        input: Vec<SymbolNumber>,
+        max_weight: Option<Weight>,
+        max_edits: Option<u8>,
+        profile: Option<InputProfile>,
+        nodes: Vec<DetachedNode>,
+        capacity: usize,
+    ) {
+        if capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock();
+        let entry = Entry {
+            max_weight,
+            max_edits,
+            profile,
+            nodes: Arc::new(nodes),
+        };
+
+        if entries.map.insert(input.clone(), entry).is_none() {
+            entries.order.push_back(input);
+        }
+
+        while entries.order.len() > capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.map.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().map.is_empty()
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock();
+        entries.map.clear();
+        entries.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::tree_node::{NodeArena, TreeNode};
+
+    #[test]
+    fn longest_prefix_and_eviction() {
+        let mut arena = NodeArena::new();
+        arena.reset(0);
+        let node = arena.detach(&TreeNode::empty());
+
+        let cache = PrefixCache::new();
+        cache.insert(vec![1], Some(10.0), None, None, vec![node.clone()], 2);
+        cache.insert(
+            vec![1, 2],
+            Some(10.0),
+            None,
+            None,
+            vec![node.clone(), node.clone()],
+            2,
+        );
+
+        let (len, nodes) = cache
+            .longest_prefix(&[1, 2, 3], Some(10.0), None, None)
+            .unwrap();
+        assert_eq!(len, 2);
+        assert_eq!(nodes.len(), 2);
+        assert!(cache.longest_prefix(&[1, 2, 3], None, None, None).is_none());
+        assert!(cache
+            .longest_prefix(&[1, 2, 3], Some(10.0), None, Some(InputProfile::Ocr))
+            .is_none());
+        assert!(cache
+            .longest_prefix(&[1, 2, 3], Some(10.0), Some(1), None)
+            .is_none());
+        assert!(cache.longest_prefix(&[2], Some(10.0), None, None).is_none());
+
+        cache.insert(vec![3], Some(10.0), None, None, vec![node], 2);
+        assert_eq!(cache.len(), 2);
+        assert!(cache
+            .longest_prefix(&[1, 9], Some(10.0), None, None)
+            .is_none());
+    }
+}
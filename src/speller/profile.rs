@@ -0,0 +1,233 @@
+//! Error model profiles for the ways text gets typed in, so that one
+//! archive's error model can serve a touch keyboard, a physical one, OCR
+//! output and dictation alike.
+//!
+//! A profile is chosen per query with `SpellerConfig::input_profile`, or
+//! with `SpellerConfig::with_profile`, which also adjusts how candidates are
+//! searched for. While searching, every error model transition is given an
+//! extra weight by the kind of edit it makes, seen from the typo's side: a
+//! letter missing, a letter extra, or a letter replaced, where replacements
+//! the profile considers close (neighbouring keys, similar glyphs, similar
+//! sounds) are cheaper than others. Edits the error model spells over
+//! several transitions, such as transpositions, are weighted per part.
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::SpellerConfig;
+use crate::types::Weight;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputProfile {
+    /// Neighbouring keys are hit often and double taps are common.
+    TouchKeyboard,
+    /// Neighbouring keys are hit now and then.
+    PhysicalKeyboard,
+    /// Letters are mistaken for ones that look alike and lose diacritics.
+    Ocr,
+    /// Whole words are heard right or wrong, and letters are mistaken for
+    /// ones that sound alike.
+    Dictation,
+}
+
+/// Weights an `InputProfile` adds to the error model's, by kind of edit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EditPenalties {
+    /// The typo lacks a letter the word has.
+    pub deletion: Weight,
+    /// The typo has a letter the word doesn't.
+    pub insertion: Weight,
+    /// A letter replaced by one the profile considers close.
+    pub close_substitution: Weight,
+    /// Any other letter replaced.
+    pub substitution: Weight,
+}
+
+const QWERTY: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+/// Letters that OCR takes for one another, in either direction.
+const LOOKALIKES: &[(char, char)] = &[
+    ('i', 'l'),
+    ('l', '1'),
+    ('i', '1'),
+    ('o', '0'),
+    ('o', 'c'),
+    ('c', 'e'),
+    ('u', 'v'),
+    ('n', 'm'),
+    ('h', 'b'),
+    ('a', 'á'),
+    ('c', 'č'),
+    ('d', 'đ'),
+    ('n', 'ŋ'),
+    ('s', 'š'),
+    ('t', 'ŧ'),
+    ('z', 'ž'),
+];
+
+/// Letters a dictation engine takes for one another, in either direction.
+const SOUNDALIKES: &[(char, char)] = &[
+    ('c', 'k'),
+    ('c', 's'),
+    ('s', 'z'),
+    ('f', 'v'),
+    ('b', 'p'),
+    ('d', 't'),
+    ('g', 'k'),
+    ('i', 'y'),
+    ('i', 'e'),
+    ('o', 'u'),
+    ('a', 'á'),
+    ('c', 'č'),
+    ('s', 'š'),
+    ('z', 'ž'),
+];
+
+fn single_char(symbol: &str) -> Option<char> {
+    let mut chars = symbol.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+fn key_position(ch: char) -> Option<(isize, isize)> {
+    QWERTY.iter().enumerate().find_map(|(row, keys)| {
+        keys.chars()
+            .position(|k| k == ch)
+            .map(|col| (row as isize, col as isize))
+    })
+}
+
+/// Whether two keys touch on a QWERTY layout, rows shifted as in
+/// `errmodel::typos::Keyboard`.
+fn is_neighbour_key(a: char, b: char) -> bool {
+    match (key_position(a), key_position(b)) {
+        (Some((ar, ac)), Some((br, bc))) => match br - ar {
+            0 => (ac - bc).abs() == 1,
+            -1 => bc == ac || bc == ac + 1,
+            1 => bc == ac || bc == ac - 1,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_pair(pairs: &[(char, char)], a: char, b: char) -> bool {
+    pairs
+        .iter()
+        .any(|&(x, y)| (x == a && y == b) || (x == b && y == a))
+}
+
+impl InputProfile {
+    pub fn penalties(self) -> EditPenalties {
+        match self {
+            InputProfile::TouchKeyboard => EditPenalties {
+                deletion: 1.0,
+                insertion: 0.5,
+                close_substitution: 0.0,
+                substitution: 2.0,
+            },
+            InputProfile::PhysicalKeyboard => EditPenalties {
+                deletion: 0.5,
+                insertion: 0.5,
+                close_substitution: 0.0,
+                substitution: 1.0,
+            },
+            InputProfile::Ocr => EditPenalties {
+                deletion: 3.0,
+                insertion: 2.0,
+                close_substitution: 0.0,
+                substitution: 3.0,
+            },
+            InputProfile::Dictation => EditPenalties {
+                deletion: 1.0,
+                insertion: 1.0,
+                close_substitution: 0.0,
+                substitution: 3.0,
+            },
+        }
+    }
+
+    /// Whether this kind of input makes `a` out of `b`, or `b` out of `a`,
+    /// more often than other replacements. Case is ignored.
+    pub fn is_close(self, a: char, b: char) -> bool {
+        let (a, b) = (lower(a), lower(b));
+
+        match self {
+            InputProfile::TouchKeyboard | InputProfile::PhysicalKeyboard => is_neighbour_key(a, b),
+            InputProfile::Ocr => is_pair(LOOKALIKES, a, b),
+            InputProfile::Dictation => is_pair(SOUNDALIKES, a, b),
+        }
+    }
+
+    /// The weight added to an error model transition from the symbol
+    /// `input` to `output`, either `None` for an epsilon.
+    pub fn penalty(self, input: Option<&str>, output: Option<&str>) -> Weight {
+        let penalties = self.penalties();
+
+        match (input, output) {
+            (None, None) => 0.0,
+            (None, Some(_)) => penalties.deletion,
+            (Some(_), None) => penalties.insertion,
+            (Some(input), Some(output)) if input == output => 0.0,
+            (Some(input), Some(output)) => match (single_char(input), single_char(output)) {
+                (Some(a), Some(b)) if self.is_close(a, b) => penalties.close_substitution,
+                _ => penalties.substitution,
+            },
+        }
+    }
+
+    /// `config` with this profile set, and its search adjusted to suit:
+    /// touch typing and dictation produce more varied errors, so their beam
+    /// adapts to how many candidates turn up, and OCR mangles case, so its
+    /// case variants are always tried.
+    pub fn configure(self, config: &SpellerConfig) -> SpellerConfig {
+        let mut config = SpellerConfig {
+            input_profile: Some(self),
+            ..config.clone()
+        };
+
+        match self {
+            InputProfile::TouchKeyboard => config.adaptive_beam = true,
+            InputProfile::PhysicalKeyboard => {}
+            InputProfile::Ocr => config.with_caps = true,
+            InputProfile::Dictation => {
+                config.adaptive_beam = true;
+                config.beam_max = config.beam_max.max(60.0);
+            }
+        }
+
+        config
+    }
+}
+
+/// Lower cases the letters that have a single lower case form.
+fn lower(ch: char) -> char {
+    let mut lower = ch.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(c), None) => c,
+        _ => ch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn penalties_by_edit() {
+        let touch = InputProfile::TouchKeyboard;
+        assert_eq!(touch.penalty(Some("s"), Some("a")), 0.0);
+        assert_eq!(touch.penalty(Some("S"), Some("d")), 0.0);
+        assert_eq!(touch.penalty(Some("s"), Some("p")), 2.0);
+        assert_eq!(touch.penalty(Some("s"), Some("s")), 0.0);
+        assert_eq!(touch.penalty(None, Some("s")), 1.0);
+        assert_eq!(touch.penalty(Some("s"), None), 0.5);
+
+        assert!(InputProfile::Ocr.is_close('č', 'c'));
+        assert!(!InputProfile::Ocr.is_close('s', 'a'));
+        assert!(InputProfile::Dictation.is_close('v', 'f'));
+        assert!(is_neighbour_key('g', 'b') && !is_neighbour_key('g', 'n'));
+    }
+}
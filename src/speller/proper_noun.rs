@@ -0,0 +1,68 @@
+//! Suggestions for words that are likely names.
+//!
+//! A word capitalized in the middle of a sentence is most likely a name, and
+//! its suggestions should be names too: "Bergn" is more likely "Bergen" than
+//! "berg". Only the client knows where sentences start, so the mode is
+//! turned on per query with `SpellerConfig::proper_nouns`, for words that
+//! aren't sentence-initial, and it only changes anything for words that are
+//! capitalized.
+//!
+//! In this mode a capitalized word is searched for as written first, so that
+//! its suggestions keep the case the lexicon gives them instead of being
+//! lower cased, looked up and capitalized again; its case variants are only
+//! searched if that finds nothing. Lexicons whose entries carry tag symbols,
+//! multicharacter symbols starting with `+` that are written with an input
+//! epsilon, also have suggestions that pass no `+Prop` tag weighed down by
+//! `PROPER_NOUN_PREFERENCE`. In this mode tags are left out of suggestions'
+//! text; otherwise suggestions have them as the lexicon writes them.
+
+use crate::types::Weight;
+
+/// The tag of proper noun entries, as in Giella lexicons.
+pub const PROPER_NOUN_TAG: &str = "+Prop";
+
+/// Added to the weight of suggestions that aren't tagged as proper nouns,
+/// about what the error model gives one uncommon edit.
+pub const PROPER_NOUN_PREFERENCE: Weight = 10.0;
+
+/// Whether the lexicon key `key` is a tag rather than text.
+pub fn is_tag(key: &str) -> bool {
+    key.len() > 1 && key.starts_with('+')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speller::{ArcSpeller, SpellerConfig};
+    use crate::transducer::{test_speller, test_tagged_speller, HfstTransducer};
+
+    #[test]
+    fn names_keep_their_case() {
+        assert!(is_tag(PROPER_NOUN_TAG));
+        assert!(!is_tag("+"));
+        assert!(!is_tag("á"));
+
+        let pairs = [("bergr", "bergn", 2)];
+        let speller = test_speller(&pairs, &["Bergen", "berger"]);
+        let tagged =
+            test_tagged_speller(&pairs, &[("Bergen", &["+N", "+Prop"]), ("berger", &["+N"])]);
+
+        let values = |speller: &ArcSpeller<HfstTransducer>, proper_nouns: bool| {
+            let config = SpellerConfig {
+                proper_nouns,
+                ..SpellerConfig::default()
+            };
+            speller
+                .clone()
+                .suggest_with_config("Berger", &config)
+                .into_iter()
+                .map(|s| s.value)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(values(&speller, false)[0], "Berger");
+        assert_eq!(values(&speller, true), vec!["Bergen"]);
+        assert_eq!(values(&tagged, false)[0], "Berger+N");
+        assert_eq!(values(&tagged, true), vec!["Bergen"]);
+    }
+}
@@ -0,0 +1,344 @@
+//! Recording what a suggestion search decides at every node, so that a
+//! change in suggestion order between two crate versions or configs can be
+//! traced to the first node they handled differently.
+//!
+//! `Speller::suggest_traced` runs a query as `suggest_with_config` would and
+//! returns a `SearchTrace`: one step per node taken off the stack, per
+//! correction found and per suggestion returned. With `std` a trace is
+//! written as plain text, one step per line, so two traces can be compared
+//! with `diff` or with `SearchTrace::diff`. `Speller::replay` runs a saved
+//! trace's query again.
+//!
+//! Anything that isn't reproducible is turned off for a traced query: the
+//! time budget and the prefix cache. The memory budget is kept, and a
+//! search it stops records where.
+
+use alloc::vec::Vec;
+use smol_str::SmolStr;
+
+use crate::speller::SpellerConfig;
+use crate::transducer::tree_node::TreeNode;
+use crate::types::{TransitionTableIndex, Weight};
+
+/// Written first in every trace, with the format version.
+#[cfg(feature = "std")]
+const MAGIC: &str = "divvunspell-trace 1";
+
+/// What the search did with a node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    /// Pruned: its weight is over the limit at the time.
+    OverLimit { limit: Weight },
+    /// Skipped by `SpellerConfig::seen_nodes`.
+    Seen,
+    /// Expanded, pushing this many nodes.
+    Expanded { children: usize },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    /// The search of a case variant starts.
+    Variant(SmolStr),
+    Node {
+        input_state: u32,
+        mutator_state: TransitionTableIndex,
+        lexicon_state: TransitionTableIndex,
+        weight: Weight,
+        outcome: Outcome,
+    },
+    /// The last node reached final states in both transducers, with this
+    /// weight, and was kept if it was under `limit`.
+    Final { weight: Weight, limit: Weight },
+    /// A new correction, or a cheaper weight for one already found.
+    Correction(SmolStr, Weight),
+    /// The memory budget stopped the search.
+    OverMemory,
+    /// One of the suggestions the query returned, in order.
+    Suggestion(SmolStr, Weight),
+}
+
+impl Step {
+    pub(crate) fn node(node: &TreeNode, outcome: Outcome) -> Step {
+        Step::Node {
+            input_state: node.input_state,
+            mutator_state: node.mutator_state,
+            lexicon_state: node.lexicon_state,
+            weight: node.weight(),
+            outcome,
+        }
+    }
+}
+
+/// Where two traces part ways, from `SearchTrace::diff`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    /// Index of the first step that differs.
+    pub step: usize,
+    /// That step in the trace `diff` was called on, if it has that many.
+    pub expected: Option<Step>,
+    /// That step in the other trace.
+    pub found: Option<Step>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchTrace {
+    pub word: SmolStr,
+    /// The config the query ran with, as adjusted for tracing.
+    pub config: SpellerConfig,
+    pub steps: Vec<Step>,
+}
+
+impl SearchTrace {
+    /// The first step at which `other` differs from this trace, or `None`
+    /// if they are the same. The headers aren't compared.
+    pub fn diff(&self, other: &SearchTrace) -> Option<Divergence> {
+        let len = self.steps.len().max(other.steps.len());
+
+        (0..len)
+            .find(|i| self.steps.get(*i) != other.steps.get(*i))
+            .map(|step| Divergence {
+                step,
+                expected: self.steps.get(step).cloned(),
+                found: other.steps.get(step).cloned(),
+            })
+    }
+
+    /// The suggestions the query returned.
+    pub fn suggestions(&self) -> Vec<(SmolStr, Weight)> {
+        self.steps
+            .iter()
+            .filter_map(|step| match step {
+                Step::Suggestion(value, weight) => Some((value.clone(), *weight)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// `config` with whatever would make a query unrepeatable turned off.
+pub(crate) fn traced_config(config: &SpellerConfig) -> SpellerConfig {
+    SpellerConfig {
+        time_budget_ms: None,
+        prefix_cache: None,
+        ..config.clone()
+    }
+}
+
+#[cfg(feature = "std")]
+mod text {
+    use std::fmt;
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[derive(Debug)]
+    pub enum TraceError {
+        NotATrace,
+        UnsupportedVersion(String),
+        Config(serde_json::Error),
+        BadStep { line: usize, text: String },
+    }
+
+    impl std::error::Error for TraceError {}
+
+    impl fmt::Display for TraceError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                TraceError::NotATrace => write!(f, "not a search trace"),
+                TraceError::UnsupportedVersion(v) => write!(f, "unsupported trace version: {}", v),
+                TraceError::Config(e) => write!(f, "bad config: {}", e),
+                TraceError::BadStep { line, text } => {
+                    write!(f, "line {}: bad step: {}", line, text)
+                }
+            }
+        }
+    }
+
+    impl fmt::Display for Step {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Step::Variant(word) => write!(f, "v {}", word),
+                Step::Node {
+                    input_state,
+                    mutator_state,
+                    lexicon_state,
+                    weight,
+                    outcome,
+                } => {
+                    let (code, extra) = match outcome {
+                        Outcome::OverLimit { limit } => ('o', format!(" {}", limit)),
+                        Outcome::Seen => ('s', String::new()),
+                        Outcome::Expanded { children } => ('e', format!(" {}", children)),
+                    };
+                    write!(
+                        f,
+                        "{} {} {} {} {}{}",
+                        code, input_state, mutator_state, lexicon_state, weight, extra
+                    )
+                }
+                Step::Final { weight, limit } => write!(f, "f {} {}", weight, limit),
+                Step::Correction(value, weight) => write!(f, "c {} {}", weight, value),
+                Step::OverMemory => write!(f, "m"),
+                Step::Suggestion(value, weight) => write!(f, "= {} {}", weight, value),
+            }
+        }
+    }
+
+    fn parse_step(line: &str) -> Option<Step> {
+        let mut fields = line.splitn(2, ' ');
+        let code = fields.next()?;
+        let rest = fields.next().unwrap_or("");
+
+        // Weighted values put the value last, as it may contain spaces.
+        let weighted = |rest: &str| -> Option<(SmolStr, Weight)> {
+            let mut parts = rest.splitn(2, ' ');
+            let weight = parts.next()?.parse().ok()?;
+            Some((SmolStr::from(parts.next().unwrap_or("")), weight))
+        };
+
+        let numbers: Vec<&str> = rest.split(' ').collect();
+        let number = |i: usize| numbers.get(i).and_then(|x| u32::from_str(x).ok());
+        let weight = |i: usize| numbers.get(i).and_then(|x| Weight::from_str(x).ok());
+        let node = |outcome| {
+            Some(Step::Node {
+                input_state: number(0)?,
+                mutator_state: number(1)?,
+                lexicon_state: number(2)?,
+                weight: weight(3)?,
+                outcome,
+            })
+        };
+
+        match code {
+            "v" => Some(Step::Variant(SmolStr::from(rest))),
+            "o" => node(Outcome::OverLimit { limit: weight(4)? }),
+            "s" => node(Outcome::Seen),
+            "e" => node(Outcome::Expanded {
+                children: numbers.get(4)?.parse().ok()?,
+            }),
+            "f" => Some(Step::Final {
+                weight: weight(0)?,
+                limit: weight(1)?,
+            }),
+            "c" => weighted(rest).map(|(value, weight)| Step::Correction(value, weight)),
+            "m" => Some(Step::OverMemory),
+            "=" => weighted(rest).map(|(value, weight)| Step::Suggestion(value, weight)),
+            _ => None,
+        }
+    }
+
+    impl SearchTrace {
+        /// Reads a trace written with `Display`.
+        pub fn parse(text: &str) -> Result<SearchTrace, TraceError> {
+            let mut lines = text.lines();
+
+            match lines.next() {
+                Some(MAGIC) => {}
+                Some(line) if line.starts_with("divvunspell-trace ") => {
+                    return Err(TraceError::UnsupportedVersion(line[18..].to_string()));
+                }
+                _ => return Err(TraceError::NotATrace),
+            }
+
+            let word = match lines.next() {
+                Some(line) if line.starts_with("word ") => SmolStr::from(&line[5..]),
+                _ => return Err(TraceError::NotATrace),
+            };
+
+            let config = match lines.next() {
+                Some(line) if line.starts_with("config ") => {
+                    serde_json::from_str(&line[7..]).map_err(TraceError::Config)?
+                }
+                _ => return Err(TraceError::NotATrace),
+            };
+
+            let steps = lines
+                .enumerate()
+                .filter(|(_, line)| !line.is_empty())
+                .map(|(i, line)| {
+                    parse_step(line).ok_or_else(|| TraceError::BadStep {
+                        line: i + 4,
+                        text: line.to_string(),
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(SearchTrace {
+                word,
+                config,
+                steps,
+            })
+        }
+    }
+
+    impl fmt::Display for SearchTrace {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            writeln!(f, "{}", MAGIC)?;
+            writeln!(f, "word {}", self.word)?;
+            writeln!(
+                f,
+                "config {}",
+                serde_json::to_string(&self.config).map_err(|_| fmt::Error)?
+            )?;
+
+            for step in &self.steps {
+                writeln!(f, "{}", step)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub use self::text::TraceError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip() {
+        let trace = SearchTrace {
+            word: SmolStr::from("gielaa"),
+            config: traced_config(&SpellerConfig::default()),
+            steps: vec![
+                Step::Variant(SmolStr::from("gielaa")),
+                Step::Node {
+                    input_state: 0,
+                    mutator_state: 0,
+                    lexicon_state: 0,
+                    weight: 0.0,
+                    outcome: Outcome::Expanded { children: 12 },
+                },
+                Step::Node {
+                    input_state: 2,
+                    mutator_state: 31,
+                    lexicon_state: 502,
+                    weight: 7.25,
+                    outcome: Outcome::OverLimit { limit: 6.5 },
+                },
+                Step::Final {
+                    weight: 10.2,
+                    limit: 3.4028235e38,
+                },
+                Step::Correction(SmolStr::from("giella ja"), 10.2),
+                Step::Suggestion(SmolStr::from("giella ja"), 10.2),
+            ],
+        };
+
+        let text = trace.to_string();
+        assert!(text.contains("\no 2 31 502 7.25 6.5\n"));
+        assert!(text.contains("\nc 10.2 giella ja\n"));
+
+        let parsed = SearchTrace::parse(&text).unwrap();
+        assert_eq!(parsed.word, trace.word);
+        assert_eq!(trace.diff(&parsed), None);
+
+        let mut changed = parsed.clone();
+        changed.steps.truncate(3);
+        let divergence = trace.diff(&changed).unwrap();
+        assert_eq!(divergence.step, 3);
+        assert_eq!(divergence.found, None);
+    }
+}
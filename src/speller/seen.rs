@@ -0,0 +1,242 @@
+//! Tracking of expanded search nodes, so that a node reached a second time
+//! by another path is not expanded again.
+//!
+//! Nodes are identified by `NodeArena::hash`. How they are remembered is
+//! chosen with `SpellerConfig::seen_nodes`.
+
+#[cfg(feature = "std")]
+use core::f64::consts::LN_2;
+use hashbrown::HashMap;
+use serde_derive::{Deserialize, Serialize};
+
+use super::SpellerConfig;
+use crate::types::Weight;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeenNodes {
+    /// Expand every node, duplicates included.
+    #[default]
+    Off,
+    /// Remember one in `seen_node_sample_rate` expanded nodes, with its
+    /// weight. A duplicate is only skipped if it is no cheaper, so results
+    /// are unchanged; the sampling bounds the memory used.
+    Sampled,
+    /// Remember every expanded node in a bloom filter. Memory stays small
+    /// on very large searches, but a duplicate is skipped even if it is
+    /// cheaper, and a false positive (at `seen_false_positive_rate`) skips
+    /// a node that was never expanded. Without `std`, which has no
+    /// logarithms to size the filter with, this is `Sampled` instead.
+    Bloom,
+}
+
+/// Items the first bloom filter layer is sized for. Each further layer is
+/// twice the size of the last.
+#[cfg(feature = "std")]
+const BLOOM_INITIAL_CAPACITY: usize = 4096;
+
+/// How much tighter each layer's false positive rate is than the last's,
+/// so that the rate over all layers stays under twice the configured one.
+#[cfg(feature = "std")]
+const BLOOM_TIGHTENING: f64 = 0.5;
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct BloomLayer {
+    bits: Vec<u64>,
+    hashes: u32,
+    capacity: usize,
+    len: usize,
+}
+
+#[cfg(feature = "std")]
+impl BloomLayer {
+    fn new(capacity: usize, false_positive_rate: f64) -> BloomLayer {
+        let bits = (-(capacity as f64) * false_positive_rate.ln() / (LN_2 * LN_2)).ceil();
+        let words = (bits as usize).div_ceil(64);
+        let hashes = ((words * 64) as f64 / capacity as f64 * LN_2)
+            .round()
+            .max(1.0);
+
+        BloomLayer {
+            bits: vec![0; words.max(1)],
+            hashes: hashes as u32,
+            capacity,
+            len: 0,
+        }
+    }
+
+    /// Double hashing: the `i`th probe is `h1 + i * h2`.
+    #[inline(always)]
+    fn probe(&self, hash: u64, i: u32) -> (usize, u64) {
+        let bit_count = self.bits.len() as u64 * 64;
+        let h1 = hash & 0xffff_ffff;
+        let h2 = (hash >> 32) | 1;
+        let bit = h1.wrapping_add((i as u64).wrapping_mul(h2)) % bit_count;
+
+        ((bit / 64) as usize, 1 << (bit % 64))
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        (0..self.hashes).all(|i| {
+            let (word, mask) = self.probe(hash, i);
+            self.bits[word] & mask != 0
+        })
+    }
+
+    fn insert(&mut self, hash: u64) {
+        for i in 0..self.hashes {
+            let (word, mask) = self.probe(hash, i);
+            self.bits[word] |= mask;
+        }
+        self.len += 1;
+    }
+}
+
+/// A bloom filter that adds a larger layer whenever the last one is full,
+/// since how many nodes a search expands isn't known up front.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ScalableBloom {
+    layers: Vec<BloomLayer>,
+    false_positive_rate: f64,
+}
+
+#[cfg(feature = "std")]
+impl ScalableBloom {
+    pub fn new(false_positive_rate: f64) -> ScalableBloom {
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+
+        ScalableBloom {
+            layers: vec![BloomLayer::new(
+                BLOOM_INITIAL_CAPACITY,
+                rate * BLOOM_TIGHTENING,
+            )],
+            false_positive_rate: rate,
+        }
+    }
+
+    pub fn contains(&self, hash: u64) -> bool {
+        self.layers.iter().any(|layer| layer.contains(hash))
+    }
+
+    /// Adds `hash`, returning false if it may already have been present.
+    pub fn insert(&mut self, hash: u64) -> bool {
+        if self.contains(hash) {
+            return false;
+        }
+
+        let full = {
+            let last = self.layers.last().unwrap();
+            last.len >= last.capacity
+        };
+
+        if full {
+            let n = self.layers.len() as i32 + 1;
+            let capacity = BLOOM_INITIAL_CAPACITY << (n - 1);
+            let rate = self.false_positive_rate * BLOOM_TIGHTENING.powi(n);
+            self.layers.push(BloomLayer::new(capacity, rate));
+        }
+
+        self.layers.last_mut().unwrap().insert(hash);
+        true
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.layers.iter().map(|layer| layer.bits.len() * 8).sum()
+    }
+}
+
+/// The seen-set of one search.
+#[derive(Debug)]
+pub enum SeenSet {
+    Off,
+    Sampled {
+        seen: HashMap<u64, Weight>,
+        rate: u64,
+        count: u64,
+    },
+    #[cfg(feature = "std")]
+    Bloom(ScalableBloom),
+}
+
+impl SeenSet {
+    pub fn new(config: &SpellerConfig) -> SeenSet {
+        match config.seen_nodes {
+            SeenNodes::Off => SeenSet::Off,
+            #[cfg(feature = "std")]
+            SeenNodes::Bloom => SeenSet::Bloom(ScalableBloom::new(config.seen_false_positive_rate)),
+            _ => SeenSet::Sampled {
+                seen: HashMap::new(),
+                rate: config.seen_node_sample_rate.max(1),
+                count: 0,
+            },
+        }
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        match self {
+            SeenSet::Off => 0,
+            SeenSet::Sampled { seen, .. } => {
+                seen.capacity() * core::mem::size_of::<(u64, Weight)>()
+            }
+            #[cfg(feature = "std")]
+            SeenSet::Bloom(bloom) => bloom.memory_bytes(),
+        }
+    }
+
+    /// Whether a node with this weight should be expanded. Records it as
+    /// seen if so, subject to sampling. `hash` is only called when needed.
+    pub fn visit<F: FnOnce() -> u64>(&mut self, hash: F, weight: Weight) -> bool {
+        match self {
+            SeenSet::Off => true,
+            SeenSet::Sampled { seen, rate, count } => {
+                let hash = hash();
+
+                if let Some(seen_weight) = seen.get(&hash) {
+                    if *seen_weight <= weight {
+                        return false;
+                    }
+                }
+
+                *count += 1;
+                if *count % *rate == 0 {
+                    seen.insert(hash, weight);
+                }
+
+                true
+            }
+            #[cfg(feature = "std")]
+            SeenSet::Bloom(bloom) => bloom.insert(hash()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bloom_false_positive_rate() {
+        let mut bloom = ScalableBloom::new(0.01);
+
+        // Spread the hashes out the way `NodeArena::hash` would.
+        let hash = |i: u64| i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+
+        let rejected = (0..20_000).filter(|i| !bloom.insert(hash(*i))).count();
+        assert!(rejected < 400, "{} rejected", rejected);
+        for i in 0..20_000 {
+            assert!(bloom.contains(hash(i)));
+        }
+
+        let false_positives = (20_000..120_000)
+            .filter(|i| bloom.contains(hash(*i)))
+            .count();
+        assert!(
+            false_positives < 2_000,
+            "{} false positives",
+            false_positives
+        );
+        assert!(bloom.memory_bytes() < 64 * 1024);
+    }
+}
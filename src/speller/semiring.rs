@@ -0,0 +1,108 @@
+//! How weights along a path and across paths are combined.
+//!
+//! Weights are negative log probabilities. Along a path both semirings add
+//! them. Where several paths spell the same suggestion, the tropical
+//! semiring keeps the cheapest, as HFST does; the log semiring adds up their
+//! probabilities, which some error models are trained to expect, so that a
+//! correction with many likely edit paths ranks above one with a single
+//! slightly cheaper path.
+//!
+//! Chosen with `SpellerConfig::semiring`. Only paths the search actually
+//! finds are added up: nodes pruned by the weight limits, and duplicates
+//! skipped by `SpellerConfig::seen_nodes`, are not counted, so log weights
+//! are best searched with `SeenNodes::Off`.
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::types::Weight;
+
+pub trait Semiring {
+    /// The weight of a path made of a path weighing `a` followed by one
+    /// weighing `b`.
+    fn times(&self, a: Weight, b: Weight) -> Weight;
+
+    /// The weight of a suggestion reached by paths weighing `a` and `b`.
+    fn plus(&self, a: Weight, b: Weight) -> Weight;
+}
+
+/// Min-plus: a suggestion weighs what its cheapest path does.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tropical;
+
+impl Semiring for Tropical {
+    fn times(&self, a: Weight, b: Weight) -> Weight {
+        a + b
+    }
+
+    fn plus(&self, a: Weight, b: Weight) -> Weight {
+        a.min(b)
+    }
+}
+
+/// Log-plus: a suggestion's probability is the sum of its paths'. Without
+/// `std`, which has no logarithms, this is `Tropical` instead.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Log;
+
+impl Semiring for Log {
+    fn times(&self, a: Weight, b: Weight) -> Weight {
+        a + b
+    }
+
+    #[cfg(feature = "std")]
+    fn plus(&self, a: Weight, b: Weight) -> Weight {
+        let (min, max) = if a < b { (a, b) } else { (b, a) };
+        if max.is_infinite() {
+            return min;
+        }
+
+        // -ln(e^-a + e^-b), arranged not to underflow for large weights.
+        min - (min - max).exp().ln_1p()
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn plus(&self, a: Weight, b: Weight) -> Weight {
+        Tropical.plus(a, b)
+    }
+}
+
+/// Which semiring a search uses, as a config value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WeightSemiring {
+    #[default]
+    Tropical,
+    Log,
+}
+
+impl Semiring for WeightSemiring {
+    fn times(&self, a: Weight, b: Weight) -> Weight {
+        match self {
+            WeightSemiring::Tropical => Tropical.times(a, b),
+            WeightSemiring::Log => Log.times(a, b),
+        }
+    }
+
+    fn plus(&self, a: Weight, b: Weight) -> Weight {
+        match self {
+            WeightSemiring::Tropical => Tropical.plus(a, b),
+            WeightSemiring::Log => Log.plus(a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plus() {
+        assert_eq!(Tropical.plus(2.0, 3.5), 2.0);
+
+        let two_paths = Log.plus(4.0, 4.0);
+        assert!((two_paths - (4.0 - core::f32::consts::LN_2)).abs() < 1e-6);
+        assert!(Log.plus(2.0, 3.5) < 2.0);
+        assert_eq!(Log.plus(2.0, f32::INFINITY), 2.0);
+        assert_eq!(Log.plus(200.0, 200.0), Log.plus(0.0, 0.0) + 200.0);
+    }
+}
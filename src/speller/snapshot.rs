@@ -0,0 +1,258 @@
+//! What a speller works out at runtime, kept in a file so that a restarted
+//! process can skip working it out again.
+//!
+//! A `SpellerSnapshot` holds a speller's case table and, if given them, a
+//! suggestion cache's most asked for words and a user dictionary with its
+//! compiled overlay. Restoring one replaces the speller's case table with
+//! the kept one, fills an empty suggestion cache, and gives back the user
+//! dictionary without compiling it again. Search states, such as
+//! the prefix cache's, are not kept.
+//!
+//! A snapshot belongs to one archive. It records `SpellerArchive::fingerprint`
+//! and a format version, and `load` ignores one that doesn't match either,
+//! so a rebuilt archive or a newer release starts over rather than restoring
+//! state that no longer fits.
+
+use serde_derive::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use super::suggestion::Suggestion;
+use super::suggestion_cache::{config_fingerprint, SuggestionCache};
+use super::user_dictionary::UserDictionary;
+use super::user_overlay::UserOverlay;
+use super::Speller;
+use crate::tokenizer::caps::CaseTable;
+use crate::transducer::{HfstTransducer, Transducer};
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{}", e),
+            SnapshotError::Parse(e) => write!(f, "invalid snapshot: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> SnapshotError {
+        SnapshotError::Io(e)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CachedSuggestions {
+    /// The `config_fingerprint` of the cache's config.
+    config: u64,
+    entries: Vec<(SmolStr, Vec<Suggestion>)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct UserWords {
+    words: Vec<SmolStr>,
+    /// The words the overlay was compiled from, if there was one. Its
+    /// acceptor follows the rest of the snapshot in the file.
+    overlay: Option<Vec<SmolStr>>,
+}
+
+/// A snapshot is written as a line of JSON, followed by the user
+/// dictionary's compiled overlay, if it has one, as it is.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpellerSnapshot {
+    version: u32,
+    archive: u64,
+    case_table: Option<CaseTable>,
+    suggestions: Option<CachedSuggestions>,
+    user: Option<UserWords>,
+    #[serde(skip)]
+    overlay_acceptor: Vec<u8>,
+}
+
+impl SpellerSnapshot {
+    /// The state of `speller`, which belongs to the archive with the given
+    /// fingerprint. Builds its case table if it hasn't yet.
+    pub fn take<T: Transducer>(archive: u64, speller: &Speller<T>) -> SpellerSnapshot {
+        SpellerSnapshot {
+            version: SNAPSHOT_VERSION,
+            archive,
+            case_table: Some((*speller.case_table()).clone()),
+            suggestions: None,
+            user: None,
+            overlay_acceptor: vec![],
+        }
+    }
+
+    /// Keeps `cache`'s most asked for words too.
+    pub fn with_suggestions(mut self, cache: &SuggestionCache) -> SpellerSnapshot {
+        self.suggestions = Some(CachedSuggestions {
+            config: config_fingerprint(cache.config()),
+            entries: cache.most_asked(),
+        });
+        self
+    }
+
+    /// Keeps `dictionary`'s words too, and its overlay if it has one.
+    pub fn with_user_dictionary(mut self, dictionary: &UserDictionary) -> SpellerSnapshot {
+        let overlay = dictionary.overlay();
+        self.user = Some(UserWords {
+            words: dictionary.words().map(SmolStr::from).collect(),
+            overlay: overlay.map(|o| o.words().map(SmolStr::from).collect()),
+        });
+        self.overlay_acceptor = overlay.map(|o| o.acceptor().to_vec()).unwrap_or_default();
+        self
+    }
+
+    /// The fingerprint of the archive the snapshot was taken of.
+    pub fn archive(&self) -> u64 {
+        self.archive
+    }
+
+    /// Writes the snapshot to `path`, replacing the file whole, so a reader
+    /// never sees half of it.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        let mut bytes = serde_json::to_vec(self).map_err(SnapshotError::Parse)?;
+        bytes.push(b'\n');
+        bytes.extend_from_slice(&self.overlay_acceptor);
+
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, bytes)?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    /// Reads the snapshot at `path` if it was taken of the archive with the
+    /// given fingerprint. A missing file, or one taken of another archive or
+    /// by another version, gives `None`.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        archive: u64,
+    ) -> Result<Option<SpellerSnapshot>, SnapshotError> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let end = bytes
+            .iter()
+            .position(|&b| b == b'\n')
+            .unwrap_or(bytes.len());
+        let mut snapshot: SpellerSnapshot =
+            serde_json::from_slice(&bytes[..end]).map_err(SnapshotError::Parse)?;
+        if snapshot.version != SNAPSHOT_VERSION || snapshot.archive != archive {
+            return Ok(None);
+        }
+
+        snapshot.overlay_acceptor = bytes.get(end + 1..).unwrap_or_default().to_vec();
+        Ok(Some(snapshot))
+    }
+
+    /// Installs the case table in `speller`, which has to belong to the
+    /// archive the snapshot was taken of.
+    pub fn restore<T: Transducer>(&self, speller: &Speller<T>) {
+        if let Some(table) = &self.case_table {
+            speller.set_case_table(table.clone());
+        }
+    }
+
+    /// Fills `cache` with the kept suggestions if it is empty and has the
+    /// config they were kept with, returning how many words it was given.
+    pub fn restore_suggestions(&self, cache: &SuggestionCache) -> usize {
+        let kept = match &self.suggestions {
+            Some(kept) => kept,
+            None => return 0,
+        };
+        if !cache.is_empty() || kept.config != config_fingerprint(cache.config()) {
+            return 0;
+        }
+
+        for (word, suggestions) in &kept.entries {
+            cache.insert(word, suggestions.clone());
+        }
+        kept.entries.len()
+    }
+
+    /// The kept user dictionary, with its overlay searched with `mutator`,
+    /// the error model of the archive the snapshot was taken of.
+    pub fn user_dictionary(&self, mutator: &HfstTransducer) -> io::Result<Option<UserDictionary>> {
+        let user = match &self.user {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+
+        let mut dictionary = UserDictionary::new();
+        for word in &user.words {
+            dictionary.insert(word);
+        }
+        if let Some(words) = &user.overlay {
+            let overlay = UserOverlay::from_acceptor(
+                words.iter().map(|w| &**w),
+                &self.overlay_acceptor,
+                mutator,
+            )?;
+            dictionary.install(overlay);
+        }
+
+        Ok(Some(dictionary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speller::SpellerConfig;
+    use crate::transducer::test_mutator;
+
+    #[test]
+    fn saved_and_restored() {
+        let mutator = test_mutator(&[("divun", "divvun", 2)], &[]);
+
+        let mut dictionary = UserDictionary::new();
+        dictionary.insert("divvun");
+        let overlay = dictionary.compile(&mutator).unwrap();
+        dictionary.install(overlay);
+        dictionary.insert("giella");
+
+        let speller = dictionary.overlay().unwrap().speller();
+        let snapshot = SpellerSnapshot::take(42, &speller).with_user_dictionary(&dictionary);
+
+        let dir = tempdir::TempDir::new("divvunspell-snapshot").unwrap();
+        let path = dir.path().join("se.snapshot");
+        snapshot.save(&path).unwrap();
+
+        assert!(SpellerSnapshot::load(&path, 43).unwrap().is_none());
+        let loaded = SpellerSnapshot::load(&path, 42).unwrap().unwrap();
+        loaded.restore(&speller);
+
+        let restored = loaded.user_dictionary(&mutator).unwrap().unwrap();
+        assert_eq!(
+            restored.words().collect::<Vec<_>>(),
+            vec!["divvun", "giella"]
+        );
+        assert!(restored.overlay().unwrap().contains("divvun"));
+        let merged = restored.merge("divun", vec![], Some(1));
+        assert_eq!(merged[0].value(), "divvun");
+
+        let cache = SuggestionCache::open(
+            dir.path().join("se.json"),
+            42,
+            &SpellerConfig::default(),
+            10,
+        )
+        .unwrap();
+        assert_eq!(loaded.restore_suggestions(&cache), 0);
+    }
+}
@@ -0,0 +1,292 @@
+//! Checking long documents a chunk at a time, for servers that send results
+//! as they go rather than after the whole document.
+//!
+//! `Speller::check_stream` splits a document into chunks of whole sentences
+//! of about `DEFAULT_CHUNK_BYTES`, and checks each only when the stream is
+//! asked for it, so nothing runs ahead of what the client has read. Offsets
+//! in each chunk's issues are into the whole document, as if it had been
+//! checked at once.
+//!
+//! A `Backpressure` handle lets another thread pause the stream, as when
+//! the client falls behind, resume it, or cancel it; a paused stream waits
+//! in `next` until resumed, and a cancelled one ends. With a deadline,
+//! chunks started after it passes have their misspellings without
+//! suggestions, so the rest of a long document is still checked in time.
+
+use serde_derive::Serialize;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+use super::issue::SpellingIssue;
+use super::{Speller, SpellerConfig};
+use crate::tokenizer::Tokenize;
+use crate::transducer::Transducer;
+
+/// How much text is checked at a time, unless the stream is given another
+/// size.
+pub const DEFAULT_CHUNK_BYTES: usize = 64 * 1024;
+
+/// The misspellings in the document from byte `start` to `end`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CheckedChunk {
+    pub start: usize,
+    pub end: usize,
+    pub issues: Vec<SpellingIssue>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Flow {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+/// Pauses, resumes and cancels a stream from another thread. Clones control
+/// the same stream.
+#[derive(Clone, Debug)]
+pub struct Backpressure {
+    flow: Arc<(Mutex<Flow>, Condvar)>,
+}
+
+impl Default for Backpressure {
+    fn default() -> Backpressure {
+        Backpressure {
+            flow: Arc::new((Mutex::new(Flow::Running), Condvar::new())),
+        }
+    }
+}
+
+impl Backpressure {
+    pub fn new() -> Backpressure {
+        Backpressure::default()
+    }
+
+    /// Holds the stream before its next chunk. A chunk being checked is
+    /// finished.
+    pub fn pause(&self) {
+        self.set(Flow::Paused);
+    }
+
+    pub fn resume(&self) {
+        self.set(Flow::Running);
+    }
+
+    /// Ends the stream before its next chunk, even if paused.
+    pub fn cancel(&self) {
+        self.set(Flow::Cancelled);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.get() == Flow::Paused
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.get() == Flow::Cancelled
+    }
+
+    fn get(&self) -> Flow {
+        *self.flow.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn set(&self, flow: Flow) {
+        let (lock, resumed) = &*self.flow;
+        let mut current = lock.lock().unwrap_or_else(|e| e.into_inner());
+        if *current != Flow::Cancelled {
+            *current = flow;
+        }
+        resumed.notify_all();
+    }
+
+    /// Waits while paused, returning whether the stream may go on.
+    fn wait(&self) -> bool {
+        let (lock, resumed) = &*self.flow;
+        let mut flow = lock.lock().unwrap_or_else(|e| e.into_inner());
+        while *flow == Flow::Paused {
+            flow = resumed.wait(flow).unwrap_or_else(|e| e.into_inner());
+        }
+        *flow == Flow::Running
+    }
+}
+
+/// The largest char boundary of `text` at or before `i`.
+fn floor_boundary(text: &str, mut i: usize) -> usize {
+    while !text.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+/// Where the chunk starting `text` ends: at the start of the last sentence
+/// that begins within `chunk_bytes`, or failing that, after the last
+/// whitespace, so that words and, where possible, sentences aren't split.
+fn chunk_end<T: Transducer>(speller: &Speller<T>, text: &str, chunk_bytes: usize) -> usize {
+    if text.len() <= chunk_bytes {
+        return text.len();
+    }
+
+    let window = &text[..floor_boundary(text, chunk_bytes.max(1))];
+    let sentences = window.sentence_bound_indices_with(&speller.abbreviations());
+    if let Some(&(start, _)) = sentences.iter().rev().find(|&&(start, _)| start > 0) {
+        return start;
+    }
+
+    match window.rfind(char::is_whitespace) {
+        Some(i) if i > 0 => i,
+        _ => window
+            .len()
+            .max(text.chars().next().map_or(0, char::len_utf8)),
+    }
+}
+
+pub struct CheckStream<T: Transducer, S: AsRef<str>> {
+    speller: Arc<Speller<T>>,
+    text: S,
+    config: SpellerConfig,
+    chunk_bytes: usize,
+    backpressure: Option<Backpressure>,
+    deadline: Option<Instant>,
+    /// Where the next chunk starts, in bytes and in UTF-16 code units.
+    position: usize,
+    utf16_position: usize,
+}
+
+impl<T: Transducer, S: AsRef<str>> CheckStream<T, S> {
+    /// Checks about `bytes` of text at a time.
+    pub fn with_chunk_bytes(mut self, bytes: usize) -> CheckStream<T, S> {
+        self.chunk_bytes = bytes;
+        self
+    }
+
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> CheckStream<T, S> {
+        self.backpressure = Some(backpressure);
+        self
+    }
+
+    /// Stops suggesting for chunks started after `deadline`, and keeps each
+    /// suggestion query's time budget within it.
+    pub fn with_deadline(mut self, deadline: Instant) -> CheckStream<T, S> {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// How much of the document has been checked, in bytes.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<T: Transducer, S: AsRef<str>> Iterator for CheckStream<T, S> {
+    type Item = CheckedChunk;
+
+    fn next(&mut self) -> Option<CheckedChunk> {
+        let text = self.text.as_ref();
+        if self.position >= text.len() {
+            return None;
+        }
+        if let Some(backpressure) = &self.backpressure {
+            if !backpressure.wait() {
+                return None;
+            }
+        }
+
+        let rest = &text[self.position..];
+        let chunk = &rest[..chunk_end(&self.speller, rest, self.chunk_bytes)];
+
+        let now = Instant::now();
+        let mut issues = match self.deadline {
+            Some(deadline) if now >= deadline => {
+                SpellingIssue::locate(chunk, self.speller.clone().check_text(chunk))
+            }
+            Some(deadline) => {
+                let remaining = (deadline - now).as_millis() as u64;
+                let config = SpellerConfig {
+                    time_budget_ms: Some(
+                        self.config
+                            .time_budget_ms
+                            .map_or(remaining, |ms| ms.min(remaining)),
+                    ),
+                    ..self.config.clone()
+                };
+                self.speller.clone().check_text_with_config(chunk, &config)
+            }
+            None => self
+                .speller
+                .clone()
+                .check_text_with_config(chunk, &self.config),
+        };
+
+        for issue in &mut issues {
+            issue.offset += self.position;
+            issue.start += self.utf16_position;
+            issue.end += self.utf16_position;
+        }
+
+        let start = self.position;
+        self.position += chunk.len();
+        self.utf16_position += chunk.encode_utf16().count();
+
+        Some(CheckedChunk {
+            start,
+            end: self.position,
+            issues,
+        })
+    }
+}
+
+impl<T: Transducer> Speller<T> {
+    /// Checks `text` as `check_text_with_config` does, a chunk at a time as
+    /// the stream is read.
+    pub fn check_stream<S: AsRef<str>>(
+        self: Arc<Self>,
+        text: S,
+        config: &SpellerConfig,
+    ) -> CheckStream<T, S> {
+        CheckStream {
+            speller: self,
+            text,
+            config: config.clone(),
+            chunk_bytes: DEFAULT_CHUNK_BYTES,
+            backpressure: None,
+            deadline: None,
+            position: 0,
+            utf16_position: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::test_speller;
+
+    #[test]
+    fn chunks_match_whole_text() {
+        let speller = test_speller(
+            &[("giela", "giella", 2)],
+            &["mun", "lean", "giella", "sámi"],
+        );
+
+        let config = SpellerConfig::default();
+        let text = "Mun lean giela. Sámi giella 😀 lean. Mun lean sámi giellaa!";
+        let whole = speller.clone().check_text_with_config(text, &config);
+
+        let chunks: Vec<CheckedChunk> = speller
+            .clone()
+            .check_stream(text, &config)
+            .with_chunk_bytes(20)
+            .collect();
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.last().unwrap().end, text.len());
+        let streamed: Vec<SpellingIssue> = chunks.into_iter().flat_map(|c| c.issues).collect();
+        assert_eq!(streamed, whole);
+
+        let backpressure = Backpressure::new();
+        let mut stream = speller
+            .check_stream(text, &config)
+            .with_chunk_bytes(20)
+            .with_backpressure(backpressure.clone());
+        assert!(stream.next().is_some());
+        backpressure.cancel();
+        assert!(stream.next().is_none());
+    }
+}
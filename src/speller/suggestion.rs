@@ -1,9 +1,13 @@
 use crate::types::Weight;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::cmp::Ordering::Equal;
 use serde_derive::{Deserialize, Serialize};
 use smol_str::SmolStr;
-use std::cmp::Ordering;
-use std::cmp::Ordering::Equal;
 
+/// A suggested word and its weight. Cheap to clone: values of up to 22
+/// bytes are stored inline and longer ones share a single `Arc<str>`, and
+/// both serialize as plain strings, as in `{"value": "giella", "weight": 14.5}`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Suggestion {
     pub value: SmolStr,
@@ -52,3 +56,32 @@ impl PartialEq for Suggestion {
 }
 
 impl Eq for Suggestion {}
+
+/// Drops the suggestions in `sorted`, lightest first, that weigh more than
+/// `gap` past the first one.
+pub(crate) fn within_gap(sorted: &mut Vec<Suggestion>, gap: Option<Weight>) {
+    if let (Some(gap), Some(best)) = (gap, sorted.first().map(Suggestion::weight)) {
+        sorted.retain(|s| s.weight() - best <= gap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gap_from_best() {
+        let mut suggestions: Vec<Suggestion> =
+            vec![("giella", 10.0), ("gielat", 14.5), ("gealla", 30.0)]
+                .into_iter()
+                .map(|(v, w)| Suggestion::new(SmolStr::from(v), w))
+                .collect();
+
+        within_gap(&mut suggestions, None);
+        assert_eq!(suggestions.len(), 3);
+
+        within_gap(&mut suggestions, Some(5.0));
+        let values: Vec<&str> = suggestions.iter().map(Suggestion::value).collect();
+        assert_eq!(values, vec!["giella", "gielat"]);
+    }
+}
@@ -0,0 +1,542 @@
+//! Suggestions for frequent misspellings, kept on disk between runs.
+//!
+//! Real typos are Zipfian: a few hundred misspellings make up much of what a
+//! server is asked about. A `SuggestionCache` answers those from memory and
+//! is saved to a file, so that a restarted daemon or server answers them
+//! without a search from its first request.
+//!
+//! A cache file belongs to one archive and one config. It records
+//! `SpellerArchive::fingerprint` and `config_fingerprint`, and is started
+//! over if either no longer matches, so a rebuilt archive or changed setting
+//! never serves stale suggestions. Only the `capacity` most asked for words
+//! are saved, and their counts are halved on loading, so that words which
+//! stop coming up make way for new ones.
+//!
+//! Servers keep one cache per language in a directory, as `TAG.json`.
+//! `SuggestionCaches` opens each the first time its language is asked for
+//! and saves them all every so often; `CachedLanguage` holds a language's
+//! archive and cache for a request.
+
+use hashbrown::HashMap;
+use serde_derive::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::issue::SpellingIssue;
+use super::suggestion::Suggestion;
+use super::{Speller, SpellerConfig, WordCheck};
+use crate::archive::repository::{ArchiveHandle, RepositoryError, SpellerRepository};
+use crate::archive::SpellerArchive;
+use crate::sync::{Mutex, RwLock};
+use crate::transducer::Transducer;
+
+const FILE_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SuggestionCacheError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+}
+
+impl std::error::Error for SuggestionCacheError {}
+
+impl fmt::Display for SuggestionCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SuggestionCacheError::Io(e) => write!(f, "{}", e),
+            SuggestionCacheError::Parse(e) => write!(f, "invalid cache file: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for SuggestionCacheError {
+    fn from(e: io::Error) -> SuggestionCacheError {
+        SuggestionCacheError::Io(e)
+    }
+}
+
+/// FNV-1a, for fingerprints that stay the same from one run to the next.
+pub(crate) fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// What suggestions depend on in `config`. The number of suggestions, time
+/// budget and prefix cache are left out: fewer suggestions are a prefix of
+/// more, and searches cut short are not cached.
+pub fn config_fingerprint(config: &SpellerConfig) -> u64 {
+    let config = SpellerConfig {
+        n_best: None,
+        time_budget_ms: None,
+        prefix_cache: None,
+        ..config.clone()
+    };
+    let json = serde_json::to_string(&config).unwrap_or_default();
+    fnv1a(FNV_OFFSET, json.as_bytes())
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    archive: u64,
+    config: u64,
+    entries: Vec<CacheEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    word: SmolStr,
+    hits: u64,
+    suggestions: Vec<Suggestion>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    suggestions: Vec<Suggestion>,
+    hits: AtomicU64,
+}
+
+#[derive(Debug)]
+pub struct SuggestionCache {
+    path: PathBuf,
+    archive: u64,
+    config: SpellerConfig,
+    config_fingerprint: u64,
+    capacity: usize,
+    entries: RwLock<HashMap<SmolStr, Entry>>,
+    dirty: AtomicBool,
+}
+
+impl SuggestionCache {
+    /// Loads the cache at `path` for an archive with the given fingerprint,
+    /// queried with `config`. A missing file, or one written for another
+    /// archive or config, gives an empty cache.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        archive: u64,
+        config: &SpellerConfig,
+        capacity: usize,
+    ) -> Result<SuggestionCache, SuggestionCacheError> {
+        let cache = SuggestionCache {
+            path: path.as_ref().to_path_buf(),
+            archive,
+            config: config.clone(),
+            config_fingerprint: config_fingerprint(config),
+            capacity,
+            entries: RwLock::new(HashMap::new()),
+            dirty: AtomicBool::new(false),
+        };
+
+        let bytes = match std::fs::read(&cache.path) {
+            Ok(bytes) => bytes,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(cache),
+            Err(e) => return Err(e.into()),
+        };
+        let file: CacheFile =
+            serde_json::from_slice(&bytes).map_err(SuggestionCacheError::Parse)?;
+
+        if file.version == FILE_VERSION
+            && file.archive == archive
+            && file.config == cache.config_fingerprint
+        {
+            let mut entries = cache.entries.write();
+            for entry in file.entries.into_iter().take(capacity) {
+                entries.insert(
+                    entry.word,
+                    Entry {
+                        suggestions: entry.suggestions,
+                        hits: AtomicU64::new(entry.hits / 2),
+                    },
+                );
+            }
+        }
+
+        Ok(cache)
+    }
+
+    /// The config the cache's suggestions were searched for with.
+    pub fn config(&self) -> &SpellerConfig {
+        &self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.read().is_empty()
+    }
+
+    /// Whether suggestions for `config` can come from this cache: it has to
+    /// match the cache's config, except for a time budget and for asking
+    /// for no more suggestions than the cache keeps.
+    pub fn serves(&self, config: &SpellerConfig) -> bool {
+        let enough = match (config.n_best, self.config.n_best) {
+            (_, None) => true,
+            (Some(wanted), Some(kept)) => wanted <= kept,
+            (None, Some(_)) => false,
+        };
+
+        enough && config_fingerprint(config) == self.config_fingerprint
+    }
+
+    /// The cached suggestions for `word`, at most `n_best` of them.
+    pub fn get(&self, word: &str, n_best: Option<usize>) -> Option<Vec<Suggestion>> {
+        let entries = self.entries.read();
+        let entry = entries.get(word)?;
+        entry.hits.fetch_add(1, Ordering::Relaxed);
+
+        let n = n_best.unwrap_or(entry.suggestions.len());
+        Some(entry.suggestions.iter().take(n).cloned().collect())
+    }
+
+    /// Caches the suggestions for `word`, searched for with the cache's
+    /// config. Past twice the capacity, only the `capacity` most asked for
+    /// words are kept, ties broken arbitrarily.
+    pub fn insert(&self, word: &str, suggestions: Vec<Suggestion>) {
+        let mut entries = self.entries.write();
+
+        if entries.len() >= self.capacity.saturating_mul(2) {
+            let mut kept: Vec<(SmolStr, Entry)> = entries.drain().collect();
+            if self.capacity < kept.len() {
+                kept.select_nth_unstable_by_key(self.capacity, |(_, e)| {
+                    Reverse(e.hits.load(Ordering::Relaxed))
+                });
+                kept.truncate(self.capacity);
+            }
+            entries.extend(kept);
+        }
+
+        entries.insert(
+            SmolStr::new(word),
+            Entry {
+                suggestions,
+                hits: AtomicU64::new(1),
+            },
+        );
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Suggestions for `word` from the cache, or else from `speller`, caching
+    /// them if `config` is one the cache serves and the search wasn't cut
+    /// short.
+    pub fn suggest<T: Transducer>(
+        &self,
+        speller: Arc<Speller<T>>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        if !self.serves(config) {
+            return speller.suggest_with_config(word, config);
+        }
+
+        if let Some(suggestions) = self.get(word, config.n_best) {
+            speller.metrics().suggestion_cache(true);
+            return suggestions;
+        }
+        speller.metrics().suggestion_cache(false);
+
+        let search = SpellerConfig {
+            n_best: self.config.n_best,
+            ..config.clone()
+        };
+        let cut_short = speller.search_memory().cut_short();
+        let start = Instant::now();
+        let mut suggestions = speller.clone().suggest_with_config(word, &search);
+
+        let out_of_time = config
+            .time_budget_ms
+            .is_some_and(|ms| start.elapsed().as_millis() as u64 >= ms);
+        if !out_of_time && speller.search_memory().cut_short() == cut_short {
+            self.insert(word, suggestions.clone());
+        }
+
+        if let Some(n) = config.n_best {
+            suggestions.truncate(n);
+        }
+        suggestions
+    }
+
+    /// The `capacity` most asked for words and their suggestions, most asked
+    /// for first.
+    pub fn most_asked(&self) -> Vec<(SmolStr, Vec<Suggestion>)> {
+        self.top_entries()
+            .into_iter()
+            .map(|entry| (entry.word, entry.suggestions))
+            .collect()
+    }
+
+    fn top_entries(&self) -> Vec<CacheEntry> {
+        let mut entries: Vec<CacheEntry> = self
+            .entries
+            .read()
+            .iter()
+            .map(|(word, entry)| CacheEntry {
+                word: word.clone(),
+                hits: entry.hits.load(Ordering::Relaxed),
+                suggestions: entry.suggestions.clone(),
+            })
+            .collect();
+        entries.sort_by(|a, b| b.hits.cmp(&a.hits).then_with(|| a.word.cmp(&b.word)));
+        entries.truncate(self.capacity);
+        entries
+    }
+
+    /// Writes the `capacity` most asked for words to the cache's file, if
+    /// anything was added since it was last written. The file is replaced
+    /// whole, so a reader never sees half of it.
+    pub fn save(&self) -> Result<(), SuggestionCacheError> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let file = CacheFile {
+            version: FILE_VERSION,
+            archive: self.archive,
+            config: self.config_fingerprint,
+            entries: self.top_entries(),
+        };
+        let json = serde_json::to_vec(&file).map_err(SuggestionCacheError::Parse)?;
+
+        let temp = self.path.with_extension("tmp");
+        let result = std::fs::write(&temp, json).and_then(|_| std::fs::rename(&temp, &self.path));
+        if result.is_err() {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        result.map_err(SuggestionCacheError::Io)
+    }
+}
+
+/// Run as each language's cache is opened, with its tag and path, or with
+/// why it couldn't be, for the application to report.
+pub type OnOpen =
+    Box<dyn Fn(&str, &Path, Result<&SuggestionCache, &SuggestionCacheError>) + Send + Sync>;
+
+/// Each language's suggestion cache, kept in a directory as `TAG.json`.
+pub struct SuggestionCaches {
+    dir: PathBuf,
+    config: SpellerConfig,
+    capacity: usize,
+    on_open: Option<OnOpen>,
+    /// Kept when the language's archive is closed, so it is still saved.
+    opened: Mutex<BTreeMap<String, Arc<SuggestionCache>>>,
+}
+
+impl SuggestionCaches {
+    /// Caches in `dir`, created if it doesn't exist, for suggestions searched
+    /// for with `config`, keeping `capacity` words each.
+    pub fn new<P: Into<PathBuf>>(
+        dir: P,
+        config: &SpellerConfig,
+        capacity: usize,
+    ) -> io::Result<SuggestionCaches> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(SuggestionCaches {
+            dir,
+            config: config.clone(),
+            capacity,
+            on_open: None,
+            opened: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    pub fn set_on_open(&mut self, on_open: OnOpen) {
+        self.on_open = Some(on_open);
+    }
+
+    /// The cache for `tag`, loaded for `archive` the first time it is asked
+    /// for. A cache that can't be loaded is tried again on the next call.
+    pub fn get(&self, tag: &str, archive: &SpellerArchive) -> Option<Arc<SuggestionCache>> {
+        let mut opened = self.opened.lock();
+        if let Some(cache) = opened.get(tag) {
+            return Some(cache.clone());
+        }
+
+        let path = self.dir.join(format!("{}.json", tag));
+        let result =
+            SuggestionCache::open(&path, archive.fingerprint(), &self.config, self.capacity);
+        if let Some(on_open) = &self.on_open {
+            on_open(tag, &path, result.as_ref());
+        }
+
+        let cache = Arc::new(result.ok()?);
+        opened.insert(tag.to_string(), cache.clone());
+        Some(cache)
+    }
+
+    /// Saves every cache opened so far, with the tags of those that failed.
+    pub fn save(&self) -> Vec<(String, SuggestionCacheError)> {
+        let opened: Vec<(String, Arc<SuggestionCache>)> = self
+            .opened
+            .lock()
+            .iter()
+            .map(|(tag, cache)| (tag.clone(), cache.clone()))
+            .collect();
+
+        opened
+            .into_iter()
+            .filter_map(|(tag, cache)| cache.save().err().map(|e| (tag, e)))
+            .collect()
+    }
+
+    /// Saves the caches every `interval` on a thread of its own, passing
+    /// failures to `on_error`.
+    pub fn save_every<F>(self: Arc<Self>, interval: Duration, on_error: F) -> JoinHandle<()>
+    where
+        F: Fn(&str, &SuggestionCacheError) + Send + 'static,
+    {
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            for (tag, e) in self.save() {
+                on_error(&tag, &e);
+            }
+        })
+    }
+}
+
+/// A language's archive and suggestion cache, held for one request.
+pub struct CachedLanguage {
+    pub tag: String,
+    pub archive: ArchiveHandle,
+    pub cache: Option<Arc<SuggestionCache>>,
+}
+
+impl CachedLanguage {
+    /// The language for a BCP-47 tag, or the closest less specific one, so
+    /// `se-Latn-NO` falls back to `se`. Its archive is opened if it isn't.
+    pub fn open(
+        repository: &SpellerRepository,
+        caches: Option<&SuggestionCaches>,
+        tag: &str,
+    ) -> Result<CachedLanguage, RepositoryError> {
+        let archive = repository.get(tag)?;
+        let tag = repository.resolve(tag).unwrap_or(tag).to_string();
+        let cache = caches.and_then(|caches| caches.get(&tag, &archive));
+
+        Ok(CachedLanguage {
+            tag,
+            archive,
+            cache,
+        })
+    }
+
+    pub fn suggest(&self, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
+        let speller = self.archive.speller();
+        match &self.cache {
+            Some(cache) => cache.suggest(speller, word, config),
+            None => speller.suggest_with_config(word, config),
+        }
+    }
+
+    /// The misspellings in `text`, as `Speller::check_text_until` finds
+    /// them, with suggestions from the cache where it has them.
+    pub fn check_text(
+        &self,
+        text: &str,
+        config: &SpellerConfig,
+        deadline: Option<Instant>,
+    ) -> Vec<SpellingIssue> {
+        self.archive
+            .speller()
+            .check_text_until(text, config, deadline, |word, config| {
+                self.suggest(word, config)
+            })
+    }
+
+    /// Whether `word` is correct and suggestions for it, in one pass unless
+    /// the suggestions come from the cache.
+    pub fn check(&self, word: &str, config: &SpellerConfig) -> WordCheck {
+        let speller = self.archive.speller();
+        match &self.cache {
+            Some(cache) => WordCheck {
+                correct: speller.clone().is_correct(word),
+                suggestions: cache.suggest(speller, word, config),
+            },
+            None => speller.check(word, config),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_and_loaded() {
+        let dir = tempdir::TempDir::new("divvunspell-cache").unwrap();
+        let path = dir.path().join("se.json");
+        let config = SpellerConfig {
+            n_best: Some(5),
+            ..SpellerConfig::default()
+        };
+
+        let cache = SuggestionCache::open(&path, 42, &config, 10).unwrap();
+        cache.insert(
+            "gielaa",
+            vec![
+                Suggestion::new("giella".into(), 1.0),
+                Suggestion::new("gielat".into(), 2.0),
+            ],
+        );
+        assert_eq!(cache.get("gielaa", Some(1)).unwrap().len(), 1);
+        cache.save().unwrap();
+
+        let loaded = SuggestionCache::open(&path, 42, &config, 10).unwrap();
+        assert_eq!(loaded.get("gielaa", None).unwrap()[0].value(), "giella");
+        assert!(loaded.serves(&SpellerConfig {
+            n_best: Some(3),
+            time_budget_ms: Some(50),
+            ..config.clone()
+        }));
+        assert!(!loaded.serves(&SpellerConfig {
+            n_best: Some(10),
+            ..config.clone()
+        }));
+
+        assert!(SuggestionCache::open(&path, 43, &config, 10)
+            .unwrap()
+            .is_empty());
+        let wider = SpellerConfig {
+            beam: Some(10.0),
+            ..config
+        };
+        assert!(SuggestionCache::open(&path, 42, &wider, 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn evicts_down_to_capacity() {
+        let dir = tempdir::TempDir::new("divvunspell-cache").unwrap();
+        let path = dir.path().join("se.json");
+        let cache = SuggestionCache::open(&path, 42, &SpellerConfig::default(), 2).unwrap();
+
+        // Every count tied: still keeps two of them, not none.
+        for word in &["a", "b", "c", "d"] {
+            cache.insert(word, vec![]);
+        }
+        cache.insert("e", vec![]);
+        assert_eq!(cache.len(), 3);
+
+        // The most asked for survive the next eviction.
+        cache.get("e", None);
+        cache.get("e", None);
+        cache.insert("f", vec![]);
+        cache.insert("g", vec![]);
+        assert!(cache.get("e", None).is_some());
+        assert_eq!(cache.len(), 3);
+    }
+}
@@ -0,0 +1,190 @@
+//! Words added by the user on top of a speller's lexicon.
+//!
+//! Suggestions among user words are found by scanning them all, until a
+//! `UserOverlay` compiled from them is installed; after that only words
+//! added since are scanned. See `speller::user_overlay`.
+
+use smol_str::SmolStr;
+use std::collections::BTreeSet;
+use std::io;
+use std::sync::Arc;
+
+use crate::errmodel::align::levenshtein;
+use crate::speller::suggestion::Suggestion;
+use crate::speller::user_overlay::UserOverlay;
+use crate::speller::SpellerConfig;
+use crate::transducer::HfstTransducer;
+use crate::types::Weight;
+
+/// Edit distance within which user words are offered as suggestions.
+pub const USER_SUGGESTION_DISTANCE: usize = 2;
+
+/// Scanned words past which `needs_compile` suggests an overlay.
+pub const COMPILE_THRESHOLD: usize = 200;
+
+#[derive(Debug, Clone, Default)]
+pub struct UserDictionary {
+    words: BTreeSet<SmolStr>,
+    /// The words not in `overlay`, which are scanned for suggestions.
+    uncompiled: BTreeSet<SmolStr>,
+    overlay: Option<Arc<UserOverlay>>,
+}
+
+impl UserDictionary {
+    pub fn new() -> UserDictionary {
+        UserDictionary::default()
+    }
+
+    /// Returns `false` if the word was already present.
+    pub fn insert(&mut self, word: &str) -> bool {
+        if !self.overlay.as_ref().is_some_and(|o| o.contains(word)) {
+            self.uncompiled.insert(SmolStr::from(word));
+        }
+        self.words.insert(SmolStr::from(word))
+    }
+
+    pub fn remove(&mut self, word: &str) -> bool {
+        self.uncompiled.remove(word);
+        self.words.remove(word)
+    }
+
+    /// Whether enough words are scanned for suggestions that compiling an
+    /// overlay would pay off.
+    pub fn needs_compile(&self) -> bool {
+        self.uncompiled.len() >= COMPILE_THRESHOLD
+    }
+
+    /// Compiles the words into an overlay searched with `mutator`, the
+    /// speller's error model. Takes a while for a large dictionary, so
+    /// keyboards call it on a copy, off the UI thread, and `install` the
+    /// result.
+    pub fn compile(&self, mutator: &HfstTransducer) -> io::Result<UserOverlay> {
+        UserOverlay::compile(self.words(), mutator, 0.0)
+    }
+
+    /// Uses `overlay` for suggestions among the words it was compiled from.
+    /// Words added since it was compiled are still scanned, and words removed
+    /// since are no longer suggested.
+    pub fn install(&mut self, overlay: UserOverlay) {
+        self.uncompiled = self
+            .words
+            .iter()
+            .filter(|w| !overlay.contains(w))
+            .cloned()
+            .collect();
+        self.overlay = Some(Arc::new(overlay));
+    }
+
+    pub fn overlay(&self) -> Option<&UserOverlay> {
+        self.overlay.as_deref()
+    }
+
+    /// Whether `word` was added, either as written or in lower case, so that
+    /// a sentence-initial or all-caps form of a user word is accepted too.
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(word) || self.words.contains(&*word.to_lowercase())
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.words.iter().map(|x| &**x)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// User words outside the overlay within `max_distance` edits of
+    /// `word`, weighted by their distance, closest first.
+    pub fn suggest(&self, word: &str, max_distance: usize) -> Vec<Suggestion> {
+        let mut out: Vec<Suggestion> = self
+            .uncompiled
+            .iter()
+            .filter_map(|candidate| {
+                let distance = levenshtein(word, candidate);
+                if distance > 0 && distance <= max_distance {
+                    Some(Suggestion::new(candidate.clone(), distance as Weight))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        out.sort();
+        out
+    }
+
+    /// Puts close user words ahead of the speller's own suggestions, dropping
+    /// duplicates and truncating to `n_best`. Corrections from the overlay
+    /// come first, then scanned words.
+    pub fn merge(
+        &self,
+        word: &str,
+        suggestions: Vec<Suggestion>,
+        n_best: Option<usize>,
+    ) -> Vec<Suggestion> {
+        let mut out: Vec<Suggestion> = match &self.overlay {
+            Some(overlay) => {
+                let config = SpellerConfig {
+                    n_best,
+                    ..SpellerConfig::default()
+                };
+                overlay
+                    .suggest(word, &config)
+                    .into_iter()
+                    .filter(|s| self.words.contains(s.value()) && s.value() != word)
+                    .collect()
+            }
+            None => vec![],
+        };
+
+        for suggestion in self.suggest(word, USER_SUGGESTION_DISTANCE) {
+            if !out.iter().any(|x| x.value == suggestion.value) {
+                out.push(suggestion);
+            }
+        }
+
+        for suggestion in suggestions {
+            if !out.iter().any(|x| x.value == suggestion.value) {
+                out.push(suggestion);
+            }
+        }
+
+        if let Some(n) = n_best {
+            out.truncate(n);
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_and_merge() {
+        let mut dict = UserDictionary::new();
+        assert!(dict.insert("divvun"));
+        assert!(!dict.insert("divvun"));
+        dict.insert("giellatekno");
+
+        assert!(dict.contains("divvun"));
+        assert!(dict.contains("Divvun"));
+        assert!(!dict.contains("divvu"));
+
+        let speller = vec![
+            Suggestion::new("divan".into(), 12.0),
+            Suggestion::new("divvun".into(), 15.0),
+        ];
+        let merged = dict.merge("divun", speller, Some(2));
+        let values: Vec<&str> = merged.iter().map(|x| x.value()).collect();
+        assert_eq!(values, vec!["divvun", "divan"]);
+
+        assert!(dict.remove("divvun"));
+        assert_eq!(dict.words().collect::<Vec<_>>(), vec!["giellatekno"]);
+    }
+}
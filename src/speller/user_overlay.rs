@@ -0,0 +1,394 @@
+//! A user dictionary compiled into a small acceptor, searched alongside the
+//! archive's own with the same error model.
+//!
+//! Scanning every user word for ones close to a typo gets slow once a
+//! keyboard has learned a few thousand of them, and ranks them by edit
+//! distance rather than by the error model. `UserOverlay::compile` builds the
+//! words into a trie written as an HFST optimized-lookup transducer, so that
+//! they are looked up and corrected like the lexicon's own: a suggestion
+//! search over the overlay and one over the acceptor together give what a
+//! search over their union would. States are packed into the index table
+//! the way HFST packs them, sharing slots wherever their symbols don't
+//! collide, so the overlay stays a few bytes per letter.
+//!
+//! Compiling takes time in proportion to the number of letters, so it is
+//! meant to be done off the UI thread and the result handed to
+//! `UserDictionary::install`.
+
+use alloc::sync::Arc;
+use smol_str::SmolStr;
+use std::collections::BTreeSet;
+use std::io;
+
+use super::suggestion::Suggestion;
+use super::{Speller, SpellerConfig};
+use crate::constants::TARGET_TABLE;
+use crate::errmodel::hfst::{self, Arc as Transition};
+use crate::transducer::{HfstTransducer, TransducerBuf};
+use crate::types::{SymbolNumber, TransitionTableIndex, Weight};
+
+#[derive(Debug, Default)]
+struct Node {
+    children: Vec<(SymbolNumber, usize)>,
+    is_final: bool,
+}
+
+/// The words as a trie over `alphabet`, the root first.
+fn trie(words: &BTreeSet<SmolStr>, alphabet: &[char]) -> Vec<Node> {
+    let mut nodes = vec![Node::default()];
+
+    for word in words {
+        let mut current = 0;
+        for ch in word.chars() {
+            let symbol = alphabet.binary_search(&ch).map(|i| i as u16 + 1).unwrap();
+            current = match nodes[current].children.iter().find(|c| c.0 == symbol) {
+                Some(&(_, child)) => child,
+                None => {
+                    nodes.push(Node::default());
+                    let child = nodes.len() - 1;
+                    nodes[current].children.push((symbol, child));
+                    child
+                }
+            };
+        }
+        nodes[current].is_final = true;
+    }
+
+    nodes
+}
+
+/// Gives each node a place in the index table: a slot of its own for its
+/// final weight, followed by one per symbol, of which only those it has
+/// transitions on are taken. The first place where those slots are all free
+/// is used.
+fn pack(nodes: &[Node]) -> (Vec<TransitionTableIndex>, usize) {
+    let mut used: Vec<bool> = vec![];
+    let mut bases = Vec::with_capacity(nodes.len());
+    let mut first_free = 0;
+
+    for node in nodes {
+        let slots = |base: usize| {
+            std::iter::once(base).chain(
+                node.children
+                    .iter()
+                    .map(move |&(symbol, _)| base + 1 + symbol as usize),
+            )
+        };
+
+        let mut base = first_free;
+        while slots(base).any(|slot| used.get(slot).cloned().unwrap_or(false)) {
+            base += 1;
+        }
+
+        for slot in slots(base) {
+            if slot >= used.len() {
+                used.resize(slot + 1, false);
+            }
+            used[slot] = true;
+        }
+        while used.get(first_free).cloned().unwrap_or(false) {
+            first_free += 1;
+        }
+
+        bases.push(base as TransitionTableIndex);
+    }
+
+    (bases, used.len())
+}
+
+/// Writes `words` as an acceptor giving each of them `weight`.
+fn write<W: io::Write>(w: W, words: &BTreeSet<SmolStr>, weight: Weight) -> io::Result<()> {
+    let mut alphabet: Vec<char> = words.iter().flat_map(|w| w.chars()).collect();
+    alphabet.sort();
+    alphabet.dedup();
+
+    let symbol_count = alphabet.len() + 1;
+    if symbol_count >= u16::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "alphabet too large",
+        ));
+    }
+
+    let nodes = trie(words, &alphabet);
+    let (bases, used) = pack(&nodes);
+
+    // Room past the last node for a lookup of any symbol, as the search
+    // reads a node's slot for every symbol it tries.
+    let mut index = vec![(u16::MAX, u32::MAX); used + symbol_count + 1];
+    let mut transitions = vec![];
+
+    for (node, &base) in nodes.iter().zip(&bases) {
+        if node.is_final {
+            index[base as usize] = (u16::MAX, weight.to_bits());
+        }
+
+        for &(symbol, child) in &node.children {
+            index[base as usize + 1 + symbol as usize] =
+                (symbol, TARGET_TABLE + transitions.len() as u32);
+            transitions.push(Some(Transition {
+                input: symbol,
+                output: symbol,
+                target: bases[child],
+                weight: 0.0,
+            }));
+            transitions.push(None);
+        }
+    }
+
+    // Weighted, deterministic, input deterministic, minimized, cyclic,
+    // epsilon-epsilon, input epsilon, input epsilon cycles, unweighted
+    // input epsilon cycles
+    let properties = [true, true, true, false, false, false, false, false, false];
+    let arc_count = nodes.len() - 1;
+
+    hfst::write_tables(
+        w,
+        &alphabet,
+        &index,
+        &transitions,
+        nodes.len() as u32,
+        arc_count,
+        properties,
+    )
+}
+
+/// An acceptor of `entries`, each a word and the tags that follow it, with
+/// the tags written with an input epsilon as in lexicons that keep their
+/// analyses, such as `("Bergen", &["+N", "+Prop"])`. No two entries may have
+/// different tags after the same letters.
+#[cfg(test)]
+pub(crate) fn tagged_acceptor(entries: &[(&str, &[&str])]) -> io::Result<HfstTransducer> {
+    let mut letters: Vec<char> = entries.iter().flat_map(|(w, _)| w.chars()).collect();
+    letters.sort();
+    letters.dedup();
+    let mut tags: Vec<&str> = entries
+        .iter()
+        .flat_map(|(_, t)| t.iter().cloned())
+        .collect();
+    tags.sort();
+    tags.dedup();
+
+    let mut alphabet: Vec<String> = letters.iter().map(|ch| ch.to_string()).collect();
+    alphabet.extend(tags.iter().map(|tag| tag.to_string()));
+    let tag_symbol = |tag: &str| (letters.len() + tags.binary_search(&tag).unwrap() + 1) as u16;
+
+    // Tags are children on symbol 0, epsilon, with their own symbol as output.
+    let mut nodes = vec![Node::default()];
+    let mut outputs = std::collections::HashMap::new();
+    for (word, word_tags) in entries {
+        let mut current = 0;
+        let symbols = word
+            .chars()
+            .map(|ch| {
+                let symbol = letters.binary_search(&ch).unwrap() as u16 + 1;
+                (symbol, symbol)
+            })
+            .chain(word_tags.iter().map(|tag| (0, tag_symbol(tag))));
+        for (input, output) in symbols {
+            current = match nodes[current].children.iter().find(|c| c.0 == input) {
+                Some(&(_, child)) => {
+                    assert_eq!(outputs.get(&child), Some(&output));
+                    child
+                }
+                None => {
+                    nodes.push(Node::default());
+                    let child = nodes.len() - 1;
+                    nodes[current].children.push((input, child));
+                    outputs.insert(child, output);
+                    child
+                }
+            };
+        }
+        nodes[current].is_final = true;
+    }
+
+    let (bases, used) = pack(&nodes);
+    let mut index = vec![(u16::MAX, u32::MAX); used + alphabet.len() + 2];
+    let mut transitions = vec![];
+    for (node, &base) in nodes.iter().zip(&bases) {
+        if node.is_final {
+            index[base as usize] = (u16::MAX, 0.0f32.to_bits());
+        }
+        for &(symbol, child) in &node.children {
+            index[base as usize + 1 + symbol as usize] =
+                (symbol, TARGET_TABLE + transitions.len() as u32);
+            transitions.push(Some(Transition {
+                input: symbol,
+                output: outputs[&child],
+                target: bases[child],
+                weight: 0.0,
+            }));
+            transitions.push(None);
+        }
+    }
+
+    let properties = [true, false, false, false, false, false, true, false, false];
+    let mut bytes = vec![];
+    hfst::write_tables(
+        &mut bytes,
+        &alphabet,
+        &index,
+        &transitions,
+        nodes.len() as u32,
+        nodes.len() - 1,
+        properties,
+    )?;
+    load(bytes)
+}
+
+/// The transducer `write` put in `bytes`.
+fn load(bytes: Vec<u8>) -> io::Result<HfstTransducer> {
+    HfstTransducer::from_mapped_memory(Arc::new(TransducerBuf::from(bytes)))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[derive(Debug)]
+pub struct UserOverlay {
+    words: BTreeSet<SmolStr>,
+    speller: Arc<Speller<HfstTransducer>>,
+}
+
+impl UserOverlay {
+    /// Compiles `words` into an acceptor giving each of them `weight`, to be
+    /// searched with `mutator`, the error model of the speller the words
+    /// were added to.
+    pub fn compile<'a, I: IntoIterator<Item = &'a str>>(
+        words: I,
+        mutator: &HfstTransducer,
+        weight: Weight,
+    ) -> io::Result<UserOverlay> {
+        let words: BTreeSet<SmolStr> = words.into_iter().map(SmolStr::from).collect();
+
+        let mut bytes = vec![];
+        write(&mut bytes, &words, weight)?;
+        let lexicon = load(bytes)?;
+
+        Ok(UserOverlay {
+            words,
+            speller: Speller::new(mutator.share(), lexicon),
+        })
+    }
+
+    /// An overlay of `words` from the acceptor `compile` built for them, as
+    /// given by `acceptor`, so that a kept overlay needn't be compiled
+    /// again. The acceptor is read through bounds checked accessors, as it
+    /// comes from outside the archive.
+    pub fn from_acceptor<'a, I: IntoIterator<Item = &'a str>>(
+        words: I,
+        acceptor: &[u8],
+        mutator: &HfstTransducer,
+    ) -> io::Result<UserOverlay> {
+        let words: BTreeSet<SmolStr> = words.into_iter().map(SmolStr::from).collect();
+        let mut lexicon = HfstTransducer::from_bytes(acceptor)?;
+        lexicon.set_checked(true);
+
+        Ok(UserOverlay {
+            words,
+            speller: Speller::new(mutator.share(), lexicon),
+        })
+    }
+
+    /// The compiled acceptor, for `from_acceptor`.
+    pub fn acceptor(&self) -> &[u8] {
+        self.speller.lexicon().buffer()
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.words.iter().map(|x| &**x)
+    }
+
+    /// Whether the overlay was compiled with `word`, exactly as written.
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(word)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Whether `word`, or one of its case variants, is in the overlay.
+    pub fn is_correct(&self, word: &str) -> bool {
+        self.speller.clone().is_correct(word)
+    }
+
+    /// Corrections of `word` among the overlay's words, weighted by the
+    /// error model.
+    pub fn suggest(&self, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
+        self.speller.clone().suggest_with_config(word, config)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn speller(&self) -> Arc<Speller<HfstTransducer>> {
+        self.speller.clone()
+    }
+
+    /// The compiled acceptor's size, in bytes.
+    pub fn size(&self) -> usize {
+        self.speller.lexicon().buffer().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::{test_mutator, Transducer};
+
+    #[test]
+    fn trie_acceptor() {
+        let words: BTreeSet<SmolStr> = ["sámi", "sápmi", "sámit"]
+            .iter()
+            .map(|w| SmolStr::from(*w))
+            .collect();
+
+        let mut bytes = vec![];
+        write(&mut bytes, &words, 1.5).unwrap();
+        let t = load(bytes).unwrap();
+        let key = |ch: &str| {
+            t.alphabet()
+                .key_table()
+                .iter()
+                .position(|k| k == ch)
+                .unwrap() as SymbolNumber
+        };
+
+        let accepts = |word: &str| {
+            let mut state = 0;
+            for ch in word.chars() {
+                let symbol = key(&ch.to_string());
+                if !t.has_transitions(state + 1, Some(symbol)) {
+                    return None;
+                }
+                let next = t.next(state, symbol).unwrap();
+                state = t.take_non_epsilons(next, symbol)?.target()?;
+            }
+            if t.is_final(state) {
+                t.final_weight(state)
+            } else {
+                None
+            }
+        };
+
+        assert_eq!(accepts("sámi"), Some(1.5));
+        assert_eq!(accepts("sámit"), Some(1.5));
+        assert_eq!(accepts("sápmi"), Some(1.5));
+        assert_eq!(accepts("sám"), None);
+        assert_eq!(accepts("sápm"), None);
+        assert!(!t.has_epsilons_or_flags(1));
+    }
+
+    #[test]
+    fn corrects_with_error_model() {
+        let mutator = test_mutator(&[("sami", "sámi", 3), ("divun", "divvun", 2)], &[]);
+        let overlay = UserOverlay::compile(vec!["divvun", "sámi"], &mutator, 0.0).unwrap();
+        assert!(overlay.is_correct("divvun"));
+        assert!(!overlay.is_correct("divun"));
+
+        let suggestions = overlay.suggest("divun", &SpellerConfig::default());
+        assert_eq!(suggestions[0].value(), "divvun");
+    }
+}
@@ -0,0 +1,88 @@
+//! Touching the parts of the transducers a first lookup reads, so that an
+//! app can pay for them at launch rather than on the user's first keystroke.
+//!
+//! Mapped transducers are read from storage one page fault at a time, and
+//! every search starts from the same few states. Walking the start state's
+//! index rows and transitions, and those of the states a symbol or two away,
+//! faults those pages in; looking up common words as well covers the paths
+//! most first queries take.
+
+use alloc::sync::Arc;
+use hashbrown::HashSet;
+
+use super::worker::SpellerWorker;
+use super::{Speller, SpellerConfig};
+use crate::transducer::Transducer;
+use crate::types::{SymbolNumber, TransitionTableIndex};
+
+/// How many symbols away from the start state the walk goes.
+const WARM_UP_DEPTH: usize = 2;
+
+/// The most states the walk visits in one transducer.
+const WARM_UP_STATES: usize = 4096;
+
+/// Visits the states within `WARM_UP_DEPTH` symbols of the start, returning
+/// how many it visited.
+fn touch_states<T: Transducer>(transducer: &T) -> usize {
+    let symbols = transducer.alphabet().key_table().len() as SymbolNumber;
+    let mut seen: HashSet<TransitionTableIndex> = HashSet::new();
+    let mut frontier = vec![0];
+    seen.insert(0);
+
+    for _ in 0..WARM_UP_DEPTH {
+        let mut next_frontier = vec![];
+
+        for state in frontier {
+            for symbol in 0..symbols {
+                if !transducer.has_transitions(state + 1, Some(symbol)) {
+                    continue;
+                }
+
+                let first = match transducer.next(state, symbol) {
+                    Some(first) => first,
+                    None => continue,
+                };
+
+                for i in first..first + transducer.non_epsilon_run(first, symbol) {
+                    if let Some(target) = transducer.transition(i).target() {
+                        if seen.len() < WARM_UP_STATES && seen.insert(target) {
+                            next_frontier.push(target);
+                        }
+                    }
+                }
+            }
+        }
+
+        frontier = next_frontier;
+    }
+
+    seen.len()
+}
+
+impl<T: Transducer> Speller<T> {
+    /// Faults in the pages around both transducers' start states, returning
+    /// how many states were visited. Cheap enough to call on a background
+    /// thread at every launch.
+    pub fn warm_up(&self) -> usize {
+        touch_states(self.lexicon()) + touch_states(self.mutator())
+    }
+
+    /// Like `warm_up`, and also looks up `words`, such as the most common
+    /// ones from `FrequencyList::most_frequent`, to fault in their paths
+    /// through the lexicon. The lookups aren't reported to `metrics`.
+    pub fn warm_up_words<S: AsRef<str>>(self: Arc<Self>, words: &[S]) -> usize {
+        let states = self.warm_up();
+
+        for word in words {
+            let worker = SpellerWorker::new(
+                self.clone(),
+                self.to_input_vec(word.as_ref()),
+                SpellerConfig::default(),
+                None,
+            );
+            worker.is_correct();
+        }
+
+        states
+    }
+}
@@ -1,22 +1,130 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::cell::RefCell;
+use core::f32;
 use hashbrown::HashMap;
 use smol_str::SmolStr;
-use std::f32;
-use std::sync::Arc;
 
-use lifeguard::{Pool, Recycled};
-
-use super::{Speller, SpellerConfig};
-use crate::speller::suggestion::Suggestion;
-use crate::transducer::tree_node::TreeNode;
+use super::beam::AdaptiveBeam;
+use super::filter;
+use super::prefix_cache::MAX_FRONTIER_NODES;
+use super::proper_noun::{is_tag, PROPER_NOUN_PREFERENCE};
+use super::replay::{Outcome, Step};
+use super::seen::SeenSet;
+use super::semiring::Semiring;
+use super::{Instant, Speller, SpellerConfig};
+use crate::metrics::{Metrics, SearchStop};
+use crate::speller::suggestion::{self, Suggestion};
+use crate::transducer::tree_node::{NodeArena, TreeNode};
 use crate::transducer::Transducer;
 use crate::types::{SymbolNumber, Weight};
 
-#[inline(always)]
-fn speller_start_node(pool: &Pool<TreeNode>, size: usize) -> Vec<Recycled<TreeNode>> {
-    let start_node = TreeNode::empty(pool, vec![0; size]);
-    let mut nodes = Vec::with_capacity(256);
-    nodes.push(start_node);
-    nodes
+/// Per-thread search state, reused by every query on the thread so that
+/// parallel callers never contend for it and buffers are only allocated
+/// while they grow.
+struct SearchPool {
+    arena: NodeArena,
+    nodes: Vec<TreeNode>,
+}
+
+impl SearchPool {
+    fn new() -> SearchPool {
+        SearchPool {
+            arena: NodeArena::new(),
+            nodes: vec![],
+        }
+    }
+
+    /// Readies the pool for a query: the arena is emptied and the stack
+    /// holds only the start node. A stack that grew past `pool_max` during
+    /// the last query gives the excess back.
+    fn reset(&mut self, state_size: usize, config: &SpellerConfig) {
+        self.arena.reset(state_size);
+        self.nodes.clear();
+        if self.nodes.capacity() > config.pool_max.max(config.pool_start) {
+            self.nodes.shrink_to_fit();
+        }
+        self.nodes.reserve(config.pool_start);
+        self.nodes.push(TreeNode::empty());
+    }
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static POOL: RefCell<SearchPool> = RefCell::new(SearchPool::new());
+}
+
+/// Runs `f` with the thread's pool, reset for a new query. A query started
+/// while another holds it gets a pool of its own. Buffers that end up
+/// larger than `retain_limit` bytes are not kept for the next query, and
+/// neither is a stack past `pool_max`; either is reported to `metrics`.
+#[cfg(feature = "std")]
+fn with_pool<R, F>(
+    state_size: usize,
+    config: &SpellerConfig,
+    retain_limit: Option<usize>,
+    metrics: &dyn Metrics,
+    f: F,
+) -> R
+where
+    F: FnOnce(&mut NodeArena, &mut Vec<TreeNode>) -> R,
+{
+    POOL.with(|cell| match cell.try_borrow_mut() {
+        Ok(mut pool) => {
+            pool.reset(state_size, config);
+            let result = {
+                let SearchPool { arena, nodes } = &mut *pool;
+                f(arena, nodes)
+            };
+
+            let over_limit = retain_limit
+                .map(|limit| search_bytes(&pool.arena, &pool.nodes) > limit)
+                .unwrap_or(false);
+
+            if over_limit {
+                *pool = SearchPool::new();
+                metrics.pool_exhausted();
+            } else if pool.nodes.capacity() > config.pool_max.max(config.pool_start) {
+                metrics.pool_exhausted();
+            }
+
+            result
+        }
+        Err(_) => with_new_pool(state_size, config, f),
+    })
+}
+
+/// Without `std` there are no thread locals to keep a pool in, so every
+/// query gets a new one.
+#[cfg(not(feature = "std"))]
+fn with_pool<R, F>(
+    state_size: usize,
+    config: &SpellerConfig,
+    _retain_limit: Option<usize>,
+    _metrics: &dyn Metrics,
+    f: F,
+) -> R
+where
+    F: FnOnce(&mut NodeArena, &mut Vec<TreeNode>) -> R,
+{
+    with_new_pool(state_size, config, f)
+}
+
+fn with_new_pool<R, F>(state_size: usize, config: &SpellerConfig, f: F) -> R
+where
+    F: FnOnce(&mut NodeArena, &mut Vec<TreeNode>) -> R,
+{
+    let mut pool = SearchPool::new();
+    pool.reset(state_size, config);
+    let SearchPool { arena, nodes } = &mut pool;
+    f(arena, nodes)
+}
+
+/// Memory held by a search's node stack and arena.
+fn search_bytes(arena: &NodeArena, nodes: &Vec<TreeNode>) -> usize {
+    arena.memory_bytes() + nodes.capacity() * core::mem::size_of::<TreeNode>()
 }
 
 #[inline(always)]
@@ -24,10 +132,14 @@ fn speller_max_weight(config: &SpellerConfig) -> Weight {
     config.max_weight.unwrap_or(f32::MAX)
 }
 
+/// How many nodes are expanded between checks of the deadline.
+const DEADLINE_CHECK_INTERVAL: usize = 256;
+
 pub struct SpellerWorker<T: Transducer> {
     speller: Arc<Speller<T>>,
     input: Vec<SymbolNumber>,
     config: SpellerConfig,
+    deadline: Option<Instant>,
 }
 
 impl<'t, T: Transducer + 't> SpellerWorker<T> {
@@ -36,21 +148,23 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
         speller: Arc<Speller<T>>,
         input: Vec<SymbolNumber>,
         config: SpellerConfig,
+        deadline: Option<Instant>,
     ) -> Arc<SpellerWorker<T>> {
         Arc::new(SpellerWorker {
             speller,
             input,
             config,
+            deadline,
         })
     }
 
     #[inline(always)]
-    fn lexicon_epsilons<'a>(
+    fn lexicon_epsilons(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
         let lexicon = self.speller.lexicon();
         let operations = lexicon.alphabet().operations();
@@ -69,7 +183,7 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                     if self
                         .is_under_weight_limit(max_weight, next_node.weight() + transition_weight)
                     {
-                        let new_node = next_node.update_lexicon(pool, transition);
+                        let new_node = next_node.update_lexicon(arena, transition);
                         output_nodes.push(new_node);
                     }
                 } else {
@@ -81,7 +195,8 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                             continue;
                         }
 
-                        if let Some(applied_node) = next_node.apply_operation(pool, op, &transition)
+                        if let Some(applied_node) =
+                            next_node.apply_operation(arena, op, &transition)
                         {
                             output_nodes.push(applied_node);
                         }
@@ -94,12 +209,12 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
     }
 
     #[inline(always)]
-    fn mutator_epsilons<'a>(
+    fn mutator_epsilons(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
         let mutator = self.speller.mutator();
         let lexicon = self.speller.lexicon();
@@ -117,7 +232,7 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                     max_weight,
                     next_node.weight() + transition.weight().unwrap(),
                 ) {
-                    let new_node = next_node.update_mutator(pool, transition);
+                    let new_node = next_node.update_mutator(transition);
                     output_nodes.push(new_node);
                 }
 
@@ -126,7 +241,16 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
             }
 
             if let Some(sym) = transition.symbol() {
+                // An insertion.
+                let next_node = &match self.edited(next_node) {
+                    Some(node) => node,
+                    None => {
+                        next_m += 1;
+                        continue;
+                    }
+                };
                 let trans_sym = alphabet_translator[sym as usize];
+                let weight = transition.weight().unwrap() + self.profile_penalty(None, Some(sym));
 
                 if !lexicon.has_transitions(next_node.lexicon_state + 1, Some(trans_sym)) {
                     // we have no regular transitions for this
@@ -138,12 +262,12 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                             lexicon.alphabet().unknown(),
                         ) {
                             self.queue_lexicon_arcs(
-                                pool,
+                                arena,
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().unknown().unwrap(),
                                 transition.target().unwrap(),
-                                transition.weight().unwrap(),
+                                weight,
                                 0,
                                 output_nodes,
                             );
@@ -154,12 +278,12 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                             lexicon.alphabet().identity(),
                         ) {
                             self.queue_lexicon_arcs(
-                                pool,
+                                arena,
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().identity().unwrap(),
                                 transition.target().unwrap(),
-                                transition.weight().unwrap(),
+                                weight,
                                 0,
                                 output_nodes,
                             );
@@ -171,12 +295,12 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                 }
 
                 self.queue_lexicon_arcs(
-                    pool,
+                    arena,
                     max_weight,
                     &next_node,
                     trans_sym,
                     transition.target().unwrap(),
-                    transition.weight().unwrap(),
+                    weight,
                     0,
                     output_nodes,
                 );
@@ -187,22 +311,25 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
     }
 
     #[inline(always)]
-    pub fn queue_lexicon_arcs<'a>(
+    pub fn queue_lexicon_arcs(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
         input_sym: SymbolNumber,
         mutator_state: u32,
         mutator_weight: Weight,
         input_increment: i16,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
         let lexicon = self.speller.lexicon();
         let identity = lexicon.alphabet().identity();
-        let mut next = lexicon.next(next_node.lexicon_state, input_sym).unwrap();
+        let first = lexicon.next(next_node.lexicon_state, input_sym).unwrap();
+        let run = lexicon.non_epsilon_run(first, input_sym);
+
+        for next in first..first + run {
+            let noneps_trans = lexicon.transition(next);
 
-        while let Some(noneps_trans) = lexicon.take_non_epsilons(next, input_sym) {
             if let Some(mut sym) = noneps_trans.symbol() {
                 // Symbol replacement here is unfortunate but necessary.
                 if let Some(id) = identity {
@@ -218,7 +345,7 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
 
                 if is_under_weight_limit {
                     let new_node = next_node.update(
-                        pool,
+                        arena,
                         sym,
                         Some(next_node.input_state + input_increment as u32),
                         mutator_state,
@@ -229,34 +356,40 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                     output_nodes.push(new_node);
                 }
             }
-
-            next += 1;
         }
     }
 
     #[inline(always)]
-    fn queue_mutator_arcs<'a>(
+    fn queue_mutator_arcs(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
         input_sym: SymbolNumber,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
         let mutator = self.speller.mutator();
         let lexicon = self.speller.lexicon();
         let alphabet_translator = self.speller.alphabet_translator();
 
-        let mut next_m = mutator.next(next_node.mutator_state, input_sym).unwrap();
+        let first = mutator.next(next_node.mutator_state, input_sym).unwrap();
+        let run = mutator.non_epsilon_run(first, input_sym);
 
-        while let Some(transition) = mutator.take_non_epsilons(next_m, input_sym) {
+        for next_m in first..first + run {
+            let transition = mutator.transition(next_m);
             let symbol = transition.symbol();
 
             if let Some(0) = symbol {
-                let transition_weight = transition.weight().unwrap();
+                // A deletion.
+                let edited = match self.edited(next_node) {
+                    Some(node) => node,
+                    None => continue,
+                };
+                let transition_weight =
+                    transition.weight().unwrap() + self.profile_penalty(Some(input_sym), None);
                 if self.is_under_weight_limit(max_weight, next_node.weight() + transition_weight) {
-                    let new_node = next_node.update(
-                        pool,
+                    let new_node = edited.update(
+                        arena,
                         0,
                         Some(next_node.input_state + 1),
                         transition.target().unwrap(),
@@ -267,12 +400,24 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                     output_nodes.push(new_node);
                 }
 
-                next_m += 1;
                 continue;
             }
 
             if let Some(sym) = symbol {
+                // A substitution, unless the symbol passes through.
+                let edited;
+                let next_node = if sym == input_sym {
+                    next_node
+                } else {
+                    edited = match self.edited(next_node) {
+                        Some(node) => node,
+                        None => continue,
+                    };
+                    &edited
+                };
                 let trans_sym = alphabet_translator[sym as usize];
+                let weight =
+                    transition.weight().unwrap() + self.profile_penalty(Some(input_sym), Some(sym));
 
                 if !lexicon.has_transitions(next_node.lexicon_state + 1, Some(trans_sym)) {
                     if trans_sym >= lexicon.alphabet().initial_symbol_count() {
@@ -281,12 +426,12 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                             lexicon.alphabet().unknown(),
                         ) {
                             self.queue_lexicon_arcs(
-                                pool,
+                                arena,
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().unknown().unwrap(),
                                 transition.target().unwrap(),
-                                transition.weight().unwrap(),
+                                weight,
                                 1,
                                 output_nodes,
                             );
@@ -296,44 +441,41 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                             lexicon.alphabet().identity(),
                         ) {
                             self.queue_lexicon_arcs(
-                                pool,
+                                arena,
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().identity().unwrap(),
                                 transition.target().unwrap(),
-                                transition.weight().unwrap(),
+                                weight,
                                 1,
                                 output_nodes,
                             );
                         }
                     }
-                    next_m += 1;
                     continue;
                 }
 
                 self.queue_lexicon_arcs(
-                    pool,
+                    arena,
                     max_weight,
                     &next_node,
                     trans_sym,
                     transition.target().unwrap(),
-                    transition.weight().unwrap(),
+                    weight,
                     1,
                     output_nodes,
                 );
-
-                next_m += 1;
             }
         }
     }
 
     #[inline(always)]
-    fn consume_input<'a>(
+    fn consume_input(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
         let mutator = self.speller.mutator();
         let input_state = next_node.input_state as usize;
@@ -351,7 +493,7 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                     .has_transitions(next_node.mutator_state + 1, mutator.alphabet().identity())
                 {
                     self.queue_mutator_arcs(
-                        pool,
+                        arena,
                         max_weight,
                         &next_node,
                         mutator.alphabet().identity().unwrap(),
@@ -364,7 +506,7 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                     .has_transitions(next_node.mutator_state + 1, mutator.alphabet().unknown())
                 {
                     self.queue_mutator_arcs(
-                        pool,
+                        arena,
                         max_weight,
                         &next_node,
                         mutator.alphabet().unknown().unwrap(),
@@ -373,17 +515,17 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                 }
             }
         } else {
-            self.queue_mutator_arcs(pool, max_weight, &next_node, input_sym, output_nodes)
+            self.queue_mutator_arcs(arena, max_weight, &next_node, input_sym, output_nodes)
         }
     }
 
     #[inline(always)]
-    fn lexicon_consume<'a>(
+    fn lexicon_consume(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
         let mutator = self.speller.mutator();
         let lexicon = self.speller.lexicon();
@@ -403,7 +545,7 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                 let identity = mutator.alphabet().identity();
                 if lexicon.has_transitions(next_lexicon_state, identity) {
                     self.queue_lexicon_arcs(
-                        pool,
+                        arena,
                         max_weight,
                         &next_node,
                         identity.unwrap(),
@@ -417,7 +559,7 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
                 let unknown = mutator.alphabet().unknown();
                 if lexicon.has_transitions(next_lexicon_state, unknown) {
                     self.queue_lexicon_arcs(
-                        pool,
+                        arena,
                         max_weight,
                         &next_node,
                         unknown.unwrap(),
@@ -433,7 +575,7 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
         }
 
         self.queue_lexicon_arcs(
-            pool,
+            arena,
             max_weight,
             &next_node,
             input_sym,
@@ -446,7 +588,7 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
 
     #[inline(always)]
     fn update_weight_limit(&self, best_weight: Weight, suggestions: &[Suggestion]) -> Weight {
-        use std::cmp::Ordering::{Equal, Less};
+        use core::cmp::Ordering::{Equal, Less};
 
         let c = &self.config;
         let mut max_weight = c.max_weight.unwrap_or(f32::MAX);
@@ -469,98 +611,394 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
         max_weight
     }
 
+    /// What `SpellerConfig::input_profile` adds to a mutator transition
+    /// from `input` to `output`, either `None` for an epsilon.
+    #[inline(always)]
+    fn profile_penalty(&self, input: Option<SymbolNumber>, output: Option<SymbolNumber>) -> Weight {
+        let profile = match self.config.input_profile {
+            Some(profile) => profile,
+            None => return 0.0,
+        };
+
+        let key_table = self.speller.mutator().alphabet().key_table();
+        profile.penalty(
+            input.map(|i| &*key_table[i as usize]),
+            output.map(|o| &*key_table[o as usize]),
+        )
+    }
+
     #[inline(always)]
     fn is_under_weight_limit(&self, max_weight: Weight, w: Weight) -> bool {
         w <= max_weight
     }
 
+    /// `node` after one more error model edit, or `None` if that takes it
+    /// past `max_edit_distance`.
     #[inline(always)]
+    fn edited(&self, node: &TreeNode) -> Option<TreeNode> {
+        let node = node.edited();
+        match self.config.max_edit_distance {
+            Some(max) if node.edits > max => None,
+            _ => Some(node),
+        }
+    }
+
+    #[inline(always)]
+    fn is_past_deadline(&self) -> bool {
+        match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+
     fn state_size(&self) -> usize {
         self.speller.lexicon().alphabet().state_size() as usize
     }
 
     pub fn is_correct(&self) -> bool {
         let max_weight = speller_max_weight(&self.config);
-        let pool = Pool::with_size_and_max(0, 0);
-        let mut nodes = speller_start_node(&pool, self.state_size() as usize);
+        let metrics = self.speller.metrics();
+
+        with_pool(
+            self.state_size(),
+            &self.config,
+            self.speller.max_search_bytes(),
+            &*metrics,
+            |arena, nodes| {
+                while let Some(next_node) = nodes.pop() {
+                    if next_node.input_state as usize == self.input.len()
+                        && self.speller.lexicon().is_final(next_node.lexicon_state)
+                    {
+                        return true;
+                    }
+
+                    self.lexicon_epsilons(arena, max_weight, &next_node, nodes);
+                    self.lexicon_consume(arena, max_weight, &next_node, nodes);
+                }
+
+                false
+            },
+        )
+    }
+
+    /// The lightest weight the lexicon gives the input, if it accepts it.
+    pub fn lexicon_weight(&self) -> Option<Weight> {
+        let max_weight = speller_max_weight(&self.config);
+        let metrics = self.speller.metrics();
+        let lexicon = self.speller.lexicon();
 
-        while let Some(next_node) = nodes.pop() {
-            if next_node.input_state as usize == self.input.len()
-                && self.speller.lexicon().is_final(next_node.lexicon_state)
-            {
-                return true;
-            }
+        with_pool(
+            self.state_size(),
+            &self.config,
+            self.speller.max_search_bytes(),
+            &*metrics,
+            |arena, nodes| {
+                let mut best: Option<Weight> = None;
+
+                while let Some(next_node) = nodes.pop() {
+                    if next_node.input_state as usize == self.input.len()
+                        && lexicon.is_final(next_node.lexicon_state)
+                    {
+                        let weight = next_node.weight()
+                            + lexicon.final_weight(next_node.lexicon_state).unwrap();
+                        best = Some(best.map_or(weight, |best| best.min(weight)));
+                    }
 
-            self.lexicon_epsilons(&pool, max_weight, &next_node, &mut nodes);
-            self.lexicon_consume(&pool, max_weight, &next_node, &mut nodes);
-        }
+                    self.lexicon_epsilons(arena, max_weight, &next_node, nodes);
+                    self.lexicon_consume(arena, max_weight, &next_node, nodes);
+                }
 
-        false
+                best
+            },
+        )
     }
 
     pub fn suggest(self: Arc<Self>) -> Vec<Suggestion> {
-        let pool = Pool::with_size_and_max(self.config.pool_start, self.config.pool_max);
-        let mut nodes = speller_start_node(&pool, self.state_size() as usize);
+        trace_span!("search", input_len = self.input.len());
+
+        let metrics = self.speller.metrics();
+
+        with_pool(
+            self.state_size(),
+            &self.config,
+            self.speller.max_search_bytes(),
+            &*metrics,
+            |arena, nodes| self.search(arena, nodes, &*metrics, None),
+        )
+    }
+
+    /// Like `suggest`, also recording every decision the search makes in
+    /// `steps`; see `replay`.
+    pub fn suggest_traced(self: Arc<Self>, steps: &mut Vec<Step>) -> Vec<Suggestion> {
+        let metrics = self.speller.metrics();
+
+        with_pool(
+            self.state_size(),
+            &self.config,
+            self.speller.max_search_bytes(),
+            &*metrics,
+            |arena, nodes| self.search(arena, nodes, &*metrics, Some(steps)),
+        )
+    }
+
+    fn search(
+        &self,
+        arena: &mut NodeArena,
+        nodes: &mut Vec<TreeNode>,
+        metrics: &dyn Metrics,
+        mut trace: Option<&mut Vec<Step>>,
+    ) -> Vec<Suggestion> {
+        let cache = self.speller.prefix_cache();
+        let input_len = self.input.len();
+
+        // Nodes that consume the last input symbol, kept for the next query
+        // when the cache is on; see `prefix_cache`.
+        let mut frontier = match self.config.prefix_cache {
+            Some(_) if input_len > 0 => Some(vec![]),
+            _ => None,
+        };
+
+        if frontier.is_some() {
+            let cached = cache.longest_prefix(
+                &self.input,
+                self.config.max_weight,
+                self.config.max_edit_distance,
+                self.config.input_profile,
+            );
+            metrics.prefix_cache(cached.is_some());
+
+            if let Some((len, cached)) = cached {
+                nodes.clear();
+                nodes.extend(cached.iter().map(|node| arena.attach(node)));
+
+                if len == input_len {
+                    frontier = None;
+                }
+            }
+        }
+
+        let fixed_weight = speller_max_weight(&self.config);
+        let mut timed_out = false;
+        let mut seen = SeenSet::new(&self.config);
+        let search_limit = self.speller.max_search_bytes();
+        let mut cut_short = false;
         let mut corrections = HashMap::new();
         let mut suggestions: Vec<Suggestion> = vec![];
         let mut best_weight = self.config.max_weight.unwrap_or(f32::MAX);
         let key_table = self.speller.lexicon().alphabet().key_table();
+        let mut expanded = 0;
+        let mut beam = AdaptiveBeam::new(&self.config);
+        let mut symbols = Vec::with_capacity(self.input.len() * 2);
+        let mut string = String::with_capacity(self.input.len() * 2);
+
+        // The word searched for, spelled out only if there are filters to
+        // give it to.
+        let filters = self.speller.filters();
+        let word: String = if filters.is_empty() {
+            String::new()
+        } else {
+            let keys = self.speller.mutator().alphabet().key_table();
+            self.input.iter().map(|&s| &*keys[s as usize]).collect()
+        };
 
         while let Some(next_node) = nodes.pop() {
-            let max_weight = self.update_weight_limit(best_weight, &suggestions);
+            expanded += 1;
+            if expanded % DEADLINE_CHECK_INTERVAL == 0 {
+                if self.is_past_deadline() {
+                    timed_out = true;
+                    break;
+                }
+
+                if let Some(limit) = search_limit {
+                    if search_bytes(arena, nodes) + seen.memory_bytes() > limit {
+                        cut_short = true;
+                        if let Some(trace) = trace.as_mut() {
+                            trace.push(Step::OverMemory);
+                        }
+                        break;
+                    }
+                }
+
+                if let Some(beam) = beam.as_mut() {
+                    beam.adjust(&suggestions);
+                }
+            }
+
+            let max_weight = if frontier.is_some() && (next_node.input_state as usize) < input_len {
+                fixed_weight
+            } else if let Some(beam) = beam.as_ref() {
+                beam.limit(fixed_weight, &suggestions)
+            } else {
+                self.update_weight_limit(best_weight, &suggestions)
+            };
+
+            let under_limit = self.is_under_weight_limit(max_weight, next_node.weight());
+            if let Some(beam) = beam.as_mut() {
+                beam.observe(!under_limit && next_node.weight() <= fixed_weight);
+            }
 
-            if !self.is_under_weight_limit(max_weight, next_node.weight()) {
+            if !under_limit {
+                if let Some(trace) = trace.as_mut() {
+                    let outcome = Outcome::OverLimit { limit: max_weight };
+                    trace.push(Step::node(&next_node, outcome));
+                }
+                continue;
+            }
+
+            if !seen.visit(|| arena.hash(&next_node), next_node.weight()) {
+                if let Some(trace) = trace.as_mut() {
+                    trace.push(Step::node(&next_node, Outcome::Seen));
+                }
                 continue;
             }
 
-            self.lexicon_epsilons(&pool, max_weight, &next_node, &mut nodes);
-            self.mutator_epsilons(&pool, max_weight, &next_node, &mut nodes);
+            let stack_len = nodes.len();
+            self.lexicon_epsilons(arena, max_weight, &next_node, nodes);
+            self.mutator_epsilons(arena, max_weight, &next_node, nodes);
+
+            if next_node.input_state as usize != input_len {
+                let first = nodes.len();
+                self.consume_input(arena, max_weight, &next_node, nodes);
+
+                if let Some(trace) = trace.as_mut() {
+                    let children = nodes.len() - stack_len;
+                    trace.push(Step::node(&next_node, Outcome::Expanded { children }));
+                }
+
+                if next_node.input_state as usize + 1 == input_len {
+                    if let Some(found) = frontier.as_mut() {
+                        found.extend_from_slice(&nodes[first..]);
+                    }
+                    if frontier.as_ref().map(Vec::len).unwrap_or(0) > MAX_FRONTIER_NODES {
+                        frontier = None;
+                    }
+                }
 
-            if next_node.input_state as usize != self.input.len() {
-                self.consume_input(&pool, max_weight, &next_node, &mut nodes);
                 continue;
             }
 
+            if let Some(trace) = trace.as_mut() {
+                let children = nodes.len() - stack_len;
+                trace.push(Step::node(&next_node, Outcome::Expanded { children }));
+            }
+
             if !self.speller.mutator().is_final(next_node.mutator_state)
                 || !self.speller.lexicon().is_final(next_node.lexicon_state)
             {
                 continue;
             }
 
-            let weight = next_node.weight()
-                + self
-                    .speller
-                    .lexicon()
-                    .final_weight(next_node.lexicon_state)
-                    .unwrap()
-                + self
-                    .speller
-                    .mutator()
-                    .final_weight(next_node.mutator_state)
-                    .unwrap();
+            let semiring = self.config.semiring;
+            let weight = semiring.times(
+                next_node.weight(),
+                semiring.times(
+                    self.speller
+                        .lexicon()
+                        .final_weight(next_node.lexicon_state)
+                        .unwrap(),
+                    self.speller
+                        .mutator()
+                        .final_weight(next_node.mutator_state)
+                        .unwrap(),
+                ),
+            );
+
+            let weight = match self.speller.proper_noun_tag() {
+                Some(tag) if self.config.proper_nouns => {
+                    arena.string(&next_node, &mut symbols);
+                    if symbols.contains(&tag) {
+                        weight
+                    } else {
+                        semiring.times(weight, PROPER_NOUN_PREFERENCE)
+                    }
+                }
+                _ => weight,
+            };
+
+            if let Some(trace) = trace.as_mut() {
+                trace.push(Step::Final {
+                    weight,
+                    limit: max_weight,
+                });
+            }
 
             if !self.is_under_weight_limit(max_weight, weight) {
                 continue;
             }
-            let string: SmolStr = next_node
-                .string
-                .iter()
-                .map(|s| &*key_table[*s as usize])
-                .collect();
+
+            arena.string(&next_node, &mut symbols);
+            string.clear();
+            for s in &symbols {
+                let key = &key_table[*s as usize];
+                if !self.config.proper_nouns || !is_tag(key) {
+                    string.push_str(key);
+                }
+            }
+
+            if !filter::allows(&filters, &word, &string) {
+                continue;
+            }
 
             if weight < best_weight {
                 best_weight = weight;
             }
 
-            {
-                let entry = corrections.entry(string).or_insert(weight);
+            // Only a new or cheaper correction changes the suggestions, and
+            // only a new one needs its string stored.
+            let changed = match corrections.get_mut(string.as_str()) {
+                Some(entry) => {
+                    let combined = semiring.plus(*entry, weight);
+                    if combined < *entry {
+                        *entry = combined;
+                        Some(combined)
+                    } else {
+                        None
+                    }
+                }
+                None => {
+                    corrections.insert(SmolStr::new(&string), weight);
+                    Some(weight)
+                }
+            };
 
-                if *entry > weight {
-                    *entry = weight;
+            if let Some(weight) = changed {
+                if let Some(trace) = trace.as_mut() {
+                    trace.push(Step::Correction(SmolStr::new(&string), weight));
                 }
+                suggestions = self.generate_sorted_suggestions(&corrections);
             }
+        }
 
-            suggestions = self.generate_sorted_suggestions(&corrections);
+        trace_event!(
+            expanded = expanded,
+            suggestions = suggestions.len(),
+            timed_out = timed_out,
+            cut_short = cut_short,
+            "search finished"
+        );
+
+        self.speller
+            .search_memory()
+            .record(search_bytes(arena, nodes) + seen.memory_bytes(), cut_short);
+
+        if timed_out {
+            metrics.search_stopped(SearchStop::TimedOut);
+        } else if cut_short {
+            metrics.search_stopped(SearchStop::OverMemory);
+        }
+
+        if let (Some(found), Some(capacity), false) =
+            (frontier, self.config.prefix_cache, timed_out || cut_short)
+        {
+            let detached = found.iter().map(|node| arena.detach(node)).collect();
+            cache.insert(
+                self.input.clone(),
+                self.config.max_weight,
+                self.config.max_edit_distance,
+                self.config.input_profile,
+                detached,
+                capacity,
+            );
         }
 
         suggestions
@@ -576,6 +1014,7 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
             .collect();
 
         c.sort();
+        suggestion::within_gap(&mut c, self.config.max_weight_gap);
 
         if let Some(n) = self.config.n_best {
             c.truncate(n);
@@ -584,3 +1023,27 @@ impl<'t, T: Transducer + 't> SpellerWorker<T> {
         c
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_reset_gives_back_excess() {
+        let config = SpellerConfig {
+            pool_start: 4,
+            pool_max: 16,
+            ..SpellerConfig::default()
+        };
+        let mut pool = SearchPool::new();
+
+        pool.reset(1, &config);
+        assert_eq!(pool.nodes, vec![TreeNode::empty()]);
+        assert!(pool.nodes.capacity() >= 4);
+
+        pool.nodes.extend((0..1000).map(|_| TreeNode::empty()));
+        pool.reset(1, &config);
+        assert_eq!(pool.nodes.len(), 1);
+        assert!(pool.nodes.capacity() <= 16);
+    }
+}
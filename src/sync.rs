@@ -0,0 +1,40 @@
+//! Locks for the caches a speller shares between queries: parking_lot's
+//! with `std`, and `RefCell`s without it, where there are no threads to
+//! share with.
+
+#[cfg(feature = "std")]
+pub use parking_lot::{Mutex, RwLock};
+
+#[cfg(not(feature = "std"))]
+pub use self::single_threaded::{Mutex, RwLock};
+
+#[cfg(not(feature = "std"))]
+mod single_threaded {
+    use core::cell::{Ref, RefCell, RefMut};
+
+    #[derive(Debug, Default)]
+    pub struct Mutex<T>(RefCell<T>);
+
+    impl<T> Mutex<T> {
+        pub fn lock(&self) -> RefMut<'_, T> {
+            self.0.borrow_mut()
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct RwLock<T>(RefCell<T>);
+
+    impl<T> RwLock<T> {
+        pub fn new(value: T) -> RwLock<T> {
+            RwLock(RefCell::new(value))
+        }
+
+        pub fn read(&self) -> Ref<'_, T> {
+            self.0.borrow()
+        }
+
+        pub fn write(&self) -> RefMut<'_, T> {
+            self.0.borrow_mut()
+        }
+    }
+}
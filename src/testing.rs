@@ -0,0 +1,228 @@
+//! Invariants every speller should hold, as functions that language repos
+//! can run against their own archives from their test suites.
+//!
+//! Each check looks up one word and returns the first `Violation` it finds.
+//! `check_invariants` runs them all over a list of words, such as a corpus,
+//! and `assert_invariants` panics listing what it found. `single_edits` turns
+//! correct words into plausible misspellings to check as well.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::speller::suggestion::Suggestion;
+use crate::speller::{Speller, SpellerConfig};
+use crate::transducer::Transducer;
+use crate::types::Weight;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    /// A suggestion for `word` that `is_correct` rejects.
+    NotAccepted { word: String, suggestion: String },
+    /// `word` is correct, but isn't its own first suggestion; `first` is
+    /// what came first instead, if anything did.
+    CorrectNotFirst { word: String, first: Option<String> },
+    /// The suggestion at `index` weighs less than the one before it.
+    OutOfOrder {
+        word: String,
+        index: usize,
+        previous: Weight,
+        weight: Weight,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::NotAccepted { word, suggestion } => write!(
+                f,
+                "{}: suggestion {} is not accepted by the lexicon",
+                word, suggestion
+            ),
+            Violation::CorrectNotFirst { word, first: None } => {
+                write!(f, "{}: correct but has no suggestions", word)
+            }
+            Violation::CorrectNotFirst {
+                word,
+                first: Some(first),
+            } => write!(f, "{}: correct but {} is suggested first", word, first),
+            Violation::OutOfOrder {
+                word,
+                index,
+                previous,
+                weight,
+            } => write!(
+                f,
+                "{}: suggestion {} weighs {}, less than the {} before it",
+                word, index, weight, previous
+            ),
+        }
+    }
+}
+
+/// Every suggestion for `word` is itself a correct word.
+pub fn check_suggestions_accepted<T: Transducer>(
+    speller: &Arc<Speller<T>>,
+    word: &str,
+    config: &SpellerConfig,
+) -> Result<(), Violation> {
+    let suggestions = speller.clone().suggest_with_config(word, config);
+    suggestions_accepted(speller, word, &suggestions)
+}
+
+fn suggestions_accepted<T: Transducer>(
+    speller: &Arc<Speller<T>>,
+    word: &str,
+    suggestions: &[Suggestion],
+) -> Result<(), Violation> {
+    match suggestions
+        .iter()
+        .find(|s| !speller.clone().is_correct(s.value()))
+    {
+        Some(s) => Err(Violation::NotAccepted {
+            word: word.into(),
+            suggestion: s.value().into(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// If `word` is correct, it is its own first suggestion.
+pub fn check_correct_suggests_itself<T: Transducer>(
+    speller: &Arc<Speller<T>>,
+    word: &str,
+    config: &SpellerConfig,
+) -> Result<(), Violation> {
+    let suggestions = speller.clone().suggest_with_config(word, config);
+    correct_suggests_itself(speller, word, &suggestions)
+}
+
+fn correct_suggests_itself<T: Transducer>(
+    speller: &Arc<Speller<T>>,
+    word: &str,
+    suggestions: &[Suggestion],
+) -> Result<(), Violation> {
+    if !speller.clone().is_correct(word) {
+        return Ok(());
+    }
+
+    match suggestions.first() {
+        Some(first) if first.value() == word => Ok(()),
+        first => Err(Violation::CorrectNotFirst {
+            word: word.into(),
+            first: first.map(|s| s.value().into()),
+        }),
+    }
+}
+
+/// `suggestions`, as returned for `word`, never get lighter.
+pub fn check_weights_ordered(word: &str, suggestions: &[Suggestion]) -> Result<(), Violation> {
+    for (index, pair) in suggestions.windows(2).enumerate() {
+        if pair[1].weight() < pair[0].weight() {
+            return Err(Violation::OutOfOrder {
+                word: word.into(),
+                index: index + 1,
+                previous: pair[0].weight(),
+                weight: pair[1].weight(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs every check on each of `words`, suggesting for each once.
+pub fn check_invariants<T: Transducer, S: AsRef<str>>(
+    speller: &Arc<Speller<T>>,
+    words: &[S],
+    config: &SpellerConfig,
+) -> Vec<Violation> {
+    let mut violations = vec![];
+
+    for word in words {
+        let word = word.as_ref();
+        let suggestions = speller.clone().suggest_with_config(word, config);
+
+        let results = [
+            suggestions_accepted(speller, word, &suggestions),
+            correct_suggests_itself(speller, word, &suggestions),
+            check_weights_ordered(word, &suggestions),
+        ];
+        violations.extend(results.iter().cloned().filter_map(Result::err));
+    }
+
+    violations
+}
+
+/// Like `check_invariants`, panicking with every violation found.
+pub fn assert_invariants<T: Transducer, S: AsRef<str>>(
+    speller: &Arc<Speller<T>>,
+    words: &[S],
+    config: &SpellerConfig,
+) {
+    let violations = check_invariants(speller, words, config);
+    if violations.is_empty() {
+        return;
+    }
+
+    let mut message = format!("{} invariant violation(s):", violations.len());
+    for violation in &violations {
+        message.push_str(&format!("\n  {}", violation));
+    }
+    panic!("{}", message);
+}
+
+/// Every word one deletion, transposition or doubled letter away from
+/// `word`, for checking misspellings alongside the words they came from.
+pub fn single_edits(word: &str) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut edits = vec![];
+
+    for i in 0..chars.len() {
+        let mut deleted = chars.clone();
+        deleted.remove(i);
+        edits.push(deleted.into_iter().collect());
+
+        let mut doubled = chars.clone();
+        doubled.insert(i, chars[i]);
+        edits.push(doubled.into_iter().collect());
+
+        if i + 1 < chars.len() && chars[i] != chars[i + 1] {
+            let mut swapped = chars.clone();
+            swapped.swap(i, i + 1);
+            edits.push(swapped.into_iter().collect());
+        }
+    }
+
+    edits.sort();
+    edits.dedup();
+    edits.retain(|edit: &String| !edit.is_empty() && edit != word);
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordering_and_edits() {
+        let suggestions = vec![
+            Suggestion::new("giella".into(), 1.0),
+            Suggestion::new("gielas".into(), 3.0),
+            Suggestion::new("gilla".into(), 2.5),
+        ];
+        assert_eq!(
+            check_weights_ordered("gielaa", &suggestions),
+            Err(Violation::OutOfOrder {
+                word: "gielaa".into(),
+                index: 2,
+                previous: 3.0,
+                weight: 2.5,
+            })
+        );
+        assert_eq!(check_weights_ordered("gielaa", &suggestions[..2]), Ok(()));
+
+        assert_eq!(single_edits("abb"), vec!["aabb", "ab", "abbb", "bab", "bb"]);
+    }
+}
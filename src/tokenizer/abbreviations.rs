@@ -0,0 +1,110 @@
+//! Abbreviations and acronyms a language writes that its lexicon may not
+//! have, such as "s.b." or "UiT".
+//!
+//! An archive can bring its own in an `abbreviations.txt` entry, one per
+//! line, with `#` starting a comment line. Abbreviations ending in a full
+//! stop include it, so that "s.b." is known from "s.b" followed by one, and
+//! a sentence doesn't end after it. Matching is exact, as acronyms are
+//! written with their capitals.
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use smol_str::SmolStr;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Abbreviations {
+    words: BTreeSet<SmolStr>,
+}
+
+impl Abbreviations {
+    pub fn new() -> Abbreviations {
+        Abbreviations::default()
+    }
+
+    /// Reads one abbreviation per line, skipping blank lines and lines
+    /// starting with `#`.
+    pub fn parse(data: &str) -> Abbreviations {
+        data.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect()
+    }
+
+    pub fn insert(&mut self, word: &str) {
+        self.words.insert(SmolStr::from(word));
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(word)
+    }
+
+    /// The abbreviations in `text`, with their byte offsets: pieces of it
+    /// between white space that are one, leaving out any brackets or quotes
+    /// around them, and the punctuation after them unless it is their own
+    /// full stop. Found this way rather than from words, as word segmentation
+    /// splits some abbreviations and not others.
+    pub fn find<'a>(&self, text: &'a str) -> Vec<(usize, &'a str)> {
+        let mut found = vec![];
+        if self.words.is_empty() {
+            return found;
+        }
+
+        let mut start = 0;
+        for piece in text.split(char::is_whitespace) {
+            let offset = start;
+            start += piece.len()
+                + text[start + piece.len()..]
+                    .chars()
+                    .next()
+                    .map_or(0, char::len_utf8);
+
+            let trimmed = piece.trim_start_matches(|c: char| !c.is_alphanumeric());
+            let offset = offset + piece.len() - trimmed.len();
+
+            let stopped = trimmed.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '.');
+            let bare = trimmed.trim_end_matches(|c: char| !c.is_alphanumeric());
+            if self.contains(stopped) {
+                found.push((offset, stopped));
+            } else if !bare.is_empty() && self.contains(bare) {
+                found.push((offset, bare));
+            }
+        }
+
+        found
+    }
+
+    /// Whether the `.` at byte `stop` in `text` ends an abbreviation, going
+    /// back to the white space before it.
+    pub fn ends_at(&self, text: &str, stop: usize) -> bool {
+        if self.words.is_empty() {
+            return false;
+        }
+
+        let start = text[..stop]
+            .rfind(char::is_whitespace)
+            .map(|i| i + text[i..].chars().next().map_or(1, char::len_utf8))
+            .unwrap_or(0);
+        let word = text[start..=stop].trim_start_matches(|c: char| !c.is_alphanumeric());
+        self.contains(word)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.words.iter().map(|x| &**x)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+impl<'a> core::iter::FromIterator<&'a str> for Abbreviations {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Abbreviations {
+        Abbreviations {
+            words: iter.into_iter().map(SmolStr::from).collect(),
+        }
+    }
+}
@@ -1,5 +1,12 @@
+use alloc::borrow::ToOwned;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use hashbrown::{HashMap, HashSet};
+use serde_derive::{Deserialize, Serialize};
 use smol_str::SmolStr;
 
+use crate::types::SymbolNumber;
+
 fn trim_start(alphabet: &[SmolStr], word: &str) -> SmolStr {
     word.trim_start_matches(|x: char| !alphabet.contains(&SmolStr::from(x.to_string())))
         .into()
@@ -86,6 +93,234 @@ pub fn is_first_caps(word: &str) -> bool {
     upper_first(word) == word
 }
 
+/// How a word is capitalized. Serialized in snake case, as `"first_caps"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Caps {
+    /// No upper case letters, which includes words without letters.
+    Lower,
+    /// Only the first letter upper case, as at the start of a sentence.
+    FirstCaps,
+    AllCaps,
+    /// Any other mix, such as `McDonald` or `iPhone`.
+    Mixed,
+}
+
+impl Caps {
+    pub fn of(word: &str) -> Caps {
+        let lower = lower_case(word);
+
+        if lower == word {
+            Caps::Lower
+        } else if upper_first(&lower) == word {
+            Caps::FirstCaps
+        } else if is_all_caps(word) {
+            Caps::AllCaps
+        } else {
+            Caps::Mixed
+        }
+    }
+}
+
+/// Case mappings for a transducer's alphabet, built once when a speller is
+/// created so that case variants come from table lookups rather than a scan
+/// of the alphabet for every character of every query.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "CaseTableParts", into = "CaseTableParts")]
+pub struct CaseTable {
+    /// Symbols that are a single character and not punctuation. Variants
+    /// trim any other characters from the ends of a word.
+    letters: HashSet<char>,
+    /// The alphabet's letters whose other case is a single character.
+    to_lower: HashMap<char, char>,
+    to_upper: HashMap<char, char>,
+    /// For each symbol number, the symbol for its other case, if it differs
+    /// and is in the alphabet.
+    lower_symbols: Vec<Option<SymbolNumber>>,
+    upper_symbols: Vec<Option<SymbolNumber>>,
+}
+
+/// A `CaseTable` as serialized, with its maps as lists.
+#[derive(Serialize, Deserialize)]
+struct CaseTableParts {
+    letters: Vec<char>,
+    to_lower: Vec<(char, char)>,
+    to_upper: Vec<(char, char)>,
+    lower_symbols: Vec<Option<SymbolNumber>>,
+    upper_symbols: Vec<Option<SymbolNumber>>,
+}
+
+impl From<CaseTable> for CaseTableParts {
+    fn from(table: CaseTable) -> CaseTableParts {
+        CaseTableParts {
+            letters: table.letters.into_iter().collect(),
+            to_lower: table.to_lower.into_iter().collect(),
+            to_upper: table.to_upper.into_iter().collect(),
+            lower_symbols: table.lower_symbols,
+            upper_symbols: table.upper_symbols,
+        }
+    }
+}
+
+impl From<CaseTableParts> for CaseTable {
+    fn from(parts: CaseTableParts) -> CaseTable {
+        CaseTable {
+            letters: parts.letters.into_iter().collect(),
+            to_lower: parts.to_lower.into_iter().collect(),
+            to_upper: parts.to_upper.into_iter().collect(),
+            lower_symbols: parts.lower_symbols,
+            upper_symbols: parts.upper_symbols,
+        }
+    }
+}
+
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Some(ch),
+        _ => None,
+    }
+}
+
+impl CaseTable {
+    pub fn new(alphabet: &[SmolStr]) -> CaseTable {
+        let letters: HashSet<char> = without_punctuation(alphabet)
+            .iter()
+            .filter_map(|s| single_char(s))
+            .collect();
+
+        let mut to_lower = HashMap::new();
+        let mut to_upper = HashMap::new();
+        for &ch in &letters {
+            if let Some(lower) = single_char(&ch.to_lowercase().collect::<String>()) {
+                to_lower.insert(ch, lower);
+            }
+            if let Some(upper) = single_char(&ch.to_uppercase().collect::<String>()) {
+                to_upper.insert(ch, upper);
+            }
+        }
+
+        let numbers: HashMap<&str, SymbolNumber> = alphabet
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.as_str(), i as SymbolNumber))
+            .collect();
+        let other_case = |f: fn(&str) -> SmolStr| -> Vec<Option<SymbolNumber>> {
+            alphabet
+                .iter()
+                .map(|s| {
+                    let other = f(s);
+                    if other == *s {
+                        None
+                    } else {
+                        numbers.get(other.as_str()).cloned()
+                    }
+                })
+                .collect()
+        };
+        let lower_symbols = other_case(lower_case);
+        let upper_symbols = other_case(upper_case);
+
+        CaseTable {
+            letters,
+            to_lower,
+            to_upper,
+            lower_symbols,
+            upper_symbols,
+        }
+    }
+
+    /// Whether `ch` is one of the alphabet's letters.
+    pub fn is_letter(&self, ch: char) -> bool {
+        self.letters.contains(&ch)
+    }
+
+    pub fn lower_symbol(&self, symbol: SymbolNumber) -> Option<SymbolNumber> {
+        self.lower_symbols.get(symbol as usize).and_then(|s| *s)
+    }
+
+    pub fn upper_symbol(&self, symbol: SymbolNumber) -> Option<SymbolNumber> {
+        self.upper_symbols.get(symbol as usize).and_then(|s| *s)
+    }
+
+    fn push_lower(&self, ch: char, out: &mut String) {
+        match self.to_lower.get(&ch) {
+            Some(lower) => out.push(*lower),
+            None => out.extend(ch.to_lowercase()),
+        }
+    }
+
+    fn push_upper(&self, ch: char, out: &mut String) {
+        match self.to_upper.get(&ch) {
+            Some(upper) => out.push(*upper),
+            None => out.extend(ch.to_uppercase()),
+        }
+    }
+
+    /// As the free `lower_case`, using the table for the alphabet's letters.
+    pub fn lower_case(&self, s: &str) -> SmolStr {
+        let mut out = String::with_capacity(s.len());
+        s.chars().for_each(|ch| self.push_lower(ch, &mut out));
+        SmolStr::new(out)
+    }
+
+    pub fn upper_case(&self, s: &str) -> SmolStr {
+        let mut out = String::with_capacity(s.len());
+        s.chars().for_each(|ch| self.push_upper(ch, &mut out));
+        SmolStr::new(out)
+    }
+
+    pub fn upper_first(&self, s: &str) -> SmolStr {
+        let mut chars = s.chars();
+        let mut out = String::with_capacity(s.len());
+        if let Some(first) = chars.next() {
+            self.push_upper(first, &mut out);
+        }
+        out.push_str(chars.as_str());
+        SmolStr::new(out)
+    }
+
+    pub fn is_all_caps(&self, word: &str) -> bool {
+        self.upper_case(word) == word
+    }
+
+    pub fn is_first_caps(&self, word: &str) -> bool {
+        self.upper_first(word) == word
+    }
+
+    /// As the free `word_variants`, for the alphabet the table was built
+    /// from.
+    pub fn word_variants(&self, word: &str) -> Vec<SmolStr> {
+        let not_letter = |ch: char| !self.is_letter(ch);
+
+        let mut base: Vec<SmolStr> = vec![
+            word.into(),
+            word.trim_start_matches(not_letter).into(),
+            word.trim_end_matches(not_letter).into(),
+            word.trim_matches(not_letter).into(),
+        ];
+
+        let capitalized: Vec<SmolStr> = base
+            .iter()
+            .filter(|x| self.is_all_caps(x))
+            .map(|x| self.upper_first(&self.lower_case(x)))
+            .collect();
+        base.extend(capitalized);
+
+        let lowered: Vec<SmolStr> = base.iter().map(|x| self.lower_case(x)).collect();
+        base.extend(lowered);
+
+        let mut ret = vec![];
+        for b in base.into_iter() {
+            if !ret.contains(&b) {
+                ret.push(b);
+            }
+        }
+
+        ret
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,4 +336,35 @@ mod tests {
         println!("{:?}", word_variants(&a, "abc"));
         println!("{:?}", word_variants(&a, "$GIELLA$"));
     }
+
+    #[test]
+    fn case_table_matches_variants() {
+        let a = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZáÁđĐ"
+            .chars()
+            .map(|c| SmolStr::from(c.to_string()))
+            .chain(vec![SmolStr::from("."), SmolStr::from("@_SAME_@")])
+            .collect::<Vec<SmolStr>>();
+        let table = CaseTable::new(&a);
+
+        for word in &[
+            "FOO", "Giella", "abc", "$GIELLA$", "ÁĐA.", "ǅemal", "straße", "",
+        ] {
+            assert_eq!(table.word_variants(word), word_variants(&a, word));
+        }
+
+        assert_eq!(table.lower_symbol(26), Some(0));
+        assert_eq!(table.upper_symbol(52), Some(53));
+        assert_eq!(table.upper_symbol(26), None);
+        assert_eq!(table.lower_symbol(57), None);
+    }
+
+    #[test]
+    fn caps_of() {
+        assert_eq!(Caps::of("giella"), Caps::Lower);
+        assert_eq!(Caps::of("123"), Caps::Lower);
+        assert_eq!(Caps::of("Giella"), Caps::FirstCaps);
+        assert_eq!(Caps::of("Á"), Caps::FirstCaps);
+        assert_eq!(Caps::of("ÁĐA"), Caps::AllCaps);
+        assert_eq!(Caps::of("McDonald"), Caps::Mixed);
+    }
 }
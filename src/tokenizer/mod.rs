@@ -1,12 +1,26 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use unic_segment::{WordBoundIndices, Words};
 
+pub mod abbreviations;
 pub mod caps;
 
+use self::abbreviations::Abbreviations;
+
+/// Word segmentation, from `unic-segment`, which needs `std`.
+#[cfg(feature = "std")]
 pub trait Tokenize {
     fn word_bound_indices(&self) -> WordBoundIndices;
     fn words(&self) -> Words;
+    /// Sentences with their byte offsets, covering the whole text. See
+    /// `sentence_bound_indices`.
+    fn sentence_bound_indices(&self) -> Vec<(usize, &str)>;
+    /// As `sentence_bound_indices`, not ending a sentence after one of
+    /// `abbreviations`.
+    fn sentence_bound_indices_with(&self, abbreviations: &Abbreviations) -> Vec<(usize, &str)>;
 }
 
+#[cfg(feature = "std")]
 impl Tokenize for str {
     fn word_bound_indices(&self) -> WordBoundIndices {
         WordBoundIndices::new(self)
@@ -15,12 +29,109 @@ impl Tokenize for str {
     fn words(&self) -> Words {
         Words::new(self, |s| s.chars().any(|ch| ch.is_alphanumeric()))
     }
+
+    fn sentence_bound_indices(&self) -> Vec<(usize, &str)> {
+        sentence_bound_indices(self)
+    }
+
+    fn sentence_bound_indices_with(&self, abbreviations: &Abbreviations) -> Vec<(usize, &str)> {
+        sentence_bound_indices_with(self, abbreviations)
+    }
+}
+
+fn is_terminal(ch: char) -> bool {
+    matches!(ch, '.' | '!' | '?' | '…' | '‼' | '⁇' | '⁈' | '⁉')
+}
+
+fn is_closing(ch: char) -> bool {
+    matches!(ch, '"' | '\'' | ')' | ']' | '»' | '”' | '’')
+}
+
+/// Splits `text` after sentence-final punctuation, with any closing quotes
+/// or brackets, that is followed by white space, and after line breaks. The
+/// white space stays with the sentence before it. Abbreviations such as
+/// "e.g." followed by a space end a sentence too; this is meant for choosing
+/// a language or spotting sentence-initial words, not for linguistics.
+pub fn sentence_bound_indices(text: &str) -> Vec<(usize, &str)> {
+    sentence_bound_indices_with(text, &Abbreviations::new())
+}
+
+/// As `sentence_bound_indices`, except that a full stop ending one of
+/// `abbreviations`, such as "s.b.", doesn't end the sentence.
+pub fn sentence_bound_indices_with<'a>(
+    text: &'a str,
+    abbreviations: &Abbreviations,
+) -> Vec<(usize, &'a str)> {
+    let mut sentences = vec![];
+    let mut start = 0;
+    let mut ended = false;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, ch)) = chars.next() {
+        if ch == '\n' {
+            ended = true;
+        } else if ch == '.' && abbreviations.ends_at(text, i) {
+            continue;
+        } else if is_terminal(ch) {
+            while let Some(&(_, next)) = chars.peek() {
+                if is_terminal(next) || is_closing(next) {
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            ended = chars.peek().is_some_and(|&(_, next)| next.is_whitespace());
+            continue;
+        } else if ended && !ch.is_whitespace() {
+            sentences.push((start, &text[start..i]));
+            start = i;
+            ended = false;
+        }
+    }
+
+    if start < text.len() {
+        sentences.push((start, &text[start..]));
+    }
+
+    sentences
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn sentences() {
+        let text = "Mun lean sámi. \"Jeg er norsk!\" Ok?!\nNy linje 2.5 km.";
+        let sentences: Vec<&str> = sentence_bound_indices(text)
+            .into_iter()
+            .map(|(_, s)| s)
+            .collect();
+
+        assert_eq!(
+            sentences,
+            vec![
+                "Mun lean sámi. ",
+                "\"Jeg er norsk!\" ",
+                "Ok?!\n",
+                "Ny linje 2.5 km."
+            ]
+        );
+        assert_eq!(sentence_bound_indices("").len(), 0);
+
+        let abbreviations = Abbreviations::parse("# Northern Sami\ns.b.\nUiT\n");
+        let text = "Dat lea (s.b. UiT) buorre. Mun lean dáppe.";
+        let sentences: Vec<&str> = sentence_bound_indices_with(text, &abbreviations)
+            .into_iter()
+            .map(|(_, s)| s)
+            .collect();
+        assert_eq!(
+            sentences,
+            vec!["Dat lea (s.b. UiT) buorre. ", "Mun lean dáppe."]
+        );
+        assert_eq!(abbreviations.find(text), vec![(9, "s.b."), (14, "UiT")]);
+    }
+
     #[test]
     fn basic() {
         let msg = "this is an ordinary sentence! \"This was quoted,\", an emoji: (😄), and\t a tab was there and a new line.\n Some extreme unicode; bismala: (﷽), in long form: بِسْمِ اللهِ الرَّحْمٰنِ الرَّحِيْمِ.";
@@ -0,0 +1,32 @@
+//! Instrumentation for the `tracing` feature. Archive loading, transducer
+//! setup and each phase of a suggestion (case variants, search, reranking)
+//! run inside a `debug` level span, so an application's own subscriber can
+//! time them in production. The library never prints to stderr itself:
+//! progress is logged at `debug` level and problems with a transducer at
+//! `warn`. Without the feature these macros expand to nothing.
+
+/// Enters a `tracing::debug_span!` for the rest of the enclosing block.
+macro_rules! trace_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!($($arg)*);
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+    };
+}
+
+/// Emits a `tracing::debug!` event.
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+/// Emits a `tracing::warn!` event.
+macro_rules! trace_warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    };
+}
@@ -1,4 +1,7 @@
-use super::Transducer;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{Transducer, TransducerError};
 use crate::types::{FlagDiacriticOperation, FlagDiacriticOperator, SymbolNumber, ValueNumber};
 use hashbrown::HashMap;
 use smol_str::SmolStr;
@@ -48,10 +51,21 @@ impl TransducerAlphabetParser {
         }
     }
 
-    fn handle_special_symbol(&mut self, i: SymbolNumber, key: &str) {
+    /// The value number after `val_n`, as of symbol `i`. Flag values are
+    /// numbered in an `i16`, which an alphabet can have more symbols than.
+    fn next_value(&self, i: SymbolNumber) -> Result<ValueNumber, TransducerError> {
+        self.val_n
+            .checked_add(1)
+            .ok_or(TransducerError::InvalidFlag(i))
+    }
+
+    fn handle_special_symbol(&mut self, i: SymbolNumber, key: &str) -> Result<(), TransducerError> {
         let mut chunks = key.split('.');
 
-        let fdo = FlagDiacriticOperator::from_str(&chunks.next().unwrap()[1..]).unwrap();
+        let fdo = chunks
+            .next()
+            .and_then(|op| FlagDiacriticOperator::from_str(&op[1..]))
+            .ok_or(TransducerError::InvalidFlag(i))?;
         let feature: SmolStr = chunks
             .next()
             .unwrap_or("")
@@ -72,7 +86,7 @@ impl TransducerAlphabetParser {
 
         if !self.value_bucket.contains_key(&value) {
             self.value_bucket.insert(value.clone(), self.val_n);
-            self.val_n += 1;
+            self.val_n = self.next_value(i)?;
         }
 
         let op = FlagDiacriticOperation {
@@ -83,27 +97,27 @@ impl TransducerAlphabetParser {
 
         self.operations.insert(i, op);
         self.key_table.push(key.into());
+        Ok(())
     }
 
-    fn parse_inner(&mut self, buf: &[u8], symbols: SymbolNumber) {
+    fn parse_inner(&mut self, buf: &[u8], symbols: SymbolNumber) -> Result<(), TransducerError> {
         let mut offset = 0usize;
 
         for i in 0..symbols {
-            let mut end = 0usize;
-
-            while buf[offset + end] != 0 {
-                end += 1;
-            }
+            let end = buf
+                .get(offset..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or(TransducerError::Truncated)?;
 
             let key: SmolStr = String::from_utf8_lossy(&buf[offset..offset + end]).into();
 
             if key.len() > 1 && key.starts_with('@') && key.ends_with('@') {
-                if key.chars().nth(2).unwrap() == '.' {
-                    self.handle_special_symbol(i, &key);
+                if key.chars().nth(2) == Some('.') {
+                    self.handle_special_symbol(i, &key)?;
                 } else if key == "@_EPSILON_SYMBOL_@" {
                     self.value_bucket.insert("".into(), self.val_n);
                     self.key_table.push("".into());
-                    self.val_n += 1;
+                    self.val_n = self.next_value(i)?;
                 } else if key == "@_IDENTITY_SYMBOL_@" {
                     self.identity_symbol = Some(i);
                     self.key_table.push(key);
@@ -112,7 +126,7 @@ impl TransducerAlphabetParser {
                     self.key_table.push(key);
                 } else {
                     // No idea, skip.
-                    eprintln!("Unhandled alphabet key: {}", &key);
+                    trace_warn!(key = &*key, "unhandled alphabet key");
                     self.key_table.push(SmolStr::from(""));
                 }
             } else {
@@ -126,18 +140,19 @@ impl TransducerAlphabetParser {
         self.flag_state_size = self.feature_bucket.len() as SymbolNumber;
 
         // Count remaining null padding bytes
-        while buf[offset] == b'\0' {
+        while buf.get(offset) == Some(&b'\0') {
             offset += 1;
         }
 
         self.length = offset;
+        Ok(())
     }
 
-    fn parse(buf: &[u8], symbols: SymbolNumber) -> TransducerAlphabet {
+    fn parse(buf: &[u8], symbols: SymbolNumber) -> Result<TransducerAlphabet, TransducerError> {
         let mut p = TransducerAlphabetParser::new();
-        p.parse_inner(buf, symbols);
+        p.parse_inner(buf, symbols)?;
 
-        TransducerAlphabet {
+        Ok(TransducerAlphabet {
             key_table: p.key_table,
             initial_symbol_count: symbols,
             length: p.length,
@@ -146,12 +161,15 @@ impl TransducerAlphabetParser {
             operations: p.operations,
             identity_symbol: p.identity_symbol,
             unknown_symbol: p.unknown_symbol,
-        }
+        })
     }
 }
 
 impl TransducerAlphabet {
-    pub fn new(buf: &[u8], symbols: SymbolNumber) -> TransducerAlphabet {
+    /// Reads `symbols` null terminated symbols from the start of `buf`,
+    /// failing if `buf` ends before they do or a flag diacritic among them
+    /// is malformed.
+    pub fn new(buf: &[u8], symbols: SymbolNumber) -> Result<TransducerAlphabet, TransducerError> {
         TransducerAlphabetParser::parse(buf, symbols)
     }
 
@@ -221,3 +239,157 @@ impl TransducerAlphabet {
         translator
     }
 }
+
+/// Converting between text and symbol numbers, for tools that walk a
+/// transducer themselves rather than through a speller.
+impl TransducerAlphabet {
+    /// The text `symbol` stands for: its key, or nothing for epsilon, flag
+    /// diacritics, the identity and unknown symbols and symbols not in the
+    /// alphabet, which stand for no fixed text.
+    pub fn symbol_text(&self, symbol: SymbolNumber) -> &str {
+        if self.is_flag(symbol)
+            || Some(symbol) == self.identity_symbol
+            || Some(symbol) == self.unknown_symbol
+        {
+            return "";
+        }
+
+        self.key_table
+            .get(symbol as usize)
+            .map(|key| &**key)
+            .unwrap_or("")
+    }
+
+    /// The text `symbols` spell, each as `symbol_text` gives it.
+    pub fn symbols_to_string(&self, symbols: &[SymbolNumber]) -> String {
+        symbols.iter().map(|&s| self.symbol_text(s)).collect()
+    }
+
+    /// `text` as this alphabet's symbols, taking at each point the longest
+    /// symbol it starts with, so that a multicharacter symbol such as "ij"
+    /// is preferred to "i" followed by "j". `None` if some part of `text`
+    /// starts no symbol.
+    pub fn string_to_symbols(&self, text: &str) -> Option<Vec<SymbolNumber>> {
+        let longest = self
+            .string_to_symbol
+            .keys()
+            .map(|key| key.chars().count())
+            .max()
+            .unwrap_or(0);
+        let boundaries: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(Some(text.len()))
+            .collect();
+
+        let mut symbols = Vec::with_capacity(text.len());
+        let mut at = 0;
+        while at + 1 < boundaries.len() {
+            let start = boundaries[at];
+            let (chars, symbol) = (1..=longest.min(boundaries.len() - 1 - at))
+                .rev()
+                .find_map(|n| {
+                    self.string_to_symbol
+                        .get(&text[start..boundaries[at + n]])
+                        .map(|&symbol| (n, symbol))
+                })?;
+            symbols.push(symbol);
+            at += chars;
+        }
+
+        Some(symbols)
+    }
+}
+
+/// `text` without the flag diacritics, such as `@U.Case.Gen@`, written into
+/// it by a lookup that kept them.
+pub fn strip_flags(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('@') {
+        let flag_len = rest[start + 1..]
+            .find('@')
+            .map(|end| end + 2)
+            .filter(|&len| is_flag_key(&rest[start..start + len]));
+
+        match flag_len {
+            Some(len) => {
+                out.push_str(&rest[..start]);
+                rest = &rest[start + len..];
+            }
+            None => {
+                out.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Whether `key` is written as a flag diacritic: `@`, an operator, `.`, a
+/// feature and optionally `.` and a value, then `@`.
+fn is_flag_key(key: &str) -> bool {
+    let bytes = key.as_bytes();
+    bytes.len() >= 5
+        && bytes[0] == b'@'
+        && bytes[bytes.len() - 1] == b'@'
+        && b"PNRDCU".contains(&bytes[1])
+        && bytes[2] == b'.'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alphabet() -> TransducerAlphabet {
+        let keys = [
+            "@_EPSILON_SYMBOL_@",
+            "a",
+            "i",
+            "j",
+            "ij",
+            "@P.Case.Gen@",
+            "+N",
+        ];
+        let mut buf = keys.join("\0").into_bytes();
+        buf.extend_from_slice(b"\0\0x");
+        TransducerAlphabet::new(&buf, keys.len() as SymbolNumber).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let alphabet = alphabet();
+
+        assert_eq!(alphabet.string_to_symbols("aija"), Some(vec![1, 4, 1]));
+        assert_eq!(alphabet.string_to_symbols("ji+N"), Some(vec![3, 2, 6]));
+        assert_eq!(alphabet.string_to_symbols("ab"), None);
+        assert_eq!(alphabet.symbols_to_string(&[1, 0, 4, 5, 1]), "aija");
+    }
+
+    #[test]
+    fn malformed() {
+        let buf = b"@_EPSILON_SYMBOL_@\0a\0b";
+        assert_eq!(
+            TransducerAlphabet::new(buf, 3).unwrap_err(),
+            TransducerError::Truncated
+        );
+        assert_eq!(TransducerAlphabet::new(buf, 2).unwrap().len(), 21);
+
+        let buf = b"@_EPSILON_SYMBOL_@\0@X.Case@\0";
+        assert_eq!(
+            TransducerAlphabet::new(buf, 2).unwrap_err(),
+            TransducerError::InvalidFlag(1)
+        );
+        assert!(TransducerAlphabet::new(b"@@\0", 1).is_ok());
+    }
+
+    #[test]
+    fn flags_stripped() {
+        assert_eq!(strip_flags("@P.Case.Gen@giella@U.Case.Gen@"), "giella");
+        assert_eq!(strip_flags("user@example.com"), "user@example.com");
+        assert_eq!(strip_flags("@_EPSILON_SYMBOL_@"), "@_EPSILON_SYMBOL_@");
+    }
+}
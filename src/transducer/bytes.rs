@@ -0,0 +1,111 @@
+//! Reads of little endian values out of transducer tables.
+//!
+//! Tables are packed, not aligned: index table rows are 6 bytes, so every
+//! other row's target starts at an odd offset, and a table copied to the
+//! heap may start anywhere. Every table read goes through `ByteAccess`,
+//! which reads with `ptr::read_unaligned`; this is the only unsafe code on
+//! the lookup path.
+//!
+//! The unchecked reads rely on one invariant, kept by the tables: no row at
+//! or past a table's `size` is read, and `size` is clamped when the table is
+//! built to the rows its buffer holds. The `get_*` reads are always safe.
+//!
+//! The tests here read from the heap rather than a mapping, so that they run
+//! under Miri: `cargo miri test --lib transducer::bytes`. The table tests
+//! read through the same functions, with `debug_assert`s on the invariant.
+
+use core::ptr;
+
+pub trait ByteAccess {
+    /// The little endian `u16` at `index`, or `None` if it runs past the
+    /// end.
+    fn get_u16(&self, index: usize) -> Option<u16>;
+    fn get_u32(&self, index: usize) -> Option<u32>;
+
+    #[cfg_attr(not(feature = "internals"), allow(dead_code))]
+    fn get_f32(&self, index: usize) -> Option<f32> {
+        self.get_u32(index).map(f32::from_bits)
+    }
+
+    /// Like `get_u16`, without the bounds check.
+    ///
+    /// # Safety
+    ///
+    /// `index + 2` must be at most the length.
+    unsafe fn read_u16(&self, index: usize) -> u16;
+
+    /// Like `get_u32`, without the bounds check.
+    ///
+    /// # Safety
+    ///
+    /// `index + 4` must be at most the length.
+    unsafe fn read_u32(&self, index: usize) -> u32;
+
+    /// Like `get_f32`, without the bounds check.
+    ///
+    /// # Safety
+    ///
+    /// `index + 4` must be at most the length.
+    #[cfg_attr(not(feature = "internals"), allow(dead_code))]
+    unsafe fn read_f32(&self, index: usize) -> f32 {
+        f32::from_bits(self.read_u32(index))
+    }
+}
+
+impl ByteAccess for [u8] {
+    #[inline(always)]
+    fn get_u16(&self, index: usize) -> Option<u16> {
+        if index.checked_add(2)? > self.len() {
+            return None;
+        }
+
+        // In bounds, as just checked.
+        Some(unsafe { self.read_u16(index) })
+    }
+
+    #[inline(always)]
+    fn get_u32(&self, index: usize) -> Option<u32> {
+        if index.checked_add(4)? > self.len() {
+            return None;
+        }
+
+        // In bounds, as just checked.
+        Some(unsafe { self.read_u32(index) })
+    }
+
+    #[inline(always)]
+    unsafe fn read_u16(&self, index: usize) -> u16 {
+        debug_assert!(index + 2 <= self.len());
+        u16::from_le(ptr::read_unaligned(self.as_ptr().add(index) as *const u16))
+    }
+
+    #[inline(always)]
+    unsafe fn read_u32(&self, index: usize) -> u32 {
+        debug_assert!(index + 4 <= self.len());
+        u32::from_le(ptr::read_unaligned(self.as_ptr().add(index) as *const u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unaligned_reads() {
+        // Starts one byte in, so that every value is misaligned.
+        let mut bytes = vec![0xffu8];
+        bytes.extend_from_slice(&0x1234u16.to_le_bytes());
+        bytes.extend_from_slice(&0xdead_beefu32.to_le_bytes());
+        bytes.extend_from_slice(&2.5f32.to_bits().to_le_bytes());
+        let bytes = &bytes[..];
+
+        assert_eq!(bytes.get_u16(1), Some(0x1234));
+        assert_eq!(bytes.get_u32(3), Some(0xdead_beef));
+        assert_eq!(bytes.get_f32(7), Some(2.5));
+        assert_eq!(unsafe { bytes.read_u32(3) }, 0xdead_beef);
+
+        assert_eq!(bytes.get_u16(10), None);
+        assert_eq!(bytes.get_u32(8), None);
+        assert_eq!(bytes.get_u32(usize::MAX - 1), None);
+    }
+}
@@ -1,17 +1,16 @@
-#![allow(clippy::cast_ptr_alignment)] // FIXME: This at least needs a comment
-
 use std::fs::File;
 use std::mem;
-use std::ptr;
 use std::{u16, u32};
 
 use crate::constants::TARGET_TABLE;
+use crate::transducer::bytes::ByteAccess;
 use crate::transducer::symbol_transition::SymbolTransition;
 use crate::types::{SymbolNumber, TransitionTableIndex, Weight};
 use memmap::Mmap;
 use serde_derive::{Deserialize, Serialize};
 
 mod alphabet;
+pub mod verify;
 
 use self::alphabet::TransducerAlphabetParser;
 use super::TransducerAlphabet;
@@ -47,12 +46,12 @@ pub struct MetaRecord {
 }
 
 impl MetaRecord {
-    pub fn serialize(&self, target_dir: &std::path::Path) {
+    pub fn serialize(&self, target_dir: &std::path::Path) -> std::io::Result<()> {
         use std::io::Write;
 
-        let s = serde_json::to_string_pretty(self).unwrap();
-        let mut f = std::fs::File::create(target_dir.join("meta")).unwrap();
-        writeln!(f, "{}", s).unwrap();
+        let s = serde_json::to_string_pretty(self)?;
+        let mut f = std::fs::File::create(target_dir.join("meta"))?;
+        writeln!(f, "{}", s)
     }
 }
 
@@ -78,8 +77,8 @@ impl IndexTable {
 
         let index = INDEX_TABLE_SIZE * i as usize;
 
-        let input_symbol: SymbolNumber =
-            unsafe { ptr::read(self.buf.as_ptr().add(index) as *const _) };
+        // `size` is the rows the buffer holds, so this is in bounds.
+        let input_symbol: SymbolNumber = unsafe { self.buf.read_u16(index) };
 
         if input_symbol == u16::MAX {
             None
@@ -94,8 +93,7 @@ impl IndexTable {
         }
 
         let index = (INDEX_TABLE_SIZE * i as usize) + 4;
-        let target: TransitionTableIndex =
-            unsafe { ptr::read(self.buf.as_ptr().add(index) as *const _) };
+        let target: TransitionTableIndex = unsafe { self.buf.read_u32(index) };
 
         if target == u32::MAX {
             None
@@ -112,7 +110,7 @@ impl IndexTable {
         }
 
         let index = (INDEX_TABLE_SIZE * i as usize) + 4;
-        let weight: Weight = unsafe { ptr::read(self.buf.as_ptr().add(index) as *const _) };
+        let weight: Weight = unsafe { self.buf.read_f32(index) };
 
         Some(weight)
     }
@@ -139,7 +137,8 @@ impl TransitionTable {
 
     #[inline]
     fn read_symbol_from_cursor(&self, index: usize) -> Option<SymbolNumber> {
-        let x = unsafe { ptr::read(self.buf.as_ptr().add(index) as *const _) };
+        // Only called for rows under `size`, the rows the buffer holds.
+        let x = unsafe { self.buf.read_u16(index) };
         if x == u16::MAX {
             None
         } else {
@@ -173,8 +172,7 @@ impl TransitionTable {
 
         let index = (TRANS_TABLE_SIZE * i as usize) + (2 * mem::size_of::<SymbolNumber>());
 
-        let x: TransitionTableIndex =
-            unsafe { ptr::read(self.buf.as_ptr().add(index) as *const _) };
+        let x: TransitionTableIndex = unsafe { self.buf.read_u32(index) };
         if x == u32::MAX {
             None
         } else {
@@ -189,7 +187,7 @@ impl TransitionTable {
 
         let index = (TRANS_TABLE_SIZE * i as usize) + 8;
 
-        let x: Weight = unsafe { ptr::read(self.buf.as_ptr().add(index) as *const _) };
+        let x: Weight = unsafe { self.buf.read_f32(index) };
 
         Some(x)
     }
@@ -406,6 +404,12 @@ impl Transducer for ChfstTransducer {
         }
     }
 
+    #[inline(always)]
+    fn transition(&self, i: TransitionTableIndex) -> SymbolTransition {
+        let (page, index) = self.transition_rel_index(i);
+        self.transition_tables[page].symbol_transition(index)
+    }
+
     #[inline(always)]
     fn next(&self, i: TransitionTableIndex, symbol: SymbolNumber) -> Option<TransitionTableIndex> {
         if i >= TARGET_TABLE {
@@ -442,3 +446,96 @@ impl ChfstBundle {
         Speller::new(self.mutator, self.lexicon)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::{test_buf, HfstTransducer, TransducerBuf};
+    use crate::transducer::{SerializeStage, TransducerSerializeError};
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use std::io::Write;
+
+    /// A weighted HFST transducer accepting only "a", with an index-table
+    /// start state and a transition-table final state.
+    fn tiny_hfst() -> Arc<TransducerBuf> {
+        let mut buf = vec![];
+        let header = b"version\0";
+
+        buf.write_all(b"HFST\0").unwrap();
+        buf.write_u16::<LittleEndian>(header.len() as u16).unwrap();
+        buf.write_u8(0).unwrap();
+        buf.write_all(header).unwrap();
+
+        buf.write_u16::<LittleEndian>(2).unwrap(); // input symbols
+        buf.write_u16::<LittleEndian>(2).unwrap(); // symbols
+        buf.write_u32::<LittleEndian>(3).unwrap(); // index table size
+        buf.write_u32::<LittleEndian>(3).unwrap(); // target table size
+        buf.write_u32::<LittleEndian>(2).unwrap(); // states
+        buf.write_u32::<LittleEndian>(1).unwrap(); // transitions
+        buf.write_u32::<LittleEndian>(1).unwrap(); // weighted
+        for _ in 1..9 {
+            buf.write_u32::<LittleEndian>(0).unwrap();
+        }
+
+        buf.write_all(b"@_EPSILON_SYMBOL_@\0a\0").unwrap();
+
+        // Index table: not final, no epsilons, "a" goes to transition 0
+        for &(sym, target) in &[
+            (u16::MAX, u32::MAX),
+            (u16::MAX, u32::MAX),
+            (1, TARGET_TABLE),
+        ] {
+            buf.write_u16::<LittleEndian>(sym).unwrap();
+            buf.write_u32::<LittleEndian>(target).unwrap();
+        }
+
+        // Transition table: a:a to transition 1, which is final
+        for &(input, output, target, weight) in &[
+            (1, 1, TARGET_TABLE + 1, 0.5f32),
+            (u16::MAX, u16::MAX, 1, 0.25),
+            (u16::MAX, u16::MAX, u32::MAX, 0.0),
+        ] {
+            buf.write_u16::<LittleEndian>(input).unwrap();
+            buf.write_u16::<LittleEndian>(output).unwrap();
+            buf.write_u32::<LittleEndian>(target).unwrap();
+            buf.write_f32::<LittleEndian>(weight).unwrap();
+        }
+
+        test_buf(&buf)
+    }
+
+    #[test]
+    fn round_trip() {
+        let hfst = HfstTransducer::from_mapped_memory(tiny_hfst()).unwrap();
+        let dir = tempdir::TempDir::new("divvunspell-chfst").unwrap();
+
+        // Small enough to split the transition table across chunks
+        let mut progress = vec![];
+        let report = hfst
+            .serialize_with_progress(24, dir.path(), |p| progress.push(p))
+            .unwrap();
+        assert!(report.transition_table_chunks > 1);
+        match hfst.serialize(16, dir.path()) {
+            Err(TransducerSerializeError::InvalidChunkSize) => {}
+            _ => panic!("expected 16 to be refused as a chunk size"),
+        }
+        assert_eq!(
+            progress
+                .iter()
+                .filter(|p| p.stage == SerializeStage::TransitionTable)
+                .count(),
+            report.transition_table_chunks
+        );
+        assert_eq!(progress.last().unwrap().stage, SerializeStage::Meta);
+        let chfst = ChfstTransducer::from_path(dir.path()).unwrap();
+
+        assert_eq!(chfst.alphabet().key_table(), hfst.alphabet().key_table());
+
+        let checked = verify::spot_check(&hfst, &chfst, 3, 3, 100).unwrap();
+        assert_eq!(checked, 6);
+
+        assert_eq!(chfst.next(0, 1), Some(0));
+        assert!(chfst.is_final(TARGET_TABLE + 1));
+        assert_eq!(chfst.final_weight(TARGET_TABLE + 1), Some(0.25));
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+
+use crate::constants::TARGET_TABLE;
+use crate::transducer::symbol_transition::SymbolTransition;
+use crate::transducer::Transducer;
+use crate::types::{SymbolNumber, TransitionTableIndex, Weight};
+
+#[derive(Debug)]
+pub struct SpotCheckMismatch {
+    pub index: TransitionTableIndex,
+    pub what: &'static str,
+}
+
+impl std::error::Error for SpotCheckMismatch {}
+
+impl fmt::Display for SpotCheckMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.index >= TARGET_TABLE {
+            write!(
+                f,
+                "{} differs at transition {}",
+                self.what,
+                self.index - TARGET_TABLE
+            )
+        } else {
+            write!(f, "{} differs at index {}", self.what, self.index)
+        }
+    }
+}
+
+fn weight_bits(weight: Option<Weight>) -> Option<u32> {
+    weight.map(f32::to_bits)
+}
+
+fn transition_key(
+    t: Option<SymbolTransition>,
+) -> Option<(
+    Option<TransitionTableIndex>,
+    Option<SymbolNumber>,
+    Option<u32>,
+)> {
+    t.map(|t| (t.target(), t.symbol(), weight_bits(t.weight())))
+}
+
+/// Indexes to compare: everything if `samples` covers the table, otherwise
+/// an even spread that always includes both ends.
+fn sample_indexes(size: u32, samples: u32) -> Vec<u32> {
+    if size == 0 {
+        return vec![];
+    }
+
+    if samples >= size || samples < 2 {
+        return (0..size).collect();
+    }
+
+    let step = f64::from(size - 1) / f64::from(samples - 1);
+    let mut out: Vec<u32> = (0..samples)
+        .map(|i| (f64::from(i) * step).round() as u32)
+        .collect();
+    out.dedup();
+    out
+}
+
+/// Compares two transducers through the `Transducer` interface at up to
+/// `samples` positions in each table, returning how many positions matched.
+///
+/// Used to check that a converted transducer reads back the same as its source.
+pub fn spot_check(
+    expected: &dyn Transducer,
+    actual: &dyn Transducer,
+    index_table_size: u32,
+    transition_table_size: u32,
+    samples: u32,
+) -> Result<usize, SpotCheckMismatch> {
+    let symbol_count = expected.alphabet().initial_symbol_count();
+    let mut checked = 0;
+
+    macro_rules! compare {
+        ($index:expr, $what:expr, $a:expr, $b:expr) => {
+            if $a != $b {
+                return Err(SpotCheckMismatch {
+                    index: $index,
+                    what: $what,
+                });
+            }
+        };
+    }
+
+    for i in sample_indexes(index_table_size, samples) {
+        compare!(i, "is_final", expected.is_final(i), actual.is_final(i));
+        compare!(
+            i,
+            "final_weight",
+            weight_bits(expected.final_weight(i)),
+            weight_bits(actual.final_weight(i))
+        );
+        compare!(
+            i,
+            "has_epsilons_or_flags",
+            expected.has_epsilons_or_flags(i),
+            actual.has_epsilons_or_flags(i)
+        );
+
+        // Symbol slots past the end of the table are not addressable.
+        for sym in 0..symbol_count {
+            if i + 1 + u32::from(sym) >= index_table_size {
+                break;
+            }
+
+            compare!(
+                i,
+                "has_transitions",
+                expected.has_transitions(i, Some(sym)),
+                actual.has_transitions(i, Some(sym))
+            );
+
+            // `next` is only meaningful (and only safe to read) where the
+            // slot it reads holds an arc for this symbol.
+            if expected.has_transitions(i + 1, Some(sym)) {
+                compare!(i, "next", expected.next(i, sym), actual.next(i, sym));
+            }
+        }
+
+        checked += 1;
+    }
+
+    for i in sample_indexes(transition_table_size, samples) {
+        let t = TARGET_TABLE + i;
+
+        compare!(
+            t,
+            "transition_input_symbol",
+            expected.transition_input_symbol(i),
+            actual.transition_input_symbol(i)
+        );
+        compare!(t, "is_final", expected.is_final(t), actual.is_final(t));
+        compare!(
+            t,
+            "final_weight",
+            weight_bits(expected.final_weight(t)),
+            weight_bits(actual.final_weight(t))
+        );
+        compare!(
+            t,
+            "take_epsilons_and_flags",
+            transition_key(expected.take_epsilons_and_flags(i)),
+            transition_key(actual.take_epsilons_and_flags(i))
+        );
+
+        if let Some(sym) = expected.transition_input_symbol(i) {
+            compare!(
+                t,
+                "take_non_epsilons",
+                transition_key(expected.take_non_epsilons(i, sym)),
+                transition_key(actual.take_non_epsilons(i, sym))
+            );
+        }
+
+        checked += 1;
+    }
+
+    Ok(checked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples() {
+        assert_eq!(sample_indexes(0, 10), Vec::<u32>::new());
+        assert_eq!(sample_indexes(3, 10), vec![0, 1, 2]);
+        assert_eq!(sample_indexes(101, 5), vec![0, 25, 50, 75, 100]);
+    }
+}
@@ -1,8 +1,35 @@
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::Cursor;
+use byteorder::{ByteOrder, LittleEndian};
 
+use super::TransducerError;
 use crate::types::{HeaderFlag, SymbolNumber, TransitionTableIndex};
 
+/// Reads little endian integers from the front of a buffer, the way an
+/// `io::Cursor` would, but without `std`. Reads past the end of the buffer
+/// are `TransducerError::Truncated`.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], TransducerError> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or(TransducerError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, TransducerError> {
+        Ok(LittleEndian::read_u16(self.take(2)?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, TransducerError> {
+        Ok(LittleEndian::read_u32(self.take(4)?))
+    }
+}
+
 #[derive(Debug)]
 pub struct TransducerHeader {
     symbols: SymbolNumber,
@@ -18,34 +45,33 @@ pub struct TransducerHeader {
 }
 
 impl TransducerHeader {
-    pub fn new(buf: &[u8]) -> TransducerHeader {
-        let mut rdr = Cursor::new(buf);
+    /// Reads the header at the start of `buf`, failing if `buf` ends before
+    /// it does.
+    pub fn new(buf: &[u8]) -> Result<TransducerHeader, TransducerError> {
+        let mut rdr = Reader { buf, pos: 0 };
 
         // Skip HFST string
-        rdr.set_position(5);
-
-        let header_len = rdr.read_u16::<LittleEndian>().unwrap();
+        rdr.pos = 5;
 
-        rdr.set_position(8);
+        let header_len = rdr.read_u16()?;
 
-        let pos = rdr.position() + u64::from(header_len);
-        rdr.set_position(pos);
+        rdr.pos = 8 + header_len as usize;
 
-        let input_symbols = rdr.read_u16::<LittleEndian>().unwrap();
-        let symbols = rdr.read_u16::<LittleEndian>().unwrap();
-        let trans_index_table = rdr.read_u32::<LittleEndian>().unwrap() as usize;
-        let trans_target_table = rdr.read_u32::<LittleEndian>().unwrap() as usize;
-        let states = rdr.read_u32::<LittleEndian>().unwrap();
-        let transitions = rdr.read_u32::<LittleEndian>().unwrap();
+        let input_symbols = rdr.read_u16()?;
+        let symbols = rdr.read_u16()?;
+        let trans_index_table = rdr.read_u32()? as usize;
+        let trans_target_table = rdr.read_u32()? as usize;
+        let states = rdr.read_u32()?;
+        let transitions = rdr.read_u32()?;
 
         let mut props = [false; 9];
 
         for i in 0..props.len() {
-            let v = rdr.read_u32::<LittleEndian>().unwrap();
+            let v = rdr.read_u32()?;
             props[i] = v != 0
         }
 
-        TransducerHeader {
+        Ok(TransducerHeader {
             symbols,
             input_symbols,
             trans_index_table,
@@ -55,8 +81,8 @@ impl TransducerHeader {
             properties: props,
 
             string_content_size: header_len,
-            header_size: rdr.position() as usize,
-        }
+            header_size: rdr.pos,
+        })
     }
 
     pub fn symbol_count(&self) -> SymbolNumber {
@@ -95,3 +121,26 @@ impl TransducerHeader {
         self.header_size as usize
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated() {
+        let mut buf = b"HFST\0\0\0\0".to_vec();
+        buf.extend_from_slice(&[0; 56]);
+
+        assert_eq!(TransducerHeader::new(&buf).unwrap().len(), 64);
+        for len in 0..buf.len() {
+            assert_eq!(
+                TransducerHeader::new(&buf[..len]).unwrap_err(),
+                TransducerError::Truncated
+            );
+        }
+
+        // A header length past the end of the buffer.
+        buf[5] = 0xff;
+        assert!(TransducerHeader::new(&buf).is_err());
+    }
+}
@@ -1,23 +1,21 @@
-#![allow(clippy::cast_ptr_alignment)] // FIXME: This at least needs a comment
-
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use std::cmp;
-use std::fmt;
-use std::io::Cursor;
-use std::mem;
-use std::ptr;
-use std::{u16, u32};
+#[cfg(feature = "std")]
+use byteorder::{LittleEndian, WriteBytesExt};
+use core::fmt;
+use core::mem;
+use core::{cmp, u16, u32};
 
 use crate::constants::INDEX_TABLE_SIZE;
+use crate::transducer::bytes::ByteAccess;
+use crate::transducer::TransducerBuf;
 use crate::types::{SymbolNumber, TransitionTableIndex, Weight};
-use memmap::Mmap;
-use std::sync::Arc;
+use alloc::sync::Arc;
 
 pub struct IndexTable {
     size: TransitionTableIndex,
-    mmap: Arc<Mmap>,
+    mmap: Arc<TransducerBuf>,
     offset: usize,
     len: usize,
+    checked: bool,
 }
 
 impl fmt::Debug for IndexTable {
@@ -29,38 +27,72 @@ impl fmt::Debug for IndexTable {
 
 impl IndexTable {
     pub fn new(
-        buf: Arc<Mmap>,
+        buf: Arc<TransducerBuf>,
         offset: usize,
         len: usize,
         size: TransitionTableIndex,
     ) -> IndexTable {
+        // No row at or past `size` is read, so this keeps unchecked reads in
+        // the buffer however large the header says the table is.
+        let rows = buf.len().saturating_sub(offset) / INDEX_TABLE_SIZE;
+        let size = cmp::min(size as usize, rows) as TransitionTableIndex;
+
         IndexTable {
             size,
             mmap: buf,
             offset,
             len,
+            checked: cfg!(feature = "paranoid"),
         }
     }
 
+    /// Reads rows with bounds checks, running no unsafe code, for archives
+    /// that may not be what they claim.
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked || cfg!(feature = "paranoid");
+    }
+
     #[inline(always)]
-    pub fn len(&self) -> usize {
-        self.len - self.offset
+    fn read_u16(&self, index: usize) -> Option<u16> {
+        if self.checked {
+            self.mmap.get_u16(index)
+        } else {
+            // Callers only read rows under `size`, which fit in the buffer.
+            Some(unsafe { self.mmap.read_u16(index) })
+        }
     }
 
     #[inline(always)]
-    fn make_cursor<'a>(&'a self) -> Cursor<&'a [u8]> {
-        Cursor::new(&self.mmap)
+    fn read_u32(&self, index: usize) -> Option<u32> {
+        if self.checked {
+            self.mmap.get_u32(index)
+        } else {
+            // As for `read_u16`.
+            Some(unsafe { self.mmap.read_u32(index) })
+        }
     }
 
-    pub fn serialize(&self, chunk_size: usize, target_dir: &std::path::Path) -> Result<usize, ()> {
-        eprintln!(
-            "size: {}, len: {}, offset: {}",
-            self.size, self.len, self.offset
-        );
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len - self.offset
+    }
 
-        if chunk_size % 8 != 0 {
-            panic!("Chunk size must be divisible by 8");
-        }
+    #[cfg(feature = "std")]
+    /// Writes the table in chunks of `chunk_size` bytes, calling `progress`
+    /// with the number of chunks written and the total after each one.
+    /// `chunk_size` must have passed `check_chunk_size`.
+    pub fn serialize(
+        &self,
+        chunk_size: usize,
+        target_dir: &std::path::Path,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> std::io::Result<usize> {
+        trace_event!(
+            size = self.size,
+            len = self.len,
+            offset = self.offset,
+            "serializing index table"
+        );
 
         // Size is the number of indexes, and that multiplied by TRANS_TABLE_SIZE is the total byte size
         let real_total_bytes = self.len - self.offset;
@@ -74,30 +106,32 @@ impl IndexTable {
         // Divide the chunks
         let has_excess = total_bytes % chunk_size != 0;
         let chunk_count = total_bytes / chunk_size + (if has_excess { 1 } else { 0 });
-        eprintln!(
-            "Chunk count: {} max index per iter: {} total bytes: {}",
-            chunk_count, max_index_per_iter, total_bytes
+        trace_event!(
+            chunks = chunk_count,
+            per_chunk = max_index_per_iter,
+            bytes = total_bytes,
+            "dividing index table"
         );
 
         for i in 1usize..=chunk_count {
-            eprintln!("Writing chunk: {}", i);
-
             let filename = format!("index-{:02}", i - 1);
-            let mut file = std::fs::File::create(target_dir.join(filename)).unwrap();
+            let mut file = std::fs::File::create(target_dir.join(filename))?;
 
             let begin = (max_index_per_iter * (i - 1usize)) as u32;
-            let end = cmp::min(max_index_per_iter * i, self.size as usize) as u32;
+            let end = std::cmp::min(max_index_per_iter * i, self.size as usize) as u32;
 
-            eprintln!("Chunk {}: {}..{}", i, begin, end);
+            trace_event!(chunk = i, begin = begin, end = end, "writing chunk");
 
             for index in begin..end {
                 let input_symbol = self.input_symbol(index).unwrap_or(u16::MAX);
                 let targetish = self.target(index).unwrap_or(u32::MAX);
 
-                file.write_u16::<LittleEndian>(input_symbol).unwrap();
-                file.write_u16::<LittleEndian>(0).unwrap();
-                file.write_u32::<LittleEndian>(targetish).unwrap();
+                file.write_u16::<LittleEndian>(input_symbol)?;
+                file.write_u16::<LittleEndian>(0)?;
+                file.write_u32::<LittleEndian>(targetish)?;
             }
+
+            progress(i, chunk_count);
         }
 
         Ok(chunk_count)
@@ -110,15 +144,7 @@ impl IndexTable {
         }
 
         let index = self.offset + INDEX_TABLE_SIZE * i as usize;
-
-        let input_symbol: SymbolNumber =
-            if cfg!(all(target_arch = "arm", target_pointer_width = "32")) {
-                let mut cursor = self.make_cursor();
-                cursor.set_position(index as u64);
-                cursor.read_u16::<LittleEndian>().unwrap()
-            } else {
-                unsafe { ptr::read(self.mmap.as_ptr().add(index) as *const _) }
-            };
+        let input_symbol: SymbolNumber = self.read_u16(index)?;
 
         if input_symbol == u16::MAX {
             None
@@ -134,14 +160,7 @@ impl IndexTable {
         }
 
         let index = self.offset + INDEX_TABLE_SIZE * i as usize;
-        let target: TransitionTableIndex =
-            if cfg!(all(target_arch = "arm", target_pointer_width = "32")) {
-                let mut cursor = self.make_cursor();
-                cursor.set_position((index + mem::size_of::<SymbolNumber>()) as u64);
-                cursor.read_u32::<LittleEndian>().unwrap()
-            } else {
-                unsafe { ptr::read(self.mmap.as_ptr().add(index + 2) as *const _) }
-            };
+        let target: TransitionTableIndex = self.read_u32(index + mem::size_of::<SymbolNumber>())?;
 
         if target == u32::MAX {
             None
@@ -159,15 +178,9 @@ impl IndexTable {
         }
 
         let index = self.offset + INDEX_TABLE_SIZE * i as usize;
-        let weight: Weight = if cfg!(all(target_arch = "arm", target_pointer_width = "32")) {
-            let mut cursor = self.make_cursor();
-            cursor.set_position((index + mem::size_of::<SymbolNumber>()) as u64);
-            cursor.read_f32::<LittleEndian>().unwrap()
-        } else {
-            unsafe { ptr::read(self.mmap.as_ptr().add(index + 2) as *const _) }
-        };
+        let weight = self.read_u32(index + mem::size_of::<SymbolNumber>())?;
 
-        Some(weight)
+        Some(Weight::from_bits(weight))
     }
 
     #[inline(always)]
@@ -175,3 +188,26 @@ impl IndexTable {
         self.input_symbol(i) == None && self.target(i) != None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_stop_at_buffer() {
+        // One row, in a table that claims four.
+        let mut row = vec![];
+        row.extend_from_slice(&3u16.to_le_bytes());
+        row.extend_from_slice(&7u32.to_le_bytes());
+        let buf = crate::transducer::test_buf(&row);
+
+        for &checked in &[false, true] {
+            let mut table = IndexTable::new(buf.clone(), 0, 4 * INDEX_TABLE_SIZE, 4);
+            table.set_checked(checked);
+            assert_eq!(table.input_symbol(0), Some(3));
+            assert_eq!(table.target(0), Some(7));
+            assert_eq!(table.input_symbol(1), None);
+            assert_eq!(table.final_weight(3), None);
+        }
+    }
+}
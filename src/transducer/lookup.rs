@@ -0,0 +1,290 @@
+//! Looking strings up in a transducer directly, for tools that read an
+//! archive's acceptor or error model themselves, such as analysers and
+//! grammar checkers built on the same archives.
+//!
+//! `lookup` follows every path through a transducer that reads the input and
+//! returns what each writes, lightest first, and `lookup_weight` follows only
+//! those writing a given output. The input is split into the
+//! longest symbols of the alphabet, so multicharacter symbols are read whole,
+//! and a character not in it goes by the identity or unknown symbol if the
+//! transducer has one. Flag diacritics are obeyed and left out of the output.
+//! Paths through epsilon cycles are followed for at most `MAX_EPSILONS`
+//! epsilons in a row, and a lookup gives up after `MAX_STEPS` steps, so a
+//! transducer with endless paths returns some of them rather than hanging.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::symbol_transition::SymbolTransition;
+use super::Transducer;
+use crate::types::{
+    FlagDiacriticOperation, FlagDiacriticOperator, SymbolNumber, TransitionTableIndex, Weight,
+};
+
+pub const MAX_EPSILONS: usize = 32;
+pub const MAX_STEPS: usize = 100_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LookupResult {
+    pub output: String,
+    pub weight: Weight,
+}
+
+/// A piece of the input: the alphabet symbol it is, if any, and its text.
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    symbol: Option<SymbolNumber>,
+    text: &'a str,
+}
+
+fn tokenize<'a, T: Transducer>(t: &T, input: &'a str) -> Vec<Token<'a>> {
+    let symbols = t.alphabet().string_to_symbol();
+    let longest = symbols.keys().map(|k| k.len()).max().unwrap_or(0);
+    let mut tokens = Vec::new();
+    let mut rest = input;
+
+    while let Some(ch) = rest.chars().next() {
+        let mut token = Token {
+            symbol: None,
+            text: &rest[..ch.len_utf8()],
+        };
+
+        for end in (1..=longest.min(rest.len())).rev() {
+            if !rest.is_char_boundary(end) {
+                continue;
+            }
+            if let Some(&symbol) = symbols.get(&rest[..end]) {
+                token = Token {
+                    symbol: Some(symbol),
+                    text: &rest[..end],
+                };
+                break;
+            }
+        }
+
+        rest = &rest[token.text.len()..];
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+/// Whether `op` may be taken with `flags`, updating them if so.
+pub(crate) fn apply_flag(op: &FlagDiacriticOperation, flags: &mut [i16]) -> bool {
+    let current = flags[op.feature as usize];
+
+    let (allowed, value) = match op.operation {
+        FlagDiacriticOperator::PositiveSet => (true, op.value),
+        FlagDiacriticOperator::NegativeSet => (true, -op.value),
+        FlagDiacriticOperator::Require if op.value == 0 => (current != 0, current),
+        FlagDiacriticOperator::Require => (current == op.value, current),
+        FlagDiacriticOperator::Disallow if op.value == 0 => (current == 0, current),
+        FlagDiacriticOperator::Disallow => (current != op.value, current),
+        FlagDiacriticOperator::Clear => (true, 0),
+        FlagDiacriticOperator::Unification => (
+            current == 0 || current == op.value || (current < 0 && -current != op.value),
+            op.value,
+        ),
+    };
+
+    if allowed {
+        flags[op.feature as usize] = value;
+    }
+    allowed
+}
+
+struct Search<'a, T: Transducer> {
+    t: &'a T,
+    input: Vec<Token<'a>>,
+    /// The only output wanted, if any; paths writing anything else are cut.
+    target: Option<&'a str>,
+    results: Vec<LookupResult>,
+    steps: usize,
+    /// What the path being followed has written so far.
+    output: String,
+    /// The flag diacritic values set along the path being followed.
+    flags: Vec<i16>,
+}
+
+impl<'a, T: Transducer> Search<'a, T> {
+    fn step(&mut self, state: TransitionTableIndex, pos: usize, epsilons: usize, weight: Weight) {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return;
+        }
+
+        let t = self.t;
+
+        if pos == self.input.len()
+            && t.is_final(state)
+            && self.target.is_none_or(|target| target == self.output)
+        {
+            self.results.push(LookupResult {
+                output: self.output.clone(),
+                weight: weight + t.final_weight(state).unwrap_or(0.0),
+            });
+        }
+
+        if epsilons < MAX_EPSILONS && t.has_epsilons_or_flags(state + 1) {
+            if let Some(mut next) = t.next(state, 0) {
+                while let Some(transition) = t.take_epsilons_and_flags(next) {
+                    match t.transition_input_symbol(next) {
+                        Some(0) => self.follow(&transition, pos, epsilons + 1, "", weight),
+                        Some(symbol) => {
+                            if let Some(op) = t.alphabet().operations().get(&symbol) {
+                                let saved = self.flags[op.feature as usize];
+                                if apply_flag(op, &mut self.flags) {
+                                    self.follow(&transition, pos, epsilons + 1, "", weight);
+                                }
+                                self.flags[op.feature as usize] = saved;
+                            }
+                        }
+                        None => {}
+                    }
+
+                    next += 1;
+                }
+            }
+        }
+
+        let token = match self.input.get(pos) {
+            Some(&token) => token,
+            None => return,
+        };
+
+        // Symbols added past the transducer's own, such as an error model's
+        // letters in a lexicon's alphabet, have no transitions of their own.
+        let alphabet = t.alphabet();
+        let symbols = match token.symbol {
+            Some(symbol) if symbol < alphabet.initial_symbol_count() => [Some(symbol), None],
+            _ => [alphabet.identity(), alphabet.unknown()],
+        };
+
+        for &symbol in symbols.iter().flatten() {
+            if !t.has_transitions(state + 1, Some(symbol)) {
+                continue;
+            }
+
+            let first = match t.next(state, symbol) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            for i in first..first + t.non_epsilon_run(first, symbol) {
+                let transition = t.transition(i);
+                self.follow(&transition, pos + 1, 0, token.text, weight);
+            }
+        }
+    }
+
+    /// Takes `transition`, which read `text`, and searches on from its
+    /// target.
+    fn follow(
+        &mut self,
+        transition: &SymbolTransition,
+        pos: usize,
+        epsilons: usize,
+        text: &str,
+        weight: Weight,
+    ) {
+        let target = match transition.target() {
+            Some(v) => v,
+            None => return,
+        };
+
+        let alphabet = self.t.alphabet();
+        let len = self.output.len();
+
+        if let Some(symbol) = transition.symbol() {
+            if Some(symbol) == alphabet.identity() || Some(symbol) == alphabet.unknown() {
+                self.output.push_str(text);
+            } else if !alphabet.is_flag(symbol) {
+                if let Some(key) = alphabet.key_table().get(symbol as usize) {
+                    self.output.push_str(key);
+                }
+            }
+        }
+
+        if self
+            .target
+            .is_none_or(|wanted| wanted.starts_with(self.output.as_str()))
+        {
+            let weight = weight + transition.weight().unwrap_or(0.0);
+            self.step(target, pos, epsilons, weight);
+        }
+        self.output.truncate(len);
+    }
+}
+
+/// What the paths through `t` that read `input` write, each output once with
+/// the weight of its lightest path, lightest first, at most `limit` of them.
+pub fn lookup<T: Transducer>(t: &T, input: &str, limit: usize) -> Vec<LookupResult> {
+    let mut results = search(t, input, None);
+    results.sort_by(|a, b| {
+        a.output.cmp(&b.output).then_with(|| {
+            a.weight
+                .partial_cmp(&b.weight)
+                .unwrap_or(core::cmp::Ordering::Equal)
+        })
+    });
+    results.dedup_by(|a, b| a.output == b.output);
+    results.sort_by(|a, b| {
+        a.weight
+            .partial_cmp(&b.weight)
+            .unwrap_or(core::cmp::Ordering::Equal)
+    });
+    results.truncate(limit);
+    results
+}
+
+/// The weight of the lightest path through `t` that reads `input` and writes
+/// `output`, or `None` if there is none. Paths are cut as soon as they write
+/// something else, so this is much cheaper than finding `output` among
+/// everything `lookup` returns.
+pub fn lookup_weight<T: Transducer>(t: &T, input: &str, output: &str) -> Option<Weight> {
+    search(t, input, Some(output))
+        .into_iter()
+        .map(|result| result.weight)
+        .fold(None, |lightest: Option<Weight>, weight| {
+            Some(lightest.map_or(weight, |w| w.min(weight)))
+        })
+}
+
+fn search<T: Transducer>(t: &T, input: &str, target: Option<&str>) -> Vec<LookupResult> {
+    let mut search = Search {
+        t,
+        input: tokenize(t, input),
+        target,
+        results: Vec::new(),
+        steps: 0,
+        output: String::new(),
+        flags: alloc::vec![0i16; t.alphabet().state_size() as usize],
+    };
+
+    search.step(0, 0, 0, 0.0);
+    search.results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::test_mutator;
+
+    #[test]
+    fn error_model_outputs() {
+        let t = test_mutator(&[("sami", "sámi", 3)], &[]);
+
+        let results = lookup(&t, "sami", 100);
+        assert_eq!(results[0].output, "sami");
+        assert_eq!(results[0].weight, 0.0);
+        assert!(results.iter().any(|r| r.output == "sámi"));
+        assert_eq!(lookup(&t, "sami", 1).len(), 1);
+
+        assert!(lookup(&t, "xyz", 10).is_empty());
+
+        let sami = results.iter().find(|r| r.output == "sámi").unwrap();
+        assert_eq!(lookup_weight(&t, "sami", "sámi"), Some(sami.weight));
+        assert_eq!(lookup_weight(&t, "sami", "sami"), Some(0.0));
+        assert_eq!(lookup_weight(&t, "sami", "sá"), None);
+    }
+}
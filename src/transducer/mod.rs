@@ -1,14 +1,39 @@
 pub mod alphabet;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 pub mod chunk;
+pub mod lookup;
+pub mod symbol_transition;
+
+// The table layout and search internals, which change between releases.
+// The `internals` feature makes them public for tools that read transducers
+// directly.
+#[cfg(feature = "internals")]
+pub mod bytes;
+#[cfg(not(feature = "internals"))]
+pub(crate) mod bytes;
+#[cfg(feature = "internals")]
 pub mod header;
+#[cfg(not(feature = "internals"))]
+pub(crate) mod header;
+#[cfg(feature = "internals")]
 pub mod index_table;
-pub mod symbol_transition;
+#[cfg(not(feature = "internals"))]
+pub(crate) mod index_table;
+#[cfg(feature = "internals")]
+pub mod scan;
+#[cfg(not(feature = "internals"))]
+pub(crate) mod scan;
+#[cfg(feature = "internals")]
 pub mod transition_table;
+#[cfg(not(feature = "internals"))]
+pub(crate) mod transition_table;
+#[cfg(feature = "internals")]
 pub mod tree_node;
+#[cfg(not(feature = "internals"))]
+pub(crate) mod tree_node;
 
-use memmap::Mmap;
-use std::fmt;
-use std::sync::Arc;
+use alloc::sync::Arc;
+use core::fmt;
 
 use crate::constants::{INDEX_TABLE_SIZE, TARGET_TABLE, TRANS_TABLE_SIZE};
 use crate::types::{HeaderFlag, SymbolNumber, TransitionTableIndex, Weight};
@@ -19,6 +44,99 @@ use self::index_table::IndexTable;
 use self::symbol_transition::SymbolTransition;
 use self::transition_table::TransitionTable;
 
+/// Memory a transducer is read from. Mapped from a file where the platform
+/// supports it; on WebAssembly and without `std`, an owned buffer.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub enum TransducerBuf {
+    Mapped(memmap::Mmap),
+    /// Bytes on the heap, for transducers built in memory and for tests,
+    /// which miri can't run against a mapping.
+    Owned(alloc::vec::Vec<u8>),
+}
+#[cfg(any(not(feature = "std"), target_arch = "wasm32"))]
+pub type TransducerBuf = alloc::vec::Vec<u8>;
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl core::ops::Deref for TransducerBuf {
+    type Target = [u8];
+
+    #[inline(always)]
+    fn deref(&self) -> &[u8] {
+        match self {
+            TransducerBuf::Mapped(map) => map,
+            TransducerBuf::Owned(bytes) => bytes,
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl From<memmap::Mmap> for TransducerBuf {
+    fn from(map: memmap::Mmap) -> TransducerBuf {
+        TransducerBuf::Mapped(map)
+    }
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl From<alloc::vec::Vec<u8>> for TransducerBuf {
+    fn from(bytes: alloc::vec::Vec<u8>) -> TransducerBuf {
+        TransducerBuf::Owned(bytes)
+    }
+}
+
+/// `bytes` as a transducer buffer, for table tests.
+#[cfg(test)]
+pub(crate) fn test_buf(bytes: &[u8]) -> Arc<TransducerBuf> {
+    Arc::new(bytes.to_vec().into())
+}
+
+/// An error model trained on `pairs` of typo, correction and count, that
+/// also passes the letters of `words` through unchanged.
+#[cfg(all(test, feature = "std"))]
+pub(crate) fn test_mutator(pairs: &[(&str, &str, u64)], words: &[&str]) -> HfstTransducer {
+    use crate::errmodel::{EditCounts, ErrorModel, TrainConfig};
+
+    let cfg = TrainConfig::default();
+    let mut counts = EditCounts::new();
+    for &(typo, correction, count) in pairs {
+        counts.add(typo, correction, count, &cfg);
+    }
+    words
+        .iter()
+        .flat_map(|word| word.chars())
+        .for_each(|ch| counts.add_alphabet_char(ch));
+
+    let mut bytes = vec![];
+    ErrorModel::train(&counts, &cfg)
+        .write_hfst(&mut bytes)
+        .unwrap();
+    HfstTransducer::from_mapped_memory(test_buf(&bytes)).unwrap()
+}
+
+/// A speller for `words`, with the error model of `test_mutator`.
+#[cfg(all(test, feature = "std"))]
+pub(crate) fn test_speller(
+    pairs: &[(&str, &str, u64)],
+    words: &[&str],
+) -> Arc<crate::speller::Speller<HfstTransducer>> {
+    let mutator = test_mutator(pairs, words);
+    crate::speller::user_overlay::UserOverlay::compile(words.iter().cloned(), &mutator, 0.0)
+        .unwrap()
+        .speller()
+}
+
+/// A speller for the tagged `entries` of `user_overlay::tagged_acceptor`,
+/// with the error model of `test_mutator`.
+#[cfg(all(test, feature = "std"))]
+pub(crate) fn test_tagged_speller(
+    pairs: &[(&str, &str, u64)],
+    entries: &[(&str, &[&str])],
+) -> Arc<crate::speller::Speller<HfstTransducer>> {
+    let words: Vec<&str> = entries.iter().map(|&(word, _)| word).collect();
+    let mutator = test_mutator(pairs, &words);
+    let lexicon = crate::speller::user_overlay::tagged_acceptor(entries).unwrap();
+    crate::speller::Speller::new(mutator, lexicon)
+}
+
 pub trait Transducer {
     fn alphabet(&self) -> &TransducerAlphabet;
     fn mut_alphabet(&mut self) -> &mut TransducerAlphabet;
@@ -33,15 +151,26 @@ pub trait Transducer {
         i: TransitionTableIndex,
         symbol: SymbolNumber,
     ) -> Option<SymbolTransition>;
+    /// The transition at `i` in the transition table, whatever its symbol.
+    fn transition(&self, i: TransitionTableIndex) -> SymbolTransition;
+    /// How many transitions from `i` on `take_non_epsilons` would take.
+    fn non_epsilon_run(&self, i: TransitionTableIndex, symbol: SymbolNumber) -> u32 {
+        let mut n = 0;
+        while self.take_non_epsilons(i + n, symbol).is_some() {
+            n += 1;
+        }
+        n
+    }
     fn is_final(&self, i: TransitionTableIndex) -> bool;
     fn final_weight(&self, i: TransitionTableIndex) -> Option<Weight>;
 }
 pub struct HfstTransducer {
-    buf: Arc<Mmap>,
+    buf: Arc<TransducerBuf>,
     header: TransducerHeader,
     alphabet: TransducerAlphabet,
     index_table: IndexTable,
     transition_table: TransitionTable,
+    checked: bool,
 }
 
 impl fmt::Debug for HfstTransducer {
@@ -54,25 +183,126 @@ impl fmt::Debug for HfstTransducer {
     }
 }
 
+/// Why a buffer could not be read as an HFST transducer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransducerError {
+    /// The buffer ends before its header or alphabet does.
+    Truncated,
+    /// The alphabet symbol with this number is a malformed flag diacritic,
+    /// or one more flag value than can be numbered.
+    InvalidFlag(SymbolNumber),
+}
+
+impl fmt::Display for TransducerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransducerError::Truncated => write!(f, "transducer is truncated"),
+            TransducerError::InvalidFlag(symbol) => {
+                write!(f, "invalid flag diacritic at symbol {}", symbol)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TransducerError {}
+
 #[derive(Debug)]
 pub enum TransducerSerializeError {
+    /// The chunk size isn't a positive multiple of 24, which both tables'
+    /// records divide.
     InvalidChunkSize,
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
 }
 
+impl fmt::Display for TransducerSerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransducerSerializeError::InvalidChunkSize => {
+                write!(f, "chunk size must be a positive multiple of 24")
+            }
+            #[cfg(feature = "std")]
+            TransducerSerializeError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TransducerSerializeError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for TransducerSerializeError {
+    fn from(e: std::io::Error) -> TransducerSerializeError {
+        TransducerSerializeError::Io(e)
+    }
+}
+
+/// Fails unless `chunk_size` is a positive multiple of 24, so that chunks
+/// of both tables end on a record: index records are written as 8 bytes and
+/// transitions as 12. `HfstTransducer::serialize` checks this itself.
+pub fn check_chunk_size(chunk_size: usize) -> Result<(), TransducerSerializeError> {
+    if chunk_size == 0 || chunk_size % 24 != 0 {
+        return Err(TransducerSerializeError::InvalidChunkSize);
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
 pub struct TransducerSerializeReport {
     pub index_table_chunks: usize,
     pub transition_table_chunks: usize,
 }
 
+/// What `HfstTransducer::serialize_with_progress` is writing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SerializeStage {
+    IndexTable,
+    TransitionTable,
+    Meta,
+}
+
+/// `chunk` of `chunks` of the current stage has been written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SerializeProgress {
+    pub stage: SerializeStage,
+    pub chunk: usize,
+    pub chunks: usize,
+}
+
 impl HfstTransducer {
+    /// Reads the header and alphabet at the start of `buf`, failing if they
+    /// are cut short or malformed. The tables are read on lookup, bounds
+    /// checked or clamped to `buf` (see `set_checked`).
     #[inline(always)]
-    pub fn from_mapped_memory(buf: Arc<Mmap>) -> HfstTransducer {
-        let header = TransducerHeader::new(&buf);
-        let alphabet_offset = header.len();
+    pub fn from_mapped_memory(buf: Arc<TransducerBuf>) -> Result<HfstTransducer, TransducerError> {
+        trace_span!("transducer_tables", bytes = buf.len());
+
+        let header = TransducerHeader::new(&buf)?;
         let alphabet =
-            TransducerAlphabet::new(&buf[alphabet_offset..buf.len()], header.symbol_count());
+            TransducerAlphabet::new(&buf[header.len()..buf.len()], header.symbol_count())?;
+
+        Ok(HfstTransducer::from_parts(buf, header, alphabet))
+    }
+
+    /// Another transducer reading the same memory, for a speller of its own.
+    pub fn share(&self) -> HfstTransducer {
+        // The header and alphabet were read from this memory once already.
+        let mut transducer = HfstTransducer::from_mapped_memory(self.buf.clone())
+            .expect("transducer memory was read before");
+        transducer.set_checked(self.checked);
+        transducer
+    }
 
-        let index_table_offset = alphabet_offset + alphabet.len();
+    /// Finishes a transducer whose header and alphabet were read from `buf`
+    /// separately, so that an archive can time each step. The tables are
+    /// taken as they are; nothing in them is read until a lookup.
+    pub(crate) fn from_parts(
+        buf: Arc<TransducerBuf>,
+        header: TransducerHeader,
+        alphabet: TransducerAlphabet,
+    ) -> HfstTransducer {
+        let index_table_offset = header.len() + alphabet.len();
 
         let index_table_end = index_table_offset + INDEX_TABLE_SIZE * header.index_table_size();
         let index_table = IndexTable::new(
@@ -90,64 +320,131 @@ impl HfstTransducer {
             header.target_table_size() as u32,
         );
 
+        trace_event!(
+            symbols = header.symbol_count(),
+            index_rows = header.index_table_size(),
+            transition_rows = header.target_table_size(),
+            "read transducer tables"
+        );
+
         HfstTransducer {
             buf,
             header,
             alphabet,
             index_table,
             transition_table: trans_table,
+            checked: cfg!(feature = "paranoid"),
         }
     }
 
+    /// Loads a transducer from bytes already in memory, such as an archive
+    /// entry or a `Uint8Array` on WebAssembly.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<HfstTransducer> {
+        let mut map = memmap::MmapMut::map_anon(bytes.len())?;
+        map.copy_from_slice(bytes);
+        HfstTransducer::from_mapped_memory(Arc::new(map.make_read_only()?.into()))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Loads a transducer from bytes already in memory, such as an archive
+    /// entry or a `Uint8Array` on WebAssembly.
+    #[cfg(all(feature = "std", target_arch = "wasm32"))]
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<HfstTransducer> {
+        HfstTransducer::from_vec(bytes.to_vec())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Takes a transducer already read into memory, for builds without
+    /// `std` that have no files to map it from, such as one embedded with
+    /// `include_bytes!` or received from a sandbox's host.
+    #[cfg(any(not(feature = "std"), target_arch = "wasm32"))]
+    pub fn from_vec(bytes: alloc::vec::Vec<u8>) -> Result<HfstTransducer, TransducerError> {
+        HfstTransducer::from_mapped_memory(Arc::new(bytes))
+    }
+
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
     pub fn serialize(
         &self,
         chunk_size: usize,
         target_dir: &std::path::Path,
-    ) -> Result<(), TransducerSerializeError> {
-        if chunk_size % 8 != 0 {
-            return Err(TransducerSerializeError::InvalidChunkSize);
-        }
+    ) -> Result<TransducerSerializeReport, TransducerSerializeError> {
+        self.serialize_with_progress(chunk_size, target_dir, |_| {})
+    }
+
+    /// Writes the transducer as a CHFST directory, calling `progress` after
+    /// every chunk so that a caller can show how far along it is.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn serialize_with_progress<F: FnMut(SerializeProgress)>(
+        &self,
+        chunk_size: usize,
+        target_dir: &std::path::Path,
+        mut progress: F,
+    ) -> Result<TransducerSerializeReport, TransducerSerializeError> {
+        check_chunk_size(chunk_size)?;
 
         // Ensure target path exists
         if !target_dir.exists() {
-            eprintln!("Creating directory: {:?}", target_dir);
-            std::fs::create_dir_all(target_dir).expect("create target dir");
+            trace_event!(dir = ?target_dir, "creating directory");
+            std::fs::create_dir_all(target_dir)?;
         }
 
         // Write index table chunks
-        eprintln!(
-            "Writing index table... (Size: {})",
-            self.index_table().len()
-        );
-        let index_table_count = self
-            .index_table()
-            .serialize(chunk_size, target_dir)
-            .unwrap();
+        let index_table_count =
+            self.index_table()
+                .serialize(chunk_size, target_dir, &mut |chunk, chunks| {
+                    progress(SerializeProgress {
+                        stage: SerializeStage::IndexTable,
+                        chunk,
+                        chunks,
+                    })
+                })?;
 
         // Write transition table chunks
-        eprintln!("Writing transition table...");
-        let transition_table_count = self
-            .transition_table()
-            .serialize(chunk_size, target_dir)
-            .unwrap();
+        let transition_table_count =
+            self.transition_table()
+                .serialize(chunk_size, target_dir, &mut |chunk, chunks| {
+                    progress(SerializeProgress {
+                        stage: SerializeStage::TransitionTable,
+                        chunk,
+                        chunks,
+                    })
+                })?;
 
         // Write header + meta index
         let meta = self::chunk::MetaRecord {
             index_table_count,
             transition_table_count,
             chunk_size,
+            // Leave out symbols added by a speller's alphabet translator, and
+            // restore the epsilon name the alphabet parser blanks out.
             raw_alphabet: self
                 .alphabet()
                 .key_table()
                 .iter()
-                .map(|x| x.to_string())
+                .take(self.alphabet().initial_symbol_count() as usize)
+                .enumerate()
+                .map(|(i, x)| {
+                    if i == 0 {
+                        "@_EPSILON_SYMBOL_@".to_string()
+                    } else {
+                        x.to_string()
+                    }
+                })
                 .collect(),
         };
 
-        eprintln!("Writing meta index...");
-        meta.serialize(target_dir);
-
-        Ok(())
+        meta.serialize(target_dir)?;
+        progress(SerializeProgress {
+            stage: SerializeStage::Meta,
+            chunk: 1,
+            chunks: 1,
+        });
+
+        Ok(TransducerSerializeReport {
+            index_table_chunks: index_table_count,
+            transition_table_chunks: transition_table_count,
+        })
     }
 
     #[inline(always)]
@@ -174,6 +471,58 @@ impl HfstTransducer {
     pub fn header(&self) -> &TransducerHeader {
         &self.header
     }
+
+    /// Reads the tables only through bounds checked accessors, and treats
+    /// transitions to states that don't exist as missing, so that a corrupt
+    /// or hostile archive can make lookups wrong but not read outside it.
+    /// Lookups are slower for it. Always on with the `paranoid` feature.
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked || cfg!(feature = "paranoid");
+        self.index_table.set_checked(self.checked);
+        self.transition_table.set_checked(self.checked);
+    }
+
+    pub fn is_checked(&self) -> bool {
+        self.checked
+    }
+
+    /// Whether `target`, as read from a transition, names a state in one of
+    /// the tables.
+    #[inline(always)]
+    fn valid_target(&self, target: TransitionTableIndex) -> bool {
+        if target >= TARGET_TABLE {
+            ((target - TARGET_TABLE) as usize) < self.header.target_table_size()
+        } else {
+            (target as usize) < self.header.index_table_size()
+        }
+    }
+
+    /// The transition at `i`. Checked, `None` if its target or weight is
+    /// out of range, as the search relies on both.
+    #[inline(always)]
+    fn checked_transition(&self, i: TransitionTableIndex) -> Option<SymbolTransition> {
+        let transition = self.transition_table.symbol_transition(i);
+        if !self.checked {
+            return Some(transition);
+        }
+
+        match (transition.target(), transition.weight()) {
+            (Some(target), Some(_)) if self.valid_target(target) => Some(transition),
+            _ => None,
+        }
+    }
+
+    /// Where index row `i` points in the transition table. Checked, `None`
+    /// if that is past its end.
+    #[inline(always)]
+    fn index_target(&self, i: TransitionTableIndex) -> Option<TransitionTableIndex> {
+        let target = self.index_table.target(i)?;
+        if self.checked && !(target >= TARGET_TABLE && self.valid_target(target)) {
+            return None;
+        }
+
+        Some(target - TARGET_TABLE)
+    }
 }
 
 impl Transducer for HfstTransducer {
@@ -209,6 +558,9 @@ impl Transducer for HfstTransducer {
             }
         } else {
             match self.index_table.input_symbol(i + u32::from(sym)) {
+                Some(res) if self.checked => {
+                    sym == res && self.index_target(i + u32::from(sym)).is_some()
+                }
                 Some(res) => sym == res,
                 None => false,
             }
@@ -232,7 +584,7 @@ impl Transducer for HfstTransducer {
     #[inline(always)]
     fn take_epsilons(&self, i: TransitionTableIndex) -> Option<SymbolTransition> {
         if let Some(0) = self.transition_table.input_symbol(i) {
-            Some(self.transition_table.symbol_transition(i))
+            self.checked_transition(i)
         } else {
             None
         }
@@ -244,7 +596,7 @@ impl Transducer for HfstTransducer {
             if sym != 0 && !self.alphabet.is_flag(sym) {
                 None
             } else {
-                Some(self.transition_table.symbol_transition(i))
+                self.checked_transition(i)
             }
         } else {
             None
@@ -261,21 +613,37 @@ impl Transducer for HfstTransducer {
             if input_sym != symbol {
                 None
             } else {
-                Some(self.transition_table.symbol_transition(i))
+                self.checked_transition(i)
             }
         } else {
             None
         }
     }
 
+    #[inline(always)]
+    fn transition(&self, i: TransitionTableIndex) -> SymbolTransition {
+        self.transition_table.symbol_transition(i)
+    }
+
+    #[inline(always)]
+    fn non_epsilon_run(&self, i: TransitionTableIndex, symbol: SymbolNumber) -> u32 {
+        let run = self.transition_table.symbol_run(i, symbol);
+        if !self.checked {
+            return run;
+        }
+
+        // Cut the run short at the first transition the search can't take.
+        (0..run)
+            .take_while(|n| self.checked_transition(i + n).is_some())
+            .count() as u32
+    }
+
     #[inline(always)]
     fn next(&self, i: TransitionTableIndex, symbol: SymbolNumber) -> Option<TransitionTableIndex> {
         if i >= TARGET_TABLE {
             Some(i - TARGET_TABLE + 1)
-        } else if let Some(v) = self.index_table.target(i + 1 + u32::from(symbol)) {
-            Some(v - TARGET_TABLE)
         } else {
-            None
+            self.index_target(i + 1 + u32::from(symbol))
         }
     }
 
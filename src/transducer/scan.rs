@@ -0,0 +1,223 @@
+//! Scans over transition rows and key tables, with SSE2 and NEON fast paths
+//! behind the `simd` feature. The scalar versions are always built and are
+//! what the fast paths fall back to on other targets and for short tails.
+
+use crate::constants::TRANS_TABLE_SIZE;
+use crate::types::SymbolNumber;
+
+/// How many rows at the start of `table` have `symbol` as their input
+/// symbol. `table` is a run of transition table rows, each beginning with
+/// its input symbol as a little endian `u16`.
+#[inline(always)]
+pub fn symbol_run(table: &[u8], symbol: SymbolNumber) -> usize {
+    #[cfg(all(
+        feature = "simd",
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse2"
+    ))]
+    {
+        return unsafe { sse2::symbol_run(table, symbol) };
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+    {
+        return unsafe { neon::symbol_run(table, symbol) };
+    }
+
+    #[allow(unreachable_code)]
+    symbol_run_scalar(table, symbol)
+}
+
+#[inline(always)]
+pub fn symbol_run_scalar(table: &[u8], symbol: SymbolNumber) -> usize {
+    table
+        .chunks_exact(TRANS_TABLE_SIZE)
+        .take_while(|row| u16::from_le_bytes([row[0], row[1]]) == symbol)
+        .count()
+}
+
+/// The index of the first `needle` in `haystack`.
+#[inline(always)]
+pub fn position(haystack: &[u32], needle: u32) -> Option<usize> {
+    #[cfg(all(
+        feature = "simd",
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse2"
+    ))]
+    {
+        return unsafe { sse2::position(haystack, needle) };
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+    {
+        return unsafe { neon::position(haystack, needle) };
+    }
+
+    #[allow(unreachable_code)]
+    position_scalar(haystack, needle)
+}
+
+#[inline(always)]
+pub fn position_scalar(haystack: &[u32], needle: u32) -> Option<usize> {
+    haystack.iter().position(|x| *x == needle)
+}
+
+// Four rows are 48 bytes, read as three 16 byte vectors. Their input
+// symbols sit at bytes 0 and 12 of the first vector, 8 of the second and 4
+// of the third, so all fall on 16 bit lanes: 0 and 6, 4, and 2.
+
+#[cfg(all(
+    feature = "simd",
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+mod sse2 {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::*;
+
+    use super::{position_scalar, symbol_run_scalar, TRANS_TABLE_SIZE};
+
+    const BLOCK: usize = 4 * TRANS_TABLE_SIZE;
+
+    #[inline(always)]
+    pub unsafe fn symbol_run(table: &[u8], symbol: u16) -> usize {
+        let needle = _mm_set1_epi16(symbol as i16);
+        let mut offset = 0;
+
+        while offset + BLOCK <= table.len() {
+            let p = table.as_ptr().add(offset);
+            let a = _mm_loadu_si128(p as *const __m128i);
+            let b = _mm_loadu_si128(p.add(16) as *const __m128i);
+            let c = _mm_loadu_si128(p.add(32) as *const __m128i);
+
+            // Two mask bits per lane; the low one is enough.
+            let a = _mm_movemask_epi8(_mm_cmpeq_epi16(a, needle)) as u32;
+            let b = _mm_movemask_epi8(_mm_cmpeq_epi16(b, needle)) as u32;
+            let c = _mm_movemask_epi8(_mm_cmpeq_epi16(c, needle)) as u32;
+            let hits = (a & 1) | ((a >> 12) & 1) << 1 | ((b >> 8) & 1) << 2 | ((c >> 4) & 1) << 3;
+
+            if hits != 0b1111 {
+                return offset / TRANS_TABLE_SIZE + (!hits).trailing_zeros() as usize;
+            }
+
+            offset += BLOCK;
+        }
+
+        offset / TRANS_TABLE_SIZE + symbol_run_scalar(&table[offset..], symbol)
+    }
+
+    #[inline(always)]
+    pub unsafe fn position(haystack: &[u32], needle: u32) -> Option<usize> {
+        let wanted = _mm_set1_epi32(needle as i32);
+        let mut i = 0;
+
+        while i + 4 <= haystack.len() {
+            let v = _mm_loadu_si128(haystack.as_ptr().add(i) as *const __m128i);
+            let mask = _mm_movemask_ps(_mm_castsi128_ps(_mm_cmpeq_epi32(v, wanted)));
+
+            if mask != 0 {
+                return Some(i + mask.trailing_zeros() as usize);
+            }
+
+            i += 4;
+        }
+
+        position_scalar(&haystack[i..], needle).map(|x| i + x)
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "aarch64"))]
+mod neon {
+    use core::arch::aarch64::*;
+
+    use super::{position_scalar, symbol_run_scalar, TRANS_TABLE_SIZE};
+
+    const BLOCK: usize = 4 * TRANS_TABLE_SIZE;
+
+    #[inline(always)]
+    pub unsafe fn symbol_run(table: &[u8], symbol: u16) -> usize {
+        let needle = vdupq_n_u16(symbol);
+        let mut offset = 0;
+
+        while offset + BLOCK <= table.len() {
+            let p = table.as_ptr().add(offset);
+            let a = vceqq_u16(vreinterpretq_u16_u8(vld1q_u8(p)), needle);
+            let b = vceqq_u16(vreinterpretq_u16_u8(vld1q_u8(p.add(16))), needle);
+            let c = vceqq_u16(vreinterpretq_u16_u8(vld1q_u8(p.add(32))), needle);
+
+            let hits = (vgetq_lane_u16(a, 0) & 1) as u32
+                | ((vgetq_lane_u16(a, 6) & 1) as u32) << 1
+                | ((vgetq_lane_u16(b, 4) & 1) as u32) << 2
+                | ((vgetq_lane_u16(c, 2) & 1) as u32) << 3;
+
+            if hits != 0b1111 {
+                return offset / TRANS_TABLE_SIZE + (!hits).trailing_zeros() as usize;
+            }
+
+            offset += BLOCK;
+        }
+
+        offset / TRANS_TABLE_SIZE + symbol_run_scalar(&table[offset..], symbol)
+    }
+
+    #[inline(always)]
+    pub unsafe fn position(haystack: &[u32], needle: u32) -> Option<usize> {
+        let wanted = vdupq_n_u32(needle);
+        let mut i = 0;
+
+        while i + 4 <= haystack.len() {
+            let v = vceqq_u32(vld1q_u32(haystack.as_ptr().add(i)), wanted);
+
+            if vmaxvq_u32(v) != 0 {
+                return position_scalar(&haystack[i..i + 4], needle).map(|x| i + x);
+            }
+
+            i += 4;
+        }
+
+        position_scalar(&haystack[i..], needle).map(|x| i + x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(symbols: &[u16]) -> Vec<u8> {
+        let mut out = vec![];
+        for (i, symbol) in symbols.iter().enumerate() {
+            out.extend_from_slice(&symbol.to_le_bytes());
+            // Output symbols, targets and weights that look like the input
+            // symbol, to catch reads from the wrong offset.
+            out.extend_from_slice(&symbol.to_le_bytes());
+            out.extend_from_slice(&(*symbol as u32).to_le_bytes());
+            out.extend_from_slice(&(i as u32 | u32::from(*symbol)).to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn matches_scalar() {
+        for len in 0..20 {
+            for split in 0..=len {
+                let mut symbols = vec![7u16; split];
+                symbols.extend(core::iter::repeat_n(8, len - split));
+                let table = rows(&symbols);
+
+                assert_eq!(symbol_run(&table, 7), split);
+                assert_eq!(symbol_run(&table, 7), symbol_run_scalar(&table, 7));
+                assert_eq!(symbol_run(&table, u16::MAX), 0);
+            }
+        }
+
+        let haystack: Vec<u32> = (0..37).map(|x| x * 3).collect();
+        for needle in 0..120 {
+            assert_eq!(
+                position(&haystack, needle),
+                position_scalar(&haystack, needle)
+            );
+        }
+    }
+}
@@ -1,22 +1,23 @@
-#![allow(clippy::cast_ptr_alignment)] // FIXME: This at least needs a comment
-
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use memmap::Mmap;
-use std::fmt;
-use std::io::Cursor;
-use std::ptr;
-use std::sync::Arc;
-use std::{cmp, mem, u16, u32};
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use byteorder::{LittleEndian, WriteBytesExt};
+use core::fmt;
+use core::{cmp, mem, u16, u32};
 
 use crate::constants::TRANS_TABLE_SIZE;
+use crate::transducer::bytes::ByteAccess;
+use crate::transducer::scan;
 use crate::transducer::symbol_transition::SymbolTransition;
+use crate::transducer::TransducerBuf;
 use crate::types::{SymbolNumber, TransitionTableIndex, Weight};
 
 pub struct TransitionTable {
     size: TransitionTableIndex,
-    mmap: Arc<Mmap>,
+    mmap: Arc<TransducerBuf>,
     offset: usize,
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
     len: usize,
+    checked: bool,
 }
 
 impl fmt::Debug for TransitionTable {
@@ -28,24 +29,62 @@ impl fmt::Debug for TransitionTable {
 
 impl TransitionTable {
     #[inline(always)]
-    pub fn new(mmap: Arc<Mmap>, offset: usize, len: usize, size: u32) -> TransitionTable {
+    pub fn new(mmap: Arc<TransducerBuf>, offset: usize, len: usize, size: u32) -> TransitionTable {
+        // As in `IndexTable::new`, clamped to the rows the buffer holds.
+        let rows = mmap.len().saturating_sub(offset) / TRANS_TABLE_SIZE;
+        let size = cmp::min(size as usize, rows) as TransitionTableIndex;
+
         TransitionTable {
             size,
             mmap,
             offset,
             len,
+            checked: cfg!(feature = "paranoid"),
         }
     }
 
-    pub fn serialize(&self, chunk_size: usize, target_dir: &std::path::Path) -> Result<usize, ()> {
-        eprintln!(
-            "size: {}, len: {}, offset: {}",
-            self.size, self.len, self.offset
-        );
+    /// Reads rows with bounds checks, running no unsafe code, for archives
+    /// that may not be what they claim.
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked || cfg!(feature = "paranoid");
+    }
 
-        if chunk_size % 12 != 0 {
-            panic!("Chunk size must be divisible by 12");
+    #[inline(always)]
+    fn read_u16(&self, index: usize) -> Option<u16> {
+        if self.checked {
+            self.mmap.get_u16(index)
+        } else {
+            // Callers only read rows under `size`, which fit in the buffer.
+            Some(unsafe { self.mmap.read_u16(index) })
         }
+    }
+
+    #[inline(always)]
+    fn read_u32(&self, index: usize) -> Option<u32> {
+        if self.checked {
+            self.mmap.get_u32(index)
+        } else {
+            // As for `read_u16`.
+            Some(unsafe { self.mmap.read_u32(index) })
+        }
+    }
+
+    #[cfg(feature = "std")]
+    /// Writes the table in chunks of `chunk_size` bytes, calling `progress`
+    /// with the number of chunks written and the total after each one.
+    /// `chunk_size` must have passed `check_chunk_size`.
+    pub fn serialize(
+        &self,
+        chunk_size: usize,
+        target_dir: &std::path::Path,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> std::io::Result<usize> {
+        trace_event!(
+            size = self.size,
+            len = self.len,
+            offset = self.offset,
+            "serializing transition table"
+        );
 
         // Size is the number of indexes, and that multiplied by TRANS_TABLE_SIZE is the total byte size
         let total_bytes = self.len - self.offset;
@@ -56,21 +95,21 @@ impl TransitionTable {
         // Divide the chunks
         let has_excess = total_bytes % chunk_size != 0;
         let chunk_count = total_bytes / chunk_size + (if has_excess { 1 } else { 0 });
-        eprintln!(
-            "Chunk count: {} max index per iter: {} total bytes: {}",
-            chunk_count, max_index_per_iter, total_bytes
+        trace_event!(
+            chunks = chunk_count,
+            per_chunk = max_index_per_iter,
+            bytes = total_bytes,
+            "dividing transition table"
         );
 
         for i in 1usize..=chunk_count {
-            eprintln!("Writing chunk: {}", i);
-
             let filename = format!("transition-{:02}", i - 1);
-            let mut file = std::fs::File::create(target_dir.join(filename)).unwrap();
+            let mut file = std::fs::File::create(target_dir.join(filename))?;
 
             let begin = (max_index_per_iter * (i - 1usize)) as u32;
             let end = cmp::min(max_index_per_iter * i, self.size as usize) as u32;
 
-            eprintln!("Chunk {}: {}..{}", i, begin, end);
+            trace_event!(chunk = i, begin = begin, end = end, "writing chunk");
 
             for index in begin..end {
                 let input_symbol = self.input_symbol(index).unwrap_or(u16::MAX);
@@ -78,34 +117,21 @@ impl TransitionTable {
                 let target = self.target(index).unwrap_or(u32::MAX);
                 let weight = self.weight(index).unwrap();
 
-                file.write_u16::<LittleEndian>(input_symbol).unwrap();
-                file.write_u16::<LittleEndian>(output_symbol).unwrap();
-                file.write_u32::<LittleEndian>(target).unwrap();
-                file.write_u32::<LittleEndian>(unsafe { std::mem::transmute::<f32, u32>(weight) })
-                    .unwrap();
+                file.write_u16::<LittleEndian>(input_symbol)?;
+                file.write_u16::<LittleEndian>(output_symbol)?;
+                file.write_u32::<LittleEndian>(target)?;
+                file.write_u32::<LittleEndian>(unsafe { std::mem::transmute::<f32, u32>(weight) })?;
             }
-        }
 
-        eprintln!("Done transition serialize.");
+            progress(i, chunk_count);
+        }
 
         Ok(chunk_count as usize)
     }
 
-    #[inline(always)]
-    fn make_cursor(&self) -> Cursor<&[u8]> {
-        Cursor::new(&self.mmap)
-    }
-
     #[inline(always)]
     fn read_symbol_from_cursor(&self, index: usize) -> Option<SymbolNumber> {
-        let index = self.offset + index;
-        let x: SymbolNumber = if cfg!(all(target_arch = "arm", target_pointer_width = "32")) {
-            let mut cursor = self.make_cursor();
-            cursor.set_position(index as u64);
-            cursor.read_u16::<LittleEndian>().unwrap()
-        } else {
-            unsafe { ptr::read(self.mmap.as_ptr().add(index) as *const _) }
-        };
+        let x: SymbolNumber = self.read_u16(self.offset + index)?;
         if x == u16::MAX {
             None
         } else {
@@ -124,6 +150,26 @@ impl TransitionTable {
         sym
     }
 
+    /// How many rows from `i` on have `symbol` as their input symbol.
+    #[inline(always)]
+    pub fn symbol_run(&self, i: TransitionTableIndex, symbol: SymbolNumber) -> u32 {
+        if i >= self.size || symbol == u16::MAX {
+            return 0;
+        }
+
+        let start = self.offset + TRANS_TABLE_SIZE * i as usize;
+        let end = cmp::min(
+            self.offset + TRANS_TABLE_SIZE * self.size as usize,
+            self.mmap.len(),
+        );
+
+        if start >= end {
+            return 0;
+        }
+
+        scan::symbol_run(&self.mmap[start..end], symbol) as u32
+    }
+
     #[inline(always)]
     pub fn output_symbol(&self, i: TransitionTableIndex) -> Option<SymbolNumber> {
         if i >= self.size {
@@ -142,15 +188,7 @@ impl TransitionTable {
 
         let index =
             self.offset + ((TRANS_TABLE_SIZE * i as usize) + (2 * mem::size_of::<SymbolNumber>()));
-
-        let x: TransitionTableIndex = if cfg!(all(target_arch = "arm", target_pointer_width = "32"))
-        {
-            let mut cursor = self.make_cursor();
-            cursor.set_position(index as u64);
-            cursor.read_u32::<LittleEndian>().unwrap()
-        } else {
-            unsafe { ptr::read(self.mmap.as_ptr().add(index) as *const _) }
-        };
+        let x: TransitionTableIndex = self.read_u32(index)?;
         if x == u32::MAX {
             None
         } else {
@@ -168,15 +206,8 @@ impl TransitionTable {
             + ((TRANS_TABLE_SIZE * i as usize)
                 + (2 * mem::size_of::<SymbolNumber>())
                 + mem::size_of::<TransitionTableIndex>());
-
-        let x: Weight = if cfg!(all(target_arch = "arm", target_pointer_width = "32")) {
-            let mut cursor = self.make_cursor();
-            cursor.set_position(index as u64);
-            cursor.read_f32::<LittleEndian>().unwrap()
-        } else {
-            unsafe { ptr::read(self.mmap.as_ptr().add(index) as *const _) }
-        };
-        Some(x)
+        let x = self.read_u32(index)?;
+        Some(Weight::from_bits(x))
     }
 
     #[inline(always)]
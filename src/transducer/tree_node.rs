@@ -1,121 +1,213 @@
-use lifeguard::{Pool, Recycled};
-use std::hash::{Hash, Hasher};
-use std::cmp::Ordering;
+use alloc::vec::Vec;
+use core::hash::{Hash, Hasher};
 
 use super::symbol_transition::SymbolTransition;
 use crate::types::{
-    FlagDiacriticOperation, FlagDiacriticOperator, FlagDiacriticState, SymbolNumber,
-    TransitionTableIndex, Weight,
+    FlagDiacriticOperation, FlagDiacriticOperator, SymbolNumber, TransitionTableIndex, ValueNumber,
+    Weight,
 };
 
-#[derive(Debug, Clone)]
-pub struct TreeNode {
-    pub lexicon_state: TransitionTableIndex,
-    pub mutator_state: TransitionTableIndex,
-    pub input_state: u32,
-    pub weight: f32,
-    pub flag_state: FlagDiacriticState,
-    pub string: Vec<SymbolNumber>,
+/// Marks a node whose output string is empty.
+const EMPTY_STRING: u32 = u32::MAX;
+
+/// Buffers that grew past this many entries during one query are freed
+/// rather than kept for the next, so one pathological word doesn't pin the
+/// memory for good.
+const MAX_RETAINED_ENTRIES: usize = 1 << 20;
+
+/// FNV-1a, finished with a mix so that both halves of the hash are good
+/// enough for the bloom filter's double hashing. Unlike `DefaultHasher`, it
+/// doesn't need `std`.
+struct NodeHasher(u64);
+
+impl NodeHasher {
+    fn new() -> NodeHasher {
+        NodeHasher(0xcbf2_9ce4_8422_2325)
+    }
 }
 
-impl TreeNode {
+impl Hasher for NodeHasher {
     #[inline(always)]
-    pub fn key(&self) -> TreeNode {
-        self.clone()
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
     }
-}
 
-impl std::cmp::PartialEq for TreeNode {
-    fn eq(&self, other: &TreeNode) -> bool {
-        self.lexicon_state == other.lexicon_state
-            && self.mutator_state == other.mutator_state
-            && self.input_state == other.input_state
-            && self.weight == other.weight
-            && self.flag_state == other.flag_state
-            && self.string == other.string
+    fn finish(&self) -> u64 {
+        let mut hash = self.0;
+        hash = (hash ^ (hash >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+        hash = (hash ^ (hash >> 33)).wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        hash ^ (hash >> 33)
     }
 }
 
-impl std::cmp::Ord for TreeNode {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if self.weight < other.weight {
-            return Ordering::Less;
-        } else if self.weight > other.weight {
-            return Ordering::Greater;
-        } else {
-            return self.string.cmp(&other.string)
-        }
-    }
+/// Storage for the output strings and flag diacritic states of every node
+/// in one search, so that expanding a node allocates nothing.
+///
+/// Output strings are kept as a tree: each symbol records the one before
+/// it, so a child node shares its parent's string and adds at most one
+/// entry. Flag states are only copied when a flag diacritic changes them.
+/// `reset` empties the arena for the next query and keeps its capacity.
+#[derive(Debug, Default)]
+pub struct NodeArena {
+    symbols: Vec<(SymbolNumber, u32)>,
+    flags: Vec<ValueNumber>,
+    state_size: usize,
 }
 
-impl std::cmp::PartialOrd for TreeNode {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl NodeArena {
+    pub fn new() -> NodeArena {
+        NodeArena::default()
     }
-}
 
-impl std::cmp::Eq for TreeNode {}
+    /// Empties the arena and adds the all-clear flag state that start nodes
+    /// use.
+    pub fn reset(&mut self, state_size: usize) {
+        if self.symbols.capacity() > MAX_RETAINED_ENTRIES {
+            self.symbols = Vec::new();
+        }
+        if self.flags.capacity() > MAX_RETAINED_ENTRIES {
+            self.flags = Vec::new();
+        }
+        self.symbols.clear();
+        self.flags.clear();
+        self.flags.resize(state_size, 0);
+        self.state_size = state_size;
+    }
 
-impl Hash for TreeNode {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        state.write_u32(self.input_state);
-        state.write_u32(self.mutator_state);
-        state.write_u32(self.lexicon_state);
+    #[inline(always)]
+    fn push_symbol(&mut self, string: u32, symbol: SymbolNumber) -> u32 {
+        self.symbols.push((symbol, string));
+        (self.symbols.len() - 1) as u32
     }
-}
 
-impl lifeguard::Recycleable for TreeNode {
-    fn new() -> Self {
-        TreeNode {
-            string: Vec::with_capacity(1),
-            input_state: 0,
-            mutator_state: 0,
-            lexicon_state: 0,
-            flag_state: vec![],
-            weight: 0.0,
+    #[inline(always)]
+    pub fn flags(&self, node: &TreeNode) -> &[ValueNumber] {
+        let start = node.flag_state as usize;
+        &self.flags[start..start + self.state_size]
+    }
+
+    #[inline(always)]
+    fn set_flag(&mut self, flag_state: u32, feature: SymbolNumber, value: ValueNumber) -> u32 {
+        let start = flag_state as usize;
+        let new_start = self.flags.len();
+        self.flags.reserve(self.state_size);
+        for i in start..start + self.state_size {
+            let v = self.flags[i];
+            self.flags.push(v);
         }
+        self.flags[new_start + feature as usize] = value;
+        new_start as u32
     }
 
-    fn reset(&mut self) {
-        // There is nothing done to reset it.
-        // Implementers must reset any fields where used!
+    /// Bytes allocated for the arena's buffers.
+    pub fn memory_bytes(&self) -> usize {
+        self.symbols.capacity() * core::mem::size_of::<(SymbolNumber, u32)>()
+            + self.flags.capacity() * core::mem::size_of::<ValueNumber>()
     }
-}
 
-impl lifeguard::InitializeWith<&TreeNode> for TreeNode {
-    fn initialize_with(&mut self, source: &TreeNode) {
-        if self.string != source.string {
-            self.string.truncate(0);
-            self.string.extend(&source.string);
+    /// Writes the node's output string into `out`, replacing its contents.
+    pub fn string(&self, node: &TreeNode, out: &mut Vec<SymbolNumber>) {
+        out.clear();
+
+        let mut index = node.string;
+        while index != EMPTY_STRING {
+            let (symbol, prev) = self.symbols[index as usize];
+            out.push(symbol);
+            index = prev;
         }
 
-        self.input_state = source.input_state;
-        self.mutator_state = source.mutator_state;
-        self.lexicon_state = source.lexicon_state;
+        out.reverse();
+    }
 
-        if self.flag_state != source.flag_state {
-            self.flag_state.truncate(0);
-            self.flag_state.extend_from_slice(&source.flag_state.as_slice());
+    /// A hash of everything that decides where the search goes from the
+    /// node and what it outputs: its states, flag state and string. Nodes
+    /// with equal hashes differ, if at all, in weight.
+    pub fn hash(&self, node: &TreeNode) -> u64 {
+        let mut hasher = NodeHasher::new();
+        node.lexicon_state.hash(&mut hasher);
+        node.mutator_state.hash(&mut hasher);
+        node.input_state.hash(&mut hasher);
+        self.flags(node).hash(&mut hasher);
+
+        let mut index = node.string;
+        while index != EMPTY_STRING {
+            let (symbol, prev) = self.symbols[index as usize];
+            symbol.hash(&mut hasher);
+            index = prev;
         }
 
-        self.weight = source.weight;
+        hasher.finish()
+    }
+
+    /// Copies the node's string and flag state out of the arena, so that it
+    /// can outlive the query.
+    pub fn detach(&self, node: &TreeNode) -> DetachedNode {
+        let mut string = vec![];
+        self.string(node, &mut string);
+
+        DetachedNode {
+            node: *node,
+            string,
+            flags: self.flags(node).to_vec(),
+        }
     }
+
+    /// Recreates a detached node in this arena.
+    pub fn attach(&mut self, detached: &DetachedNode) -> TreeNode {
+        let mut string = EMPTY_STRING;
+        for &symbol in &detached.string {
+            string = self.push_symbol(string, symbol);
+        }
+
+        let flag_state = self.flags.len() as u32;
+        self.flags.extend_from_slice(&detached.flags);
+
+        TreeNode {
+            string,
+            flag_state,
+            ..detached.node
+        }
+    }
+}
+
+/// A node that owns its output string and flag state, made by
+/// `NodeArena::detach`.
+#[derive(Debug, Clone)]
+pub struct DetachedNode {
+    node: TreeNode,
+    string: Vec<SymbolNumber>,
+    flags: Vec<ValueNumber>,
+}
+
+/// A state of the search. Its output string and flag state live in the
+/// `NodeArena` it was created with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TreeNode {
+    pub lexicon_state: TransitionTableIndex,
+    pub mutator_state: TransitionTableIndex,
+    pub input_state: u32,
+    pub weight: f32,
+    /// Error model edits made on the way: insertions, deletions and
+    /// substitutions.
+    pub edits: u8,
+    flag_state: u32,
+    string: u32,
 }
 
 impl TreeNode {
     #[inline(always)]
-    pub fn empty<'a>(
-        pool: &'a Pool<TreeNode>,
-        start_state: FlagDiacriticState,
-    ) -> Recycled<'a, TreeNode> {
-        pool.attach(TreeNode {
-            string: vec![],
-            input_state: 0,
-            mutator_state: 0,
+    pub fn empty() -> TreeNode {
+        TreeNode {
             lexicon_state: 0,
-            flag_state: start_state,
+            mutator_state: 0,
+            input_state: 0,
             weight: 0.0,
-        })
+            edits: 0,
+            flag_state: 0,
+            string: EMPTY_STRING,
+        }
     }
 
     #[inline(always)]
@@ -123,195 +215,142 @@ impl TreeNode {
         self.weight
     }
 
+    /// This node with one more edit counted.
     #[inline(always)]
-    pub fn flag_state(&self) -> &FlagDiacriticState {
-        &self.flag_state
+    pub fn edited(&self) -> TreeNode {
+        TreeNode {
+            edits: self.edits.saturating_add(1),
+            ..*self
+        }
     }
 
     #[inline(always)]
-    pub fn update_lexicon<'a>(
-        &self,
-        pool: &'a Pool<TreeNode>,
-        transition: SymbolTransition,
-    ) -> Recycled<'a, TreeNode> {
-        let mut node = pool.new();
-
-        if node.string != self.string {
-            node.string.truncate(0);
-            node.string.extend(&self.string);
-        }
+    pub fn update_lexicon(&self, arena: &mut NodeArena, transition: SymbolTransition) -> TreeNode {
+        let mut string = self.string;
 
         if let Some(value) = transition.symbol() {
             if value != 0 {
-                node.string.push(value);
+                string = arena.push_symbol(string, value);
             }
         }
 
-        node.input_state = self.input_state;
-        node.mutator_state = self.mutator_state;
-        node.lexicon_state = transition.target().unwrap();
-
-        if node.flag_state != self.flag_state {
-            node.flag_state.truncate(0);
-            node.flag_state.extend_from_slice(&self.flag_state.as_slice());
+        TreeNode {
+            lexicon_state: transition.target().unwrap(),
+            weight: self.weight + transition.weight().unwrap(),
+            string,
+            ..*self
         }
-
-        node.weight = self.weight + transition.weight().unwrap();
-
-        node
     }
 
     #[inline(always)]
-    pub fn update_mutator<'a>(
-        &self,
-        pool: &'a Pool<TreeNode>,
-        transition: SymbolTransition,
-    ) -> Recycled<'a, TreeNode> {
-        let mut node = pool.new();
-        if node.string != self.string {
-            node.string.truncate(0);
-            node.string.extend(&self.string);
-        }
-        node.input_state = self.input_state;
-        node.mutator_state = transition.target().unwrap();
-        node.lexicon_state = self.lexicon_state;
-
-        if node.flag_state != self.flag_state {
-            node.flag_state.truncate(0);
-            node.flag_state.extend_from_slice(&self.flag_state.as_slice());
+    pub fn update_mutator(&self, transition: SymbolTransition) -> TreeNode {
+        TreeNode {
+            mutator_state: transition.target().unwrap(),
+            weight: self.weight + transition.weight().unwrap(),
+            ..*self
         }
-
-        node.weight = self.weight + transition.weight().unwrap();
-        node
     }
 
     #[inline(always)]
-    pub fn update<'a>(
+    pub fn update(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         output_symbol: SymbolNumber,
         next_input: Option<u32>,
         next_mutator: TransitionTableIndex,
         next_lexicon: TransitionTableIndex,
         weight: Weight,
-    ) -> Recycled<'a, TreeNode> {
-        let mut node = pool.new();
-
-        if node.string != self.string {
-            node.string.truncate(0);
-            node.string.extend(&self.string);
-        }
-
-        if output_symbol != 0 {
-            node.string.push(output_symbol);
-        }
-
-        node.mutator_state = next_mutator;
-        node.lexicon_state = next_lexicon;
-
-        if node.flag_state != self.flag_state {
-            node.flag_state.truncate(0);
-            node.flag_state.extend_from_slice(&self.flag_state.as_slice());
-        }
-
-        node.weight = self.weight + weight;
-
-        if let Some(input) = next_input {
-            node.input_state = input;
+    ) -> TreeNode {
+        let string = if output_symbol != 0 {
+            arena.push_symbol(self.string, output_symbol)
         } else {
-            node.input_state = self.input_state;
-        }
+            self.string
+        };
 
-        node
+        TreeNode {
+            mutator_state: next_mutator,
+            lexicon_state: next_lexicon,
+            input_state: next_input.unwrap_or(self.input_state),
+            weight: self.weight + weight,
+            string,
+            ..*self
+        }
     }
 
     #[inline(always)]
-    fn update_flag<'a>(
+    fn update_flag(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         feature: SymbolNumber,
-        value: i16,
+        value: ValueNumber,
         transition: &SymbolTransition,
-    ) -> Recycled<'a, TreeNode> {
-        let mut node = self.apply_transition(pool, transition);
-        node.flag_state[feature as usize] = value;
-        node
+    ) -> TreeNode {
+        TreeNode {
+            flag_state: arena.set_flag(self.flag_state, feature, value),
+            ..self.apply_transition(transition)
+        }
     }
 
     #[inline(always)]
-    pub fn apply_transition<'a>(
-        &self,
-        pool: &'a Pool<TreeNode>,
-        transition: &SymbolTransition,
-    ) -> Recycled<'a, TreeNode> {
-        let mut node = pool.new();
-
-        if node.string != self.string {
-            node.string.truncate(0);
-            node.string.extend(&self.string);
-        }
-
-        node.input_state = self.input_state;
-        node.mutator_state = self.mutator_state;
-        node.lexicon_state = transition.target().unwrap();
-
-        if node.flag_state != self.flag_state {
-            node.flag_state.truncate(0);
-            node.flag_state.extend_from_slice(&self.flag_state.as_slice());
+    pub fn apply_transition(&self, transition: &SymbolTransition) -> TreeNode {
+        TreeNode {
+            lexicon_state: transition.target().unwrap(),
+            weight: self.weight + transition.weight().unwrap(),
+            ..*self
         }
-
-        node.weight = self.weight + transition.weight().unwrap();
-        node
     }
 
     #[inline(always)]
-    pub fn apply_operation<'a>(
+    pub fn apply_operation(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         op: &FlagDiacriticOperation,
         transition: &SymbolTransition,
-    ) -> Option<Recycled<'a, TreeNode>> {
+    ) -> Option<TreeNode> {
+        let current = arena.flags(self)[op.feature as usize];
+
         match op.operation {
             FlagDiacriticOperator::PositiveSet => {
-                Some(self.update_flag(pool, op.feature, op.value, transition))
+                Some(self.update_flag(arena, op.feature, op.value, transition))
             }
             FlagDiacriticOperator::NegativeSet => {
-                Some(self.update_flag(pool, op.feature, -1 * op.value, transition))
+                Some(self.update_flag(arena, op.feature, -1 * op.value, transition))
             }
             FlagDiacriticOperator::Require => {
                 let res = if op.value == 0 {
-                    self.flag_state[op.feature as usize] != 0
+                    current != 0
                 } else {
-                    self.flag_state[op.feature as usize] == op.value
+                    current == op.value
                 };
 
                 if res {
-                    Some(self.apply_transition(pool, transition))
+                    Some(self.apply_transition(transition))
                 } else {
                     None
                 }
             }
             FlagDiacriticOperator::Disallow => {
                 let res = if op.value == 0 {
-                    self.flag_state[op.feature as usize] == 0
+                    current == 0
                 } else {
-                    self.flag_state[op.feature as usize] != op.value
+                    current != op.value
                 };
 
                 if res {
-                    Some(self.apply_transition(pool, transition))
+                    Some(self.apply_transition(transition))
                 } else {
                     None
                 }
             }
-            FlagDiacriticOperator::Clear => Some(self.update_flag(pool, op.feature, 0, transition)),
+            FlagDiacriticOperator::Clear => {
+                Some(self.update_flag(arena, op.feature, 0, transition))
+            }
             FlagDiacriticOperator::Unification => {
                 // if the feature is unset OR the feature is to this value already OR
                 // the feature is negatively set to something else than this value
-                let f = self.flag_state[op.feature as usize];
-
-                if f == 0 || f == op.value || (f < 0 && f * -1 != op.value) {
-                    Some(self.update_flag(pool, op.feature, op.value, transition))
+                if current == 0 || current == op.value || (current < 0 && current * -1 != op.value)
+                {
+                    Some(self.update_flag(arena, op.feature, op.value, transition))
                 } else {
                     None
                 }
@@ -319,3 +358,40 @@ impl TreeNode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_strings() {
+        let mut arena = NodeArena::new();
+        arena.reset(2);
+
+        let root = TreeNode::empty();
+        let a = root.update(&mut arena, 1, None, 0, 0, 1.0);
+        let ab = a.update(&mut arena, 2, Some(1), 0, 0, 1.0);
+        let ac = a.update(&mut arena, 3, Some(1), 0, 0, 0.5);
+        let ac_eps = ac.update(&mut arena, 0, Some(2), 0, 0, 0.0);
+
+        let mut out = vec![];
+        arena.string(&ab, &mut out);
+        assert_eq!(out, vec![1, 2]);
+        arena.string(&ac_eps, &mut out);
+        assert_eq!(out, vec![1, 3]);
+        arena.string(&root, &mut out);
+        assert!(out.is_empty());
+        assert_eq!(ac_eps.weight(), 1.5);
+
+        let flagged = TreeNode {
+            flag_state: arena.set_flag(root.flag_state, 1, 5),
+            ..root
+        };
+        assert_eq!(arena.flags(&root), &[0, 0]);
+        assert_eq!(arena.flags(&flagged), &[0, 5]);
+
+        arena.reset(2);
+        arena.string(&TreeNode::empty(), &mut out);
+        assert!(out.is_empty());
+    }
+}
@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum FlagDiacriticOperator {
     PositiveSet,
@@ -0,0 +1,92 @@
+//! JavaScript bindings for WebAssembly, built with the `wasm` feature:
+//!
+//! ```sh
+//! wasm-pack build --target web -- --features wasm
+//! ```
+//!
+//! ```js
+//! const bytes = new Uint8Array(await (await fetch("se.zhfst")).arrayBuffer());
+//! const speller = Speller.fromBytes(bytes);
+//! speller.isCorrect("giella");        // true
+//! speller.suggest("gielaa", 5);       // [{ value: "giella", weight: 10.2 }, ...]
+//! speller.checkText("Mun lean gielaa."); // [{ word: "gielaa", start: 9, end: 15 }]
+//! ```
+//!
+//! Offsets from `checkText` count UTF-16 code units, like JavaScript string
+//! indexes, so they can be passed straight to `String.prototype.slice`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::archive::SpellerArchive;
+use crate::speller::SpellerConfig;
+use crate::tokenizer::Tokenize;
+
+#[derive(Serialize)]
+struct Misspelling<'a> {
+    word: &'a str,
+    start: usize,
+    end: usize,
+}
+
+fn js_error<E: std::fmt::Display>(e: E) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
+
+#[wasm_bindgen(js_name = Speller)]
+pub struct WasmSpeller {
+    archive: SpellerArchive,
+}
+
+#[wasm_bindgen(js_class = Speller)]
+impl WasmSpeller {
+    /// Loads a ZHFST archive from the bytes of a `Uint8Array`.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmSpeller, JsValue> {
+        let archive = SpellerArchive::from_bytes(bytes).map_err(js_error)?;
+        Ok(WasmSpeller { archive })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn locale(&self) -> String {
+        self.archive.metadata().info.locale.clone()
+    }
+
+    #[wasm_bindgen(js_name = isCorrect)]
+    pub fn is_correct(&self, word: &str) -> bool {
+        self.archive.speller().is_correct(word)
+    }
+
+    /// Returns `{ value, weight }` objects, best first.
+    pub fn suggest(&self, word: &str, n_best: Option<u32>) -> Result<JsValue, JsValue> {
+        let cfg = SpellerConfig {
+            n_best: n_best.map(|n| n as usize),
+            ..SpellerConfig::default()
+        };
+
+        let suggestions = self.archive.speller().suggest_with_config(word, &cfg);
+        JsValue::from_serde(&suggestions).map_err(js_error)
+    }
+
+    /// Returns a `{ word, start, end }` object for every misspelled word.
+    #[wasm_bindgen(js_name = checkText)]
+    pub fn check_text(&self, text: &str) -> Result<JsValue, JsValue> {
+        let speller = self.archive.speller();
+        let mut offset = 0;
+        let mut misspellings = vec![];
+
+        for (_, token) in text.word_bound_indices() {
+            let start = offset;
+            offset += token.encode_utf16().count();
+
+            if token.chars().any(char::is_alphabetic) && !speller.clone().is_correct(token) {
+                misspellings.push(Misspelling {
+                    word: token,
+                    start,
+                    end: offset,
+                });
+            }
+        }
+
+        JsValue::from_serde(&misspellings).map_err(js_error)
+    }
+}